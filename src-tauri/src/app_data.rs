@@ -0,0 +1,460 @@
+//! The boat data of the active dataset parsed once and shared by the commands, so the frontend
+//! doesn't need to read the file again or send the whole dataset with each query.
+//!
+//! The data is read from the storage in use when it is first needed, and is replaced or dropped
+//! whenever the app writes the data or the file watcher sees it changed. Edits made with
+//! [`AppData::update`] are only made to the cached data, which is written to storage later by
+//! the [`autosave`](crate::autosave). The current path is already kept in
+//! [`CurrentPath`](crate::path::CurrentPath), so only the boat data is cached here.
+//!
+//! # Lock Ordering
+//!
+//! The locks are taken in the order: the write lock of the autosave, the lock of the cache,
+//! then the lock of the [`DataIndex`], which is rebuilt while the cache is locked. Files are
+//! read without holding the lock of the cache, so commands reading the data while it is written
+//! get either the old or the new data without waiting for the file. Data read from storage is
+//! discarded if the data changed while it was being read.
+
+use std::sync::{Arc, RwLock};
+
+use tauri::{AppHandle, Manager};
+
+use crate::{
+    autosave::AutosaveState,
+    data::{BoatData, BoatDataFeature},
+    datasets::active_data_file,
+    errors::AppError,
+    index::DataIndex,
+    journal,
+    storage::sqlite,
+};
+
+/// The cached data and its generation.
+#[derive(Debug, Default)]
+struct Cache {
+    /// The boat data, `None` until it is read or after it is invalidated.
+    data: Option<Arc<BoatData>>,
+    /// Increased each time the data changes, to discard reads of older data.
+    generation: u64,
+    /// Whether the data has edits that aren't written to storage.
+    dirty: bool,
+    /// Whether the stored data is backed up since it was read.
+    backed_up: bool,
+}
+
+/// The boat data of the active dataset.
+#[derive(Debug, Default)]
+pub struct AppData {
+    cache: RwLock<Cache>,
+}
+
+impl AppData {
+    /// Gets the boat data of the active dataset, reading it from storage if it isn't cached.
+    pub fn get(&self, app_handle: &AppHandle) -> Result<Arc<BoatData>, AppError> {
+        self.get_or_read(
+            || {
+                let data_file = active_data_file(app_handle)?;
+                log::debug!("Caching Boat Data: {}", data_file.display());
+                if sqlite::enabled(app_handle) {
+                    sqlite::read_stored(&data_file)
+                } else {
+                    journal::read_with_journal(&data_file)
+                }
+            },
+            |data| app_handle.state::<DataIndex>().rebuild(data),
+        )
+    }
+
+    /// Gets the cached boat data, reading it with `read` if it isn't cached.
+    ///
+    /// `cached` is called with the data read while the lock of the cache is held, before
+    /// other commands can get the data.
+    fn get_or_read(
+        &self,
+        read: impl FnOnce() -> Result<BoatData, AppError>,
+        cached: impl FnOnce(&BoatData),
+    ) -> Result<Arc<BoatData>, AppError> {
+        let generation = {
+            let cache = self.cache.read().unwrap();
+            if let Some(data) = &cache.data {
+                return Ok(data.clone());
+            }
+            cache.generation
+        };
+
+        let data = Arc::new(read()?);
+
+        let mut cache = self.cache.write().unwrap();
+        if cache.generation != generation {
+            log::debug!("Discarding Boat Data Changed while Reading");
+            return Ok(cache.data.clone().unwrap_or(data));
+        }
+        cached(&data);
+        cache.data = Some(data.clone());
+        cache.dirty = false;
+        cache.backed_up = false;
+        Ok(data)
+    }
+
+    /// Edits the cached data, marking it to be written to storage by the autosave.
+    ///
+    /// The data isn't marked if `edit` returns an error.
+    pub fn update<T>(
+        &self,
+        app_handle: &AppHandle,
+        edit: impl FnOnce(&mut BoatData) -> Result<T, AppError>,
+    ) -> Result<T, AppError> {
+        let read = self.get(app_handle)?;
+        let value = self.edit(read, edit, |data| {
+            app_handle.state::<DataIndex>().rebuild(data)
+        })?;
+        app_handle.state::<AutosaveState>().changed(app_handle);
+        Ok(value)
+    }
+
+    /// Edits the cached data like [`AppData::update`], or `read` if the data was invalidated
+    /// after it was read.
+    ///
+    /// `edited` is called with the edited data while the lock of the cache is held.
+    fn edit<T>(
+        &self,
+        read: Arc<BoatData>,
+        edit: impl FnOnce(&mut BoatData) -> Result<T, AppError>,
+        edited: impl FnOnce(&BoatData),
+    ) -> Result<T, AppError> {
+        let mut guard = self.cache.write().unwrap();
+        let cache = &mut *guard;
+        let data = Arc::make_mut(cache.data.get_or_insert(read));
+        let value = edit(data)?;
+        edited(data);
+        cache.dirty = true;
+        cache.generation += 1;
+        Ok(value)
+    }
+
+    /// Gets the generation of the data, which increases each time the data changes.
+    pub fn generation(&self) -> u64 {
+        self.cache.read().unwrap().generation
+    }
+
+    /// Takes the edited data to write it to storage, `None` if there are no edits.
+    ///
+    /// Returns whether the stored data needs to be backed up first, which is only the first time
+    /// after it is read.
+    pub fn take_dirty(&self) -> Option<(Arc<BoatData>, bool)> {
+        let mut cache = self.cache.write().unwrap();
+        if !cache.dirty {
+            return None;
+        }
+        let data = cache.data.clone()?;
+        let backup = !cache.backed_up;
+        cache.dirty = false;
+        cache.backed_up = true;
+        Some((data, backup))
+    }
+
+    /// Marks the data to be written again, e.g. after writing it failed.
+    pub fn mark_dirty(&self) {
+        self.cache.write().unwrap().dirty = true;
+    }
+
+    /// Replaces the cached data after the data is written to storage.
+    pub fn replace(&self, data: BoatData) {
+        let mut cache = self.cache.write().unwrap();
+        cache.data = Some(Arc::new(data));
+        cache.generation += 1;
+        cache.dirty = false;
+    }
+
+    /// Adds data points appended to storage to the cached data.
+    ///
+    /// The data points are already stored, so the data isn't marked to be written.
+    pub fn append(&self, features: &[BoatDataFeature]) {
+        let mut cache = self.cache.write().unwrap();
+        if let Some(data) = &mut cache.data {
+            Arc::make_mut(data).extend(features.iter().cloned());
+        }
+        cache.generation += 1;
+    }
+
+    /// Drops the cached data, e.g. when the file is changed by another program, so it is read
+    /// again when it is next needed.
+    ///
+    /// Edits that aren't written to storage are lost.
+    pub fn invalidate(&self) {
+        let mut cache = self.cache.write().unwrap();
+        if cache.dirty {
+            log::warn!("Discarding Unsaved Edits of the Boat Data");
+        }
+        cache.data = None;
+        cache.generation += 1;
+        cache.dirty = false;
+        cache.backed_up = false;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{sync::mpsc, thread};
+
+    use chrono::{TimeZone, Utc};
+    use geo_types::Point;
+
+    use super::*;
+    use crate::data::Layer;
+
+    fn feature(seconds: i64) -> BoatDataFeature {
+        BoatDataFeature::new(
+            28.5,
+            0.5,
+            Layer::Surface,
+            Utc.timestamp_opt(1_715_495_400 + seconds, 0).unwrap(),
+            Point::new(101.5, 3.1),
+        )
+    }
+
+    fn data(seconds: &[i64]) -> BoatData {
+        BoatData::new(
+            BoatData::default().version().to_string(),
+            seconds.iter().map(|s| feature(*s)).collect(),
+        )
+    }
+
+    /// Gets the cached data, failing the test if it would be read.
+    fn cached(app_data: &AppData) -> Arc<BoatData> {
+        app_data
+            .get_or_read(|| panic!("The data is read again"), |_| {})
+            .unwrap()
+    }
+
+    #[test]
+    fn data_is_read_once() {
+        let app_data = AppData::default();
+        let mut stored = vec![];
+
+        let data = app_data
+            .get_or_read(|| Ok(data(&[0])), |data| stored.push(data.clone()))
+            .unwrap();
+        assert_eq!(*data, self::data(&[0]));
+        assert_eq!(stored, [self::data(&[0])]);
+        assert!(Arc::ptr_eq(&cached(&app_data), &data));
+    }
+
+    #[test]
+    fn errors_are_not_cached() {
+        let app_data = AppData::default();
+        let error = AppError::from(String::from("Unreadable"));
+
+        assert_eq!(
+            app_data.get_or_read(|| Err(error.clone()), |_| {}),
+            Err(error)
+        );
+        assert_eq!(
+            *app_data.get_or_read(|| Ok(data(&[0])), |_| {}).unwrap(),
+            data(&[0])
+        );
+    }
+
+    #[test]
+    fn writes_update_the_cached_data() {
+        let app_data = AppData::default();
+        app_data.replace(data(&[0]));
+        assert_eq!(*cached(&app_data), data(&[0]));
+
+        app_data.append(&[feature(60), feature(120)]);
+        assert_eq!(*cached(&app_data), data(&[0, 60, 120]));
+
+        app_data.invalidate();
+        let read = app_data.get_or_read(|| Ok(data(&[30])), |_| {}).unwrap();
+        assert_eq!(*read, data(&[30]));
+    }
+
+    #[test]
+    fn appending_to_uncached_data_reads_it_again() {
+        let app_data = AppData::default();
+        app_data.append(&[feature(60)]);
+
+        let read = app_data.get_or_read(|| Ok(data(&[0, 60])), |_| {}).unwrap();
+        assert_eq!(*read, data(&[0, 60]));
+    }
+
+    #[test]
+    fn data_changed_while_reading_is_discarded() {
+        let app_data = AppData::default();
+        let mut stored = false;
+
+        let read = app_data
+            .get_or_read(
+                || {
+                    app_data.replace(data(&[60]));
+                    Ok(data(&[0]))
+                },
+                |_| stored = true,
+            )
+            .unwrap();
+        assert_eq!(*read, data(&[60]));
+        assert!(!stored);
+        assert_eq!(*cached(&app_data), data(&[60]));
+    }
+
+    #[test]
+    fn cache_is_not_locked_while_reading() {
+        let app_data = Arc::new(AppData::default());
+        let (reading, started) = mpsc::channel();
+        let (finish, finished) = mpsc::channel::<()>();
+
+        let reader = {
+            let app_data = app_data.clone();
+            thread::spawn(move || {
+                app_data
+                    .get_or_read(
+                        || {
+                            reading.send(()).unwrap();
+                            finished.recv().unwrap();
+                            Ok(data(&[0]))
+                        },
+                        |_| {},
+                    )
+                    .unwrap()
+            })
+        };
+
+        // Writes and reads of the cache don't wait for the file being read
+        started.recv().unwrap();
+        app_data.replace(data(&[60]));
+        assert_eq!(*cached(&app_data), data(&[60]));
+        finish.send(()).unwrap();
+        assert_eq!(*reader.join().unwrap(), data(&[60]));
+    }
+
+    #[test]
+    fn concurrent_reads_and_writes_do_not_deadlock() {
+        let app_data = Arc::new(AppData::default());
+        app_data.replace(data(&[0]));
+
+        let threads: Vec<_> = (0..8)
+            .map(|i| {
+                let app_data = app_data.clone();
+                thread::spawn(move || {
+                    for j in 0..200 {
+                        match (i + j) % 4 {
+                            0 => app_data.append(&[feature(j)]),
+                            1 => app_data.invalidate(),
+                            _ => {
+                                let data = app_data.get_or_read(|| Ok(data(&[0])), |_| {});
+                                assert!(!data.unwrap().features().is_empty());
+                            }
+                        }
+                    }
+                })
+            })
+            .collect();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+    }
+
+    /// Edits the cached data like `update`, adding a data point.
+    fn add_point(app_data: &AppData) -> Result<usize, AppError> {
+        let read = app_data.get_or_read(|| Ok(data(&[0])), |_| {})?;
+        app_data.edit(
+            read,
+            |data| {
+                data.push(feature(60));
+                Ok(data.features().len())
+            },
+            |_| {},
+        )
+    }
+
+    #[test]
+    fn edits_mark_the_data_dirty() {
+        let app_data = AppData::default();
+        assert!(app_data.take_dirty().is_none());
+
+        assert_eq!(add_point(&app_data), Ok(2));
+        let (data, backup) = app_data.take_dirty().unwrap();
+        assert_eq!(data.features().len(), 2);
+        assert!(backup);
+        assert!(app_data.take_dirty().is_none());
+    }
+
+    #[test]
+    fn only_the_first_autosave_after_reading_backs_up() {
+        let app_data = AppData::default();
+        add_point(&app_data).unwrap();
+        assert!(app_data.take_dirty().unwrap().1);
+
+        add_point(&app_data).unwrap();
+        assert!(!app_data.take_dirty().unwrap().1);
+        app_data.mark_dirty();
+        assert!(!app_data.take_dirty().unwrap().1);
+
+        app_data.invalidate();
+        add_point(&app_data).unwrap();
+        assert!(app_data.take_dirty().unwrap().1);
+    }
+
+    #[test]
+    fn failed_edits_are_not_marked() {
+        let app_data = AppData::default();
+        let read = app_data.get_or_read(|| Ok(data(&[0])), |_| {}).unwrap();
+        let error = AppError::from(String::from("Unable to edit"));
+
+        let result: Result<(), _> = app_data.edit(read, |_| Err(error.clone()), |_| {});
+        assert_eq!(result, Err(error));
+        assert!(app_data.take_dirty().is_none());
+    }
+
+    #[test]
+    fn writes_and_invalidation_drop_the_edits() {
+        let app_data = AppData::default();
+        add_point(&app_data).unwrap();
+        app_data.replace(data(&[0]));
+        assert!(app_data.take_dirty().is_none());
+
+        add_point(&app_data).unwrap();
+        app_data.invalidate();
+        assert!(app_data.take_dirty().is_none());
+    }
+
+    #[test]
+    fn edits_of_invalidated_data_use_the_data_read() {
+        let app_data = AppData::default();
+        let read = app_data.get_or_read(|| Ok(data(&[0, 60])), |_| {}).unwrap();
+        app_data.invalidate();
+
+        let mut edited = None;
+        app_data
+            .edit(read, |_| Ok(()), |data| edited = Some(data.clone()))
+            .unwrap();
+        assert_eq!(edited, Some(data(&[0, 60])));
+        assert_eq!(*cached(&app_data), data(&[0, 60]));
+    }
+
+    /// Reads the data into the cache, leaving it as it is if it is already cached.
+    fn cached_or_read(app_data: &AppData) {
+        app_data.get_or_read(|| Ok(data(&[0])), |_| {}).unwrap();
+    }
+
+    #[test]
+    fn every_change_increases_the_generation() {
+        let app_data = AppData::default();
+        let mut generations = vec![app_data.generation()];
+
+        cached_or_read(&app_data);
+        generations.push(app_data.generation());
+        app_data.append(&[feature(60)]);
+        generations.push(app_data.generation());
+        add_point(&app_data).unwrap();
+        generations.push(app_data.generation());
+        app_data.replace(data(&[0]));
+        generations.push(app_data.generation());
+
+        assert_eq!(generations[0], generations[1]);
+        assert!(
+            generations[1..].windows(2).all(|w| w[0] < w[1]),
+            "{generations:?}"
+        );
+    }
+}
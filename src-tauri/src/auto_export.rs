@@ -0,0 +1,386 @@
+//! Exporting the boat data to a folder after each mission, e.g. a folder synced to the cloud, so
+//! nobody has to remember to export it after an outing.
+//!
+//! The export runs when the progress reports of the boat show the mission is complete, or when
+//! [`run_auto_export`] is invoked. Exports that fail in the background are kept until they are
+//! dismissed, so the operator is told even if no window was listening.
+
+use std::{
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager};
+
+use crate::{
+    app_data::AppData,
+    autosave, data,
+    datasets::active_dataset,
+    errors::{codes, AppError},
+    settings::SettingsState,
+};
+
+/// The most exports with the same name, to stop looking for a free name at some point.
+const MAX_COUNTER: u32 = 10_000;
+
+/// The formats the boat data is exported in.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    /// CSV in the units of the settings.
+    Csv,
+    /// Boat data GeoJSON.
+    Geojson,
+}
+
+impl ExportFormat {
+    /// Gets the extension of the exported files.
+    fn extension(self) -> &'static str {
+        match self {
+            Self::Csv => "csv",
+            Self::Geojson => "geojson",
+        }
+    }
+}
+
+/// Where and how the boat data is exported after each mission.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct AutoExportSettings {
+    /// The folder the exports are written to.
+    pub directory: PathBuf,
+    /// The format of the exports.
+    pub format: ExportFormat,
+    /// The name of the exports, where `{date}` is replaced with the date of the export,
+    /// `{dataset}` with the name of the dataset and `{site}` with the site in its metadata.
+    ///
+    /// The extension of the format is added if the name doesn't end with it.
+    pub filename_template: String,
+}
+
+impl AutoExportSettings {
+    /// Checks the settings are usable.
+    pub fn validate(&self) -> Result<(), String> {
+        if self.directory.as_os_str().is_empty() {
+            return Err(String::from("The directory is empty"));
+        }
+        expand_template(&self.filename_template, "date", "dataset", "site").map(|_| ())
+    }
+}
+
+/// Replaces the characters that can't be in file names, so a placeholder can't add folders.
+fn sanitize(value: &str) -> String {
+    value
+        .trim()
+        .chars()
+        .map(|c| match c {
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+            c if c.is_control() => '_',
+            c => c,
+        })
+        .collect()
+}
+
+/// Expands the placeholders of a file name template.
+///
+/// Returns an error if the template has an unknown placeholder, a brace that isn't part of a
+/// placeholder, or expands to an empty name.
+pub fn expand_template(
+    template: &str,
+    date: &str,
+    dataset: &str,
+    site: &str,
+) -> Result<String, String> {
+    let mut name = String::new();
+    let mut rest = template;
+    while let Some(start) = rest.find(['{', '}']) {
+        name.push_str(&rest[..start]);
+        if rest[start..].starts_with('}') {
+            return Err(format!(
+                "Invalid File Name Template: Unmatched '}}' in {template}"
+            ));
+        }
+        let end = rest[start..]
+            .find('}')
+            .ok_or_else(|| format!("Invalid File Name Template: Unmatched '{{' in {template}"))?;
+        let placeholder = &rest[start + 1..start + end];
+        let value = match placeholder {
+            "date" => date,
+            "dataset" => dataset,
+            "site" => site,
+            _ => {
+                return Err(format!(
+                    "Invalid File Name Template: Unknown Placeholder {{{placeholder}}}"
+                ))
+            }
+        };
+        name.push_str(&sanitize(value));
+        rest = &rest[start + end + 1..];
+    }
+    name.push_str(rest);
+
+    let name = sanitize(&name);
+    if name.is_empty() || name.chars().all(|c| c == '.') {
+        return Err(format!(
+            "Invalid File Name Template: {template} gives an empty name"
+        ));
+    }
+    Ok(name)
+}
+
+/// Gets a path in `directory` named `name` that doesn't exist yet, adding a counter to the name
+/// if it does, e.g. `survey-2.csv`.
+fn free_path(directory: &Path, name: &str, extension: &str) -> Result<PathBuf, AppError> {
+    let stem = name.strip_suffix(&format!(".{extension}")).unwrap_or(name);
+    let path = directory.join(format!("{stem}.{extension}"));
+    if !path.exists() {
+        return Ok(path);
+    }
+    (2..MAX_COUNTER)
+        .map(|i| directory.join(format!("{stem}-{i}.{extension}")))
+        .find(|path| !path.exists())
+        .ok_or_else(|| {
+            AppError::new(
+                codes::AUTO_EXPORT_TOO_MANY,
+                format!("Unable to Auto Export: Too many exports named {stem}"),
+            )
+            .with("name", stem)
+        })
+}
+
+/// An auto export that failed in the background.
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct AutoExportFailure {
+    /// The reason the export failed.
+    error: AppError,
+    /// The time of the export.
+    time: DateTime<Utc>,
+}
+
+/// The auto exports that failed in the background and haven't been dismissed.
+#[derive(Debug, Default)]
+pub struct AutoExportState {
+    failures: Mutex<Vec<AutoExportFailure>>,
+}
+
+/// Exports the boat data of the active dataset as the auto export settings say, returning the
+/// path of the export.
+fn export(app_handle: &AppHandle) -> Result<PathBuf, AppError> {
+    let settings = app_handle.state::<SettingsState>().get();
+    let auto_export = settings.auto_export.clone().ok_or_else(|| {
+        AppError::new(
+            codes::AUTO_EXPORT_NOT_SET_UP,
+            "Unable to Auto Export: Auto export is not set up",
+        )
+    })?;
+
+    autosave::flush(app_handle)?;
+    let data = app_handle.state::<AppData>().get(app_handle)?;
+    let date = Utc::now()
+        .with_timezone(&settings.timezone())
+        .format("%Y-%m-%d")
+        .to_string();
+    let site = data.metadata().site_name.unwrap_or_default();
+    let name = expand_template(
+        &auto_export.filename_template,
+        &date,
+        &active_dataset(app_handle),
+        &site,
+    )
+    .map_err(|e| {
+        AppError::reason(
+            codes::AUTO_EXPORT_INVALID_TEMPLATE,
+            "Unable to Auto Export",
+            e,
+        )
+    })?;
+
+    std::fs::create_dir_all(&auto_export.directory)
+        .map_err(|e| AppError::io(e, &auto_export.directory))?;
+    let path = free_path(
+        &auto_export.directory,
+        &name,
+        auto_export.format.extension(),
+    )?;
+    log::info!("Auto Exporting Boat Data: {}", path.display());
+    match auto_export.format {
+        ExportFormat::Csv => {
+            data::export_data_csv(
+                path.clone(),
+                (*data).clone(),
+                Some(settings.units),
+                None,
+                None,
+            )?;
+        }
+        ExportFormat::Geojson => data::write_data(&path, &data)?,
+    }
+    Ok(path)
+}
+
+/// Exports the boat data in the background after a mission is complete, if auto export is set
+/// up.
+///
+/// Emits `auto-export-finished` with the path of the export, or keeps the failure and emits
+/// `auto-export-failed`.
+pub fn export_in_background(app_handle: &AppHandle) {
+    if app_handle
+        .state::<SettingsState>()
+        .get()
+        .auto_export
+        .is_none()
+    {
+        return;
+    }
+    let app_handle = app_handle.clone();
+    std::thread::spawn(move || match export(&app_handle) {
+        Ok(path) => {
+            if let Err(e) = app_handle.emit_all("auto-export-finished", path) {
+                log::warn!("Unable to emit auto-export-finished event: {}", e);
+            }
+        }
+        Err(error) => {
+            log::error!("Unable to Auto Export: {}", error);
+            let failure = AutoExportFailure {
+                error,
+                time: Utc::now(),
+            };
+            app_handle
+                .state::<AutoExportState>()
+                .failures
+                .lock()
+                .unwrap()
+                .push(failure.clone());
+            if let Err(e) = app_handle.emit_all("auto-export-failed", failure) {
+                log::warn!("Unable to emit auto-export-failed event: {}", e);
+            }
+        }
+    });
+}
+
+/// Export the boat data of the active dataset now as the auto export settings say, returning
+/// the path of the export.
+#[tauri::command]
+pub async fn run_auto_export(app_handle: AppHandle) -> Result<PathBuf, AppError> {
+    log::debug!("Running Auto Export");
+    tauri::async_runtime::spawn_blocking(move || export(&app_handle))
+        .await
+        .map_err(|e| AppError::from(e.to_string()))?
+}
+
+/// Get the auto exports that failed in the background and haven't been dismissed.
+#[tauri::command]
+pub fn auto_export_failures(state: tauri::State<AutoExportState>) -> Vec<AutoExportFailure> {
+    state.failures.lock().unwrap().clone()
+}
+
+/// Dismiss the auto exports that failed in the background.
+#[tauri::command]
+pub fn dismiss_auto_export_failures(state: tauri::State<AutoExportState>) {
+    log::debug!("Dismissing Auto Export Failures");
+    state.failures.lock().unwrap().clear();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::tests::temp_dir;
+
+    fn expand(template: &str) -> Result<String, String> {
+        expand_template(template, "2024-05-12", "Lake Survey", "Tasik/Chini")
+    }
+
+    #[test]
+    fn placeholders_are_expanded() {
+        assert_eq!(
+            expand("{date}_{dataset}").unwrap(),
+            "2024-05-12_Lake Survey"
+        );
+        assert_eq!(
+            expand("{site}-{site}.csv").unwrap(),
+            "Tasik_Chini-Tasik_Chini.csv"
+        );
+        assert_eq!(expand("survey").unwrap(), "survey");
+    }
+
+    #[test]
+    fn invalid_templates_are_rejected() {
+        for (template, reason) in [
+            ("{time}", "Unknown Placeholder {time}"),
+            ("{}", "Unknown Placeholder {}"),
+            ("{DATE}", "Unknown Placeholder {DATE}"),
+            ("{date", "Unmatched '{'"),
+            ("date}", "Unmatched '}'"),
+            ("", "gives an empty name"),
+            ("..", "gives an empty name"),
+        ] {
+            let error = expand(template).unwrap_err();
+
+            assert!(error.contains(reason), "{template}: {error}");
+        }
+    }
+
+    #[test]
+    fn empty_values_can_give_an_empty_name() {
+        let error = expand_template("{site}", "2024-05-12", "survey", " ").unwrap_err();
+
+        assert!(error.contains("empty name"), "{error}");
+    }
+
+    #[test]
+    fn templates_are_checked_with_the_settings() {
+        let mut settings = AutoExportSettings {
+            directory: PathBuf::from("exports"),
+            format: ExportFormat::Csv,
+            filename_template: String::from("{date}-{dataset}"),
+        };
+        assert!(settings.validate().is_ok());
+
+        settings.filename_template = String::from("{mission}");
+        assert!(settings.validate().is_err());
+
+        settings.filename_template = String::from("{date}");
+        settings.directory = PathBuf::new();
+        assert!(settings.validate().is_err());
+    }
+
+    #[test]
+    fn collisions_add_a_counter() {
+        let directory = temp_dir("auto_export_collisions");
+
+        let first = free_path(&directory, "survey", "csv").unwrap();
+        assert_eq!(first, directory.join("survey.csv"));
+        std::fs::write(&first, "").unwrap();
+
+        let second = free_path(&directory, "survey.csv", "csv").unwrap();
+        assert_eq!(second, directory.join("survey-2.csv"));
+        std::fs::write(&second, "").unwrap();
+
+        assert_eq!(
+            free_path(&directory, "survey", "csv").unwrap(),
+            directory.join("survey-3.csv")
+        );
+        assert_eq!(
+            free_path(&directory, "survey", "geojson").unwrap(),
+            directory.join("survey.geojson")
+        );
+
+        std::fs::remove_dir_all(directory).unwrap();
+    }
+
+    #[test]
+    fn the_counter_fills_gaps() {
+        let directory = temp_dir("auto_export_gaps");
+        for name in ["survey.csv", "survey-3.csv"] {
+            std::fs::write(directory.join(name), "").unwrap();
+        }
+
+        assert_eq!(
+            free_path(&directory, "survey", "csv").unwrap(),
+            directory.join("survey-2.csv")
+        );
+
+        std::fs::remove_dir_all(directory).unwrap();
+    }
+}
@@ -0,0 +1,208 @@
+//! Saving of the edits to the cached boat data in the background, so the frontend doesn't need
+//! to save the data after each edit.
+//!
+//! Edits mark the cached data dirty, and it is written to storage once no edit is made for the
+//! autosave interval in the settings, when [`flush_now`] is invoked or before the app exits.
+//! Only the first autosave after the data is read backs up the stored data, so the backups
+//! aren't replaced by versions a few seconds apart. Data points appended while collecting are
+//! already written to the journal or the database, so they don't mark the data dirty.
+
+use std::{
+    sync::{mpsc, Mutex, MutexGuard},
+    time::Duration,
+};
+
+use tauri::{AppHandle, Manager};
+
+use crate::{app_data::AppData, data, errors::AppError, settings::SettingsState};
+
+/// The longest time exiting waits for the edits to be written.
+const EXIT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The autosave task running in the background.
+#[derive(Debug, Default)]
+pub struct AutosaveState {
+    /// Notifies the task of edits, `None` until the first edit.
+    sender: Mutex<Option<mpsc::Sender<()>>>,
+    /// Held while writing the boat data, so writes and appends to the journal don't overlap.
+    writing: Mutex<()>,
+}
+
+impl AutosaveState {
+    /// Records an edit of the cached data, starting the autosave task if it isn't running.
+    pub fn changed(&self, app_handle: &AppHandle) {
+        let mut sender = self.sender.lock().unwrap();
+        let sender = sender.get_or_insert_with(|| start(app_handle));
+        if sender.send(()).is_err() {
+            log::warn!("Autosave Stopped, the edits are saved on exit");
+        }
+    }
+
+    /// Stops the boat data from being written until the guard is dropped, e.g. while appending
+    /// to the journal so the appended data points aren't cleared with it.
+    pub fn lock_writes(&self) -> MutexGuard<'_, ()> {
+        self.writing.lock().unwrap()
+    }
+}
+
+/// Starts the autosave task, returning the sender notifying it of edits.
+fn start(app_handle: &AppHandle) -> mpsc::Sender<()> {
+    log::info!("Starting Autosave");
+    let (sender, receiver) = mpsc::channel();
+    let app_handle = app_handle.clone();
+    std::thread::spawn(move || run(app_handle, receiver));
+    sender
+}
+
+/// Writes the edits once they stop for the autosave interval.
+fn run(app_handle: AppHandle, receiver: mpsc::Receiver<()>) {
+    while receiver.recv().is_ok() {
+        let seconds = app_handle.state::<SettingsState>().get().autosave_seconds;
+        wait_for_quiet(&receiver, Duration::from_secs(seconds));
+        if let Err(e) = flush(&app_handle) {
+            log::warn!("Unable to Autosave the Boat Data: {}", e);
+        }
+    }
+}
+
+/// Waits until no edit is made for `interval`.
+fn wait_for_quiet(receiver: &mpsc::Receiver<()>, interval: Duration) {
+    while receiver.recv_timeout(interval).is_ok() {}
+}
+
+/// Writes the edits of the cached boat data to storage, if there are any.
+pub fn flush(app_handle: &AppHandle) -> Result<(), AppError> {
+    let _writing = app_handle.state::<AutosaveState>().inner().lock_writes();
+    flush_locked(app_handle)
+}
+
+/// Writes the edits of the cached boat data to storage while the write lock is already held by
+/// the caller.
+pub fn flush_locked(app_handle: &AppHandle) -> Result<(), AppError> {
+    let app_data = app_handle.state::<AppData>();
+    let (data, backup) = match app_data.take_dirty() {
+        Some(v) => v,
+        None => return Ok(()),
+    };
+
+    log::info!("Autosaving {} Data Points", data.features().len());
+    let result = data::write_stored(app_handle, &data, backup);
+    if result.is_err() {
+        app_data.mark_dirty();
+    }
+    result
+}
+
+/// Writes the edits before the app exits, waiting up to [`EXIT_TIMEOUT`].
+pub fn flush_before_exit(app_handle: &AppHandle) {
+    let (sender, receiver) = mpsc::channel();
+    let app_handle = app_handle.clone();
+    std::thread::spawn(move || {
+        let _ = sender.send(flush(&app_handle));
+    });
+    match receiver.recv_timeout(EXIT_TIMEOUT) {
+        Ok(Ok(())) => {}
+        Ok(Err(e)) => log::warn!("Unable to Save the Boat Data before Exiting: {}", e),
+        Err(_) => log::warn!("Timed out Saving the Boat Data before Exiting"),
+    }
+}
+
+/// Write the edits of the boat data to storage now instead of waiting for the autosave.
+#[tauri::command]
+pub fn flush_now(app_handle: AppHandle) -> Result<(), AppError> {
+    log::debug!("Flushing Boat Data");
+    flush(&app_handle)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{str::FromStr, sync::Arc, thread, time::Instant};
+
+    use chrono::{TimeZone, Utc};
+    use geo_types::Point;
+
+    use super::*;
+    use crate::{
+        data::{BoatData, BoatDataFeature, Layer},
+        storage::{tests::temp_dir, write_atomically},
+    };
+
+    /// Creates boat data with `count` data points, so each write has different content.
+    fn boat_data(count: usize) -> BoatData {
+        let features = (0..count)
+            .map(|i| {
+                BoatDataFeature::new(
+                    25.0 + i as f64 / 10.0,
+                    0.5,
+                    Layer::Surface,
+                    Utc.timestamp_opt(1_715_500_000 + i as i64, 0).unwrap(),
+                    Point::new(101.5, 3.1 + i as f64 * 1e-5),
+                )
+            })
+            .collect();
+        BoatData::new(BoatData::default().version().to_string(), features)
+    }
+
+    #[test]
+    fn waits_until_edits_stop() {
+        let (sender, receiver) = mpsc::channel();
+        let start = Instant::now();
+        let editing = thread::spawn(move || {
+            for _ in 0..10 {
+                sender.send(()).unwrap();
+                thread::sleep(Duration::from_millis(20));
+            }
+            sender
+        });
+
+        wait_for_quiet(&receiver, Duration::from_millis(100));
+        assert!(start.elapsed() >= Duration::from_millis(280));
+        drop(editing.join().unwrap());
+    }
+
+    #[test]
+    fn stops_waiting_once_the_edits_stop_being_sent() {
+        let (sender, receiver) = mpsc::channel::<()>();
+        drop(sender);
+
+        let start = Instant::now();
+        wait_for_quiet(&receiver, Duration::from_secs(60));
+        assert!(start.elapsed() < Duration::from_secs(60));
+    }
+
+    #[test]
+    fn concurrent_saves_leave_the_last_write() {
+        let dir = temp_dir("concurrent-saves");
+        let path = Arc::new(dir.join("default.geojson"));
+        let state = Arc::new(AutosaveState::default());
+        // The content of the last write, recorded while the write lock is held
+        let last = Arc::new(Mutex::new(String::new()));
+
+        let threads: Vec<_> = (0..16)
+            .map(|thread| {
+                let (path, state, last) = (path.clone(), state.clone(), last.clone());
+                thread::spawn(move || {
+                    for save in 0..8 {
+                        let content = boat_data(thread * 8 + save).to_string();
+                        let _writing = state.lock_writes();
+                        write_atomically(&path, |file| file.write_all(content.as_bytes())).unwrap();
+                        *last.lock().unwrap() = content;
+                    }
+                })
+            })
+            .collect();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+
+        let stored = std::fs::read_to_string(&*path).unwrap();
+        let last = last.lock().unwrap();
+        assert_eq!(stored, *last);
+        assert_eq!(
+            BoatData::from_str(&stored).unwrap(),
+            BoatData::from_str(&last).unwrap()
+        );
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 1);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
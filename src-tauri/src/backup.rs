@@ -0,0 +1,384 @@
+//! Timestamped backups of the boat data in application storage.
+
+use std::{
+    fs,
+    io::ErrorKind,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use chrono::{DateTime, NaiveDateTime, Utc};
+use serde::Serialize;
+use serde_json::json;
+use tauri::AppHandle;
+
+use crate::{
+    data::BoatData,
+    errors::{codes, AppError},
+    operations,
+    storage::app_data_file,
+};
+
+/// The number of backups kept when it is not specified.
+pub const DEFAULT_MAX_BACKUPS: usize = 10;
+
+/// The format of the timestamp in backup file names.
+const TIMESTAMP_FORMAT: &str = "%Y-%m-%dT%H-%M-%S";
+
+/// Information on a backup of the boat data.
+#[derive(Debug, Serialize)]
+pub struct BackupInfo {
+    /// The file name of the backup.
+    name: String,
+    /// The time the backup is created.
+    time: DateTime<Utc>,
+    /// The number of features in the backup.
+    features: usize,
+}
+
+/// Gets the directory the backups are stored in.
+pub fn backup_dir(app_handle: &AppHandle) -> Result<PathBuf, AppError> {
+    app_data_file(app_handle, "backups")
+}
+
+/// Parses the creation time from a backup file name.
+///
+/// Returns `None` if `name` is not the name of a backup.
+fn backup_time(name: &str) -> Option<DateTime<Utc>> {
+    let timestamp = name.strip_prefix("data-")?.strip_suffix(".geojson")?;
+    NaiveDateTime::parse_from_str(timestamp, TIMESTAMP_FORMAT)
+        .ok()
+        .map(|time| time.and_utc())
+}
+
+/// Gets the names of the backups in `dir` sorted from oldest to newest.
+fn backup_names(dir: &Path) -> Result<Vec<String>, AppError> {
+    let entries = match fs::read_dir(dir) {
+        Ok(v) => v,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(vec![]),
+        Err(e) => return Err(AppError::io(e, dir)),
+    };
+
+    let mut names: Vec<String> = entries
+        .filter_map(|entry| entry.ok()?.file_name().into_string().ok())
+        .filter(|name| backup_time(name).is_some())
+        .collect();
+    // The timestamp format sorts chronologically
+    names.sort();
+    Ok(names)
+}
+
+/// Copies `data_file` into `dir` and removes the backups beyond `max_backups`.
+///
+/// Nothing is copied if `data_file` does not exist.
+pub fn backup(data_file: &Path, dir: &Path, max_backups: usize) -> Result<(), AppError> {
+    if max_backups == 0 || !data_file.exists() {
+        log::debug!("Skipping Backup of: {}", data_file.display());
+        return Ok(());
+    }
+
+    fs::create_dir_all(dir).map_err(|e| AppError::io(e, dir))?;
+    let name = format!("data-{}.geojson", Utc::now().format(TIMESTAMP_FORMAT));
+    log::info!("Backing up Data to: {}", name);
+    let backup = dir.join(name);
+    fs::copy(data_file, &backup).map_err(|e| AppError::io(e, &backup))?;
+
+    prune(dir, max_backups)
+}
+
+/// Writes `data` into `dir` as a backup and removes the backups beyond `max_backups`.
+///
+/// Used for boat data that isn't stored in a GeoJSON file, so its backups can be listed and
+/// restored like the others.
+pub fn backup_data(data: &BoatData, dir: &Path, max_backups: usize) -> Result<(), AppError> {
+    if max_backups == 0 {
+        log::debug!("Skipping Backup of Data");
+        return Ok(());
+    }
+
+    fs::create_dir_all(dir).map_err(|e| AppError::io(e, dir))?;
+    let name = format!("data-{}.geojson", Utc::now().format(TIMESTAMP_FORMAT));
+    log::info!("Backing up Data to: {}", name);
+    let backup = dir.join(name);
+    fs::write(&backup, data.to_string()).map_err(|e| AppError::io(e, &backup))?;
+
+    prune(dir, max_backups)
+}
+
+/// Removes the oldest backups in `dir` so at most `max_backups` remain.
+pub fn prune(dir: &Path, max_backups: usize) -> Result<(), AppError> {
+    let names = backup_names(dir)?;
+    let excess = names.len().saturating_sub(max_backups);
+    for name in &names[..excess] {
+        log::info!("Removing Old Backup: {}", name);
+        let backup = dir.join(name);
+        fs::remove_file(&backup).map_err(|e| AppError::io(e, &backup))?;
+    }
+    Ok(())
+}
+
+/// Reads a backup from `dir`.
+fn read_backup(dir: &Path, name: &str) -> Result<BoatData, AppError> {
+    // Only names we generate are accepted so the path can't escape the directory
+    if backup_time(name).is_none() {
+        return Err(AppError::new(
+            codes::BACKUP_INVALID_NAME,
+            format!("Invalid Backup Name: {name}"),
+        )
+        .with("name", name));
+    }
+    let path = dir.join(name);
+    let content = fs::read_to_string(&path).map_err(|e| AppError::io(e, &path))?;
+    BoatData::from_str(&content)
+}
+
+/// Lists the backups in `dir` from newest to oldest, skipping the ones that can't be read.
+fn list(dir: &Path) -> Result<Vec<BackupInfo>, AppError> {
+    let mut backups = vec![];
+    for name in backup_names(dir)?.into_iter().rev() {
+        let features = match read_backup(dir, &name) {
+            Ok(v) => v.features().len(),
+            Err(e) => {
+                log::warn!("Unable to Read Backup: {}, Reason: {}", name, e);
+                continue;
+            }
+        };
+        backups.push(BackupInfo {
+            // We can safely unwrap as we only list valid backup names
+            time: backup_time(&name).unwrap(),
+            name,
+            features,
+        });
+    }
+    Ok(backups)
+}
+
+/// List the backups of the boat data from newest to oldest.
+#[tauri::command]
+pub fn list_backups(app_handle: AppHandle) -> Result<Vec<BackupInfo>, AppError> {
+    log::debug!("Listing Backups");
+    list(&backup_dir(&app_handle)?)
+}
+
+/// Restore a backup as the boat data in application storage.
+///
+/// The current data is backed up before it is replaced.
+#[tauri::command]
+pub fn restore_backup(app_handle: AppHandle, name: String) -> Result<BoatData, AppError> {
+    operations::record(
+        &app_handle,
+        "restore_backup",
+        json!({ "name": name }),
+        || {
+            log::info!("Restoring Backup: {}", name);
+            let data = read_backup(&backup_dir(&app_handle)?, &name)?;
+            crate::data::save_data(app_handle.clone(), data.clone(), None, None)?;
+            Ok(data)
+        },
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+    use geo_types::Point;
+
+    use super::*;
+    use crate::{
+        data::{BoatDataFeature, Layer},
+        storage::tests::temp_dir,
+    };
+
+    /// Creates boat data with `count` data points, with the ids given to the data points when
+    /// they are read.
+    fn boat_data(count: usize) -> BoatData {
+        let features = (0..count)
+            .map(|i| {
+                BoatDataFeature::new(
+                    28.5,
+                    0.5,
+                    Layer::Surface,
+                    Utc.timestamp_opt(1_715_495_400 + i as i64, 0).unwrap(),
+                    Point::new(101.5, 3.1),
+                )
+            })
+            .collect();
+        let mut data = BoatData::new(BoatData::default().version().to_string(), features);
+        data.assign_ids();
+        data
+    }
+
+    /// Gets the name of the backup created `seconds` after the first one.
+    fn backup_name(seconds: u32) -> String {
+        format!(
+            "data-2024-05-12T10-{:02}-{:02}.geojson",
+            seconds / 60,
+            seconds % 60
+        )
+    }
+
+    /// Writes a backup with `count` data points created `seconds` after the first one.
+    fn write_backup(dir: &Path, seconds: u32, count: usize) -> String {
+        let name = backup_name(seconds);
+        fs::write(dir.join(&name), boat_data(count).to_string()).unwrap();
+        name
+    }
+
+    #[test]
+    fn backup_time_reads_generated_names() {
+        assert_eq!(
+            backup_time("data-2024-05-12T10-33-02.geojson"),
+            Some(Utc.with_ymd_and_hms(2024, 5, 12, 10, 33, 2).unwrap())
+        );
+        for name in [
+            "data-2024-05-12.geojson",
+            "data-2024-05-12T10-33-02.json",
+            "path-2024-05-12T10-33-02.geojson",
+            "../data-2024-05-12T10-33-02.geojson",
+        ] {
+            assert_eq!(backup_time(name), None, "{name}");
+        }
+    }
+
+    #[test]
+    fn backup_copies_data_to_timestamped_file() {
+        let dir = temp_dir("backup-copy");
+        let data_file = dir.join("data.geojson");
+        let backups = dir.join("backups");
+        let data = boat_data(3).to_string();
+        fs::write(&data_file, &data).unwrap();
+
+        let before = Utc::now().timestamp();
+        backup(&data_file, &backups, DEFAULT_MAX_BACKUPS).unwrap();
+        let after = Utc::now().timestamp();
+
+        let names = backup_names(&backups).unwrap();
+        assert_eq!(names.len(), 1);
+        let time = backup_time(&names[0]).unwrap().timestamp();
+        assert!((before..=after).contains(&time), "{}", names[0]);
+        assert_eq!(fs::read_to_string(backups.join(&names[0])).unwrap(), data);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn backup_skips_missing_data_and_disabled_backups() {
+        let dir = temp_dir("backup-skip");
+        let backups = dir.join("backups");
+        backup(&dir.join("data.geojson"), &backups, DEFAULT_MAX_BACKUPS).unwrap();
+        assert!(!backups.exists());
+
+        let data_file = dir.join("data.geojson");
+        fs::write(&data_file, boat_data(1).to_string()).unwrap();
+        backup(&data_file, &backups, 0).unwrap();
+        assert!(!backups.exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn prune_keeps_most_recent_backups() {
+        let dir = temp_dir("backup-prune");
+        // Written out of order so the file system order doesn't match the time order
+        for seconds in [7, 2, 12, 0, 5, 9, 1, 11, 3, 8, 6, 10, 4] {
+            write_backup(&dir, seconds, 1);
+        }
+        fs::write(dir.join("notes.txt"), "not a backup").unwrap();
+
+        prune(&dir, 10).unwrap();
+
+        let expected: Vec<_> = (3..13).map(backup_name).collect();
+        assert_eq!(backup_names(&dir).unwrap(), expected);
+        assert!(dir.join("notes.txt").exists());
+
+        prune(&dir, 10).unwrap();
+        assert_eq!(backup_names(&dir).unwrap(), expected);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn backup_removes_oldest_beyond_limit() {
+        let dir = temp_dir("backup-rotate");
+        let data_file = dir.join("data.geojson");
+        let backups = dir.join("backups");
+        fs::create_dir_all(&backups).unwrap();
+        for seconds in 0..3 {
+            write_backup(&backups, seconds, 1);
+        }
+        fs::write(&data_file, boat_data(2).to_string()).unwrap();
+
+        backup(&data_file, &backups, 3).unwrap();
+
+        let names = backup_names(&backups).unwrap();
+        assert_eq!(names.len(), 3);
+        assert!(!names.contains(&backup_name(0)));
+        assert_eq!(names[..2], [backup_name(1), backup_name(2)]);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn list_returns_newest_first_with_feature_counts() {
+        let dir = temp_dir("backup-list");
+        assert!(list(&dir.join("backups")).unwrap().is_empty());
+
+        write_backup(&dir, 0, 1);
+        write_backup(&dir, 20, 3);
+        write_backup(&dir, 10, 2);
+        fs::write(dir.join(backup_name(30)), "{ not json").unwrap();
+        fs::write(dir.join("data.geojson"), boat_data(4).to_string()).unwrap();
+
+        let backups = list(&dir).unwrap();
+        let listed: Vec<_> = backups
+            .iter()
+            .map(|b| (b.name.clone(), b.features))
+            .collect();
+        assert_eq!(
+            listed,
+            vec![
+                (backup_name(20), 3),
+                (backup_name(10), 2),
+                (backup_name(0), 1)
+            ]
+        );
+        assert_eq!(backups[0].time, backup_time(&backup_name(20)).unwrap());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn read_backup_round_trips_and_rejects_other_names() {
+        let dir = temp_dir("backup-read");
+        let data_file = dir.join("data.geojson");
+        let data = boat_data(5);
+        fs::write(&data_file, data.to_string()).unwrap();
+        backup(&data_file, &dir, DEFAULT_MAX_BACKUPS).unwrap();
+
+        let name = backup_names(&dir).unwrap().remove(0);
+        let restored = read_backup(&dir, &name).unwrap();
+        assert_eq!(restored.to_string(), data.to_string());
+
+        for name in ["data.geojson", "../data.geojson", "data-../../x.geojson"] {
+            assert!(read_backup(&dir, name).is_err(), "{name}");
+        }
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn data_is_written_as_a_readable_backup() {
+        let dir = temp_dir("backup_data");
+        let data = boat_data(3);
+
+        backup_data(&data, &dir, 5).unwrap();
+
+        let backups = list(&dir).unwrap();
+        assert_eq!(backups.len(), 1);
+        assert_eq!(read_backup(&dir, &backups[0].name).unwrap(), data);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn data_is_not_backed_up_without_backups() {
+        let dir = temp_dir("backup_data_none").join("backups");
+
+        backup_data(&boat_data(3), &dir, 0).unwrap();
+
+        assert!(!dir.exists());
+    }
+}
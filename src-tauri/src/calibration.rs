@@ -0,0 +1,305 @@
+//! Correction of the temperatures measured by each thermistor with the calibration found by
+//! testing it in the lab, so the readings don't need to be corrected in a spreadsheet.
+//!
+//! The calibrations are stored in the settings by the id of the sensor sent with each reading.
+//! Corrected readings keep the temperature measured by the sensor in `temperature_raw`, so the
+//! correction can be checked and redone with a new calibration.
+
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tauri::{AppHandle, Manager};
+
+use crate::{
+    app_data::AppData,
+    autosave::AutosaveState,
+    data::{BoatData, BoatDataFeature},
+    datasets::{active_dataset, dataset_file, read_dataset},
+    errors::{codes, AppError},
+    operations,
+    settings::{self, SettingsState},
+    storage::write_atomically,
+    watcher,
+};
+
+/// The linear correction of a temperature sensor: `corrected = raw * scale + offset`.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub struct Calibration {
+    /// The degrees Celsius added after scaling.
+    pub offset: f64,
+    /// The factor the measured temperature is multiplied by.
+    pub scale: f64,
+}
+
+impl Calibration {
+    /// Checks the calibration can be applied and undone.
+    pub fn validate(&self) -> Result<(), String> {
+        if !self.offset.is_finite() {
+            return Err(format!("Offset {}", self.offset));
+        }
+        if !self.scale.is_finite() || self.scale == 0.0 {
+            return Err(format!("Scale {}", self.scale));
+        }
+        Ok(())
+    }
+
+    /// Corrects a temperature measured by the sensor.
+    pub fn apply(&self, raw: f64) -> f64 {
+        raw * self.scale + self.offset
+    }
+
+    /// Gets the temperature measured by the sensor from a temperature corrected with this
+    /// calibration.
+    fn undo(&self, corrected: f64) -> f64 {
+        (corrected - self.offset) / self.scale
+    }
+}
+
+/// Creates the error of a calibration that can't be applied.
+fn invalid_calibration(reason: String) -> AppError {
+    AppError::reason(codes::CALIBRATION_INVALID, "Invalid Calibration", reason)
+}
+
+/// Corrects the temperature of a data point with a calibration.
+///
+/// The temperature measured is `temperature_raw` if the data point has it, otherwise the
+/// temperature is taken to be corrected with `previous`, or not corrected if it is `None`.
+pub fn recalibrate(
+    feature: &mut BoatDataFeature,
+    previous: Option<&Calibration>,
+    calibration: &Calibration,
+) {
+    let raw = feature.temperature_raw().unwrap_or_else(|| {
+        previous.map_or(feature.temperature(), |p| p.undo(feature.temperature()))
+    });
+    feature.set_temperature(calibration.apply(raw), Some(raw));
+}
+
+/// Recalibrates the data points of a sensor in boat data, returning the number recalibrated.
+fn recalibrate_data(
+    data: &mut BoatData,
+    sensor_id: &str,
+    old: &Calibration,
+    new: &Calibration,
+) -> usize {
+    let mut count = 0;
+    for feature in data.features_mut() {
+        if feature.sensor_id() == Some(sensor_id) {
+            recalibrate(feature, Some(old), new);
+            count += 1;
+        }
+    }
+    count
+}
+
+/// Set the calibration of a temperature sensor, applied to the readings it sends from now on.
+///
+/// Returns the calibrations of every sensor.
+#[tauri::command]
+pub fn set_calibration(
+    app_handle: AppHandle,
+    sensor_id: String,
+    offset: f64,
+    scale: f64,
+) -> Result<BTreeMap<String, Calibration>, AppError> {
+    operations::record(
+        &app_handle,
+        "set_calibration",
+        json!({ "sensor_id": sensor_id, "offset": offset, "scale": scale }),
+        || {
+            log::info!(
+                "Setting Calibration of Sensor {}: Offset {}, Scale {}",
+                sensor_id,
+                offset,
+                scale
+            );
+            if sensor_id.trim().is_empty() {
+                return Err(invalid_calibration(String::from("The sensor id is empty")));
+            }
+            let calibration = Calibration { offset, scale };
+            calibration.validate().map_err(invalid_calibration)?;
+
+            let mut calibrations = app_handle.state::<SettingsState>().get().calibrations;
+            calibrations.insert(sensor_id, calibration);
+            let patch = json!({ "calibrations": calibrations });
+            Ok(settings::update(&app_handle, patch)?.calibrations)
+        },
+    )
+}
+
+/// Correct the temperatures of a sensor already stored in a dataset with a new calibration,
+/// returning the number of data points corrected.
+///
+/// The temperatures are corrected from `temperature_raw` when the data points have it. Data
+/// points without it are taken to be corrected with `old_cal`.
+#[tauri::command]
+pub fn recalibrate_dataset(
+    app_handle: AppHandle,
+    dataset: String,
+    sensor_id: String,
+    old_cal: Calibration,
+    new_cal: Calibration,
+) -> Result<usize, AppError> {
+    operations::record(
+        &app_handle,
+        "recalibrate_dataset",
+        json!({
+            "dataset": dataset,
+            "sensor_id": sensor_id,
+            "old_cal": old_cal,
+            "new_cal": new_cal,
+        }),
+        || {
+            log::info!("Recalibrating Sensor {} in Dataset: {}", sensor_id, dataset);
+            old_cal.validate().map_err(invalid_calibration)?;
+            new_cal.validate().map_err(invalid_calibration)?;
+
+            let count = if active_dataset(&app_handle) == dataset {
+                // The corrections are written by the autosave like the other edits
+                app_handle.state::<AppData>().update(&app_handle, |data| {
+                    Ok(recalibrate_data(data, &sensor_id, &old_cal, &new_cal))
+                })?
+            } else {
+                let file = dataset_file(&app_handle, &dataset)?;
+                let _writing = app_handle.state::<AutosaveState>().inner().lock_writes();
+                let mut data = read_dataset(&file, &dataset)?;
+                let count = recalibrate_data(&mut data, &sensor_id, &old_cal, &new_cal);
+                write_atomically(&file, |file| write!(file, "{}", data))?;
+                watcher::note_write(&app_handle, &file);
+                count
+            };
+            log::info!("Recalibrated {} Readings", count);
+            Ok(count)
+        },
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeZone, Utc};
+    use geo_types::Point;
+
+    use super::*;
+    use crate::data::Layer;
+
+    const CALIBRATION: Calibration = Calibration {
+        offset: 0.5,
+        scale: 1.1,
+    };
+
+    fn feature(temperature: f64, sensor_id: &str) -> BoatDataFeature {
+        BoatDataFeature::new(
+            temperature,
+            0.5,
+            Layer::Surface,
+            Utc.timestamp_opt(1_715_495_400, 0).unwrap(),
+            Point::new(101.5, 3.1),
+        )
+        .with_sensor_id(Some(String::from(sensor_id)))
+    }
+
+    fn assert_near(actual: f64, expected: f64) {
+        assert!((actual - expected).abs() < 1e-9, "{actual} != {expected}");
+    }
+
+    #[test]
+    fn calibrations_are_undone() {
+        assert_near(CALIBRATION.apply(20.0), 22.5);
+        assert_near(CALIBRATION.undo(22.5), 20.0);
+    }
+
+    #[test]
+    fn calibrations_must_be_invertible() {
+        let invalid = [
+            Calibration {
+                offset: f64::NAN,
+                scale: 1.0,
+            },
+            Calibration {
+                offset: 0.0,
+                scale: 0.0,
+            },
+            Calibration {
+                offset: 0.0,
+                scale: f64::INFINITY,
+            },
+        ];
+
+        assert!(CALIBRATION.validate().is_ok());
+        for calibration in invalid {
+            let error = invalid_calibration(calibration.validate().unwrap_err());
+
+            assert_eq!(
+                serde_json::to_value(error).unwrap()["code"],
+                codes::CALIBRATION_INVALID
+            );
+        }
+    }
+
+    #[test]
+    fn calibrating_keeps_the_measured_temperature() {
+        let mut feature = feature(20.0, "T1");
+
+        recalibrate(&mut feature, None, &CALIBRATION);
+
+        assert_near(feature.temperature(), 22.5);
+        assert_eq!(feature.temperature_raw(), Some(20.0));
+    }
+
+    #[test]
+    fn recalibrating_starts_from_the_measured_temperature() {
+        let mut feature = feature(22.5, "T1").with_temperature_raw(Some(20.0));
+        let new = Calibration {
+            offset: -1.0,
+            scale: 1.0,
+        };
+
+        // The old calibration isn't needed when the measured temperature is known
+        recalibrate(&mut feature, Some(&new), &new);
+
+        assert_near(feature.temperature(), 19.0);
+        assert_eq!(feature.temperature_raw(), Some(20.0));
+    }
+
+    #[test]
+    fn recalibrating_undoes_the_old_calibration() {
+        let mut feature = feature(22.5, "T1");
+
+        recalibrate(
+            &mut feature,
+            Some(&CALIBRATION),
+            &Calibration {
+                offset: 0.0,
+                scale: 1.0,
+            },
+        );
+
+        assert_near(feature.temperature(), 20.0);
+        assert_near(feature.temperature_raw().unwrap(), 20.0);
+    }
+
+    #[test]
+    fn only_the_readings_of_the_sensor_are_recalibrated() {
+        let mut data = BoatData::new(
+            BoatData::default().version().to_string(),
+            vec![
+                feature(20.0, "T1"),
+                feature(20.0, "T2"),
+                feature(21.0, "T1"),
+            ],
+        );
+        let old = Calibration {
+            offset: 0.0,
+            scale: 1.0,
+        };
+
+        assert_eq!(recalibrate_data(&mut data, "T1", &old, &CALIBRATION), 2);
+
+        let temperatures: Vec<_> = data.features().iter().map(|f| f.temperature()).collect();
+        assert_near(temperatures[0], 22.5);
+        assert_near(temperatures[1], 20.0);
+        assert_near(temperatures[2], 23.6);
+        assert_eq!(data.features()[1].temperature_raw(), None);
+    }
+}
@@ -0,0 +1,188 @@
+//! Copying data points to the system clipboard, e.g. to paste them into a spreadsheet.
+
+use chrono_tz::Tz;
+use serde::Deserialize;
+use tauri::{AppHandle, ClipboardManager, Manager};
+
+use crate::{
+    data::{BoatData, BoatDataFeature},
+    errors::{codes, AppError},
+    migrations,
+    settings::SettingsState,
+    units::UnitSystem,
+};
+
+/// The format of data points copied to the clipboard.
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ClipboardFormat {
+    /// Tab-separated values, so spreadsheets paste the values into columns.
+    Csv,
+    /// A GeoJSON feature collection.
+    Geojson,
+}
+
+/// The format of times in tab-separated values, which spreadsheets recognize as dates.
+const TIME_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+/// Formats data points as tab-separated values with a header, with the times in `timezone` and
+/// the temperatures and depths in `units`.
+fn to_tsv(features: &[BoatDataFeature], timezone: Tz, units: UnitSystem) -> Result<String, String> {
+    let mut writer = csv::WriterBuilder::new()
+        .delimiter(b'\t')
+        .from_writer(vec![]);
+    writer
+        .write_record([
+            units.temperature_column(),
+            units.depth_column(),
+            "layer",
+            "time",
+            "lat",
+            "lng",
+        ])
+        .map_err(|e| e.to_string())?;
+    for feature in features {
+        writer
+            .write_record([
+                units.temperature(feature.temperature()).to_string(),
+                units.depth(feature.depth()).to_string(),
+                feature.layer().to_string(),
+                feature
+                    .time()
+                    .with_timezone(&timezone)
+                    .format(TIME_FORMAT)
+                    .to_string(),
+                feature.geometry().y().to_string(),
+                feature.geometry().x().to_string(),
+            ])
+            .map_err(|e| e.to_string())?;
+    }
+    let content = writer.into_inner().map_err(|e| e.to_string())?;
+    String::from_utf8(content).map_err(|e| e.to_string())
+}
+
+/// Copy data points to the system clipboard.
+///
+/// In `csv` format the data points are copied as tab-separated values with the times in the
+/// configured time zone. In `geojson` format they are copied as a feature collection with the
+/// times in RFC 3339 UTC. Returns the number of data points copied, leaving the clipboard
+/// unchanged if there are none.
+#[tauri::command]
+pub fn copy_features_to_clipboard(
+    app_handle: AppHandle,
+    features: Vec<BoatDataFeature>,
+    format: ClipboardFormat,
+) -> Result<usize, AppError> {
+    log::debug!(
+        "Copying {} Data Points to Clipboard as {:?}",
+        features.len(),
+        format
+    );
+    if features.is_empty() {
+        return Ok(0);
+    }
+
+    let clipboard_error = |e| {
+        AppError::reason(
+            codes::CLIPBOARD_FAILED,
+            "Unable to Copy to the Clipboard",
+            e,
+        )
+    };
+    let count = features.len();
+    let content = match format {
+        ClipboardFormat::Csv => {
+            let settings = app_handle.state::<SettingsState>().get();
+            to_tsv(&features, settings.timezone(), settings.units).map_err(clipboard_error)?
+        }
+        ClipboardFormat::Geojson => {
+            BoatData::new(String::from(migrations::BOAT_DATA_VERSION), features).to_string()
+        }
+    };
+    app_handle
+        .clipboard_manager()
+        .write_text(content)
+        .map_err(|e| clipboard_error(e.to_string()))?;
+    Ok(count)
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeZone, Utc};
+    use geo_types::Point;
+
+    use super::*;
+    use crate::data::Layer;
+
+    /// Data points at the surface and the sea bed.
+    fn features() -> Vec<BoatDataFeature> {
+        let time = Utc.with_ymd_and_hms(2024, 5, 12, 23, 30, 5).unwrap();
+        vec![
+            BoatDataFeature::new(28.5, 0.5, Layer::Surface, time, Point::new(101.5, 3.1)),
+            BoatDataFeature::new(25.0, 10.0, Layer::SeaBed, time, Point::new(101.6, 3.2)),
+        ]
+    }
+
+    #[test]
+    fn tsv_has_a_header_and_a_line_per_data_point() {
+        let tsv = to_tsv(&features(), Tz::UTC, UnitSystem::Metric).unwrap();
+
+        let lines: Vec<Vec<&str>> = tsv.lines().map(|l| l.split('\t').collect()).collect();
+        assert_eq!(lines.len(), 3);
+        assert_eq!(
+            lines[0],
+            vec![
+                UnitSystem::Metric.temperature_column(),
+                UnitSystem::Metric.depth_column(),
+                "layer",
+                "time",
+                "lat",
+                "lng"
+            ]
+        );
+        assert_eq!(
+            lines[1],
+            vec![
+                "28.5",
+                "0.5",
+                "surface",
+                "2024-05-12 23:30:05",
+                "3.1",
+                "101.5"
+            ]
+        );
+        assert_eq!(lines[2][2], Layer::SeaBed.to_string());
+    }
+
+    #[test]
+    fn tsv_times_are_in_the_time_zone() {
+        let tsv = to_tsv(&features(), Tz::Asia__Kuala_Lumpur, UnitSystem::Metric).unwrap();
+
+        let line = tsv.lines().nth(1).unwrap();
+        assert_eq!(line.split('\t').nth(3), Some("2024-05-13 07:30:05"));
+    }
+
+    #[test]
+    fn tsv_values_are_in_the_units() {
+        let tsv = to_tsv(&features(), Tz::UTC, UnitSystem::Imperial).unwrap();
+
+        let lines: Vec<Vec<&str>> = tsv.lines().map(|l| l.split('\t').collect()).collect();
+        assert_eq!(lines[0][0], UnitSystem::Imperial.temperature_column());
+        assert_eq!(lines[0][1], UnitSystem::Imperial.depth_column());
+        assert_eq!(
+            lines[2][..2],
+            [
+                UnitSystem::Imperial.temperature(25.0).to_string(),
+                UnitSystem::Imperial.depth(10.0).to_string()
+            ]
+        );
+    }
+
+    #[test]
+    fn formats_are_parsed_in_lowercase() {
+        let format: ClipboardFormat = serde_json::from_str("\"geojson\"").unwrap();
+
+        assert_eq!(format, ClipboardFormat::Geojson);
+        assert!(serde_json::from_str::<ClipboardFormat>("\"tsv\"").is_err());
+    }
+}
@@ -0,0 +1,288 @@
+//! Correction of the times of the readings for the drift of the real-time clock of the boat, so
+//! the readings can be compared with weather data.
+//!
+//! The offset of the clock is measured when connecting like NTP: the desktop application sends
+//! its time, and the boat replies with the times it received the message and sent the reply by
+//! its own clock. Assuming the link takes as long each way, the boat is ahead by
+//! `((receive - origin) + (transmit - reply)) / 2`. A few samples are taken and the one with the
+//! shortest round trip is used, as it is the least affected by delays on the link.
+
+use std::time::Duration;
+
+use chrono::Utc;
+use serde::Serialize;
+use tauri::{AppHandle, Manager};
+
+use crate::{
+    app_data::AppData,
+    communication::{Link, Priority, Reply, RequestError},
+    data::BoatData,
+    errors::{codes, AppError},
+    handshake::ConnectionInfoState,
+    messages::{packet_type, TimeSync},
+    settings::SettingsState,
+};
+
+/// The number of time syncs sent to measure the offset.
+const SAMPLES: usize = 4;
+
+/// The time to wait for the reply to a time sync.
+const SYNC_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// A measurement of the offset of the clock of the boat.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct ClockSample {
+    /// How far the clock of the boat is ahead in milliseconds.
+    offset_ms: i64,
+    /// The time the time sync spent on the link in milliseconds.
+    delay_ms: i64,
+}
+
+impl ClockSample {
+    /// Computes the sample from the reply of the boat, received at `reply_ms` by the clock of
+    /// the desktop application.
+    fn new(sync: &TimeSync, reply_ms: i64) -> Self {
+        Self {
+            offset_ms: ((sync.receive_ms - sync.origin_ms) + (sync.transmit_ms - reply_ms)) / 2,
+            delay_ms: (reply_ms - sync.origin_ms) - (sync.transmit_ms - sync.receive_ms),
+        }
+    }
+}
+
+/// Payload of the `clock-offset-warning` event.
+#[derive(Debug, Serialize, Clone)]
+struct ClockOffsetPayload {
+    /// How far the clock of the boat is ahead in milliseconds.
+    offset_ms: i64,
+    /// The offset above which the warning is emitted, from the settings.
+    threshold_ms: u64,
+}
+
+/// Sends time syncs to the boat, returning the sample with the shortest round trip.
+///
+/// Returns `None` if the boat didn't reply to any of them.
+fn measure(link: &Link) -> Option<ClockSample> {
+    let mut best: Option<ClockSample> = None;
+    for _ in 0..SAMPLES {
+        let request = TimeSync {
+            sequence: link.next_sequence(),
+            origin_ms: Utc::now().timestamp_millis(),
+            receive_ms: 0,
+            transmit_ms: 0,
+        };
+        // Retries would be sent with the time of the first attempt, so samples are taken again
+        // instead
+        let reply = link.exchange(
+            packet_type::TIME_SYNC,
+            &request,
+            request.sequence,
+            Priority::Urgent,
+            SYNC_TIMEOUT,
+            0,
+        );
+        let reply_ms = Utc::now().timestamp_millis();
+        match reply {
+            Ok(Reply::TimeSync(sync)) if sync.origin_ms == request.origin_ms => {
+                let sample = ClockSample::new(&sync, reply_ms);
+                if sample.delay_ms < 0 {
+                    log::warn!("Ignoring Time Sync with a Negative Round Trip");
+                } else if best.map_or(true, |best| sample.delay_ms < best.delay_ms) {
+                    best = Some(sample);
+                }
+            }
+            Ok(_) | Err(RequestError::Rejected(_)) | Err(RequestError::Timeout { .. }) => {
+                log::debug!("Boat did not Reply to Time Sync {}", request.sequence);
+            }
+            Err(RequestError::NotConnected) => return None,
+        }
+    }
+    best
+}
+
+/// Measures the offset of the clock of the boat, storing it with the information of the
+/// connection.
+///
+/// The readings received from now on are corrected by the offset, and it is recorded in the
+/// metadata of the active dataset. The readings are still corrected if the offset is larger
+/// than the `clock_offset_warning_ms` setting, but a `clock-offset-warning` event is emitted so
+/// the user can check the clock of the boat.
+pub fn synchronize(app_handle: &AppHandle, link: &Link) {
+    let sample = match measure(link) {
+        Some(v) => v,
+        None => {
+            log::warn!(
+                "Unable to Measure the Clock Offset of the Boat on {}",
+                link.port_name()
+            );
+            return;
+        }
+    };
+    log::info!(
+        "Clock of the Boat is {} ms Ahead, Measured with a Round Trip of {} ms",
+        sample.offset_ms,
+        sample.delay_ms
+    );
+    app_handle
+        .state::<ConnectionInfoState>()
+        .set_clock_offset(sample.offset_ms);
+
+    let result = app_handle.state::<AppData>().update(app_handle, |data| {
+        let mut metadata = data.metadata();
+        metadata.clock_offset_ms = Some(sample.offset_ms);
+        data.set_metadata(&metadata);
+        Ok(())
+    });
+    if let Err(e) = result {
+        log::warn!("Unable to Record the Clock Offset: {}", e);
+    }
+
+    let threshold_ms = app_handle
+        .state::<SettingsState>()
+        .get()
+        .clock_offset_warning_ms;
+    if sample.offset_ms.unsigned_abs() > threshold_ms {
+        log::warn!(
+            "Clock Offset of {} ms is Larger than {} ms",
+            sample.offset_ms,
+            threshold_ms
+        );
+        let payload = ClockOffsetPayload {
+            offset_ms: sample.offset_ms,
+            threshold_ms,
+        };
+        if let Err(e) = app_handle.emit_all("clock-offset-warning", payload) {
+            log::warn!("Unable to emit clock-offset-warning event: {}", e);
+        }
+    }
+}
+
+/// Correct the times of boat data collected while the clock of the boat was `offset_ms`
+/// milliseconds ahead, e.g. data collected before the offset was measured.
+///
+/// A negative offset corrects a clock that was behind. The offset is added to the clock offset
+/// in the metadata of the data, so it shows the total correction applied.
+#[tauri::command]
+pub fn correct_timestamps(mut data: BoatData, offset_ms: i64) -> Result<BoatData, AppError> {
+    log::debug!("Correcting Timestamps by {} ms", offset_ms);
+    let invalid_offset = |message: String| {
+        AppError::new(codes::CLOCK_INVALID_OFFSET, message).with("offset_ms", offset_ms)
+    };
+    if offset_ms == i64::MIN {
+        return Err(invalid_offset(format!(
+            "Invalid Clock Offset: {offset_ms} is out of range"
+        )));
+    }
+    let offset = chrono::Duration::milliseconds(offset_ms);
+    for feature in data.features_mut() {
+        let time = feature.time().checked_sub_signed(offset).ok_or_else(|| {
+            invalid_offset(format!(
+                "Invalid Clock Offset: {} minus {offset_ms} ms is out of range",
+                feature.time()
+            ))
+        })?;
+        feature.set_time(time);
+    }
+
+    let mut metadata = data.metadata();
+    metadata.clock_offset_ms = Some(
+        metadata
+            .clock_offset_ms
+            .unwrap_or(0)
+            .saturating_add(offset_ms),
+    );
+    data.set_metadata(&metadata);
+    Ok(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeZone, Utc};
+    use geo_types::Point;
+
+    use super::*;
+    use crate::data::{BoatDataFeature, Layer};
+
+    /// Creates data with a data point at each of the times in seconds since the Unix epoch.
+    fn data(seconds: &[i64]) -> BoatData {
+        let features = seconds
+            .iter()
+            .map(|s| {
+                BoatDataFeature::new(
+                    28.5,
+                    0.5,
+                    Layer::Surface,
+                    Utc.timestamp_opt(*s, 0).unwrap(),
+                    Point::new(101.5, 3.1),
+                )
+            })
+            .collect();
+        BoatData::new(BoatData::default().version().to_string(), features)
+    }
+
+    /// Gets the times of the data points in seconds since the Unix epoch.
+    fn times(data: &BoatData) -> Vec<i64> {
+        data.features()
+            .iter()
+            .map(|f| f.time().timestamp())
+            .collect()
+    }
+
+    #[test]
+    fn samples_remove_the_time_spent_by_the_boat() {
+        // The boat is 5 s ahead, each way takes 100 ms and the boat takes 50 ms to reply
+        let sync = TimeSync {
+            sequence: 1,
+            origin_ms: 10_000,
+            receive_ms: 15_100,
+            transmit_ms: 15_150,
+        };
+
+        assert_eq!(
+            ClockSample::new(&sync, 10_250),
+            ClockSample {
+                offset_ms: 5_000,
+                delay_ms: 200,
+            }
+        );
+    }
+
+    #[test]
+    fn samples_of_a_clock_behind_are_negative() {
+        let sync = TimeSync {
+            sequence: 1,
+            origin_ms: 10_000,
+            receive_ms: 7_010,
+            transmit_ms: 7_010,
+        };
+
+        assert_eq!(ClockSample::new(&sync, 10_020).offset_ms, -3_000);
+    }
+
+    #[test]
+    fn corrections_shift_the_times_and_add_up_in_the_metadata() {
+        let corrected = correct_timestamps(data(&[1_000, 2_000]), 60_000).unwrap();
+        assert_eq!(times(&corrected), vec![940, 1_940]);
+        assert_eq!(corrected.metadata().clock_offset_ms, Some(60_000));
+
+        let corrected = correct_timestamps(corrected, -15_000).unwrap();
+        assert_eq!(times(&corrected), vec![955, 1_955]);
+        assert_eq!(corrected.metadata().clock_offset_ms, Some(45_000));
+    }
+
+    #[test]
+    fn offsets_out_of_range_are_rejected() {
+        assert_eq!(
+            correct_timestamps(data(&[0]), i64::MIN),
+            Err(AppError::new(
+                codes::CLOCK_INVALID_OFFSET,
+                format!("Invalid Clock Offset: {} is out of range", i64::MIN)
+            )
+            .with("offset_ms", i64::MIN))
+        );
+        let error = correct_timestamps(data(&[0]), i64::MAX).unwrap_err();
+        assert_eq!(
+            serde_json::to_value(error).unwrap()["code"],
+            codes::CLOCK_INVALID_OFFSET
+        );
+    }
+}
@@ -18,6 +18,7 @@ use self::babara_project::{
     connection::{self, packet::PacketType, Connect, Received},
     data::{BoatData, PathData},
 };
+use crate::errors::{codes, AppError};
 
 /// Googles protobuf package
 pub mod google {
@@ -59,79 +60,102 @@ pub mod babara_project {
     pub mod data {
         include!(concat!(env!("OUT_DIR"), "/babara_project.data.rs"));
 
-        impl From<&crate::data::BoatData> for BoatData {
-            fn from(value: &crate::data::BoatData) -> Self {
-                Self {
+        impl TryFrom<&crate::data::BoatData> for BoatData {
+            type Error = String;
+
+            fn try_from(value: &crate::data::BoatData) -> Result<Self, Self::Error> {
+                Ok(Self {
                     version: value.version().to_string(),
                     features: value
                         .features()
                         .iter()
-                        .map(boat_data::BoatDataFeature::from)
-                        .collect(),
-                }
+                        .map(boat_data::BoatDataFeature::try_from)
+                        .collect::<Result<Vec<_>, String>>()?,
+                })
             }
         }
 
-        impl From<crate::data::BoatData> for BoatData {
-            fn from(value: crate::data::BoatData) -> Self {
-                Self::from(&value)
+        impl TryFrom<crate::data::BoatData> for BoatData {
+            type Error = String;
+
+            fn try_from(value: crate::data::BoatData) -> Result<Self, Self::Error> {
+                Self::try_from(&value)
             }
         }
 
-        impl From<&mut crate::data::BoatData> for BoatData {
-            fn from(value: &mut crate::data::BoatData) -> Self {
-                Self::from(&*value)
+        impl TryFrom<&mut crate::data::BoatData> for BoatData {
+            type Error = String;
+
+            fn try_from(value: &mut crate::data::BoatData) -> Result<Self, Self::Error> {
+                Self::try_from(&*value)
             }
         }
 
-        impl From<&crate::data::BoatDataFeature> for boat_data::BoatDataFeature {
-            fn from(value: &crate::data::BoatDataFeature) -> Self {
-                Self {
+        impl TryFrom<&crate::data::BoatDataFeature> for boat_data::BoatDataFeature {
+            type Error = String;
+
+            fn try_from(value: &crate::data::BoatDataFeature) -> Result<Self, Self::Error> {
+                Ok(Self {
                     temperature: value.temperature(),
                     depth: value.depth(),
-                    layer: boat_data::Layer::from(value.layer()).into(),
+                    layer: boat_data::Layer::try_from(value.layer())?.into(),
                     time: Some(prost_types::Timestamp {
                         seconds: value.time().timestamp(),
                         // Do we need that much precision?
                         nanos: 0,
                     }),
                     geometry: Some(value.geometry().into()),
-                }
+                })
             }
         }
 
-        impl From<crate::data::BoatDataFeature> for boat_data::BoatDataFeature {
-            fn from(value: crate::data::BoatDataFeature) -> Self {
-                Self::from(&value)
+        impl TryFrom<crate::data::BoatDataFeature> for boat_data::BoatDataFeature {
+            type Error = String;
+
+            fn try_from(value: crate::data::BoatDataFeature) -> Result<Self, Self::Error> {
+                Self::try_from(&value)
             }
         }
 
-        impl From<&mut crate::data::BoatDataFeature> for boat_data::BoatDataFeature {
-            fn from(value: &mut crate::data::BoatDataFeature) -> Self {
-                Self::from(&*value)
+        impl TryFrom<&mut crate::data::BoatDataFeature> for boat_data::BoatDataFeature {
+            type Error = String;
+
+            fn try_from(value: &mut crate::data::BoatDataFeature) -> Result<Self, Self::Error> {
+                Self::try_from(&*value)
             }
         }
 
-        impl From<&crate::data::Layer> for boat_data::Layer {
-            fn from(value: &crate::data::Layer) -> Self {
+        impl TryFrom<&crate::data::Layer> for boat_data::Layer {
+            type Error = String;
+
+            /// Converts a layer, failing for layers the protocol doesn't have so they aren't
+            /// sent as another layer.
+            fn try_from(value: &crate::data::Layer) -> Result<Self, Self::Error> {
                 use crate::data::Layer;
                 match value {
-                    Layer::Surface => Self::Surface,
-                    Layer::Middle => Self::Middle,
-                    Layer::SeaBed => Self::SeaBed,
+                    Layer::Surface => Ok(Self::Surface),
+                    Layer::Middle => Ok(Self::Middle),
+                    Layer::SeaBed => Ok(Self::SeaBed),
+                    Layer::Other(name) => Err(format!(
+                        "Unable to Convert Layer {name}: The protocol has no such layer"
+                    )),
                 }
             }
         }
 
-        impl From<&mut crate::data::Layer> for boat_data::Layer {
-            fn from(value: &mut crate::data::Layer) -> Self {
-                Self::from(&*value)
+        impl TryFrom<&mut crate::data::Layer> for boat_data::Layer {
+            type Error = String;
+
+            fn try_from(value: &mut crate::data::Layer) -> Result<Self, Self::Error> {
+                Self::try_from(&*value)
             }
         }
 
-        impl From<crate::data::Layer> for boat_data::Layer {
-            fn from(value: crate::data::Layer) -> Self {
-                Self::from(&value)
+        impl TryFrom<crate::data::Layer> for boat_data::Layer {
+            type Error = String;
+
+            fn try_from(value: crate::data::Layer) -> Result<Self, Self::Error> {
+                Self::try_from(&value)
             }
         }
 
@@ -315,7 +339,7 @@ impl BoatPort {
             data: packet.encode_to_vec(),
         };
         self.port
-            .write(&data.encode_length_delimited_to_vec())
+            .write(&crate::protocol::encode_frame(&data, false))
             .map_err(|e| e.to_string())?;
         Ok(())
     }
@@ -429,7 +453,7 @@ impl BoatPort {
 pub async fn find_ports(
     state: tauri::State<'_, ConnectedBoats>,
     app_handle: tauri::AppHandle,
-) -> Result<Vec<String>, String> {
+) -> Result<Vec<String>, AppError> {
     let mut boats = state.boats.lock().unwrap();
 
     // Clearing all disconnected ports
@@ -445,7 +469,13 @@ pub async fn find_ports(
     }
 
     log::info!("Finding Available Ports");
-    let ports = serialport::available_ports().map_err(|e| e.to_string())?;
+    let ports = serialport::available_ports().map_err(|e| {
+        AppError::reason(
+            codes::CONNECTION_LIST_PORTS_FAILED,
+            "Unable to List Ports",
+            e,
+        )
+    })?;
     let ports: Vec<SerialPortInfo> = ports
         .into_iter()
         .filter(|v| !boats.contains_key(&v.port_name))
@@ -504,11 +534,66 @@ pub fn send_path(
     state: tauri::State<ConnectedBoats>,
     port: String,
     data: crate::path::PathData,
-) -> Result<(), String> {
+) -> Result<(), AppError> {
     log::info!("Sending Path Data to {port}");
+    data.check_boundary()?;
     let mut ports = state.boats.lock().unwrap();
-    let port = ports
-        .get_mut(&port)
-        .ok_or(format!("Unable to find port: {port}"))?;
-    port.send_path(data.into())
+    let boat = ports.get_mut(&port).ok_or_else(|| {
+        AppError::new(
+            codes::CONNECTION_PORT_NOT_FOUND,
+            format!("Unable to find port: {port}"),
+        )
+        .with("port", port.as_str())
+    })?;
+    boat.send_path(data.into())
+        .map_err(|e| AppError::reason(codes::CONNECTION_SEND_FAILED, "Unable to Send Path", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::babara_project::data::{boat_data, BoatData};
+    use crate::data::Layer;
+
+    #[test]
+    fn layer_conversion_keeps_known_layers() {
+        for (layer, expected) in [
+            (Layer::Surface, boat_data::Layer::Surface),
+            (Layer::Middle, boat_data::Layer::Middle),
+            (Layer::SeaBed, boat_data::Layer::SeaBed),
+        ] {
+            assert_eq!(boat_data::Layer::try_from(&layer), Ok(expected));
+            assert_eq!(Layer::from(expected), layer);
+        }
+    }
+
+    #[test]
+    fn layer_conversion_rejects_unknown_layers() {
+        let layer = Layer::Other(String::from("thermocline"));
+
+        let error = boat_data::Layer::try_from(&layer).unwrap_err();
+
+        assert!(error.contains("thermocline"), "{error}");
+    }
+
+    #[test]
+    fn boat_data_with_unknown_layer_is_not_sent() {
+        let data: crate::data::BoatData = r#"{
+            "type": "FeatureCollection",
+            "version": "0.2.0",
+            "features": [{
+                "type": "Feature",
+                "geometry": { "type": "Point", "coordinates": [101.5, 3.1] },
+                "properties": {
+                    "temperature": 24.25,
+                    "depth": 6.0,
+                    "layer": "thermocline",
+                    "time": "2024-05-12T06:31:00Z"
+                }
+            }]
+        }"#
+        .parse()
+        .unwrap();
+
+        assert!(BoatData::try_from(&data).is_err());
+    }
 }
@@ -0,0 +1,1343 @@
+//! Communication with the boat through its telemetry radio or WiFi bridge.
+
+use std::{
+    collections::{HashMap, VecDeque},
+    fmt::Display,
+    sync::{
+        atomic::{AtomicBool, AtomicU32, Ordering},
+        mpsc::{self, Receiver, RecvTimeoutError, Sender},
+        Arc, Condvar, Mutex,
+    },
+    thread::JoinHandle,
+    time::{Duration, Instant},
+};
+
+use prost::Message;
+use serde::Serialize;
+use serialport::SerialPortType;
+use tauri::{AppHandle, Manager};
+
+use crate::{
+    comm_proto::babara_project::connection::Packet,
+    errors::{codes, AppError},
+    frame_log::{Direction, FrameLogState},
+    handshake::{self, ConnectionInfoState},
+    health,
+    heartbeat::{self, LinkStats},
+    messages::{packet_type, Ack, Hello, Nack, Pong, TimeSync},
+    progress,
+    protocol::{self, ProtocolState, ProtocolStats},
+    settings::SettingsState,
+    simulation::ReplayState,
+    telemetry, tracking,
+    transport::{SerialTransport, TcpError, TcpTransport, Transport, READ_TIMEOUT},
+};
+
+/// The time to wait for the threads using the port to stop when disconnecting.
+const THREAD_STOP_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// The number of frames kept waiting to be written, e.g. while reconnecting.
+const MAX_QUEUED_FRAMES: usize = 256;
+
+/// The time to wait before the first attempt to reconnect.
+const INITIAL_RECONNECT_DELAY: Duration = Duration::from_secs(1);
+
+/// The longest time to wait between attempts to reconnect.
+const MAX_RECONNECT_DELAY: Duration = Duration::from_secs(30);
+
+/// Information on a serial port available for connecting to the boat.
+#[derive(Debug, Serialize, Clone, PartialEq, Eq)]
+pub struct SerialPortInfo {
+    /// The name of the port used for connecting.
+    name: String,
+    /// The kind of device of the port, one of `usb`, `pci`, `bluetooth` or `unknown`.
+    port_type: &'static str,
+    /// The USB vendor ID of the device.
+    vid: Option<u16>,
+    /// The USB product ID of the device.
+    pid: Option<u16>,
+    /// The USB serial number of the device.
+    serial_number: Option<String>,
+    /// The manufacturer of the device.
+    manufacturer: Option<String>,
+    /// The product name of the device.
+    product: Option<String>,
+}
+
+impl From<serialport::SerialPortInfo> for SerialPortInfo {
+    fn from(value: serialport::SerialPortInfo) -> Self {
+        let mut info = Self {
+            name: value.port_name,
+            port_type: "unknown",
+            vid: None,
+            pid: None,
+            serial_number: None,
+            manufacturer: None,
+            product: None,
+        };
+        match value.port_type {
+            SerialPortType::UsbPort(usb) => {
+                info.port_type = "usb";
+                info.vid = Some(usb.vid);
+                info.pid = Some(usb.pid);
+                info.serial_number = usb.serial_number;
+                info.manufacturer = usb.manufacturer;
+                info.product = usb.product;
+            }
+            SerialPortType::PciPort => info.port_type = "pci",
+            SerialPortType::BluetoothPort => info.port_type = "bluetooth",
+            SerialPortType::Unknown => {
+                log::debug!("Unable to Read the Device of Port: {}", info.name);
+            }
+        }
+        info
+    }
+}
+
+impl SerialPortInfo {
+    /// Checks if the port is a USB serial device.
+    fn is_usb(&self) -> bool {
+        self.port_type == "usb"
+    }
+}
+
+/// List the serial ports available for connecting to the boat.
+///
+/// Only USB serial devices are listed when `usb_only` is `true`.
+#[tauri::command]
+pub fn list_serial_ports(usb_only: Option<bool>) -> Result<Vec<SerialPortInfo>, AppError> {
+    log::debug!("Listing Serial Ports");
+    let ports = serialport::available_ports().map_err(|e| {
+        AppError::reason(
+            codes::CONNECTION_LIST_PORTS_FAILED,
+            "Unable to List Ports",
+            e,
+        )
+    })?;
+    let ports: Vec<SerialPortInfo> = ports
+        .into_iter()
+        .map(SerialPortInfo::from)
+        .filter(|port| !usb_only.unwrap_or(false) || port.is_usb())
+        .collect();
+    log::debug!("Found Ports: {:?}", ports);
+    Ok(ports)
+}
+
+/// Payload of the `serial-status` event.
+#[derive(Debug, Serialize, Clone)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum SerialStatus {
+    /// The port is connected.
+    Connected {
+        /// The name of the port, or the address for TCP.
+        port: String,
+        /// The baud rate of a serial connection.
+        baud_rate: Option<u32>,
+    },
+    /// The port is disconnected.
+    Disconnected {
+        /// The name of the port.
+        port: String,
+    },
+    /// The connection failed.
+    Error {
+        /// The name of the port.
+        port: String,
+        /// The reason the connection failed.
+        message: String,
+    },
+    /// The port is being opened again after it failed.
+    Reconnecting {
+        /// The name of the port.
+        port: String,
+        /// The number of the attempt to reconnect.
+        attempt: u32,
+        /// The time before the attempt in milliseconds.
+        delay_ms: u64,
+    },
+}
+
+/// Payload of the `serial-data` event.
+#[derive(Debug, Serialize, Clone)]
+struct SerialDataPayload {
+    /// The name of the port the frame is received from.
+    port: String,
+    /// The type of the packet received.
+    packet_type: i32,
+    /// The encoded message in the packet.
+    data: Vec<u8>,
+}
+
+/// Notifies the windows of a change to the connection status.
+fn emit_status(app_handle: &AppHandle, status: SerialStatus) {
+    log::info!("Serial Status: {:?}", status);
+    if let Err(e) = app_handle.emit_all("serial-status", status) {
+        log::warn!("Unable to emit serial-status event: {}", e);
+    }
+}
+
+/// A reply from the boat to a message sent.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Reply {
+    /// The boat accepted the message.
+    Ack,
+    /// The boat rejected the message with a reason.
+    Nack(String),
+    /// The boat replied to a hello with its own.
+    Hello(Hello),
+    /// The boat replied to a time sync with its times.
+    TimeSync(TimeSync),
+}
+
+/// The senders waiting for a reply, by the sequence number of the message sent.
+type PendingReplies = Arc<Mutex<HashMap<u32, Sender<Reply>>>>;
+
+/// The priority of a message sent to the boat.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Priority {
+    /// Sent in order with the other messages.
+    Normal,
+    /// Sent ahead of the normal messages waiting to be written.
+    Urgent,
+}
+
+/// Errors when the boat doesn't accept a message.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RequestError {
+    /// The connection is closed.
+    NotConnected,
+    /// The boat rejected the message with a reason.
+    Rejected(String),
+    /// The boat didn't reply after sending the message `attempts` times.
+    Timeout {
+        /// The number of times the message was sent.
+        attempts: u32,
+    },
+}
+
+impl Display for RequestError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::NotConnected => write!(f, "Not Connected to the Boat"),
+            Self::Rejected(reason) => write!(f, "Rejected by the Boat: {reason}"),
+            Self::Timeout { attempts } => {
+                write!(f, "No Response from the Boat after {attempts} Attempts")
+            }
+        }
+    }
+}
+
+impl std::error::Error for RequestError {}
+
+impl From<RequestError> for AppError {
+    fn from(value: RequestError) -> Self {
+        let message = value.to_string();
+        match value {
+            RequestError::NotConnected => AppError::new(codes::CONNECTION_NOT_CONNECTED, message),
+            RequestError::Rejected(reason) => {
+                AppError::new(codes::CONNECTION_REJECTED, message).with("reason", reason)
+            }
+            RequestError::Timeout { attempts } => {
+                AppError::new(codes::CONNECTION_TIMEOUT, message).with("attempts", attempts)
+            }
+        }
+    }
+}
+
+/// The packets waiting to be written to the port.
+///
+/// The packets are encoded into frames when they are written, so frames queued before the
+/// protocol version is negotiated get the CRC trailer if the boat supports it.
+#[derive(Debug, Default)]
+struct Frames {
+    /// The urgent packets, written first.
+    urgent: VecDeque<Packet>,
+    /// The normal packets.
+    normal: VecDeque<Packet>,
+    /// The number of frames dropped since the queue last overflowed.
+    dropped: usize,
+}
+
+impl Frames {
+    /// Queues a packet, dropping the oldest packet when the queue is full.
+    ///
+    /// Normal packets are dropped before urgent packets.
+    fn push(&mut self, priority: Priority, packet: Packet) {
+        if self.urgent.len() + self.normal.len() >= MAX_QUEUED_FRAMES {
+            if self.normal.pop_front().is_none() {
+                self.urgent.pop_front();
+            }
+            self.dropped += 1;
+        }
+        match priority {
+            Priority::Normal => self.normal.push_back(packet),
+            Priority::Urgent => self.urgent.push_back(packet),
+        }
+    }
+
+    /// Takes the next packet to write.
+    fn pop(&mut self) -> Option<(Priority, Packet)> {
+        match self.urgent.pop_front() {
+            Some(packet) => Some((Priority::Urgent, packet)),
+            None => self
+                .normal
+                .pop_front()
+                .map(|packet| (Priority::Normal, packet)),
+        }
+    }
+
+    /// Puts back a packet that couldn't be written, to be written first.
+    fn unpop(&mut self, priority: Priority, packet: Packet) {
+        match priority {
+            Priority::Normal => self.normal.push_front(packet),
+            Priority::Urgent => self.urgent.push_front(packet),
+        }
+    }
+}
+
+/// Queue of frames written to the port by the writer thread.
+#[derive(Debug, Default)]
+struct WriteQueue {
+    frames: Mutex<Frames>,
+    /// Notified when a frame is queued or the port is closed.
+    ready: Condvar,
+}
+
+/// Handle for sending messages to the boat on an open connection.
+///
+/// The handle can be used without holding the lock on the connection. Messages sent while
+/// reconnecting are queued until the port is open again.
+#[derive(Clone)]
+pub struct Link {
+    /// The name of the port connected to, which can change when reconnecting.
+    port_name: Arc<Mutex<String>>,
+    /// The frames waiting to be written to the boat.
+    queue: Arc<WriteQueue>,
+    /// The messages waiting for a reply.
+    pending: PendingReplies,
+    /// The sequence number of the next message.
+    sequence: Arc<AtomicU32>,
+    /// Set when the connection is closed.
+    stop: Arc<AtomicBool>,
+    /// Whether the frames have the CRC trailer.
+    crc: Arc<AtomicBool>,
+}
+
+impl Link {
+    /// Gets the name of the port connected to.
+    pub fn port_name(&self) -> String {
+        self.port_name.lock().unwrap().clone()
+    }
+
+    /// Checks if the connection is still open.
+    pub fn is_open(&self) -> bool {
+        !self.stop.load(Ordering::Relaxed)
+    }
+
+    /// Checks if the frames have the CRC trailer.
+    pub fn uses_crc(&self) -> bool {
+        self.crc.load(Ordering::Relaxed)
+    }
+
+    /// Sets whether the frames have the CRC trailer, e.g. after the protocol version is
+    /// negotiated.
+    pub fn set_crc(&self, crc: bool) {
+        self.crc.store(crc, Ordering::Relaxed);
+    }
+
+    /// Marks the connection as closed, stopping the threads using it.
+    pub fn shutdown(&self) {
+        self.stop.store(true, Ordering::Relaxed);
+        self.queue.ready.notify_all();
+    }
+
+    /// Queues a message to the boat in a packet of `packet_type`.
+    pub fn send<M: Message>(
+        &self,
+        packet_type: i32,
+        message: &M,
+        priority: Priority,
+    ) -> Result<(), RequestError> {
+        if !self.is_open() {
+            return Err(RequestError::NotConnected);
+        }
+        let packet = Packet {
+            version: String::from(protocol::PROTOCOL_VERSION),
+            r#type: packet_type,
+            data: message.encode_to_vec(),
+        };
+        self.queue.frames.lock().unwrap().push(priority, packet);
+        self.queue.ready.notify_one();
+        Ok(())
+    }
+
+    /// Gets a sequence number for a message expecting a reply.
+    pub fn next_sequence(&self) -> u32 {
+        self.sequence.fetch_add(1, Ordering::Relaxed)
+    }
+
+    /// Sends a message with `sequence` until the boat replies or the attempts run out.
+    ///
+    /// The message is sent again up to `retries` times if the boat doesn't reply within
+    /// `timeout`.
+    pub fn request<M: Message>(
+        &self,
+        packet_type: i32,
+        message: &M,
+        sequence: u32,
+        priority: Priority,
+        timeout: Duration,
+        retries: u32,
+    ) -> Result<(), RequestError> {
+        match self.exchange(packet_type, message, sequence, priority, timeout, retries)? {
+            Reply::Nack(reason) => Err(RequestError::Rejected(reason)),
+            Reply::Ack | Reply::Hello(_) | Reply::TimeSync(_) => Ok(()),
+        }
+    }
+
+    /// Sends a message with `sequence` until the boat replies or the attempts run out,
+    /// returning the reply.
+    ///
+    /// The message is sent again up to `retries` times if the boat doesn't reply within
+    /// `timeout`.
+    pub fn exchange<M: Message>(
+        &self,
+        packet_type: i32,
+        message: &M,
+        sequence: u32,
+        priority: Priority,
+        timeout: Duration,
+        retries: u32,
+    ) -> Result<Reply, RequestError> {
+        let (tx, reply) = mpsc::channel();
+        self.pending.lock().unwrap().insert(sequence, tx);
+        let result = (|| {
+            for attempt in 1..=retries + 1 {
+                log::debug!("Sending Message {} Attempt {}", sequence, attempt);
+                self.send(packet_type, message, priority)?;
+                match reply.recv_timeout(timeout) {
+                    Ok(reply) => return Ok(reply),
+                    Err(RecvTimeoutError::Timeout) => continue,
+                    // The connection is closed
+                    Err(RecvTimeoutError::Disconnected) => return Err(RequestError::NotConnected),
+                }
+            }
+            Err(RequestError::Timeout {
+                attempts: retries + 1,
+            })
+        })();
+        self.pending.lock().unwrap().remove(&sequence);
+        result
+    }
+}
+
+/// Identifies a USB serial device, as the OS can rename its port when it is plugged in again.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct UsbDevice {
+    /// The USB vendor ID of the device.
+    vid: u16,
+    /// The USB product ID of the device.
+    pid: u16,
+    /// The USB serial number of the device.
+    serial_number: Option<String>,
+}
+
+impl UsbDevice {
+    /// Finds the USB device of a port.
+    fn of_port(port_name: &str) -> Option<Self> {
+        serialport::available_ports()
+            .ok()?
+            .into_iter()
+            .find(|port| port.port_name == port_name)
+            .and_then(|port| match port.port_type {
+                SerialPortType::UsbPort(usb) => Some(Self {
+                    vid: usb.vid,
+                    pid: usb.pid,
+                    serial_number: usb.serial_number,
+                }),
+                _ => None,
+            })
+    }
+
+    /// Finds the port of the device.
+    fn find_port(&self) -> Option<String> {
+        serialport::available_ports()
+            .ok()?
+            .into_iter()
+            .find(|port| match &port.port_type {
+                SerialPortType::UsbPort(usb) => {
+                    usb.vid == self.vid
+                        && usb.pid == self.pid
+                        && usb.serial_number == self.serial_number
+                }
+                _ => false,
+            })
+            .map(|port| port.port_name)
+    }
+}
+
+/// Where a connection to the boat is made to.
+#[derive(Debug, Clone)]
+enum Endpoint {
+    /// A serial port.
+    Serial {
+        /// The name of the port first connected to.
+        port_name: String,
+        /// The baud rate of the connection.
+        baud_rate: u32,
+        /// The USB device of the port.
+        device: Option<UsbDevice>,
+    },
+    /// A TCP address.
+    Tcp {
+        /// The host name or IP address.
+        host: String,
+        /// The TCP port.
+        port: u16,
+    },
+}
+
+impl Endpoint {
+    /// Gets the current name of the endpoint.
+    ///
+    /// A serial port is found by its USB device if it has one, as its name can change.
+    fn current_name(&self) -> String {
+        match self {
+            Self::Serial {
+                port_name, device, ..
+            } => device
+                .as_ref()
+                .and_then(UsbDevice::find_port)
+                .unwrap_or_else(|| port_name.clone()),
+            Self::Tcp { host, port } => format!("{host}:{port}"),
+        }
+    }
+
+    /// Gets the baud rate of a serial endpoint.
+    fn baud_rate(&self) -> Option<u32> {
+        match self {
+            Self::Serial { baud_rate, .. } => Some(*baud_rate),
+            Self::Tcp { .. } => None,
+        }
+    }
+
+    /// Opens a transport to the endpoint named `name`.
+    fn open(&self, name: &str) -> Result<Box<dyn Transport>, String> {
+        match self {
+            Self::Serial { baud_rate, .. } => {
+                Ok(Box::new(SerialTransport::open(name, *baud_rate)?))
+            }
+            Self::Tcp { host, port } => TcpTransport::connect(host, *port)
+                .map(|transport| Box::new(transport) as Box<dyn Transport>)
+                .map_err(|e| e.to_string()),
+        }
+    }
+}
+
+/// Payload of the `serial-queue-overflow` event.
+#[derive(Debug, Serialize, Clone)]
+struct QueueOverflowPayload {
+    /// The name of the port or address reconnected to.
+    port: String,
+    /// The number of messages dropped while reconnecting.
+    dropped: usize,
+}
+
+/// An open connection to the boat.
+struct Connection {
+    /// Handle for sending messages on the connection.
+    link: Link,
+    /// The threads using the transport and sending heartbeats on it.
+    threads: Vec<JoinHandle<()>>,
+    /// Receives when each thread stops.
+    stopped: Receiver<()>,
+}
+
+impl Connection {
+    /// Opens a serial port.
+    fn open_serial(
+        app_handle: &AppHandle,
+        port_name: String,
+        baud_rate: u32,
+    ) -> Result<Self, String> {
+        log::info!("Opening Port: {} at {} Baud", port_name, baud_rate);
+        let endpoint = Endpoint::Serial {
+            device: UsbDevice::of_port(&port_name),
+            port_name: port_name.clone(),
+            baud_rate,
+        };
+        let transport = endpoint.open(&port_name)?;
+        Ok(Self::start(app_handle, endpoint, port_name, transport))
+    }
+
+    /// Starts the threads using an open transport and sending heartbeats on it.
+    fn start(
+        app_handle: &AppHandle,
+        endpoint: Endpoint,
+        name: String,
+        transport: Box<dyn Transport>,
+    ) -> Self {
+        let baud_rate = endpoint.baud_rate();
+        *app_handle.state::<ProtocolState>().stats.lock().unwrap() = ProtocolStats::default();
+        app_handle.state::<LinkStats>().reset();
+        let link = Link {
+            port_name: Arc::new(Mutex::new(name.clone())),
+            queue: Arc::new(WriteQueue::default()),
+            pending: PendingReplies::default(),
+            sequence: Arc::new(AtomicU32::new(0)),
+            stop: Arc::new(AtomicBool::new(false)),
+            crc: Arc::new(AtomicBool::new(false)),
+        };
+        let (stopped_tx, stopped) = mpsc::channel();
+        let threads = vec![
+            {
+                let app_handle = app_handle.clone();
+                let link = link.clone();
+                let stopped_tx = stopped_tx.clone();
+                std::thread::spawn(move || {
+                    port_loop(app_handle, link, endpoint, transport, stopped_tx)
+                })
+            },
+            {
+                let app_handle = app_handle.clone();
+                let link = link.clone();
+                std::thread::spawn(move || {
+                    heartbeat::heartbeat_loop(&app_handle, &link);
+                    let _ = stopped_tx.send(());
+                })
+            },
+        ];
+
+        emit_status(
+            app_handle,
+            SerialStatus::Connected {
+                port: name,
+                baud_rate,
+            },
+        );
+        Self {
+            link,
+            threads,
+            stopped,
+        }
+    }
+
+    /// Stops the threads using the port and closes it.
+    ///
+    /// The threads are left running if they don't stop in time.
+    fn close(self, app_handle: &AppHandle) {
+        let port_name = self.link.port_name();
+        log::info!("Closing Port: {}", port_name);
+        self.link.shutdown();
+
+        let mut running = self.threads.len();
+        while running > 0 {
+            match self.stopped.recv_timeout(THREAD_STOP_TIMEOUT) {
+                Ok(_) => running -= 1,
+                Err(RecvTimeoutError::Disconnected) => running = 0,
+                Err(RecvTimeoutError::Timeout) => {
+                    log::warn!("Threads of {} did not Stop in Time", port_name);
+                    break;
+                }
+            }
+        }
+        if running == 0 {
+            for thread in self.threads {
+                if thread.join().is_err() {
+                    log::warn!("A Thread of {} Panicked", port_name);
+                }
+            }
+        }
+
+        // Dropping the senders so anything waiting for a reply stops
+        self.link.pending.lock().unwrap().clear();
+        app_handle.state::<ConnectionInfoState>().reset();
+        emit_status(app_handle, SerialStatus::Disconnected { port: port_name });
+    }
+}
+
+/// Passes a reply from the boat to the message waiting for it.
+fn handle_reply(link: &Link, sequence: u32, reply: Reply) {
+    match link.pending.lock().unwrap().remove(&sequence) {
+        Some(tx) => {
+            let _ = tx.send(reply);
+        }
+        None => log::debug!("Ignoring Reply to Message {}: {:?}", sequence, reply),
+    }
+}
+
+/// Handles a packet received from the boat, other than the replies to messages sent.
+///
+/// `source` is the name of the port or file the packet is received from.
+pub fn dispatch_packet(app_handle: &AppHandle, source: &str, packet: Packet) {
+    match packet.r#type {
+        packet_type::READING => telemetry::handle_reading(app_handle, &packet.data),
+        packet_type::POSITION => tracking::handle_position(app_handle, &packet.data),
+        packet_type::HEALTH => health::handle_health(app_handle, &packet.data),
+        packet_type::PROGRESS => progress::handle_progress(app_handle, &packet.data),
+        _ => {
+            let payload = SerialDataPayload {
+                port: source.to_string(),
+                packet_type: packet.r#type,
+                data: packet.data,
+            };
+            if let Err(e) = app_handle.emit_all("serial-data", payload) {
+                log::warn!("Unable to emit serial-data event: {}", e);
+            }
+        }
+    }
+}
+
+/// Handles a packet received from the boat.
+fn handle_packet(app_handle: &AppHandle, link: &Link, packet: Packet) {
+    match packet.r#type {
+        packet_type::ACK => match Ack::decode(&*packet.data) {
+            Ok(ack) => handle_reply(link, ack.sequence, Reply::Ack),
+            Err(e) => log::warn!("Received an Invalid Ack: {}", e),
+        },
+        packet_type::NACK => match Nack::decode(&*packet.data) {
+            Ok(nack) => handle_reply(link, nack.sequence, Reply::Nack(nack.reason)),
+            Err(e) => log::warn!("Received an Invalid Nack: {}", e),
+        },
+        packet_type::PONG => match Pong::decode(&*packet.data) {
+            Ok(pong) => handle_reply(link, pong.sequence, Reply::Ack),
+            Err(e) => log::warn!("Received an Invalid Pong: {}", e),
+        },
+        packet_type::HELLO => match Hello::decode(&*packet.data) {
+            Ok(hello) => handle_reply(link, hello.sequence, Reply::Hello(hello)),
+            Err(e) => log::warn!("Received an Invalid Hello: {}", e),
+        },
+        packet_type::TIME_SYNC => match TimeSync::decode(&*packet.data) {
+            Ok(sync) => handle_reply(link, sync.sequence, Reply::TimeSync(sync)),
+            Err(e) => log::warn!("Received an Invalid Time Sync: {}", e),
+        },
+        _ => dispatch_packet(app_handle, &link.port_name(), packet),
+    }
+}
+
+/// Reads frames from the transport until it fails or is stopped, handling the packets received.
+fn read_loop(
+    app_handle: &AppHandle,
+    link: &Link,
+    mut transport: Box<dyn Transport>,
+    failed: &AtomicBool,
+) -> Result<(), String> {
+    let protocol = app_handle.state::<ProtocolState>();
+    let frame_log = app_handle.state::<FrameLogState>();
+    while link.is_open() && !failed.load(Ordering::Relaxed) {
+        transport.set_crc(link.uses_crc());
+        if let Some((packet, bytes)) = transport.read_frame()? {
+            frame_log.record(Direction::Inbound, &bytes);
+            handle_packet(app_handle, link, packet);
+            *protocol.stats.lock().unwrap() = transport.stats();
+        }
+    }
+    Ok(())
+}
+
+/// Writes the frames queued to the transport until it fails or is stopped, urgent frames first.
+///
+/// The packets are encoded with the CRC trailer negotiated when they are written. A frame that
+/// can't be written is kept in the queue.
+fn write_loop(
+    app_handle: &AppHandle,
+    link: &Link,
+    mut transport: Box<dyn Transport>,
+    failed: &AtomicBool,
+) -> Result<(), String> {
+    let frame_log = app_handle.state::<FrameLogState>();
+    loop {
+        let next = {
+            let mut frames = link.queue.frames.lock().unwrap();
+            loop {
+                if !link.is_open() || failed.load(Ordering::Relaxed) {
+                    break None;
+                }
+                if let Some(next) = frames.pop() {
+                    break Some(next);
+                }
+                frames = link
+                    .queue
+                    .ready
+                    .wait_timeout(frames, READ_TIMEOUT)
+                    .unwrap()
+                    .0;
+            }
+        };
+        let (priority, packet) = match next {
+            Some(v) => v,
+            None => return Ok(()),
+        };
+
+        let frame = protocol::encode_frame(&packet, link.uses_crc());
+        if let Err(e) = transport.write_frame(&frame) {
+            link.queue.frames.lock().unwrap().unpop(priority, packet);
+            return Err(e);
+        }
+        frame_log.record(Direction::Outbound, &frame);
+    }
+}
+
+/// Reads from and writes to an open transport until it fails or the connection is closed.
+///
+/// Returns the reason the transport failed.
+fn run_transport(
+    app_handle: &AppHandle,
+    link: &Link,
+    transport: Box<dyn Transport>,
+) -> Result<(), String> {
+    let writer = transport.try_clone()?;
+    let failed = Arc::new(AtomicBool::new(false));
+    let writer = {
+        let app_handle = app_handle.clone();
+        let link = link.clone();
+        let failed = failed.clone();
+        std::thread::spawn(move || {
+            let result = write_loop(&app_handle, &link, writer, &failed);
+            failed.store(true, Ordering::Relaxed);
+            result
+        })
+    };
+
+    // Negotiating again as the boat can be restarted with other firmware while reconnecting
+    link.set_crc(false);
+    {
+        let app_handle = app_handle.clone();
+        let link = link.clone();
+        std::thread::spawn(move || handshake::negotiate(&app_handle, &link));
+    }
+
+    let closer = transport.try_clone();
+    let read_result = read_loop(app_handle, link, transport, &failed);
+    failed.store(true, Ordering::Relaxed);
+    link.queue.ready.notify_all();
+    let write_result = writer
+        .join()
+        .unwrap_or_else(|_| Err(String::from("Writer Thread Panicked")));
+    if let Ok(mut closer) = closer {
+        closer.close();
+    }
+    read_result.and(write_result)
+}
+
+/// Gets the time to wait after an attempt to reconnect that waited `delay`.
+fn next_reconnect_delay(delay: Duration) -> Duration {
+    (delay * 2).min(MAX_RECONNECT_DELAY)
+}
+
+/// Opens the transport again with exponential backoff while auto-reconnect is enabled.
+///
+/// Returns `None` if auto-reconnect is disabled or the connection is closed.
+fn reconnect(
+    app_handle: &AppHandle,
+    link: &Link,
+    endpoint: &Endpoint,
+) -> Option<Box<dyn Transport>> {
+    let state = app_handle.state::<SerialConnection>();
+    let mut delay = INITIAL_RECONNECT_DELAY;
+    let mut attempt = 0;
+    loop {
+        if !link.is_open() || !state.auto_reconnect.load(Ordering::Relaxed) {
+            return None;
+        }
+        attempt += 1;
+        emit_status(
+            app_handle,
+            SerialStatus::Reconnecting {
+                port: link.port_name(),
+                attempt,
+                delay_ms: delay.as_millis() as u64,
+            },
+        );
+
+        let deadline = Instant::now() + delay;
+        while link.is_open() && Instant::now() < deadline {
+            std::thread::sleep(READ_TIMEOUT);
+        }
+        if !link.is_open() {
+            return None;
+        }
+
+        let name = endpoint.current_name();
+        match endpoint.open(&name) {
+            Ok(transport) => {
+                *link.port_name.lock().unwrap() = name.clone();
+                emit_status(
+                    app_handle,
+                    SerialStatus::Connected {
+                        port: name.clone(),
+                        baud_rate: endpoint.baud_rate(),
+                    },
+                );
+
+                let dropped = std::mem::take(&mut link.queue.frames.lock().unwrap().dropped);
+                if dropped > 0 {
+                    log::warn!("Dropped {} Messages while Reconnecting", dropped);
+                    let payload = QueueOverflowPayload {
+                        port: name,
+                        dropped,
+                    };
+                    if let Err(e) = app_handle.emit_all("serial-queue-overflow", payload) {
+                        log::warn!("Unable to emit serial-queue-overflow event: {}", e);
+                    }
+                }
+                return Some(transport);
+            }
+            Err(e) => {
+                log::info!("Unable to Reconnect to {}: {}", name, e);
+                delay = next_reconnect_delay(delay);
+            }
+        }
+    }
+}
+
+/// Uses the transport until the connection is closed, reconnecting when the transport fails.
+fn port_loop(
+    app_handle: AppHandle,
+    link: Link,
+    endpoint: Endpoint,
+    transport: Box<dyn Transport>,
+    stopped: Sender<()>,
+) {
+    let mut transport = Some(transport);
+    while let Some(current) = transport.take() {
+        if let Err(e) = run_transport(&app_handle, &link, current) {
+            emit_status(
+                &app_handle,
+                SerialStatus::Error {
+                    port: link.port_name(),
+                    message: e,
+                },
+            );
+            transport = reconnect(&app_handle, &link, &endpoint);
+        }
+    }
+    link.shutdown();
+    let _ = stopped.send(());
+}
+
+/// The connection to the boat, through a serial port or TCP.
+#[derive(Default)]
+pub struct SerialConnection {
+    connection: Mutex<Option<Connection>>,
+    /// Whether the transport is opened again when it fails.
+    auto_reconnect: AtomicBool,
+}
+
+impl SerialConnection {
+    /// Sets whether the transport is opened again when it fails.
+    pub fn set_auto_reconnect(&self, enabled: bool) {
+        self.auto_reconnect.store(enabled, Ordering::Relaxed);
+    }
+
+    /// Gets a handle for sending messages on the connection if there is one.
+    pub fn link(&self) -> Option<Link> {
+        self.connection
+            .lock()
+            .unwrap()
+            .as_ref()
+            .map(|connection| connection.link.clone())
+    }
+
+    /// Closes the connection if there is one.
+    pub fn close(&self, app_handle: &AppHandle) {
+        if let Some(connection) = self.connection.lock().unwrap().take() {
+            connection.close(app_handle);
+        }
+    }
+}
+
+/// Connect to the boat through a serial port.
+///
+/// The port is opened at the `baud_rate` setting if `baud_rate` isn't given. The current
+/// connection is closed before connecting.
+#[tauri::command]
+pub fn connect_serial(
+    app_handle: AppHandle,
+    state: tauri::State<SerialConnection>,
+    port_name: String,
+    baud_rate: Option<u32>,
+) -> Result<(), AppError> {
+    let baud_rate =
+        baud_rate.unwrap_or_else(|| app_handle.state::<SettingsState>().get().baud_rate);
+    if app_handle.state::<ReplayState>().is_running() {
+        return Err(AppError::new(
+            codes::CONNECTION_REPLAY_RUNNING,
+            "Unable to Connect: Stop the Replay First",
+        ));
+    }
+    let mut connection = state.connection.lock().unwrap();
+    if let Some(old) = connection.take() {
+        old.close(&app_handle);
+    }
+
+    match Connection::open_serial(&app_handle, port_name.clone(), baud_rate) {
+        Ok(v) => {
+            *connection = Some(v);
+            Ok(())
+        }
+        Err(e) => {
+            let error = AppError::reason(
+                codes::CONNECTION_OPEN_FAILED,
+                &format!("Unable to Open Port {port_name}"),
+                &e,
+            )
+            .with("port", port_name.as_str());
+            emit_status(
+                &app_handle,
+                SerialStatus::Error {
+                    port: port_name,
+                    message: e,
+                },
+            );
+            Err(error)
+        }
+    }
+}
+
+/// Connect to the boat through TCP, e.g. its WiFi bridge.
+///
+/// The current connection is closed before connecting.
+#[tauri::command]
+pub async fn connect_tcp(app_handle: AppHandle, host: String, port: u16) -> Result<(), TcpError> {
+    log::info!("Connecting to {}:{}", host, port);
+    if app_handle.state::<ReplayState>().is_running() {
+        return Err(TcpError::Io {
+            address: format!("{host}:{port}"),
+            message: String::from("Stop the Replay First"),
+        });
+    }
+    let connecting = {
+        let host = host.clone();
+        tauri::async_runtime::spawn_blocking(move || TcpTransport::connect(&host, port))
+    };
+    let transport = connecting.await.map_err(|e| TcpError::Io {
+        address: format!("{host}:{port}"),
+        message: e.to_string(),
+    })?;
+    let transport = match transport {
+        Ok(v) => v,
+        Err(e) => {
+            emit_status(
+                &app_handle,
+                SerialStatus::Error {
+                    port: format!("{host}:{port}"),
+                    message: e.to_string(),
+                },
+            );
+            return Err(e);
+        }
+    };
+
+    let state = app_handle.state::<SerialConnection>();
+    let mut connection = state.connection.lock().unwrap();
+    if let Some(old) = connection.take() {
+        old.close(&app_handle);
+    }
+    let name = format!("{host}:{port}");
+    *connection = Some(Connection::start(
+        &app_handle,
+        Endpoint::Tcp { host, port },
+        name,
+        Box::new(transport),
+    ));
+    Ok(())
+}
+
+/// Disconnect from the boat.
+#[tauri::command]
+pub fn disconnect_serial(app_handle: AppHandle, state: tauri::State<SerialConnection>) {
+    log::debug!("Disconnecting Serial");
+    state.close(&app_handle);
+}
+
+/// Disconnect from the boat, through a serial port or TCP.
+#[tauri::command]
+pub fn disconnect(app_handle: AppHandle, state: tauri::State<SerialConnection>) {
+    log::debug!("Disconnecting");
+    state.close(&app_handle);
+}
+
+/// Set whether the connection is opened again when it fails, e.g. when the radio is unplugged.
+///
+/// A serial port is found by its USB device, so it is reconnected even if the OS renames it.
+#[tauri::command]
+pub fn set_auto_reconnect(state: tauri::State<SerialConnection>, enabled: bool) {
+    log::debug!("Setting Auto Reconnect: {}", enabled);
+    state.set_auto_reconnect(enabled);
+}
+
+#[cfg(test)]
+mod tests {
+    use serialport::UsbPortInfo;
+
+    use super::*;
+
+    fn port(name: &str, port_type: SerialPortType) -> serialport::SerialPortInfo {
+        serialport::SerialPortInfo {
+            port_name: name.to_string(),
+            port_type,
+        }
+    }
+
+    #[test]
+    fn usb_port_has_device_details() {
+        let info = SerialPortInfo::from(port(
+            "/dev/ttyUSB0",
+            SerialPortType::UsbPort(UsbPortInfo {
+                vid: 0x0403,
+                pid: 0x6015,
+                serial_number: Some("D30A1B2C".to_string()),
+                manufacturer: Some("FTDI".to_string()),
+                product: None,
+            }),
+        ));
+
+        assert!(info.is_usb());
+        assert_eq!(info.name, "/dev/ttyUSB0");
+        assert_eq!(info.vid, Some(0x0403));
+        assert_eq!(info.pid, Some(0x6015));
+        assert_eq!(info.serial_number.as_deref(), Some("D30A1B2C"));
+        assert_eq!(info.manufacturer.as_deref(), Some("FTDI"));
+        assert_eq!(info.product, None);
+    }
+
+    #[test]
+    fn other_ports_have_no_device_details() {
+        for (port_type, expected) in [
+            (SerialPortType::PciPort, "pci"),
+            (SerialPortType::BluetoothPort, "bluetooth"),
+            (SerialPortType::Unknown, "unknown"),
+        ] {
+            let info = SerialPortInfo::from(port("/dev/ttyS0", port_type));
+
+            assert!(!info.is_usb());
+            assert_eq!(info.port_type, expected);
+            assert_eq!(info.vid, None);
+            assert_eq!(info.pid, None);
+            assert_eq!(info.manufacturer, None);
+        }
+    }
+
+    #[test]
+    fn usb_port_serializes_for_frontend() {
+        let info = SerialPortInfo::from(port(
+            "COM3",
+            SerialPortType::UsbPort(UsbPortInfo {
+                vid: 0x10c4,
+                pid: 0xea60,
+                serial_number: None,
+                manufacturer: None,
+                product: Some("CP2102".to_string()),
+            }),
+        ));
+
+        assert_eq!(
+            serde_json::to_value(info).unwrap(),
+            serde_json::json!({
+                "name": "COM3",
+                "port_type": "usb",
+                "vid": 0x10c4,
+                "pid": 0xea60,
+                "serial_number": null,
+                "manufacturer": null,
+                "product": "CP2102",
+            })
+        );
+    }
+
+    #[test]
+    fn status_serializes_with_tag() {
+        let connected = SerialStatus::Connected {
+            port: "COM3".to_string(),
+            baud_rate: Some(57600),
+        };
+        let error = SerialStatus::Error {
+            port: "COM3".to_string(),
+            message: "Access denied".to_string(),
+        };
+
+        assert_eq!(
+            serde_json::to_value(connected).unwrap(),
+            serde_json::json!({"status": "connected", "port": "COM3", "baud_rate": 57600})
+        );
+        assert_eq!(
+            serde_json::to_value(error).unwrap(),
+            serde_json::json!({"status": "error", "port": "COM3", "message": "Access denied"})
+        );
+    }
+
+    /// Creates a link to a port that isn't opened, leaving the frames in the queue.
+    fn link() -> Link {
+        Link {
+            port_name: Arc::new(Mutex::new(String::from("COM3"))),
+            queue: Arc::new(WriteQueue::default()),
+            pending: PendingReplies::default(),
+            sequence: Arc::new(AtomicU32::new(0)),
+            stop: Arc::new(AtomicBool::new(false)),
+            crc: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    /// Creates a packet of `data` to queue.
+    fn packet(data: Vec<u8>) -> Packet {
+        Packet {
+            version: String::from("0.1.0"),
+            r#type: packet_type::ACK,
+            data,
+        }
+    }
+
+    #[test]
+    fn urgent_frames_are_written_first() {
+        let link = link();
+        let ack = |sequence| Ack { sequence };
+
+        link.send(packet_type::ACK, &ack(1), Priority::Normal)
+            .unwrap();
+        link.send(packet_type::ACK, &ack(2), Priority::Normal)
+            .unwrap();
+        link.send(packet_type::ACK, &ack(3), Priority::Urgent)
+            .unwrap();
+
+        let mut frames = link.queue.frames.lock().unwrap();
+        let order: Vec<_> = std::iter::from_fn(|| frames.pop())
+            .map(|(_, frame)| Ack::decode(&*frame.data).unwrap().sequence)
+            .collect();
+        assert_eq!(order, vec![3, 1, 2]);
+    }
+
+    #[test]
+    fn request_returns_reply_from_boat() {
+        let link = link();
+        let boat = {
+            let link = link.clone();
+            std::thread::spawn(move || {
+                // Rejects the first message and accepts the second
+                for reply in [Reply::Nack(String::from("Busy")), Reply::Ack] {
+                    let frame = loop {
+                        if let Some((_, frame)) = link.queue.frames.lock().unwrap().pop() {
+                            break frame;
+                        }
+                        std::thread::sleep(Duration::from_millis(1));
+                    };
+                    let sequence = Ack::decode(&*frame.data).unwrap().sequence;
+                    handle_reply(&link, sequence, reply);
+                }
+            })
+        };
+        let request = |sequence| {
+            link.request(
+                packet_type::ACK,
+                &Ack { sequence },
+                sequence,
+                Priority::Urgent,
+                Duration::from_secs(5),
+                0,
+            )
+        };
+
+        assert_eq!(
+            request(link.next_sequence()),
+            Err(RequestError::Rejected(String::from("Busy")))
+        );
+        assert_eq!(request(link.next_sequence()), Ok(()));
+        boat.join().unwrap();
+        assert!(link.pending.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn request_without_reply_is_sent_again() {
+        let link = link();
+
+        let result = link.request(
+            packet_type::ACK,
+            &Ack { sequence: 7 },
+            7,
+            Priority::Urgent,
+            Duration::from_millis(10),
+            3,
+        );
+
+        assert_eq!(result, Err(RequestError::Timeout { attempts: 4 }));
+        assert_eq!(link.queue.frames.lock().unwrap().urgent.len(), 4);
+        assert!(link.pending.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn closed_link_does_not_send() {
+        let link = link();
+        link.shutdown();
+
+        let result = link.request(
+            packet_type::ACK,
+            &Ack { sequence: 0 },
+            0,
+            Priority::Urgent,
+            Duration::from_millis(10),
+            3,
+        );
+
+        assert_eq!(result, Err(RequestError::NotConnected));
+        assert!(link.queue.frames.lock().unwrap().pop().is_none());
+    }
+
+    #[test]
+    fn full_queue_drops_oldest_normal_frames() {
+        let mut frames = Frames::default();
+        frames.push(Priority::Urgent, packet(vec![0]));
+        for i in 1..MAX_QUEUED_FRAMES {
+            frames.push(Priority::Normal, packet(vec![i as u8]));
+        }
+        assert_eq!(frames.dropped, 0);
+
+        frames.push(Priority::Normal, packet(vec![1, 0]));
+        frames.push(Priority::Urgent, packet(vec![2, 0]));
+
+        assert_eq!(frames.dropped, 2);
+        assert_eq!(frames.urgent, [packet(vec![0]), packet(vec![2, 0])]);
+        assert_eq!(frames.normal.len(), MAX_QUEUED_FRAMES - 2);
+        assert_eq!(frames.normal.front(), Some(&packet(vec![3])));
+        assert_eq!(frames.normal.back(), Some(&packet(vec![1, 0])));
+    }
+
+    #[test]
+    fn full_queue_of_urgent_frames_drops_oldest() {
+        let mut frames = Frames::default();
+        for i in 0..MAX_QUEUED_FRAMES {
+            frames.push(Priority::Urgent, packet(vec![i as u8]));
+        }
+
+        frames.push(Priority::Normal, packet(vec![1, 0]));
+
+        assert_eq!(frames.dropped, 1);
+        assert_eq!(frames.urgent.front(), Some(&packet(vec![1])));
+        assert_eq!(frames.normal, [packet(vec![1, 0])]);
+    }
+
+    #[test]
+    fn frame_not_written_is_written_first() {
+        let mut frames = Frames::default();
+        frames.push(Priority::Normal, packet(vec![1]));
+        frames.push(Priority::Normal, packet(vec![2]));
+
+        let (priority, frame) = frames.pop().unwrap();
+        frames.unpop(priority, frame);
+
+        assert_eq!(frames.pop(), Some((Priority::Normal, packet(vec![1]))));
+        assert_eq!(frames.pop(), Some((Priority::Normal, packet(vec![2]))));
+        assert_eq!(frames.pop(), None);
+    }
+
+    #[test]
+    fn reconnect_delay_doubles_up_to_limit() {
+        let delays: Vec<_> = std::iter::successors(Some(INITIAL_RECONNECT_DELAY), |delay| {
+            Some(next_reconnect_delay(*delay))
+        })
+        .take(8)
+        .map(|delay| delay.as_secs())
+        .collect();
+
+        assert_eq!(delays, [1, 2, 4, 8, 16, 30, 30, 30]);
+    }
+}
@@ -0,0 +1,344 @@
+//! Comparison of two surveys of the same water body, e.g. to see what changed between monthly
+//! surveys of a pond.
+//!
+//! Both datasets are binned into the same grid as the heatmap. The grid is anchored to a whole
+//! degree instead of the bounding box of either dataset, so the cells line up between the
+//! datasets and between comparisons.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use geo_types::{Coord, LineString, Polygon};
+use geojson::{Feature, FeatureCollection, GeoJson, Geometry, Value};
+use serde::Serialize;
+use serde_json::{json, Map};
+
+use crate::{
+    data::BoatData,
+    errors::AppError,
+    geometry::Projection,
+    heatmap::{invalid_cell_size, Grid},
+};
+
+/// Whether a cell has readings in both datasets.
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+enum CellStatus {
+    /// The cell has readings in both datasets.
+    Both,
+    /// The cell only has readings in the first dataset.
+    OnlyA,
+    /// The cell only has readings in the second dataset.
+    OnlyB,
+}
+
+/// Statistics of the differences of the cells, in degrees Celsius.
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct ComparisonSummary {
+    /// The number of cells with readings in both datasets.
+    compared_cells: usize,
+    /// The number of cells only with readings in the first dataset.
+    only_a_cells: usize,
+    /// The number of cells only with readings in the second dataset.
+    only_b_cells: usize,
+    /// The mean difference of the compared cells.
+    mean_difference: Option<f64>,
+    /// The mean difference of the cells that warmed.
+    mean_warming: Option<f64>,
+    /// The largest difference of the cells that warmed.
+    max_warming: Option<f64>,
+    /// The mean difference of the cells that cooled, negative.
+    mean_cooling: Option<f64>,
+    /// The largest cooling of the cells that cooled, negative.
+    max_cooling: Option<f64>,
+}
+
+impl ComparisonSummary {
+    /// Summarizes the cells compared.
+    fn new(cells: &[(CellStatus, Option<f64>)]) -> Self {
+        let count = |status| cells.iter().filter(|(s, _)| *s == status).count();
+        let differences: Vec<f64> = cells.iter().filter_map(|(_, d)| *d).collect();
+        let warming: Vec<f64> = differences.iter().copied().filter(|d| *d > 0.0).collect();
+        let cooling: Vec<f64> = differences.iter().copied().filter(|d| *d < 0.0).collect();
+        Self {
+            compared_cells: count(CellStatus::Both),
+            only_a_cells: count(CellStatus::OnlyA),
+            only_b_cells: count(CellStatus::OnlyB),
+            mean_difference: mean(&differences),
+            mean_warming: mean(&warming),
+            max_warming: warming.iter().copied().reduce(f64::max),
+            mean_cooling: mean(&cooling),
+            max_cooling: cooling.iter().copied().reduce(f64::min),
+        }
+    }
+}
+
+/// The differences between two datasets.
+#[derive(Debug, Serialize)]
+pub struct ComparisonResult {
+    /// A polygon feature for each cell with the `layer`, the `status` of the cell, the mean
+    /// temperatures `a` and `b` and their `difference`, b minus a. Cells only with readings in
+    /// one dataset have no difference.
+    cells: GeoJson,
+    /// The statistics of all the cells.
+    summary: ComparisonSummary,
+    /// The statistics of the cells of each layer.
+    layers: BTreeMap<String, ComparisonSummary>,
+}
+
+/// Gets the mean of the values, `None` if there are none.
+fn mean(values: &[f64]) -> Option<f64> {
+    (!values.is_empty()).then(|| values.iter().sum::<f64>() / values.len() as f64)
+}
+
+/// Creates the projection of the grid, centered on the whole degree below the mean coordinate
+/// of the readings.
+///
+/// Returns `None` if there are no readings with a coordinate.
+fn anchor(a: &BoatData, b: &BoatData) -> Option<Projection> {
+    let coords: Vec<Coord<f64>> = a
+        .features()
+        .iter()
+        .chain(b.features())
+        .map(|feature| Coord::from(feature.geometry()))
+        .filter(|coord| coord.x.is_finite() && coord.y.is_finite())
+        .collect();
+    if coords.is_empty() {
+        return None;
+    }
+    let len = coords.len() as f64;
+    Some(Projection::at(Coord {
+        x: (coords.iter().map(|c| c.x).sum::<f64>() / len).floor(),
+        y: (coords.iter().map(|c| c.y).sum::<f64>() / len).floor(),
+    }))
+}
+
+/// Gets the mean temperature of each cell of a grid by its position.
+fn cell_values(grid: Option<Grid>) -> BTreeMap<(i64, i64), f64> {
+    let mut values = BTreeMap::new();
+    if let Some(grid) = grid {
+        let (columns, rows) = grid.size();
+        for row in 0..rows {
+            for column in 0..columns {
+                if let Some(value) = grid.value(column, row) {
+                    values.insert(grid.cell_position(column, row), value);
+                }
+            }
+        }
+    }
+    values
+}
+
+/// Creates the polygon of the cell at a position.
+fn cell_polygon(projection: &Projection, cell_size: f64, (x, y): (i64, i64)) -> Polygon<f64> {
+    let corner = |dx: i64, dy: i64| {
+        projection.inverse([(x + dx) as f64 * cell_size, (y + dy) as f64 * cell_size])
+    };
+    let ring = LineString::from(vec![
+        corner(0, 0),
+        corner(1, 0),
+        corner(1, 1),
+        corner(0, 1),
+        corner(0, 0),
+    ]);
+    Polygon::new(ring, vec![])
+}
+
+/// Compare the mean temperatures of two datasets, e.g. monthly surveys of the same pond.
+///
+/// The readings of each layer are binned into square cells of `cell_size_m` meters like the
+/// heatmap. The difference of a cell is the mean temperature of `b` minus the mean
+/// temperature of `a`. Cells with readings in only one dataset are flagged by their `status`
+/// instead of having a difference.
+#[tauri::command]
+pub fn compare_datasets(
+    a: BoatData,
+    b: BoatData,
+    cell_size_m: f64,
+) -> Result<ComparisonResult, AppError> {
+    log::debug!("Comparing Datasets with Cell Size {}m", cell_size_m);
+    if !cell_size_m.is_finite() || cell_size_m <= 0.0 {
+        return Err(invalid_cell_size(cell_size_m));
+    }
+
+    let mut layers = a.layers();
+    for layer in b.layers() {
+        if !layers.contains(&layer) {
+            layers.push(layer);
+        }
+    }
+
+    let mut features = vec![];
+    let mut all_cells = vec![];
+    let mut summaries = BTreeMap::new();
+    if let Some(projection) = anchor(&a, &b) {
+        for layer in layers {
+            let grid = |data: &BoatData| -> Result<BTreeMap<(i64, i64), f64>, AppError> {
+                Ok(cell_values(Grid::aligned(
+                    data,
+                    &layer,
+                    cell_size_m,
+                    projection,
+                )?))
+            };
+            let (a_values, b_values) = (grid(&a)?, grid(&b)?);
+            let positions: BTreeSet<_> = a_values.keys().chain(b_values.keys()).collect();
+
+            let mut cells = vec![];
+            for position in positions {
+                let (a_value, b_value) = (a_values.get(position), b_values.get(position));
+                let (status, difference) = match (a_value, b_value) {
+                    (Some(before), Some(after)) => (CellStatus::Both, Some(after - before)),
+                    (Some(_), None) => (CellStatus::OnlyA, None),
+                    _ => (CellStatus::OnlyB, None),
+                };
+                cells.push((status, difference));
+
+                let mut properties = Map::new();
+                properties.insert(String::from("layer"), json!(layer.to_string()));
+                properties.insert(String::from("status"), json!(status));
+                properties.insert(String::from("a"), json!(a_value));
+                properties.insert(String::from("b"), json!(b_value));
+                properties.insert(String::from("difference"), json!(difference));
+                let polygon = cell_polygon(&projection, cell_size_m, *position);
+                features.push(Feature {
+                    bbox: None,
+                    geometry: Some(Geometry::new(Value::from(&polygon))),
+                    id: None,
+                    properties: Some(properties),
+                    foreign_members: None,
+                });
+            }
+            summaries.insert(layer.to_string(), ComparisonSummary::new(&cells));
+            all_cells.extend(cells);
+        }
+    }
+
+    let summary = ComparisonSummary::new(&all_cells);
+    log::info!(
+        "Compared {} Cells, {} only in A and {} only in B",
+        summary.compared_cells,
+        summary.only_a_cells,
+        summary.only_b_cells
+    );
+    Ok(ComparisonResult {
+        cells: GeoJson::from(FeatureCollection {
+            bbox: None,
+            features,
+            foreign_members: None,
+        }),
+        summary,
+        layers: summaries,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeZone, Utc};
+    use geo_types::Point;
+
+    use crate::data::{BoatDataFeature, Layer};
+
+    use super::*;
+
+    /// The coordinate `east` meters east of the corner of the grid.
+    fn east_of_corner(east: f64) -> Coord<f64> {
+        Projection::at(Coord { x: 101.0, y: 3.0 }).inverse([east + 0.5, 0.5])
+    }
+
+    fn reading(temperature: f64, layer: Layer, east: f64) -> BoatDataFeature {
+        BoatDataFeature::new(
+            temperature,
+            0.5,
+            layer,
+            Utc.timestamp_opt(1_715_495_400, 0).unwrap(),
+            Point::from(east_of_corner(east)),
+        )
+    }
+
+    fn data(features: Vec<BoatDataFeature>) -> BoatData {
+        BoatData::new(BoatData::default().version().to_string(), features)
+    }
+
+    fn properties(result: &ComparisonResult) -> Vec<Map<String, serde_json::Value>> {
+        match &result.cells {
+            GeoJson::FeatureCollection(collection) => collection
+                .features
+                .iter()
+                .map(|feature| feature.properties.clone().unwrap())
+                .collect(),
+            other => panic!("Expected a FeatureCollection: {other:?}"),
+        }
+    }
+
+    #[test]
+    fn cells_of_both_datasets_are_compared() {
+        let a = data(vec![
+            reading(20.0, Layer::Surface, 0.0),
+            reading(22.0, Layer::Surface, 1.0),
+        ]);
+        let b = data(vec![
+            reading(24.0, Layer::Surface, 2.0),
+            reading(18.0, Layer::Surface, 25.0),
+        ]);
+        let c = data(vec![reading(19.0, Layer::Surface, 25.0)]);
+
+        let result = compare_datasets(a, b.clone(), 10.0).unwrap();
+        let cells = properties(&result);
+        assert_eq!(cells.len(), 2);
+        assert_eq!(cells[0]["status"], json!("both"));
+        assert_eq!(cells[0]["difference"], json!(3.0));
+        assert_eq!(cells[1]["status"], json!("only_b"));
+        assert!(cells[1]["difference"].is_null());
+        assert_eq!(result.summary.compared_cells, 1);
+        assert_eq!(result.summary.only_b_cells, 1);
+        assert_eq!(result.summary.max_warming, Some(3.0));
+
+        let result = compare_datasets(b, c, 10.0).unwrap();
+        let cells = properties(&result);
+        assert_eq!(cells[0]["status"], json!("only_a"));
+        assert_eq!(cells[1]["difference"], json!(1.0));
+    }
+
+    #[test]
+    fn differences_are_b_minus_a() {
+        let warm = data(vec![reading(25.0, Layer::Surface, 0.0)]);
+        let cold = data(vec![reading(21.0, Layer::Surface, 0.0)]);
+
+        let result = compare_datasets(warm, cold, 10.0).unwrap();
+        assert_eq!(properties(&result)[0]["difference"], json!(-4.0));
+        assert_eq!(result.summary.max_cooling, Some(-4.0));
+        assert_eq!(result.summary.mean_warming, None);
+    }
+
+    #[test]
+    fn layers_are_compared_separately() {
+        let a = data(vec![
+            reading(20.0, Layer::Surface, 0.0),
+            reading(15.0, Layer::SeaBed, 0.0),
+        ]);
+        let b = data(vec![reading(21.0, Layer::Surface, 0.0)]);
+
+        let result = compare_datasets(a, b, 10.0).unwrap();
+        assert_eq!(result.layers["surface"].compared_cells, 1);
+        assert_eq!(result.layers["sea bed"].only_a_cells, 1);
+        assert_eq!(result.summary.compared_cells, 1);
+        assert_eq!(result.summary.only_a_cells, 1);
+    }
+
+    #[test]
+    fn invalid_cell_sizes_are_rejected() {
+        assert_eq!(
+            compare_datasets(BoatData::default(), BoatData::default(), 0.0).err(),
+            Some(invalid_cell_size(0.0))
+        );
+        assert!(compare_datasets(BoatData::default(), BoatData::default(), f64::NAN).is_err());
+    }
+
+    #[test]
+    fn empty_datasets_have_no_cells() {
+        let result = compare_datasets(BoatData::default(), BoatData::default(), 10.0).unwrap();
+        assert!(properties(&result).is_empty());
+        assert_eq!(result.summary.compared_cells, 0);
+        assert_eq!(result.summary.mean_difference, None);
+    }
+}
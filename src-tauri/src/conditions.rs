@@ -0,0 +1,504 @@
+//! The weather when the data is collected, as the air temperature, wind and cloud cover change
+//! the temperature of the water near the surface.
+//!
+//! The conditions are fetched from the weather API in the settings for the centroid of the
+//! mission, or entered by the operator when offline. They are stored in the metadata of the
+//! active dataset, so they are written in the reports and CSV exports.
+
+use std::{ops::RangeInclusive, time::Duration};
+
+use chrono::{DateTime, Utc};
+use geo::Centroid;
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager};
+
+use crate::{
+    app_data::AppData,
+    errors::{codes, AppError},
+    path::CurrentPath,
+    settings::SettingsState,
+};
+
+/// The time to wait for the weather API.
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The weather API the conditions are fetched from.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct WeatherApiSettings {
+    /// The URL of the current weather, where `{lat}` and `{lng}` are replaced with the position
+    /// and `{key}` with the API key.
+    ///
+    /// The response must be the current weather of OpenWeatherMap in metric units, e.g.
+    /// `https://api.openweathermap.org/data/2.5/weather?lat={lat}&lon={lng}&units=metric&appid={key}`.
+    pub url_template: String,
+    /// The key of the weather API.
+    pub api_key: String,
+}
+
+impl WeatherApiSettings {
+    /// Checks the settings are usable.
+    pub fn validate(&self) -> Result<(), String> {
+        let url = self.url_template.trim();
+        if !url.starts_with("http://") && !url.starts_with("https://") {
+            return Err(format!("{} is not an HTTP URL", self.url_template));
+        }
+        if !url.contains("{lat}") || !url.contains("{lng}") {
+            return Err(String::from("The URL must have {lat} and {lng}"));
+        }
+        Ok(())
+    }
+
+    /// Gets the URL of the current weather at a position.
+    fn url(&self, lat: f64, lng: f64) -> String {
+        self.url_template
+            .trim()
+            .replace("{lat}", &lat.to_string())
+            .replace("{lng}", &lng.to_string())
+            .replace("{key}", self.api_key.trim())
+    }
+}
+
+/// Where the conditions come from.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ConditionsSource {
+    /// Fetched from the weather API.
+    Api,
+    /// Entered by the operator.
+    Manual,
+}
+
+impl Default for ConditionsSource {
+    fn default() -> Self {
+        Self::Manual
+    }
+}
+
+/// The weather when the data is collected.
+///
+/// Values that weren't observed are `None`.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct Conditions {
+    /// The time the conditions were observed, the current time if it isn't given.
+    #[serde(default = "Utc::now")]
+    pub time: DateTime<Utc>,
+    /// The air temperature in degrees Celsius.
+    pub air_temperature: Option<f64>,
+    /// The wind speed in metres per second.
+    pub wind_speed: Option<f64>,
+    /// The direction the wind blows from in degrees clockwise from north.
+    pub wind_direction: Option<f64>,
+    /// The cloud cover in percent.
+    pub cloud_cover: Option<f64>,
+    /// Where the conditions come from.
+    #[serde(default)]
+    pub source: ConditionsSource,
+}
+
+impl Conditions {
+    /// Checks the values are in range.
+    fn validate(&self) -> Result<(), String> {
+        let check = |name: &str, value: Option<f64>, range: RangeInclusive<f64>| match value {
+            Some(v) if !v.is_finite() || !range.contains(&v) => Err(format!(
+                "{name} {v} is not between {} and {}",
+                range.start(),
+                range.end()
+            )),
+            _ => Ok(()),
+        };
+        check("Air Temperature", self.air_temperature, -90.0..=60.0)?;
+        check("Wind Speed", self.wind_speed, 0.0..=120.0)?;
+        check("Wind Direction", self.wind_direction, 0.0..=360.0)?;
+        check("Cloud Cover", self.cloud_cover, 0.0..=100.0)
+    }
+
+    /// Gets the names and values of the conditions observed, to be written in exports.
+    pub fn rows(&self) -> Vec<(&'static str, String)> {
+        let mut rows = vec![];
+        if let Some(temperature) = self.air_temperature {
+            rows.push(("Air Temperature", format!("{temperature} C")));
+        }
+        match (self.wind_speed, self.wind_direction) {
+            (Some(speed), Some(direction)) => {
+                rows.push(("Wind", format!("{speed} m/s from {direction} degrees")))
+            }
+            (Some(speed), None) => rows.push(("Wind", format!("{speed} m/s"))),
+            (None, Some(direction)) => rows.push(("Wind", format!("From {direction} degrees"))),
+            (None, None) => {}
+        }
+        if let Some(cloud_cover) = self.cloud_cover {
+            rows.push(("Cloud Cover", format!("{cloud_cover}%")));
+        }
+        if !rows.is_empty() {
+            let source = match self.source {
+                ConditionsSource::Api => "Weather API",
+                ConditionsSource::Manual => "Entered by the Operator",
+            };
+            rows.push((
+                "Conditions Observed",
+                format!("{} ({source})", self.time.format("%Y-%m-%d %H:%M UTC")),
+            ));
+        }
+        rows
+    }
+}
+
+/// The parts of the current weather of OpenWeatherMap that are used.
+#[derive(Debug, Deserialize)]
+struct WeatherResponse {
+    /// The time of the observation in seconds since the Unix epoch.
+    dt: Option<i64>,
+    main: Option<MainResponse>,
+    wind: Option<WindResponse>,
+    clouds: Option<CloudsResponse>,
+}
+
+/// The temperature of the current weather.
+#[derive(Debug, Deserialize)]
+struct MainResponse {
+    temp: Option<f64>,
+}
+
+/// The wind of the current weather.
+#[derive(Debug, Deserialize)]
+struct WindResponse {
+    speed: Option<f64>,
+    deg: Option<f64>,
+}
+
+/// The clouds of the current weather.
+#[derive(Debug, Deserialize)]
+struct CloudsResponse {
+    all: Option<f64>,
+}
+
+/// Parses the response of the weather API.
+fn parse_conditions(content: &str) -> Result<Conditions, String> {
+    let response: WeatherResponse =
+        serde_json::from_str(content).map_err(|e| format!("Invalid Weather API Response: {e}"))?;
+    let conditions = Conditions {
+        time: response
+            .dt
+            .and_then(|dt| DateTime::<Utc>::from_timestamp(dt, 0))
+            .unwrap_or_else(Utc::now),
+        air_temperature: response.main.and_then(|main| main.temp),
+        wind_speed: response.wind.as_ref().and_then(|wind| wind.speed),
+        wind_direction: response.wind.as_ref().and_then(|wind| wind.deg),
+        cloud_cover: response.clouds.and_then(|clouds| clouds.all),
+        source: ConditionsSource::Api,
+    };
+    conditions
+        .validate()
+        .map_err(|e| format!("Invalid Weather API Response: {e}"))?;
+    Ok(conditions)
+}
+
+/// Fetches the current conditions at a position from the weather API.
+fn fetch(api: &WeatherApiSettings, lat: f64, lng: f64) -> Result<Conditions, AppError> {
+    // The URL isn't logged or returned as it has the API key
+    log::info!("Fetching Conditions at {}, {}", lat, lng);
+    let api_failed = |reason: String| {
+        AppError::new(codes::CONDITIONS_API_FAILED, reason.clone()).with("reason", reason)
+    };
+    let response = ureq::get(&api.url(lat, lng))
+        .timeout(TIMEOUT)
+        .call()
+        .map_err(|e| match e {
+            ureq::Error::Status(status, _) => format!("Weather API Error: Status {status}"),
+            ureq::Error::Transport(e) => format!("Unable to Reach the Weather API: {}", e.kind()),
+        })
+        .map_err(api_failed)?;
+    let content = response
+        .into_string()
+        .map_err(|e| api_failed(format!("Invalid Weather API Response: {e}")))?;
+    parse_conditions(&content).map_err(api_failed)
+}
+
+/// Gets the latitude and longitude of the centroid of the current mission.
+fn mission_centroid(app_handle: &AppHandle) -> Result<(f64, f64), AppError> {
+    let path = app_handle.state::<CurrentPath>();
+    let centroid = path.path.lock().unwrap().path().centroid();
+    centroid.map(|point| (point.y(), point.x())).ok_or_else(|| {
+        AppError::new(
+            codes::CONDITIONS_NO_PATH,
+            "No Mission Path to Capture the Conditions of",
+        )
+    })
+}
+
+/// Stores the conditions in the metadata of the active dataset.
+fn store(app_handle: &AppHandle, conditions: &Conditions) -> Result<(), AppError> {
+    app_handle.state::<AppData>().update(app_handle, |data| {
+        let mut metadata = data.metadata();
+        metadata.conditions = Some(conditions.clone());
+        data.set_metadata(&metadata);
+        Ok(())
+    })
+}
+
+/// Fetch the current conditions at the centroid of the mission from the weather API in the
+/// settings, storing them in the metadata of the active dataset.
+///
+/// Fails when offline or if no weather API is set, in which case the conditions can be entered
+/// with [`set_conditions`]. The metadata is saved with the boat data by the autosave.
+#[tauri::command]
+pub async fn capture_conditions(app_handle: AppHandle) -> Result<Conditions, AppError> {
+    log::debug!("Capturing Conditions");
+    let api = app_handle
+        .state::<SettingsState>()
+        .get()
+        .weather_api
+        .ok_or_else(|| {
+            AppError::new(
+                codes::CONDITIONS_NO_API,
+                "No Weather API is Set in the Settings",
+            )
+        })?;
+    let (lat, lng) = mission_centroid(&app_handle)?;
+    let conditions = tauri::async_runtime::spawn_blocking(move || fetch(&api, lat, lng))
+        .await
+        .map_err(|e| AppError::from(e.to_string()))??;
+    store(&app_handle, &conditions)?;
+    Ok(conditions)
+}
+
+/// Set the conditions entered by the operator, e.g. when the weather API can't be reached,
+/// storing them in the metadata of the active dataset.
+///
+/// The metadata is saved with the boat data by the autosave.
+#[tauri::command]
+pub fn set_conditions(
+    app_handle: AppHandle,
+    conditions: Conditions,
+) -> Result<Conditions, AppError> {
+    log::debug!("Setting Conditions");
+    let conditions = Conditions {
+        source: ConditionsSource::Manual,
+        ..conditions
+    };
+    conditions
+        .validate()
+        .map_err(|e| AppError::reason(codes::CONDITIONS_INVALID, "Invalid Conditions", e))?;
+    store(&app_handle, &conditions)?;
+    Ok(conditions)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::{BufRead, BufReader, Write},
+        net::TcpListener,
+        thread::JoinHandle,
+    };
+
+    use chrono::TimeZone;
+    use serde_json::Value;
+
+    use super::*;
+
+    /// A current weather response of OpenWeatherMap, trimmed to the parts that are used.
+    const RESPONSE: &str = r#"{
+        "coord": { "lon": 101.5, "lat": 3.1 },
+        "main": { "temp": 31.2, "humidity": 70 },
+        "wind": { "speed": 3.6, "deg": 220 },
+        "clouds": { "all": 40 },
+        "dt": 1717207200
+    }"#;
+
+    /// Serves one request with `status` and `body`, returning the settings of a weather API at
+    /// the server and the request line received.
+    fn serve(status: &str, body: &'static str) -> (WeatherApiSettings, JoinHandle<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let status = status.to_string();
+        let server = std::thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream);
+            let mut request = String::new();
+            reader.read_line(&mut request).unwrap();
+            // Reading the headers up to the blank line
+            let mut line = String::new();
+            while reader.read_line(&mut line).unwrap() > 2 {
+                line.clear();
+            }
+            let mut stream = reader.into_inner();
+            write!(
+                stream,
+                "HTTP/1.1 {status}\r\nContent-Type: application/json\r\n\
+                 Content-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                body.len()
+            )
+            .unwrap();
+            request.trim_end().to_string()
+        });
+        let api = WeatherApiSettings {
+            url_template: format!(
+                "http://127.0.0.1:{port}/weather?lat={{lat}}&lon={{lng}}&appid={{key}}"
+            ),
+            api_key: String::from("secret-key"),
+        };
+        (api, server)
+    }
+
+    /// Gets the code and message of an error.
+    fn code_and_message(error: AppError) -> (Value, Value) {
+        let error = serde_json::to_value(error).unwrap();
+        (error["code"].clone(), error["message"].clone())
+    }
+
+    #[test]
+    fn fetch_maps_the_current_weather() {
+        let (api, server) = serve("200 OK", RESPONSE);
+
+        let conditions = fetch(&api, 3.1, 101.5).unwrap();
+
+        assert_eq!(
+            server.join().unwrap(),
+            "GET /weather?lat=3.1&lon=101.5&appid=secret-key HTTP/1.1"
+        );
+        assert_eq!(
+            conditions,
+            Conditions {
+                time: Utc.with_ymd_and_hms(2024, 6, 1, 2, 0, 0).unwrap(),
+                air_temperature: Some(31.2),
+                wind_speed: Some(3.6),
+                wind_direction: Some(220.0),
+                cloud_cover: Some(40.0),
+                source: ConditionsSource::Api,
+            }
+        );
+    }
+
+    #[test]
+    fn fetch_keeps_missing_values_empty() {
+        let (api, server) = serve("200 OK", r#"{ "main": { "temp": 12.0 } }"#);
+
+        let conditions = fetch(&api, 3.1, 101.5).unwrap();
+        server.join().unwrap();
+
+        assert_eq!(conditions.air_temperature, Some(12.0));
+        assert_eq!(conditions.wind_speed, None);
+        assert_eq!(conditions.wind_direction, None);
+        assert_eq!(conditions.cloud_cover, None);
+    }
+
+    #[test]
+    fn fetch_reports_error_statuses_without_the_key() {
+        let (api, server) = serve("401 Unauthorized", r#"{ "cod": 401 }"#);
+
+        let error = fetch(&api, 3.1, 101.5).unwrap_err();
+        server.join().unwrap();
+
+        let (code, message) = code_and_message(error);
+        assert_eq!(code, codes::CONDITIONS_API_FAILED);
+        assert_eq!(message, "Weather API Error: Status 401");
+    }
+
+    #[test]
+    fn fetch_rejects_invalid_responses() {
+        let (api, server) = serve("200 OK", "<html>Maintenance</html>");
+        let error = fetch(&api, 3.1, 101.5).unwrap_err();
+        server.join().unwrap();
+        let (code, message) = code_and_message(error);
+        assert_eq!(code, codes::CONDITIONS_API_FAILED);
+        assert!(message
+            .as_str()
+            .unwrap()
+            .starts_with("Invalid Weather API Response: "));
+
+        let (api, server) = serve("200 OK", r#"{ "clouds": { "all": 140 } }"#);
+        let error = fetch(&api, 3.1, 101.5).unwrap_err();
+        server.join().unwrap();
+        assert_eq!(
+            code_and_message(error).1,
+            "Invalid Weather API Response: Cloud Cover 140 is not between 0 and 100"
+        );
+    }
+
+    #[test]
+    fn fetch_reports_unreachable_apis_without_the_key() {
+        let port = {
+            let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+            listener.local_addr().unwrap().port()
+        };
+        let api = WeatherApiSettings {
+            url_template: format!("http://127.0.0.1:{port}/?lat={{lat}}&lon={{lng}}&key={{key}}"),
+            api_key: String::from("secret-key"),
+        };
+
+        let (code, message) = code_and_message(fetch(&api, 3.1, 101.5).unwrap_err());
+
+        assert_eq!(code, codes::CONDITIONS_API_FAILED);
+        let message = message.as_str().unwrap();
+        assert!(message.starts_with("Unable to Reach the Weather API: "));
+        assert!(!message.contains("secret-key"));
+    }
+
+    #[test]
+    fn settings_need_an_http_url_with_the_position() {
+        let api = |url: &str| WeatherApiSettings {
+            url_template: url.to_string(),
+            api_key: String::new(),
+        };
+
+        assert_eq!(
+            api("https://weather.test/?lat={lat}&lon={lng}").validate(),
+            Ok(())
+        );
+        assert_eq!(
+            api("ftp://weather.test/?lat={lat}&lon={lng}").validate(),
+            Err(String::from(
+                "ftp://weather.test/?lat={lat}&lon={lng} is not an HTTP URL"
+            ))
+        );
+        assert_eq!(
+            api("https://weather.test/?q=pond").validate(),
+            Err(String::from("The URL must have {lat} and {lng}"))
+        );
+    }
+
+    #[test]
+    fn rows_list_the_observed_conditions() {
+        let conditions = Conditions {
+            time: Utc.with_ymd_and_hms(2024, 6, 1, 2, 0, 0).unwrap(),
+            air_temperature: Some(31.2),
+            wind_speed: Some(3.6),
+            wind_direction: None,
+            cloud_cover: Some(40.0),
+            source: ConditionsSource::Manual,
+        };
+
+        assert_eq!(
+            conditions.rows(),
+            vec![
+                ("Air Temperature", String::from("31.2 C")),
+                ("Wind", String::from("3.6 m/s")),
+                ("Cloud Cover", String::from("40%")),
+                (
+                    "Conditions Observed",
+                    String::from("2024-06-01 02:00 UTC (Entered by the Operator)")
+                ),
+            ]
+        );
+
+        let empty = Conditions {
+            air_temperature: None,
+            wind_speed: None,
+            cloud_cover: None,
+            ..conditions
+        };
+        assert!(empty.rows().is_empty());
+    }
+
+    #[test]
+    fn manual_conditions_default_to_now() {
+        let conditions: Conditions = serde_json::from_str(
+            r#"{ "air_temperature": 25.0, "wind_speed": null,
+                "wind_direction": null, "cloud_cover": null }"#,
+        )
+        .unwrap();
+
+        assert_eq!(conditions.source, ConditionsSource::Manual);
+        assert!((Utc::now() - conditions.time).num_seconds() < 60);
+    }
+}
@@ -0,0 +1,376 @@
+//! Isotherm lines of boat data, traced by marching squares over the temperature grid.
+
+use std::collections::{HashMap, VecDeque};
+
+use geo_types::LineString;
+use geojson::{Feature, FeatureCollection, GeoJson, Geometry, Value};
+use serde_json::{json, Map};
+
+use crate::{
+    data::{BoatData, Layer},
+    errors::{codes, AppError},
+    heatmap::Grid,
+};
+
+/// The cell size of the grid in meters when it is not specified.
+const DEFAULT_CELL_SIZE: f64 = 10.0;
+
+/// The largest number of temperatures contours are traced at.
+const MAX_LEVELS: usize = 1000;
+
+/// An edge between the centers of two neighbouring cells.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Edge {
+    /// From the cell at the column and row to the next column.
+    Horizontal(usize, usize),
+    /// From the cell at the column and row to the next row.
+    Vertical(usize, usize),
+}
+
+/// Traces the segments of the contour at `level` through each square of cell centers.
+fn segments(grid: &Grid, level: f64) -> Vec<(Edge, Edge)> {
+    let (columns, rows) = grid.size();
+    let mut segments = vec![];
+    for row in 0..rows.saturating_sub(1) {
+        for column in 0..columns.saturating_sub(1) {
+            let corners = [
+                grid.value(column, row),
+                grid.value(column + 1, row),
+                grid.value(column + 1, row + 1),
+                grid.value(column, row + 1),
+            ];
+            // Squares with a cell without data are skipped
+            let [bottom_left, bottom_right, top_right, top_left] = match corners {
+                [Some(a), Some(b), Some(c), Some(d)] => [a, b, c, d],
+                _ => continue,
+            };
+
+            let above = |value: f64| value >= level;
+            let bottom = Edge::Horizontal(column, row);
+            let right = Edge::Vertical(column + 1, row);
+            let top = Edge::Horizontal(column, row + 1);
+            let left = Edge::Vertical(column, row);
+            let crossed: Vec<_> = [
+                (bottom, bottom_left, bottom_right),
+                (right, bottom_right, top_right),
+                (top, top_left, top_right),
+                (left, bottom_left, top_left),
+            ]
+            .into_iter()
+            .filter(|(_, a, b)| above(*a) != above(*b))
+            .map(|(edge, _, _)| edge)
+            .collect();
+
+            match crossed[..] {
+                [a, b] => segments.push((a, b)),
+                [_, _, _, _] => {
+                    // A saddle, separating the corners that differ from the center
+                    let center = (bottom_left + bottom_right + top_right + top_left) / 4.0;
+                    if above(bottom_left) != above(center) {
+                        segments.push((left, bottom));
+                        segments.push((right, top));
+                    } else {
+                        segments.push((bottom, right));
+                        segments.push((top, left));
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+    segments
+}
+
+/// Joins segments sharing edges into lines.
+///
+/// Returns the edges of each line, with the first edge repeated at the end of closed lines.
+fn join(segments: &[(Edge, Edge)]) -> Vec<Vec<Edge>> {
+    let mut by_edge: HashMap<Edge, Vec<usize>> = HashMap::new();
+    for (i, (a, b)) in segments.iter().enumerate() {
+        by_edge.entry(*a).or_default().push(i);
+        by_edge.entry(*b).or_default().push(i);
+    }
+
+    let mut used = vec![false; segments.len()];
+    // Takes the segment continuing from `edge`, returning the edge at its other end
+    let next = |edge: Edge, used: &mut Vec<bool>| {
+        let i = *by_edge.get(&edge)?.iter().find(|&&i| !used[i])?;
+        used[i] = true;
+        let (a, b) = segments[i];
+        Some(if a == edge { b } else { a })
+    };
+
+    let mut lines = vec![];
+    for i in 0..segments.len() {
+        if used[i] {
+            continue;
+        }
+        used[i] = true;
+        let (a, b) = segments[i];
+        let mut line = VecDeque::from(vec![a, b]);
+        while let Some(edge) = next(*line.back().unwrap(), &mut used) {
+            line.push_back(edge);
+            if edge == a {
+                break;
+            }
+        }
+        if line.back() != Some(&a) {
+            while let Some(edge) = next(*line.front().unwrap(), &mut used) {
+                line.push_front(edge);
+            }
+        }
+        lines.push(line.into_iter().collect());
+    }
+    lines
+}
+
+/// Gets the coordinate where the contour at `level` crosses an edge.
+fn crossing(grid: &Grid, edge: Edge, level: f64) -> geo_types::Coord<f64> {
+    let (column, row, next) = match edge {
+        Edge::Horizontal(column, row) => (column, row, (column + 1, row)),
+        Edge::Vertical(column, row) => (column, row, (column, row + 1)),
+    };
+    // Both cells have values as the edge is on a traced square
+    let start = grid.value(column, row).unwrap_or(level);
+    let end = grid.value(next.0, next.1).unwrap_or(level);
+    let t = if start == end {
+        0.5
+    } else {
+        (level - start) / (end - start)
+    };
+    let (x, y) = match edge {
+        Edge::Horizontal(..) => (column as f64 + t, row as f64),
+        Edge::Vertical(..) => (column as f64, row as f64 + t),
+    };
+    // The values are at the centers of the cells
+    grid.coord(x + 0.5, y + 0.5)
+}
+
+/// Generate isotherm lines of boat data at `layer` every `interval` degrees.
+///
+/// The readings are binned into a grid of `cell_size_m` meters, 10 m by default, and gaps are
+/// interpolated from the cells within `idw_radius_m` meters, twice the cell size by default.
+/// Each line is a LineString feature with its `temperature`, and closed contours are closed
+/// rings.
+#[tauri::command]
+pub fn generate_contours(
+    data: BoatData,
+    layer: Layer,
+    interval: f64,
+    cell_size_m: Option<f64>,
+    idw_radius_m: Option<f64>,
+) -> Result<GeoJson, AppError> {
+    log::debug!("Generating Contours of {} every {}", layer, interval);
+    let invalid_interval = |message: String| {
+        AppError::new(codes::CONTOURS_INVALID_INTERVAL, message).with("interval", interval)
+    };
+    if !interval.is_finite() || interval <= 0.0 {
+        return Err(invalid_interval(format!(
+            "Invalid Contour Interval: {interval} must be greater than 0"
+        )));
+    }
+
+    let cell_size = cell_size_m.unwrap_or(DEFAULT_CELL_SIZE);
+    let mut features = vec![];
+    if let Some(mut grid) = Grid::new(&data, layer, cell_size)? {
+        grid.interpolate(idw_radius_m.unwrap_or(2.0 * cell_size))?;
+
+        let (columns, rows) = grid.size();
+        let values: Vec<_> = (0..rows)
+            .flat_map(|row| (0..columns).map(move |column| (column, row)))
+            .filter_map(|(column, row)| grid.value(column, row))
+            .collect();
+        let min = values.iter().copied().fold(f64::INFINITY, f64::min);
+        let max = values.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+        let first = (min / interval).ceil() as i64;
+        let last = (max / interval).floor() as i64;
+        if last - first >= MAX_LEVELS as i64 {
+            return Err(invalid_interval(format!(
+                "Too Many Contours: Interval {interval} would trace more than {MAX_LEVELS} levels"
+            )));
+        }
+
+        for level in (first..=last).map(|i| i as f64 * interval) {
+            for edges in join(&segments(&grid, level)) {
+                let line: LineString<f64> = edges
+                    .into_iter()
+                    .map(|edge| crossing(&grid, edge, level))
+                    .collect();
+                let mut properties = Map::new();
+                properties.insert(String::from("temperature"), json!(level));
+                features.push(Feature {
+                    bbox: None,
+                    geometry: Some(Geometry::new(Value::from(&line))),
+                    id: None,
+                    properties: Some(properties),
+                    foreign_members: None,
+                });
+            }
+        }
+    }
+    log::info!("Generated {} Contour Lines", features.len());
+    Ok(GeoJson::from(FeatureCollection {
+        bbox: None,
+        features,
+        foreign_members: None,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeZone, Utc};
+    use geo_types::{Coord, Point};
+
+    use crate::{data::BoatDataFeature, geometry::Projection};
+
+    use super::*;
+
+    /// The projection the readings are placed with, in meters from the first corner.
+    fn projection() -> Projection {
+        Projection::new(&[Coord { x: 101.5, y: 3.1 }])
+    }
+
+    /// The center of the radial temperature field.
+    const CENTER: [f64; 2] = [105.0, 105.0];
+
+    /// A temperature rising 1 degree every 20 meters from the center.
+    fn radial_temperature(point: [f64; 2]) -> f64 {
+        20.0 + (point[0] - CENTER[0]).hypot(point[1] - CENTER[1]) / 20.0
+    }
+
+    fn reading(point: [f64; 2], temperature: f64) -> BoatDataFeature {
+        BoatDataFeature::new(
+            temperature,
+            0.5,
+            Layer::Surface,
+            Utc.timestamp_opt(1_715_495_400, 0).unwrap(),
+            Point::from(projection().inverse(point)),
+        )
+    }
+
+    /// Readings of the radial field in the middle of each 10 m cell of a 21 by 21 grid.
+    fn radial_field() -> BoatData {
+        // A reading at the corner places the others in the middle of their cells
+        let mut features = vec![reading([0.0, 0.0], radial_temperature([5.0, 5.0]))];
+        for row in 0..21 {
+            for column in 0..21 {
+                let point = [column as f64 * 10.0 + 5.0, row as f64 * 10.0 + 5.0];
+                features.push(reading(point, radial_temperature(point)));
+            }
+        }
+        BoatData::new(BoatData::default().version().to_string(), features)
+    }
+
+    fn features(geojson: GeoJson) -> Vec<Feature> {
+        match geojson {
+            GeoJson::FeatureCollection(collection) => collection.features,
+            other => panic!("Expected a FeatureCollection: {other:?}"),
+        }
+    }
+
+    fn temperature(feature: &Feature) -> f64 {
+        feature.properties.as_ref().unwrap()["temperature"]
+            .as_f64()
+            .unwrap()
+    }
+
+    fn line(feature: &Feature) -> LineString<f64> {
+        match &feature.geometry.as_ref().unwrap().value {
+            Value::LineString(line) => LineString::from(
+                line.iter()
+                    .map(|position| (position[0], position[1]))
+                    .collect::<Vec<_>>(),
+            ),
+            other => panic!("Expected a LineString: {other:?}"),
+        }
+    }
+
+    #[test]
+    fn contours_of_a_radial_field_are_circles() {
+        let features =
+            features(generate_contours(radial_field(), Layer::Surface, 1.0, None, None).unwrap());
+
+        let projection = projection();
+        for level in [21.0, 22.0, 23.0, 24.0] {
+            let contours: Vec<_> = features
+                .iter()
+                .filter(|feature| temperature(feature) == level)
+                .collect();
+            assert_eq!(contours.len(), 1, "Contours at {level}");
+
+            let line = line(contours[0]);
+            assert!(line.is_closed(), "Contour at {level} is not closed");
+            assert!(line.0.len() > 8);
+            let radius = (level - 20.0) * 20.0;
+            for coord in line.coords() {
+                let [x, y] = projection.forward(*coord);
+                let distance = (x - CENTER[0]).hypot(y - CENTER[1]);
+                assert!(
+                    (distance - radius).abs() < 3.0,
+                    "Contour at {level} is {distance}m from the center"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn contours_reaching_the_edge_are_open() {
+        let features =
+            features(generate_contours(radial_field(), Layer::Surface, 1.0, None, None).unwrap());
+
+        let edge: Vec<_> = features
+            .iter()
+            .filter(|feature| temperature(feature) == 26.0)
+            .collect();
+        assert_eq!(edge.len(), 4);
+        assert!(edge.iter().all(|feature| !line(feature).is_closed()));
+    }
+
+    #[test]
+    fn invalid_interval_is_rejected() {
+        for interval in [0.0, -0.5, f64::NAN, f64::INFINITY] {
+            assert!(
+                generate_contours(radial_field(), Layer::Surface, interval, None, None).is_err(),
+                "{interval}"
+            );
+        }
+    }
+
+    #[test]
+    fn too_many_levels_are_refused() {
+        let error = generate_contours(radial_field(), Layer::Surface, 0.001, None, None)
+            .err()
+            .unwrap();
+        assert!(
+            error.to_string().starts_with("Too Many Contours"),
+            "{error}"
+        );
+    }
+
+    #[test]
+    fn contours_without_readings_are_empty() {
+        let features =
+            features(generate_contours(radial_field(), Layer::SeaBed, 1.0, None, None).unwrap());
+        assert!(features.is_empty());
+    }
+
+    #[test]
+    fn segments_sharing_edges_are_joined_into_lines() {
+        let segments = [
+            (Edge::Horizontal(0, 0), Edge::Vertical(1, 0)),
+            (Edge::Horizontal(1, 1), Edge::Vertical(1, 0)),
+            (Edge::Vertical(0, 0), Edge::Horizontal(0, 0)),
+            (Edge::Horizontal(1, 1), Edge::Vertical(0, 1)),
+            (Edge::Vertical(2, 2), Edge::Horizontal(2, 2)),
+        ];
+
+        let lines = join(&segments);
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0].len(), 5);
+        assert_eq!(
+            [lines[0].first(), lines[0].last()],
+            [Some(&Edge::Vertical(0, 0)), Some(&Edge::Vertical(0, 1))]
+        );
+        assert_eq!(lines[1], [Edge::Vertical(2, 2), Edge::Horizontal(2, 2)]);
+    }
+}
@@ -0,0 +1,90 @@
+//! Safety commands controlling the boat.
+
+use std::time::Duration;
+
+use serde::Serialize;
+use tauri::{AppHandle, Manager};
+
+use crate::{
+    communication::{Priority, RequestError, SerialConnection},
+    errors::AppError,
+    messages::{packet_type, Control, ControlCommand},
+};
+
+/// The time to wait for the boat to accept a command.
+const CONTROL_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// The number of times a command is sent again when the boat doesn't reply.
+const CONTROL_RETRIES: u32 = 3;
+
+/// Payload of the `control-failed` event.
+#[derive(Debug, Serialize, Clone)]
+struct ControlFailedPayload {
+    /// The command that failed, e.g. `emergency_stop`.
+    command: &'static str,
+    /// The reason the command failed.
+    error: AppError,
+}
+
+/// Sends a command to the boat ahead of the other messages, waiting for the boat to accept it.
+///
+/// A `control-failed` event is emitted if the boat doesn't accept the command.
+async fn send_command(
+    app_handle: AppHandle,
+    command: ControlCommand,
+    name: &'static str,
+) -> Result<(), AppError> {
+    let result = match app_handle.state::<SerialConnection>().link() {
+        Some(link) => tauri::async_runtime::spawn_blocking(move || {
+            let message = Control {
+                sequence: link.next_sequence(),
+                command: command.into(),
+            };
+            link.request(
+                packet_type::CONTROL,
+                &message,
+                message.sequence,
+                Priority::Urgent,
+                CONTROL_TIMEOUT,
+                CONTROL_RETRIES,
+            )
+            .map_err(AppError::from)
+        })
+        .await
+        .map_err(|e| AppError::from(e.to_string()))
+        .and_then(|v| v),
+        None => Err(AppError::from(RequestError::NotConnected)),
+    };
+
+    match result {
+        Ok(_) => {
+            log::info!("Boat Accepted Command: {}", name);
+            Ok(())
+        }
+        Err(e) => {
+            log::error!("Boat did not Accept Command {}: {}", name, e);
+            let payload = ControlFailedPayload {
+                command: name,
+                error: e.clone(),
+            };
+            if let Err(e) = app_handle.emit_all("control-failed", payload) {
+                log::warn!("Unable to emit control-failed event: {}", e);
+            }
+            Err(e)
+        }
+    }
+}
+
+/// Stop the boat immediately.
+#[tauri::command]
+pub async fn emergency_stop(app_handle: AppHandle) -> Result<(), AppError> {
+    log::warn!("Sending Emergency Stop");
+    send_command(app_handle, ControlCommand::EmergencyStop, "emergency_stop").await
+}
+
+/// Return the boat to where it started.
+#[tauri::command]
+pub async fn return_home(app_handle: AppHandle) -> Result<(), AppError> {
+    log::info!("Sending Return Home");
+    send_command(app_handle, ControlCommand::ReturnHome, "return_home").await
+}
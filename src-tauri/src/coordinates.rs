@@ -0,0 +1,466 @@
+//! Parsing and formatting of coordinates typed by the operator, e.g. the launch site read off a
+//! marine GPS.
+//!
+//! Coordinates can be written in decimal degrees (`45.5231, -122.6765`), degrees and decimal
+//! minutes (`45°31.386'N 122°40.59'W`) or degrees, minutes and seconds
+//! (`45°31'23"N, 122°40'35"W`). The hemisphere letters can be before or after the numbers.
+//!
+//! # Which Number is the Latitude
+//!
+//! The latitude is written first unless the hemisphere letters say otherwise: `N` and `S` mark
+//! the latitude and `E` and `W` mark the longitude wherever they are. Without hemisphere
+//! letters, the first coordinate is the latitude and the second the longitude, as written by GPS
+//! receivers and most maps, even though the files of the app store the longitude first.
+
+use serde::Deserialize;
+
+use crate::errors::{codes, AppError};
+
+/// The symbols allowed between the degrees, minutes and seconds.
+const SEPARATORS: &[char] = &['°', '\'', '"'];
+
+/// How a coordinate is written.
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum CoordinateStyle {
+    /// Signed decimal degrees, e.g. `45.523100, -122.676500`.
+    Decimal,
+    /// Degrees and decimal minutes with hemisphere letters, e.g. `45°31.386'N 122°40.590'W`.
+    DegreesMinutes,
+    /// Degrees, minutes and seconds with hemisphere letters, e.g. `45°31'23.2"N 122°40'35.4"W`.
+    DegreesMinutesSeconds,
+}
+
+/// The axis of a coordinate.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Axis {
+    /// North or south.
+    Latitude,
+    /// East or west.
+    Longitude,
+}
+
+/// A hemisphere letter.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Hemisphere {
+    /// The axis the letter marks.
+    axis: Axis,
+    /// Whether the coordinate is negative, i.e. south or west.
+    negative: bool,
+}
+
+impl Hemisphere {
+    /// Gets the hemisphere of a letter, `None` if it isn't one.
+    fn from_letter(letter: char) -> Option<Self> {
+        let (axis, negative) = match letter.to_ascii_uppercase() {
+            'N' => (Axis::Latitude, false),
+            'S' => (Axis::Latitude, true),
+            'E' => (Axis::Longitude, false),
+            'W' => (Axis::Longitude, true),
+            _ => return None,
+        };
+        Some(Self { axis, negative })
+    }
+}
+
+/// Replaces the look-alike symbols typed on some keyboards with the ones parsed.
+fn normalize(input: &str) -> String {
+    input
+        .chars()
+        .map(|c| match c {
+            'º' | '˚' => '°',
+            '′' | '’' | '‘' | '`' | '´' => '\'',
+            '″' | '“' | '”' => '"',
+            c => c,
+        })
+        .collect()
+}
+
+/// Splits the input into the text of the two coordinates and their hemisphere letters.
+fn split(input: &str) -> Result<Vec<(String, Option<Hemisphere>)>, String> {
+    let mut letters = 0;
+    for c in input.chars().filter(|c| c.is_alphabetic()) {
+        if Hemisphere::from_letter(c).is_none() {
+            return Err(format!("Invalid Coordinate: Unexpected '{c}'"));
+        }
+        letters += 1;
+    }
+
+    if letters == 0 {
+        let parts: Vec<&str> = input.split([',', ';']).collect();
+        let parts = if parts.len() == 1 {
+            input.split_whitespace().collect()
+        } else {
+            parts
+        };
+        return Ok(parts.into_iter().map(|p| (p.to_string(), None)).collect());
+    }
+
+    // The letters are either all before or all after their numbers
+    let prefixed = input.trim_start().starts_with(|c: char| c.is_alphabetic());
+    let mut parts = vec![];
+    let mut current = String::new();
+    let mut pending = None;
+    for c in input.chars() {
+        let hemisphere = match Hemisphere::from_letter(c) {
+            Some(v) => v,
+            None => {
+                current.push(c);
+                continue;
+            }
+        };
+        if prefixed {
+            if pending.is_some() || !is_blank(&current) {
+                parts.push((std::mem::take(&mut current), pending));
+            }
+            pending = Some(hemisphere);
+        } else {
+            parts.push((std::mem::take(&mut current), Some(hemisphere)));
+        }
+    }
+    if pending.is_some() || !is_blank(&current) {
+        parts.push((current, pending));
+    }
+    Ok(parts)
+}
+
+/// Checks if the text has nothing but whitespace and separators between the coordinates.
+fn is_blank(text: &str) -> bool {
+    text.chars()
+        .all(|c| c.is_whitespace() || c == ',' || c == ';')
+}
+
+/// Parses an angle of degrees and optionally minutes and seconds, e.g. `-45°31'23.5"`.
+///
+/// Only the last number can have a fractional part, and the minutes and seconds must be less
+/// than 60.
+fn parse_angle(text: &str) -> Result<f64, String> {
+    let text = text.trim_matches(|c: char| c.is_whitespace() || c == ',' || c == ';');
+    if text.is_empty() {
+        return Err(String::from("Invalid Coordinate: Missing Number"));
+    }
+    let numbers = text
+        .split(|c: char| c.is_whitespace() || SEPARATORS.contains(&c))
+        .filter(|s| !s.is_empty())
+        .map(|s| {
+            s.parse::<f64>()
+                .ok()
+                .filter(|v| v.is_finite())
+                .ok_or_else(|| format!("Invalid Coordinate: Invalid Number '{s}'"))
+        })
+        .collect::<Result<Vec<f64>, String>>()?;
+    if numbers.len() > 3 {
+        return Err(format!("Invalid Coordinate: Too Many Numbers in '{text}'"));
+    }
+
+    let negative = text.starts_with('-');
+    let mut angle = 0.0;
+    for (i, number) in numbers.iter().enumerate() {
+        if i > 0 && !(0.0..60.0).contains(number) {
+            return Err(format!("Invalid Coordinate: {number} is not less than 60"));
+        }
+        if i + 1 < numbers.len() && number.fract() != 0.0 {
+            return Err(format!(
+                "Invalid Coordinate: Only the last number of '{text}' can have decimals"
+            ));
+        }
+        angle += number.abs() / 60f64.powi(i as i32);
+    }
+    Ok(if negative { -angle } else { angle })
+}
+
+/// Parses a coordinate, returning its longitude and latitude in decimal degrees.
+///
+/// See the [module documentation](self) for the formats accepted.
+pub fn parse(input: &str) -> Result<(f64, f64), String> {
+    let input = normalize(input);
+    let parts = split(&input)?;
+    if parts.len() != 2 {
+        return Err(String::from(
+            "Invalid Coordinate: Expected a latitude and a longitude",
+        ));
+    }
+
+    let mut values = Vec::with_capacity(2);
+    for (text, hemisphere) in parts {
+        let mut value = parse_angle(&text)?;
+        if let Some(hemisphere) = hemisphere {
+            if value < 0.0 {
+                return Err(format!(
+                    "Invalid Coordinate: '{}' has both a sign and a hemisphere",
+                    text.trim()
+                ));
+            }
+            if hemisphere.negative {
+                value = -value;
+            }
+        }
+        values.push((value, hemisphere.map(|h| h.axis)));
+    }
+
+    let (lat, lng) = match (values[0], values[1]) {
+        ((_, Some(a)), (_, Some(b))) if a == b => {
+            return Err(String::from(
+                "Invalid Coordinate: Both coordinates are in the same axis",
+            ))
+        }
+        ((lng, Some(Axis::Longitude)), (lat, _)) | ((lng, _), (lat, Some(Axis::Latitude))) => {
+            (lat, lng)
+        }
+        ((lat, _), (lng, _)) => (lat, lng),
+    };
+    if !(-90.0..=90.0).contains(&lat) {
+        return Err(format!(
+            "Invalid Coordinate: Latitude {lat} is out of range"
+        ));
+    }
+    if !(-180.0..=180.0).contains(&lng) {
+        return Err(format!(
+            "Invalid Coordinate: Longitude {lng} is out of range"
+        ));
+    }
+    Ok((lng, lat))
+}
+
+/// Formats an angle in degrees and minutes, or degrees, minutes and seconds when `seconds` is
+/// set, followed by the hemisphere letter.
+fn format_angle(value: f64, positive: char, negative: char, seconds: bool) -> String {
+    let hemisphere = if value < 0.0 { negative } else { positive };
+    // Rounding the smallest unit first so it never rounds up to 60
+    let (unit, decimals) = if seconds { (3600.0, 1) } else { (60.0, 3) };
+    let scale = 10f64.powi(decimals);
+    let total = (value.abs() * unit * scale).round() / scale;
+    let degrees = (total / unit).floor();
+    let rest = total - degrees * unit;
+    if seconds {
+        let minutes = (rest / 60.0).floor();
+        let seconds = rest - minutes * 60.0;
+        format!("{degrees}°{minutes}'{seconds:.1}\"{hemisphere}")
+    } else {
+        format!("{degrees}°{rest:.3}'{hemisphere}")
+    }
+}
+
+/// Formats a coordinate, writing the latitude first.
+pub fn format(lng: f64, lat: f64, style: CoordinateStyle) -> Result<String, String> {
+    if !lat.is_finite() || !(-90.0..=90.0).contains(&lat) {
+        return Err(format!(
+            "Invalid Coordinate: Latitude {lat} is out of range"
+        ));
+    }
+    if !lng.is_finite() || !(-180.0..=180.0).contains(&lng) {
+        return Err(format!(
+            "Invalid Coordinate: Longitude {lng} is out of range"
+        ));
+    }
+    Ok(match style {
+        CoordinateStyle::Decimal => format!("{lat:.6}, {lng:.6}"),
+        CoordinateStyle::DegreesMinutes => format!(
+            "{} {}",
+            format_angle(lat, 'N', 'S', false),
+            format_angle(lng, 'E', 'W', false)
+        ),
+        CoordinateStyle::DegreesMinutesSeconds => format!(
+            "{} {}",
+            format_angle(lat, 'N', 'S', true),
+            format_angle(lng, 'E', 'W', true)
+        ),
+    })
+}
+
+/// Parse a coordinate typed by the operator, returning its longitude and latitude.
+///
+/// The latitude is expected first unless hemisphere letters say otherwise.
+#[tauri::command]
+pub fn parse_coordinate(input: String) -> Result<(f64, f64), AppError> {
+    log::debug!("Parsing Coordinate: {}", input);
+    parse(&input)
+        .map_err(|e| AppError::new(codes::COORDINATES_INVALID, e).with("input", input.as_str()))
+}
+
+/// Format a coordinate in `style`, writing the latitude first.
+#[tauri::command]
+pub fn format_coordinate(lng: f64, lat: f64, style: CoordinateStyle) -> Result<String, AppError> {
+    format(lng, lat, style).map_err(|e| {
+        AppError::new(codes::COORDINATES_OUT_OF_RANGE, e)
+            .with("lng", lng)
+            .with("lat", lat)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Checks that `input` parses to `expected` as `(lng, lat)` within 0.05 seconds of arc.
+    fn assert_parses(input: &str, expected: (f64, f64)) {
+        let (lng, lat) = parse(input).unwrap_or_else(|e| panic!("{input}: {e}"));
+        assert!(
+            (lng - expected.0).abs() < 1.4e-5 && (lat - expected.1).abs() < 1.4e-5,
+            "{input} parsed to ({lng}, {lat}) instead of {expected:?}"
+        );
+    }
+
+    #[test]
+    fn valid_coordinates_are_parsed() {
+        let portland = (-122.6765, 45.5231);
+        let cases = [
+            ("45.5231, -122.6765", portland),
+            ("45.5231 -122.6765", portland),
+            ("45.5231;-122.6765", portland),
+            ("  45.5231 ,  -122.6765  ", portland),
+            ("45°31.386'N 122°40.59'W", portland),
+            ("45°31.386'N, 122°40.59'W", portland),
+            ("N45°31.386' W122°40.59'", portland),
+            ("N 45 31.386 W 122 40.59", portland),
+            ("45 31.386 N 122 40.59 W", portland),
+            ("45°31'23.16\"N, 122°40'35.4\"W", portland),
+            ("45º31′23.16″N 122º40′35.4″W", portland),
+            ("45°31'23.16\"n 122°40'35.4\"w", portland),
+            // The hemisphere letters override the order
+            ("122°40.59'W 45°31.386'N", portland),
+            ("122.6765 W, 45.5231", portland),
+            ("33°52'S 151°12'E", (151.2, -33.866_666_7)),
+            ("0, 0", (0.0, 0.0)),
+            ("-90, 180", (180.0, -90.0)),
+            ("90 N 180 W", (-180.0, 90.0)),
+        ];
+        for (input, expected) in cases {
+            assert_parses(input, expected);
+        }
+    }
+
+    #[test]
+    fn malformed_coordinates_are_rejected() {
+        let cases = [
+            (
+                "",
+                "Invalid Coordinate: Expected a latitude and a longitude",
+            ),
+            (
+                "45.5231",
+                "Invalid Coordinate: Expected a latitude and a longitude",
+            ),
+            (
+                "45.5231, -122.6765, 10",
+                "Invalid Coordinate: Expected a latitude and a longitude",
+            ),
+            ("45.5231, abc", "Invalid Coordinate: Unexpected 'a'"),
+            (
+                "45.5.231, -122.6765",
+                "Invalid Coordinate: Invalid Number '45.5.231'",
+            ),
+            (
+                "45°61'N 122°40'W",
+                "Invalid Coordinate: 61 is not less than 60",
+            ),
+            (
+                "45°31'60\"N 122°40'W",
+                "Invalid Coordinate: 60 is not less than 60",
+            ),
+            (
+                "45.5°31'N 122°40'W",
+                "Invalid Coordinate: Only the last number of '45.5°31'' can have decimals",
+            ),
+            (
+                "45°31'23\"1 N 122°40'W",
+                "Invalid Coordinate: Too Many Numbers in '45°31'23\"1'",
+            ),
+            (
+                "45°N 46°S",
+                "Invalid Coordinate: Both coordinates are in the same axis",
+            ),
+            (
+                "-45°N 122°W",
+                "Invalid Coordinate: '-45°' has both a sign and a hemisphere",
+            ),
+            (
+                "-122.6765 W, 45.5231",
+                "Invalid Coordinate: '-122.6765' has both a sign and a hemisphere",
+            ),
+            ("91, 0", "Invalid Coordinate: Latitude 91 is out of range"),
+            (
+                "0, -180.5",
+                "Invalid Coordinate: Longitude -180.5 is out of range",
+            ),
+            ("N W", "Invalid Coordinate: Missing Number"),
+            ("inf, 0", "Invalid Coordinate: Unexpected 'i'"),
+            ("NaN, 0", "Invalid Coordinate: Unexpected 'a'"),
+        ];
+        for (input, expected) in cases {
+            assert_eq!(parse(input), Err(String::from(expected)), "{input}");
+        }
+    }
+
+    #[test]
+    fn coordinates_are_formatted_latitude_first() {
+        let cases = [
+            (CoordinateStyle::Decimal, "45.523100, -122.676500"),
+            (CoordinateStyle::DegreesMinutes, "45°31.386'N 122°40.590'W"),
+            (
+                CoordinateStyle::DegreesMinutesSeconds,
+                "45°31'23.2\"N 122°40'35.4\"W",
+            ),
+        ];
+        for (style, expected) in cases {
+            assert_eq!(
+                format(-122.6765, 45.5231, style),
+                Ok(String::from(expected))
+            );
+        }
+        assert_eq!(
+            format(151.2, -33.8666667, CoordinateStyle::DegreesMinutes),
+            Ok(String::from("33°52.000'S 151°12.000'E"))
+        );
+    }
+
+    #[test]
+    fn formatting_never_rounds_up_to_sixty() {
+        assert_eq!(
+            format(
+                0.999_999_9,
+                -0.999_999_9,
+                CoordinateStyle::DegreesMinutesSeconds
+            ),
+            Ok(String::from("1°0'0.0\"S 1°0'0.0\"E"))
+        );
+        assert_eq!(
+            format(10.999_999_9, 0.0, CoordinateStyle::DegreesMinutes),
+            Ok(String::from("0°0.000'N 11°0.000'E"))
+        );
+    }
+
+    #[test]
+    fn formatted_coordinates_parse_back() {
+        for style in [
+            CoordinateStyle::Decimal,
+            CoordinateStyle::DegreesMinutes,
+            CoordinateStyle::DegreesMinutesSeconds,
+        ] {
+            for (lng, lat) in [(-122.6765, 45.5231), (151.2093, -33.8688), (0.0, 0.0)] {
+                let formatted = format(lng, lat, style).unwrap();
+                assert_parses(&formatted, (lng, lat));
+            }
+        }
+    }
+
+    #[test]
+    fn out_of_range_coordinates_are_not_formatted() {
+        let error = format_coordinate(0.0, 90.5, CoordinateStyle::Decimal).unwrap_err();
+        let error = serde_json::to_value(error).unwrap();
+        assert_eq!(error["code"], codes::COORDINATES_OUT_OF_RANGE);
+        assert_eq!(error["params"]["lat"], 90.5);
+
+        assert!(format(f64::NAN, 0.0, CoordinateStyle::Decimal).is_err());
+        assert!(format(-180.1, 0.0, CoordinateStyle::Decimal).is_err());
+    }
+
+    #[test]
+    fn parse_errors_carry_the_input() {
+        let error = parse_coordinate(String::from("north")).unwrap_err();
+        let error = serde_json::to_value(error).unwrap();
+
+        assert_eq!(error["code"], codes::COORDINATES_INVALID);
+        assert_eq!(error["params"]["input"], "north");
+    }
+}
@@ -0,0 +1,253 @@
+//! Checking the boat sampled at every collection point of a mission, for the checklist shown
+//! after the mission.
+
+use geo::GeodesicDistance;
+use geo_types::Point;
+use serde::Serialize;
+
+use crate::{
+    data::{BoatData, Layer},
+    errors::{codes, AppError},
+    geometry::Projection,
+    path::PathData,
+};
+
+/// The number of readings of a layer near a collection point.
+#[derive(Debug, Serialize, Clone, PartialEq, Eq)]
+pub struct LayerReadings {
+    /// The layer of the readings.
+    layer: Layer,
+    /// The number of readings within the radius.
+    count: usize,
+}
+
+/// The readings near a collection point.
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct PointCoverage {
+    /// The index of the collection point in the path.
+    index: usize,
+    /// The geodesic distance in meters to the closest reading of any layer, `None` if there
+    /// are no readings.
+    closest_distance: Option<f64>,
+    /// The number of readings within the radius of each layer with readings, the expected
+    /// layers first.
+    readings: Vec<LayerReadings>,
+    /// The expected layers without readings within the radius.
+    missing_layers: Vec<Layer>,
+    /// Whether an expected layer has no readings within the radius, or there are no readings
+    /// within the radius at all when no layers are expected.
+    missed: bool,
+}
+
+/// Gets the readings near a collection point.
+fn point_coverage(
+    index: usize,
+    point: Point<f64>,
+    data: &BoatData,
+    radius_m: f64,
+    expected_layers: &[Layer],
+) -> PointCoverage {
+    // The projection is accurate near the point, the distance to the closest reading is
+    // measured again on the ellipsoid
+    let projection = Projection::at(point.0);
+    let mut closest: Option<(f64, Point<f64>)> = None;
+    let mut readings: Vec<LayerReadings> = expected_layers
+        .iter()
+        .map(|layer| LayerReadings {
+            layer: layer.clone(),
+            count: 0,
+        })
+        .collect();
+    for feature in data.features() {
+        let [x, y] = projection.forward(feature.geometry().0);
+        let distance = x.hypot(y);
+        if !distance.is_finite() {
+            continue;
+        }
+        if closest.map_or(true, |(d, _)| distance < d) {
+            closest = Some((distance, feature.geometry()));
+        }
+        if distance <= radius_m {
+            let layer = feature.layer();
+            match readings.iter_mut().find(|r| r.layer == layer) {
+                Some(r) => r.count += 1,
+                None => readings.push(LayerReadings { layer, count: 1 }),
+            }
+        }
+    }
+
+    let missing_layers: Vec<Layer> = readings
+        .iter()
+        .filter(|r| r.count == 0)
+        .map(|r| r.layer.clone())
+        .collect();
+    readings.retain(|r| r.count > 0);
+    let missed = if expected_layers.is_empty() {
+        readings.is_empty()
+    } else {
+        !missing_layers.is_empty()
+    };
+    PointCoverage {
+        index,
+        closest_distance: closest.map(|(_, reading)| point.geodesic_distance(&reading)),
+        readings,
+        missing_layers,
+        missed,
+    }
+}
+
+/// Check which layers were sampled within `radius_m` meters of each collection point of a
+/// mission.
+///
+/// The coverage is in the order of the collection points. A point is `missed` when any of
+/// `expected_layers` has no readings within the radius, or when no layers are expected and
+/// there are no readings within the radius.
+#[tauri::command]
+pub fn mission_coverage(
+    path: PathData,
+    data: BoatData,
+    radius_m: f64,
+    expected_layers: Vec<Layer>,
+) -> Result<Vec<PointCoverage>, AppError> {
+    log::debug!("Checking Mission Coverage within {}m", radius_m);
+    if !radius_m.is_finite() || radius_m < 0.0 {
+        return Err(AppError::new(
+            codes::COVERAGE_INVALID_RADIUS,
+            format!("Invalid Radius: {radius_m}"),
+        )
+        .with("radius", radius_m));
+    }
+
+    let coverage: Vec<PointCoverage> = path
+        .collection_points()
+        .iter()
+        .enumerate()
+        .map(|(i, point)| point_coverage(i, *point, &data, radius_m, &expected_layers))
+        .collect();
+    log::info!(
+        "Missed {} of {} Collection Points",
+        coverage.iter().filter(|c| c.missed).count(),
+        coverage.len()
+    );
+    Ok(coverage)
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeZone, Utc};
+    use geo_types::{LineString, MultiPoint};
+
+    use super::*;
+    use crate::data::BoatDataFeature;
+
+    /// Creates a reading at `layer` `north_m` meters north of the first collection point.
+    fn reading(layer: Layer, north_m: f64) -> BoatDataFeature {
+        BoatDataFeature::new(
+            28.5,
+            0.5,
+            layer,
+            Utc.timestamp_opt(1_715_495_400, 0).unwrap(),
+            Point::new(101.5, 3.1 + north_m / 110_576.0),
+        )
+    }
+
+    fn data(features: Vec<BoatDataFeature>) -> BoatData {
+        BoatData::new(BoatData::default().version().to_string(), features)
+    }
+
+    /// Creates a path with collection points at `lats` along the meridian at 101.5°.
+    fn path(lats: &[f64]) -> PathData {
+        PathData::new(
+            LineString(vec![]),
+            MultiPoint(lats.iter().map(|lat| Point::new(101.5, *lat)).collect()),
+        )
+    }
+
+    #[test]
+    fn readings_are_counted_by_layer() {
+        let data = data(vec![
+            reading(Layer::Surface, 1.0),
+            reading(Layer::Surface, 3.0),
+            reading(Layer::SeaBed, 4.0),
+            reading(Layer::SeaBed, 50.0),
+        ]);
+
+        let coverage =
+            mission_coverage(path(&[3.1]), data, 5.0, vec![Layer::Surface, Layer::SeaBed]).unwrap();
+
+        assert_eq!(
+            coverage[0].readings,
+            [
+                LayerReadings {
+                    layer: Layer::Surface,
+                    count: 2
+                },
+                LayerReadings {
+                    layer: Layer::SeaBed,
+                    count: 1
+                }
+            ]
+        );
+        assert!(coverage[0].missing_layers.is_empty());
+        assert!(!coverage[0].missed);
+        let closest = coverage[0].closest_distance.unwrap();
+        assert!((closest - 1.0).abs() < 0.01, "{closest}");
+    }
+
+    #[test]
+    fn points_missing_an_expected_layer_are_missed() {
+        let data = data(vec![
+            reading(Layer::Middle, 1.0),
+            reading(Layer::SeaBed, 20.0),
+        ]);
+
+        let coverage =
+            mission_coverage(path(&[3.1]), data, 5.0, vec![Layer::Surface, Layer::SeaBed]).unwrap();
+
+        assert_eq!(coverage[0].missing_layers, [Layer::Surface, Layer::SeaBed]);
+        assert_eq!(
+            coverage[0].readings,
+            [LayerReadings {
+                layer: Layer::Middle,
+                count: 1
+            }]
+        );
+        assert!(coverage[0].missed);
+    }
+
+    #[test]
+    fn without_expected_layers_any_reading_covers_a_point() {
+        let data = data(vec![reading(Layer::Middle, 1.0)]);
+
+        let coverage = mission_coverage(path(&[3.1, 3.2]), data, 5.0, vec![]).unwrap();
+
+        assert!(!coverage[0].missed);
+        assert!(coverage[1].missed);
+        assert!(coverage[1].closest_distance.unwrap() > 10_000.0);
+    }
+
+    #[test]
+    fn coverage_follows_the_collection_point_order() {
+        let coverage = mission_coverage(path(&[3.3, 3.1, 3.2]), data(vec![]), 5.0, vec![]).unwrap();
+
+        assert_eq!(
+            coverage.iter().map(|c| c.index).collect::<Vec<_>>(),
+            [0, 1, 2]
+        );
+        assert!(coverage
+            .iter()
+            .all(|c| c.missed && c.closest_distance.is_none()));
+    }
+
+    #[test]
+    fn invalid_radius_is_rejected() {
+        for radius in [-1.0, f64::NAN, f64::INFINITY] {
+            let error = mission_coverage(path(&[3.1]), data(vec![]), radius, vec![]).unwrap_err();
+
+            assert_eq!(
+                serde_json::to_value(error).unwrap()["code"],
+                codes::COVERAGE_INVALID_RADIUS
+            );
+        }
+    }
+}
@@ -1,22 +1,51 @@
 //! Data structure and function for working with data collected by the boat.
 
 use std::{
+    collections::HashSet,
     fmt::Display,
-    io::{ErrorKind, Write},
-    path::PathBuf,
+    io::{BufReader, ErrorKind, Read},
+    path::{Path, PathBuf},
     str::FromStr,
 };
 
-use chrono::{DateTime, Utc};
+use chrono::{
+    format::{Item, StrftimeItems},
+    DateTime, Datelike, LocalResult, NaiveDateTime, SecondsFormat, TimeZone, Utc,
+};
+use chrono_tz::Tz;
+use geo::GeodesicDistance;
 use geo_types::Point;
 use geojson::{
     de::deserialize_geometry, ser::serialize_geometry, FeatureCollection, GeoJson, JsonObject,
 };
+use rayon::iter::{IntoParallelIterator, ParallelIterator};
 use serde::{Deserialize, Serialize};
-use serde_json::{json, Map};
+use serde_json::{json, Map, Value};
 use tauri::{
     api::{self, file},
-    AppHandle,
+    AppHandle, Manager,
+};
+use uuid::Uuid;
+
+use crate::{
+    app_data::AppData,
+    autosave::{self, AutosaveState},
+    backup,
+    datasets::active_data_file,
+    errors::{codes, AppError},
+    export_preview::{self, ExportPreview},
+    imports::{self, ImportTask},
+    index::DataIndex,
+    journal,
+    metadata::DatasetMetadata,
+    migrations,
+    operations::{self, Operation},
+    recovery,
+    settings::SettingsState,
+    storage::{self, sqlite, write_atomically},
+    streaming,
+    units::{convert_feature_to_stored_units, convert_feature_units, UnitSystem},
+    watcher,
 };
 
 /// Data received from the boat in GeoJSON format.
@@ -25,15 +54,31 @@ use tauri::{
 ///
 /// `version`: The version of the BoatData format.
 /// `features`: The data collected by the boat.
-#[derive(Debug, Clone)]
+/// `members`: The other foreign members of the feature collection, e.g. the
+/// [metadata](DatasetMetadata) of the dataset.
+///
+/// Boat data is equal when its features are equal in the same order and its members are equal.
+#[derive(Debug, Clone, PartialEq)]
 pub struct BoatData {
     /// The version of the communication protocol used.
     version: String,
     /// The individual data point collected.
     features: Vec<BoatDataFeature>,
+    /// The foreign members of the feature collection other than the version, kept so they
+    /// aren't lost when the data is written again.
+    members: Map<String, Value>,
 }
 
 impl BoatData {
+    /// Creates a new `BoatData` from its version and features.
+    pub fn new(version: String, features: Vec<BoatDataFeature>) -> Self {
+        Self {
+            version,
+            features,
+            members: Map::new(),
+        }
+    }
+
     /// Gets the version of the communication protocol used.
     pub fn version(&self) -> &str {
         &self.version
@@ -43,12 +88,334 @@ impl BoatData {
     pub fn features(&self) -> &[BoatDataFeature] {
         &self.features
     }
+
+    /// Gets the foreign members of the feature collection other than the version.
+    pub fn members(&self) -> &Map<String, Value> {
+        &self.members
+    }
+
+    /// Replaces the foreign members of the feature collection, ignoring the version.
+    pub fn set_members(&mut self, mut members: Map<String, Value>) {
+        members.remove("version");
+        self.members = members;
+    }
+
+    /// Gets the metadata of the dataset.
+    pub fn metadata(&self) -> DatasetMetadata {
+        DatasetMetadata::from_members(&self.members)
+    }
+
+    /// Replaces the metadata of the dataset.
+    pub fn set_metadata(&mut self, metadata: &DatasetMetadata) {
+        metadata.write_members(&mut self.members);
+    }
+
+    /// Appends a data point to the collected data.
+    pub fn push(&mut self, feature: BoatDataFeature) {
+        self.features.push(feature);
+    }
+
+    /// Gets the data points measured between `start` and `end` inclusively.
+    ///
+    /// A missing bound leaves that side of the range open.
+    pub fn filter_time_range(
+        &self,
+        start: Option<DateTime<Utc>>,
+        end: Option<DateTime<Utc>>,
+    ) -> BoatData {
+        let features = self
+            .features
+            .iter()
+            .filter(|f| start.map_or(true, |start| f.time >= start))
+            .filter(|f| end.map_or(true, |end| f.time <= end))
+            .cloned()
+            .collect();
+        Self::new(self.version.clone(), features)
+    }
+
+    /// Gets the data points measured at `layer`.
+    pub fn filter_layer(&self, layer: Layer) -> BoatData {
+        let features = self
+            .features
+            .iter()
+            .filter(|f| f.layer == layer)
+            .cloned()
+            .collect();
+        Self::new(self.version.clone(), features)
+    }
+
+    /// Gets the layers of the data points, the known layers from the surface down followed by
+    /// the other layers in the order they first appear.
+    pub fn layers(&self) -> Vec<Layer> {
+        let mut layers: Vec<Layer> = Layer::KNOWN
+            .into_iter()
+            .filter(|layer| self.features.iter().any(|f| f.layer == *layer))
+            .collect();
+        for feature in &self.features {
+            if !layers.contains(&feature.layer) {
+                layers.push(feature.layer.clone());
+            }
+        }
+        layers
+    }
+
+    /// Gets the data points with a value of `sensor` between `min` and `max` inclusive.
+    ///
+    /// Data points without a value of the sensor are left out.
+    pub fn filter_sensor(&self, sensor: Sensor, min: Option<f64>, max: Option<f64>) -> BoatData {
+        let features = self
+            .features
+            .iter()
+            .filter(|f| {
+                f.sensor(sensor).map_or(false, |value| {
+                    min.map_or(true, |min| value >= min) && max.map_or(true, |max| value <= max)
+                })
+            })
+            .cloned()
+            .collect();
+        Self::new(self.version.clone(), features)
+    }
+
+    /// Gets the data points inside a bounding box, including the points on its boundary.
+    ///
+    /// A box with `min_lng` greater than `max_lng` crosses the antimeridian.
+    pub fn within_bbox(&self, min_lng: f64, min_lat: f64, max_lng: f64, max_lat: f64) -> BoatData {
+        let features = self
+            .features
+            .iter()
+            .filter(|f| {
+                let (lng, lat) = (f.geometry.x(), f.geometry.y());
+                let lng_inside = if min_lng <= max_lng {
+                    (min_lng..=max_lng).contains(&lng)
+                } else {
+                    lng >= min_lng || lng <= max_lng
+                };
+                lng_inside && (min_lat..=max_lat).contains(&lat)
+            })
+            .cloned()
+            .collect();
+        Self::new(self.version.clone(), features)
+    }
+
+    /// Gets the data point nearest to `point` using geodesic distance.
+    ///
+    /// Data points farther than `max_distance` meters are ignored. When several data points are
+    /// equally near, the most recent one is returned.
+    pub fn nearest(
+        &self,
+        point: Point<f64>,
+        max_distance: Option<f64>,
+    ) -> Option<&BoatDataFeature> {
+        self.features
+            .iter()
+            .map(|f| (f.geometry.geodesic_distance(&point), f))
+            .filter(|(distance, _)| {
+                !distance.is_nan() && max_distance.map_or(true, |max| *distance <= max)
+            })
+            .min_by(|(a_distance, a), (b_distance, b)| {
+                // The distances are comparable as NaN distances are skipped
+                a_distance
+                    .partial_cmp(b_distance)
+                    .unwrap()
+                    .then_with(|| b.time.cmp(&a.time))
+            })
+            .map(|(_, f)| f)
+    }
+
+    /// Removes the repeated data points, keeping the first occurrence.
+    ///
+    /// Data points are repeated when they share the same time, coordinates, depth and layer.
+    /// Returns the number of data points removed.
+    pub fn dedup(&mut self) -> usize {
+        let before = self.features.len();
+        let mut seen = HashSet::new();
+        self.features
+            .retain(|f| seen.insert((f.key(), f.depth.to_bits(), f.layer.clone())));
+        before - self.features.len()
+    }
+
+    /// Gets the data point with an id.
+    pub fn feature(&self, id: &str) -> Option<&BoatDataFeature> {
+        self.features.iter().find(|f| f.id() == Some(id))
+    }
+
+    /// Gets the data point with an id to change it.
+    pub fn feature_mut(&mut self, id: &str) -> Option<&mut BoatDataFeature> {
+        self.features.iter_mut().find(|f| f.id() == Some(id))
+    }
+
+    /// Gets the data points to change them.
+    pub fn features_mut(&mut self) -> &mut [BoatDataFeature] {
+        &mut self.features
+    }
+
+    /// Gets the ids of the data points with a note containing `query`, ignoring case.
+    pub fn search_notes(&self, query: &str) -> Vec<String> {
+        let query = query.to_lowercase();
+        self.features
+            .iter()
+            .filter(|f| {
+                f.note()
+                    .map_or(false, |note| note.to_lowercase().contains(&query))
+            })
+            .filter_map(|f| f.id.clone())
+            .collect()
+    }
+
+    /// Removes the data points with the ids, returning the number of data points removed.
+    pub fn remove_ids(&mut self, ids: &HashSet<String>) -> usize {
+        let before = self.features.len();
+        self.features
+            .retain(|f| f.id.as_ref().map_or(true, |id| !ids.contains(id)));
+        before - self.features.len()
+    }
+
+    /// Gives the data points without an id an id generated from their values.
+    ///
+    /// The ids are the same each time the same data is read, so importing a file again doesn't
+    /// change the ids of its data points.
+    pub fn assign_ids(&mut self) {
+        let mut ids = FeatureIds::default();
+        for feature in &mut self.features {
+            ids.assign(feature);
+        }
+    }
+
+    /// Checks if the data points are in order of time.
+    pub fn is_sorted_by_time(&self) -> bool {
+        self.features.windows(2).all(|w| w[0].time <= w[1].time)
+    }
+
+    /// Sorts the data points by time, e.g. after readings arrived out of order over the radio.
+    ///
+    /// The sort is stable, so data points measured at the same time keep their order. Returns
+    /// `false` if the data points were already in order.
+    pub fn sort_by_time(&mut self) -> bool {
+        if self.is_sorted_by_time() {
+            return false;
+        }
+        self.features.sort_by_key(|f| f.time);
+        true
+    }
+
+    /// Appends the features of `other` that are not already in the data.
+    ///
+    /// Two features are considered the same if they share the same time and geometry.
+    pub fn merge(&mut self, other: BoatData) -> Result<(), AppError> {
+        if self.version != other.version {
+            return Err(AppError::new(
+                codes::DATA_VERSION_MISMATCH,
+                format!(
+                    "Unable to Merge Boat Data: Version {} does not match stored version {}",
+                    other.version, self.version
+                ),
+            )
+            .with("version", other.version.as_str())
+            .with("stored", self.version.as_str()));
+        }
+
+        let mut seen: HashSet<_> = self.features.iter().map(BoatDataFeature::key).collect();
+        for feature in other.features {
+            if seen.insert(feature.key()) {
+                self.push(feature);
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks if two boat data are equal, allowing the measurements and coordinates to differ by
+    /// up to `epsilon`, e.g. after being written and read again.
+    pub fn approx_eq(&self, other: &Self, epsilon: f64) -> bool {
+        self.version == other.version
+            && self.members == other.members
+            && self.features.len() == other.features.len()
+            && self
+                .features
+                .iter()
+                .zip(&other.features)
+                .all(|(a, b)| a.approx_eq(b, epsilon))
+    }
+
+    /// Checks if two boat data have the same features regardless of their order.
+    pub fn eq_unordered(&self, other: &Self) -> bool {
+        if self.version != other.version || self.features.len() != other.features.len() {
+            return false;
+        }
+        fn sorted(data: &BoatData) -> Vec<&BoatDataFeature> {
+            let mut features: Vec<_> = data.features.iter().collect();
+            features.sort_by(|a, b| a.cmp_total(b));
+            features
+        }
+        sorted(self) == sorted(other)
+    }
+
+    /// Checks the values of the data points are in range.
+    ///
+    /// Returns an issue for each problem found, in the order of the data points.
+    pub fn validate(&self) -> Vec<ValidationIssue> {
+        self.features
+            .iter()
+            .enumerate()
+            .flat_map(|(index, feature)| {
+                feature
+                    .problems()
+                    .into_iter()
+                    .map(move |reason| ValidationIssue {
+                        index,
+                        row: None,
+                        reason,
+                    })
+            })
+            .collect()
+    }
+
+    /// Removes the data points at `indices`.
+    fn remove_indices(&mut self, indices: &HashSet<usize>) {
+        let mut index = 0;
+        self.features.retain(|_| {
+            index += 1;
+            !indices.contains(&(index - 1))
+        });
+    }
+}
+
+/// Maps a number to an integer in the same order, ordering NaN like `f64::total_cmp`, which
+/// needs a newer Rust than the minimum supported.
+fn total_order(value: f64) -> i64 {
+    let bits = value.to_bits() as i64;
+    bits ^ (((bits >> 63) as u64) >> 1) as i64
+}
+
+/// The earliest year a data point can be measured in.
+const MIN_YEAR: i32 = 2000;
+
+/// The year data points must be measured before.
+const MAX_YEAR: i32 = 2100;
+
+/// A data point with a value out of range.
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct ValidationIssue {
+    /// The index of the data point.
+    index: usize,
+    /// The line of the data point in CSV.
+    row: Option<u64>,
+    /// The problem with the data point.
+    reason: String,
+}
+
+impl Display for ValidationIssue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.row {
+            Some(row) => write!(f, "Row {}: {}", row, self.reason),
+            None => write!(f, "Feature {}: {}", self.index, self.reason),
+        }
+    }
 }
 
 impl Default for BoatData {
     /// Default `BoatData`.
     ///
-    /// The version would default to "0.1.0" and an empty feature array.
+    /// The version would default to "0.2.0" and an empty feature array.
     ///
     /// # Examples
     ///
@@ -56,71 +423,156 @@ impl Default for BoatData {
     /// use babara_project_desktop::data::BoatData;
     ///
     /// let default = BoatData::default();
-    /// assert_eq!(default.version, String::from("0.1.0"));
-    /// assert_eq!(default.features, vec![]);
+    /// assert_eq!(default.version(), "0.2.0");
+    /// assert!(default.features().is_empty());
     /// ```
     fn default() -> Self {
-        Self {
-            version: String::from("0.1.0"),
-            features: vec![],
-        }
+        Self::new(String::from(migrations::BOAT_DATA_VERSION), vec![])
+    }
+}
+
+impl IntoIterator for BoatData {
+    type Item = BoatDataFeature;
+    type IntoIter = std::vec::IntoIter<BoatDataFeature>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.features.into_iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a BoatData {
+    type Item = &'a BoatDataFeature;
+    type IntoIter = std::slice::Iter<'a, BoatDataFeature>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.features.iter()
+    }
+}
+
+impl Extend<BoatDataFeature> for BoatData {
+    fn extend<T: IntoIterator<Item = BoatDataFeature>>(&mut self, iter: T) {
+        self.features.extend(iter);
     }
 }
 
 impl FromStr for BoatData {
-    type Err = String;
+    type Err = AppError;
 
     /// Creates a new `BoatData` from a GeoJSON string.
     fn from_str(value: &str) -> Result<Self, Self::Err> {
-        let geojson: GeoJson = value.parse().map_err(|e| format!("{e}"))?;
+        let geojson: GeoJson = value
+            .parse()
+            .map_err(|e| AppError::new(codes::DATA_INVALID_JSON, format!("{e}")))?;
+        Self::try_from(geojson)
+    }
+}
+
+impl Display for BoatData {
+    /// Display the `BoatData` in GeoJSON fromat.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", GeoJson::from(self))
+    }
+}
+
+impl TryFrom<GeoJson> for BoatData {
+    type Error = AppError;
 
+    /// Creates a new `BoatData` from a `GeoJson` struct.
+    ///
+    /// Data from older versions of the format is migrated to the current version, and features
+    /// without an id are given one generated from their values.
+    fn try_from(value: GeoJson) -> Result<Self, Self::Error> {
         log::info!("Parsing Feature Collection");
-        let features =
-            FeatureCollection::try_from(geojson).map_err(|_| String::from("Invalid GeoJSON"))?;
+        let features = FeatureCollection::try_from(value)
+            .map_err(|_| AppError::new(codes::DATA_INVALID_GEOJSON, "Invalid GeoJSON"))?;
         log::debug!("Feature Collection: {}", features);
 
         // Checking for version
         log::info!("Checking Version");
-        let foreign_members = features
+        let missing_version = || {
+            AppError::new(
+                codes::DATA_MISSING_VERSION,
+                "Invalid Boat Data GeoJSON: Missing Version",
+            )
+        };
+        let version = features
             .foreign_members
-            .ok_or(String::from("Invalid Boat Data GeoJSON: Missing Version"))?;
-        let version = foreign_members
+            .as_ref()
+            .ok_or_else(missing_version)?
             .get("version")
-            .ok_or(String::from("Invalid Boat Data GeoJSON: Missing Version"))?
+            .ok_or_else(missing_version)?
             .as_str()
-            .ok_or(String::from("Invalid Boat Data GeoJSON: Invalid Version"))?;
+            .ok_or_else(|| {
+                AppError::new(
+                    codes::DATA_INVALID_VERSION,
+                    "Invalid Boat Data GeoJSON: Invalid Version",
+                )
+            })?
+            .to_string();
         log::debug!("Version: {}", version);
+        let value = migrations::migrate(GeoJson::from(features), &version)?;
+        let members = match &value {
+            GeoJson::FeatureCollection(collection) => {
+                collection.foreign_members.clone().unwrap_or_default()
+            }
+            _ => Map::new(),
+        };
 
         log::info!("Extracting Features");
-        let features = features.features;
-        let features = if features.is_empty() {
-            vec![]
-        } else {
-            geojson::de::deserialize_feature_collection_str_to_vec(value)
-                .map_err(|_| "Invalid Boat Data GeoJSON: Invalid Data Features")?
+        let ids: Vec<Option<String>> = match &value {
+            GeoJson::FeatureCollection(collection) => {
+                collection.features.iter().map(feature_id).collect()
+            }
+            _ => vec![],
         };
+        let mut features: Vec<BoatDataFeature> = match &value {
+            GeoJson::FeatureCollection(collection) if collection.features.is_empty() => vec![],
+            _ => geojson::de::deserialize_feature_collection_str_to_vec(&value.to_string())
+                .map_err(|_| {
+                    AppError::new(
+                        codes::DATA_INVALID_FEATURES,
+                        "Invalid Boat Data GeoJSON: Invalid Data Features",
+                    )
+                })?,
+        };
+        // The ids of the features aren't part of their properties
+        for (feature, id) in features.iter_mut().zip(ids) {
+            if id.is_some() {
+                feature.id = id;
+            }
+        }
 
-        Ok(Self {
-            version: String::from(version),
-            features,
-        })
+        let mut data = Self::new(String::from(migrations::BOAT_DATA_VERSION), features);
+        data.set_members(members);
+        data.assign_ids();
+        Ok(data)
     }
 }
 
-impl Display for BoatData {
-    /// Display the `BoatData` in GeoJSON fromat.
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}", GeoJson::from(self))
+/// Gets the id of a GeoJSON feature as a string.
+fn feature_id(feature: &geojson::Feature) -> Option<String> {
+    match &feature.id {
+        Some(geojson::feature::Id::String(id)) => Some(id.clone()),
+        Some(geojson::feature::Id::Number(id)) => Some(id.to_string()),
+        None => None,
     }
 }
 
-impl TryFrom<GeoJson> for BoatData {
-    type Error = String;
+/// Creates the error of a GeoJSON feature not being a data point.
+fn invalid_feature(error: geojson::Error) -> AppError {
+    AppError::new(codes::DATA_INVALID_GEOJSON, error.to_string())
+}
 
-    /// Creates a new `BoatData` from a `GeoJson` struct.
-    fn try_from(value: GeoJson) -> Result<Self, Self::Error> {
-        value.to_string().parse()
+/// Reads a data point from a GeoJSON feature, keeping the id of the feature.
+pub fn read_feature(feature: geojson::Feature) -> Result<BoatDataFeature, AppError> {
+    let id = feature_id(&feature);
+    let mut value: BoatDataFeature =
+        geojson::de::deserialize_single_feature(feature.to_string().as_bytes())
+            .map_err(invalid_feature)?;
+    if id.is_some() {
+        value.id = id;
     }
+    Ok(value)
 }
 
 impl From<BoatData> for GeoJson {
@@ -141,7 +593,7 @@ impl From<&BoatData> for GeoJson {
     /// Converts `BoatData` to `GeoJson` struct.
     fn from(value: &BoatData) -> Self {
         let features = value.features.iter().map(geojson::Feature::from).collect();
-        let mut foreign_members = Map::new();
+        let mut foreign_members = value.members.clone();
         foreign_members.insert(String::from("version"), json!(&value.version));
 
         let collection = FeatureCollection {
@@ -179,14 +631,12 @@ impl TryFrom<crate::comm_proto::babara_project::data::BoatData> for BoatData {
     fn try_from(
         value: crate::comm_proto::babara_project::data::BoatData,
     ) -> Result<Self, Self::Error> {
-        Ok(Self {
-            version: value.version,
-            features: value
-                .features
-                .into_iter()
-                .map(BoatDataFeature::try_from)
-                .collect::<Result<Vec<BoatDataFeature>, String>>()?,
-        })
+        let features = value
+            .features
+            .into_iter()
+            .map(BoatDataFeature::try_from)
+            .collect::<Result<Vec<BoatDataFeature>, String>>()?;
+        Ok(Self::new(value.version, features))
     }
 }
 
@@ -197,17 +647,23 @@ impl TryFrom<crate::comm_proto::babara_project::data::BoatData> for BoatData {
 /// `Surface`: The data is collected from the surface of the water body.
 /// `Middle`: The data is collected from the middle of the water body.
 /// `SeaBed`: The data is collected from the sea bed of the water body.
-#[derive(Debug, Serialize, Deserialize, Clone, Copy)]
+/// `Other`: A layer added by newer firmware, kept by its name so the data isn't lost.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Hash)]
+#[serde(try_from = "String", into = "String")]
 pub enum Layer {
-    #[serde(rename = "surface")]
     /// The data is collected from the surface of the water body.
     Surface,
-    #[serde(rename = "middle")]
     /// The data is collected from the middle of the water body.
     Middle,
-    #[serde(rename = "sea bed")]
     /// The data is collected from the sea bed of the water body.
     SeaBed,
+    /// The data is collected from a layer this version doesn't know.
+    Other(String),
+}
+
+impl Layer {
+    /// The layers this version knows, from the surface down.
+    pub const KNOWN: [Layer; 3] = [Self::Surface, Self::Middle, Self::SeaBed];
 }
 
 impl Display for Layer {
@@ -225,17 +681,66 @@ impl Display for Layer {
     /// assert_eq!(surface.to_string(), "surface");
     /// assert_eq!(middle.to_string(), "middle");
     /// assert_eq!(seabed.to_string(), "sea bed");
+    /// assert_eq!(Layer::Other(String::from("thermocline")).to_string(), "thermocline");
     /// ```
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let output = match self {
             Self::Surface => "surface",
             Layer::Middle => "middle",
             Layer::SeaBed => "sea bed",
+            Layer::Other(name) => name.as_str(),
         };
         write!(f, "{output}")
     }
 }
 
+impl FromStr for Layer {
+    type Err = String;
+
+    /// Parses a layer from its GeoJSON name.
+    ///
+    /// Names of layers this version doesn't know are kept as `Other`, only an empty name is
+    /// rejected.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use babara_project_desktop::data::Layer;
+    ///
+    /// assert_eq!("sea bed".parse(), Ok(Layer::SeaBed));
+    /// assert_eq!("thermocline".parse(), Ok(Layer::Other(String::from("thermocline"))));
+    /// assert!("".parse::<Layer>().is_err());
+    /// ```
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "surface" => Ok(Self::Surface),
+            "middle" => Ok(Self::Middle),
+            "sea bed" => Ok(Self::SeaBed),
+            _ if value.trim().is_empty() => Err(String::from(
+                "Invalid Layer: Empty Name, expected e.g. surface, middle or sea bed",
+            )),
+            _ => Ok(Self::Other(value.to_string())),
+        }
+    }
+}
+
+impl TryFrom<String> for Layer {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl From<Layer> for String {
+    fn from(value: Layer) -> Self {
+        match value {
+            Layer::Other(name) => name,
+            layer => layer.to_string(),
+        }
+    }
+}
+
 impl From<crate::comm_proto::babara_project::data::boat_data::Layer> for Layer {
     fn from(value: crate::comm_proto::babara_project::data::boat_data::Layer) -> Self {
         match value {
@@ -255,8 +760,18 @@ impl From<crate::comm_proto::babara_project::data::boat_data::Layer> for Layer {
 /// `layer`: The layer of the water body the temperature is collected at.
 /// `time`: The date and time the temperature is collected.
 /// `geometry`: The coordinate the temperature is collected.
-#[derive(Debug, Serialize, Deserialize, Clone)]
+/// `ph`, `dissolved_oxygen`, `turbidity`: The values of the water quality sensors, if the boat
+/// has them.
+/// `id`: The unique id of the data point, written as the id of the GeoJSON feature.
+/// `note`: The note of the operator on the data point, e.g. "sample bottle #3 taken".
+/// `temperature_raw`: The temperature measured by the sensor before it was calibrated.
+/// `sensor_id`: The id of the temperature sensor sent by the boat.
+/// `extra`: The other properties of the GeoJSON feature, e.g. written by another tool.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 pub struct BoatDataFeature {
+    /// The unique id of the data point.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    id: Option<String>,
     /// The temperature measured at the location.
     temperature: f64,
     /// The depth the temperature is measured at.
@@ -271,9 +786,193 @@ pub struct BoatDataFeature {
         deserialize_with = "deserialize_geometry"
     )]
     geometry: Point<f64>,
+    /// The pH measured at the location.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    ph: Option<f64>,
+    /// The dissolved oxygen measured at the location in mg/L.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    dissolved_oxygen: Option<f64>,
+    /// The turbidity measured at the location in NTU.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    turbidity: Option<f64>,
+    /// The note of the operator on the data point.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    note: Option<String>,
+    /// The temperature measured by the sensor, `None` if it isn't calibrated.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    temperature_raw: Option<f64>,
+    /// The id of the temperature sensor, `None` if the boat didn't send it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    sensor_id: Option<String>,
+    /// The properties of the feature not used by the app, kept so they aren't lost when the
+    /// data is written again.
+    #[serde(flatten)]
+    extra: JsonObject,
+}
+
+/// The water quality sensors only some boats have.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Sensor {
+    /// The pH of the water.
+    Ph,
+    /// The dissolved oxygen in mg/L.
+    DissolvedOxygen,
+    /// The turbidity in NTU.
+    Turbidity,
+}
+
+impl Sensor {
+    /// Every sensor, in the order of their columns.
+    pub const ALL: [Sensor; 3] = [Self::Ph, Self::DissolvedOxygen, Self::Turbidity];
+
+    /// Gets the name of the property and column of the sensor.
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Ph => "ph",
+            Self::DissolvedOxygen => "dissolved_oxygen",
+            Self::Turbidity => "turbidity",
+        }
+    }
+
+    /// Gets the name of the sensor shown in messages.
+    fn label(self) -> &'static str {
+        match self {
+            Self::Ph => "pH",
+            Self::DissolvedOxygen => "Dissolved Oxygen",
+            Self::Turbidity => "Turbidity",
+        }
+    }
 }
 
 impl BoatDataFeature {
+    /// Creates a new data point.
+    pub fn new(
+        temperature: f64,
+        depth: f64,
+        layer: Layer,
+        time: DateTime<Utc>,
+        geometry: Point<f64>,
+    ) -> Self {
+        Self {
+            id: None,
+            temperature,
+            depth,
+            layer,
+            time,
+            geometry,
+            ph: None,
+            dissolved_oxygen: None,
+            turbidity: None,
+            note: None,
+            temperature_raw: None,
+            sensor_id: None,
+            extra: JsonObject::new(),
+        }
+    }
+
+    /// Sets the id of the data point.
+    pub fn with_id(mut self, id: Option<String>) -> Self {
+        self.id = id;
+        self
+    }
+
+    /// Gives the data point a random id if it doesn't have one, for data points received from
+    /// the boat.
+    pub fn with_random_id(mut self) -> Self {
+        if self.id.is_none() {
+            self.id = Some(Uuid::new_v4().to_string());
+        }
+        self
+    }
+
+    /// Gets the unique id of the data point, `None` if it hasn't been given one.
+    pub fn id(&self) -> Option<&str> {
+        self.id.as_deref()
+    }
+
+    /// Sets the note of the data point.
+    pub fn with_note(mut self, note: Option<String>) -> Self {
+        self.note = note;
+        self
+    }
+
+    /// Gets the note of the operator on the data point.
+    pub fn note(&self) -> Option<&str> {
+        self.note.as_deref()
+    }
+
+    /// Sets the id of the temperature sensor.
+    pub fn with_sensor_id(mut self, sensor_id: Option<String>) -> Self {
+        self.sensor_id = sensor_id;
+        self
+    }
+
+    /// Gets the id of the temperature sensor, `None` if the boat didn't send it.
+    pub fn sensor_id(&self) -> Option<&str> {
+        self.sensor_id.as_deref()
+    }
+
+    /// Sets the temperature measured by the sensor before it was calibrated.
+    pub fn with_temperature_raw(mut self, temperature_raw: Option<f64>) -> Self {
+        self.temperature_raw = temperature_raw;
+        self
+    }
+
+    /// Gets the temperature measured by the sensor, `None` if it isn't calibrated.
+    pub fn temperature_raw(&self) -> Option<f64> {
+        self.temperature_raw
+    }
+
+    /// Sets the temperature and the temperature measured by the sensor, e.g. after calibrating
+    /// it.
+    pub fn set_temperature(&mut self, temperature: f64, temperature_raw: Option<f64>) {
+        self.temperature = temperature;
+        self.temperature_raw = temperature_raw;
+    }
+
+    /// Sets the properties of the feature not used by the app.
+    pub fn with_extra(mut self, extra: JsonObject) -> Self {
+        self.extra = extra;
+        self
+    }
+
+    /// Gets the properties of the feature not used by the app.
+    pub fn extra(&self) -> &JsonObject {
+        &self.extra
+    }
+
+    /// Sets the note of the data point, removing it if `text` is blank.
+    pub fn set_note(&mut self, text: String) {
+        self.note = if text.trim().is_empty() {
+            None
+        } else {
+            Some(text)
+        };
+    }
+
+    /// Sets the values of the water quality sensors.
+    pub fn with_sensors(
+        mut self,
+        ph: Option<f64>,
+        dissolved_oxygen: Option<f64>,
+        turbidity: Option<f64>,
+    ) -> Self {
+        self.ph = ph;
+        self.dissolved_oxygen = dissolved_oxygen;
+        self.turbidity = turbidity;
+        self
+    }
+
+    /// Gets the value of a water quality sensor, `None` if it wasn't measured.
+    pub fn sensor(&self, sensor: Sensor) -> Option<f64> {
+        match sensor {
+            Sensor::Ph => self.ph,
+            Sensor::DissolvedOxygen => self.dissolved_oxygen,
+            Sensor::Turbidity => self.turbidity,
+        }
+    }
+
     /// Gets the temperature measured at the location.
     pub fn temperature(&self) -> f64 {
         self.temperature
@@ -286,7 +985,7 @@ impl BoatDataFeature {
 
     /// Gets the layer the temperature is measured at.
     pub fn layer(&self) -> Layer {
-        self.layer
+        self.layer.clone()
     }
 
     /// Gets the timestamp the temperature is measured at.
@@ -294,10 +993,136 @@ impl BoatDataFeature {
         self.time
     }
 
+    /// Sets the timestamp the temperature is measured at, e.g. after correcting the clock.
+    pub fn set_time(&mut self, time: DateTime<Utc>) {
+        self.time = time;
+    }
+
     /// Gets the location the temperature is measured at.
     pub fn geometry(&self) -> Point<f64> {
         self.geometry
     }
+
+    /// Checks if two data points are equal, allowing the measurements and coordinates to differ
+    /// by up to `epsilon`.
+    pub fn approx_eq(&self, other: &Self, epsilon: f64) -> bool {
+        let close = |a: f64, b: f64| a == b || (a - b).abs() <= epsilon;
+        self.layer == other.layer
+            && self.time == other.time
+            && close(self.temperature, other.temperature)
+            && close(self.depth, other.depth)
+            && close(self.geometry.x(), other.geometry.x())
+            && close(self.geometry.y(), other.geometry.y())
+            && Sensor::ALL.iter().all(
+                |&sensor| match (self.sensor(sensor), other.sensor(sensor)) {
+                    (Some(a), Some(b)) => close(a, b),
+                    (a, b) => a.is_none() && b.is_none(),
+                },
+            )
+    }
+
+    /// Orders data points by all of their values, for comparing data regardless of its order.
+    fn cmp_total(&self, other: &Self) -> std::cmp::Ordering {
+        self.key()
+            .cmp(&other.key())
+            .then(self.layer.to_string().cmp(&other.layer.to_string()))
+            .then(total_order(self.depth).cmp(&total_order(other.depth)))
+            .then(total_order(self.temperature).cmp(&total_order(other.temperature)))
+    }
+
+    /// Gets the problems with the values of the data point.
+    fn problems(&self) -> Vec<String> {
+        let mut problems = vec![];
+        let (lng, lat) = (self.geometry.x(), self.geometry.y());
+        if !(-180.0..=180.0).contains(&lng) {
+            problems.push(format!("Longitude {lng} is not within [-180, 180]"));
+        }
+        if !(-90.0..=90.0).contains(&lat) {
+            problems.push(format!("Latitude {lat} is not within [-90, 90]"));
+        }
+        if !self.temperature.is_finite() {
+            problems.push(format!("Temperature {} is not a number", self.temperature));
+        }
+        if !self.depth.is_finite() {
+            problems.push(format!("Depth {} is not a number", self.depth));
+        } else if self.depth < 0.0 {
+            problems.push(format!("Depth {} is negative", self.depth));
+        }
+        for sensor in Sensor::ALL {
+            if let Some(value) = self.sensor(sensor) {
+                if !value.is_finite() {
+                    problems.push(format!("{} {value} is not a number", sensor.label()));
+                }
+            }
+        }
+        if let Some(ph) = self.ph {
+            if ph.is_finite() && !(0.0..=14.0).contains(&ph) {
+                problems.push(format!("pH {ph} is not within [0, 14]"));
+            }
+        }
+        let year = self.time.year();
+        if !(MIN_YEAR..MAX_YEAR).contains(&year) {
+            problems.push(format!(
+                "Time {} is not between {MIN_YEAR} and {MAX_YEAR}",
+                self.time.to_rfc3339()
+            ));
+        }
+        problems
+    }
+
+    /// Key identifying the time and location of the measurement.
+    fn key(&self) -> (DateTime<Utc>, u64, u64) {
+        (
+            self.time,
+            self.geometry.x().to_bits(),
+            self.geometry.y().to_bits(),
+        )
+    }
+
+    /// Generates the id of the data point from its values.
+    ///
+    /// `occurrence` tells apart data points with the same values.
+    fn content_id(&self, occurrence: usize) -> String {
+        let content = format!(
+            "{}|{}|{}|{}|{}|{}|{:?}|{:?}|{:?}|{}",
+            self.time.to_rfc3339(),
+            self.geometry.x().to_bits(),
+            self.geometry.y().to_bits(),
+            self.depth.to_bits(),
+            self.layer,
+            self.temperature.to_bits(),
+            self.ph.map(f64::to_bits),
+            self.dissolved_oxygen.map(f64::to_bits),
+            self.turbidity.map(f64::to_bits),
+            occurrence
+        );
+        Uuid::new_v5(&FEATURE_ID_NAMESPACE, content.as_bytes()).to_string()
+    }
+}
+
+/// The namespace of the ids generated from the values of the data points.
+const FEATURE_ID_NAMESPACE: Uuid = Uuid::from_u128(0x5f1c_2a8e_9b3d_4c71_a6e0_7d2f_8b41_c93a);
+
+/// The ids given to the data points while reading boat data.
+#[derive(Debug, Default)]
+struct FeatureIds {
+    used: HashSet<String>,
+}
+
+impl FeatureIds {
+    /// Records the id of a data point, generating one from its values if it doesn't have one.
+    fn assign(&mut self, feature: &mut BoatDataFeature) {
+        if let Some(id) = &feature.id {
+            self.used.insert(id.clone());
+            return;
+        }
+        let id = (0..)
+            .map(|occurrence| feature.content_id(occurrence))
+            .find(|id| !self.used.contains(id))
+            .unwrap();
+        self.used.insert(id.clone());
+        feature.id = Some(id);
+    }
 }
 
 impl From<BoatDataFeatureCSV> for BoatDataFeature {
@@ -318,11 +1143,19 @@ impl From<&BoatDataFeatureCSV> for BoatDataFeature {
     /// Converts to the CSV representation of the data.
     fn from(value: &BoatDataFeatureCSV) -> Self {
         Self {
+            id: value.id.clone(),
             geometry: Point::new(value.lng, value.lat),
             time: value.time,
             temperature: value.temperature,
             depth: value.depth,
-            layer: value.layer,
+            layer: value.layer.clone(),
+            ph: value.ph,
+            dissolved_oxygen: value.dissolved_oxygen,
+            turbidity: value.turbidity,
+            note: value.note.clone(),
+            temperature_raw: None,
+            sensor_id: None,
+            extra: JsonObject::new(),
         }
     }
 }
@@ -346,11 +1179,19 @@ impl TryFrom<crate::comm_proto::babara_project::data::boat_data::BoatDataFeature
             .clone()
             .ok_or(String::from("There is no geometry value"))?;
         Ok(Self {
+            id: Some(Uuid::new_v4().to_string()),
             temperature: value.temperature,
             depth: value.depth,
             layer: value.layer().into(),
             time: timestamp.into(),
             geometry: Point::new(geometry.longitude, geometry.latitude),
+            ph: None,
+            dissolved_oxygen: None,
+            turbidity: None,
+            note: None,
+            temperature_raw: None,
+            sensor_id: None,
+            extra: JsonObject::new(),
         })
     }
 }
@@ -374,17 +1215,33 @@ impl From<&BoatDataFeature> for geojson::Feature {
     fn from(value: &BoatDataFeature) -> Self {
         let geometry = geojson::Value::from(&value.geometry);
 
-        let mut properties = Map::new();
+        // The properties used by the app replace extra properties of the same name
+        let mut properties = value.extra.clone();
         properties.insert(String::from("temperature"), value.temperature.into());
         properties.insert(String::from("depth"), value.depth.into());
         properties.insert(String::from("layer"), value.layer.to_string().into());
         properties.insert(String::from("time"), value.time.to_rfc3339().into());
+        // Absent sensor values are left out so data without them stays small
+        for sensor in Sensor::ALL {
+            if let Some(v) = value.sensor(sensor) {
+                properties.insert(String::from(sensor.name()), v.into());
+            }
+        }
+        if let Some(note) = &value.note {
+            properties.insert(String::from("note"), note.clone().into());
+        }
+        if let Some(raw) = value.temperature_raw {
+            properties.insert(String::from("temperature_raw"), raw.into());
+        }
+        if let Some(sensor_id) = &value.sensor_id {
+            properties.insert(String::from("sensor_id"), sensor_id.clone().into());
+        }
 
         Self {
             bbox: None,
             geometry: Some(geometry.into()),
-            id: None,
-            properties: Some(JsonObject::from(properties)),
+            id: value.id.clone().map(geojson::feature::Id::String),
+            properties: Some(properties),
             foreign_members: None,
         }
     }
@@ -400,6 +1257,10 @@ impl From<&BoatDataFeature> for geojson::Feature {
 /// `time`: The date and time the temperature is collected.
 /// `lat`: The latitude of the coordinate the temperature is collected.
 /// `lng`: The longitude of the coordinate the temperature is collected.
+/// `ph`, `dissolved_oxygen`, `turbidity`: The values of the water quality sensors, empty if the
+/// boat doesn't have them.
+/// `id`: The unique id of the data point.
+/// `note`: The note of the operator on the data point, empty if there is none.
 #[derive(Debug, Serialize, Deserialize)]
 pub struct BoatDataFeatureCSV {
     /// The temperature measured at the location.
@@ -415,6 +1276,21 @@ pub struct BoatDataFeatureCSV {
     lat: f64,
     /// The longitude coordinate the temperature is measured at.
     lng: f64,
+    /// The pH measured at the location.
+    #[serde(default)]
+    ph: Option<f64>,
+    /// The dissolved oxygen measured at the location in mg/L.
+    #[serde(default)]
+    dissolved_oxygen: Option<f64>,
+    /// The turbidity measured at the location in NTU.
+    #[serde(default)]
+    turbidity: Option<f64>,
+    /// The unique id of the data point.
+    #[serde(default)]
+    id: Option<String>,
+    /// The note of the operator on the data point.
+    #[serde(default)]
+    note: Option<String>,
 }
 
 impl From<BoatDataFeature> for BoatDataFeatureCSV {
@@ -440,7 +1316,12 @@ impl From<&BoatDataFeature> for BoatDataFeatureCSV {
             time: value.time,
             temperature: value.temperature,
             depth: value.depth,
-            layer: value.layer,
+            layer: value.layer.clone(),
+            ph: value.ph,
+            dissolved_oxygen: value.dissolved_oxygen,
+            turbidity: value.turbidity,
+            id: value.id.clone(),
+            note: value.note.clone(),
         }
     }
 }
@@ -468,23 +1349,128 @@ impl From<&BoatDataFeatureCSV> for geojson::Feature {
 
 /// Read boat data from application storage.
 #[tauri::command]
-pub fn read_data(app_handle: AppHandle) -> Result<BoatData, String> {
+pub fn read_data(app_handle: AppHandle) -> Result<BoatData, AppError> {
     log::debug!("Reading Path");
-    let mut data_dir = app_handle
-        .path_resolver()
-        .app_data_dir()
-        .ok_or(String::from("Unable to Get App Data Directory"))?;
-    data_dir.push("data.geojson");
-    log::debug!("Application GeoJSON Boat Data: {}", data_dir.display());
-
-    import_data(data_dir)
+    let data = app_handle.state::<AppData>().get(&app_handle)?;
+    Ok(BoatData::clone(&data))
 }
 
-/// Import boat data from the file system.
+/// The number of validation issues listed in the error of an import.
+const MAX_REPORTED_ISSUES: usize = 10;
+
+/// Boat data imported from the file system.
+#[derive(Debug, Serialize, Clone)]
+pub struct ImportedData {
+    /// The data imported.
+    data: BoatData,
+    /// The number of repeated data points removed.
+    duplicates: usize,
+    /// The problems of the invalid data points skipped.
+    skipped: Vec<ValidationIssue>,
+}
+
+impl ImportedData {
+    /// Validates the data points, removes the repeated data points unless `dedup` is `false`
+    /// and sorts the data points by time. Data points without an id are given one generated
+    /// from their values.
+    ///
+    /// An error listing the problems is returned if there are invalid data points, unless
+    /// `skip_invalid` is `true` in which case they are removed. `rows` are the lines of the data
+    /// points in CSV.
+    pub fn new(
+        mut data: BoatData,
+        rows: Option<&[u64]>,
+        dedup: Option<bool>,
+        skip_invalid: Option<bool>,
+    ) -> Result<Self, AppError> {
+        let mut skipped = data.validate();
+        if let Some(rows) = rows {
+            for issue in &mut skipped {
+                issue.row = rows.get(issue.index).copied();
+            }
+        }
+        if !skipped.is_empty() {
+            if !skip_invalid.unwrap_or(false) {
+                let mut issues: Vec<_> = skipped
+                    .iter()
+                    .take(MAX_REPORTED_ISSUES)
+                    .map(ToString::to_string)
+                    .collect();
+                if skipped.len() > MAX_REPORTED_ISSUES {
+                    issues.push(format!("and {} More", skipped.len() - MAX_REPORTED_ISSUES));
+                }
+                let message = format!("Invalid Boat Data: {}", issues.join("; "));
+                return Err(AppError::new(codes::DATA_INVALID_POINTS, message)
+                    .with("count", skipped.len())
+                    .with("issues", issues));
+            }
+            let invalid: HashSet<_> = skipped.iter().map(|issue| issue.index).collect();
+            data.remove_indices(&invalid);
+            log::warn!("Skipped {} Invalid Data Points", invalid.len());
+        }
+
+        let duplicates = if dedup.unwrap_or(true) {
+            data.dedup()
+        } else {
+            0
+        };
+        log::info!("Removed {} Repeated Data Points", duplicates);
+        data.assign_ids();
+        if data.sort_by_time() {
+            log::info!("Sorted Data Points by Time");
+        }
+        Ok(Self {
+            data,
+            duplicates,
+            skipped,
+        })
+    }
+}
+
+/// Import boat data from the file system in the background, returning the id of the import.
+///
+/// Emits `import-progress` events while the file is read and an `import-finished` event with
+/// the result. Files with data points out of range are rejected, unless `skip_invalid` is
+/// `true` in which case the invalid data points are skipped and listed in the result. Repeated
+/// data points are removed unless `dedup` is `false`.
 #[tauri::command]
-pub fn import_data(import_path: PathBuf) -> Result<BoatData, String> {
+pub async fn import_data(
+    app_handle: AppHandle,
+    import_path: PathBuf,
+    dedup: Option<bool>,
+    skip_invalid: Option<bool>,
+) -> Result<u64, AppError> {
+    log::debug!("Importing from: {}", import_path.display());
+    let operation = Operation::start(
+        "import_data",
+        json!({ "path": import_path, "dedup": dedup, "skip_invalid": skip_invalid }),
+    );
+    Ok(imports::spawn_import(app_handle, operation, move |task| {
+        // The file is parsed while it is read, as huge files don't fit in memory as a string
+        let data = match std::fs::File::open(&import_path) {
+            Ok(file) => {
+                let version = streaming::read_version(BufReader::new(file))?;
+                let reader = BufReader::new(task.reader(&import_path)?);
+                streaming::read_boat_data(reader, &version)?
+            }
+            Err(e) if e.kind() == ErrorKind::NotFound => {
+                log::warn!(
+                    "Unable to find Path: {}, using default BoatData",
+                    import_path.display()
+                );
+                BoatData::default()
+            }
+            Err(e) => return Err(AppError::io(e, &import_path)),
+        };
+        task.rows_parsed(data.features.len())?;
+        ImportedData::new(data, None, dedup, skip_invalid)
+    }))
+}
+
+/// Loads boat data from the file system, defaulting to empty data if the file doesn't exist.
+pub fn load_data(import_path: &Path) -> Result<BoatData, AppError> {
     log::debug!("Importing from: {}", import_path.display());
-    Ok(match file::read_string(&import_path) {
+    Ok(match file::read_string(import_path) {
         Ok(v) => BoatData::from_str(&v)?,
         Err(api::Error::Io(e)) => match e.kind() {
             ErrorKind::NotFound => {
@@ -494,70 +1480,3187 @@ pub fn import_data(import_path: PathBuf) -> Result<BoatData, String> {
                 );
                 BoatData::default()
             }
-            _ => return Err(e.to_string()),
+            _ => return Err(AppError::io(e, import_path)),
         },
-        Err(e) => return Err(e.to_string()),
+        Err(e) => {
+            return Err(AppError::new(codes::IO_OTHER, e.to_string())
+                .with("path", import_path.display().to_string()))
+        }
     })
 }
 
+/// Writes boat data in GeoJSON format to the file system.
+pub fn write_data(path: &Path, data: &BoatData) -> Result<(), AppError> {
+    write_atomically(path, |file| write!(file, "{}", data))
+}
+
 /// Export boat data to the file system.
+///
+/// If `dry_run` is `true`, nothing is written and a preview of the export is returned instead.
 #[tauri::command]
-pub fn export_data(export_path: PathBuf, data: BoatData) -> Result<(), String> {
+pub fn export_data(
+    export_path: PathBuf,
+    data: BoatData,
+    dry_run: Option<bool>,
+) -> Result<Option<ExportPreview>, AppError> {
+    if dry_run.unwrap_or(false) {
+        // Values that are not a number are written as null by serde_json
+        return export_preview::preview(&export_path, &data, "null", |file| {
+            write!(file, "{}", data)
+        })
+        .map(Some);
+    }
     log::debug!("Exporting to: {}", export_path.display());
-    let mut file = std::fs::File::create(export_path).map_err(|e| e.to_string())?;
-    write!(file, "{}", data).map_err(|e| e.to_string())?;
-    Ok(())
+    write_data(&export_path, &data)?;
+    Ok(None)
+}
+
+/// Get the generation of the boat data in application storage, which increases each time the
+/// data changes.
+///
+/// Getting the generation before reading the data lets [`save_data`] reject saves of data that
+/// changed since it was read.
+#[tauri::command]
+pub fn data_generation(app_handle: AppHandle) -> u64 {
+    app_handle.state::<AppData>().generation()
+}
+
+/// Save boat data to application storage, returning the new generation of the data.
+///
+/// The stored data is backed up before it is overwritten, keeping at most `max_backups`
+/// backups, or the `max_backups` setting if it isn't given. Saves wait for the writes in
+/// progress, e.g. the autosave, to finish. If `base_generation` is given and the data changed
+/// since that generation, e.g. another window saved or data points were appended, nothing is
+/// saved so the newer data isn't overwritten, returning a
+/// [`DATA_STALE_GENERATION`](codes::DATA_STALE_GENERATION) error.
+///
+/// Nothing is written if the data directory is read only or doesn't have space for the data,
+/// returning a [`STORAGE_READ_ONLY`](codes::STORAGE_READ_ONLY) or
+/// [`STORAGE_DISK_FULL`](codes::STORAGE_DISK_FULL) error with the path.
+#[tauri::command]
+pub fn save_data(
+    app_handle: AppHandle,
+    data: BoatData,
+    max_backups: Option<usize>,
+    base_generation: Option<u64>,
+) -> Result<u64, AppError> {
+    operations::record(
+        &app_handle,
+        "save_data",
+        json!({ "features": data.features().len(), "base_generation": base_generation }),
+        || {
+            log::debug!("Saving Path");
+            let _writing = app_handle.state::<AutosaveState>().inner().lock_writes();
+            let app_data = app_handle.state::<AppData>();
+            if let Some(base) = base_generation {
+                let generation = app_data.generation();
+                if base != generation {
+                    log::warn!(
+                        "Rejecting Save of Stale Boat Data: Generation {} is older than {}",
+                        base,
+                        generation
+                    );
+                    return Err(AppError::new(
+                        codes::DATA_STALE_GENERATION,
+                        format!(
+                            "Stale Boat Data: Generation {base} is older than {generation}, \
+                             reload the data before saving"
+                        ),
+                    )
+                    .with("base", base)
+                    .with("current", generation));
+                }
+            }
+            let data_dir = active_data_file(&app_handle)?;
+            log::debug!("Application GeoJSON Path: {}", data_dir.display());
+            let backup_dir = backup::backup_dir(&app_handle)?;
+            let max_backups = max_backups
+                .unwrap_or_else(|| app_handle.state::<SettingsState>().get().max_backups);
+            // The size of the GeoJSON is close enough to the size of the database
+            let content = data.to_string();
+            storage::preflight_write(&data_dir, content.len() as u64)?;
+            if sqlite::enabled(&app_handle) {
+                sqlite::save(
+                    &sqlite::database_file(&data_dir),
+                    &data,
+                    &backup_dir,
+                    max_backups,
+                )?;
+                app_handle.state::<DataIndex>().rebuild(&data);
+                app_data.replace(data);
+                recovery::clear_recovery(&app_handle)?;
+                return Ok(app_data.generation());
+            }
+
+            backup::backup(&data_dir, &backup_dir, max_backups)?;
+            app_handle.state::<DataIndex>().rebuild(&data);
+            write_atomically(&data_dir, |file| file.write_all(content.as_bytes()))?;
+            app_data.replace(data);
+            watcher::note_write(&app_handle, &data_dir);
+            // The data saved includes the data points in the journal
+            journal::clear_journal(&data_dir)?;
+            recovery::clear_recovery(&app_handle)?;
+            Ok(app_data.generation())
+        },
+    )
+}
+
+/// Query the boat data inside a bounding box.
+#[tauri::command]
+pub fn query_data_bbox(
+    data: BoatData,
+    min_lng: f64,
+    min_lat: f64,
+    max_lng: f64,
+    max_lat: f64,
+) -> BoatData {
+    log::debug!(
+        "Querying Data in [{}, {}, {}, {}]",
+        min_lng,
+        min_lat,
+        max_lng,
+        max_lat
+    );
+    data.within_bbox(min_lng, min_lat, max_lng, max_lat)
+}
+
+/// Query the boat data in application storage inside a bounding box, without sending the data
+/// from the frontend.
+#[tauri::command]
+pub fn query_data_bbox_stored(
+    app_handle: AppHandle,
+    min_lng: f64,
+    min_lat: f64,
+    max_lng: f64,
+    max_lat: f64,
+) -> Result<BoatData, AppError> {
+    log::debug!(
+        "Querying Stored Data in [{}, {}, {}, {}]",
+        min_lng,
+        min_lat,
+        max_lng,
+        max_lat
+    );
+    let data = app_handle.state::<AppData>().get(&app_handle)?;
+    Ok(data.within_bbox(min_lng, min_lat, max_lng, max_lat))
+}
+
+/// Find the data point nearest to a coordinate within an optional distance in meters.
+#[tauri::command]
+pub fn nearest_feature(
+    data: BoatData,
+    lng: f64,
+    lat: f64,
+    max_distance_m: Option<f64>,
+) -> Option<BoatDataFeature> {
+    log::debug!("Finding Nearest Feature to [{}, {}]", lng, lat);
+    data.nearest(Point::new(lng, lat), max_distance_m).cloned()
+}
+
+/// Merge new boat data into the data in application storage.
+///
+/// The merged data is written back to storage and returned.
+#[tauri::command]
+pub fn merge_data(app_handle: AppHandle, new: BoatData) -> Result<BoatData, AppError> {
+    operations::record(
+        &app_handle,
+        "merge_data",
+        json!({ "features": new.features().len() }),
+        || {
+            log::debug!("Merging Data");
+            let _writing = app_handle.state::<AutosaveState>().inner().lock_writes();
+            autosave::flush_locked(&app_handle)?;
+            let data_dir = active_data_file(&app_handle)?;
+            log::debug!("Application GeoJSON Path: {}", data_dir.display());
+
+            let sqlite_storage = sqlite::enabled(&app_handle);
+            let mut data = if sqlite_storage {
+                sqlite::read_stored(&data_dir)?
+            } else {
+                journal::read_with_journal(&data_dir)?
+            };
+            let stored = data.features().len();
+            data.merge(new)?;
+            if sqlite_storage {
+                sqlite::replace(&sqlite::database_file(&data_dir), &data)?;
+            } else {
+                write_atomically(&data_dir, |file| write!(file, "{}", data))?;
+                watcher::note_write(&app_handle, &data_dir);
+                journal::clear_journal(&data_dir)?;
+            }
+            app_handle.state::<DataIndex>().extend(&data, stored);
+            app_handle.state::<AppData>().replace(data.clone());
+            Ok(data)
+        },
+    )
+}
+
+/// Writes boat data to the active dataset in the storage in use, including the data points in
+/// the journal.
+///
+/// The stored data is backed up first if `backup` is `true` and the data is stored in GeoJSON.
+pub fn write_stored(app_handle: &AppHandle, data: &BoatData, backup: bool) -> Result<(), AppError> {
+    let data_file = active_data_file(app_handle)?;
+    if sqlite::enabled(app_handle) {
+        return sqlite::replace(&sqlite::database_file(&data_file), data);
+    }
+
+    let content = data.to_string();
+    storage::preflight_write(&data_file, content.len() as u64)?;
+    if backup {
+        let max_backups = app_handle.state::<SettingsState>().get().max_backups;
+        backup::backup(&data_file, &backup::backup_dir(app_handle)?, max_backups)?;
+    }
+    write_atomically(&data_file, |file| file.write_all(content.as_bytes()))?;
+    watcher::note_write(app_handle, &data_file);
+    journal::clear_journal(&data_file)
+}
+
+/// Get a data point of the boat data in application storage by its id.
+#[tauri::command]
+pub fn get_feature(app_handle: AppHandle, id: String) -> Result<Option<BoatDataFeature>, AppError> {
+    log::debug!("Getting Feature: {}", id);
+    let data = app_handle.state::<AppData>().get(&app_handle)?;
+    Ok(data.feature(&id).cloned())
+}
+
+/// Delete data points from the boat data in application storage by their ids.
+///
+/// Ids that aren't in the data are ignored. Returns the number of data points deleted. The
+/// deletion is written to storage by the autosave.
+#[tauri::command]
+pub fn delete_features(app_handle: AppHandle, ids: Vec<String>) -> Result<usize, AppError> {
+    operations::record(
+        &app_handle,
+        "delete_features",
+        json!({ "ids": ids.len() }),
+        || {
+            log::info!("Deleting {} Features", ids.len());
+            let state = app_handle.state::<AppData>();
+            let data = state.get(&app_handle)?;
+            if !ids.iter().any(|id| data.feature(id).is_some()) {
+                return Ok(0);
+            }
+
+            let deleted = state.update(&app_handle, |data| {
+                Ok(data.remove_ids(&ids.into_iter().collect()))
+            })?;
+            log::info!("Deleted {} Features", deleted);
+            Ok(deleted)
+        },
+    )
+}
+
+/// Set the note of a data point of the boat data in application storage, e.g. "sensor fouled
+/// here".
+///
+/// An empty note removes the note. Returns the data point with its new note. The note is
+/// written to storage by the autosave.
+#[tauri::command]
+pub fn set_feature_note(
+    app_handle: AppHandle,
+    id: String,
+    text: String,
+) -> Result<BoatDataFeature, AppError> {
+    log::info!("Setting Note of Feature: {}", id);
+    app_handle.state::<AppData>().update(&app_handle, |data| {
+        let feature = data.feature_mut(&id).ok_or_else(|| {
+            AppError::new(
+                codes::DATA_FEATURE_NOT_FOUND,
+                format!("Unable to find Feature: {id}"),
+            )
+            .with("id", id.as_str())
+        })?;
+        feature.set_note(text);
+        Ok(feature.clone())
+    })
+}
+
+/// Search the notes of the boat data in application storage, ignoring case.
+///
+/// Returns the ids of the data points with a note containing `query`.
+#[tauri::command]
+pub fn search_notes(app_handle: AppHandle, query: String) -> Result<Vec<String>, AppError> {
+    log::debug!("Searching Notes for: {}", query);
+    let data = app_handle.state::<AppData>().get(&app_handle)?;
+    Ok(data.search_notes(&query))
+}
+
+/// Compare two boat data, e.g. to check if the data has unsaved changes.
+///
+/// The measurements and coordinates can differ by up to `epsilon` if it is given, otherwise
+/// the data must be exactly equal. The order of the features is ignored if `unordered` is
+/// `true`.
+#[tauri::command]
+pub fn compare_data(
+    a: BoatData,
+    b: BoatData,
+    epsilon: Option<f64>,
+    unordered: Option<bool>,
+) -> Result<bool, AppError> {
+    match (epsilon, unordered.unwrap_or(false)) {
+        (Some(_), true) => Err(AppError::new(
+            codes::DATA_UNSUPPORTED_COMPARISON,
+            "Unable to Compare Boat Data: Approximate unordered comparison is not supported",
+        )),
+        (None, true) => Ok(a.eq_unordered(&b)),
+        (Some(epsilon), false) => Ok(a.approx_eq(&b, epsilon)),
+        (None, false) => Ok(a == b),
+    }
 }
 
-/// Save boat data to application storage.
+/// Filter boat data to the data measured within a time range.
 #[tauri::command]
-pub fn save_data(app_handle: AppHandle, data: BoatData) -> Result<(), String> {
-    log::debug!("Saving Path");
-    let mut data_dir = app_handle
-        .path_resolver()
-        .app_data_dir()
-        .ok_or(String::from("Unable to Get App Data Directory"))?;
-    data_dir.push("data.geojson");
-    log::debug!("Application GeoJSON Path: {}", data_dir.display());
+pub fn filter_data(
+    data: BoatData,
+    start: Option<DateTime<Utc>>,
+    end: Option<DateTime<Utc>>,
+) -> BoatData {
+    log::debug!("Filtering Data from {:?} to {:?}", start, end);
+    data.filter_time_range(start, end)
+}
+
+/// Filter the boat data in application storage to a time range, without sending the data from
+/// the frontend.
+#[tauri::command]
+pub fn filter_data_stored(
+    app_handle: AppHandle,
+    start: Option<DateTime<Utc>>,
+    end: Option<DateTime<Utc>>,
+) -> Result<BoatData, AppError> {
+    log::debug!("Filtering Stored Data from {:?} to {:?}", start, end);
+    let data = app_handle.state::<AppData>().get(&app_handle)?;
+    Ok(data.filter_time_range(start, end))
+}
+
+/// Sort boat data by time.
+///
+/// Data points measured at the same time keep their order.
+#[tauri::command]
+pub fn sort_data(mut data: BoatData) -> BoatData {
+    log::debug!("Sorting Data by Time");
+    data.sort_by_time();
+    data
+}
+
+/// Filter boat data to the data measured at a layer.
+#[tauri::command]
+pub fn filter_data_layer(data: BoatData, layer: String) -> Result<BoatData, AppError> {
+    log::debug!("Filtering Data for Layer: {}", layer);
+    let parsed = layer.parse().map_err(|e: String| {
+        AppError::new(codes::DATA_INVALID_LAYER, e).with("layer", layer.as_str())
+    })?;
+    Ok(data.filter_layer(parsed))
+}
+
+/// Filter boat data to the data points with a value of a water quality sensor within a range.
+///
+/// Data points without a value of the sensor are removed, so without `min` and `max` this keeps
+/// the data points the sensor measured.
+#[tauri::command]
+pub fn filter_data_sensor(
+    data: BoatData,
+    sensor: Sensor,
+    min: Option<f64>,
+    max: Option<f64>,
+) -> BoatData {
+    log::debug!(
+        "Filtering Data for {:?} from {:?} to {:?}",
+        sensor,
+        min,
+        max
+    );
+    data.filter_sensor(sensor, min, max)
+}
+
+/// Creates a writer of boat data CSV, writing the header with the columns in `units`.
+fn csv_writer<W: std::io::Write>(writer: W, units: UnitSystem) -> csv::Result<csv::Writer<W>> {
+    let mut writer = csv::WriterBuilder::new()
+        .has_headers(false)
+        .from_writer(writer);
+    writer.write_record([
+        units.temperature_column(),
+        units.depth_column(),
+        "layer",
+        "time",
+        "lat",
+        "lng",
+        Sensor::Ph.name(),
+        Sensor::DissolvedOxygen.name(),
+        Sensor::Turbidity.name(),
+        "id",
+        "note",
+    ])?;
+    Ok(writer)
+}
+
+/// The fields of the data points that can be exported to CSV.
+const CSV_FIELDS: [&str; 13] = [
+    "id",
+    "time",
+    "lat",
+    "lng",
+    "depth",
+    "layer",
+    "temperature",
+    "ph",
+    "dissolved_oxygen",
+    "turbidity",
+    "note",
+    "temperature_raw",
+    "sensor_id",
+];
+
+/// A field of the data points exported to CSV.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CsvField {
+    Id,
+    Time,
+    Lat,
+    Lng,
+    Depth,
+    Layer,
+    Temperature,
+    Sensor(Sensor),
+    Note,
+    TemperatureRaw,
+    SensorId,
+}
+
+impl FromStr for CsvField {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "id" => Ok(Self::Id),
+            "time" => Ok(Self::Time),
+            "lat" => Ok(Self::Lat),
+            "lng" => Ok(Self::Lng),
+            "depth" => Ok(Self::Depth),
+            "layer" => Ok(Self::Layer),
+            "temperature" => Ok(Self::Temperature),
+            "ph" => Ok(Self::Sensor(Sensor::Ph)),
+            "dissolved_oxygen" => Ok(Self::Sensor(Sensor::DissolvedOxygen)),
+            "turbidity" => Ok(Self::Sensor(Sensor::Turbidity)),
+            "note" => Ok(Self::Note),
+            "temperature_raw" => Ok(Self::TemperatureRaw),
+            "sensor_id" => Ok(Self::SensorId),
+            _ => Err(format!(
+                "Invalid Column Field: {value}, expected one of {}",
+                CSV_FIELDS.join(", ")
+            )),
+        }
+    }
+}
+
+/// The format of the times exported to CSV.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum CsvTimeFormat {
+    /// Milliseconds since the epoch.
+    EpochMillis,
+    /// RFC 3339 in UTC.
+    Rfc3339,
+    /// A strftime pattern, formatted in UTC.
+    Pattern(String),
+}
+
+impl FromStr for CsvTimeFormat {
+    type Err = String;
 
-    export_data(data_dir, data)
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "epoch_ms" => Ok(Self::EpochMillis),
+            "rfc3339" => Ok(Self::Rfc3339),
+            // Formatting an invalid pattern panics, so it is checked beforehand
+            _ if StrftimeItems::new(value).any(|item| item == Item::Error) => {
+                Err(format!("Invalid Time Format: {value}"))
+            }
+            _ => Ok(Self::Pattern(value.to_string())),
+        }
+    }
+}
+
+/// A column of exported boat data CSV.
+#[derive(Debug, Deserialize)]
+pub struct ColumnSpec {
+    /// The field written in the column, one of `CSV_FIELDS`.
+    field: String,
+    /// The header of the column, the default name of the field if not given.
+    header: Option<String>,
+    /// The format of the times in the column: `epoch_ms`, `rfc3339` or a strftime pattern.
+    /// Defaults to `epoch_ms`, and can only be given for the `time` field.
+    format: Option<String>,
+}
+
+/// A validated column of exported boat data CSV.
+#[derive(Debug)]
+struct CsvColumn {
+    field: CsvField,
+    header: String,
+    time_format: CsvTimeFormat,
+}
+
+impl CsvColumn {
+    /// Validates a column, naming the temperatures and depths in `units` by default.
+    fn new(spec: ColumnSpec, units: UnitSystem) -> Result<Self, AppError> {
+        let invalid = |reason: String| {
+            AppError::reason(
+                codes::CSV_INVALID_COLUMN,
+                &format!("Invalid Column {}", spec.field),
+                reason,
+            )
+            .with("column", spec.field.as_str())
+        };
+        let field: CsvField = spec.field.parse().map_err(invalid)?;
+        let time_format = match (field, spec.format) {
+            (CsvField::Time, Some(format)) => format.parse().map_err(invalid)?,
+            (_, Some(_)) => return Err(invalid(String::from("Only time columns have a format"))),
+            (_, None) => CsvTimeFormat::EpochMillis,
+        };
+        let header = spec.header.unwrap_or_else(|| match field {
+            CsvField::Temperature => units.temperature_column().to_string(),
+            CsvField::TemperatureRaw => units.temperature_raw_column().to_string(),
+            CsvField::Depth => units.depth_column().to_string(),
+            _ => spec.field.clone(),
+        });
+        Ok(Self {
+            field,
+            header,
+            time_format,
+        })
+    }
+
+    /// Gets the value of the column for a data point.
+    fn value(&self, feature: &BoatDataFeature) -> CsvValue {
+        match self.field {
+            CsvField::Id => CsvValue::Text(feature.id.clone().unwrap_or_default()),
+            CsvField::Time => match &self.time_format {
+                CsvTimeFormat::EpochMillis => CsvValue::Integer(feature.time.timestamp_millis()),
+                CsvTimeFormat::Rfc3339 => {
+                    CsvValue::Text(feature.time.to_rfc3339_opts(SecondsFormat::AutoSi, true))
+                }
+                CsvTimeFormat::Pattern(pattern) => {
+                    CsvValue::Text(feature.time.format(pattern).to_string())
+                }
+            },
+            CsvField::Lat => CsvValue::Number(feature.geometry.y()),
+            CsvField::Lng => CsvValue::Number(feature.geometry.x()),
+            CsvField::Depth => CsvValue::Number(feature.depth),
+            CsvField::Layer => CsvValue::Text(feature.layer.to_string()),
+            CsvField::Temperature => CsvValue::Number(feature.temperature),
+            CsvField::Sensor(sensor) => CsvValue::Optional(feature.sensor(sensor)),
+            CsvField::Note => CsvValue::Text(feature.note.clone().unwrap_or_default()),
+            CsvField::TemperatureRaw => CsvValue::Optional(feature.temperature_raw),
+            CsvField::SensorId => CsvValue::Text(feature.sensor_id.clone().unwrap_or_default()),
+        }
+    }
+}
+
+/// A value of exported boat data CSV, serialized like the fields of `BoatDataFeatureCSV`.
+#[derive(Debug, Serialize)]
+#[serde(untagged)]
+enum CsvValue {
+    Number(f64),
+    /// Written as an empty field when absent.
+    Optional(Option<f64>),
+    Integer(i64),
+    Text(String),
 }
 
 /// Export boat data in CSV format to the file system.
+///
+/// The temperatures and depths are converted to `units`, metric by default, and the names of
+/// their columns show the units, e.g. `temperature_f` and `depth_ft`.
+///
+/// `columns` selects the fields written and their order, headers and time format. Without it,
+/// every field is written with its default name.
+///
+/// If `dry_run` is `true`, nothing is written and a preview of the export is returned instead.
 #[tauri::command]
-pub fn export_data_csv(export_path: PathBuf, data: BoatData) -> Result<(), String> {
+pub fn export_data_csv(
+    export_path: PathBuf,
+    data: BoatData,
+    units: Option<UnitSystem>,
+    columns: Option<Vec<ColumnSpec>>,
+    dry_run: Option<bool>,
+) -> Result<Option<ExportPreview>, AppError> {
+    let units = units.unwrap_or_default();
+    let columns = columns
+        .map(|columns| {
+            columns
+                .into_iter()
+                .map(|spec| CsvColumn::new(spec, units))
+                .collect::<Result<Vec<_>, _>>()
+        })
+        .transpose()?;
+    if columns.as_ref().map_or(false, Vec::is_empty) {
+        return Err(AppError::new(
+            codes::CSV_NO_COLUMNS,
+            "Unable to Export CSV: No Columns Selected",
+        ));
+    }
+
+    let write = |file: &mut dyn std::io::Write| write_csv(file, &data, units, columns.as_deref());
+    if dry_run.unwrap_or(false) {
+        return export_preview::preview(&export_path, &data, "NaN", write).map(Some);
+    }
     log::debug!("Exporting to: {}", export_path.display());
-    let mut writer = csv::Writer::from_path(export_path).map_err(|e| e.to_string())?;
-    for record in data.features {
-        let record = BoatDataFeatureCSV::from(record);
-        writer.serialize(record).map_err(|e| e.to_string())?;
+    write_atomically(&export_path, write)?;
+    Ok(None)
+}
+
+/// Writes boat data CSV with the metadata as comments, writing `columns` if they are given.
+fn write_csv(
+    file: &mut dyn std::io::Write,
+    data: &BoatData,
+    units: UnitSystem,
+    columns: Option<&[CsvColumn]>,
+) -> std::io::Result<()> {
+    data.metadata().write_csv_comments(file)?;
+    let columns = match columns {
+        Some(v) => v,
+        None => {
+            let mut writer = csv_writer(file, units)?;
+            for record in data.features() {
+                writer.serialize(BoatDataFeatureCSV::from(convert_feature_units(
+                    record, units,
+                )))?;
+            }
+            return writer.flush();
+        }
+    };
+
+    let mut writer = csv::WriterBuilder::new()
+        .has_headers(false)
+        .from_writer(file);
+    writer.write_record(columns.iter().map(|column| &column.header))?;
+    for record in data.features() {
+        let record = convert_feature_units(record, units);
+        let values: Vec<_> = columns.iter().map(|column| column.value(&record)).collect();
+        writer.serialize(values)?;
     }
-    Ok(())
+    writer.flush()
 }
 
-/// Import boat data in CSV format from the file system.
+/// The number of rows written between `export-progress` events.
+const EXPORT_PROGRESS_ROWS: usize = 5000;
+
+/// Options to select the data points to export.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct ExportOptions {
+    /// Only export data points measured at or after this time.
+    start: Option<DateTime<Utc>>,
+    /// Only export data points measured at or before this time.
+    end: Option<DateTime<Utc>>,
+    /// Only export data points measured at this layer.
+    layer: Option<Layer>,
+    /// The units the temperatures and depths are exported in.
+    units: UnitSystem,
+}
+
+impl ExportOptions {
+    /// Checks if a data point should be exported.
+    fn includes(&self, feature: &BoatDataFeature) -> bool {
+        self.start.map_or(true, |start| feature.time >= start)
+            && self.end.map_or(true, |end| feature.time <= end)
+            && self
+                .layer
+                .as_ref()
+                .map_or(true, |layer| feature.layer == *layer)
+    }
+}
+
+/// Event payload of the progress of an export.
+#[derive(Debug, Serialize, Clone)]
+struct ExportProgressPayload {
+    /// The number of rows written.
+    rows: usize,
+}
+
+/// Export boat data in application storage in CSV format to the file system.
+///
+/// The data points are streamed from storage instead of being loaded into memory, emitting
+/// `export-progress` events as rows are written. Returns the number of rows written.
 #[tauri::command]
-pub fn import_data_csv(import_path: PathBuf) -> Result<BoatData, String> {
-    log::debug!("Importing from: {}", import_path.display());
-    Ok(match file::read_string(&import_path) {
-        Ok(v) => BoatData {
-            version: String::from("0.1.0"),
-            features: csv::Reader::from_reader(v.as_bytes())
-                .deserialize::<BoatDataFeatureCSV>()
-                .map(|v| v.map(BoatDataFeature::from))
-                .collect::<Result<Vec<_>, csv::Error>>()
-                .map_err(|e| e.to_string())?,
-        },
-        Err(api::Error::Io(e)) => match e.kind() {
-            ErrorKind::NotFound => {
-                log::warn!(
-                    "Unable to find Path: {}, using default BoatData",
-                    import_path.display()
-                );
-                BoatData::default()
+pub async fn export_stored_data_csv(
+    app_handle: AppHandle,
+    export_path: PathBuf,
+    options: Option<ExportOptions>,
+) -> Result<usize, AppError> {
+    log::debug!("Exporting to: {}", export_path.display());
+    let options = options.unwrap_or_default();
+    autosave::flush(&app_handle)?;
+    let data_path = active_data_file(&app_handle)?;
+    log::debug!("Application GeoJSON Path: {}", data_path.display());
+
+    let sqlite_storage = sqlite::enabled(&app_handle);
+    let metadata = app_handle.state::<AppData>().get(&app_handle)?.metadata();
+
+    tauri::async_runtime::spawn_blocking(move || {
+        let features: Box<dyn Iterator<Item = Result<BoatDataFeature, AppError>>> =
+            if sqlite_storage {
+                // The time index narrows the data points down before the other options
+                let data = sqlite::read_time_range(
+                    &sqlite::database_file(&data_path),
+                    options.start,
+                    options.end,
+                )?;
+                Box::new(data.features.into_iter().map(Ok))
+            } else {
+                let file =
+                    std::fs::File::open(&data_path).map_err(|e| AppError::io(e, &data_path))?;
+                let journal = journal::read_journal(&journal::journal_file(&data_path))?;
+                Box::new(
+                    geojson::FeatureReader::from_reader(std::io::BufReader::new(file))
+                        .features()
+                        .map(|feature| feature.map_err(invalid_feature).and_then(read_feature))
+                        .chain(journal.into_iter().map(Ok)),
+                )
+            };
+
+        let mut rows = 0;
+        let mut ids = FeatureIds::default();
+        write_atomically(&export_path, |file| {
+            metadata.write_csv_comments(file)?;
+            let mut writer = csv_writer(file, options.units)?;
+            for feature in features {
+                let mut feature =
+                    feature.map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+                ids.assign(&mut feature);
+                if !options.includes(&feature) {
+                    continue;
+                }
+
+                writer.serialize(BoatDataFeatureCSV::from(convert_feature_units(
+                    &feature,
+                    options.units,
+                )))?;
+                rows += 1;
+                if rows % EXPORT_PROGRESS_ROWS == 0 {
+                    let _ = app_handle.emit_all("export-progress", ExportProgressPayload { rows });
+                }
             }
-            _ => return Err(e.to_string()),
-        },
-        Err(e) => return Err(e.to_string()),
+            writer.flush()
+        })?;
+
+        let _ = app_handle.emit_all("export-progress", ExportProgressPayload { rows });
+        log::info!("Exported {} Rows", rows);
+        Ok(rows)
     })
+    .await
+    .map_err(|e| AppError::from(e.to_string()))?
+}
+
+/// The columns required in boat data CSV.
+const CSV_COLUMNS: [&str; 6] = ["temperature", "depth", "layer", "time", "lat", "lng"];
+
+/// The start of the comment lines before the header of boat data CSV.
+const CSV_COMMENT: u8 = b'#';
+
+/// The delimiters detected in boat data CSV.
+const CSV_DELIMITERS: [u8; 3] = [b',', b';', b'\t'];
+
+/// The numeric columns of boat data CSV, which can use decimal commas when semicolon-delimited.
+const CSV_DECIMAL_COLUMNS: [&str; 7] = [
+    "temperature",
+    "depth",
+    "lat",
+    "lng",
+    "ph",
+    "dissolved_oxygen",
+    "turbidity",
+];
+
+/// Detects the delimiter of CSV from the most used delimiter in its header.
+///
+/// Defaults to a comma when the header doesn't contain any delimiter.
+fn sniff_delimiter(content: &str) -> u8 {
+    let header = content[comments_len(content.as_bytes())..]
+        .lines()
+        .next()
+        .unwrap_or_default();
+    let mut delimiter = b',';
+    let mut max_count = 0;
+    for candidate in CSV_DELIMITERS {
+        let count = header.matches(char::from(candidate)).count();
+        if count > max_count {
+            delimiter = candidate;
+            max_count = count;
+        }
+    }
+    delimiter
+}
+
+/// Gets the length of the comment lines at the start of CSV, e.g. the metadata of the dataset
+/// written by the exports.
+fn comments_len(content: &[u8]) -> usize {
+    let mut len = 0;
+    for line in content.split_inclusive(|byte| *byte == b'\n') {
+        if !line.starts_with(&[CSV_COMMENT]) {
+            break;
+        }
+        len += line.len();
+    }
+    len
+}
+
+/// Gets the end of the header of CSV after its comment lines, `None` if the header doesn't end
+/// in `content`.
+fn header_end(content: &[u8]) -> Option<usize> {
+    let comments = comments_len(content);
+    csv_record_ends(&content[comments..])
+        .next()
+        .map(|end| comments + end)
+}
+
+/// Parser of times in boat data CSV that aren't epoch milliseconds.
+pub(crate) struct CsvTime {
+    /// The strftime pattern of the times, RFC 3339 if not given.
+    format: Option<String>,
+    /// The timezone of times without an offset.
+    timezone: Tz,
+}
+
+impl CsvTime {
+    /// Creates a parser of times with the strftime pattern `format` in the IANA `timezone`,
+    /// UTC by default.
+    pub(crate) fn new(format: Option<String>, timezone: Option<String>) -> Result<Self, AppError> {
+        let timezone = match timezone {
+            Some(v) => v.parse().map_err(|_| {
+                AppError::new(
+                    codes::CSV_INVALID_TIMEZONE,
+                    format!("Invalid Timezone: {v}"),
+                )
+                .with("timezone", v.as_str())
+            })?,
+            None => Tz::UTC,
+        };
+        Ok(Self { format, timezone })
+    }
+
+    /// Parses a time to milliseconds since the epoch.
+    ///
+    /// Local times that don't exist or are ambiguous in the timezone, e.g. during daylight
+    /// saving time transitions, are rejected.
+    fn to_millis(&self, value: &str) -> Result<i64, String> {
+        let format = match &self.format {
+            Some(v) => v,
+            None => {
+                return DateTime::parse_from_rfc3339(value)
+                    .map(|time| time.timestamp_millis())
+                    .map_err(|e| format!("Invalid Time {value}: {e}, a time format is required"));
+            }
+        };
+        // Formats with an offset don't need the timezone
+        if let Ok(time) = DateTime::parse_from_str(value, format) {
+            return Ok(time.timestamp_millis());
+        }
+        let local = NaiveDateTime::parse_from_str(value, format)
+            .map_err(|e| format!("Invalid Time {value}: {e}"))?;
+        match self.timezone.from_local_datetime(&local) {
+            LocalResult::Single(time) => Ok(time.timestamp_millis()),
+            LocalResult::None => Err(format!(
+                "Invalid Time {value}: Time does not exist in {}",
+                self.timezone.name()
+            )),
+            LocalResult::Ambiguous(..) => Err(format!(
+                "Invalid Time {value}: Time is ambiguous in {}",
+                self.timezone.name()
+            )),
+        }
+    }
+}
+
+/// The size of the pieces CSV is split into for parsing in parallel.
+const CSV_CHUNK_SIZE: usize = 4 << 20;
+
+/// Gets the end of each record of CSV, the byte after each line break outside of a quoted
+/// field.
+fn csv_record_ends(content: &[u8]) -> impl Iterator<Item = usize> + '_ {
+    let mut quoted = false;
+    content
+        .iter()
+        .enumerate()
+        .filter_map(move |(i, byte)| match byte {
+            b'"' => {
+                quoted = !quoted;
+                None
+            }
+            b'\n' if !quoted => Some(i + 1),
+            _ => None,
+        })
+}
+
+/// Counts the line breaks in CSV.
+fn line_breaks(content: &str) -> u64 {
+    content.bytes().filter(|byte| *byte == b'\n').count() as u64
+}
+
+/// Splits CSV records starting at line `line` into pieces of about `size` bytes, without
+/// splitting a record.
+///
+/// Returns each piece with the line it starts on.
+fn split_csv(content: &str, mut line: u64, size: usize) -> Vec<(u64, &str)> {
+    let mut chunks = vec![];
+    let mut start = 0;
+    for end in csv_record_ends(content.as_bytes()) {
+        if end - start >= size {
+            let chunk = &content[start..end];
+            chunks.push((line, chunk));
+            line += line_breaks(chunk);
+            start = end;
+        }
+    }
+    if start < content.len() {
+        chunks.push((line, &content[start..]));
+    }
+    chunks
+}
+
+/// Converts CSV records to data points.
+struct CsvRecordParser<'a> {
+    delimiter: u8,
+    /// The lowercase headers.
+    headers: csv::StringRecord,
+    time_column: usize,
+    /// The columns with decimal commas, empty unless the CSV is semicolon-delimited.
+    decimal_columns: Vec<usize>,
+    time: &'a CsvTime,
+    /// The units of the temperatures and depths.
+    units: UnitSystem,
+}
+
+impl<'a> CsvRecordParser<'a> {
+    /// Reads the header at the start of boat data CSV, detecting the delimiter when it is not
+    /// given.
+    ///
+    /// Returns the parser of the records and the length of the header.
+    fn new(
+        content: &str,
+        delimiter: Option<u8>,
+        time: &'a CsvTime,
+    ) -> Result<(Self, usize), AppError> {
+        let delimiter = delimiter.unwrap_or_else(|| sniff_delimiter(content));
+        log::debug!("CSV Delimiter: {:?}", char::from(delimiter));
+        let mut reader = csv::ReaderBuilder::new()
+            .delimiter(delimiter)
+            .flexible(true)
+            .trim(csv::Trim::Headers)
+            .comment(Some(CSV_COMMENT))
+            .from_reader(content.as_bytes());
+
+        let headers: csv::StringRecord = reader
+            .headers()
+            .map_err(|e| AppError::new(codes::CSV_INVALID, e.to_string()).with("row", 1))?
+            .iter()
+            .map(|header| header.trim_start_matches('\u{feff}').trim().to_lowercase())
+            .collect();
+        log::debug!("CSV Headers: {:?}", headers);
+        // CSV exported in imperial units has its own temperature and depth columns
+        let imperial = UnitSystem::Imperial;
+        let units = if headers.iter().any(|header| {
+            header == imperial.temperature_column() || header == imperial.depth_column()
+        }) {
+            UnitSystem::Imperial
+        } else {
+            UnitSystem::Metric
+        };
+        let headers: csv::StringRecord = headers
+            .iter()
+            .map(|header| {
+                if header == units.temperature_column() {
+                    UnitSystem::Metric.temperature_column()
+                } else if header == units.depth_column() {
+                    UnitSystem::Metric.depth_column()
+                } else {
+                    header
+                }
+            })
+            .collect();
+        if let Some(column) = CSV_COLUMNS
+            .iter()
+            .find(|column| !headers.iter().any(|header| header == **column))
+        {
+            let message = format!("Invalid Boat Data CSV: Missing Column \"{column}\" in Row 1");
+            return Err(AppError::new(codes::CSV_MISSING_COLUMN, message)
+                .with("column", *column)
+                .with("row", 1));
+        }
+        let decimal_columns = if delimiter == b';' {
+            headers
+                .iter()
+                .enumerate()
+                .filter(|(_, header)| CSV_DECIMAL_COLUMNS.contains(header))
+                .map(|(i, _)| i)
+                .collect()
+        } else {
+            vec![]
+        };
+
+        let parser = Self {
+            delimiter,
+            // The column exists as it is checked above
+            time_column: headers.iter().position(|header| header == "time").unwrap(),
+            headers,
+            decimal_columns,
+            time,
+            units,
+        };
+        Ok((
+            parser,
+            header_end(content.as_bytes()).unwrap_or(content.len()),
+        ))
+    }
+
+    /// Converts the record on line `row` to a data point.
+    fn parse(&self, mut record: csv::StringRecord, row: u64) -> Result<BoatDataFeature, AppError> {
+        if !self.decimal_columns.is_empty() {
+            record = record
+                .iter()
+                .enumerate()
+                .map(|(i, field)| {
+                    if self.decimal_columns.contains(&i) {
+                        field.replace(',', ".")
+                    } else {
+                        field.to_string()
+                    }
+                })
+                .collect();
+        }
+        let value = record.get(self.time_column).unwrap_or_default().trim();
+        if !value.is_empty() && value.parse::<i64>().is_err() {
+            let millis = self.time.to_millis(value).map_err(|e| {
+                let message = format!("Invalid Boat Data CSV: Row {row}: {e}");
+                AppError::new(codes::CSV_INVALID_TIME, message)
+                    .with("row", row)
+                    .with("value", value)
+            })?;
+            record = record
+                .iter()
+                .enumerate()
+                .map(|(i, field)| {
+                    if i == self.time_column {
+                        millis.to_string()
+                    } else {
+                        field.to_string()
+                    }
+                })
+                .collect();
+        }
+        let feature = record
+            .deserialize::<BoatDataFeatureCSV>(Some(&self.headers))
+            .map_err(|e| {
+                let message = format!("Invalid Boat Data CSV: Row {row}: {e}");
+                AppError::new(codes::CSV_INVALID_ROW, message).with("row", row)
+            })?;
+        Ok(convert_feature_to_stored_units(
+            &BoatDataFeature::from(feature),
+            self.units,
+        ))
+    }
+
+    /// Converts the records of a piece of CSV without its header, starting on line `line`.
+    ///
+    /// Each row parsed is counted by `task`, stopping if the import is cancelled.
+    fn parse_chunk(
+        &self,
+        chunk: &str,
+        line: u64,
+        task: &ImportTask,
+    ) -> Result<Vec<(u64, BoatDataFeature)>, AppError> {
+        let mut reader = csv::ReaderBuilder::new()
+            .delimiter(self.delimiter)
+            .flexible(true)
+            .has_headers(false)
+            .from_reader(chunk.as_bytes());
+
+        let mut features = vec![];
+        for record in reader.records() {
+            let record = record.map_err(|e| {
+                let row = e.position().map(|p| line + p.line() - 1);
+                AppError::new(codes::CSV_INVALID, format!("Invalid Boat Data CSV: {e}"))
+                    .with("row", row)
+            })?;
+            let row = line + record.position().map_or(1, |p| p.line()) - 1;
+            features.push((row, self.parse(record, row)?));
+            task.row_parsed()?;
+        }
+        Ok(features)
+    }
+}
+
+/// Parses the data points in boat data CSV.
+///
+/// The delimiter is detected when it is not given. The columns can be in any order, the
+/// header names are case-insensitive and extra columns are ignored. Comment lines before the
+/// header, e.g. the metadata written by the exports, are skipped.
+///
+/// Semicolon-delimited CSV can use decimal commas in the numeric columns, and times that aren't
+/// epoch milliseconds are parsed by `time`. CSV exported in imperial units, with `temperature_f`
+/// and `depth_ft` columns, is converted back to degrees Celsius and meters.
+///
+/// Returns the data points with the lines they are on. The rows parsed are reported to `task`,
+/// stopping if the import is cancelled.
+fn parse_csv(
+    content: &str,
+    delimiter: Option<u8>,
+    time: &CsvTime,
+    task: &ImportTask,
+) -> Result<Vec<(u64, BoatDataFeature)>, AppError> {
+    let (parser, header_end) = CsvRecordParser::new(content, delimiter, time)?;
+    let (header, body) = content.split_at(header_end);
+    parser.parse_chunk(body, 1 + line_breaks(header), task)
+}
+
+/// Parses the data points in boat data CSV like [`parse_csv`] on every core, while the CSV is
+/// read from `file`.
+///
+/// The CSV is read in batches of about `batch_size` bytes, which are split on the records into
+/// pieces of [`CSV_CHUNK_SIZE`] bytes parsed in parallel. Only one batch is in memory at a time
+/// besides the data points, so unlike reading the whole file first, the memory used doesn't
+/// grow with the size of the file beyond the data points parsed.
+///
+/// The rows parsed are counted by every thread and reported to `task`, stopping if the import
+/// is cancelled.
+fn parse_csv_parallel(
+    mut file: impl Read,
+    delimiter: Option<u8>,
+    time: &CsvTime,
+    batch_size: usize,
+    task: &mut ImportTask,
+) -> Result<Vec<(u64, BoatDataFeature)>, AppError> {
+    log::debug!("Parsing CSV in Batches of {} Bytes", batch_size);
+    // The CSV read that isn't parsed yet, starting at a record
+    let mut pending = Vec::with_capacity(batch_size);
+    let mut parser = None;
+    let mut line = 1;
+    let mut features = vec![];
+    loop {
+        let read = task.read(&mut file, &mut pending, batch_size)?;
+        // The last record of the batch is left for the next one until the end of the file
+        let end = if read == 0 {
+            pending.len()
+        } else {
+            match csv_record_ends(&pending).last() {
+                // The parser is only created once the header is read
+                Some(v) if parser.is_some() || header_end(&pending).is_some() => v,
+                _ => continue,
+            }
+        };
+        let batch = std::str::from_utf8(&pending[..end])
+            .map_err(|e| AppError::new(codes::IO_OTHER, e.to_string()))?;
+
+        let mut body = batch;
+        if parser.is_none() {
+            let (header_parser, header_end) = CsvRecordParser::new(batch, delimiter, time)?;
+            let (header, rest) = batch.split_at(header_end);
+            line += line_breaks(header);
+            body = rest;
+            parser = Some(header_parser);
+        }
+        // The parser is created from the first batch above
+        let records = parser.as_ref().unwrap();
+        let chunks = split_csv(body, line, CSV_CHUNK_SIZE);
+        line += line_breaks(body);
+
+        let shared: &ImportTask = task;
+        let results: Vec<_> = chunks
+            .into_par_iter()
+            .map(|(line, chunk)| records.parse_chunk(chunk, line, shared))
+            .collect();
+        // The errors are checked in order so the first invalid row is reported
+        for result in results {
+            features.extend(result?);
+        }
+
+        pending.drain(..end);
+        if read == 0 {
+            task.rows_parsed(features.len())?;
+            return Ok(features);
+        }
+    }
+}
+
+/// Import boat data in CSV format from the file system.
+///
+/// The delimiter is detected from the header when `delimiter` is not given. Times are epoch
+/// milliseconds or RFC 3339, or are parsed with the strftime pattern `time_format` in the IANA
+/// `timezone`, UTC by default. Local times skipped or repeated by daylight saving time are
+/// rejected with their row.
+///
+/// Files with data points out of range are rejected, unless `skip_invalid` is `true` in which
+/// case the invalid rows are skipped and listed in the result. Repeated data points are removed
+/// unless `dedup` is `false`.
+///
+/// The file is imported in the background like `import_data`, returning the id of the import.
+/// It is parsed on every core unless `parallel_csv_import` is turned off in the settings.
+#[tauri::command]
+pub async fn import_data_csv(
+    app_handle: AppHandle,
+    import_path: PathBuf,
+    delimiter: Option<char>,
+    dedup: Option<bool>,
+    skip_invalid: Option<bool>,
+    timezone: Option<String>,
+    time_format: Option<String>,
+) -> Result<u64, AppError> {
+    log::debug!("Importing from: {}", import_path.display());
+    let delimiter = delimiter
+        .map(|d| {
+            u8::try_from(d).map_err(|_| {
+                AppError::new(
+                    codes::CSV_INVALID_DELIMITER,
+                    format!("Invalid CSV Delimiter: {d}"),
+                )
+                .with("delimiter", d.to_string())
+            })
+        })
+        .transpose()?;
+    let time = CsvTime::new(time_format, timezone)?;
+    let parallel = app_handle
+        .state::<SettingsState>()
+        .get()
+        .parallel_csv_import;
+    let operation = Operation::start(
+        "import_data_csv",
+        json!({ "path": import_path, "dedup": dedup, "skip_invalid": skip_invalid }),
+    );
+    Ok(imports::spawn_import(app_handle, operation, move |task| {
+        let data = read_csv(task, &import_path, delimiter, &time, parallel)?;
+        match data {
+            Some((data, rows)) => ImportedData::new(data, Some(&rows), dedup, skip_invalid),
+            None => ImportedData::new(BoatData::default(), None, dedup, skip_invalid),
+        }
+    }))
+}
+
+/// Reads the boat data in the CSV file at `path` for an import, with the line of each data
+/// point.
+///
+/// The file is read as it is parsed on every core if `parallel`, else it is read whole first.
+///
+/// Returns `None` if the file doesn't exist.
+pub(crate) fn read_csv(
+    task: &mut ImportTask,
+    path: &Path,
+    delimiter: Option<u8>,
+    time: &CsvTime,
+    parallel: bool,
+) -> Result<Option<(BoatData, Vec<u64>)>, AppError> {
+    let features = if parallel {
+        task.open(path)?
+            .map(|file| {
+                // A piece for each thread
+                let batch_size = CSV_CHUNK_SIZE * rayon::current_num_threads();
+                parse_csv_parallel(file, delimiter, time, batch_size, task)
+            })
+            .transpose()?
+    } else {
+        task.read_to_string(path)?
+            .map(|content| parse_csv(&content, delimiter, time, task))
+            .transpose()?
+    };
+    let features = match features {
+        Some(v) => v,
+        None => {
+            log::warn!(
+                "Unable to find Path: {}, using default BoatData",
+                path.display()
+            );
+            return Ok(None);
+        }
+    };
+    let (rows, features) = features.into_iter().unzip();
+    let data = BoatData::new(String::from(migrations::BOAT_DATA_VERSION), features);
+    Ok(Some((data, rows)))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{atomic::AtomicBool, Arc};
+
+    use chrono::TimeZone;
+
+    use super::*;
+    use crate::protocol::tests::Random;
+
+    /// Boat data CSV in the column order of the exports.
+    const CSV: &str = "temperature,depth,layer,time,lat,lng\n\
+        28.5,0.5,surface,1715495400000,3.1,101.5\n\
+        29.25,1,middle,1715495460000,3.1001,101.5002\n";
+
+    /// The data in [`CSV`] with a byte order mark, the columns in another order and the
+    /// header names capitalized and padded with spaces.
+    const REORDERED_CSV: &str = "\u{feff} Time ,LNG,Lat,Layer,Depth,TEMPERATURE\n\
+        1715495400000,101.5,3.1,surface,0.5,28.5\n\
+        1715495460000,101.5002,3.1001,middle,1,29.25\n";
+
+    /// The data in [`CSV`] saved by Excel with an index column and a comment column.
+    const EXTRA_COLUMNS_CSV: &str = ",temperature,depth,layer,time,lat,lng,comment\n\
+        0,28.5,0.5,surface,1715495400000,3.1,101.5,calm water\n\
+        1,29.25,1,middle,1715495460000,3.1001,101.5002,\n";
+
+    /// The data in [`CSV`] saved by Excel in a European locale, with CRLF line breaks and
+    /// decimal commas.
+    const SEMICOLON_CSV: &str = "Temperature;Depth;Layer;Time;Lat;Lng\r\n\
+        28,5;0,5;surface;1715495400000;3,1;101,5\r\n\
+        29,25;1;middle;1715495460000;3,1001;101,5002\r\n";
+
+    /// The data in [`CSV`] copied from a spreadsheet, which is tab-delimited.
+    const TAB_CSV: &str = "temperature\tdepth\ttemperature_unit\tlayer\ttime\tlat\tlng\n\
+        28.5\t0.5\tC\tsurface\t1715495400000\t3.1\t101.5\n\
+        29.25\t1\tC\tmiddle\t1715495460000\t3.1001\t101.5002\n";
+
+    /// Semicolon-delimited CSV with a comment column containing commas.
+    const COMMENT_CSV: &str = "temperature;depth;layer;time;lat;lng;comment\n\
+        28,5;0,5;surface;1715495400000;3,1;101,5;bottle 3, jetty\n";
+
+    /// Semicolon-delimited CSV with a single data point and no line break at the end.
+    const ONE_ROW_CSV: &str = "temperature;depth;layer;time;lat;lng\n\
+        28,5;0,5;surface;1715495400000;3,1;101,5";
+
+    /// The temperature, depth, layer, time and location of a data point.
+    type Values = (f64, f64, Layer, DateTime<Utc>, Point<f64>);
+
+    /// Gets the values of data points for comparing them.
+    fn values(features: &[BoatDataFeature]) -> Vec<Values> {
+        features
+            .iter()
+            .map(|f| {
+                (
+                    f.temperature(),
+                    f.depth(),
+                    f.layer(),
+                    f.time(),
+                    f.geometry(),
+                )
+            })
+            .collect()
+    }
+
+    /// Creates the task of an import that isn't cancelled.
+    fn task() -> ImportTask {
+        ImportTask::new(0, Arc::new(AtomicBool::new(false)), |_| {})
+    }
+
+    /// Parses the data points in boat data CSV without their lines.
+    fn parse(content: &str, delimiter: Option<u8>) -> Result<Vec<BoatDataFeature>, String> {
+        let time = CsvTime {
+            format: None,
+            timezone: Tz::UTC,
+        };
+        parse_csv(content, delimiter, &time, &task())
+            .map(|features| features.into_iter().map(|(_, feature)| feature).collect())
+            .map_err(String::from)
+    }
+
+    /// Creates a data point measured at `layer`, `seconds` after the start of the mission.
+    fn feature(layer: Layer, seconds: i64) -> BoatDataFeature {
+        BoatDataFeature::new(
+            28.5,
+            0.5,
+            layer,
+            Utc.timestamp_opt(1_715_495_400 + seconds, 0).unwrap(),
+            Point::new(101.5, 3.1),
+        )
+    }
+
+    /// Creates boat data with a data point at each of `layers`.
+    fn data(layers: &[Layer]) -> BoatData {
+        let features = layers
+            .iter()
+            .enumerate()
+            .map(|(i, layer)| feature(layer.clone(), i as i64))
+            .collect();
+        BoatData::new(BoatData::default().version().to_string(), features)
+    }
+
+    /// Parses boat data with a data point at each of `points`, given as the seconds after the
+    /// start of the mission and the longitude.
+    fn track(points: &[(i64, f64)]) -> BoatData {
+        let features: Vec<_> = points
+            .iter()
+            .map(|(seconds, lng)| {
+                serde_json::json!({
+                    "type": "Feature",
+                    "geometry": { "type": "Point", "coordinates": [lng, 3.1] },
+                    "properties": {
+                        "temperature": 28.5,
+                        "depth": 0.5,
+                        "layer": "surface",
+                        "time": Utc.timestamp_opt(1_715_495_400 + seconds, 0).unwrap(),
+                    },
+                })
+            })
+            .collect();
+        serde_json::json!({
+            "type": "FeatureCollection",
+            "version": BoatData::default().version(),
+            "features": features,
+        })
+        .to_string()
+        .parse()
+        .unwrap()
+    }
+
+    /// Gets the seconds after the start of the mission and the longitude of the data points.
+    fn keys(data: &BoatData) -> Vec<(i64, f64)> {
+        data.features()
+            .iter()
+            .map(|f| (f.time().timestamp() - 1_715_495_400, f.geometry().x()))
+            .collect()
+    }
+
+    #[test]
+    fn merge_appends_only_new_data_points() {
+        let mut data = track(&[(0, 101.5), (1, 101.5)]);
+
+        data.merge(track(&[(1, 101.5), (1, 101.6), (2, 101.5)]))
+            .unwrap();
+
+        assert_eq!(
+            keys(&data),
+            vec![(0, 101.5), (1, 101.5), (1, 101.6), (2, 101.5)]
+        );
+    }
+
+    #[test]
+    fn merge_rejects_other_versions() {
+        let mut data = track(&[(0, 101.5)]);
+        let mut other = track(&[(1, 101.5)]);
+        other.version = String::from("0.0.1");
+
+        assert!(data.merge(other).is_err());
+        assert_eq!(keys(&data), vec![(0, 101.5)]);
+    }
+
+    #[test]
+    fn data_points_are_pushed_and_extended_in_order() {
+        let time = Utc.timestamp_opt(1_715_495_400, 0).unwrap();
+        let feature =
+            |lng| BoatDataFeature::new(28.5, 0.5, Layer::Surface, time, Point::new(lng, 3.1));
+        let mut data = BoatData::new(String::from("0.1.0"), vec![feature(101.5)]);
+
+        data.push(feature(101.6));
+        data.extend(vec![feature(101.7), feature(101.8)]);
+
+        assert_eq!(data.version(), "0.1.0");
+        let lngs: Vec<_> = (&data).into_iter().map(|f| f.geometry().x()).collect();
+        assert_eq!(lngs, vec![101.5, 101.6, 101.7, 101.8]);
+        let temperatures: Vec<_> = data.into_iter().map(|f| f.temperature()).collect();
+        assert_eq!(temperatures, vec![28.5; 4]);
+    }
+
+    #[test]
+    fn time_range_includes_its_bounds() {
+        let data = track(&[(0, 101.5), (10, 101.5), (20, 101.5), (30, 101.5)]);
+        let at = |seconds: i64| Some(Utc.timestamp_opt(1_715_495_400 + seconds, 0).unwrap());
+
+        let filtered = filter_data(data.clone(), at(10), at(20));
+        assert_eq!(filtered.version(), data.version());
+        assert_eq!(keys(&filtered), vec![(10, 101.5), (20, 101.5)]);
+        assert_eq!(
+            keys(&data.filter_time_range(at(15), None)),
+            vec![(20, 101.5), (30, 101.5)]
+        );
+        assert_eq!(keys(&data.filter_time_range(None, at(0))), vec![(0, 101.5)]);
+        assert_eq!(keys(&data.filter_time_range(None, None)).len(), 4);
+        assert!(data.filter_time_range(at(20), at(10)).features().is_empty());
+    }
+
+    #[test]
+    fn filter_layer_keeps_matching_features_in_order() {
+        let data = data(&[Layer::Surface, Layer::SeaBed, Layer::Surface, Layer::Middle]);
+        let surface = data.filter_layer(Layer::Surface);
+
+        assert_eq!(surface.version(), data.version());
+        let times: Vec<_> = surface.features().iter().map(|f| f.time()).collect();
+        assert_eq!(
+            times,
+            vec![data.features()[0].time(), data.features()[2].time()]
+        );
+        assert!(surface
+            .features()
+            .iter()
+            .all(|f| f.layer() == Layer::Surface));
+    }
+
+    #[test]
+    fn filter_layer_without_matches_is_empty() {
+        let data = data(&[Layer::Surface, Layer::Middle]);
+        assert!(data.filter_layer(Layer::SeaBed).features().is_empty());
+        assert!(BoatData::default()
+            .filter_layer(Layer::Surface)
+            .features()
+            .is_empty());
+    }
+
+    #[test]
+    fn layer_parses_geojson_names() {
+        assert_eq!("surface".parse(), Ok(Layer::Surface));
+        assert_eq!("middle".parse(), Ok(Layer::Middle));
+        assert_eq!("sea bed".parse(), Ok(Layer::SeaBed));
+        for name in ["bottom", "Surface", "sea_bed"] {
+            assert_eq!(name.parse(), Ok(Layer::Other(name.to_string())));
+        }
+        for name in ["", " "] {
+            assert!(name.parse::<Layer>().is_err(), "{name:?} parsed");
+        }
+    }
+
+    #[test]
+    fn filter_data_layer_rejects_empty_layer() {
+        let error = filter_data_layer(data(&[Layer::Surface]), String::new()).unwrap_err();
+        assert_eq!(
+            error,
+            AppError::new(
+                codes::DATA_INVALID_LAYER,
+                "Invalid Layer: Empty Name, expected e.g. surface, middle or sea bed",
+            )
+            .with("layer", "")
+        );
+        let surface = filter_data_layer(data(&[Layer::Surface]), String::from("surface")).unwrap();
+        assert_eq!(surface.features().len(), 1);
+    }
+
+    #[test]
+    fn parse_csv_reads_export_column_order() {
+        let features = parse(CSV, None).unwrap();
+
+        assert_eq!(
+            values(&features),
+            vec![
+                (
+                    28.5,
+                    0.5,
+                    Layer::Surface,
+                    Utc.timestamp_millis_opt(1_715_495_400_000).unwrap(),
+                    Point::new(101.5, 3.1)
+                ),
+                (
+                    29.25,
+                    1.0,
+                    Layer::Middle,
+                    Utc.timestamp_millis_opt(1_715_495_460_000).unwrap(),
+                    Point::new(101.5002, 3.1001)
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_csv_accepts_reordered_columns_and_byte_order_mark() {
+        let expected = values(&parse(CSV, None).unwrap());
+        assert_eq!(values(&parse(REORDERED_CSV, None).unwrap()), expected);
+    }
+
+    #[test]
+    fn parse_csv_ignores_extra_columns() {
+        let expected = values(&parse(CSV, None).unwrap());
+        assert_eq!(values(&parse(EXTRA_COLUMNS_CSV, None).unwrap()), expected);
+    }
+
+    #[test]
+    fn parse_csv_names_missing_column() {
+        let error = parse(
+            "Temperature,Depth,Time,Lat,Lng\n28.5,0.5,1715495400000,3.1,101.5\n",
+            None,
+        )
+        .unwrap_err();
+
+        assert!(error.contains("\"layer\""), "{error}");
+        assert!(error.contains("Row 1"), "{error}");
+    }
+
+    #[test]
+    fn parse_csv_names_invalid_row() {
+        let csv = format!("{CSV}30,2,,1715495520000,3.1002,101.5004\n");
+
+        let error = parse(&csv, None).unwrap_err();
+
+        assert!(error.contains("Row 4"), "{error}");
+    }
+
+    #[test]
+    fn sniff_delimiter_detects_samples() {
+        assert_eq!(sniff_delimiter(CSV), b',');
+        assert_eq!(sniff_delimiter(SEMICOLON_CSV), b';');
+        assert_eq!(sniff_delimiter(TAB_CSV), b'\t');
+    }
+
+    #[test]
+    fn sniff_delimiter_detects_one_row_file() {
+        assert_eq!(sniff_delimiter(ONE_ROW_CSV), b';');
+        assert_eq!(
+            sniff_delimiter("temperature,depth,layer,time,lat,lng"),
+            b','
+        );
+    }
+
+    #[test]
+    fn sniff_delimiter_defaults_to_comma() {
+        assert_eq!(sniff_delimiter(""), b',');
+        assert_eq!(sniff_delimiter("temperature\n28.5\n"), b',');
+    }
+
+    #[test]
+    fn parse_csv_reads_every_delimiter_alike() {
+        let expected = values(&parse(CSV, None).unwrap());
+        assert_eq!(values(&parse(SEMICOLON_CSV, None).unwrap()), expected);
+        assert_eq!(values(&parse(TAB_CSV, None).unwrap()), expected);
+    }
+
+    #[test]
+    fn parse_csv_reads_decimal_commas_of_numeric_columns_only() {
+        let features = parse(COMMENT_CSV, None).unwrap();
+
+        assert_eq!(features.len(), 1);
+        assert_eq!(features[0].temperature(), 28.5);
+        assert_eq!(features[0].depth(), 0.5);
+        assert_eq!(features[0].geometry(), Point::new(101.5, 3.1));
+    }
+
+    #[test]
+    fn parse_csv_reads_one_row_file() {
+        let features = parse(ONE_ROW_CSV, None).unwrap();
+
+        assert_eq!(features.len(), 1);
+        assert_eq!(features[0].temperature(), 28.5);
+    }
+
+    #[test]
+    fn parse_csv_uses_given_delimiter() {
+        let content = "temperature;depth;layer;time;lat;lng\n28.5;0.5;surface;0;3.1;101.5\n";
+        assert_eq!(parse(content, Some(b';')).unwrap().len(), 1);
+
+        let error = parse(CSV, Some(b';')).unwrap_err();
+        assert!(error.contains("Missing Column"), "{error}");
+    }
+
+    #[test]
+    fn export_options_include_everything_by_default() {
+        let options = ExportOptions::default();
+        assert!(data(&[Layer::Surface, Layer::Middle, Layer::SeaBed])
+            .features()
+            .iter()
+            .all(|f| options.includes(f)));
+    }
+
+    #[test]
+    fn export_options_filter_by_time_range_and_layer() {
+        let data = data(&[
+            Layer::Surface,
+            Layer::Middle,
+            Layer::Surface,
+            Layer::Surface,
+        ]);
+        let options = ExportOptions {
+            start: Some(data.features()[1].time()),
+            end: Some(data.features()[2].time()),
+            layer: Some(Layer::Surface),
+            units: UnitSystem::Metric,
+        };
+
+        let included: Vec<_> = data
+            .features()
+            .iter()
+            .map(|f| options.includes(f))
+            .collect();
+        assert_eq!(included, vec![false, false, true, false]);
+    }
+
+    /// Creates boat data with a data point at each of `points`, given as `(lng, lat)`.
+    fn points(points: &[(f64, f64)]) -> BoatData {
+        let features = points
+            .iter()
+            .map(|(lng, lat)| {
+                let mut feature = feature(Layer::Surface, 0);
+                feature.geometry = Point::new(*lng, *lat);
+                feature
+            })
+            .collect();
+        BoatData::new(BoatData::default().version().to_string(), features)
+    }
+
+    /// Gets the `(lng, lat)` of the data points.
+    fn coordinates(data: &BoatData) -> Vec<(f64, f64)> {
+        data.features()
+            .iter()
+            .map(|f| (f.geometry().x(), f.geometry().y()))
+            .collect()
+    }
+
+    #[test]
+    fn within_bbox_includes_points_on_boundary() {
+        let data = points(&[
+            (101.0, 3.0),
+            (102.0, 4.0),
+            (101.5, 3.0),
+            (101.0, 3.5),
+            (101.5, 3.5),
+            (100.9999, 3.5),
+            (101.5, 4.0001),
+        ]);
+
+        let inside = data.within_bbox(101.0, 3.0, 102.0, 4.0);
+
+        assert_eq!(
+            coordinates(&inside),
+            vec![
+                (101.0, 3.0),
+                (102.0, 4.0),
+                (101.5, 3.0),
+                (101.0, 3.5),
+                (101.5, 3.5)
+            ]
+        );
+    }
+
+    #[test]
+    fn within_bbox_splits_box_crossing_antimeridian() {
+        let data = points(&[
+            (179.5, 0.0),
+            (-179.5, 0.0),
+            (170.0, 0.0),
+            (180.0, 0.0),
+            (-180.0, 0.0),
+            (0.0, 0.0),
+            (-170.0, 0.0),
+        ]);
+
+        let inside = data.within_bbox(170.0, -1.0, -170.0, 1.0);
+
+        assert_eq!(
+            coordinates(&inside),
+            vec![
+                (179.5, 0.0),
+                (-179.5, 0.0),
+                (170.0, 0.0),
+                (180.0, 0.0),
+                (-180.0, 0.0),
+                (-170.0, 0.0)
+            ]
+        );
+    }
+
+    /// Creates boat data with a data point at each of `points`, given as `(lng, lat, seconds)`.
+    fn timed_points(points: &[(f64, f64, i64)]) -> BoatData {
+        let features = points
+            .iter()
+            .map(|(lng, lat, seconds)| {
+                let mut feature = feature(Layer::Surface, *seconds);
+                feature.geometry = Point::new(*lng, *lat);
+                feature
+            })
+            .collect();
+        BoatData::new(BoatData::default().version().to_string(), features)
+    }
+
+    #[test]
+    fn nearest_wraps_around_antimeridian() {
+        // Euclidean distance puts the point at -179.9 on the other side of the globe
+        let data = timed_points(&[(178.0, 0.0, 0), (-179.9, 0.0, 1)]);
+
+        let nearest = data.nearest(Point::new(179.9, 0.0), None).unwrap();
+
+        assert_eq!(nearest.geometry(), Point::new(-179.9, 0.0));
+    }
+
+    #[test]
+    fn nearest_accounts_for_converging_meridians_near_poles() {
+        // Near the pole, 90 degrees of longitude is shorter than 1 degree of latitude
+        let data = timed_points(&[(0.0, 88.0, 0), (90.0, 89.5, 1)]);
+
+        let nearest = data.nearest(Point::new(0.0, 89.5), None).unwrap();
+
+        assert_eq!(nearest.geometry(), Point::new(90.0, 89.5));
+    }
+
+    #[test]
+    fn nearest_prefers_most_recent_of_equally_near_points() {
+        let data = timed_points(&[(101.5, 3.1, 0), (101.5, 3.1, 60), (101.5, 3.1, 30)]);
+
+        let nearest = data.nearest(Point::new(101.6, 3.2), None).unwrap();
+
+        assert_eq!(nearest.time(), data.features()[1].time());
+    }
+
+    #[test]
+    fn nearest_ignores_points_beyond_max_distance() {
+        // 0.01 degrees of latitude is about 1.1 km
+        let data = timed_points(&[(101.5, 3.11, 0)]);
+
+        assert!(data.nearest(Point::new(101.5, 3.1), Some(1000.0)).is_none());
+        assert!(data.nearest(Point::new(101.5, 3.1), Some(1200.0)).is_some());
+        assert!(BoatData::default()
+            .nearest(Point::new(101.5, 3.1), None)
+            .is_none());
+    }
+
+    #[test]
+    fn dedup_removes_repeated_points_keeping_first() {
+        let mut first = feature(Layer::Surface, 0);
+        first.temperature = 28.0;
+        let mut repeated = feature(Layer::Surface, 0);
+        repeated.temperature = 30.0;
+        let mut data = BoatData::new(
+            BoatData::default().version().to_string(),
+            vec![
+                first,
+                feature(Layer::Surface, 1),
+                repeated,
+                feature(Layer::Surface, 1),
+            ],
+        );
+
+        assert_eq!(data.dedup(), 2);
+
+        assert_eq!(data.features().len(), 2);
+        assert_eq!(data.features()[0].temperature(), 28.0);
+        assert_eq!(data.dedup(), 0);
+    }
+
+    #[test]
+    fn dedup_keeps_points_differing_in_depth_layer_or_coordinate_bits() {
+        let mut deeper = feature(Layer::Surface, 0);
+        deeper.depth = 1.5;
+        let mut negative_zero = feature(Layer::Surface, 0);
+        negative_zero.geometry = Point::new(-0.0, 0.0);
+        let mut zero = feature(Layer::Surface, 0);
+        zero.geometry = Point::new(0.0, 0.0);
+        let mut data = BoatData::new(
+            BoatData::default().version().to_string(),
+            vec![
+                feature(Layer::Surface, 0),
+                deeper,
+                feature(Layer::Middle, 0),
+                negative_zero,
+                zero,
+            ],
+        );
+
+        assert_eq!(data.dedup(), 0);
+        assert_eq!(data.features().len(), 5);
+    }
+
+    #[test]
+    fn parse_csv_converts_imperial_export() {
+        let data = BoatData::new(
+            BoatData::default().version().to_string(),
+            parse(CSV, None).unwrap(),
+        );
+        let mut content = vec![];
+        let mut writer = csv_writer(&mut content, UnitSystem::Imperial).unwrap();
+        for feature in data.features() {
+            writer
+                .serialize(BoatDataFeatureCSV::from(convert_feature_units(
+                    feature,
+                    UnitSystem::Imperial,
+                )))
+                .unwrap();
+        }
+        writer.flush().unwrap();
+        drop(writer);
+        let content = String::from_utf8(content).unwrap();
+        assert!(content.starts_with("temperature_f,depth_ft,"));
+
+        let features = parse(&content, None).unwrap();
+
+        assert_eq!(features.len(), 2);
+        for (imported, expected) in features.iter().zip(data.features()) {
+            assert!((imported.temperature() - expected.temperature()).abs() < 1e-9);
+            assert!((imported.depth() - expected.depth()).abs() < 1e-9);
+            assert_eq!(imported.time(), expected.time());
+        }
+    }
+
+    #[test]
+    fn parse_csv_returns_lines_of_data_points() {
+        let time = CsvTime {
+            format: None,
+            timezone: Tz::UTC,
+        };
+        let rows: Vec<_> = parse_csv(CSV, None, &time, &task())
+            .unwrap()
+            .into_iter()
+            .map(|(row, _)| row)
+            .collect();
+        assert_eq!(rows, vec![2, 3]);
+    }
+
+    #[test]
+    fn validate_accepts_valid_data() {
+        assert_eq!(
+            data(&[Layer::Surface, Layer::Middle, Layer::SeaBed]).validate(),
+            vec![]
+        );
+    }
+
+    #[test]
+    fn validate_reports_each_problem() {
+        let mut outside = feature(Layer::Surface, 1);
+        outside.geometry = Point::new(181.0, -91.0);
+        let mut not_numbers = feature(Layer::Surface, 2);
+        not_numbers.temperature = f64::NAN;
+        not_numbers.depth = f64::INFINITY;
+        let mut negative = feature(Layer::Surface, 3);
+        negative.depth = -0.5;
+        let mut old = feature(Layer::Surface, 4);
+        old.time = Utc.with_ymd_and_hms(1999, 12, 31, 23, 59, 59).unwrap();
+        let mut future = feature(Layer::Surface, 5);
+        future.time = Utc.with_ymd_and_hms(2100, 1, 1, 0, 0, 0).unwrap();
+        let data = BoatData::new(
+            BoatData::default().version().to_string(),
+            vec![
+                feature(Layer::Surface, 0),
+                outside,
+                not_numbers,
+                negative,
+                old,
+                future,
+            ],
+        );
+
+        let issues: Vec<_> = data
+            .validate()
+            .into_iter()
+            .map(|issue| (issue.index, issue.reason))
+            .collect();
+
+        assert_eq!(
+            issues,
+            vec![
+                (1, String::from("Longitude 181 is not within [-180, 180]")),
+                (1, String::from("Latitude -91 is not within [-90, 90]")),
+                (2, String::from("Temperature NaN is not a number")),
+                (2, String::from("Depth inf is not a number")),
+                (3, String::from("Depth -0.5 is negative")),
+                (
+                    4,
+                    String::from("Time 1999-12-31T23:59:59+00:00 is not between 2000 and 2100")
+                ),
+                (
+                    5,
+                    String::from("Time 2100-01-01T00:00:00+00:00 is not between 2000 and 2100")
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn import_rejects_invalid_rows_by_default() {
+        let csv = format!("{CSV}30,2,surface,1715495520000,512,101.5\n");
+        let time = CsvTime {
+            format: None,
+            timezone: Tz::UTC,
+        };
+        let (rows, features): (Vec<_>, Vec<_>) = parse_csv(&csv, None, &time, &task())
+            .unwrap()
+            .into_iter()
+            .unzip();
+        let data = BoatData::new(BoatData::default().version().to_string(), features);
+
+        let error = ImportedData::new(data, Some(&rows), None, None).unwrap_err();
+
+        let message = "Row 4: Latitude 512 is not within [-90, 90]";
+        assert_eq!(
+            error,
+            AppError::new(
+                codes::DATA_INVALID_POINTS,
+                format!("Invalid Boat Data: {message}")
+            )
+            .with("count", 1)
+            .with("issues", vec![message])
+        );
+    }
+
+    #[test]
+    fn import_skips_invalid_data_points_when_asked() {
+        let mut invalid = feature(Layer::Middle, 1);
+        invalid.temperature = f64::NAN;
+        let data = BoatData::new(
+            BoatData::default().version().to_string(),
+            vec![
+                feature(Layer::Surface, 0),
+                invalid,
+                feature(Layer::SeaBed, 2),
+            ],
+        );
+
+        let imported = ImportedData::new(data, None, None, Some(true)).unwrap();
+
+        assert_eq!(
+            imported
+                .data
+                .features()
+                .iter()
+                .map(|f| f.layer())
+                .collect::<Vec<_>>(),
+            vec![Layer::Surface, Layer::SeaBed]
+        );
+        assert_eq!(imported.skipped.len(), 1);
+        assert_eq!(imported.skipped[0].index, 1);
+        assert_eq!(
+            imported.skipped[0].to_string(),
+            "Feature 1: Temperature NaN is not a number"
+        );
+    }
+
+    #[test]
+    fn import_error_lists_the_first_issues() {
+        let features = (0..MAX_REPORTED_ISSUES + 3)
+            .map(|i| {
+                let mut feature = feature(Layer::Surface, i as i64);
+                feature.depth = -1.0;
+                feature
+            })
+            .collect();
+        let data = BoatData::new(BoatData::default().version().to_string(), features);
+
+        let error = ImportedData::new(data, None, None, Some(false)).unwrap_err();
+
+        let message = error.to_string();
+        assert_eq!(message.matches("is negative").count(), MAX_REPORTED_ISSUES);
+        assert!(message.ends_with("; and 3 More"), "{message}");
+    }
+
+    /// The format of local times in the CSV of partners.
+    const LOCAL_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+    /// Creates a parser of local times in `timezone`.
+    fn local_time(timezone: &str) -> CsvTime {
+        CsvTime {
+            format: Some(String::from(LOCAL_FORMAT)),
+            timezone: timezone.parse().unwrap(),
+        }
+    }
+
+    /// Parses boat data CSV with the times `times`, starting on line 2.
+    fn parse_times(times: &[&str], time: &CsvTime) -> Result<Vec<(u64, BoatDataFeature)>, String> {
+        let mut content = String::from("temperature,depth,layer,time,lat,lng\n");
+        for value in times {
+            content.push_str(&format!("28.5,0.5,surface,{value},3.1,101.5\n"));
+        }
+        parse_csv(&content, None, time, &task()).map_err(String::from)
+    }
+
+    #[test]
+    fn csv_time_converts_local_time_to_utc() {
+        let time = local_time("Asia/Kuala_Lumpur");
+
+        assert_eq!(time.to_millis("2024-05-12 14:30:00"), Ok(1715495400000));
+    }
+
+    #[test]
+    fn csv_time_detects_rfc_3339() {
+        let time = CsvTime {
+            format: None,
+            timezone: Tz::UTC,
+        };
+
+        assert_eq!(time.to_millis("2024-05-12T06:30:00Z"), Ok(1715495400000));
+        assert_eq!(
+            time.to_millis("2024-05-12T14:30:00+08:00"),
+            Ok(1715495400000)
+        );
+        assert!(time.to_millis("2024-05-12 14:30:00").is_err());
+    }
+
+    #[test]
+    fn csv_time_rejects_spring_forward_time() {
+        // Clocks in New York went from 02:00 to 03:00 on 10 March 2024
+        let time = local_time("America/New_York");
+
+        let error = time.to_millis("2024-03-10 02:30:00").unwrap_err();
+        assert!(error.contains("does not exist"), "{error}");
+        assert_eq!(time.to_millis("2024-03-10 03:00:00"), Ok(1710054000000));
+    }
+
+    #[test]
+    fn csv_time_rejects_fall_back_time() {
+        // Clocks in New York went from 02:00 back to 01:00 on 3 November 2024
+        let time = local_time("America/New_York");
+
+        let error = time.to_millis("2024-11-03 01:30:00").unwrap_err();
+        assert!(error.contains("ambiguous"), "{error}");
+    }
+
+    #[test]
+    fn parse_csv_reports_row_of_spring_forward_time() {
+        let time = local_time("America/New_York");
+
+        let error =
+            parse_times(&["2024-03-10 01:30:00", "2024-03-10 02:30:00"], &time).unwrap_err();
+
+        assert!(error.contains("Row 3"), "{error}");
+        assert!(error.contains("2024-03-10 02:30:00"), "{error}");
+        assert!(error.contains("does not exist"), "{error}");
+    }
+
+    #[test]
+    fn parse_csv_reports_row_of_fall_back_time() {
+        let time = local_time("America/New_York");
+
+        let error = parse_times(
+            &[
+                "2024-11-03 00:30:00",
+                "2024-11-03 02:30:00",
+                "2024-11-03 01:30:00",
+            ],
+            &time,
+        )
+        .unwrap_err();
+
+        assert!(error.contains("Row 4"), "{error}");
+        assert!(error.contains("ambiguous"), "{error}");
+    }
+
+    /// Creates boat data with `len` random data points.
+    fn random_data(random: &mut Random, len: usize) -> BoatData {
+        let layers = [Layer::Surface, Layer::Middle, Layer::SeaBed];
+        let features = (0..len)
+            .map(|_| {
+                BoatDataFeature::new(
+                    random.between(-5.0, 40.0),
+                    random.between(0.0, 50.0),
+                    layers[random.below(layers.len())].clone(),
+                    Utc.timestamp_millis_opt(1_700_000_000_000 + random.below(1 << 36) as i64)
+                        .unwrap(),
+                    Point::new(random.between(-180.0, 180.0), random.between(-90.0, 90.0)),
+                )
+            })
+            .collect();
+        BoatData::new(BoatData::default().version().to_string(), features)
+    }
+
+    #[test]
+    fn boat_data_round_trips_through_geojson() {
+        let mut random = Random(0x9E37_79B9_7F4A_7C15);
+        for len in 0..50 {
+            let data = random_data(&mut random, len);
+
+            let parsed = BoatData::from_str(&data.to_string()).unwrap();
+
+            assert!(parsed.approx_eq(&data, 1e-9), "{data}");
+        }
+    }
+
+    #[test]
+    fn boat_data_round_trips_through_csv() {
+        let mut random = Random(0x2545_F491_4F6C_DD1D);
+        for len in 1..50 {
+            let data = random_data(&mut random, len);
+            let mut content = vec![];
+            let mut writer = csv_writer(&mut content, UnitSystem::Metric).unwrap();
+            for feature in data.features() {
+                writer.serialize(BoatDataFeatureCSV::from(feature)).unwrap();
+            }
+            writer.flush().unwrap();
+            drop(writer);
+
+            let parsed = BoatData::new(
+                data.version().to_string(),
+                parse(&String::from_utf8(content).unwrap(), None).unwrap(),
+            );
+
+            assert!(parsed.approx_eq(&data, 1e-9), "{data}");
+        }
+    }
+
+    #[test]
+    fn approx_eq_allows_differences_up_to_epsilon() {
+        let data = data(&[Layer::Surface, Layer::Middle]);
+        let mut close = data.clone();
+        close.features[1].temperature += 1e-7;
+        close.features[1].geometry = Point::new(101.5 + 1e-7, 3.1);
+
+        assert_ne!(close, data);
+        assert!(close.approx_eq(&data, 1e-6));
+        assert!(!close.approx_eq(&data, 1e-8));
+    }
+
+    #[test]
+    fn approx_eq_compares_layer_time_and_length() {
+        let data = data(&[Layer::Surface, Layer::Middle]);
+        let mut other_layer = data.clone();
+        other_layer.features[0].layer = Layer::SeaBed;
+        let mut other_time = data.clone();
+        other_time.features[0].time += chrono::Duration::milliseconds(1);
+        let mut shorter = data.clone();
+        shorter.features.pop();
+
+        assert!(!other_layer.approx_eq(&data, 1.0));
+        assert!(!other_time.approx_eq(&data, 1.0));
+        assert!(!shorter.approx_eq(&data, 1.0));
+    }
+
+    #[test]
+    fn equality_is_order_sensitive_unless_unordered() {
+        let data = data(&[Layer::Surface, Layer::Middle, Layer::SeaBed]);
+        let mut reversed = data.clone();
+        reversed.features.reverse();
+        let mut changed = reversed.clone();
+        changed.features[0].depth = 2.0;
+
+        assert_eq!(data, data.clone());
+        assert_ne!(reversed, data);
+        assert!(!reversed.approx_eq(&data, 1e-9));
+        assert!(reversed.eq_unordered(&data));
+        assert!(!changed.eq_unordered(&data));
+    }
+
+    #[test]
+    fn unordered_equality_counts_repeated_points() {
+        let surface = feature(Layer::Surface, 0);
+        let middle = feature(Layer::Middle, 1);
+        let version = BoatData::default().version().to_string();
+        let twice = BoatData::new(
+            version.clone(),
+            vec![surface.clone(), surface.clone(), middle.clone()],
+        );
+        let once = BoatData::new(version, vec![surface, middle.clone(), middle]);
+
+        assert!(!once.eq_unordered(&twice));
+        assert!(!twice.eq_unordered(&once));
+    }
+
+    #[test]
+    fn compare_data_chooses_the_comparison() {
+        let data = data(&[Layer::Surface, Layer::Middle]);
+        let mut reversed = data.clone();
+        reversed.features.reverse();
+
+        assert_eq!(
+            compare_data(data.clone(), data.clone(), None, None),
+            Ok(true)
+        );
+        assert_eq!(
+            compare_data(data.clone(), reversed.clone(), None, None),
+            Ok(false)
+        );
+        assert_eq!(
+            compare_data(data.clone(), reversed.clone(), None, Some(true)),
+            Ok(true)
+        );
+        assert_eq!(
+            compare_data(data.clone(), reversed.clone(), Some(1e-9), None),
+            Ok(false)
+        );
+        assert!(compare_data(data, reversed, Some(1e-9), Some(true)).is_err());
+    }
+
+    #[test]
+    fn total_order_matches_numeric_order() {
+        let values = [
+            f64::NEG_INFINITY,
+            -1.5,
+            -0.0,
+            0.0,
+            f64::MIN_POSITIVE,
+            2.0,
+            f64::INFINITY,
+            f64::NAN,
+        ];
+        for pair in values.windows(2) {
+            assert!(total_order(pair[0]) < total_order(pair[1]), "{pair:?}");
+        }
+    }
+
+    /// Creates the spec of a column.
+    fn column(field: &str, header: Option<&str>, format: Option<&str>) -> ColumnSpec {
+        ColumnSpec {
+            field: field.to_string(),
+            header: header.map(String::from),
+            format: format.map(String::from),
+        }
+    }
+
+    /// Exports `data` to CSV with `columns`, returning the content of the file.
+    fn export_csv(name: &str, data: &BoatData, columns: Option<Vec<ColumnSpec>>) -> String {
+        let dir = crate::storage::tests::temp_dir(name);
+        let path = dir.join("data.csv");
+        export_data_csv(path.clone(), data.clone(), None, columns, None).unwrap();
+        let content = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_dir_all(dir).unwrap();
+        content
+    }
+
+    #[test]
+    fn export_csv_writes_selected_columns_in_order() {
+        let data = data(&[Layer::Surface, Layer::SeaBed]);
+        let columns = vec![
+            column("time", Some("timestamp"), None),
+            column("lat", Some("latitude"), None),
+            column("lng", Some("longitude"), None),
+            column("temperature", Some("temp_c"), None),
+        ];
+
+        assert_eq!(
+            export_csv("export-columns", &data, Some(columns)),
+            "timestamp,latitude,longitude,temp_c\n\
+             1715495400000,3.1,101.5,28.5\n\
+             1715495401000,3.1,101.5,28.5\n"
+        );
+    }
+
+    #[test]
+    fn export_csv_formats_times() {
+        let data = data(&[Layer::Surface]);
+        let columns = vec![
+            column("time", None, Some("%Y-%m-%d %H:%M:%S")),
+            column("time", Some("rfc3339"), Some("rfc3339")),
+            column("depth", None, None),
+            column("layer", None, None),
+        ];
+
+        assert_eq!(
+            export_csv("export-time-format", &data, Some(columns)),
+            "time,rfc3339,depth,layer\n\
+             2024-05-12 06:30:00,2024-05-12T06:30:00Z,0.5,surface\n"
+        );
+    }
+
+    #[test]
+    fn export_csv_without_columns_writes_every_field() {
+        let data = data(&[Layer::Middle]);
+        let mut expected = vec![];
+        let mut writer = csv_writer(&mut expected, UnitSystem::Metric).unwrap();
+        writer
+            .serialize(BoatDataFeatureCSV::from(&data.features()[0]))
+            .unwrap();
+        writer.flush().unwrap();
+        drop(writer);
+
+        assert_eq!(
+            export_csv("export-default-columns", &data, None),
+            String::from_utf8(expected).unwrap()
+        );
+    }
+
+    #[test]
+    fn export_csv_rejects_invalid_columns() {
+        let dir = crate::storage::tests::temp_dir("export-invalid-columns");
+        let export =
+            |columns| export_data_csv(dir.join("data.csv"), data(&[]), None, Some(columns), None);
+
+        let unknown = export(vec![column("salinity", None, None)]).unwrap_err();
+        assert_eq!(
+            unknown,
+            AppError::reason(
+                codes::CSV_INVALID_COLUMN,
+                "Invalid Column salinity",
+                "Invalid Column Field: salinity, expected one of id, time, lat, lng, depth, \
+                 layer, temperature, ph, dissolved_oxygen, turbidity, note, temperature_raw, \
+                 sensor_id",
+            )
+            .with("column", "salinity")
+        );
+        let format = export(vec![column("depth", None, Some("rfc3339"))]).unwrap_err();
+        assert_eq!(
+            format,
+            AppError::reason(
+                codes::CSV_INVALID_COLUMN,
+                "Invalid Column depth",
+                "Only time columns have a format",
+            )
+            .with("column", "depth")
+        );
+        let none = export(vec![]).unwrap_err();
+        assert_eq!(
+            none,
+            AppError::new(
+                codes::CSV_NO_COLUMNS,
+                "Unable to Export CSV: No Columns Selected"
+            )
+        );
+        assert!(!dir.join("data.csv").exists());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn boat_data_reads_sensors_of_both_versions() {
+        let old = r#"{"type":"FeatureCollection","version":"0.1.0","features":[
+            {"type":"Feature","geometry":{"type":"Point","coordinates":[101.5,3.1]},
+             "properties":{"temperature":28.5,"depth":0.5,"layer":"surface",
+                           "time":"2024-05-12T06:30:00Z"}}]}"#;
+        let new = old
+            .replace("0.1.0", "0.2.0")
+            .replace("\"depth\"", "\"ph\":7.25,\"turbidity\":3.5,\"depth\"");
+
+        let old = BoatData::from_str(old).unwrap();
+        let new = BoatData::from_str(&new).unwrap();
+
+        let expected = |feature| {
+            let mut data = BoatData::new(new.version().to_string(), vec![feature]);
+            data.assign_ids();
+            data.features
+        };
+        assert_eq!(old.features(), expected(feature(Layer::Surface, 0)));
+        assert_eq!(
+            new.features(),
+            expected(feature(Layer::Surface, 0).with_sensors(Some(7.25), None, Some(3.5)))
+        );
+    }
+
+    #[test]
+    fn geojson_leaves_out_absent_sensors() {
+        let feature = feature(Layer::Surface, 0).with_sensors(None, Some(6.5), None);
+
+        let properties = geojson::Feature::from(&feature).properties.unwrap();
+
+        assert_eq!(properties.get("dissolved_oxygen"), Some(&6.5.into()));
+        assert!(!properties.contains_key("ph"));
+        assert!(!properties.contains_key("turbidity"));
+    }
+
+    #[test]
+    fn boat_data_with_sensors_round_trips() {
+        let features = vec![
+            feature(Layer::Surface, 0).with_sensors(Some(7.25), Some(6.5), Some(3.5)),
+            feature(Layer::Middle, 1).with_sensors(Some(8.0), None, None),
+            feature(Layer::SeaBed, 2),
+        ];
+        let mut data = BoatData::new(BoatData::default().version().to_string(), features);
+        data.assign_ids();
+        let mut content = vec![];
+        let mut writer = csv_writer(&mut content, UnitSystem::Metric).unwrap();
+        for feature in data.features() {
+            writer.serialize(BoatDataFeatureCSV::from(feature)).unwrap();
+        }
+        writer.flush().unwrap();
+        drop(writer);
+
+        let geojson = BoatData::from_str(&data.to_string()).unwrap();
+        let csv = parse(&String::from_utf8(content).unwrap(), None).unwrap();
+
+        assert_eq!(geojson, data);
+        assert_eq!(csv, data.features());
+    }
+
+    #[test]
+    fn parse_csv_reads_sensor_columns() {
+        let content = "temperature;depth;layer;time;lat;lng;ph;turbidity\n\
+            28,5;0,5;surface;1715495400000;3,1;101,5;7,25;\n";
+
+        let features = parse(content, None).unwrap();
+
+        assert_eq!(
+            features,
+            vec![feature(Layer::Surface, 0).with_sensors(Some(7.25), None, None)]
+        );
+    }
+
+    #[test]
+    fn filter_sensor_keeps_values_within_range() {
+        let data = BoatData::new(
+            BoatData::default().version().to_string(),
+            vec![
+                feature(Layer::Surface, 0).with_sensors(Some(6.5), None, None),
+                feature(Layer::Surface, 1),
+                feature(Layer::Surface, 2).with_sensors(Some(7.5), None, None),
+                feature(Layer::Surface, 3).with_sensors(Some(8.5), None, None),
+            ],
+        );
+        let times = |data: BoatData| -> Vec<_> {
+            data.features()
+                .iter()
+                .map(|f| f.time().timestamp() - 1_715_495_400)
+                .collect()
+        };
+
+        assert_eq!(
+            times(data.filter_sensor(Sensor::Ph, None, None)),
+            vec![0, 2, 3]
+        );
+        assert_eq!(
+            times(data.filter_sensor(Sensor::Ph, Some(7.0), Some(8.5))),
+            vec![2, 3]
+        );
+        assert_eq!(
+            times(data.filter_sensor(Sensor::Turbidity, None, None)),
+            Vec::<i64>::new()
+        );
+    }
+
+    #[test]
+    fn validate_reports_invalid_sensor_values() {
+        let data = BoatData::new(
+            BoatData::default().version().to_string(),
+            vec![
+                feature(Layer::Surface, 0).with_sensors(Some(14.0), Some(0.0), Some(0.0)),
+                feature(Layer::Surface, 1).with_sensors(Some(14.5), None, Some(f64::NAN)),
+            ],
+        );
+
+        let issues: Vec<_> = data
+            .validate()
+            .into_iter()
+            .map(|issue| (issue.index, issue.reason))
+            .collect();
+
+        assert_eq!(
+            issues,
+            vec![
+                (1, String::from("Turbidity NaN is not a number")),
+                (1, String::from("pH 14.5 is not within [0, 14]")),
+            ]
+        );
+    }
+
+    /// Boat data GeoJSON written by the app with a layer added by newer firmware.
+    fn thermocline_data() -> BoatData {
+        let time = |ms| Utc.timestamp_millis_opt(ms).unwrap();
+        let mut data = BoatData::new(
+            String::from(migrations::BOAT_DATA_VERSION),
+            vec![
+                BoatDataFeature::new(
+                    28.5,
+                    0.5,
+                    Layer::Surface,
+                    time(1715495400000),
+                    Point::new(101.5, 3.1),
+                ),
+                BoatDataFeature::new(
+                    24.25,
+                    6.0,
+                    Layer::Other(String::from("thermocline")),
+                    time(1715495460000),
+                    Point::new(101.5002, 3.1001),
+                ),
+            ],
+        );
+        data.assign_ids();
+        data
+    }
+
+    #[test]
+    fn novel_layer_round_trips_geojson_byte_identically() {
+        let dir = crate::storage::tests::temp_dir("layer-geojson");
+        let import_path = dir.join("import.geojson");
+        let export_path = dir.join("export.geojson");
+        write_data(&import_path, &thermocline_data()).unwrap();
+        let original = std::fs::read(&import_path).unwrap();
+        assert!(String::from_utf8_lossy(&original).contains("\"layer\":\"thermocline\""));
+
+        let data = load_data(&import_path).unwrap();
+        assert_eq!(
+            data.features()[1].layer(),
+            Layer::Other(String::from("thermocline"))
+        );
+        write_data(&export_path, &data).unwrap();
+
+        assert_eq!(std::fs::read(&export_path).unwrap(), original);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    /// Writes boat data as CSV like the exports.
+    fn csv_export(data: &BoatData) -> String {
+        let mut content = vec![];
+        let mut writer = csv_writer(&mut content, UnitSystem::Metric).unwrap();
+        for feature in data.features() {
+            writer.serialize(BoatDataFeatureCSV::from(feature)).unwrap();
+        }
+        writer.flush().unwrap();
+        drop(writer);
+        String::from_utf8(content).unwrap()
+    }
+
+    #[test]
+    fn novel_layer_round_trips_csv_byte_identically() {
+        let original = csv_export(&thermocline_data());
+        assert!(original.contains(",thermocline,"));
+
+        let features = parse(&original, None).unwrap();
+        let data = BoatData::new(String::from(migrations::BOAT_DATA_VERSION), features);
+
+        assert_eq!(csv_export(&data), original);
+    }
+
+    #[test]
+    fn novel_layer_is_filtered_by_name() {
+        let data = thermocline_data();
+
+        let filtered = filter_data_layer(data, String::from("thermocline")).unwrap();
+
+        assert_eq!(filtered.features().len(), 1);
+        assert_eq!(filtered.features()[0].depth(), 6.0);
+    }
+
+    #[test]
+    fn layers_list_known_layers_first() {
+        let data = BoatData::new(
+            String::from(migrations::BOAT_DATA_VERSION),
+            vec![
+                feature(Layer::Other(String::from("thermocline")), 0),
+                feature(Layer::SeaBed, 1),
+                feature(Layer::Other(String::from("halocline")), 2),
+                feature(Layer::Surface, 3),
+                feature(Layer::Other(String::from("thermocline")), 4),
+            ],
+        );
+
+        assert_eq!(
+            data.layers(),
+            vec![
+                Layer::Surface,
+                Layer::SeaBed,
+                Layer::Other(String::from("thermocline")),
+                Layer::Other(String::from("halocline")),
+            ]
+        );
+    }
+
+    /// Readings received out of order, with the layers interleaved at the same times.
+    fn out_of_order_data() -> BoatData {
+        BoatData::new(
+            String::from(migrations::BOAT_DATA_VERSION),
+            vec![
+                feature(Layer::Surface, 60),
+                feature(Layer::SeaBed, 0),
+                feature(Layer::Middle, 60),
+                feature(Layer::Surface, 0),
+                feature(Layer::SeaBed, 60),
+                feature(Layer::Middle, 0),
+            ],
+        )
+    }
+
+    /// Gets the seconds after the start of the mission and layer of each data point.
+    fn times_and_layers(data: &BoatData) -> Vec<(i64, Layer)> {
+        data.features()
+            .iter()
+            .map(|f| (f.time().timestamp() - 1_715_495_400, f.layer()))
+            .collect()
+    }
+
+    #[test]
+    fn sort_by_time_keeps_order_of_equal_times() {
+        let mut data = out_of_order_data();
+        assert!(!data.is_sorted_by_time());
+
+        assert!(data.sort_by_time());
+
+        assert!(data.is_sorted_by_time());
+        assert_eq!(
+            times_and_layers(&data),
+            vec![
+                (0, Layer::SeaBed),
+                (0, Layer::Surface),
+                (0, Layer::Middle),
+                (60, Layer::Surface),
+                (60, Layer::Middle),
+                (60, Layer::SeaBed),
+            ]
+        );
+    }
+
+    #[test]
+    fn sort_by_time_skips_sorted_data() {
+        let mut sorted = data(&[Layer::Middle, Layer::Surface]);
+        sorted.features.push(feature(Layer::SeaBed, 1));
+        let before = sorted.clone();
+
+        assert!(sorted.is_sorted_by_time());
+        assert!(!sorted.sort_by_time());
+        assert_eq!(sorted, before);
+        assert!(BoatData::default().is_sorted_by_time());
+    }
+
+    #[test]
+    fn sort_data_sorts_by_time() {
+        let sorted = sort_data(out_of_order_data());
+
+        assert!(sorted.is_sorted_by_time());
+        assert_eq!(sorted.features().len(), 6);
+    }
+
+    #[test]
+    fn import_sorts_data_points_by_time() {
+        let imported = ImportedData::new(out_of_order_data(), None, None, None).unwrap();
+
+        assert_eq!(
+            times_and_layers(&imported.data),
+            times_and_layers(&sort_data(out_of_order_data()))
+        );
+    }
+
+    #[test]
+    fn assigned_ids_are_stable_and_unique() {
+        let mut data = data(&[Layer::Surface, Layer::Surface]);
+        data.features.push(data.features[0].clone());
+        data.features[1] = data.features[1].clone().with_id(Some(String::from("kept")));
+        let mut again = data.clone();
+
+        data.assign_ids();
+        again.assign_ids();
+
+        let ids: Vec<_> = data.features().iter().map(|f| f.id().unwrap()).collect();
+        assert_eq!(ids[1], "kept");
+        assert_ne!(ids[0], ids[2]);
+        assert_eq!(data, again);
+    }
+
+    #[test]
+    fn ids_round_trip_through_geojson_and_csv() {
+        let mut data = data(&[Layer::Surface, Layer::Middle]);
+        data.features[0] = data.features[0].clone().with_random_id();
+        data.features[1] = data.features[1]
+            .clone()
+            .with_id(Some(String::from("reading-1")));
+
+        let geojson = BoatData::from_str(&data.to_string()).unwrap();
+        let csv = parse(&csv_export(&data), None).unwrap();
+
+        assert_eq!(geojson, data);
+        assert_eq!(csv, data.features());
+    }
+
+    #[test]
+    fn importing_a_file_again_keeps_the_ids() {
+        let import = || {
+            let data = BoatData::new(
+                String::from(migrations::BOAT_DATA_VERSION),
+                parse(CSV, None).unwrap(),
+            );
+            ImportedData::new(data, None, None, None).unwrap().data
+        };
+        let first = import();
+        let second = import();
+
+        let ids = |data: &BoatData| -> Vec<_> {
+            data.features()
+                .iter()
+                .map(|f| f.id().map(String::from))
+                .collect()
+        };
+        assert!(ids(&first).iter().all(Option::is_some));
+        assert_eq!(ids(&first), ids(&second));
+    }
+
+    #[test]
+    fn features_are_found_and_removed_by_id() {
+        let mut data = data(&[Layer::Surface, Layer::Middle, Layer::SeaBed]);
+        data.assign_ids();
+        let middle = data.features()[1].id().unwrap().to_string();
+
+        assert_eq!(
+            data.feature(&middle).map(|f| f.layer()),
+            Some(Layer::Middle)
+        );
+        let removed = data.remove_ids(&HashSet::from([middle.clone(), String::from("unknown")]));
+
+        assert_eq!(removed, 1);
+        assert!(data.feature(&middle).is_none());
+        assert_eq!(data.features().len(), 2);
+    }
+
+    #[test]
+    fn random_ids_keep_existing_ids() {
+        let feature = feature(Layer::Surface, 0);
+
+        let random = feature.clone().with_random_id();
+        let kept = feature.with_id(Some(String::from("kept"))).with_random_id();
+
+        assert!(Uuid::parse_str(random.id().unwrap()).is_ok());
+        assert_eq!(kept.id(), Some("kept"));
+    }
+
+    /// Creates boat data with notes on the data points, with ids.
+    fn noted_data() -> BoatData {
+        let mut data = data(&[Layer::Surface, Layer::Middle, Layer::SeaBed]);
+        data.features[0].set_note(String::from("Sensor fouled here"));
+        data.features[2].set_note(String::from("sample bottle #3 taken, sensor cleaned"));
+        data.assign_ids();
+        data
+    }
+
+    #[test]
+    fn notes_round_trip_through_geojson_and_csv() {
+        let data = noted_data();
+
+        let geojson = BoatData::from_str(&data.to_string()).unwrap();
+        let csv = parse(&csv_export(&data), None).unwrap();
+
+        assert_eq!(geojson, data);
+        assert_eq!(csv, data.features());
+        let properties = geojson::Feature::from(&data.features()[1])
+            .properties
+            .unwrap();
+        assert!(!properties.contains_key("note"));
+    }
+
+    #[test]
+    fn blank_note_removes_the_note() {
+        let mut feature = feature(Layer::Surface, 0).with_note(Some(String::from("fouled")));
+
+        feature.set_note(String::from(" \t"));
+
+        assert_eq!(feature.note(), None);
+    }
+
+    #[test]
+    fn search_notes_ignores_case() {
+        let data = noted_data();
+        let id = |i: usize| data.features()[i].id().unwrap().to_string();
+
+        assert_eq!(data.search_notes("SENSOR"), vec![id(0), id(2)]);
+        assert_eq!(data.search_notes("bottle #3"), vec![id(2)]);
+        assert!(data.search_notes("salinity").is_empty());
+    }
+
+    /// Generates boat data CSV of `rows` data points.
+    fn generated_csv(rows: usize) -> String {
+        let mut content = String::from("temperature,depth,layer,time,lat,lng,note\n");
+        for i in 0..rows {
+            content.push_str(&format!(
+                "{},{},surface,{},{},101.5,\"point {i}, line {}\"\n",
+                25.0 + (i % 100) as f64 / 10.0,
+                (i % 20) as f64 / 4.0,
+                1_715_500_000_000 + i as u64 * 1000,
+                3.1 + i as f64 * 1e-6,
+                i + 2
+            ));
+        }
+        content
+    }
+
+    /// Parses boat data CSV on every core, reading it in batches of `batch_size` bytes.
+    fn parse_parallel(
+        content: &str,
+        batch_size: usize,
+    ) -> Result<Vec<(u64, BoatDataFeature)>, AppError> {
+        let time = CsvTime::new(None, None).unwrap();
+        parse_csv_parallel(content.as_bytes(), None, &time, batch_size, &mut task())
+    }
+
+    /// Parses boat data CSV on a single core.
+    fn parse_sequential(content: &str) -> Result<Vec<(u64, BoatDataFeature)>, AppError> {
+        parse_csv(content, None, &CsvTime::new(None, None).unwrap(), &task())
+    }
+
+    #[test]
+    fn split_csv_keeps_line_numbers() {
+        let content = "1,a\n2,\"b\nc\"\n3,d\n4,e";
+
+        let chunks = split_csv(content, 2, 4);
+
+        assert_eq!(
+            chunks,
+            vec![(2, "1,a\n"), (3, "2,\"b\nc\"\n"), (5, "3,d\n"), (6, "4,e")]
+        );
+    }
+
+    #[test]
+    fn parse_csv_in_parallel_matches_sequential() {
+        let content = generated_csv(200_000);
+        assert!(content.len() > 2 * CSV_CHUNK_SIZE);
+
+        let sequential = parse_sequential(&content).unwrap();
+        let parallel = parse_parallel(&content, 4 * CSV_CHUNK_SIZE).unwrap();
+
+        assert_eq!(parallel.len(), 200_000);
+        assert_eq!(parallel, sequential);
+        assert_eq!(parallel[199_999].0, 200_001);
+    }
+
+    #[test]
+    fn parse_csv_in_parallel_reads_in_batches() {
+        let content = generated_csv(1_000);
+
+        let sequential = parse_sequential(&content).unwrap();
+        // Batches smaller than a record are read until a record ends
+        for batch_size in [10, 1_000, 4_096, content.len() + 1] {
+            assert_eq!(parse_parallel(&content, batch_size).unwrap(), sequential);
+        }
+    }
+
+    #[test]
+    fn parse_csv_in_parallel_reads_last_row_without_line_break() {
+        let content = "temperature,depth,layer,time,lat,lng\n\
+            28.5,0.5,surface,1715495400000,3.1,101.5\n\
+            29.5,1,middle,1715495460000,3.1,101.5";
+
+        let features = parse_parallel(content, 40).unwrap();
+        assert_eq!(features, parse_sequential(content).unwrap());
+        assert_eq!(features[1].0, 3);
+        assert_eq!(
+            parse_parallel("temperature,depth,layer,time,lat,lng", 10),
+            Ok(vec![])
+        );
+    }
+
+    #[test]
+    fn parse_csv_in_parallel_reports_absolute_row() {
+        let mut content = generated_csv(200_000);
+        // Breaks the temperature of the data point on line 150 002, in a later chunk
+        let start = content.find("\"point 150000,").unwrap();
+        let start = content[..start].rfind('\n').unwrap() + 1;
+        let end = start + content[start..].find(',').unwrap();
+        content.replace_range(start..end, "warm");
+        assert!(start > CSV_CHUNK_SIZE);
+
+        for batch_size in [4 * CSV_CHUNK_SIZE, CSV_CHUNK_SIZE / 3] {
+            let error = parse_parallel(&content, batch_size).unwrap_err();
+            let error = serde_json::to_value(error).unwrap();
+            assert_eq!(error["code"], json!(codes::CSV_INVALID_ROW));
+            assert_eq!(error["params"]["row"], json!(150_002));
+            assert!(error["message"].as_str().unwrap().contains("Row 150002"));
+        }
+        let error = serde_json::to_value(parse_sequential(&content).unwrap_err()).unwrap();
+        assert_eq!(error["params"]["row"], json!(150_002));
+    }
+
+    #[test]
+    fn parse_csv_in_parallel_counts_rows_of_every_thread() {
+        let content = generated_csv(200_000);
+        let rows = Arc::new(std::sync::Mutex::new(vec![]));
+        let progress = rows.clone();
+        let mut task = ImportTask::new(0, Arc::new(AtomicBool::new(false)), move |event| {
+            progress.lock().unwrap().push(event);
+        });
+        let time = CsvTime::new(None, None).unwrap();
+
+        parse_csv_parallel(content.as_bytes(), None, &time, CSV_CHUNK_SIZE, &mut task).unwrap();
+        let last = *rows.lock().unwrap().last().unwrap();
+        assert_eq!(serde_json::to_value(last).unwrap()["rows"], json!(200_000));
+    }
+
+    #[test]
+    fn parse_csv_in_parallel_stops_when_cancelled() {
+        let content = generated_csv(1_000);
+        let mut task = ImportTask::new(0, Arc::new(AtomicBool::new(true)), |_| {});
+        let time = CsvTime::new(None, None).unwrap();
+
+        let error = parse_csv_parallel(content.as_bytes(), None, &time, 1_000, &mut task);
+        assert_eq!(
+            error.unwrap_err(),
+            AppError::new(codes::IMPORT_CANCELLED, "Import Cancelled")
+        );
+    }
+
+    /// Compares parsing large CSV in parallel with parsing it sequentially, run with
+    /// `cargo test --release -- --ignored parse_csv_benchmark --nocapture`.
+    #[test]
+    #[ignore]
+    fn parse_csv_benchmark() {
+        let content = generated_csv(2_000_000);
+        let time = |parallel| {
+            let start = std::time::Instant::now();
+            let features = if parallel {
+                parse_parallel(&content, CSV_CHUNK_SIZE * rayon::current_num_threads())
+            } else {
+                parse_sequential(&content)
+            };
+            assert_eq!(features.unwrap().len(), 2_000_000);
+            start.elapsed()
+        };
+
+        // Warms up the thread pool and the caches
+        time(true);
+        let sequential = time(false);
+        let parallel = time(true);
+        println!(
+            "Sequential: {:?}, Parallel: {:?} on {} Threads",
+            sequential,
+            parallel,
+            rayon::current_num_threads()
+        );
+        if rayon::current_num_threads() >= 4 {
+            assert!(
+                sequential >= parallel * 2,
+                "Parallel parsing is less than 2x faster"
+            );
+        }
+    }
+
+    #[test]
+    fn foreign_members_round_trip_through_geojson() {
+        let content = json!({
+            "type": "FeatureCollection",
+            "features": [],
+            "version": migrations::BOAT_DATA_VERSION,
+            "site_name": "Blue Lake",
+            "crs": { "type": "name", "properties": { "name": "EPSG:4326" } },
+        })
+        .to_string();
+
+        let data = BoatData::from_str(&content).unwrap();
+        assert_eq!(data.metadata().site_name.as_deref(), Some("Blue Lake"));
+        assert!(data.members().contains_key("crs"));
+        assert!(!data.members().contains_key("version"));
+
+        assert_eq!(BoatData::from_str(&data.to_string()).unwrap(), data);
+    }
+
+    #[test]
+    fn setting_the_metadata_keeps_the_other_members() {
+        let mut data = BoatData::default();
+        let mut members = Map::new();
+        members.insert(String::from("crs"), json!("EPSG:4326"));
+        members.insert(String::from("version"), json!("9.9.9"));
+        data.set_members(members);
+
+        data.set_metadata(&DatasetMetadata {
+            operator: Some(String::from("Aida")),
+            ..DatasetMetadata::default()
+        });
+
+        assert_eq!(data.version(), migrations::BOAT_DATA_VERSION);
+        assert_eq!(
+            Value::Object(data.members().clone()),
+            json!({ "crs": "EPSG:4326", "operator": "Aida" })
+        );
+    }
+
+    #[test]
+    fn export_csv_starts_with_the_metadata() {
+        let mut data = data(&[Layer::Surface]);
+        data.set_metadata(&DatasetMetadata {
+            site_name: Some(String::from("Blue Lake")),
+            ..DatasetMetadata::default()
+        });
+        let columns = vec![column("depth", None, None)];
+
+        assert_eq!(
+            export_csv("export-metadata", &data, Some(columns)),
+            "# Site: Blue Lake\ndepth\n0.5\n"
+        );
+    }
+
+    #[test]
+    fn parse_csv_skips_the_comments() {
+        let content = "# Site: Blue Lake\n\
+                       # Notes: a;b;c;d;e;f;g\n\
+                       temperature,depth,layer,time,lat,lng\n\
+                       28.5,0.5,surface,1715495400000,3.1,101.5\n\
+                       28.5,0.5,surface,1715495401000,3.1,101.5\n";
+
+        let features = parse_sequential(content).unwrap();
+
+        assert_eq!(
+            features.iter().map(|(line, _)| *line).collect::<Vec<_>>(),
+            [4, 5]
+        );
+        assert_eq!(parse_parallel(content, 16).unwrap(), features);
+    }
+
+    #[test]
+    fn comments_end_at_the_header() {
+        assert_eq!(comments_len(b"# a\n#b\ntime\n# c\n"), 7);
+        assert_eq!(comments_len(b"time\n"), 0);
+        assert_eq!(comments_len(b"# only\n"), 7);
+        assert_eq!(header_end(b"# a\ntime\n1\n"), Some(9));
+        assert_eq!(header_end(b"# a\ntim"), None);
+    }
+
+    #[test]
+    fn unknown_members_and_properties_round_trip_through_geojson() {
+        let geojson = json!({
+            "type": "FeatureCollection",
+            "version": migrations::BOAT_DATA_VERSION,
+            "generator": { "name": "lake-logger", "build": [3, 2] },
+            "features": [{
+                "type": "Feature",
+                "id": "reading-1",
+                "geometry": { "type": "Point", "coordinates": [101.5, 3.1] },
+                "properties": {
+                    "temperature": 28.5,
+                    "depth": 0.5,
+                    "layer": "surface",
+                    "time": "2024-05-12T06:30:00+00:00",
+                    "salinity": 0.2,
+                    "station": { "name": "A1", "tags": ["inflow"] },
+                },
+            }],
+        });
+
+        let data = BoatData::from_str(&geojson.to_string()).unwrap();
+        assert_eq!(data.features()[0].extra()["salinity"], json!(0.2));
+
+        let written: Value = serde_json::from_str(&data.to_string()).unwrap();
+        assert_eq!(written, geojson);
+    }
+
+    #[test]
+    fn unknown_properties_round_trip_through_a_feature() {
+        let feature = feature(Layer::SeaBed, 60).with_extra(
+            json!({ "salinity": 0.2, "flags": [1, 2] })
+                .as_object()
+                .unwrap()
+                .clone(),
+        );
+
+        let geojson = geojson::Feature::from(&feature);
+        assert_eq!(geojson.properties.as_ref().unwrap()["flags"], json!([1, 2]));
+
+        assert_eq!(read_feature(geojson).unwrap(), feature);
+    }
+
+    #[test]
+    fn properties_used_by_the_app_replace_unknown_ones() {
+        let feature = feature(Layer::Surface, 0)
+            .with_extra(json!({ "depth": "deep" }).as_object().unwrap().clone());
+
+        let geojson = geojson::Feature::from(&feature);
+
+        assert_eq!(geojson.properties.unwrap()["depth"], json!(0.5));
+    }
+
+    #[test]
+    fn dry_runs_preview_the_export_without_writing() {
+        let dir = crate::storage::tests::temp_dir("export-dry-run");
+        let data = data(&[Layer::Surface, Layer::SeaBed]);
+        let path = dir.join("data.csv");
+
+        let preview = export_data_csv(path.clone(), data.clone(), None, None, Some(true))
+            .unwrap()
+            .unwrap();
+        assert!(!path.exists());
+        let written = export_csv("export-dry-run-written", &data, None);
+        let preview = serde_json::to_value(preview).unwrap();
+        assert_eq!(preview["rows"], 2);
+        assert_eq!(preview["estimated_bytes"], written.len());
+        assert_eq!(preview["warnings"], json!([]));
+
+        let path = dir.join("data.geojson");
+        let preview = export_data(path.clone(), data.clone(), Some(true))
+            .unwrap()
+            .unwrap();
+        assert!(!path.exists());
+        assert_eq!(
+            serde_json::to_value(preview).unwrap()["estimated_bytes"],
+            data.to_string().len()
+        );
+        assert_eq!(export_data(path.clone(), data, Some(false)), Ok(None));
+        assert!(path.exists());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn calibrated_temperatures_are_kept() {
+        let calibrated = feature(Layer::Surface, 0)
+            .with_temperature_raw(Some(27.25))
+            .with_sensor_id(Some(String::from("T1")));
+        let data = BoatData::new(
+            BoatData::default().version().to_string(),
+            vec![calibrated.clone(), feature(Layer::Surface, 1)],
+        );
+
+        assert_eq!(
+            read_feature(geojson::Feature::from(&calibrated)).unwrap(),
+            calibrated
+        );
+        let columns = vec![
+            column("temperature", None, None),
+            column("temperature_raw", None, None),
+            column("sensor_id", None, None),
+        ];
+        assert_eq!(
+            export_csv("export-calibration", &data, Some(columns)),
+            "temperature,temperature_raw,sensor_id\n\
+             28.5,27.25,T1\n\
+             28.5,,\n"
+        );
+    }
 }
@@ -0,0 +1,422 @@
+//! Named boat data in application storage, one for each deployment.
+
+use std::{
+    fmt,
+    fs::{self, File},
+    io::{BufReader, ErrorKind},
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::Mutex,
+};
+
+use chrono::{DateTime, Utc};
+use serde::{
+    de::{SeqAccess, Visitor},
+    Deserialize, Deserializer, Serialize,
+};
+use serde_json::json;
+use tauri::{AppHandle, Manager};
+
+use crate::{
+    app_data::AppData,
+    autosave::{self, AutosaveState},
+    data::{self, BoatData, BoatDataFeature},
+    errors::{codes, AppError},
+    index::DataIndex,
+    journal, operations,
+    storage::{app_data_file, check_name, write_atomically},
+    watcher,
+};
+
+/// The extension of the dataset files.
+const EXTENSION: &str = ".geojson";
+
+/// The name of the dataset used when none is selected.
+const DEFAULT_DATASET: &str = "default";
+
+/// The dataset used by `read_data` and `save_data`.
+#[derive(Debug)]
+pub struct ActiveDataset {
+    name: Mutex<String>,
+}
+
+impl Default for ActiveDataset {
+    fn default() -> Self {
+        Self {
+            name: Mutex::new(String::from(DEFAULT_DATASET)),
+        }
+    }
+}
+
+/// Information on a dataset in application storage.
+#[derive(Debug, Serialize)]
+pub struct DatasetInfo {
+    /// The name of the dataset.
+    name: String,
+    /// The number of features in the dataset.
+    features: usize,
+    /// The time of the first measurement.
+    first_time: Option<DateTime<Utc>>,
+    /// The time of the last measurement.
+    last_time: Option<DateTime<Utc>>,
+}
+
+/// The parts of the boat data GeoJSON read when listing the datasets.
+#[derive(Debug, Deserialize)]
+struct DatasetSummary {
+    #[serde(deserialize_with = "summarize_features")]
+    features: FeaturesSummary,
+}
+
+/// The feature count and time range of a dataset.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+struct FeaturesSummary {
+    count: usize,
+    first_time: Option<DateTime<Utc>>,
+    last_time: Option<DateTime<Utc>>,
+}
+
+impl FeaturesSummary {
+    /// Counts a feature measured at `time`.
+    fn add(&mut self, time: Option<DateTime<Utc>>) {
+        self.count += 1;
+        if let Some(time) = time {
+            self.first_time = Some(self.first_time.map_or(time, |first| first.min(time)));
+            self.last_time = Some(self.last_time.map_or(time, |last| last.max(time)));
+        }
+    }
+}
+
+/// Summarizes the features array one feature at a time, so the features aren't kept in memory.
+struct FeaturesVisitor;
+
+impl<'de> Visitor<'de> for FeaturesVisitor {
+    type Value = FeaturesSummary;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        write!(formatter, "an array of features")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        let mut summary = FeaturesSummary::default();
+        while let Some(feature) = seq.next_element::<FeatureSummary>()? {
+            summary.add(feature.properties.and_then(|properties| properties.time));
+        }
+        Ok(summary)
+    }
+}
+
+/// Deserializes the features array with [`FeaturesVisitor`].
+fn summarize_features<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<FeaturesSummary, D::Error> {
+    deserializer.deserialize_seq(FeaturesVisitor)
+}
+
+/// The parts of a feature read when listing the datasets.
+#[derive(Debug, Deserialize)]
+struct FeatureSummary {
+    properties: Option<PropertiesSummary>,
+}
+
+/// The properties of a feature read when listing the datasets.
+#[derive(Debug, Deserialize)]
+struct PropertiesSummary {
+    time: Option<DateTime<Utc>>,
+}
+
+/// Gets the directory the datasets are stored in.
+pub fn datasets_dir(app_handle: &AppHandle) -> Result<PathBuf, AppError> {
+    app_data_file(app_handle, "datasets")
+}
+
+/// Gets the file a dataset is stored in.
+pub fn dataset_file(app_handle: &AppHandle, name: &str) -> Result<PathBuf, AppError> {
+    check_name(name)?;
+    Ok(datasets_dir(app_handle)?.join(format!("{name}{EXTENSION}")))
+}
+
+/// Gets the file the active dataset is stored in.
+pub fn active_data_file(app_handle: &AppHandle) -> Result<PathBuf, AppError> {
+    let name = active_dataset(app_handle);
+    dataset_file(app_handle, &name)
+}
+
+/// Gets the name of the active dataset.
+pub fn active_dataset(app_handle: &AppHandle) -> String {
+    app_handle
+        .state::<ActiveDataset>()
+        .name
+        .lock()
+        .unwrap()
+        .clone()
+}
+
+/// Creates the datasets directory and moves the boat data of older versions into the default
+/// dataset.
+///
+/// Nothing is moved if the default dataset already exists.
+pub fn init_datasets(app_handle: &AppHandle) -> Result<(), AppError> {
+    let dir = datasets_dir(app_handle)?;
+    fs::create_dir_all(&dir).map_err(|e| AppError::io(e, &dir))?;
+    let legacy = app_data_file(app_handle, "data.geojson")?;
+    let default = dataset_file(app_handle, DEFAULT_DATASET)?;
+    if !legacy.exists() || default.exists() {
+        return Ok(());
+    }
+
+    log::info!("Moving {} to the Default Dataset", legacy.display());
+    fs::rename(&legacy, default).map_err(|e| AppError::io(e, &legacy))
+}
+
+/// Creates the error of a dataset not existing.
+fn not_found(name: &str) -> AppError {
+    AppError::new(
+        codes::DATASET_NOT_FOUND,
+        format!("Unable to find Dataset: {name}"),
+    )
+    .with("name", name)
+}
+
+/// Gets the names of the datasets in `dir` sorted alphabetically.
+fn dataset_names(dir: &Path) -> Result<Vec<String>, AppError> {
+    let entries = match fs::read_dir(dir) {
+        Ok(v) => v,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(vec![]),
+        Err(e) => return Err(AppError::io(e, dir)),
+    };
+
+    let mut names: Vec<String> = entries
+        .filter_map(|entry| entry.ok()?.file_name().into_string().ok())
+        .filter_map(|name| Some(name.strip_suffix(EXTENSION)?.to_string()))
+        .filter(|name| check_name(name).is_ok())
+        .collect();
+    names.sort();
+    Ok(names)
+}
+
+/// Reads the feature count and time range of a dataset, including the data points in its
+/// journal.
+///
+/// The features are counted while the file is parsed, so a large dataset isn't read into
+/// memory.
+fn summarize(file: &Path, name: String) -> Result<DatasetInfo, AppError> {
+    let reader = BufReader::new(File::open(file).map_err(|e| AppError::io(e, file))?);
+    let summary: DatasetSummary = serde_json::from_reader(reader)
+        .map_err(|e| AppError::new(codes::DATA_INVALID_JSON, e.to_string()))?;
+
+    let mut features = summary.features;
+    for feature in journal::read_journal(&journal::journal_file(file))? {
+        features.add(Some(feature.time()));
+    }
+    Ok(DatasetInfo {
+        name,
+        features: features.count,
+        first_time: features.first_time,
+        last_time: features.last_time,
+    })
+}
+
+/// Reads a dataset from its file.
+pub fn read_dataset(file: &Path, name: &str) -> Result<BoatData, AppError> {
+    match fs::read_to_string(file) {
+        Ok(v) => BoatData::from_str(&v),
+        Err(e) if e.kind() == ErrorKind::NotFound => Err(not_found(name)),
+        Err(e) => Err(AppError::io(e, file)),
+    }
+}
+
+/// List the datasets in application storage.
+#[tauri::command]
+pub fn list_datasets(app_handle: AppHandle) -> Result<Vec<DatasetInfo>, AppError> {
+    log::debug!("Listing Datasets");
+    let dir = datasets_dir(&app_handle)?;
+
+    let mut datasets = vec![];
+    for name in dataset_names(&dir)? {
+        let file = dir.join(format!("{name}{EXTENSION}"));
+        match summarize(&file, name.clone()) {
+            Ok(v) => datasets.push(v),
+            Err(e) => log::warn!("Unable to Read Dataset: {}, Reason: {}", name, e),
+        }
+    }
+    Ok(datasets)
+}
+
+/// Create an empty dataset in application storage.
+#[tauri::command]
+pub fn create_dataset(app_handle: AppHandle, name: String) -> Result<(), AppError> {
+    log::info!("Creating Dataset: {}", name);
+    let file = dataset_file(&app_handle, &name)?;
+    let _writing = app_handle.state::<AutosaveState>().inner().lock_writes();
+    if file.exists() {
+        return Err(AppError::new(
+            codes::DATASET_ALREADY_EXISTS,
+            format!("Dataset Already Exists: {name}"),
+        )
+        .with("name", name));
+    }
+    data::write_data(&file, &BoatData::default())
+}
+
+/// Load a dataset from application storage.
+#[tauri::command]
+pub fn load_dataset(app_handle: AppHandle, name: String) -> Result<BoatData, AppError> {
+    log::info!("Loading Dataset: {}", name);
+    read_dataset(&dataset_file(&app_handle, &name)?, &name)
+}
+
+/// Delete a dataset from application storage.
+///
+/// The active dataset can't be deleted.
+#[tauri::command]
+pub fn delete_dataset(app_handle: AppHandle, name: String) -> Result<(), AppError> {
+    operations::record(
+        &app_handle,
+        "delete_dataset",
+        json!({ "name": name }),
+        || {
+            log::info!("Deleting Dataset: {}", name);
+            let file = dataset_file(&app_handle, &name)?;
+            if active_dataset(&app_handle) == name {
+                return Err(AppError::new(
+                    codes::DATASET_ACTIVE,
+                    format!("Unable to Delete the Active Dataset: {name}"),
+                )
+                .with("name", name));
+            }
+            fs::remove_file(&file).map_err(|e| match e.kind() {
+                ErrorKind::NotFound => not_found(&name),
+                _ => AppError::io(e, &file),
+            })
+        },
+    )
+}
+
+/// Append data points to a dataset in application storage.
+///
+/// Returns the number of data points in the dataset.
+#[tauri::command]
+pub fn append_to_dataset(
+    app_handle: AppHandle,
+    name: String,
+    features: Vec<BoatDataFeature>,
+) -> Result<usize, AppError> {
+    log::info!(
+        "Appending {} Data Points to Dataset: {}",
+        features.len(),
+        name
+    );
+    let features: Vec<_> = features
+        .into_iter()
+        .map(BoatDataFeature::with_random_id)
+        .collect();
+    let file = dataset_file(&app_handle, &name)?;
+    let _writing = app_handle.state::<AutosaveState>().inner().lock_writes();
+    let mut data = read_dataset(&file, &name)?;
+    data.extend(features.iter().cloned());
+    write_atomically(&file, |file| write!(file, "{}", data))?;
+    watcher::note_write(&app_handle, &file);
+
+    if active_dataset(&app_handle) == name {
+        app_handle.state::<DataIndex>().append(&features);
+        app_handle.state::<AppData>().append(&features);
+    }
+    Ok(data.features().len())
+}
+
+/// Set the dataset used by `read_data` and `save_data`, returning its data.
+#[tauri::command]
+pub fn set_active_dataset(app_handle: AppHandle, name: String) -> Result<BoatData, AppError> {
+    log::info!("Setting Active Dataset: {}", name);
+    if !dataset_file(&app_handle, &name)?.exists() {
+        return Err(not_found(&name));
+    }
+    // The edits are written to the dataset they were made to
+    autosave::flush(&app_handle)?;
+
+    *app_handle.state::<ActiveDataset>().name.lock().unwrap() = name;
+    app_handle.state::<AppData>().invalidate();
+    data::read_data(app_handle)
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+    use geo_types::Point;
+
+    use super::*;
+    use crate::{data::Layer, storage::tests::temp_dir};
+
+    /// Creates a data point measured `seconds` after the first one.
+    fn feature(seconds: i64) -> BoatDataFeature {
+        BoatDataFeature::new(
+            28.5,
+            0.5,
+            Layer::Surface,
+            Utc.timestamp_opt(1_715_500_000 + seconds, 0).unwrap(),
+            Point::new(101.5, 3.1),
+        )
+    }
+
+    #[test]
+    fn summarize_counts_features_and_journal() {
+        let dir = temp_dir("summarize");
+        let file = dir.join(format!("deployment{EXTENSION}"));
+        let data = BoatData::new(
+            BoatData::default().version().to_string(),
+            vec![feature(60), feature(0), feature(120)],
+        );
+        data::write_data(&file, &data).unwrap();
+        let journal: String = [feature(-30), feature(600)]
+            .iter()
+            .map(|f| geojson::ser::to_feature_string(f).unwrap() + "\n")
+            .collect();
+        fs::write(journal::journal_file(&file), journal).unwrap();
+
+        let info = summarize(&file, String::from("deployment")).unwrap();
+
+        assert_eq!(info.name, "deployment");
+        assert_eq!(info.features, 5);
+        assert_eq!(info.first_time, Some(feature(-30).time()));
+        assert_eq!(info.last_time, Some(feature(600).time()));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn summarize_empty_dataset() {
+        let dir = temp_dir("summarize-empty");
+        let file = dir.join(format!("empty{EXTENSION}"));
+        data::write_data(&file, &BoatData::default()).unwrap();
+
+        let info = summarize(&file, String::from("empty")).unwrap();
+
+        assert_eq!(info.features, 0);
+        assert_eq!(info.first_time, None);
+        assert_eq!(info.last_time, None);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn dataset_names_are_sorted_and_skip_other_files() {
+        let dir = temp_dir("dataset-names");
+        for name in ["river", "default", ".hidden"] {
+            fs::write(dir.join(format!("{name}{EXTENSION}")), "").unwrap();
+        }
+        fs::write(dir.join("default.geojson.tmp"), "").unwrap();
+
+        assert_eq!(dataset_names(&dir).unwrap(), vec!["default", "river"]);
+        assert!(dataset_names(&dir.join("missing")).unwrap().is_empty());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn read_dataset_names_missing_dataset() {
+        let dir = temp_dir("dataset-missing");
+
+        let error = read_dataset(&dir.join(format!("river{EXTENSION}")), "river").unwrap_err();
+
+        assert_eq!(error, not_found("river"));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,692 @@
+//! Errors sent to the frontend with a stable code, so the frontend can show them in the
+//! language of the user.
+//!
+//! The message of an error is in English and is only meant for the logs. The frontend looks up
+//! the translation of the code, filling it in with the parameters of the error.
+
+use std::{fmt::Display, path::Path};
+
+use serde::Serialize;
+use serde_json::{Map, Value};
+
+/// The codes of the errors sent to the frontend.
+///
+/// The codes are `<area>.<error>` in snake case, and are never changed once released as they
+/// are the translation keys of the frontend. [`ALL`](codes::ALL) lists every code.
+pub mod codes {
+    /// An error without a code, the message is the only information.
+    pub const UNKNOWN: &str = "unknown";
+
+    /// The file or directory doesn't exist. Parameters: `path`.
+    pub const IO_NOT_FOUND: &str = "io.not_found";
+    /// The file or directory can't be accessed. Parameters: `path`.
+    pub const IO_PERMISSION_DENIED: &str = "io.permission_denied";
+    /// The file or directory already exists. Parameters: `path`.
+    pub const IO_ALREADY_EXISTS: &str = "io.already_exists";
+    /// The file can't be read or written for another reason. Parameters: `path`.
+    pub const IO_OTHER: &str = "io.other";
+
+    /// There isn't enough free space to write the file. Parameters: `path`, `required`,
+    /// `available`.
+    pub const STORAGE_DISK_FULL: &str = "storage.disk_full";
+    /// The directory can't be written to, e.g. it is read only. Parameters: `path`.
+    pub const STORAGE_READ_ONLY: &str = "storage.read_only";
+    /// The application data directory of the platform can't be found.
+    pub const STORAGE_NO_APP_DIR: &str = "storage.no_app_dir";
+    /// The name can't be used as a file name, e.g. it has a path separator. Parameters: `name`.
+    pub const STORAGE_INVALID_NAME: &str = "storage.invalid_name";
+    /// The SQLite database can't be read or written. Parameters: `path`, `reason`.
+    pub const STORAGE_DATABASE: &str = "storage.database";
+
+    /// The file is not valid JSON or GeoJSON.
+    pub const DATA_INVALID_JSON: &str = "data.invalid_json";
+    /// The GeoJSON is not a feature collection.
+    pub const DATA_INVALID_GEOJSON: &str = "data.invalid_geojson";
+    /// The boat data has no version.
+    pub const DATA_MISSING_VERSION: &str = "data.missing_version";
+    /// The version of the boat data is not a string.
+    pub const DATA_INVALID_VERSION: &str = "data.invalid_version";
+    /// The features of the boat data are not data points. Parameters: `index`, if reading
+    /// stopped at an invalid feature.
+    pub const DATA_INVALID_FEATURES: &str = "data.invalid_features";
+    /// Some of the data points are out of range. Parameters: `count`, `issues`.
+    pub const DATA_INVALID_POINTS: &str = "data.invalid_points";
+    /// The data changed since it was read, so saving it would overwrite the changes.
+    /// Parameters: `base`, `current`.
+    pub const DATA_STALE_GENERATION: &str = "data.stale_generation";
+    /// A line of the journal of the data points received is not a data point. Parameters: `line`.
+    pub const DATA_INVALID_JOURNAL: &str = "data.invalid_journal";
+    /// The version of the data merged is not the version of the stored data. Parameters: `version`,
+    /// `stored`.
+    pub const DATA_VERSION_MISMATCH: &str = "data.version_mismatch";
+    /// Data can't be compared both approximately and ignoring the order of the data points.
+    pub const DATA_UNSUPPORTED_COMPARISON: &str = "data.unsupported_comparison";
+    /// The name of the layer is empty. Parameters: `layer`.
+    pub const DATA_INVALID_LAYER: &str = "data.invalid_layer";
+    /// There is no data point with the id. Parameters: `id`.
+    pub const DATA_FEATURE_NOT_FOUND: &str = "data.feature_not_found";
+
+    /// The CSV can't be read. Parameters: `row`.
+    pub const CSV_INVALID: &str = "csv.invalid";
+    /// The CSV header is missing a column. Parameters: `column`, `row`.
+    pub const CSV_MISSING_COLUMN: &str = "csv.missing_column";
+    /// A row of the CSV is not a data point. Parameters: `row`.
+    pub const CSV_INVALID_ROW: &str = "csv.invalid_row";
+    /// A time in the CSV can't be parsed. Parameters: `row`, `value`.
+    pub const CSV_INVALID_TIME: &str = "csv.invalid_time";
+    /// The CSV delimiter is not a single byte character. Parameters: `delimiter`.
+    pub const CSV_INVALID_DELIMITER: &str = "csv.invalid_delimiter";
+    /// The timezone is not an IANA timezone. Parameters: `timezone`.
+    pub const CSV_INVALID_TIMEZONE: &str = "csv.invalid_timezone";
+    /// A column of the CSV export is invalid. Parameters: `column`, `reason`.
+    pub const CSV_INVALID_COLUMN: &str = "csv.invalid_column";
+    /// No columns are selected for the CSV export.
+    pub const CSV_NO_COLUMNS: &str = "csv.no_columns";
+
+    /// The import was cancelled.
+    pub const IMPORT_CANCELLED: &str = "import.cancelled";
+
+    /// The version of the stored data is not a semantic version. Parameters: `version`.
+    pub const MIGRATION_INVALID_VERSION: &str = "migration.invalid_version";
+    /// The version of the stored data can't be read. Parameters: `found`, `supported`.
+    pub const MIGRATION_UNSUPPORTED_VERSION: &str = "migration.unsupported_version";
+    /// The stored GeoJSON is not a feature collection.
+    pub const MIGRATION_INVALID_GEOJSON: &str = "migration.invalid_geojson";
+
+    /// The file is not valid path GeoJSON.
+    pub const PATH_INVALID_GEOJSON: &str = "path.invalid_geojson";
+    /// The path has no version.
+    pub const PATH_MISSING_VERSION: &str = "path.missing_version";
+    /// The version of the path is not a string.
+    pub const PATH_INVALID_VERSION: &str = "path.invalid_version";
+    /// A geometry of the path is invalid. Parameters: `geometry`.
+    pub const PATH_INVALID_GEOMETRY: &str = "path.invalid_geometry";
+    /// The path has no Line String feature, or the GPX has no track or route.
+    pub const PATH_TOO_FEW_FEATURES: &str = "path.too_few_features";
+    /// There is no collection point at the index. Parameters: `index`, `len`.
+    pub const PATH_COLLECTION_POINT_OUT_OF_RANGE: &str = "path.collection_point_out_of_range";
+    /// There is no waypoint at the index. Parameters: `index`, `len`.
+    pub const PATH_WAYPOINT_OUT_OF_RANGE: &str = "path.waypoint_out_of_range";
+    /// Some of the points are outside of the boundary. Parameters: `waypoints`,
+    /// `collection_points`.
+    pub const PATH_OUTSIDE_BOUNDARY: &str = "path.outside_boundary";
+    /// The boundary is not a valid polygon. Parameters: `reason`.
+    pub const PATH_INVALID_BOUNDARY: &str = "path.invalid_boundary";
+    /// The path can't be saved to application storage. Parameters: `reason`.
+    pub const PATH_STORAGE: &str = "path.storage";
+    /// The file is not valid GPX. Parameters: `reason`.
+    pub const PATH_INVALID_GPX: &str = "path.invalid_gpx";
+
+    /// There is no connection to the boat.
+    pub const UPLOAD_NOT_CONNECTED: &str = "upload.not_connected";
+    /// The path failed the checks before uploading. Parameters: `issues`.
+    pub const UPLOAD_PREFLIGHT: &str = "upload.preflight_failed";
+    /// The boat rejected a chunk of the path. Parameters: `chunk`, `reason`.
+    pub const UPLOAD_REJECTED: &str = "upload.rejected";
+    /// The boat didn't reply to a chunk of the path. Parameters: `chunk`, `attempts`.
+    pub const UPLOAD_TIMEOUT: &str = "upload.timeout";
+    /// The upload stopped unexpectedly. Parameters: `reason`.
+    pub const UPLOAD_SERIAL: &str = "upload.serial";
+
+    /// The command changes the state so it can't be invoked from the read-only viewer.
+    /// Parameters: `command`.
+    pub const WINDOW_READ_ONLY: &str = "window.read_only";
+    /// The viewer window can't be opened or focused. Parameters: `reason`.
+    pub const WINDOW_FAILED: &str = "window.failed";
+
+    /// The name is not the name of a backup. Parameters: `name`.
+    pub const BACKUP_INVALID_NAME: &str = "backup.invalid_name";
+
+    /// There is no dataset with the name. Parameters: `name`.
+    pub const DATASET_NOT_FOUND: &str = "dataset.not_found";
+    /// There is already a dataset with the name. Parameters: `name`.
+    pub const DATASET_ALREADY_EXISTS: &str = "dataset.already_exists";
+    /// The active dataset can't be deleted. Parameters: `name`.
+    pub const DATASET_ACTIVE: &str = "dataset.active";
+
+    /// The data can't be written in the format of the export. Parameters: `path`, `reason`.
+    pub const EXPORT_FAILED: &str = "export.failed";
+    /// The boat data exported has no readings and the format can't hold none.
+    pub const EXPORT_EMPTY: &str = "export.empty";
+
+    /// There is no edit of the path to undo.
+    pub const HISTORY_NOTHING_TO_UNDO: &str = "history.nothing_to_undo";
+    /// There is no edit of the path to redo.
+    pub const HISTORY_NOTHING_TO_REDO: &str = "history.nothing_to_redo";
+
+    /// There is no mission with the name. Parameters: `name`.
+    pub const MISSION_NOT_FOUND: &str = "mission.not_found";
+    /// There is already a mission with the name. Parameters: `name`.
+    pub const MISSION_ALREADY_EXISTS: &str = "mission.already_exists";
+    /// The current mission can't be deleted. Parameters: `name`.
+    pub const MISSION_CURRENT: &str = "mission.current";
+
+    /// The auto export settings are not set.
+    pub const AUTO_EXPORT_NOT_SET_UP: &str = "auto_export.not_set_up";
+    /// The file name template of the auto export is invalid. Parameters: `reason`.
+    pub const AUTO_EXPORT_INVALID_TEMPLATE: &str = "auto_export.invalid_template";
+    /// There are too many exports with the name in the folder. Parameters: `name`.
+    pub const AUTO_EXPORT_TOO_MANY: &str = "auto_export.too_many";
+
+    /// The value of a setting is invalid. Parameters: `setting`, `reason`.
+    pub const SETTINGS_INVALID: &str = "settings.invalid";
+    /// The setting doesn't exist. Parameters: `setting`.
+    pub const SETTINGS_UNKNOWN: &str = "settings.unknown";
+    /// The settings changed are not an object of settings. Parameters: `reason`.
+    pub const SETTINGS_INVALID_PATCH: &str = "settings.invalid_patch";
+    /// The data directory can't be moved while connected to the boat.
+    pub const SETTINGS_CONNECTED: &str = "settings.connected";
+
+    /// The calibration can't be applied. Parameters: `reason`.
+    pub const CALIBRATION_INVALID: &str = "calibration.invalid";
+
+    /// The data points can't be copied to the clipboard. Parameters: `reason`.
+    pub const CLIPBOARD_FAILED: &str = "clipboard.failed";
+
+    /// The clock offset moves the times out of range. Parameters: `offset_ms`.
+    pub const CLOCK_INVALID_OFFSET: &str = "clock.invalid_offset";
+
+    /// The serial ports can't be listed. Parameters: `reason`.
+    pub const CONNECTION_LIST_PORTS_FAILED: &str = "connection.list_ports_failed";
+    /// There is no boat connected on the port. Parameters: `port`.
+    pub const CONNECTION_PORT_NOT_FOUND: &str = "connection.port_not_found";
+    /// The port can't be opened. Parameters: `port`, `reason`.
+    pub const CONNECTION_OPEN_FAILED: &str = "connection.open_failed";
+    /// The message can't be sent to the boat. Parameters: `reason`.
+    pub const CONNECTION_SEND_FAILED: &str = "connection.send_failed";
+    /// The boat can't be connected to while a replay is running.
+    pub const CONNECTION_REPLAY_RUNNING: &str = "connection.replay_running";
+    /// There is no connection to the boat.
+    pub const CONNECTION_NOT_CONNECTED: &str = "connection.not_connected";
+    /// The boat rejected the message. Parameters: `reason`.
+    pub const CONNECTION_REJECTED: &str = "connection.rejected";
+    /// The boat didn't reply to the message. Parameters: `attempts`.
+    pub const CONNECTION_TIMEOUT: &str = "connection.timeout";
+
+    /// The cell size of the grid is not a positive number. Parameters: `cell_size`.
+    pub const GRID_INVALID_CELL_SIZE: &str = "grid.invalid_cell_size";
+    /// The grid would have too many cells. Parameters: `cells`, `max`.
+    pub const GRID_TOO_MANY_CELLS: &str = "grid.too_many_cells";
+    /// The interpolation radius is negative or covers too many cells. Parameters: `radius`.
+    pub const GRID_INVALID_RADIUS: &str = "grid.invalid_radius";
+
+    /// The contour interval is not a positive number or traces too many levels. Parameters:
+    /// `interval`.
+    pub const CONTOURS_INVALID_INTERVAL: &str = "contours.invalid_interval";
+
+    /// The coordinate typed can't be parsed. Parameters: `input`.
+    pub const COORDINATES_INVALID: &str = "coordinates.invalid";
+    /// The latitude or longitude is out of range. Parameters: `lng`, `lat`.
+    pub const COORDINATES_OUT_OF_RANGE: &str = "coordinates.out_of_range";
+
+    /// The radius around the collection points is negative. Parameters: `radius`.
+    pub const COVERAGE_INVALID_RADIUS: &str = "coverage.invalid_radius";
+
+    /// No weather API is set in the settings.
+    pub const CONDITIONS_NO_API: &str = "conditions.no_api";
+    /// There is no mission path to fetch the conditions at.
+    pub const CONDITIONS_NO_PATH: &str = "conditions.no_path";
+    /// The weather API can't be reached or its response is invalid. Parameters: `reason`.
+    pub const CONDITIONS_API_FAILED: &str = "conditions.api_failed";
+    /// A value of the conditions is out of range. Parameters: `reason`.
+    pub const CONDITIONS_INVALID: &str = "conditions.invalid";
+
+    /// No frames are being logged.
+    pub const FRAME_LOG_NOT_RUNNING: &str = "frame_log.not_running";
+    /// A line of the frame log is not a frame. Parameters: `line`.
+    pub const FRAME_LOG_INVALID: &str = "frame_log.invalid";
+
+    /// An argument of a path operation is negative or not finite. Parameters: `argument`, `value`.
+    pub const GEOMETRY_INVALID_ARGUMENT: &str = "geometry.invalid_argument";
+    /// The path has no waypoints.
+    pub const GEOMETRY_EMPTY_PATH: &str = "geometry.empty_path";
+    /// The spacing would generate too many points. Parameters: `count`, `max`.
+    pub const GEOMETRY_TOO_MANY_POINTS: &str = "geometry.too_many_points";
+    /// The path is split at one of its ends. Parameters: `index`.
+    pub const GEOMETRY_SPLIT_AT_END: &str = "geometry.split_at_end";
+    /// The paths joined have different versions.
+    pub const GEOMETRY_VERSION_MISMATCH: &str = "geometry.version_mismatch";
+    /// The paths joined are too far apart. Parameters: `gap`, `max_gap`.
+    pub const GEOMETRY_GAP_TOO_LARGE: &str = "geometry.gap_too_large";
+    /// The survey polygon has too few vertices or an invalid coordinate.
+    pub const GEOMETRY_INVALID_POLYGON: &str = "geometry.invalid_polygon";
+    /// A point measured is not a valid coordinate. Parameters: `index`.
+    pub const MEASURE_INVALID_COORDINATE: &str = "measure.invalid_coordinate";
+    /// The polygon measured has fewer than three vertices.
+    pub const MEASURE_INVALID_POLYGON: &str = "measure.invalid_polygon";
+
+    /// A health threshold is not finite.
+    pub const HEALTH_INVALID_THRESHOLDS: &str = "health.invalid_thresholds";
+
+    /// The heartbeat interval isn't greater than zero. Parameters: `seconds`.
+    pub const HEARTBEAT_INVALID_INTERVAL: &str = "heartbeat.invalid_interval";
+
+    /// An asset in the manifest has a name escaping the map directory. Parameters: `name`.
+    pub const MAP_ASSETS_INVALID_NAME: &str = "map_assets.invalid_name";
+    /// The map manifest is not valid JSON. Parameters: `reason`.
+    pub const MAP_ASSETS_INVALID_MANIFEST: &str = "map_assets.invalid_manifest";
+    /// Downloading from the map asset server failed. Parameters: `url`, `reason`.
+    pub const MAP_ASSETS_DOWNLOAD_FAILED: &str = "map_assets.download_failed";
+    /// A downloaded asset doesn't match the checksum in the manifest. Parameters: `name`.
+    pub const MAP_ASSETS_CHECKSUM_MISMATCH: &str = "map_assets.checksum_mismatch";
+    /// The map assets are already being downloaded.
+    pub const MAP_ASSETS_BUSY: &str = "map_assets.busy";
+
+    /// The MBTiles database can't be opened or queried. Parameters: `db`, `reason`.
+    pub const MBTILES_DATABASE: &str = "mbtiles.database";
+    /// The MBTiles database has no tile at the position. Parameters: `zoom`, `column`, `row`.
+    pub const MBTILES_TILE_NOT_FOUND: &str = "mbtiles.tile_not_found";
+    /// The tile data isn't gzip compressed. Parameters: `reason`.
+    pub const MBTILES_INVALID_TILE: &str = "mbtiles.invalid_tile";
+    /// A metadata value of the MBTiles database is invalid. Parameters: `key`, `reason`.
+    pub const MBTILES_INVALID_METADATA: &str = "mbtiles.invalid_metadata";
+
+    /// The water level of the dataset is not finite. Parameters: `water_level_m`.
+    pub const METADATA_INVALID_WATER_LEVEL: &str = "metadata.invalid_water_level";
+
+    /// The project archive isn't a valid zip file. Parameters: `reason`.
+    pub const PROJECT_INVALID_ARCHIVE: &str = "project.invalid_archive";
+    /// The project archive is missing an entry. Parameters: `entry`.
+    pub const PROJECT_MISSING_ENTRY: &str = "project.missing_entry";
+    /// An entry of the project archive is invalid. Parameters: `entry`, `reason`.
+    pub const PROJECT_INVALID_ENTRY: &str = "project.invalid_entry";
+    /// The project archive is of a newer layout. Parameters: `format`, `supported`.
+    pub const PROJECT_UNSUPPORTED_FORMAT: &str = "project.unsupported_format";
+
+    /// The file isn't a valid Parquet file. Parameters: `path`, `reason`.
+    pub const PARQUET_INVALID: &str = "parquet.invalid";
+    /// The Parquet file is missing columns of boat data or has them with the wrong type.
+    /// Parameters: `missing`, `mistyped`.
+    pub const PARQUET_INVALID_SCHEMA: &str = "parquet.invalid_schema";
+    /// A row of the Parquet file isn't a valid data point. Parameters: `row`, `reason`.
+    pub const PARQUET_INVALID_ROW: &str = "parquet.invalid_row";
+
+    /// The corridor width is negative or not finite. Parameters: `corridor_width_m`.
+    pub const PROFILE_INVALID_CORRIDOR: &str = "profile.invalid_corridor";
+    /// The path has fewer than two waypoints.
+    pub const PROFILE_TOO_FEW_WAYPOINTS: &str = "profile.too_few_waypoints";
+
+    /// The bounding box of the region is out of range or inverted. Parameters: `bbox`.
+    pub const REGION_INVALID_BBOX: &str = "region.invalid_bbox";
+    /// The zoom levels of the region are inverted or too large. Parameters: `min_zoom`, `max_zoom`,
+    /// `max`.
+    pub const REGION_INVALID_ZOOM: &str = "region.invalid_zoom";
+    /// The number of concurrent requests is 0.
+    pub const REGION_INVALID_OPTIONS: &str = "region.invalid_options";
+    /// No tile source is set to download regions from.
+    pub const REGION_NO_TILE_SOURCE: &str = "region.no_tile_source";
+    /// The region has more tiles than the limit. Parameters: `tiles`, `max`.
+    pub const REGION_TOO_MANY_TILES: &str = "region.too_many_tiles";
+    /// A region is already being downloaded.
+    pub const REGION_BUSY: &str = "region.busy";
+    /// The region doesn't exist. Parameters: `name`.
+    pub const REGION_NOT_FOUND: &str = "region.not_found";
+    /// The offline archive can't be opened or queried. Parameters: `path`, `reason`.
+    pub const REGION_ARCHIVE: &str = "region.archive";
+
+    /// A sidecar file of the shapefile doesn't exist. Parameters: `path`.
+    pub const SHAPEFILE_MISSING_SIDECAR: &str = "shapefile.missing_sidecar";
+    /// The CRS in the `.prj` file isn't supported.
+    pub const SHAPEFILE_UNSUPPORTED_PROJECTION: &str = "shapefile.unsupported_projection";
+    /// The file isn't a valid shapefile. Parameters: `path`, `reason`.
+    pub const SHAPEFILE_INVALID: &str = "shapefile.invalid";
+    /// A record of the shapefile isn't a valid data point. Parameters: `record`, `reason`.
+    pub const SHAPEFILE_INVALID_RECORD: &str = "shapefile.invalid_record";
+    /// The path shapefile has no polyline.
+    pub const SHAPEFILE_NO_POLYLINE: &str = "shapefile.no_polyline";
+
+    /// The speed multiplier isn't greater than zero. Parameters: `speed_multiplier`.
+    pub const REPLAY_INVALID_SPEED: &str = "replay.invalid_speed";
+    /// A replay is started while connected to the boat.
+    pub const REPLAY_CONNECTED: &str = "replay.connected";
+    /// A replay is started while another is running.
+    pub const REPLAY_RUNNING: &str = "replay.running";
+    /// No replay is running.
+    pub const REPLAY_NOT_RUNNING: &str = "replay.not_running";
+
+    /// The bucket size isn't a positive count with a unit. Parameters: `bucket`.
+    pub const STATISTICS_INVALID_BUCKET: &str = "statistics.invalid_bucket";
+
+    /// The spacing is negative or not finite. Parameters: `min_spacing_m`.
+    pub const THINNING_INVALID_SPACING: &str = "thinning.invalid_spacing";
+
+    /// The event rate isn't greater than zero. Parameters: `per_second`.
+    pub const TRACKING_INVALID_EVENT_RATE: &str = "tracking.invalid_event_rate";
+    /// The track exported has fewer than two positions.
+    pub const TRACKING_TOO_FEW_POSITIONS: &str = "tracking.too_few_positions";
+
+    /// Every error code, for generating the translation keys of the frontend.
+    pub const ALL: &[&str] = &[
+        UNKNOWN,
+        IO_NOT_FOUND,
+        IO_PERMISSION_DENIED,
+        IO_ALREADY_EXISTS,
+        IO_OTHER,
+        STORAGE_DISK_FULL,
+        STORAGE_READ_ONLY,
+        STORAGE_NO_APP_DIR,
+        STORAGE_INVALID_NAME,
+        STORAGE_DATABASE,
+        DATA_INVALID_JSON,
+        DATA_INVALID_GEOJSON,
+        DATA_MISSING_VERSION,
+        DATA_INVALID_VERSION,
+        DATA_INVALID_FEATURES,
+        DATA_INVALID_POINTS,
+        DATA_STALE_GENERATION,
+        DATA_INVALID_JOURNAL,
+        DATA_VERSION_MISMATCH,
+        DATA_UNSUPPORTED_COMPARISON,
+        DATA_INVALID_LAYER,
+        DATA_FEATURE_NOT_FOUND,
+        CSV_INVALID,
+        CSV_MISSING_COLUMN,
+        CSV_INVALID_ROW,
+        CSV_INVALID_TIME,
+        CSV_INVALID_DELIMITER,
+        CSV_INVALID_TIMEZONE,
+        CSV_INVALID_COLUMN,
+        CSV_NO_COLUMNS,
+        IMPORT_CANCELLED,
+        MIGRATION_INVALID_VERSION,
+        MIGRATION_UNSUPPORTED_VERSION,
+        MIGRATION_INVALID_GEOJSON,
+        PATH_INVALID_GEOJSON,
+        PATH_MISSING_VERSION,
+        PATH_INVALID_VERSION,
+        PATH_INVALID_GEOMETRY,
+        PATH_TOO_FEW_FEATURES,
+        PATH_COLLECTION_POINT_OUT_OF_RANGE,
+        PATH_WAYPOINT_OUT_OF_RANGE,
+        PATH_OUTSIDE_BOUNDARY,
+        PATH_INVALID_BOUNDARY,
+        PATH_STORAGE,
+        PATH_INVALID_GPX,
+        UPLOAD_NOT_CONNECTED,
+        UPLOAD_PREFLIGHT,
+        UPLOAD_REJECTED,
+        UPLOAD_TIMEOUT,
+        UPLOAD_SERIAL,
+        WINDOW_READ_ONLY,
+        WINDOW_FAILED,
+        BACKUP_INVALID_NAME,
+        DATASET_NOT_FOUND,
+        DATASET_ALREADY_EXISTS,
+        DATASET_ACTIVE,
+        EXPORT_FAILED,
+        EXPORT_EMPTY,
+        HISTORY_NOTHING_TO_UNDO,
+        HISTORY_NOTHING_TO_REDO,
+        MISSION_NOT_FOUND,
+        MISSION_ALREADY_EXISTS,
+        MISSION_CURRENT,
+        AUTO_EXPORT_NOT_SET_UP,
+        AUTO_EXPORT_INVALID_TEMPLATE,
+        AUTO_EXPORT_TOO_MANY,
+        SETTINGS_INVALID,
+        SETTINGS_UNKNOWN,
+        SETTINGS_INVALID_PATCH,
+        SETTINGS_CONNECTED,
+        CALIBRATION_INVALID,
+        CLIPBOARD_FAILED,
+        CLOCK_INVALID_OFFSET,
+        CONNECTION_LIST_PORTS_FAILED,
+        CONNECTION_PORT_NOT_FOUND,
+        CONNECTION_OPEN_FAILED,
+        CONNECTION_SEND_FAILED,
+        CONNECTION_REPLAY_RUNNING,
+        CONNECTION_NOT_CONNECTED,
+        CONNECTION_REJECTED,
+        CONNECTION_TIMEOUT,
+        GRID_INVALID_CELL_SIZE,
+        GRID_TOO_MANY_CELLS,
+        GRID_INVALID_RADIUS,
+        CONTOURS_INVALID_INTERVAL,
+        COORDINATES_INVALID,
+        COORDINATES_OUT_OF_RANGE,
+        COVERAGE_INVALID_RADIUS,
+        CONDITIONS_NO_API,
+        CONDITIONS_NO_PATH,
+        CONDITIONS_API_FAILED,
+        CONDITIONS_INVALID,
+        FRAME_LOG_NOT_RUNNING,
+        FRAME_LOG_INVALID,
+        GEOMETRY_INVALID_ARGUMENT,
+        GEOMETRY_EMPTY_PATH,
+        GEOMETRY_TOO_MANY_POINTS,
+        GEOMETRY_SPLIT_AT_END,
+        GEOMETRY_VERSION_MISMATCH,
+        GEOMETRY_GAP_TOO_LARGE,
+        GEOMETRY_INVALID_POLYGON,
+        MEASURE_INVALID_COORDINATE,
+        MEASURE_INVALID_POLYGON,
+        HEALTH_INVALID_THRESHOLDS,
+        HEARTBEAT_INVALID_INTERVAL,
+        MAP_ASSETS_INVALID_NAME,
+        MAP_ASSETS_INVALID_MANIFEST,
+        MAP_ASSETS_DOWNLOAD_FAILED,
+        MAP_ASSETS_CHECKSUM_MISMATCH,
+        MAP_ASSETS_BUSY,
+        MBTILES_DATABASE,
+        MBTILES_TILE_NOT_FOUND,
+        MBTILES_INVALID_TILE,
+        MBTILES_INVALID_METADATA,
+        METADATA_INVALID_WATER_LEVEL,
+        PROJECT_INVALID_ARCHIVE,
+        PROJECT_MISSING_ENTRY,
+        PROJECT_INVALID_ENTRY,
+        PROJECT_UNSUPPORTED_FORMAT,
+        PARQUET_INVALID,
+        PARQUET_INVALID_SCHEMA,
+        PARQUET_INVALID_ROW,
+        PROFILE_INVALID_CORRIDOR,
+        PROFILE_TOO_FEW_WAYPOINTS,
+        REGION_INVALID_BBOX,
+        REGION_INVALID_ZOOM,
+        REGION_INVALID_OPTIONS,
+        REGION_NO_TILE_SOURCE,
+        REGION_TOO_MANY_TILES,
+        REGION_BUSY,
+        REGION_NOT_FOUND,
+        REGION_ARCHIVE,
+        SHAPEFILE_MISSING_SIDECAR,
+        SHAPEFILE_UNSUPPORTED_PROJECTION,
+        SHAPEFILE_INVALID,
+        SHAPEFILE_INVALID_RECORD,
+        SHAPEFILE_NO_POLYLINE,
+        REPLAY_INVALID_SPEED,
+        REPLAY_CONNECTED,
+        REPLAY_RUNNING,
+        REPLAY_NOT_RUNNING,
+        STATISTICS_INVALID_BUCKET,
+        THINNING_INVALID_SPACING,
+        TRACKING_INVALID_EVENT_RATE,
+        TRACKING_TOO_FEW_POSITIONS,
+    ];
+}
+
+/// An error sent to the frontend.
+///
+/// Serialized as `{ "code": ..., "message": ..., "params": { ... } }`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct AppError {
+    /// The stable code of the error, one of [`codes`].
+    code: &'static str,
+    /// The error in English, for the logs.
+    message: String,
+    /// The values filled into the translation of the error.
+    params: Map<String, Value>,
+}
+
+impl AppError {
+    /// Creates an error without parameters.
+    pub fn new(code: &'static str, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+            params: Map::new(),
+        }
+    }
+
+    /// Adds a parameter to the error.
+    pub fn with(mut self, name: &str, value: impl Into<Value>) -> Self {
+        self.params.insert(name.to_string(), value.into());
+        self
+    }
+
+    /// Creates an error caused by an error of another library, which is added as the `reason`
+    /// parameter.
+    ///
+    /// The message is `context` followed by the reason, e.g. `Unable to Export GPX: ...`.
+    pub fn reason(code: &'static str, context: &str, reason: impl Display) -> Self {
+        let reason = reason.to_string();
+        Self::new(code, format!("{context}: {reason}")).with("reason", reason)
+    }
+
+    /// Creates an error from an IO error on the file at `path`.
+    pub fn io(error: std::io::Error, path: &Path) -> Self {
+        Self::from(error).with("path", path.display().to_string())
+    }
+}
+
+impl Display for AppError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl std::error::Error for AppError {}
+
+impl From<String> for AppError {
+    /// Creates an error without a code, e.g. from the errors of other modules.
+    fn from(value: String) -> Self {
+        Self::new(codes::UNKNOWN, value)
+    }
+}
+
+impl From<AppError> for String {
+    fn from(value: AppError) -> Self {
+        value.message
+    }
+}
+
+impl From<std::io::Error> for AppError {
+    fn from(value: std::io::Error) -> Self {
+        let code = match value.kind() {
+            std::io::ErrorKind::NotFound => codes::IO_NOT_FOUND,
+            std::io::ErrorKind::PermissionDenied => codes::IO_PERMISSION_DENIED,
+            std::io::ErrorKind::AlreadyExists => codes::IO_ALREADY_EXISTS,
+            _ => codes::IO_OTHER,
+        };
+        Self::new(code, value.to_string())
+    }
+}
+
+/// Creates the error of the data failing to be written in the format of the export to
+/// `export_path`.
+pub fn export_error(export_path: &Path, reason: impl Display) -> AppError {
+    AppError::reason(
+        codes::EXPORT_FAILED,
+        &format!("Unable to Export to {}", export_path.display()),
+        reason,
+    )
+    .with("path", export_path.display().to_string())
+}
+
+/// List every error code, for generating the translation keys of the frontend.
+#[tauri::command]
+pub fn error_codes() -> Vec<&'static str> {
+    codes::ALL.to_vec()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashSet, io::ErrorKind};
+
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn error_is_serialized_with_code_message_and_params() {
+        let error = AppError::new(codes::DATASET_NOT_FOUND, "Unable to find Dataset: river")
+            .with("name", "river");
+
+        assert_eq!(
+            serde_json::to_value(&error).unwrap(),
+            json!({
+                "code": "dataset.not_found",
+                "message": "Unable to find Dataset: river",
+                "params": { "name": "river" },
+            })
+        );
+        assert_eq!(error.to_string(), "Unable to find Dataset: river");
+        assert_eq!(String::from(error), "Unable to find Dataset: river");
+    }
+
+    #[test]
+    fn reason_is_added_to_the_message_and_params() {
+        let error = AppError::reason(codes::PATH_INVALID_GPX, "Invalid Path GPX", "no tracks");
+
+        assert_eq!(error.to_string(), "Invalid Path GPX: no tracks");
+        assert_eq!(
+            error,
+            AppError::new(codes::PATH_INVALID_GPX, "Invalid Path GPX: no tracks")
+                .with("reason", "no tracks")
+        );
+    }
+
+    #[test]
+    fn io_errors_are_coded_by_kind() {
+        let path = Path::new("data.geojson");
+        for (kind, code) in [
+            (ErrorKind::NotFound, codes::IO_NOT_FOUND),
+            (ErrorKind::PermissionDenied, codes::IO_PERMISSION_DENIED),
+            (ErrorKind::AlreadyExists, codes::IO_ALREADY_EXISTS),
+            (ErrorKind::Other, codes::IO_OTHER),
+        ] {
+            let error = AppError::io(std::io::Error::new(kind, "failed"), path);
+            assert_eq!(
+                error,
+                AppError::new(code, "failed").with("path", "data.geojson"),
+                "{kind:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn strings_have_no_code() {
+        assert_eq!(
+            AppError::from(String::from("Failed")),
+            AppError::new(codes::UNKNOWN, "Failed")
+        );
+    }
+
+    #[test]
+    fn export_error_names_the_file() {
+        let error = export_error(Path::new("data.kml"), "fmt error");
+
+        assert_eq!(
+            serde_json::to_value(error).unwrap(),
+            json!({
+                "code": codes::EXPORT_FAILED,
+                "message": "Unable to Export to data.kml: fmt error",
+                "params": { "reason": "fmt error", "path": "data.kml" },
+            })
+        );
+    }
+
+    #[test]
+    fn codes_are_unique_and_well_formed() {
+        let unique: HashSet<_> = codes::ALL.iter().collect();
+        assert_eq!(unique.len(), codes::ALL.len());
+        for code in codes::ALL {
+            assert!(
+                code.split('.').all(|part| !part.is_empty()
+                    && part.chars().all(|c| c.is_ascii_lowercase() || c == '_')),
+                "{code}"
+            );
+        }
+        assert_eq!(error_codes(), codes::ALL);
+    }
+}
@@ -0,0 +1,245 @@
+//! Previews of exports, so the operator knows how much will be written before exporting to a
+//! slow network share.
+//!
+//! A preview serializes the data like the export but only counts the bytes, and checks the
+//! folder of the export can be written to by creating and removing a temporary file in it.
+
+use std::{
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+use serde::Serialize;
+use uuid::Uuid;
+
+use crate::{
+    data::BoatData,
+    errors::{export_error, AppError},
+};
+
+/// What an export would write.
+#[derive(Debug, Serialize, Clone, PartialEq, Eq)]
+pub struct ExportPreview {
+    /// The number of readings exported.
+    rows: usize,
+    /// The size of the export in bytes.
+    estimated_bytes: u64,
+    /// The problems the operator should know about before exporting.
+    warnings: Vec<String>,
+}
+
+/// A sink counting the bytes written to it.
+#[derive(Debug, Default)]
+struct CountingWriter {
+    bytes: u64,
+}
+
+impl Write for CountingWriter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.bytes += buf.len() as u64;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Gets the folder an export is written to.
+fn export_dir(export_path: &Path) -> PathBuf {
+    match export_path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir.to_path_buf(),
+        _ => PathBuf::from("."),
+    }
+}
+
+/// Checks files can be created in the folder of an export, returning the reason they can't.
+fn check_writable(export_path: &Path) -> Result<(), String> {
+    let dir = export_dir(export_path);
+    let test_file = dir.join(format!(".export-test-{}.tmp", Uuid::new_v4()));
+    std::fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(&test_file)
+        .map_err(|e| format!("Unable to write to {}: {e}", dir.display()))?;
+    if let Err(e) = std::fs::remove_file(&test_file) {
+        log::warn!(
+            "Unable to Remove Test File: {}, Reason: {}",
+            test_file.display(),
+            e
+        );
+    }
+    Ok(())
+}
+
+/// Gets the warnings about the readings exported, where `non_finite` is how the format writes
+/// values that are not a number.
+fn data_warnings(data: &BoatData, non_finite: &str) -> Vec<String> {
+    let features = data.features();
+    if features.is_empty() {
+        return vec![String::from("There are no readings to export")];
+    }
+
+    let mut warnings = vec![];
+    let temperatures = features
+        .iter()
+        .filter(|f| !f.temperature().is_finite())
+        .count();
+    if temperatures > 0 {
+        warnings.push(format!(
+            "{temperatures} readings have a temperature that is not a number and will be \
+             written as {non_finite}"
+        ));
+    }
+    let depths = features.iter().filter(|f| !f.depth().is_finite()).count();
+    if depths > 0 {
+        warnings.push(format!(
+            "{depths} readings have a depth that is not a number and will be written as \
+             {non_finite}"
+        ));
+    }
+    warnings
+}
+
+/// Previews exporting boat data to `export_path` without creating the file.
+///
+/// `write` serializes the export like it is written to the file, and `non_finite` is how the
+/// format writes values that are not a number. Errors serializing the export are returned like
+/// the errors of the export, while a folder that can't be written to is a warning.
+pub fn preview<F>(
+    export_path: &Path,
+    data: &BoatData,
+    non_finite: &str,
+    write: F,
+) -> Result<ExportPreview, AppError>
+where
+    F: FnOnce(&mut dyn Write) -> std::io::Result<()>,
+{
+    log::debug!("Previewing Export to: {}", export_path.display());
+    let mut sink = CountingWriter::default();
+    write(&mut sink).map_err(|e| export_error(export_path, e))?;
+
+    let mut warnings = data_warnings(data, non_finite);
+    if export_path.exists() {
+        warnings.push(format!(
+            "{} exists and will be replaced",
+            export_path.display()
+        ));
+    }
+    if let Err(e) = check_writable(export_path) {
+        warnings.push(e);
+    }
+    Ok(ExportPreview {
+        rows: data.features().len(),
+        estimated_bytes: sink.bytes,
+        warnings,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeZone, Utc};
+    use geo_types::Point;
+
+    use super::*;
+    use crate::{
+        data::{BoatDataFeature, Layer},
+        storage::tests::temp_dir,
+    };
+
+    fn data(temperatures: &[f64]) -> BoatData {
+        let features = temperatures
+            .iter()
+            .map(|temperature| {
+                BoatDataFeature::new(
+                    *temperature,
+                    0.5,
+                    Layer::Surface,
+                    Utc.timestamp_opt(1_715_495_400, 0).unwrap(),
+                    Point::new(101.5, 3.1),
+                )
+            })
+            .collect();
+        BoatData::new(BoatData::default().version().to_string(), features)
+    }
+
+    #[test]
+    fn previews_count_the_bytes_without_writing() {
+        let dir = temp_dir("preview-count");
+        let path = dir.join("export.csv");
+
+        let preview = preview(&path, &data(&[28.5, 29.0]), "NaN", |file| {
+            file.write_all(b"0123456789")
+        })
+        .unwrap();
+
+        assert_eq!(
+            preview,
+            ExportPreview {
+                rows: 2,
+                estimated_bytes: 10,
+                warnings: vec![],
+            }
+        );
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 0);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn values_that_are_not_numbers_are_warned_about() {
+        let warnings = data_warnings(&data(&[f64::NAN, 28.5, f64::INFINITY]), "empty cells");
+
+        assert_eq!(
+            warnings,
+            [
+                "2 readings have a temperature that is not a number and will be written as \
+              empty cells"
+            ]
+        );
+        assert_eq!(
+            data_warnings(&data(&[]), "NaN"),
+            ["There are no readings to export"]
+        );
+    }
+
+    #[test]
+    fn replacing_a_file_is_warned_about() {
+        let dir = temp_dir("preview-replace");
+        let path = dir.join("export.csv");
+        std::fs::write(&path, "old").unwrap();
+
+        let preview = preview(&path, &data(&[28.5]), "NaN", |_| Ok(())).unwrap();
+
+        assert_eq!(
+            preview.warnings,
+            [format!("{} exists and will be replaced", path.display())]
+        );
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "old");
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn missing_folders_are_not_writable() {
+        let dir = temp_dir("preview-missing");
+        let path = dir.join("missing").join("export.csv");
+
+        let preview = preview(&path, &data(&[28.5]), "NaN", |_| Ok(())).unwrap();
+
+        assert_eq!(preview.warnings.len(), 1);
+        assert!(preview.warnings[0].starts_with("Unable to write to"));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn serialization_errors_are_export_errors() {
+        let error = preview(Path::new("export.csv"), &data(&[28.5]), "NaN", |_| {
+            Err(std::io::Error::new(std::io::ErrorKind::Other, "Invalid"))
+        })
+        .unwrap_err();
+
+        assert_eq!(
+            serde_json::to_value(error).unwrap()["code"],
+            crate::errors::codes::EXPORT_FAILED
+        );
+    }
+}
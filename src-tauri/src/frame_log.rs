@@ -0,0 +1,400 @@
+//! Logs of the raw frames sent between the boat and desktop application.
+//!
+//! Each line of a log is a JSON entry of a frame. Frames are written to the log by a separate
+//! thread so logging doesn't hold up the connection, and are dropped if the disk can't keep up.
+
+use std::{
+    fs::File,
+    io::{BufRead, BufReader, BufWriter, Write},
+    path::PathBuf,
+    sync::{
+        mpsc::{self, Receiver, SyncSender},
+        Mutex,
+    },
+    thread::JoinHandle,
+    time::Instant,
+};
+
+use chrono::Local;
+use serde::{Deserialize, Serialize};
+use tauri::AppHandle;
+
+use crate::{
+    errors::{codes, AppError},
+    storage::app_data_file,
+};
+
+/// The number of frames waiting to be written before frames are dropped.
+const MAX_QUEUED_ENTRIES: usize = 1024;
+
+/// The format of the timestamp in the default log file names.
+const TIMESTAMP_FORMAT: &str = "%Y-%m-%dT%H-%M-%S";
+
+/// The direction a frame is sent in.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Direction {
+    /// Sent from the boat.
+    Inbound,
+    /// Sent to the boat.
+    Outbound,
+}
+
+/// An entry of a frame in a log.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct FrameLogEntry {
+    /// The time the frame is sent in milliseconds since logging started.
+    pub time_ms: f64,
+    /// The direction the frame is sent in.
+    pub direction: Direction,
+    /// The bytes of the frame as sent, in hexadecimal.
+    pub data: String,
+}
+
+impl FrameLogEntry {
+    /// Creates an entry of the bytes of a frame.
+    fn new(time_ms: f64, direction: Direction, bytes: &[u8]) -> Self {
+        Self {
+            time_ms,
+            direction,
+            data: bytes.iter().map(|b| format!("{b:02x}")).collect(),
+        }
+    }
+
+    /// Decodes the bytes of the frame.
+    pub fn bytes(&self) -> Result<Vec<u8>, String> {
+        if self.data.len() % 2 != 0 {
+            return Err(String::from("Invalid Frame Log: Odd Number of Hex Digits"));
+        }
+        (0..self.data.len())
+            .step_by(2)
+            .map(|i| {
+                self.data
+                    .get(i..i + 2)
+                    .and_then(|digits| u8::from_str_radix(digits, 16).ok())
+                    .ok_or(format!("Invalid Frame Log: Invalid Hex Digits at {i}"))
+            })
+            .collect()
+    }
+}
+
+/// Summary of a frame log when logging stops.
+#[derive(Debug, Serialize, Clone)]
+pub struct FrameLogSummary {
+    /// The path of the log.
+    path: PathBuf,
+    /// The number of frames written to the log.
+    frames: u64,
+    /// The number of frames dropped because the log couldn't keep up.
+    dropped: u64,
+}
+
+/// Writes the entries received to `file` until the sender is dropped.
+///
+/// Returns the number of entries written.
+fn write_entries(file: File, entries: Receiver<FrameLogEntry>) -> std::io::Result<u64> {
+    let mut writer = BufWriter::new(file);
+    let mut written = 0;
+    while let Ok(entry) = entries.recv() {
+        // Writing every entry waiting before flushing
+        for entry in std::iter::once(entry).chain(entries.try_iter()) {
+            serde_json::to_writer(&mut writer, &entry)?;
+            writer.write_all(b"\n")?;
+            written += 1;
+        }
+        writer.flush()?;
+    }
+    Ok(written)
+}
+
+/// A frame log being written.
+struct FrameLogger {
+    /// The path of the log.
+    path: PathBuf,
+    /// The time logging started.
+    start: Instant,
+    /// Sends the entries to the writer thread.
+    sender: SyncSender<FrameLogEntry>,
+    /// The number of frames dropped.
+    dropped: u64,
+    /// The thread writing the entries.
+    thread: JoinHandle<std::io::Result<u64>>,
+}
+
+impl FrameLogger {
+    /// Creates a log at `path`.
+    fn create(path: PathBuf) -> Result<Self, AppError> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).map_err(|e| AppError::io(e, parent))?;
+        }
+        let file = File::create(&path).map_err(|e| AppError::io(e, &path))?;
+        let (sender, receiver) = mpsc::sync_channel(MAX_QUEUED_ENTRIES);
+        let thread = std::thread::spawn(move || write_entries(file, receiver));
+        Ok(Self {
+            path,
+            start: Instant::now(),
+            sender,
+            dropped: 0,
+            thread,
+        })
+    }
+
+    /// Queues a frame to be written, dropping it if the queue is full.
+    fn record(&mut self, direction: Direction, bytes: &[u8]) {
+        let time_ms = self.start.elapsed().as_secs_f64() * 1000.0;
+        // A failed writer is reported when logging stops
+        if self
+            .sender
+            .try_send(FrameLogEntry::new(time_ms, direction, bytes))
+            .is_err()
+        {
+            self.dropped += 1;
+        }
+    }
+
+    /// Stops logging after the frames queued are written.
+    fn finish(self) -> Result<FrameLogSummary, AppError> {
+        drop(self.sender);
+        let frames = self
+            .thread
+            .join()
+            .map_err(|_| AppError::from(String::from("Frame Log Thread Panicked")))?
+            .map_err(|e| AppError::io(e, &self.path))?;
+        if self.dropped > 0 {
+            log::warn!("Dropped {} Frames from the Frame Log", self.dropped);
+        }
+        Ok(FrameLogSummary {
+            path: self.path,
+            frames,
+            dropped: self.dropped,
+        })
+    }
+}
+
+/// The frame log being written, if frames are being logged.
+#[derive(Default)]
+pub struct FrameLogState {
+    logger: Mutex<Option<FrameLogger>>,
+}
+
+impl FrameLogState {
+    /// Logs a frame if frames are being logged.
+    pub fn record(&self, direction: Direction, bytes: &[u8]) {
+        if let Some(logger) = self.logger.lock().unwrap().as_mut() {
+            logger.record(direction, bytes);
+        }
+    }
+
+    /// Stops logging if frames are being logged.
+    pub fn stop(&self) -> Option<Result<FrameLogSummary, AppError>> {
+        self.logger.lock().unwrap().take().map(FrameLogger::finish)
+    }
+}
+
+/// Start logging the frames sent to and received from the boat.
+///
+/// The log is written to `logs/frames-<timestamp>.ndjson` in the application data directory if
+/// `path` is not given. The log that is being written is stopped first. Returns the path of the
+/// log.
+#[tauri::command]
+pub fn start_frame_log(
+    app_handle: AppHandle,
+    state: tauri::State<FrameLogState>,
+    path: Option<PathBuf>,
+) -> Result<PathBuf, AppError> {
+    let path = match path {
+        Some(v) => v,
+        None => {
+            let name = format!("frames-{}.ndjson", Local::now().format(TIMESTAMP_FORMAT));
+            app_data_file(&app_handle, "logs")?.join(name)
+        }
+    };
+    log::info!("Logging Frames to: {}", path.display());
+
+    let mut logger = state.logger.lock().unwrap();
+    if let Some(old) = logger.take() {
+        if let Err(e) = old.finish() {
+            log::warn!("Unable to Write the Frame Log: {}", e);
+        }
+    }
+    *logger = Some(FrameLogger::create(path.clone())?);
+    Ok(path)
+}
+
+/// Stop logging the frames.
+///
+/// Returns the number of frames written and dropped.
+#[tauri::command]
+pub fn stop_frame_log(state: tauri::State<FrameLogState>) -> Result<FrameLogSummary, AppError> {
+    log::debug!("Stopping Frame Log");
+    state.stop().unwrap_or_else(|| {
+        Err(AppError::new(
+            codes::FRAME_LOG_NOT_RUNNING,
+            "No Frame Log Running",
+        ))
+    })
+}
+
+/// Reads up to `limit` entries of a frame log, skipping the first `offset` entries.
+#[tauri::command]
+pub fn read_frame_log(
+    path: PathBuf,
+    limit: usize,
+    offset: usize,
+) -> Result<Vec<FrameLogEntry>, AppError> {
+    log::debug!("Reading Frame Log: {}", path.display());
+    let reader = BufReader::new(File::open(&path).map_err(|e| AppError::io(e, &path))?);
+    reader
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| line.as_ref().map_or(true, |line| !line.trim().is_empty()))
+        .skip(offset)
+        .take(limit)
+        .map(|(i, line)| {
+            let line = line.map_err(|e| AppError::io(e, &path))?;
+            serde_json::from_str(&line).map_err(|e| {
+                AppError::new(
+                    codes::FRAME_LOG_INVALID,
+                    format!("Invalid Frame Log: Line {}: {e}", i + 1),
+                )
+                .with("line", i + 1)
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+    use crate::storage::tests::temp_dir;
+
+    fn entry(data: &str) -> FrameLogEntry {
+        FrameLogEntry {
+            time_ms: 0.0,
+            direction: Direction::Inbound,
+            data: data.to_string(),
+        }
+    }
+
+    #[test]
+    fn bytes_are_decoded_from_hex() {
+        assert_eq!(
+            entry("0a1BfF00").bytes().unwrap(),
+            vec![0x0a, 0x1b, 0xff, 0x00]
+        );
+        assert!(entry("").bytes().unwrap().is_empty());
+    }
+
+    #[test]
+    fn invalid_hex_is_rejected() {
+        assert_eq!(
+            entry("0a1").bytes().unwrap_err(),
+            "Invalid Frame Log: Odd Number of Hex Digits"
+        );
+        assert_eq!(
+            entry("0a1g").bytes().unwrap_err(),
+            "Invalid Frame Log: Invalid Hex Digits at 2"
+        );
+        // Not split in the middle of a character
+        assert!(entry("0aé").bytes().is_err());
+    }
+
+    #[test]
+    fn entry_round_trips_as_json_line() {
+        let line = r#"{"time_ms":12.5,"direction":"outbound","data":"0a01"}"#;
+
+        let entry: FrameLogEntry = serde_json::from_str(line).unwrap();
+
+        assert_eq!(entry.direction, Direction::Outbound);
+        assert_eq!(entry.time_ms, 12.5);
+        assert_eq!(serde_json::to_string(&entry).unwrap(), line);
+    }
+
+    #[test]
+    fn entry_of_bytes_is_hex() {
+        let entry = FrameLogEntry::new(1.5, Direction::Inbound, &[0x0a, 0xff, 0x00]);
+
+        assert_eq!(entry.data, "0aff00");
+        assert_eq!(entry.bytes().unwrap(), vec![0x0a, 0xff, 0x00]);
+    }
+
+    #[test]
+    fn logger_writes_frames_in_order() {
+        let dir = temp_dir("frame-log-write");
+        let path = dir.join("logs").join("frames.ndjson");
+
+        let mut logger = FrameLogger::create(path.clone()).unwrap();
+        for i in 0..10u8 {
+            let direction = if i % 2 == 0 {
+                Direction::Outbound
+            } else {
+                Direction::Inbound
+            };
+            logger.record(direction, &[i]);
+        }
+        let summary = logger.finish().unwrap();
+
+        assert_eq!(summary.frames, 10);
+        assert_eq!(summary.dropped, 0);
+        let entries = read_frame_log(path, 100, 0).unwrap();
+        let bytes: Vec<_> = entries.iter().map(|e| e.bytes().unwrap()[0]).collect();
+        assert_eq!(bytes, (0..10).collect::<Vec<_>>());
+        assert_eq!(entries[0].direction, Direction::Outbound);
+        assert!(entries.windows(2).all(|w| w[0].time_ms <= w[1].time_ms));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn logger_drops_frames_when_writer_falls_behind() {
+        let dir = temp_dir("frame-log-drop");
+        let path = dir.join("frames.ndjson");
+        let file = File::create(&path).unwrap();
+        let (sender, receiver) = mpsc::sync_channel(2);
+        let (go, wait) = mpsc::channel::<()>();
+        let mut logger = FrameLogger {
+            path: path.clone(),
+            start: Instant::now(),
+            sender,
+            dropped: 0,
+            // A writer that is held up until the frames are recorded
+            thread: std::thread::spawn(move || {
+                let _ = wait.recv();
+                write_entries(file, receiver)
+            }),
+        };
+
+        for i in 0..5 {
+            logger.record(Direction::Inbound, &[i]);
+        }
+        go.send(()).unwrap();
+        let summary = logger.finish().unwrap();
+
+        assert_eq!(summary.frames, 2);
+        assert_eq!(summary.dropped, 3);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn read_frame_log_pages_entries() {
+        let dir = temp_dir("frame-log-read");
+        let path = dir.join("frames.ndjson");
+        let lines: String = (0..6)
+            .map(|i| {
+                let entry = FrameLogEntry::new(i as f64, Direction::Inbound, &[i]);
+                serde_json::to_string(&entry).unwrap() + "\n"
+            })
+            .collect();
+        fs::write(&path, lines + "\n").unwrap();
+
+        let page = read_frame_log(path.clone(), 2, 3).unwrap();
+        assert_eq!(
+            page.iter().map(|e| e.time_ms).collect::<Vec<_>>(),
+            vec![3.0, 4.0]
+        );
+        assert_eq!(read_frame_log(path.clone(), 10, 5).unwrap().len(), 1);
+        assert!(read_frame_log(path, 10, 6).unwrap().is_empty());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
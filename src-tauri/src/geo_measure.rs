@@ -0,0 +1,146 @@
+//! Geodesic measurements on the WGS84 ellipsoid for the measure tool of the map.
+//!
+//! Coordinates are `(longitude, latitude)` pairs in degrees like the GeoJSON files.
+
+use geo::{
+    orient::{Direction, Orient},
+    GeodesicArea, GeodesicBearing, GeodesicDistance,
+};
+use geo_types::{Coord, LineString, Point, Polygon};
+
+use crate::errors::{codes, AppError};
+
+/// Converts the coordinates from the frontend, checking they are valid.
+fn to_coords(points: Vec<(f64, f64)>) -> Result<Vec<Coord<f64>>, AppError> {
+    let coords: Vec<Coord<f64>> = points.into_iter().map(Coord::from).collect();
+    match coords
+        .iter()
+        .position(|c| !c.x.is_finite() || !c.y.is_finite() || c.y.abs() > 90.0)
+    {
+        Some(i) => Err(AppError::new(
+            codes::MEASURE_INVALID_COORDINATE,
+            format!("Invalid Coordinate: Point {i} is not a valid coordinate"),
+        )
+        .with("index", i)),
+        None => Ok(coords),
+    }
+}
+
+/// Measure the geodesic length in meters of the line through the points.
+#[tauri::command]
+pub fn measure_distance(points: Vec<(f64, f64)>) -> Result<f64, AppError> {
+    let coords = to_coords(points)?;
+    Ok(LineString(coords)
+        .lines()
+        .map(|line| Point::from(line.start).geodesic_distance(&Point::from(line.end)))
+        .sum())
+}
+
+/// Measure the geodesic area in square meters of the polygon.
+///
+/// The polygon is closed if its last vertex isn't its first, and its vertices can be in either
+/// order.
+#[tauri::command]
+pub fn measure_area(polygon: Vec<(f64, f64)>) -> Result<f64, AppError> {
+    let coords = to_coords(polygon)?;
+    if coords.len() < 3 {
+        return Err(AppError::new(
+            codes::MEASURE_INVALID_POLYGON,
+            "Invalid Polygon: Polygon requires at least three vertices.",
+        ));
+    }
+    // A clockwise ring would measure the rest of the Earth
+    Ok(Polygon::new(LineString(coords), vec![])
+        .orient(Direction::Default)
+        .geodesic_area_unsigned())
+}
+
+/// Measure the initial bearing from `from` to `to` in degrees clockwise from north, from 0 up
+/// to 360.
+#[tauri::command]
+pub fn measure_bearing(from: (f64, f64), to: (f64, f64)) -> Result<f64, AppError> {
+    let coords = to_coords(vec![from, to])?;
+    let bearing = Point::from(coords[0]).geodesic_bearing(Point::from(coords[1]));
+    Ok(bearing.rem_euclid(360.0))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Checks that `actual` is within 0.1% of the reference value `expected`.
+    fn assert_accurate(actual: f64, expected: f64) {
+        assert!(
+            (actual - expected).abs() <= expected.abs() * 1e-3,
+            "{actual} is not within 0.1% of {expected}"
+        );
+    }
+
+    #[test]
+    fn distance_matches_reference_values() {
+        // A degree of the equator and of the meridian at the equator on WGS84
+        assert_accurate(
+            measure_distance(vec![(0.0, 0.0), (1.0, 0.0)]).unwrap(),
+            111_319.49,
+        );
+        assert_accurate(
+            measure_distance(vec![(0.0, 0.0), (0.0, 1.0)]).unwrap(),
+            110_574.39,
+        );
+        // The quarter meridian
+        assert_accurate(
+            measure_distance(vec![(0.0, 0.0), (0.0, 90.0)]).unwrap(),
+            10_001_965.73,
+        );
+    }
+
+    #[test]
+    fn distance_is_the_sum_of_the_segments() {
+        let distance = measure_distance(vec![(0.0, 0.0), (1.0, 0.0), (1.0, 1.0)]).unwrap();
+
+        assert_accurate(distance, 111_319.49 + 110_575.06);
+        assert_eq!(measure_distance(vec![(101.5, 3.1)]), Ok(0.0));
+        assert_eq!(measure_distance(vec![]), Ok(0.0));
+    }
+
+    #[test]
+    fn area_matches_reference_values() {
+        // A one degree cell at the equator on WGS84
+        let cell = vec![(0.0, 0.0), (1.0, 0.0), (1.0, 1.0), (0.0, 1.0)];
+        assert_accurate(measure_area(cell.clone()).unwrap(), 12_308_778_361.0);
+
+        let mut reversed = cell;
+        reversed.reverse();
+        reversed.push(reversed[0]);
+        assert_accurate(measure_area(reversed).unwrap(), 12_308_778_361.0);
+    }
+
+    #[test]
+    fn area_requires_three_vertices() {
+        let error = measure_area(vec![(0.0, 0.0), (1.0, 0.0)]).unwrap_err();
+
+        assert_eq!(
+            serde_json::to_value(error).unwrap()["code"],
+            codes::MEASURE_INVALID_POLYGON
+        );
+    }
+
+    #[test]
+    fn bearing_is_clockwise_from_north() {
+        assert_eq!(measure_bearing((0.0, 0.0), (0.0, 1.0)), Ok(0.0));
+        assert_accurate(measure_bearing((0.0, 0.0), (1.0, 0.0)).unwrap(), 90.0);
+        assert_accurate(measure_bearing((0.0, 1.0), (0.0, 0.0)).unwrap(), 180.0);
+        assert_accurate(measure_bearing((0.0, 0.0), (-1.0, 0.0)).unwrap(), 270.0);
+    }
+
+    #[test]
+    fn invalid_coordinates_are_named() {
+        let error = measure_distance(vec![(0.0, 0.0), (0.0, 91.0)]).unwrap_err();
+        let error = serde_json::to_value(error).unwrap();
+        assert_eq!(error["code"], codes::MEASURE_INVALID_COORDINATE);
+        assert_eq!(error["params"]["index"], 1);
+
+        let error = measure_bearing((f64::NAN, 0.0), (0.0, 0.0)).unwrap_err();
+        assert_eq!(serde_json::to_value(error).unwrap()["params"]["index"], 0);
+    }
+}
@@ -0,0 +1,1350 @@
+//! Geodesic measurements of robot paths for planning missions.
+
+use std::collections::BTreeMap;
+
+use geo::{GeodesicDistance, GeodesicIntermediate};
+use geo_types::{Coord, LineString, MultiPoint, Point};
+use serde::Serialize;
+
+use crate::{
+    errors::{codes, AppError},
+    path::{PathData, PathError},
+};
+
+/// The mean radius of the Earth in meters.
+const EARTH_RADIUS: f64 = 6_371_008.8;
+
+/// Creates the error of the argument `argument` named `label` being invalid.
+fn invalid_argument(label: &str, argument: &str, value: f64) -> AppError {
+    AppError::new(
+        codes::GEOMETRY_INVALID_ARGUMENT,
+        format!("Invalid {label}: {value}"),
+    )
+    .with("argument", argument)
+    .with("value", value)
+}
+
+/// Creates the error of the path being empty.
+fn empty_path(context: &str) -> AppError {
+    AppError::new(
+        codes::GEOMETRY_EMPTY_PATH,
+        format!("{context}: Path is empty."),
+    )
+}
+
+/// The default maximum number of collection points generated along a path.
+const MAX_COLLECTION_POINTS: usize = 10_000;
+
+/// Measurements of a path used to check if the boat can complete it.
+#[derive(Debug, Default, Serialize, Clone, PartialEq)]
+pub struct PathMetrics {
+    /// The geodesic length of the path in meters.
+    length: f64,
+    /// The geodesic length of each segment of the path in meters.
+    segment_lengths: Vec<f64>,
+    /// The time to travel the path in seconds.
+    travel_time: f64,
+    /// The time to complete the path in seconds, including the time at the collection points.
+    duration: f64,
+}
+
+/// Gets the geodesic length of each segment of the path in meters.
+pub fn segment_lengths(path: &PathData) -> Vec<f64> {
+    path.path()
+        .lines()
+        .map(|line| Point::from(line.start).geodesic_distance(&Point::from(line.end)))
+        .collect()
+}
+
+/// Compute the length and estimated duration of a path.
+///
+/// The boat travels at `speed_mps` meters per second and stays at each collection point for
+/// `dwell_seconds_per_point` seconds.
+#[tauri::command]
+pub fn path_metrics(
+    path: PathData,
+    speed_mps: f64,
+    dwell_seconds_per_point: f64,
+) -> Result<PathMetrics, AppError> {
+    log::debug!("Computing Path Metrics");
+    if !dwell_seconds_per_point.is_finite() || dwell_seconds_per_point < 0.0 {
+        return Err(invalid_argument(
+            "Dwell Time",
+            "dwell_seconds_per_point",
+            dwell_seconds_per_point,
+        ));
+    }
+
+    let segment_lengths = segment_lengths(&path);
+    let length: f64 = segment_lengths.iter().sum();
+    let travel_time = if length == 0.0 {
+        0.0
+    } else if speed_mps.is_finite() && speed_mps > 0.0 {
+        length / speed_mps
+    } else {
+        return Err(invalid_argument("Speed", "speed_mps", speed_mps));
+    };
+
+    Ok(PathMetrics {
+        length,
+        segment_lengths,
+        travel_time,
+        duration: travel_time + dwell_seconds_per_point * path.collection_points().0.len() as f64,
+    })
+}
+
+/// A path after removing the redundant waypoints.
+#[derive(Debug, Serialize)]
+pub struct SimplifiedPath {
+    /// The simplified path.
+    path: PathData,
+    /// The number of waypoints before simplifying.
+    original_vertices: usize,
+    /// The number of waypoints after simplifying.
+    simplified_vertices: usize,
+}
+
+/// Projection of coordinates to meters on a plane tangent to their mean coordinate.
+///
+/// The equirectangular projection is accurate enough over the distances the boat travels.
+#[derive(Debug, Clone, Copy)]
+pub struct Projection {
+    /// The coordinate projected to the origin.
+    origin: Coord<f64>,
+    /// The length of a degree of longitude relative to a degree of latitude.
+    scale: f64,
+}
+
+impl Projection {
+    /// Creates the projection centered on the mean of the coordinates.
+    pub fn new(coords: &[Coord<f64>]) -> Self {
+        let len = coords.len().max(1) as f64;
+        Self::at(Coord {
+            x: coords.iter().map(|c| c.x).sum::<f64>() / len,
+            y: coords.iter().map(|c| c.y).sum::<f64>() / len,
+        })
+    }
+
+    /// Creates the projection centered on `origin`.
+    pub fn at(origin: Coord<f64>) -> Self {
+        Self {
+            origin,
+            scale: origin.y.to_radians().cos(),
+        }
+    }
+
+    /// Projects a coordinate to meters.
+    pub fn forward(&self, coord: Coord<f64>) -> [f64; 2] {
+        [
+            (coord.x - self.origin.x).to_radians() * self.scale * EARTH_RADIUS,
+            (coord.y - self.origin.y).to_radians() * EARTH_RADIUS,
+        ]
+    }
+
+    /// Converts projected meters back to a coordinate.
+    pub fn inverse(&self, point: [f64; 2]) -> Coord<f64> {
+        Coord {
+            x: self.origin.x + (point[0] / (self.scale * EARTH_RADIUS)).to_degrees(),
+            y: self.origin.y + (point[1] / EARTH_RADIUS).to_degrees(),
+        }
+    }
+}
+
+/// Projects coordinates to meters.
+fn project(coords: &[Coord<f64>]) -> Vec<[f64; 2]> {
+    let projection = Projection::new(coords);
+    coords.iter().map(|c| projection.forward(*c)).collect()
+}
+
+/// Gets the fraction of the way along the segment from `start` to `end` of its point closest to
+/// `point`.
+fn closest_fraction(point: [f64; 2], start: [f64; 2], end: [f64; 2]) -> f64 {
+    let (dx, dy) = (end[0] - start[0], end[1] - start[1]);
+    let length_squared = dx * dx + dy * dy;
+    if length_squared == 0.0 {
+        0.0
+    } else {
+        (((point[0] - start[0]) * dx + (point[1] - start[1]) * dy) / length_squared).clamp(0.0, 1.0)
+    }
+}
+
+/// Gets the distance from `point` to the segment from `start` to `end`.
+pub fn segment_distance(point: [f64; 2], start: [f64; 2], end: [f64; 2]) -> f64 {
+    let t = closest_fraction(point, start, end);
+    let (x, y) = (
+        start[0] + t * (end[0] - start[0]),
+        start[1] + t * (end[1] - start[1]),
+    );
+    ((point[0] - x).powi(2) + (point[1] - y).powi(2)).sqrt()
+}
+
+/// Finds the waypoints kept by the Ramer–Douglas–Peucker algorithm.
+///
+/// The first and last waypoints are always kept.
+fn douglas_peucker(points: &[[f64; 2]], tolerance: f64) -> Vec<bool> {
+    let mut keep = vec![false; points.len()];
+    if points.is_empty() {
+        return keep;
+    }
+    keep[0] = true;
+    keep[points.len() - 1] = true;
+
+    // Using a stack instead of recursion as freehand paths can have thousands of waypoints
+    let mut ranges = vec![(0, points.len() - 1)];
+    while let Some((start, end)) = ranges.pop() {
+        let farthest = (start + 1..end)
+            .map(|i| (i, segment_distance(points[i], points[start], points[end])))
+            .max_by(|(_, a), (_, b)| a.partial_cmp(b).unwrap());
+        if let Some((i, distance)) = farthest {
+            if distance > tolerance {
+                keep[i] = true;
+                ranges.push((start, i));
+                ranges.push((i, end));
+            }
+        }
+    }
+    keep
+}
+
+/// Simplify a path by removing waypoints that are within `tolerance_m` meters of it.
+///
+/// The collection points are left unchanged.
+#[tauri::command]
+pub fn simplify_path(mut path: PathData, tolerance_m: f64) -> Result<SimplifiedPath, AppError> {
+    log::debug!("Simplifying Path with Tolerance {}m", tolerance_m);
+    if !tolerance_m.is_finite() || tolerance_m < 0.0 {
+        return Err(invalid_argument("Tolerance", "tolerance_m", tolerance_m));
+    }
+
+    let coords = &path.path().0;
+    let original_vertices = coords.len();
+    let keep = douglas_peucker(&project(coords), tolerance_m);
+    let simplified: LineString<f64> = coords
+        .iter()
+        .zip(keep)
+        .filter(|(_, keep)| *keep)
+        .map(|(c, _)| *c)
+        .collect();
+    let simplified_vertices = simplified.0.len();
+    log::info!(
+        "Simplified Path from {} to {} Waypoints",
+        original_vertices,
+        simplified_vertices
+    );
+
+    path.set_path(simplified);
+    Ok(SimplifiedPath {
+        path,
+        original_vertices,
+        simplified_vertices,
+    })
+}
+
+/// Gets the points every `spacing` meters along the path, including both of its ends.
+fn points_along(
+    path: &LineString<f64>,
+    spacing: f64,
+    max_points: usize,
+) -> Result<Vec<Point<f64>>, AppError> {
+    let (first, last) = match (path.0.first(), path.0.last()) {
+        (Some(first), Some(last)) => (Point::from(*first), Point::from(*last)),
+        _ => return Ok(vec![]),
+    };
+    let lengths: Vec<_> = path
+        .lines()
+        .map(|line| Point::from(line.start).geodesic_distance(&Point::from(line.end)))
+        .collect();
+    let length: f64 = lengths.iter().sum();
+    if length == 0.0 {
+        return Ok(vec![first]);
+    }
+
+    let count = (length / spacing).ceil() + 1.0;
+    if count > max_points as f64 {
+        return Err(AppError::new(
+            codes::GEOMETRY_TOO_MANY_POINTS,
+            format!(
+                "Too Many Collection Points: Spacing {spacing}m would place {count} points, \
+                the maximum is {max_points}"
+            ),
+        )
+        .with("count", count)
+        .with("max", max_points));
+    }
+
+    let mut points = vec![first];
+    let mut segments = path.lines().zip(lengths);
+    let mut current = segments.next();
+    // The distance along the path to the start of the current segment
+    let mut start = 0.0;
+    let mut distance = spacing;
+    while distance < length {
+        while let Some((line, segment_length)) = current {
+            if distance <= start + segment_length {
+                let fraction = (distance - start) / segment_length;
+                points.push(
+                    Point::from(line.start).geodesic_intermediate(&Point::from(line.end), fraction),
+                );
+                break;
+            }
+            start += segment_length;
+            current = segments.next();
+        }
+        distance += spacing;
+    }
+    points.push(last);
+    Ok(points)
+}
+
+/// Generate collection points every `spacing_m` meters along the path.
+///
+/// The generated points replace the existing collection points, starting at the start of the
+/// path and ending at the end of the path. An error is returned if more than `max_points`
+/// points would be generated, which defaults to 10,000.
+#[tauri::command]
+pub fn generate_collection_points(
+    mut path: PathData,
+    spacing_m: f64,
+    max_points: Option<usize>,
+) -> Result<PathData, AppError> {
+    log::debug!("Generating Collection Points every {}m", spacing_m);
+    if !spacing_m.is_finite() || spacing_m <= 0.0 {
+        return Err(invalid_argument("Spacing", "spacing_m", spacing_m));
+    }
+
+    let points = points_along(
+        path.path(),
+        spacing_m,
+        max_points.unwrap_or(MAX_COLLECTION_POINTS),
+    )?;
+    log::info!("Generated {} Collection Points", points.len());
+    path.set_collection_points(MultiPoint(points));
+    Ok(path)
+}
+
+/// What happened to a collection point when snapping it to the path.
+#[derive(Debug, Serialize, Clone, Copy, PartialEq)]
+pub struct SnappedPoint {
+    /// The geodesic distance from the point to the closest point of the path in meters.
+    distance: f64,
+    /// Whether the point was moved onto the path.
+    moved: bool,
+}
+
+/// A path after snapping its collection points to it.
+#[derive(Debug, Serialize)]
+pub struct SnappedPath {
+    /// The path with the snapped collection points.
+    path: PathData,
+    /// What happened to each collection point, in the order of the collection points.
+    points: Vec<SnappedPoint>,
+}
+
+/// Gets the point of the path closest to `point`.
+///
+/// The segments are compared in a projection centered on the point, so the distances are
+/// accurate near the point no matter how long the path is. When several segments are equally
+/// close, the first one along the path is used. A point closest to a waypoint gets the exact
+/// coordinate of the waypoint.
+fn closest_on_path(path: &LineString<f64>, point: Coord<f64>) -> Option<Coord<f64>> {
+    let projection = Projection::at(point);
+    let origin = [0.0, 0.0];
+    let mut closest: Option<(f64, Coord<f64>)> = None;
+    for line in path.lines() {
+        let (start, end) = (projection.forward(line.start), projection.forward(line.end));
+        let t = closest_fraction(origin, start, end);
+        let distance = segment_distance(origin, start, end);
+        if closest.map_or(true, |(d, _)| distance < d) {
+            let coord = if t == 0.0 {
+                line.start
+            } else if t == 1.0 {
+                line.end
+            } else {
+                projection.inverse([
+                    start[0] + t * (end[0] - start[0]),
+                    start[1] + t * (end[1] - start[1]),
+                ])
+            };
+            closest = Some((distance, coord));
+        }
+    }
+    // A path of a single waypoint has no segments
+    closest
+        .map(|(_, coord)| coord)
+        .or_else(|| path.0.first().copied())
+}
+
+/// Snap the collection points onto the closest point of the path.
+///
+/// Points within `max_snap_distance_m` meters of the path are moved onto it, farther points are
+/// left where they are. When a point is equally close to several segments, it is snapped to the
+/// first of them along the path. Points closest to a waypoint are moved onto the waypoint
+/// without adding another waypoint. The notes of the points are kept.
+#[tauri::command]
+pub fn snap_points_to_path(
+    mut path: PathData,
+    max_snap_distance_m: f64,
+) -> Result<SnappedPath, AppError> {
+    log::debug!("Snapping Collection Points within {}m", max_snap_distance_m);
+    if !max_snap_distance_m.is_finite() || max_snap_distance_m < 0.0 {
+        return Err(invalid_argument(
+            "Snap Distance",
+            "max_snap_distance_m",
+            max_snap_distance_m,
+        ));
+    }
+    let mut points = path.collection_points().clone();
+    let mut snapped = Vec::with_capacity(points.0.len());
+    for point in points.iter_mut() {
+        let closest = match closest_on_path(path.path(), point.0) {
+            Some(v) => Point::from(v),
+            None => return Err(empty_path("Unable to Snap Collection Points")),
+        };
+        let distance = point.geodesic_distance(&closest);
+        let moved = distance > 0.0 && distance <= max_snap_distance_m;
+        if moved {
+            *point = closest;
+        }
+        snapped.push(SnappedPoint { distance, moved });
+    }
+    log::info!(
+        "Snapped {} of {} Collection Points",
+        snapped.iter().filter(|p| p.moved).count(),
+        snapped.len()
+    );
+
+    path.set_collection_points(points);
+    Ok(SnappedPath {
+        path,
+        points: snapped,
+    })
+}
+
+/// Gets the geodesic distance from `point` to the closest point of the path in meters.
+fn distance_to_path(path: &LineString<f64>, point: Point<f64>) -> f64 {
+    closest_on_path(path, point.0).map_or(f64::INFINITY, |closest| {
+        point.geodesic_distance(&Point::from(closest))
+    })
+}
+
+/// Split a path in two at the waypoint at `at_waypoint_index`, e.g. to run a long mission
+/// across a battery swap.
+///
+/// The waypoint is the end of the first half and the start of the second half. Each collection
+/// point goes to the half it is closest to, or the first half if it is equally close to both,
+/// keeping its note. Both halves keep the boundary of the path.
+#[tauri::command]
+pub fn split_path(
+    path: PathData,
+    at_waypoint_index: usize,
+) -> Result<(PathData, PathData), AppError> {
+    log::debug!("Splitting Path at Waypoint {}", at_waypoint_index);
+    let coords = &path.path().0;
+    if coords.is_empty() {
+        return Err(empty_path("Unable to Split Path"));
+    }
+    if at_waypoint_index >= coords.len() {
+        return Err(PathError::WaypointOutOfRange {
+            index: at_waypoint_index,
+            len: coords.len(),
+        }
+        .into());
+    }
+    if at_waypoint_index == 0 || at_waypoint_index == coords.len() - 1 {
+        return Err(AppError::new(
+            codes::GEOMETRY_SPLIT_AT_END,
+            format!("Unable to Split Path: Waypoint {at_waypoint_index} is an end of the path."),
+        )
+        .with("index", at_waypoint_index));
+    }
+
+    let halves = [
+        LineString(coords[..=at_waypoint_index].to_vec()),
+        LineString(coords[at_waypoint_index..].to_vec()),
+    ];
+    let mut points = [vec![], vec![]];
+    let mut notes = [BTreeMap::new(), BTreeMap::new()];
+    for (i, point) in path.collection_points().iter().enumerate() {
+        let half = usize::from(
+            distance_to_path(&halves[1], *point) < distance_to_path(&halves[0], *point),
+        );
+        if let Some(note) = path.notes().get(&i) {
+            notes[half].insert(points[half].len(), note.clone());
+        }
+        points[half].push(*point);
+    }
+    log::info!(
+        "Split Path into {} and {} Collection Points",
+        points[0].len(),
+        points[1].len()
+    );
+
+    let [first_path, second_path] = halves;
+    let [first_points, second_points] = points;
+    let [first_notes, second_notes] = notes;
+    let make_half = |line: LineString<f64>, points: Vec<Point<f64>>, notes| {
+        let mut half = path.clone();
+        half.set_path(line);
+        half.set_collection_points(MultiPoint(points));
+        half.set_notes(notes);
+        half
+    };
+    Ok((
+        make_half(first_path, first_points, first_notes),
+        make_half(second_path, second_points, second_notes),
+    ))
+}
+
+/// Join two paths into one, e.g. after planning a mission in parts.
+///
+/// The end of `a` must be within `max_gap_m` meters of the start of `b`, and they are connected
+/// by a segment unless they are the same waypoint. The collection points of `b` follow those of
+/// `a`, keeping their notes. The boundary of `a` is kept, or that of `b` if `a` has none.
+#[tauri::command]
+pub fn join_paths(a: PathData, b: PathData, max_gap_m: f64) -> Result<PathData, AppError> {
+    log::debug!("Joining Paths within {}m", max_gap_m);
+    if !max_gap_m.is_finite() || max_gap_m < 0.0 {
+        return Err(invalid_argument("Gap", "max_gap_m", max_gap_m));
+    }
+    if a.version() != b.version() {
+        return Err(AppError::new(
+            codes::GEOMETRY_VERSION_MISMATCH,
+            format!(
+                "Unable to Join Paths: Version {} doesn't match version {}",
+                a.version(),
+                b.version()
+            ),
+        ));
+    }
+    let (end, start) = match (a.path().0.last(), b.path().0.first()) {
+        (Some(end), Some(start)) => (*end, *start),
+        _ => return Err(empty_path("Unable to Join Paths")),
+    };
+    let gap = Point::from(end).geodesic_distance(&Point::from(start));
+    if gap > max_gap_m {
+        return Err(AppError::new(
+            codes::GEOMETRY_GAP_TOO_LARGE,
+            format!("Unable to Join Paths: The gap of {gap:.1}m is larger than {max_gap_m}m"),
+        )
+        .with("gap", gap)
+        .with("max_gap", max_gap_m));
+    }
+
+    let mut coords = a.path().0.clone();
+    let skip = usize::from(end == start);
+    coords.extend_from_slice(&b.path().0[skip..]);
+    let offset = a.collection_points().0.len();
+    let mut points = a.collection_points().0.clone();
+    points.extend_from_slice(&b.collection_points().0);
+    let mut notes = a.notes().clone();
+    notes.extend(b.notes().iter().map(|(i, note)| (i + offset, note.clone())));
+    log::info!(
+        "Joined Paths into {} Waypoints and {} Collection Points",
+        coords.len(),
+        points.len()
+    );
+
+    let boundary = a.boundary().or(b.boundary()).cloned();
+    let mut joined = a;
+    joined.set_path(LineString(coords));
+    joined.set_collection_points(MultiPoint(points));
+    joined.set_notes(notes);
+    joined.set_boundary(boundary);
+    Ok(joined)
+}
+
+/// The maximum number of transects generated for a survey path.
+const MAX_TRANSECTS: usize = 10_000;
+
+/// A point on the boundary of the survey polygon.
+#[derive(Debug, Clone, Copy)]
+struct BoundaryPoint {
+    /// The projected coordinate of the point.
+    point: [f64; 2],
+    /// The index of the polygon edge the point is on.
+    edge: usize,
+}
+
+/// Rotates a projected point so transects with the heading run along the first axis.
+///
+/// The rotation is its own inverse, so it also rotates points back.
+fn rotate(point: [f64; 2], heading: f64) -> [f64; 2] {
+    let (sin, cos) = heading.to_radians().sin_cos();
+    [
+        point[0] * sin + point[1] * cos,
+        point[0] * cos - point[1] * sin,
+    ]
+}
+
+/// Gets the sign of the turn from `a` to `b` to `c`.
+fn orientation(a: [f64; 2], b: [f64; 2], c: [f64; 2]) -> f64 {
+    (b[0] - a[0]) * (c[1] - a[1]) - (b[1] - a[1]) * (c[0] - a[0])
+}
+
+/// Checks if a point is inside the polygon using the even-odd rule.
+fn contains(ring: &[[f64; 2]], point: [f64; 2]) -> bool {
+    let mut inside = false;
+    for (k, a) in ring.iter().enumerate() {
+        let b = ring[(k + 1) % ring.len()];
+        if (a[1] > point[1]) != (b[1] > point[1])
+            && point[0] < a[0] + (point[1] - a[1]) / (b[1] - a[1]) * (b[0] - a[0])
+        {
+            inside = !inside;
+        }
+    }
+    inside
+}
+
+/// Checks if the segment from `a` to `b` stays inside the polygon.
+///
+/// The segment may touch the boundary but not cross it.
+fn segment_inside(ring: &[[f64; 2]], a: [f64; 2], b: [f64; 2]) -> bool {
+    let crosses = ring.iter().enumerate().any(|(k, c)| {
+        let d = ring[(k + 1) % ring.len()];
+        orientation(a, b, *c) * orientation(a, b, d) < 0.0
+            && orientation(*c, d, a) * orientation(*c, d, b) < 0.0
+    });
+    !crosses && contains(ring, [(a[0] + b[0]) / 2.0, (a[1] + b[1]) / 2.0])
+}
+
+/// Gets the length of a sequence of projected points.
+fn projected_length(points: &[[f64; 2]]) -> f64 {
+    points
+        .windows(2)
+        .map(|w| ((w[1][0] - w[0][0]).powi(2) + (w[1][1] - w[0][1]).powi(2)).sqrt())
+        .sum()
+}
+
+/// Gets the vertices connecting two points on the boundary without leaving the polygon.
+///
+/// The points are connected directly when possible, otherwise by following the boundary in
+/// the shorter direction.
+fn connector(ring: &[[f64; 2]], from: BoundaryPoint, to: BoundaryPoint) -> Vec<[f64; 2]> {
+    if from.edge == to.edge || segment_inside(ring, from.point, to.point) {
+        return vec![];
+    }
+
+    let n = ring.len();
+    let forward: Vec<_> = (1..=(to.edge + n - from.edge) % n)
+        .map(|i| ring[(from.edge + i) % n])
+        .collect();
+    let backward: Vec<_> = (0..(from.edge + n - to.edge) % n)
+        .map(|i| ring[(from.edge + n - i) % n])
+        .collect();
+
+    let length = |vertices: &[[f64; 2]]| {
+        let mut points = vec![from.point];
+        points.extend_from_slice(vertices);
+        points.push(to.point);
+        projected_length(&points)
+    };
+    if length(&forward) <= length(&backward) {
+        forward
+    } else {
+        backward
+    }
+}
+
+/// Gets the parts of the transect at `v` inside the polygon, ordered along the transect.
+fn transect(ring: &[[f64; 2]], v: f64) -> Vec<(BoundaryPoint, BoundaryPoint)> {
+    let mut crossings: Vec<_> = ring
+        .iter()
+        .enumerate()
+        .filter_map(|(edge, a)| {
+            let b = ring[(edge + 1) % ring.len()];
+            if (a[1] <= v) == (b[1] <= v) {
+                return None;
+            }
+            let u = a[0] + (v - a[1]) / (b[1] - a[1]) * (b[0] - a[0]);
+            Some(BoundaryPoint {
+                point: [u, v],
+                edge,
+            })
+        })
+        .collect();
+    // The crossings are comparable as the coordinates of the polygon are checked to be finite
+    crossings.sort_by(|a, b| a.point[0].partial_cmp(&b.point[0]).unwrap());
+    crossings
+        .chunks_exact(2)
+        .map(|pair| (pair[0], pair[1]))
+        .collect()
+}
+
+/// Generate a back-and-forth survey path covering a polygon.
+///
+/// The transects are `line_spacing_m` meters apart and run along `heading_deg` degrees
+/// clockwise from north. The transects are clipped to the polygon and joined along its
+/// boundary when a direct connection would leave it. A collection point is placed at the
+/// middle of each transect when `collection_points` is `true`.
+#[tauri::command]
+pub fn generate_survey_path(
+    polygon: Vec<(f64, f64)>,
+    line_spacing_m: f64,
+    heading_deg: f64,
+    collection_points: Option<bool>,
+) -> Result<PathData, AppError> {
+    log::debug!(
+        "Generating Survey Path with Spacing {}m and Heading {}",
+        line_spacing_m,
+        heading_deg
+    );
+    if !line_spacing_m.is_finite() || line_spacing_m <= 0.0 {
+        return Err(invalid_argument(
+            "Spacing",
+            "line_spacing_m",
+            line_spacing_m,
+        ));
+    }
+    if !heading_deg.is_finite() {
+        return Err(invalid_argument("Heading", "heading_deg", heading_deg));
+    }
+
+    let mut coords: Vec<_> = polygon.into_iter().map(Coord::from).collect();
+    if coords.len() > 1 && coords.first() == coords.last() {
+        coords.pop();
+    }
+    if coords.len() < 3 {
+        return Err(AppError::new(
+            codes::GEOMETRY_INVALID_POLYGON,
+            "Invalid Survey Polygon: Polygon requires at least three vertices.",
+        ));
+    }
+    if coords.iter().any(|c| !c.x.is_finite() || !c.y.is_finite()) {
+        return Err(AppError::new(
+            codes::GEOMETRY_INVALID_POLYGON,
+            "Invalid Survey Polygon: Polygon has an invalid coordinate.",
+        ));
+    }
+
+    let projection = Projection::new(&coords);
+    let ring: Vec<_> = coords
+        .iter()
+        .map(|c| rotate(projection.forward(*c), heading_deg))
+        .collect();
+    let min_v = ring.iter().map(|p| p[1]).fold(f64::INFINITY, f64::min);
+    let max_v = ring.iter().map(|p| p[1]).fold(f64::NEG_INFINITY, f64::max);
+
+    let count = ((max_v - min_v) / line_spacing_m).floor() as usize;
+    if count > MAX_TRANSECTS {
+        return Err(AppError::new(
+            codes::GEOMETRY_TOO_MANY_POINTS,
+            format!(
+                "Too Many Transects: Spacing {line_spacing_m}m would create {count} transects, \
+                the maximum is {MAX_TRANSECTS}"
+            ),
+        )
+        .with("count", count)
+        .with("max", MAX_TRANSECTS));
+    }
+    // Centering the transects so the polygon is covered evenly on both sides
+    let offset = (max_v - min_v - count as f64 * line_spacing_m) / 2.0;
+
+    let mut path = vec![];
+    let mut midpoints = vec![];
+    let mut previous: Option<BoundaryPoint> = None;
+    for line in 0..=count {
+        let mut parts = transect(&ring, min_v + offset + line as f64 * line_spacing_m);
+        if line % 2 == 1 {
+            parts = parts.into_iter().rev().map(|(a, b)| (b, a)).collect();
+        }
+
+        for (start, end) in parts {
+            if let Some(previous) = previous {
+                path.extend(connector(&ring, previous, start));
+            }
+            path.push(start.point);
+            path.push(end.point);
+            midpoints.push([
+                (start.point[0] + end.point[0]) / 2.0,
+                (start.point[1] + end.point[1]) / 2.0,
+            ]);
+            previous = Some(end);
+        }
+    }
+    log::info!("Generated Survey Path with {} Waypoints", path.len());
+
+    let to_coord = |point: [f64; 2]| projection.inverse(rotate(point, heading_deg));
+    let points = if collection_points.unwrap_or(false) {
+        midpoints
+            .into_iter()
+            .map(|p| Point::from(to_coord(p)))
+            .collect()
+    } else {
+        vec![]
+    };
+    Ok(PathData::new(
+        path.into_iter().map(to_coord).collect(),
+        MultiPoint(points),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Creates a path through `waypoints` with `collection_points`, given as `(lng, lat)`.
+    fn path(waypoints: &[(f64, f64)], collection_points: &[(f64, f64)]) -> PathData {
+        let mut path = PathData::default();
+        for (i, (lng, lat)) in waypoints.iter().enumerate() {
+            path.insert_waypoint(i, Point::new(*lng, *lat)).unwrap();
+        }
+        for (lng, lat) in collection_points {
+            path.add_collection_point(Point::new(*lng, *lat));
+        }
+        path
+    }
+
+    /// Checks that `actual` is within `tolerance` of `expected`.
+    fn assert_near(actual: f64, expected: f64, tolerance: f64) {
+        assert!(
+            (actual - expected).abs() <= tolerance,
+            "{actual} is not within {tolerance} of {expected}"
+        );
+    }
+
+    #[test]
+    fn segment_lengths_match_known_distances() {
+        // One degree of longitude at the equator and one degree of latitude from the equator
+        let lengths = segment_lengths(&path(&[(0.0, 0.0), (1.0, 0.0), (1.0, 1.0)], &[]));
+
+        assert_eq!(lengths.len(), 2);
+        assert_near(lengths[0], 111_319.49, 0.01);
+        assert_near(lengths[1], 110_574.39, 0.01);
+    }
+
+    #[test]
+    fn segment_lengths_cross_antimeridian_the_short_way() {
+        let lengths = segment_lengths(&path(&[(179.5, 0.0), (-179.5, 0.0)], &[]));
+
+        assert_near(lengths[0], 111_319.49, 0.01);
+    }
+
+    #[test]
+    fn path_metrics_of_empty_path_are_zero() {
+        let metrics = path_metrics(PathData::default(), 0.0, 0.0).unwrap();
+
+        assert_eq!(metrics, PathMetrics::default());
+    }
+
+    #[test]
+    fn path_metrics_include_travel_and_dwell_time() {
+        let path = path(
+            &[(0.0, 0.0), (1.0, 0.0), (1.0, 1.0)],
+            &[(0.5, 0.0), (1.0, 0.5), (1.0, 1.0)],
+        );
+
+        let metrics = path_metrics(path, 2.0, 300.0).unwrap();
+
+        assert_near(metrics.length, 111_319.49 + 110_574.39, 0.02);
+        assert_eq!(metrics.length, metrics.segment_lengths.iter().sum::<f64>());
+        assert_eq!(metrics.travel_time, metrics.length / 2.0);
+        assert_eq!(metrics.duration, metrics.travel_time + 900.0);
+    }
+
+    #[test]
+    fn path_metrics_count_dwell_time_without_waypoints() {
+        let metrics = path_metrics(path(&[], &[(0.5, 0.0), (1.0, 0.5)]), 0.0, 60.0).unwrap();
+
+        assert_eq!(metrics.length, 0.0);
+        assert_eq!(metrics.duration, 120.0);
+    }
+
+    #[test]
+    fn path_metrics_reject_invalid_speed_and_dwell_time() {
+        let line = [(0.0, 0.0), (1.0, 0.0)];
+        assert_eq!(
+            path_metrics(path(&line, &[]), 0.0, 0.0).unwrap_err(),
+            AppError::new(codes::GEOMETRY_INVALID_ARGUMENT, "Invalid Speed: 0")
+                .with("argument", "speed_mps")
+                .with("value", 0.0)
+        );
+        assert!(path_metrics(path(&line, &[]), f64::NAN, 0.0).is_err());
+        assert!(path_metrics(path(&line, &[]), 2.0, -1.0).is_err());
+        assert!(path_metrics(path(&line, &[]), 2.0, f64::INFINITY).is_err());
+    }
+
+    /// Creates a path through `coords`, given as `(lng, lat)`, and projects it to meters.
+    fn projected(coords: &[(f64, f64)]) -> (PathData, Vec<[f64; 2]>) {
+        let path = path(coords, &[]);
+        let points = project(&path.path().0);
+        (path, points)
+    }
+
+    /// Creates a path zigzagging east from Kuala Lumpur with `count` waypoints.
+    ///
+    /// No three waypoints in a row are in a straight line.
+    fn zigzag(count: usize) -> Vec<(f64, f64)> {
+        (0..count)
+            .map(|i| {
+                let offset = if i % 2 == 0 {
+                    0.0
+                } else {
+                    (1 + (i * 7) % 11) as f64 * 0.00002
+                };
+                (101.5 + i as f64 * 0.0001, 3.1 + offset)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn douglas_peucker_keeps_endpoints() {
+        let (_, points) = projected(&zigzag(50));
+
+        let keep = douglas_peucker(&points, 1_000_000.0);
+
+        assert!(keep[0] && keep[49]);
+        assert_eq!(keep.iter().filter(|keep| **keep).count(), 2);
+        assert_eq!(douglas_peucker(&[], 1.0), Vec::<bool>::new());
+        assert_eq!(douglas_peucker(&points[..1], 1.0), vec![true]);
+    }
+
+    #[test]
+    fn douglas_peucker_with_zero_tolerance_keeps_all_bends() {
+        let (_, points) = projected(&zigzag(50));
+
+        let keep = douglas_peucker(&points, 0.0);
+
+        assert!(keep.iter().all(|keep| *keep));
+    }
+
+    #[test]
+    fn simplify_path_collapses_straight_line() {
+        let line: Vec<_> = (0..20).map(|i| (101.5 + i as f64 * 0.001, 3.1)).collect();
+
+        let simplified = simplify_path(path(&line, &[(101.505, 3.1)]), 0.01).unwrap();
+
+        assert_eq!(simplified.original_vertices, 20);
+        assert_eq!(simplified.simplified_vertices, 2);
+        let coords = &simplified.path.path().0;
+        assert_eq!((coords[0].x, coords[0].y), line[0]);
+        assert_eq!((coords[1].x, coords[1].y), line[19]);
+        assert_eq!(
+            simplified.path.collection_points().0,
+            vec![Point::new(101.505, 3.1)]
+        );
+    }
+
+    #[test]
+    fn simplify_path_stays_within_tolerance() {
+        let coords = zigzag(200);
+        let (_, points) = projected(&coords);
+
+        for tolerance in [1.0, 5.0, 15.0, 30.0] {
+            let simplified = simplify_path(path(&coords, &[]), tolerance).unwrap();
+
+            // Comparing in the projection of the original path, as it depends on the latitudes
+            let kept: Vec<_> = path(&coords, &[])
+                .path()
+                .0
+                .iter()
+                .zip(&points)
+                .filter(|(c, _)| simplified.path.path().0.contains(c))
+                .map(|(_, point)| *point)
+                .collect();
+            assert_eq!(kept.len(), simplified.simplified_vertices);
+            if tolerance >= 15.0 {
+                assert!(simplified.simplified_vertices < 200);
+            }
+            assert_eq!(kept.first(), points.first());
+            assert_eq!(kept.last(), points.last());
+            for point in &points {
+                let deviation = kept
+                    .windows(2)
+                    .map(|segment| segment_distance(*point, segment[0], segment[1]))
+                    .fold(f64::INFINITY, f64::min);
+                assert!(deviation <= tolerance, "{deviation} > {tolerance}");
+            }
+        }
+    }
+
+    #[test]
+    fn simplify_path_rejects_invalid_tolerance() {
+        assert!(simplify_path(PathData::default(), -1.0).is_err());
+        assert!(simplify_path(PathData::default(), f64::NAN).is_err());
+        let simplified = simplify_path(PathData::default(), 1.0).unwrap();
+        assert_eq!(simplified.original_vertices, 0);
+    }
+
+    #[test]
+    fn generate_collection_points_along_single_segment() {
+        // About 1113 m along the equator
+        let path = path(&[(0.0, 0.0), (0.01, 0.0)], &[(5.0, 5.0)]);
+
+        let path = generate_collection_points(path, 250.0, None).unwrap();
+
+        let points = &path.collection_points().0;
+        assert_eq!(points.len(), 6);
+        assert_eq!(points[0], Point::new(0.0, 0.0));
+        assert_eq!(points[5], Point::new(0.01, 0.0));
+        for (i, point) in points[..5].iter().enumerate() {
+            assert_near(
+                Point::new(0.0, 0.0).geodesic_distance(point),
+                250.0 * i as f64,
+                1e-6,
+            );
+        }
+    }
+
+    #[test]
+    fn generate_collection_points_carries_distance_across_segments() {
+        let path = path(&[(0.0, 0.0), (0.001, 0.0), (0.001, 0.01)], &[]);
+        let lengths = segment_lengths(&path);
+
+        let path = generate_collection_points(path, 500.0, None).unwrap();
+
+        let points = &path.collection_points().0;
+        assert_eq!(points.len(), 4);
+        // The second point is on the second segment, 500 m along the path
+        assert_near(
+            Point::new(0.001, 0.0).geodesic_distance(&points[1]),
+            500.0 - lengths[0],
+            1e-6,
+        );
+        assert_eq!(points[3], Point::new(0.001, 0.01));
+    }
+
+    #[test]
+    fn generate_collection_points_on_zero_length_path() {
+        let path = path(&[(101.5, 3.1), (101.5, 3.1)], &[]);
+
+        let path = generate_collection_points(path, 100.0, None).unwrap();
+
+        assert_eq!(path.collection_points().0, vec![Point::new(101.5, 3.1)]);
+        let empty = generate_collection_points(PathData::default(), 100.0, None).unwrap();
+        assert!(empty.collection_points().0.is_empty());
+    }
+
+    #[test]
+    fn generate_collection_points_rejects_too_many_points() {
+        let line = [(0.0, 0.0), (1.0, 0.0)];
+
+        assert!(generate_collection_points(path(&line, &[]), 1.0, None).is_err());
+        assert!(generate_collection_points(path(&line, &[]), 1000.0, Some(100)).is_err());
+        assert!(generate_collection_points(path(&line, &[]), 1000.0, Some(200)).is_ok());
+        assert!(generate_collection_points(path(&line, &[]), 0.0, None).is_err());
+    }
+
+    /// A square pond about 200 m across.
+    const SQUARE: [(f64, f64); 4] = [
+        (101.5, 3.1),
+        (101.5018, 3.1),
+        (101.5018, 3.1018),
+        (101.5, 3.1018),
+    ];
+
+    /// A U-shaped pond about 300 m across, which is not convex.
+    const U_SHAPE: [(f64, f64); 8] = [
+        (101.5, 3.1),
+        (101.5027, 3.1),
+        (101.5027, 3.1027),
+        (101.5018, 3.1027),
+        (101.5018, 3.1009),
+        (101.5009, 3.1009),
+        (101.5009, 3.1027),
+        (101.5, 3.1027),
+    ];
+
+    /// Checks that the waypoints and collection points are inside the polygon or within a
+    /// millimeter of its boundary.
+    fn assert_inside(polygon: &[(f64, f64)], path: &PathData) {
+        let coords: Vec<_> = polygon.iter().copied().map(Coord::from).collect();
+        let projection = Projection::new(&coords);
+        let ring: Vec<_> = coords.iter().map(|c| projection.forward(*c)).collect();
+        let points = path
+            .path()
+            .0
+            .iter()
+            .copied()
+            .chain(path.collection_points().0.iter().map(|p| p.0));
+        for coord in points {
+            let point = projection.forward(coord);
+            let distance = (0..ring.len())
+                .map(|k| segment_distance(point, ring[k], ring[(k + 1) % ring.len()]))
+                .fold(f64::INFINITY, f64::min);
+            assert!(
+                contains(&ring, point) || distance < 0.001,
+                "{coord:?} is {distance}m outside"
+            );
+        }
+    }
+
+    #[test]
+    fn survey_path_covers_square_with_transects() {
+        let path = generate_survey_path(SQUARE.to_vec(), 20.0, 90.0, Some(true)).unwrap();
+
+        assert_inside(&SQUARE, &path);
+        // The square is about 200 m across, fitting 11 transects 20 m apart
+        assert_eq!(path.path().0.len(), 22);
+        assert_eq!(path.collection_points().0.len(), 11);
+        // East-west transects alternate direction
+        let coords = &path.path().0;
+        assert!(coords[0].x < coords[1].x);
+        assert!(coords[2].x > coords[3].x);
+        assert_near(coords[0].y, coords[1].y, 1e-12);
+        let spacing =
+            Point::from(coords[0]).geodesic_distance(&Point::new(coords[0].x, coords[2].y));
+        // The projection uses the mean radius of the Earth, which is within 1% near the equator
+        assert_near(spacing, 20.0, 0.2);
+    }
+
+    #[test]
+    fn survey_path_stays_inside_non_convex_polygon() {
+        for heading in [0.0, 45.0, 90.0, 135.0] {
+            let path = generate_survey_path(U_SHAPE.to_vec(), 15.0, heading, Some(true)).unwrap();
+
+            assert!(path.path().0.len() > 2, "heading {heading}");
+            assert_inside(&U_SHAPE, &path);
+        }
+    }
+
+    #[test]
+    fn survey_path_joins_split_transects_along_boundary() {
+        // Transects running east-west through the arms of the U are split in two
+        let path = generate_survey_path(U_SHAPE.to_vec(), 15.0, 90.0, None).unwrap();
+
+        let coords: Vec<_> = U_SHAPE.iter().copied().map(Coord::from).collect();
+        let projection = Projection::new(&coords);
+        let ring: Vec<_> = coords.iter().map(|c| projection.forward(*c)).collect();
+        let points: Vec<_> = path
+            .path()
+            .0
+            .iter()
+            .map(|c| projection.forward(*c))
+            .collect();
+        for segment in points.windows(2) {
+            let middle = [
+                (segment[0][0] + segment[1][0]) / 2.0,
+                (segment[0][1] + segment[1][1]) / 2.0,
+            ];
+            let distance = (0..ring.len())
+                .map(|k| segment_distance(middle, ring[k], ring[(k + 1) % ring.len()]))
+                .fold(f64::INFINITY, f64::min);
+            assert!(contains(&ring, middle) || distance < 0.001);
+        }
+        assert!(path.collection_points().0.is_empty());
+    }
+
+    #[test]
+    fn survey_path_rejects_invalid_input() {
+        assert!(generate_survey_path(SQUARE.to_vec(), 0.0, 0.0, None).is_err());
+        assert!(generate_survey_path(SQUARE.to_vec(), 20.0, f64::NAN, None).is_err());
+        assert!(generate_survey_path(SQUARE[..2].to_vec(), 20.0, 0.0, None).is_err());
+        let closed = vec![(0.0, 0.0), (1.0, 0.0), (0.0, 0.0)];
+        assert!(generate_survey_path(closed, 20.0, 0.0, None).is_err());
+        assert!(generate_survey_path(SQUARE.to_vec(), 0.001, 0.0, None).is_err());
+    }
+
+    /// Gets the collection points of the path as `(lng, lat)`.
+    fn collection_points(path: &PathData) -> Vec<(f64, f64)> {
+        path.collection_points().iter().map(|p| p.x_y()).collect()
+    }
+
+    #[test]
+    fn close_points_are_snapped_onto_the_path() {
+        // About 11 m and 111 m north of the path
+        let path = path(
+            &[(101.5, 3.1), (101.502, 3.1)],
+            &[(101.501, 3.1001), (101.501, 3.101)],
+        );
+
+        let snapped = snap_points_to_path(path, 20.0).unwrap();
+
+        let points = collection_points(&snapped.path);
+        assert_near(points[0].0, 101.501, 1e-9);
+        assert_near(points[0].1, 3.1, 1e-9);
+        assert_eq!(points[1], (101.501, 3.101));
+        assert!(snapped.points[0].moved);
+        assert_near(snapped.points[0].distance, 11.06, 0.05);
+        assert!(!snapped.points[1].moved);
+        assert_near(snapped.points[1].distance, 110.6, 0.5);
+    }
+
+    #[test]
+    fn equally_close_segments_snap_to_the_first() {
+        // The point is halfway between the first and the last segment
+        let path = path(
+            &[(0.0, 0.0), (1.0, 0.0), (1.0, 0.5), (0.0, 0.5)],
+            &[(0.5, 0.25)],
+        );
+
+        let snapped = snap_points_to_path(path, 100_000.0).unwrap();
+
+        let points = collection_points(&snapped.path);
+        assert_near(points[0].0, 0.5, 1e-9);
+        assert_near(points[0].1, 0.0, 1e-9);
+    }
+
+    #[test]
+    fn points_closest_to_a_waypoint_snap_onto_it_exactly() {
+        let path = path(
+            &[(101.5, 3.1), (101.501, 3.1), (101.501, 3.101)],
+            &[(101.5011, 3.0999), (101.4999, 3.1)],
+        );
+
+        let snapped = snap_points_to_path(path, 50.0).unwrap();
+
+        assert_eq!(
+            collection_points(&snapped.path),
+            [(101.501, 3.1), (101.5, 3.1)]
+        );
+        assert_eq!(snapped.path.path().0.len(), 3);
+    }
+
+    #[test]
+    fn points_on_the_path_are_not_moved() {
+        let path = path(&[(101.5, 3.1), (101.501, 3.1)], &[(101.5, 3.1)]);
+
+        let snapped = snap_points_to_path(path, 50.0).unwrap();
+
+        assert_eq!(
+            snapped.points,
+            [SnappedPoint {
+                distance: 0.0,
+                moved: false
+            }]
+        );
+    }
+
+    #[test]
+    fn path_of_one_waypoint_snaps_onto_it() {
+        let path = path(&[(101.5, 3.1)], &[(101.5001, 3.1)]);
+
+        let snapped = snap_points_to_path(path, 50.0).unwrap();
+
+        assert_eq!(collection_points(&snapped.path), [(101.5, 3.1)]);
+    }
+
+    #[test]
+    fn snapping_rejects_invalid_input() {
+        let error = snap_points_to_path(path(&[(101.5, 3.1)], &[]), -1.0).unwrap_err();
+        assert_eq!(
+            error,
+            invalid_argument("Snap Distance", "max_snap_distance_m", -1.0)
+        );
+
+        let error = snap_points_to_path(path(&[], &[(101.5, 3.1)]), 5.0).unwrap_err();
+        assert_eq!(error, empty_path("Unable to Snap Collection Points"));
+    }
+
+    /// Creates a path of three waypoints with notes on the second and third collection point.
+    fn noted_path() -> PathData {
+        let mut path = path(
+            &[(101.5, 3.1), (101.501, 3.1), (101.502, 3.1)],
+            &[(101.5003, 3.1001), (101.5018, 3.0999), (101.501, 3.1002)],
+        );
+        path.set_collection_point_note(1, String::from("Outflow"))
+            .unwrap();
+        path.set_collection_point_note(2, String::from("Jetty"))
+            .unwrap();
+        path
+    }
+
+    #[test]
+    fn split_shares_the_waypoint() {
+        let (first, second) = split_path(noted_path(), 1).unwrap();
+
+        assert_eq!(
+            first.path().0,
+            [Coord::from((101.5, 3.1)), Coord::from((101.501, 3.1))]
+        );
+        assert_eq!(
+            second.path().0,
+            [Coord::from((101.501, 3.1)), Coord::from((101.502, 3.1))]
+        );
+    }
+
+    #[test]
+    fn split_assigns_points_to_the_nearest_half() {
+        let (first, second) = split_path(noted_path(), 1).unwrap();
+
+        // The point at the shared waypoint is equally close to both halves
+        assert_eq!(
+            collection_points(&first),
+            [(101.5003, 3.1001), (101.501, 3.1002)]
+        );
+        assert_eq!(collection_points(&second), [(101.5018, 3.0999)]);
+    }
+
+    #[test]
+    fn split_renumbers_the_notes() {
+        let (first, second) = split_path(noted_path(), 1).unwrap();
+
+        assert_eq!(first.notes(), &BTreeMap::from([(1, String::from("Jetty"))]));
+        assert_eq!(
+            second.notes(),
+            &BTreeMap::from([(0, String::from("Outflow"))])
+        );
+    }
+
+    #[test]
+    fn split_checks_the_index() {
+        assert_eq!(
+            split_path(noted_path(), 3).unwrap_err(),
+            AppError::from(PathError::WaypointOutOfRange { index: 3, len: 3 })
+        );
+        for index in [0, 2] {
+            let error = serde_json::to_value(split_path(noted_path(), index).unwrap_err()).unwrap();
+            assert_eq!(error["code"], codes::GEOMETRY_SPLIT_AT_END);
+            assert_eq!(error["params"]["index"], index);
+        }
+        assert_eq!(
+            split_path(PathData::default(), 0).unwrap_err(),
+            empty_path("Unable to Split Path")
+        );
+    }
+
+    #[test]
+    fn join_undoes_a_split() {
+        let (first, second) = split_path(noted_path(), 1).unwrap();
+
+        let joined = join_paths(first, second, 0.0).unwrap();
+
+        assert_eq!(joined.path(), noted_path().path());
+        assert_eq!(
+            collection_points(&joined),
+            [(101.5003, 3.1001), (101.501, 3.1002), (101.5018, 3.0999)]
+        );
+        assert_eq!(
+            joined.notes(),
+            &BTreeMap::from([(1, String::from("Jetty")), (2, String::from("Outflow"))])
+        );
+    }
+
+    #[test]
+    fn join_connects_paths_within_the_gap() {
+        // The gap is about 11 m
+        let a = path(&[(101.5, 3.1), (101.501, 3.1)], &[]);
+        let b = path(&[(101.5011, 3.1), (101.502, 3.1)], &[]);
+
+        let joined = join_paths(a, b, 20.0).unwrap();
+
+        assert_eq!(joined.path().0.len(), 4);
+        assert_eq!(joined.path().0[2], Coord::from((101.5011, 3.1)));
+    }
+
+    #[test]
+    fn join_rejects_large_gaps() {
+        let a = path(&[(101.5, 3.1), (101.501, 3.1)], &[]);
+        let b = path(&[(101.5011, 3.1), (101.502, 3.1)], &[]);
+
+        let error = serde_json::to_value(join_paths(a, b, 5.0).unwrap_err()).unwrap();
+
+        assert_eq!(error["code"], codes::GEOMETRY_GAP_TOO_LARGE);
+        assert_near(error["params"]["gap"].as_f64().unwrap(), 11.1, 0.1);
+        assert_eq!(error["params"]["max_gap"], 5.0);
+    }
+
+    #[test]
+    fn join_rejects_empty_paths_and_invalid_gaps() {
+        let a = path(&[(101.5, 3.1), (101.501, 3.1)], &[]);
+
+        assert_eq!(
+            join_paths(a.clone(), PathData::default(), 5.0).unwrap_err(),
+            empty_path("Unable to Join Paths")
+        );
+        assert_eq!(
+            join_paths(a.clone(), a, f64::NAN).unwrap_err().to_string(),
+            invalid_argument("Gap", "max_gap_m", f64::NAN).to_string()
+        );
+    }
+}
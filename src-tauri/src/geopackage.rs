@@ -0,0 +1,561 @@
+//! GeoPackage export of boat data and the path for GIS software such as QGIS.
+//!
+//! The readings, the route and the collection points are written as feature tables in WGS84,
+//! following version 1.3 of the OGC GeoPackage encoding standard.
+
+use std::path::{Path, PathBuf};
+
+use geo_types::Coord;
+use sqlx::{sqlite::SqliteConnectOptions, Connection, Executor, SqliteConnection};
+use tauri::async_runtime::block_on;
+
+use crate::{
+    data::BoatData,
+    errors::{export_error, AppError},
+    path::PathData,
+};
+
+/// The SRS ID of WGS84 longitude and latitude.
+const WGS84_SRS_ID: i32 = 4326;
+
+/// The tables required by GeoPackage, with the spatial reference systems it requires.
+const SCHEMA: &str = r#"
+PRAGMA application_id = 1196444487;
+PRAGMA user_version = 10300;
+CREATE TABLE gpkg_spatial_ref_sys (
+    srs_name TEXT NOT NULL,
+    srs_id INTEGER PRIMARY KEY,
+    organization TEXT NOT NULL,
+    organization_coordsys_id INTEGER NOT NULL,
+    definition TEXT NOT NULL,
+    description TEXT
+);
+CREATE TABLE gpkg_contents (
+    table_name TEXT NOT NULL PRIMARY KEY,
+    data_type TEXT NOT NULL,
+    identifier TEXT UNIQUE,
+    description TEXT DEFAULT '',
+    last_change DATETIME NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ', 'now')),
+    min_x DOUBLE,
+    min_y DOUBLE,
+    max_x DOUBLE,
+    max_y DOUBLE,
+    srs_id INTEGER,
+    CONSTRAINT fk_gc_r_srs_id FOREIGN KEY (srs_id) REFERENCES gpkg_spatial_ref_sys(srs_id)
+);
+CREATE TABLE gpkg_geometry_columns (
+    table_name TEXT NOT NULL,
+    column_name TEXT NOT NULL,
+    geometry_type_name TEXT NOT NULL,
+    srs_id INTEGER NOT NULL,
+    z TINYINT NOT NULL,
+    m TINYINT NOT NULL,
+    CONSTRAINT pk_geom_cols PRIMARY KEY (table_name, column_name),
+    CONSTRAINT uk_gc_table_name UNIQUE (table_name),
+    CONSTRAINT fk_gc_tn FOREIGN KEY (table_name) REFERENCES gpkg_contents(table_name),
+    CONSTRAINT fk_gc_srs FOREIGN KEY (srs_id) REFERENCES gpkg_spatial_ref_sys(srs_id)
+);
+INSERT INTO gpkg_spatial_ref_sys VALUES
+    ('Undefined cartesian SRS', -1, 'NONE', -1, 'undefined', 'undefined cartesian coordinate reference system'),
+    ('Undefined geographic SRS', 0, 'NONE', 0, 'undefined', 'undefined geographic coordinate reference system'),
+    ('WGS 84 geodetic', 4326, 'EPSG', 4326, 'GEOGCS["WGS 84",DATUM["WGS_1984",SPHEROID["WGS 84",6378137,298.257223563,AUTHORITY["EPSG","7030"]],AUTHORITY["EPSG","6326"]],PRIMEM["Greenwich",0,AUTHORITY["EPSG","8901"]],UNIT["degree",0.0174532925199433,AUTHORITY["EPSG","9122"]],AUTHORITY["EPSG","4326"]]', 'longitude/latitude coordinates in decimal degrees on the WGS 84 spheroid');
+"#;
+
+/// The WKB geometry type of a point.
+const WKB_POINT: u32 = 1;
+
+/// The WKB geometry type of a line string.
+const WKB_LINE_STRING: u32 = 2;
+
+/// Writes a WKB point without its header.
+fn write_coord(wkb: &mut Vec<u8>, coord: Coord<f64>) {
+    wkb.extend_from_slice(&coord.x.to_le_bytes());
+    wkb.extend_from_slice(&coord.y.to_le_bytes());
+}
+
+/// Gets the bounding box of coordinates as `[min_x, min_y, max_x, max_y]`.
+///
+/// Returns `None` if there are no finite coordinates.
+fn bbox(coords: impl Iterator<Item = Coord<f64>>) -> Option<[f64; 4]> {
+    coords
+        .filter(|c| c.x.is_finite() && c.y.is_finite())
+        .fold(None, |bbox, c| {
+            Some(match bbox {
+                Some([min_x, min_y, max_x, max_y]) => [
+                    min_x.min(c.x),
+                    min_y.min(c.y),
+                    max_x.max(c.x),
+                    max_y.max(c.y),
+                ],
+                None => [c.x, c.y, c.x, c.y],
+            })
+        })
+}
+
+/// Encodes a GeoPackage geometry blob of a WGS84 geometry.
+///
+/// `wkb` is the little-endian WKB of the geometry. The envelope is written for geometries with
+/// more than one coordinate.
+fn geometry_blob(wkb: &[u8], envelope: Option<[f64; 4]>, empty: bool) -> Vec<u8> {
+    // Little-endian, with the envelope indicator in bits 1 to 3 and the empty flag in bit 4
+    let mut flags = 0b0000_0001;
+    if envelope.is_some() {
+        flags |= 0b0000_0010;
+    }
+    if empty {
+        flags |= 0b0001_0000;
+    }
+
+    let mut blob = vec![b'G', b'P', 0, flags];
+    blob.extend_from_slice(&WGS84_SRS_ID.to_le_bytes());
+    if let Some([min_x, min_y, max_x, max_y]) = envelope {
+        for value in [min_x, max_x, min_y, max_y] {
+            blob.extend_from_slice(&value.to_le_bytes());
+        }
+    }
+    blob.extend_from_slice(wkb);
+    blob
+}
+
+/// Encodes a point as a GeoPackage geometry blob.
+fn point_blob(coord: Coord<f64>) -> Vec<u8> {
+    let mut wkb = vec![1];
+    wkb.extend_from_slice(&WKB_POINT.to_le_bytes());
+    write_coord(&mut wkb, coord);
+    geometry_blob(&wkb, None, false)
+}
+
+/// Encodes a line string as a GeoPackage geometry blob.
+fn line_string_blob(coords: &[Coord<f64>]) -> Vec<u8> {
+    let mut wkb = vec![1];
+    wkb.extend_from_slice(&WKB_LINE_STRING.to_le_bytes());
+    wkb.extend_from_slice(&(coords.len() as u32).to_le_bytes());
+    for coord in coords {
+        write_coord(&mut wkb, *coord);
+    }
+    geometry_blob(&wkb, bbox(coords.iter().copied()), coords.is_empty())
+}
+
+/// Creates a feature table and registers it in the GeoPackage metadata.
+///
+/// `columns` are the definitions of the attribute columns.
+async fn create_layer(
+    connection: &mut SqliteConnection,
+    table: &str,
+    description: &str,
+    geometry_type: &str,
+    columns: &str,
+    extent: Option<[f64; 4]>,
+) -> Result<(), sqlx::Error> {
+    connection
+        .execute(
+            format!(
+                "CREATE TABLE {table} (fid INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL, geom {geometry_type}, {columns})"
+            )
+            .as_str(),
+        )
+        .await?;
+    let [min_x, min_y, max_x, max_y] = match extent {
+        Some(v) => v.map(Some),
+        None => [None; 4],
+    };
+    sqlx::query(
+        "INSERT INTO gpkg_contents (table_name, data_type, identifier, description, min_x, min_y, max_x, max_y, srs_id) VALUES ($1, 'features', $1, $2, $3, $4, $5, $6, $7)",
+    )
+    .bind(table)
+    .bind(description)
+    .bind(min_x)
+    .bind(min_y)
+    .bind(max_x)
+    .bind(max_y)
+    .bind(WGS84_SRS_ID)
+    .execute(&mut *connection)
+    .await?;
+    sqlx::query("INSERT INTO gpkg_geometry_columns VALUES ($1, 'geom', $2, $3, 0, 0)")
+        .bind(table)
+        .bind(geometry_type)
+        .bind(WGS84_SRS_ID)
+        .execute(&mut *connection)
+        .await?;
+    Ok(())
+}
+
+/// Writes the readings, the route and the collection points to a new GeoPackage.
+async fn write_geopackage(
+    path: &Path,
+    data: &BoatData,
+    route: Option<&PathData>,
+) -> Result<(), sqlx::Error> {
+    let options = SqliteConnectOptions::new()
+        .filename(path)
+        .create_if_missing(true);
+    let mut connection = SqliteConnection::connect_with(&options).await?;
+    connection.execute(SCHEMA).await?;
+    let mut transaction = connection.begin().await?;
+
+    let coords = data.features().iter().map(|f| f.geometry().0);
+    create_layer(
+        &mut transaction,
+        "readings",
+        "Temperature readings of the boat",
+        "POINT",
+        "temperature REAL, depth REAL, layer TEXT, time DATETIME",
+        bbox(coords),
+    )
+    .await?;
+    for feature in data.features() {
+        sqlx::query(
+            "INSERT INTO readings (geom, temperature, depth, layer, time) VALUES ($1, $2, $3, $4, $5)",
+        )
+        .bind(point_blob(feature.geometry().0))
+        .bind(feature.temperature())
+        .bind(feature.depth())
+        .bind(feature.layer().to_string())
+        .bind(
+            feature
+                .time()
+                .format("%Y-%m-%dT%H:%M:%S%.3fZ")
+                .to_string(),
+        )
+        .execute(&mut *transaction)
+        .await?;
+    }
+
+    if let Some(route) = route {
+        let coords = &route.path().0;
+        create_layer(
+            &mut transaction,
+            "route",
+            "Path of the boat",
+            "LINESTRING",
+            "waypoints INTEGER",
+            bbox(coords.iter().copied()),
+        )
+        .await?;
+        sqlx::query("INSERT INTO route (geom, waypoints) VALUES ($1, $2)")
+            .bind(line_string_blob(coords))
+            .bind(coords.len() as i64)
+            .execute(&mut *transaction)
+            .await?;
+
+        let points = &route.collection_points().0;
+        create_layer(
+            &mut transaction,
+            "collection_points",
+            "Points the boat collects data at",
+            "POINT",
+            "point_index INTEGER",
+            bbox(points.iter().map(|p| p.0)),
+        )
+        .await?;
+        for (i, point) in points.iter().enumerate() {
+            sqlx::query("INSERT INTO collection_points (geom, point_index) VALUES ($1, $2)")
+                .bind(point_blob(point.0))
+                .bind(i as i64)
+                .execute(&mut *transaction)
+                .await?;
+        }
+    }
+    transaction.commit().await?;
+    connection.close().await
+}
+
+/// Export boat data and optionally the path to a GeoPackage.
+///
+/// The readings are written to the `readings` point layer with their temperature, depth, layer
+/// and time. The path is written to the `route` line layer and the `collection_points` point
+/// layer. An existing file at `export_path` is replaced.
+#[tauri::command]
+pub fn export_geopackage(
+    export_path: PathBuf,
+    data: BoatData,
+    route: Option<PathData>,
+) -> Result<(), AppError> {
+    log::debug!("Exporting to: {}", export_path.display());
+    match std::fs::remove_file(&export_path) {
+        Ok(_) => {}
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+        Err(e) => return Err(AppError::io(e, &export_path)),
+    }
+
+    if let Err(e) = block_on(write_geopackage(&export_path, &data, route.as_ref())) {
+        // Not leaving a partially written GeoPackage behind
+        let _ = std::fs::remove_file(&export_path);
+        return Err(export_error(&export_path, e));
+    }
+    log::info!("Exported {} Readings to GeoPackage", data.features().len());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeZone, Utc};
+    use geo_types::{LineString, MultiPoint, Point};
+    use sqlx::Row;
+
+    use super::*;
+    use crate::{
+        data::{BoatDataFeature, Layer},
+        storage::tests::temp_dir,
+    };
+
+    /// Boat data with two readings.
+    fn data() -> BoatData {
+        let time = Utc.with_ymd_and_hms(2024, 5, 12, 6, 30, 0).unwrap();
+        BoatData::new(
+            BoatData::default().version().to_string(),
+            vec![
+                BoatDataFeature::new(28.5, 0.5, Layer::Surface, time, Point::new(101.5, 3.1)),
+                BoatDataFeature::new(27.25, 4.0, Layer::SeaBed, time, Point::new(101.75, 3.0)),
+            ],
+        )
+    }
+
+    /// A path with three waypoints and two collection points.
+    fn route() -> PathData {
+        PathData::new(
+            LineString::from(vec![(101.5, 3.1), (101.6, 3.2), (101.8, 3.05)]),
+            MultiPoint::from(vec![(101.6, 3.2), (101.8, 3.05)]),
+        )
+    }
+
+    /// Opens an exported GeoPackage.
+    async fn open(path: &Path) -> SqliteConnection {
+        SqliteConnection::connect_with(&SqliteConnectOptions::new().filename(path))
+            .await
+            .unwrap()
+    }
+
+    /// Reads the coordinates of a point from a geometry blob without an envelope.
+    fn blob_point(blob: &[u8]) -> (f64, f64) {
+        let x = f64::from_le_bytes(blob[13..21].try_into().unwrap());
+        let y = f64::from_le_bytes(blob[21..29].try_into().unwrap());
+        (x, y)
+    }
+
+    #[test]
+    fn geopackage_has_the_required_metadata() {
+        let dir = temp_dir("geopackage_metadata");
+        let path = dir.join("export.gpkg");
+        export_geopackage(path.clone(), data(), Some(route())).unwrap();
+
+        block_on(async {
+            let mut connection = open(&path).await;
+            let application_id: i64 = sqlx::query_scalar("PRAGMA application_id")
+                .fetch_one(&mut connection)
+                .await
+                .unwrap();
+            let user_version: i64 = sqlx::query_scalar("PRAGMA user_version")
+                .fetch_one(&mut connection)
+                .await
+                .unwrap();
+            assert_eq!(application_id, 0x4750_4B47);
+            assert_eq!(user_version, 10300);
+
+            let srs_ids: Vec<i64> =
+                sqlx::query_scalar("SELECT srs_id FROM gpkg_spatial_ref_sys ORDER BY srs_id")
+                    .fetch_all(&mut connection)
+                    .await
+                    .unwrap();
+            assert_eq!(srs_ids, vec![-1, 0, 4326]);
+
+            let contents = sqlx::query(
+                "SELECT table_name, data_type, min_x, min_y, max_x, max_y, srs_id FROM gpkg_contents ORDER BY table_name",
+            )
+            .fetch_all(&mut connection)
+            .await
+            .unwrap();
+            let contents: Vec<(String, String, [f64; 4], i64)> = contents
+                .iter()
+                .map(|row| {
+                    (
+                        row.get(0),
+                        row.get(1),
+                        [row.get(2), row.get(3), row.get(4), row.get(5)],
+                        row.get(6),
+                    )
+                })
+                .collect();
+            assert_eq!(
+                contents,
+                vec![
+                    (
+                        String::from("collection_points"),
+                        String::from("features"),
+                        [101.6, 3.05, 101.8, 3.2],
+                        4326
+                    ),
+                    (
+                        String::from("readings"),
+                        String::from("features"),
+                        [101.5, 3.0, 101.75, 3.1],
+                        4326
+                    ),
+                    (
+                        String::from("route"),
+                        String::from("features"),
+                        [101.5, 3.05, 101.8, 3.2],
+                        4326
+                    ),
+                ]
+            );
+
+            let columns: Vec<(String, String, String, i64)> = sqlx::query_as(
+                "SELECT table_name, column_name, geometry_type_name, srs_id FROM gpkg_geometry_columns ORDER BY table_name",
+            )
+            .fetch_all(&mut connection)
+            .await
+            .unwrap();
+            assert_eq!(
+                columns,
+                vec![
+                    (
+                        String::from("collection_points"),
+                        String::from("geom"),
+                        String::from("POINT"),
+                        4326
+                    ),
+                    (
+                        String::from("readings"),
+                        String::from("geom"),
+                        String::from("POINT"),
+                        4326
+                    ),
+                    (
+                        String::from("route"),
+                        String::from("geom"),
+                        String::from("LINESTRING"),
+                        4326
+                    ),
+                ]
+            );
+            connection.close().await.unwrap();
+        });
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn readings_are_written_with_point_blobs() {
+        let dir = temp_dir("geopackage_readings");
+        let path = dir.join("export.gpkg");
+        export_geopackage(path.clone(), data(), None).unwrap();
+
+        block_on(async {
+            let mut connection = open(&path).await;
+            let rows: Vec<(Vec<u8>, f64, f64, String, String)> = sqlx::query_as(
+                "SELECT geom, temperature, depth, layer, time FROM readings ORDER BY fid",
+            )
+            .fetch_all(&mut connection)
+            .await
+            .unwrap();
+            assert_eq!(rows.len(), 2);
+
+            let (blob, temperature, depth, layer, time) = &rows[1];
+            // The magic, version 0, little-endian without an envelope, then the SRS ID
+            assert_eq!(blob[..4], [b'G', b'P', 0, 1]);
+            assert_eq!(blob[4..8], 4326i32.to_le_bytes());
+            // Little-endian WKB point
+            assert_eq!(blob[8], 1);
+            assert_eq!(blob[9..13], WKB_POINT.to_le_bytes());
+            assert_eq!(blob.len(), 29);
+            assert_eq!(blob_point(blob), (101.75, 3.0));
+            assert_eq!(*temperature, 27.25);
+            assert_eq!(*depth, 4.0);
+            assert_eq!(layer, &Layer::SeaBed.to_string());
+            assert_eq!(time, "2024-05-12T06:30:00.000Z");
+
+            let tables: Vec<String> = sqlx::query_scalar("SELECT table_name FROM gpkg_contents")
+                .fetch_all(&mut connection)
+                .await
+                .unwrap();
+            assert_eq!(tables, vec![String::from("readings")]);
+            connection.close().await.unwrap();
+        });
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn route_is_written_with_an_envelope() {
+        let dir = temp_dir("geopackage_route");
+        let path = dir.join("export.gpkg");
+        export_geopackage(path.clone(), data(), Some(route())).unwrap();
+
+        block_on(async {
+            let mut connection = open(&path).await;
+            let (blob, waypoints): (Vec<u8>, i64) =
+                sqlx::query_as("SELECT geom, waypoints FROM route")
+                    .fetch_one(&mut connection)
+                    .await
+                    .unwrap();
+            assert_eq!(waypoints, 3);
+            // Little-endian with an [min_x, max_x, min_y, max_y] envelope
+            assert_eq!(blob[..4], [b'G', b'P', 0, 0b11]);
+            let envelope: Vec<f64> = blob[8..40]
+                .chunks(8)
+                .map(|b| f64::from_le_bytes(b.try_into().unwrap()))
+                .collect();
+            assert_eq!(envelope, vec![101.5, 101.8, 3.05, 3.2]);
+            assert_eq!(blob[40], 1);
+            assert_eq!(blob[41..45], WKB_LINE_STRING.to_le_bytes());
+            assert_eq!(blob[45..49], 3u32.to_le_bytes());
+            assert_eq!(blob.len(), 49 + 3 * 16);
+
+            let points: Vec<(Vec<u8>, i64)> =
+                sqlx::query_as("SELECT geom, point_index FROM collection_points ORDER BY fid")
+                    .fetch_all(&mut connection)
+                    .await
+                    .unwrap();
+            let points: Vec<_> = points
+                .iter()
+                .map(|(blob, i)| (*i, blob_point(blob)))
+                .collect();
+            assert_eq!(points, vec![(0, (101.6, 3.2)), (1, (101.8, 3.05))]);
+            connection.close().await.unwrap();
+        });
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn existing_file_is_replaced() {
+        let dir = temp_dir("geopackage_replace");
+        let path = dir.join("export.gpkg");
+        std::fs::write(&path, "not a GeoPackage").unwrap();
+
+        export_geopackage(path.clone(), data(), Some(route())).unwrap();
+        export_geopackage(path.clone(), data(), None).unwrap();
+
+        block_on(async {
+            let mut connection = open(&path).await;
+            let tables: Vec<String> = sqlx::query_scalar("SELECT table_name FROM gpkg_contents")
+                .fetch_all(&mut connection)
+                .await
+                .unwrap();
+            assert_eq!(tables, vec![String::from("readings")]);
+            connection.close().await.unwrap();
+        });
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn empty_line_string_is_flagged_empty() {
+        let blob = line_string_blob(&[]);
+
+        assert_eq!(blob[..4], [b'G', b'P', 0, 0b0001_0001]);
+        assert_eq!(blob.len(), 8 + 9);
+    }
+
+    #[test]
+    fn bbox_skips_non_finite_coordinates() {
+        let coords = [
+            Coord { x: 1.0, y: 2.0 },
+            Coord {
+                x: f64::NAN,
+                y: 9.0,
+            },
+            Coord { x: -1.0, y: 5.0 },
+        ];
+
+        assert_eq!(bbox(coords.into_iter()), Some([-1.0, 2.0, 1.0, 5.0]));
+        assert_eq!(bbox(std::iter::empty()), None);
+    }
+}
@@ -0,0 +1,242 @@
+//! Negotiation of the protocol version with the boat firmware when connecting.
+//!
+//! Each end sends a hello with its protocol version and capabilities. The connection is refused
+//! if the major versions differ, as the messages are not compatible. The offset of the clock of
+//! the boat is then measured if the boat supports it, see [`clock`](crate::clock).
+
+use std::{sync::Mutex, time::Duration};
+
+use semver::Version;
+use serde::Serialize;
+use tauri::{AppHandle, Manager};
+
+use crate::{
+    clock,
+    communication::{Link, Priority, Reply, RequestError},
+    messages::{packet_type, Hello},
+    protocol::{self, PROTOCOL_VERSION},
+};
+
+/// The time to wait for the hello of the boat.
+const HELLO_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// The number of times the hello is sent again when the boat doesn't reply.
+const HELLO_RETRIES: u32 = 2;
+
+/// The packet types handled by the desktop application.
+const SUPPORTED_TYPES: [i32; 12] = [
+    packet_type::PATH_CHUNK,
+    packet_type::ACK,
+    packet_type::NACK,
+    packet_type::READING,
+    packet_type::POSITION,
+    packet_type::CONTROL,
+    packet_type::PING,
+    packet_type::PONG,
+    packet_type::HELLO,
+    packet_type::HEALTH,
+    packet_type::PROGRESS,
+    packet_type::TIME_SYNC,
+];
+
+/// Information on the boat negotiated when connecting.
+#[derive(Debug, Serialize, Clone, PartialEq, Eq)]
+pub struct ConnectionInfo {
+    /// The version of the communication protocol of the boat.
+    protocol_version: String,
+    /// The version of the firmware of the boat.
+    firmware_version: String,
+    /// The revision of the hardware of the boat.
+    hardware_revision: String,
+    /// The packet types supported by the boat.
+    supported_types: Vec<i32>,
+    /// Whether the frames have the CRC trailer.
+    crc: bool,
+    /// How far the clock of the boat is ahead in milliseconds, `None` until it is measured.
+    clock_offset_ms: Option<i64>,
+}
+
+impl From<Hello> for ConnectionInfo {
+    fn from(value: Hello) -> Self {
+        Self {
+            crc: protocol::supports_crc(&value.protocol_version),
+            protocol_version: value.protocol_version,
+            firmware_version: value.software_version,
+            hardware_revision: value.hardware_revision,
+            supported_types: value.supported_types,
+            clock_offset_ms: None,
+        }
+    }
+}
+
+/// Payload of the `version-mismatch` event.
+#[derive(Debug, Serialize, Clone)]
+struct VersionMismatchPayload {
+    /// The protocol version of the desktop application.
+    desktop_version: String,
+    /// The protocol version of the boat.
+    boat_version: String,
+}
+
+/// Checks if the protocol version of the boat is compatible with the desktop application.
+///
+/// Versions are compatible if their major versions are the same.
+fn is_compatible(boat_version: &str) -> Result<bool, String> {
+    let boat = Version::parse(boat_version)
+        .map_err(|_| format!("Invalid Protocol Version: {boat_version}"))?;
+    // The version of the desktop application is a constant so it is always valid
+    let desktop = Version::parse(PROTOCOL_VERSION).unwrap();
+    Ok(boat.major == desktop.major)
+}
+
+/// Exchanges hellos with the boat, storing the information negotiated.
+///
+/// The connection is closed and a `version-mismatch` event is emitted if the protocol versions
+/// are not compatible. Firmware without the handshake is used without the CRC trailer.
+///
+/// The offset of the clock of the boat is measured once the versions are compatible, if the
+/// boat supports time syncs.
+pub fn negotiate(app_handle: &AppHandle, link: &Link) {
+    let state = app_handle.state::<ConnectionInfoState>();
+    state.reset();
+    let hello = Hello {
+        sequence: link.next_sequence(),
+        protocol_version: PROTOCOL_VERSION.to_string(),
+        software_version: app_handle.package_info().version.to_string(),
+        hardware_revision: String::new(),
+        supported_types: SUPPORTED_TYPES.to_vec(),
+    };
+    let reply = link.exchange(
+        packet_type::HELLO,
+        &hello,
+        hello.sequence,
+        Priority::Urgent,
+        HELLO_TIMEOUT,
+        HELLO_RETRIES,
+    );
+    let boat = match reply {
+        Ok(Reply::Hello(boat)) => boat,
+        Ok(_) | Err(RequestError::Rejected(_)) | Err(RequestError::Timeout { .. }) => {
+            log::warn!("Boat on {} did not Reply to Hello", link.port_name());
+            return;
+        }
+        Err(RequestError::NotConnected) => return,
+    };
+
+    log::info!(
+        "Boat on {} uses Protocol Version {}",
+        link.port_name(),
+        boat.protocol_version
+    );
+    match is_compatible(&boat.protocol_version) {
+        Ok(true) => {
+            let info = ConnectionInfo::from(boat);
+            link.set_crc(info.crc);
+            let time_sync = info.supported_types.contains(&packet_type::TIME_SYNC);
+            *state.info.lock().unwrap() = Some(info);
+            if time_sync {
+                clock::synchronize(app_handle, link);
+            }
+        }
+        Ok(false) => {
+            log::error!(
+                "Protocol Version {} of the Boat does not Match {}",
+                boat.protocol_version,
+                PROTOCOL_VERSION
+            );
+            let payload = VersionMismatchPayload {
+                desktop_version: PROTOCOL_VERSION.to_string(),
+                boat_version: boat.protocol_version,
+            };
+            if let Err(e) = app_handle.emit_all("version-mismatch", payload) {
+                log::warn!("Unable to emit version-mismatch event: {}", e);
+            }
+            link.shutdown();
+        }
+        Err(e) => {
+            log::error!("Refusing Connection to the Boat: {}", e);
+            link.shutdown();
+        }
+    }
+}
+
+/// The information negotiated with the boat on the current connection.
+#[derive(Debug, Default)]
+pub struct ConnectionInfoState {
+    info: Mutex<Option<ConnectionInfo>>,
+}
+
+impl ConnectionInfoState {
+    /// Removes the information negotiated.
+    pub fn reset(&self) {
+        *self.info.lock().unwrap() = None;
+    }
+
+    /// Stores the offset of the clock of the boat measured on the current connection.
+    pub fn set_clock_offset(&self, offset_ms: i64) {
+        if let Some(info) = self.info.lock().unwrap().as_mut() {
+            info.clock_offset_ms = Some(offset_ms);
+        }
+    }
+
+    /// Gets how far the clock of the boat is ahead in milliseconds, 0 if it isn't measured.
+    pub fn clock_offset_ms(&self) -> i64 {
+        self.info
+            .lock()
+            .unwrap()
+            .as_ref()
+            .and_then(|info| info.clock_offset_ms)
+            .unwrap_or(0)
+    }
+}
+
+/// Get the information negotiated with the boat.
+///
+/// Returns `None` if the boat is not connected or didn't reply to the handshake.
+#[tauri::command]
+pub fn connection_info(state: tauri::State<ConnectionInfoState>) -> Option<ConnectionInfo> {
+    state.info.lock().unwrap().clone()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn same_major_version_is_compatible() {
+        assert_eq!(is_compatible("0.1.0"), Ok(true));
+        assert_eq!(is_compatible("0.4.2"), Ok(true));
+        assert_eq!(is_compatible("1.0.0"), Ok(false));
+    }
+
+    #[test]
+    fn invalid_version_is_rejected() {
+        assert_eq!(
+            is_compatible("v1"),
+            Err(String::from("Invalid Protocol Version: v1"))
+        );
+    }
+
+    #[test]
+    fn info_uses_crc_of_boat_version() {
+        let hello = |version: &str| Hello {
+            sequence: 3,
+            protocol_version: version.to_string(),
+            software_version: String::from("2.1.0"),
+            hardware_revision: String::from("rev-c"),
+            supported_types: vec![packet_type::READING, packet_type::POSITION],
+        };
+
+        let old = ConnectionInfo::from(hello("0.1.0"));
+        let new = ConnectionInfo::from(hello("0.2.0"));
+
+        assert!(!old.crc);
+        assert!(new.crc);
+        assert_eq!(new.firmware_version, "2.1.0");
+        assert_eq!(new.hardware_revision, "rev-c");
+        assert_eq!(
+            new.supported_types,
+            vec![packet_type::READING, packet_type::POSITION]
+        );
+    }
+}
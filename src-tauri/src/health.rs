@@ -0,0 +1,337 @@
+//! Battery and system health of the boat.
+
+use std::{
+    collections::VecDeque,
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+use chrono::{DateTime, Utc};
+use prost::Message;
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager};
+
+use crate::{
+    errors::{codes, AppError},
+    messages::Health,
+    storage::write_atomically,
+    telemetry::device_time,
+};
+
+/// The number of health reports kept, 10 hours at a report per second.
+const MAX_HEALTH_HISTORY: usize = 36_000;
+
+/// The default battery voltage below which the battery is low, for a 3S LiPo battery.
+const DEFAULT_LOW_BATTERY_VOLTAGE: f64 = 10.5;
+
+/// The default temperature inside the hull above which the boat is too hot.
+const DEFAULT_HIGH_TEMPERATURE: f64 = 60.0;
+
+/// The battery and system health of the boat.
+#[derive(Debug, Serialize, Clone, Copy, PartialEq)]
+pub struct BoatHealth {
+    /// The voltage of the battery in volts.
+    battery_voltage: f64,
+    /// The current drawn from the battery in amperes.
+    current: f64,
+    /// The strength of the radio signal received by the boat in dBm.
+    signal_strength: f64,
+    /// The temperature inside the hull in degrees Celsius.
+    temperature: f64,
+    /// The time of the report.
+    time: DateTime<Utc>,
+}
+
+impl TryFrom<Health> for BoatHealth {
+    type Error = String;
+
+    fn try_from(value: Health) -> Result<Self, String> {
+        Ok(Self {
+            battery_voltage: value.battery_voltage,
+            current: value.current,
+            signal_strength: value.signal_strength,
+            temperature: value.temperature,
+            time: device_time(value.time_ms)?,
+        })
+    }
+}
+
+/// The limits of the health of the boat that alert the operator.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub struct HealthThresholds {
+    /// The battery voltage below which the battery is low.
+    low_battery_voltage: f64,
+    /// The temperature inside the hull above which the boat is too hot.
+    high_temperature: f64,
+}
+
+impl Default for HealthThresholds {
+    fn default() -> Self {
+        Self {
+            low_battery_voltage: DEFAULT_LOW_BATTERY_VOLTAGE,
+            high_temperature: DEFAULT_HIGH_TEMPERATURE,
+        }
+    }
+}
+
+/// Payload of the `boat-alert` event.
+#[derive(Debug, Serialize, Clone, Copy, PartialEq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum BoatAlert {
+    /// The battery voltage dropped below the threshold.
+    LowBattery {
+        /// The voltage of the battery.
+        voltage: f64,
+        /// The threshold crossed.
+        threshold: f64,
+    },
+    /// The temperature inside the hull rose above the threshold.
+    HighTemperature {
+        /// The temperature inside the hull.
+        temperature: f64,
+        /// The threshold crossed.
+        threshold: f64,
+    },
+}
+
+/// The health reports received and the alerts raised.
+#[derive(Debug, Default)]
+struct HealthHistory {
+    /// The reports from oldest to newest.
+    reports: VecDeque<BoatHealth>,
+    /// The limits alerting the operator.
+    thresholds: HealthThresholds,
+    /// Whether the battery is low, so the alert is only raised when it becomes low.
+    low_battery: bool,
+    /// Whether the boat is too hot, so the alert is only raised when it becomes hot.
+    high_temperature: bool,
+}
+
+impl HealthHistory {
+    /// Records a report, returning the alerts raised by it.
+    fn record(&mut self, health: BoatHealth) -> Vec<BoatAlert> {
+        self.reports.push_back(health);
+        if self.reports.len() > MAX_HEALTH_HISTORY {
+            self.reports.pop_front();
+        }
+
+        let mut alerts = vec![];
+        let low_battery = health.battery_voltage < self.thresholds.low_battery_voltage;
+        if low_battery && !self.low_battery {
+            alerts.push(BoatAlert::LowBattery {
+                voltage: health.battery_voltage,
+                threshold: self.thresholds.low_battery_voltage,
+            });
+        }
+        self.low_battery = low_battery;
+
+        let high_temperature = health.temperature > self.thresholds.high_temperature;
+        if high_temperature && !self.high_temperature {
+            alerts.push(BoatAlert::HighTemperature {
+                temperature: health.temperature,
+                threshold: self.thresholds.high_temperature,
+            });
+        }
+        self.high_temperature = high_temperature;
+        alerts
+    }
+}
+
+/// The health of the boat received.
+#[derive(Debug, Default)]
+pub struct HealthState {
+    history: Mutex<HealthHistory>,
+}
+
+/// Handles a health message received from the boat.
+///
+/// The report is added to the history and emitted on `boat-health`. A `boat-alert` event is
+/// emitted when the battery becomes low or the boat becomes too hot.
+pub fn handle_health(app_handle: &AppHandle, data: &[u8]) {
+    let health = match Health::decode(data)
+        .map_err(|e| e.to_string())
+        .and_then(BoatHealth::try_from)
+    {
+        Ok(v) => v,
+        Err(e) => {
+            log::warn!("Received an Invalid Health Report: {}", e);
+            return;
+        }
+    };
+
+    let alerts = app_handle
+        .state::<HealthState>()
+        .history
+        .lock()
+        .unwrap()
+        .record(health);
+    if let Err(e) = app_handle.emit_all("boat-health", health) {
+        log::warn!("Unable to emit boat-health event: {}", e);
+    }
+    for alert in alerts {
+        log::warn!("Boat Alert: {:?}", alert);
+        if let Err(e) = app_handle.emit_all("boat-alert", alert) {
+            log::warn!("Unable to emit boat-alert event: {}", e);
+        }
+    }
+}
+
+/// Get the latest health of the boat.
+#[tauri::command]
+pub fn boat_health(state: tauri::State<HealthState>) -> Option<BoatHealth> {
+    state.history.lock().unwrap().reports.back().copied()
+}
+
+/// Get the health reports of the boat, from oldest to newest.
+///
+/// Only the reports after `since` are returned if given.
+#[tauri::command]
+pub fn boat_health_history(
+    state: tauri::State<HealthState>,
+    since: Option<DateTime<Utc>>,
+) -> Vec<BoatHealth> {
+    let history = state.history.lock().unwrap();
+    history
+        .reports
+        .iter()
+        .filter(|health| since.map_or(true, |since| health.time > since))
+        .copied()
+        .collect()
+}
+
+/// Set the battery voltage and temperature that raise a `boat-alert` event.
+#[tauri::command]
+pub fn set_health_thresholds(
+    state: tauri::State<HealthState>,
+    thresholds: HealthThresholds,
+) -> Result<(), AppError> {
+    if !(thresholds.low_battery_voltage.is_finite() && thresholds.high_temperature.is_finite()) {
+        return Err(AppError::new(
+            codes::HEALTH_INVALID_THRESHOLDS,
+            "Invalid Health Thresholds: Must be Finite",
+        ));
+    }
+    log::debug!("Setting Health Thresholds: {:?}", thresholds);
+    state.history.lock().unwrap().thresholds = thresholds;
+    Ok(())
+}
+
+/// Writes health reports to a CSV file with a header of the fields.
+fn write_health_csv(path: &Path, reports: &[BoatHealth]) -> Result<(), AppError> {
+    write_atomically(path, |file| {
+        let mut writer = csv::Writer::from_writer(file);
+        for health in reports {
+            writer.serialize(health)?;
+        }
+        writer.flush()
+    })
+}
+
+/// Export the health reports of the boat in CSV format to the file system.
+#[tauri::command]
+pub fn export_health_csv(state: tauri::State<HealthState>, path: PathBuf) -> Result<(), AppError> {
+    log::debug!("Exporting Health to: {}", path.display());
+    let reports: Vec<_> = state
+        .history
+        .lock()
+        .unwrap()
+        .reports
+        .iter()
+        .copied()
+        .collect();
+    write_health_csv(&path, &reports)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use chrono::TimeZone;
+
+    use super::*;
+    use crate::storage::tests::temp_dir;
+
+    /// Creates a report `seconds` after the first one.
+    fn health(seconds: i64, battery_voltage: f64, temperature: f64) -> BoatHealth {
+        BoatHealth {
+            battery_voltage,
+            current: 2.5,
+            signal_strength: -70.0,
+            temperature,
+            time: Utc.timestamp_opt(1_717_207_200 + seconds, 0).unwrap(),
+        }
+    }
+
+    #[test]
+    fn alert_is_raised_when_battery_becomes_low() {
+        let mut history = HealthHistory::default();
+
+        assert!(history.record(health(0, 11.1, 35.0)).is_empty());
+        assert_eq!(
+            history.record(health(1, 10.4, 35.0)),
+            vec![BoatAlert::LowBattery {
+                voltage: 10.4,
+                threshold: DEFAULT_LOW_BATTERY_VOLTAGE
+            }]
+        );
+        // Not raised again while the battery stays low
+        assert!(history.record(health(2, 10.3, 35.0)).is_empty());
+        // Raised again after the battery recovers, e.g. when it is changed
+        assert!(history.record(health(3, 12.6, 35.0)).is_empty());
+        assert_eq!(history.record(health(4, 10.0, 35.0)).len(), 1);
+    }
+
+    #[test]
+    fn alert_is_raised_when_boat_becomes_hot() {
+        let mut history = HealthHistory {
+            thresholds: HealthThresholds {
+                low_battery_voltage: 9.0,
+                high_temperature: 50.0,
+            },
+            ..HealthHistory::default()
+        };
+
+        assert!(history.record(health(0, 10.0, 50.0)).is_empty());
+        assert_eq!(
+            history.record(health(1, 8.5, 51.5)),
+            vec![
+                BoatAlert::LowBattery {
+                    voltage: 8.5,
+                    threshold: 9.0
+                },
+                BoatAlert::HighTemperature {
+                    temperature: 51.5,
+                    threshold: 50.0
+                }
+            ]
+        );
+    }
+
+    #[test]
+    fn history_keeps_latest_reports() {
+        let mut history = HealthHistory::default();
+
+        for i in 0..MAX_HEALTH_HISTORY as i64 + 5 {
+            history.record(health(i, 11.1, 35.0));
+        }
+
+        assert_eq!(history.reports.len(), MAX_HEALTH_HISTORY);
+        assert_eq!(history.reports.front(), Some(&health(5, 11.1, 35.0)));
+    }
+
+    #[test]
+    fn health_csv_has_header_and_reports() {
+        let dir = temp_dir("health-csv");
+        let path = dir.join("health.csv");
+
+        write_health_csv(&path, &[health(0, 11.1, 35.0), health(1, 10.9, 36.5)]).unwrap();
+
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "battery_voltage,current,signal_strength,temperature,time\n\
+             11.1,2.5,-70.0,35.0,2024-06-01T02:00:00Z\n\
+             10.9,2.5,-70.0,36.5,2024-06-01T02:00:01Z\n"
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
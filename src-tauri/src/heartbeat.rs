@@ -0,0 +1,308 @@
+//! Heartbeats monitoring the quality of the link to the boat.
+
+use std::{
+    collections::VecDeque,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use serde::Serialize;
+use tauri::{AppHandle, Manager};
+
+use crate::{
+    communication::{Link, Priority, RequestError},
+    errors::{codes, AppError},
+    messages::{packet_type, Ping},
+};
+
+/// The default time between heartbeats.
+const DEFAULT_INTERVAL: Duration = Duration::from_secs(2);
+
+/// The number of heartbeats the packet loss is calculated over.
+const LOSS_WINDOW: usize = 20;
+
+/// The number of consecutive heartbeats missed before the link is lost.
+const LOST_AFTER_MISSED: u32 = 3;
+
+/// The packet loss in percent above which the link is degraded.
+const DEGRADED_LOSS: f64 = 10.0;
+
+/// The round-trip time above which the link is degraded.
+const DEGRADED_RTT: Duration = Duration::from_secs(1);
+
+/// The time between checks if the heartbeat thread should stop.
+const STOP_CHECK_INTERVAL: Duration = Duration::from_millis(100);
+
+/// The quality of the link to the boat.
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum LinkQuality {
+    /// The boat replies to heartbeats quickly.
+    Good,
+    /// The boat replies to heartbeats slowly or some heartbeats are missed.
+    Degraded,
+    /// The boat hasn't replied to the last few heartbeats.
+    Lost,
+}
+
+/// Payload of the `link-status` event.
+#[derive(Debug, Serialize, Clone, Copy, PartialEq)]
+pub struct LinkStatus {
+    /// The round-trip time of the last heartbeat replied to in milliseconds.
+    rtt_ms: Option<f64>,
+    /// The percentage of the recent heartbeats missed.
+    packet_loss: f64,
+    /// The number of consecutive heartbeats missed.
+    missed: u32,
+    /// The quality of the link.
+    quality: LinkQuality,
+}
+
+/// The results of the recent heartbeats.
+#[derive(Debug)]
+struct Heartbeats {
+    /// The round-trip times of the recent heartbeats, `None` if missed.
+    results: VecDeque<Option<Duration>>,
+    /// The round-trip time of the last heartbeat replied to.
+    last_rtt: Option<Duration>,
+    /// The number of consecutive heartbeats missed.
+    missed: u32,
+    /// The time between heartbeats.
+    interval: Duration,
+}
+
+impl Default for Heartbeats {
+    fn default() -> Self {
+        Self {
+            results: VecDeque::new(),
+            last_rtt: None,
+            missed: 0,
+            interval: DEFAULT_INTERVAL,
+        }
+    }
+}
+
+impl Heartbeats {
+    /// Records the result of a heartbeat.
+    fn record(&mut self, rtt: Option<Duration>) {
+        self.results.push_back(rtt);
+        if self.results.len() > LOSS_WINDOW {
+            self.results.pop_front();
+        }
+        match rtt {
+            Some(rtt) => {
+                self.last_rtt = Some(rtt);
+                self.missed = 0;
+            }
+            None => self.missed += 1,
+        }
+    }
+
+    /// Gets the status of the link from the recent heartbeats.
+    fn status(&self) -> LinkStatus {
+        let lost = self.results.iter().filter(|r| r.is_none()).count();
+        let packet_loss = if self.results.is_empty() {
+            0.0
+        } else {
+            lost as f64 * 100.0 / self.results.len() as f64
+        };
+
+        let quality = if self.missed >= LOST_AFTER_MISSED {
+            LinkQuality::Lost
+        } else if packet_loss > DEGRADED_LOSS || self.last_rtt.map_or(false, |r| r > DEGRADED_RTT) {
+            LinkQuality::Degraded
+        } else {
+            LinkQuality::Good
+        };
+
+        LinkStatus {
+            rtt_ms: self.last_rtt.map(|r| r.as_secs_f64() * 1000.0),
+            packet_loss,
+            missed: self.missed,
+            quality,
+        }
+    }
+}
+
+/// The quality of the link to the boat on the current connection.
+#[derive(Debug, Default)]
+pub struct LinkStats {
+    heartbeats: Mutex<Heartbeats>,
+}
+
+impl LinkStats {
+    /// Removes the results of the heartbeats, keeping the interval.
+    pub fn reset(&self) {
+        let mut heartbeats = self.heartbeats.lock().unwrap();
+        let interval = heartbeats.interval;
+        *heartbeats = Heartbeats {
+            interval,
+            ..Default::default()
+        };
+    }
+}
+
+/// Sends heartbeats to the boat until the link is closed, emitting the status of the link.
+///
+/// A `link-lost` event is emitted when the link becomes lost.
+pub fn heartbeat_loop(app_handle: &AppHandle, link: &Link) {
+    let stats = app_handle.state::<LinkStats>();
+    while link.is_open() {
+        let start = Instant::now();
+        let interval = stats.heartbeats.lock().unwrap().interval;
+        let ping = Ping {
+            sequence: link.next_sequence(),
+        };
+        let rtt = match link.request(
+            packet_type::PING,
+            &ping,
+            ping.sequence,
+            Priority::Normal,
+            interval,
+            0,
+        ) {
+            Ok(_) => Some(start.elapsed()),
+            Err(RequestError::Timeout { .. }) | Err(RequestError::Rejected(_)) => None,
+            Err(RequestError::NotConnected) => break,
+        };
+
+        let (previous, status) = {
+            let mut heartbeats = stats.heartbeats.lock().unwrap();
+            let previous = heartbeats.status().quality;
+            heartbeats.record(rtt);
+            (previous, heartbeats.status())
+        };
+        if let Err(e) = app_handle.emit_all("link-status", status) {
+            log::warn!("Unable to emit link-status event: {}", e);
+        }
+        if status.quality == LinkQuality::Lost && previous != LinkQuality::Lost {
+            log::warn!("Lost Link to the Boat on {}", link.port_name());
+            if let Err(e) = app_handle.emit_all("link-lost", status) {
+                log::warn!("Unable to emit link-lost event: {}", e);
+            }
+        }
+
+        while link.is_open() && start.elapsed() < interval {
+            std::thread::sleep(STOP_CHECK_INTERVAL);
+        }
+    }
+}
+
+/// Get the quality of the link to the boat.
+#[tauri::command]
+pub fn link_stats(state: tauri::State<LinkStats>) -> LinkStatus {
+    state.heartbeats.lock().unwrap().status()
+}
+
+/// Set the time between heartbeats sent to the boat in seconds.
+#[tauri::command]
+pub fn set_heartbeat_interval(
+    state: tauri::State<LinkStats>,
+    seconds: f64,
+) -> Result<(), AppError> {
+    if !(seconds.is_finite() && seconds > 0.0) {
+        return Err(AppError::new(
+            codes::HEARTBEAT_INVALID_INTERVAL,
+            format!("Invalid Heartbeat Interval: {seconds} must be greater than 0"),
+        )
+        .with("seconds", seconds));
+    }
+    state.heartbeats.lock().unwrap().interval = Duration::from_secs_f64(seconds);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FAST: Option<Duration> = Some(Duration::from_millis(80));
+
+    #[test]
+    fn no_heartbeats_is_good() {
+        let status = Heartbeats::default().status();
+
+        assert_eq!(status.rtt_ms, None);
+        assert_eq!(status.packet_loss, 0.0);
+        assert_eq!(status.quality, LinkQuality::Good);
+    }
+
+    #[test]
+    fn fast_replies_are_good() {
+        let mut heartbeats = Heartbeats::default();
+        for _ in 0..5 {
+            heartbeats.record(FAST);
+        }
+
+        let status = heartbeats.status();
+        assert_eq!(status.rtt_ms, Some(80.0));
+        assert_eq!(status.quality, LinkQuality::Good);
+    }
+
+    #[test]
+    fn slow_reply_is_degraded() {
+        let mut heartbeats = Heartbeats::default();
+        heartbeats.record(Some(Duration::from_millis(1500)));
+
+        assert_eq!(heartbeats.status().quality, LinkQuality::Degraded);
+    }
+
+    #[test]
+    fn packet_loss_is_over_window() {
+        let mut heartbeats = Heartbeats::default();
+        heartbeats.record(None);
+        heartbeats.record(None);
+        for _ in 0..8 {
+            heartbeats.record(FAST);
+        }
+
+        let status = heartbeats.status();
+        assert_eq!(status.packet_loss, 20.0);
+        assert_eq!(status.missed, 0);
+        assert_eq!(status.quality, LinkQuality::Degraded);
+
+        // The missed heartbeats leave the window
+        for _ in 0..LOSS_WINDOW {
+            heartbeats.record(FAST);
+        }
+        assert_eq!(heartbeats.status().packet_loss, 0.0);
+        assert_eq!(heartbeats.status().quality, LinkQuality::Good);
+    }
+
+    #[test]
+    fn consecutive_missed_heartbeats_lose_link() {
+        let mut heartbeats = Heartbeats::default();
+        for _ in 0..LOSS_WINDOW {
+            heartbeats.record(FAST);
+        }
+
+        for missed in 1..LOST_AFTER_MISSED {
+            heartbeats.record(None);
+            assert_eq!(heartbeats.status().missed, missed);
+            assert_ne!(heartbeats.status().quality, LinkQuality::Lost);
+        }
+        heartbeats.record(None);
+        assert_eq!(heartbeats.status().quality, LinkQuality::Lost);
+
+        // A reply restores the link
+        heartbeats.record(FAST);
+        assert_ne!(heartbeats.status().quality, LinkQuality::Lost);
+        assert_eq!(heartbeats.status().missed, 0);
+    }
+
+    #[test]
+    fn reset_keeps_interval() {
+        let stats = LinkStats::default();
+        {
+            let mut heartbeats = stats.heartbeats.lock().unwrap();
+            heartbeats.interval = Duration::from_secs(5);
+            heartbeats.record(None);
+        }
+
+        stats.reset();
+
+        let heartbeats = stats.heartbeats.lock().unwrap();
+        assert_eq!(heartbeats.interval, Duration::from_secs(5));
+        assert!(heartbeats.results.is_empty());
+        assert_eq!(heartbeats.missed, 0);
+    }
+}
@@ -0,0 +1,492 @@
+//! Temperature surfaces of boat data, binned into a regular grid.
+
+use geo_types::{Coord, LineString, Polygon};
+use geojson::{Feature, FeatureCollection, GeoJson, Geometry, Value};
+use serde_json::{json, Map};
+
+use crate::{
+    data::{BoatData, Layer},
+    errors::{codes, AppError},
+    geometry::Projection,
+};
+
+/// The largest number of cells in a grid.
+const MAX_GRID_CELLS: usize = 1_000_000;
+
+/// The largest number of neighbouring cells an empty cell is interpolated from.
+const MAX_IDW_NEIGHBOURS: usize = 10_000;
+
+/// Creates the error of a cell size that is not a positive number.
+pub fn invalid_cell_size(cell_size: f64) -> AppError {
+    AppError::new(
+        codes::GRID_INVALID_CELL_SIZE,
+        format!("Invalid Cell Size: {cell_size}"),
+    )
+    .with("cell_size", cell_size)
+}
+
+/// A regular grid of the mean temperatures of boat data, in meters on a local projection.
+pub struct Grid {
+    projection: Projection,
+    /// The projected coordinate of the corner of the first cell.
+    origin: [f64; 2],
+    /// The width and height of a cell in meters.
+    cell_size: f64,
+    /// The number of cells along the longitude.
+    columns: usize,
+    /// The number of cells along the latitude.
+    rows: usize,
+    /// The temperature of each cell by row, `None` for cells without data.
+    values: Vec<Option<f64>>,
+    /// Whether each cell is interpolated from its neighbours.
+    interpolated: Vec<bool>,
+}
+
+impl Grid {
+    /// Bins the readings of `layer` into cells of `cell_size` meters, averaging the temperatures
+    /// in each cell.
+    ///
+    /// Returns `None` if there are no readings of the layer. An error is returned if the grid
+    /// would have more than 1,000,000 cells.
+    pub fn new(data: &BoatData, layer: Layer, cell_size: f64) -> Result<Option<Self>, AppError> {
+        let readings = readings(data, &layer);
+        let coords: Vec<_> = readings.iter().map(|(coord, _)| *coord).collect();
+        Self::bin(&readings, Projection::new(&coords), cell_size, false)
+    }
+
+    /// Bins the readings of `layer` like `new`, on a given projection with the cells aligned to
+    /// multiples of `cell_size` meters from the origin of the projection.
+    ///
+    /// Grids of different data on the same projection have the same cells, so they can be
+    /// compared cell by cell.
+    pub fn aligned(
+        data: &BoatData,
+        layer: &Layer,
+        cell_size: f64,
+        projection: Projection,
+    ) -> Result<Option<Self>, AppError> {
+        Self::bin(&readings(data, layer), projection, cell_size, true)
+    }
+
+    /// Bins readings into cells of `cell_size` meters on `projection`.
+    ///
+    /// The first cell starts at the smallest projected coordinate, or at the multiple of
+    /// `cell_size` below it if `aligned`.
+    fn bin(
+        readings: &[(Coord<f64>, f64)],
+        projection: Projection,
+        cell_size: f64,
+        aligned: bool,
+    ) -> Result<Option<Self>, AppError> {
+        if !cell_size.is_finite() || cell_size <= 0.0 {
+            return Err(invalid_cell_size(cell_size));
+        }
+        if readings.is_empty() {
+            return Ok(None);
+        }
+
+        let points: Vec<_> = readings
+            .iter()
+            .map(|(coord, _)| projection.forward(*coord))
+            .collect();
+        let mut min_x = points.iter().map(|p| p[0]).fold(f64::INFINITY, f64::min);
+        let mut min_y = points.iter().map(|p| p[1]).fold(f64::INFINITY, f64::min);
+        if aligned {
+            min_x = (min_x / cell_size).floor() * cell_size;
+            min_y = (min_y / cell_size).floor() * cell_size;
+        }
+        let max_x = points
+            .iter()
+            .map(|p| p[0])
+            .fold(f64::NEG_INFINITY, f64::max);
+        let max_y = points
+            .iter()
+            .map(|p| p[1])
+            .fold(f64::NEG_INFINITY, f64::max);
+
+        let columns = ((max_x - min_x) / cell_size).floor() + 1.0;
+        let rows = ((max_y - min_y) / cell_size).floor() + 1.0;
+        if columns * rows > MAX_GRID_CELLS as f64 {
+            return Err(AppError::new(
+                codes::GRID_TOO_MANY_CELLS,
+                format!(
+                    "Too Many Grid Cells: Cell Size {cell_size}m would create {} cells, \
+                    the maximum is {MAX_GRID_CELLS}",
+                    columns * rows
+                ),
+            )
+            .with("cells", columns * rows)
+            .with("max", MAX_GRID_CELLS));
+        }
+        let (columns, rows) = (columns as usize, rows as usize);
+
+        let mut sums = vec![(0.0, 0); columns * rows];
+        for (point, (_, temperature)) in points.iter().zip(readings) {
+            let column = (((point[0] - min_x) / cell_size) as usize).min(columns - 1);
+            let row = (((point[1] - min_y) / cell_size) as usize).min(rows - 1);
+            let (sum, count) = &mut sums[row * columns + column];
+            *sum += temperature;
+            *count += 1;
+        }
+
+        Ok(Some(Self {
+            projection,
+            origin: [min_x, min_y],
+            cell_size,
+            columns,
+            rows,
+            values: sums
+                .into_iter()
+                .map(|(sum, count)| (count > 0).then(|| sum / count as f64))
+                .collect(),
+            interpolated: vec![false; columns * rows],
+        }))
+    }
+
+    /// Fills the empty cells by inverse distance weighting of the cells with data within
+    /// `radius` meters.
+    ///
+    /// Cells without data within the radius are left empty.
+    pub fn interpolate(&mut self, radius: f64) -> Result<(), AppError> {
+        let invalid_radius = |message: String| {
+            AppError::new(codes::GRID_INVALID_RADIUS, message).with("radius", radius)
+        };
+        if !radius.is_finite() || radius < 0.0 {
+            return Err(invalid_radius(format!(
+                "Invalid Interpolation Radius: {radius}"
+            )));
+        }
+        let reach = (radius / self.cell_size).floor();
+        if (2.0 * reach + 1.0).powi(2) > MAX_IDW_NEIGHBOURS as f64 {
+            return Err(invalid_radius(format!(
+                "Invalid Interpolation Radius: {radius}m covers more than {MAX_IDW_NEIGHBOURS} cells"
+            )));
+        }
+        let reach = reach as isize;
+
+        let mut values = self.values.clone();
+        for row in 0..self.rows {
+            for column in 0..self.columns {
+                let index = row * self.columns + column;
+                if self.values[index].is_some() {
+                    continue;
+                }
+
+                let (mut weighted, mut weights) = (0.0, 0.0);
+                for dy in -reach..=reach {
+                    for dx in -reach..=reach {
+                        let (r, c) = (row as isize + dy, column as isize + dx);
+                        if r < 0 || c < 0 || r >= self.rows as isize || c >= self.columns as isize {
+                            continue;
+                        }
+                        let distance = ((dx * dx + dy * dy) as f64).sqrt() * self.cell_size;
+                        let value = self.values[r as usize * self.columns + c as usize]
+                            .filter(|_| distance <= radius);
+                        if let Some(value) = value {
+                            let weight = 1.0 / distance.powi(2);
+                            weighted += value * weight;
+                            weights += weight;
+                        }
+                    }
+                }
+                if weights > 0.0 {
+                    values[index] = Some(weighted / weights);
+                    self.interpolated[index] = true;
+                }
+            }
+        }
+        self.values = values;
+        Ok(())
+    }
+
+    /// Gets the number of columns and rows of the grid.
+    pub fn size(&self) -> (usize, usize) {
+        (self.columns, self.rows)
+    }
+
+    /// Gets the temperature of a cell.
+    pub fn value(&self, column: usize, row: usize) -> Option<f64> {
+        self.values[row * self.columns + column]
+    }
+
+    /// Gets the position of a cell counted in cells from the origin of the projection.
+    ///
+    /// The positions of aligned grids on the same projection refer to the same cells.
+    pub fn cell_position(&self, column: usize, row: usize) -> (i64, i64) {
+        (
+            (self.origin[0] / self.cell_size).round() as i64 + column as i64,
+            (self.origin[1] / self.cell_size).round() as i64 + row as i64,
+        )
+    }
+
+    /// Converts a position in cells, e.g. `(0.5, 0.5)` for the center of the first cell, to a
+    /// coordinate.
+    pub fn coord(&self, column: f64, row: f64) -> Coord<f64> {
+        self.projection.inverse([
+            self.origin[0] + column * self.cell_size,
+            self.origin[1] + row * self.cell_size,
+        ])
+    }
+
+    /// Converts the cells with a temperature to polygon features with a `temperature` property.
+    pub fn to_features(&self) -> Vec<Feature> {
+        let mut features = vec![];
+        for row in 0..self.rows {
+            for column in 0..self.columns {
+                let temperature = match self.value(column, row) {
+                    Some(v) => v,
+                    None => continue,
+                };
+                let (c, r) = (column as f64, row as f64);
+                let ring = LineString::from(vec![
+                    self.coord(c, r),
+                    self.coord(c + 1.0, r),
+                    self.coord(c + 1.0, r + 1.0),
+                    self.coord(c, r + 1.0),
+                    self.coord(c, r),
+                ]);
+                let polygon = Polygon::new(ring, vec![]);
+
+                let mut properties = Map::new();
+                properties.insert(String::from("temperature"), json!(temperature));
+                properties.insert(
+                    String::from("interpolated"),
+                    json!(self.interpolated[row * self.columns + column]),
+                );
+                features.push(Feature {
+                    bbox: None,
+                    geometry: Some(Geometry::new(Value::from(&polygon))),
+                    id: None,
+                    properties: Some(properties),
+                    foreign_members: None,
+                });
+            }
+        }
+        features
+    }
+}
+
+/// Gets the coordinates and finite temperatures of the readings at `layer`.
+fn readings(data: &BoatData, layer: &Layer) -> Vec<(Coord<f64>, f64)> {
+    data.features()
+        .iter()
+        .filter(|feature| feature.layer() == *layer && feature.temperature().is_finite())
+        .map(|feature| (Coord::from(feature.geometry()), feature.temperature()))
+        .filter(|(coord, _)| coord.x.is_finite() && coord.y.is_finite())
+        .collect()
+}
+
+/// Generate a grid of the mean temperatures of boat data at `layer` for a fill layer.
+///
+/// The readings are binned into square cells of `cell_size_m` meters over their bounding box.
+/// Empty cells are interpolated from the cells with data within `idw_radius_m` meters if it is
+/// given, and cells left without data are omitted. Each cell is a polygon feature with its
+/// `temperature` and whether it is `interpolated`.
+#[tauri::command]
+pub fn generate_heatmap(
+    data: BoatData,
+    layer: Layer,
+    cell_size_m: f64,
+    idw_radius_m: Option<f64>,
+) -> Result<GeoJson, AppError> {
+    log::debug!(
+        "Generating Heatmap of {} with Cell Size {}m",
+        layer,
+        cell_size_m
+    );
+    let features = match Grid::new(&data, layer, cell_size_m)? {
+        Some(mut grid) => {
+            if let Some(radius) = idw_radius_m {
+                grid.interpolate(radius)?;
+            }
+            grid.to_features()
+        }
+        None => vec![],
+    };
+    log::info!("Generated Heatmap with {} Cells", features.len());
+    Ok(GeoJson::from(FeatureCollection {
+        bbox: None,
+        features,
+        foreign_members: None,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeZone, Utc};
+    use geo_types::Point;
+
+    use crate::data::BoatDataFeature;
+
+    use super::*;
+
+    /// The coordinate `east` meters east of the first reading.
+    fn east_of_start(east: f64) -> Coord<f64> {
+        Projection::new(&[Coord { x: 101.5, y: 3.1 }]).inverse([east, 0.0])
+    }
+
+    fn reading(temperature: f64, layer: Layer, coord: Coord<f64>) -> BoatDataFeature {
+        BoatDataFeature::new(
+            temperature,
+            0.5,
+            layer,
+            Utc.timestamp_opt(1_715_495_400, 0).unwrap(),
+            Point::from(coord),
+        )
+    }
+
+    fn data(features: Vec<BoatDataFeature>) -> BoatData {
+        BoatData::new(BoatData::default().version().to_string(), features)
+    }
+
+    fn temperature(feature: &Feature) -> f64 {
+        feature.properties.as_ref().unwrap()["temperature"]
+            .as_f64()
+            .unwrap()
+    }
+
+    #[test]
+    fn readings_in_a_cell_are_averaged() {
+        let data = data(vec![
+            reading(20.0, Layer::Surface, east_of_start(0.0)),
+            reading(30.0, Layer::Surface, east_of_start(1.0)),
+            reading(99.0, Layer::SeaBed, east_of_start(0.0)),
+            reading(f64::NAN, Layer::Surface, east_of_start(0.0)),
+        ]);
+
+        let grid = Grid::new(&data, Layer::Surface, 10.0).unwrap().unwrap();
+        assert_eq!((grid.columns, grid.rows), (1, 1));
+        assert_eq!(grid.value(0, 0), Some(25.0));
+
+        let features = grid.to_features();
+        assert_eq!(features.len(), 1);
+        assert_eq!(temperature(&features[0]), 25.0);
+        assert_eq!(
+            features[0].properties.as_ref().unwrap()["interpolated"],
+            false
+        );
+    }
+
+    #[test]
+    fn empty_cells_are_omitted() {
+        let data = data(vec![
+            reading(20.0, Layer::Surface, east_of_start(0.0)),
+            reading(30.0, Layer::Surface, east_of_start(35.0)),
+        ]);
+
+        let grid = Grid::new(&data, Layer::Surface, 10.0).unwrap().unwrap();
+        assert_eq!((grid.columns, grid.rows), (4, 1));
+        assert_eq!(grid.value(0, 0), Some(20.0));
+        assert_eq!(grid.value(1, 0), None);
+        assert_eq!(grid.value(3, 0), Some(30.0));
+        assert_eq!(grid.to_features().len(), 2);
+    }
+
+    #[test]
+    fn empty_cells_are_interpolated_from_neighbours_in_the_radius() {
+        let data = data(vec![
+            reading(20.0, Layer::Surface, east_of_start(0.0)),
+            reading(30.0, Layer::Surface, east_of_start(35.0)),
+        ]);
+
+        let mut grid = Grid::new(&data, Layer::Surface, 10.0).unwrap().unwrap();
+        grid.interpolate(20.0).unwrap();
+        // Weighted by the inverse square of the distances of 10m and 20m
+        assert!((grid.value(1, 0).unwrap() - 22.0).abs() < 1e-9);
+        assert!((grid.value(2, 0).unwrap() - 28.0).abs() < 1e-9);
+        assert!(grid.interpolated[1] && grid.interpolated[2]);
+        assert!(!grid.interpolated[0] && !grid.interpolated[3]);
+        assert_eq!(grid.to_features().len(), 4);
+
+        let mut grid = Grid::new(&data, Layer::Surface, 10.0).unwrap().unwrap();
+        grid.interpolate(5.0).unwrap();
+        assert_eq!(grid.value(1, 0), None);
+        assert_eq!(grid.to_features().len(), 2);
+    }
+
+    #[test]
+    fn cells_cover_the_readings() {
+        let grid = Grid::new(
+            &data(vec![
+                reading(20.0, Layer::Surface, east_of_start(0.0)),
+                reading(30.0, Layer::Surface, east_of_start(35.0)),
+            ]),
+            Layer::Surface,
+            10.0,
+        )
+        .unwrap()
+        .unwrap();
+
+        let first = grid.coord(0.0, 0.0);
+        let last = grid.coord(4.0, 1.0);
+        let start = east_of_start(0.0);
+        let end = east_of_start(35.0);
+        assert!((first.x - start.x).abs() < 1e-9 && (first.y - start.y).abs() < 1e-9);
+        assert!(last.x > end.x && last.y > end.y);
+    }
+
+    #[test]
+    fn too_many_cells_are_refused() {
+        let data = data(vec![
+            reading(20.0, Layer::Surface, Coord { x: 101.0, y: 3.0 }),
+            reading(30.0, Layer::Surface, Coord { x: 102.0, y: 4.0 }),
+        ]);
+
+        let error = Grid::new(&data, Layer::Surface, 1.0).err().unwrap();
+        let error = serde_json::to_value(error).unwrap();
+        assert_eq!(error["code"], json!(codes::GRID_TOO_MANY_CELLS));
+        assert_eq!(error["params"]["max"], json!(MAX_GRID_CELLS));
+        assert!(Grid::new(&data, Layer::Surface, 1000.0).is_ok());
+    }
+
+    #[test]
+    fn invalid_sizes_are_rejected() {
+        let data = data(vec![reading(20.0, Layer::Surface, east_of_start(0.0))]);
+
+        assert_eq!(
+            Grid::new(&data, Layer::Surface, 0.0).err(),
+            Some(invalid_cell_size(0.0))
+        );
+        assert!(Grid::new(&data, Layer::Surface, -1.0).is_err());
+        assert!(Grid::new(&data, Layer::Surface, f64::NAN).is_err());
+        let mut grid = Grid::new(&data, Layer::Surface, 10.0).unwrap().unwrap();
+        assert!(grid.interpolate(-1.0).is_err());
+        assert!(grid.interpolate(10_000.0).is_err());
+    }
+
+    #[test]
+    fn heatmap_without_readings_is_empty() {
+        let data = data(vec![reading(20.0, Layer::Surface, east_of_start(0.0))]);
+
+        assert!(Grid::new(&data, Layer::Middle, 10.0).unwrap().is_none());
+        match generate_heatmap(data, Layer::Middle, 10.0, Some(20.0)).unwrap() {
+            GeoJson::FeatureCollection(collection) => assert!(collection.features.is_empty()),
+            other => panic!("Expected a FeatureCollection: {other:?}"),
+        }
+    }
+
+    #[test]
+    fn aligned_grids_share_cells() {
+        let projection = Projection::at(Coord { x: 101.0, y: 3.0 });
+        let near = data(vec![reading(
+            20.0,
+            Layer::Surface,
+            projection.inverse([3.0, 3.0]),
+        )]);
+        let far = data(vec![
+            reading(21.0, Layer::Surface, projection.inverse([7.0, 3.0])),
+            reading(22.0, Layer::Surface, projection.inverse([27.0, 3.0])),
+        ]);
+
+        let near = Grid::aligned(&near, &Layer::Surface, 10.0, projection)
+            .unwrap()
+            .unwrap();
+        let far = Grid::aligned(&far, &Layer::Surface, 10.0, projection)
+            .unwrap()
+            .unwrap();
+        assert_eq!(near.cell_position(0, 0), (0, 0));
+        assert_eq!(far.cell_position(0, 0), (0, 0));
+        assert_eq!(far.cell_position(2, 0), (2, 0));
+        assert_eq!(far.value(2, 0), Some(22.0));
+    }
+}
@@ -0,0 +1,241 @@
+//! Undo and redo of the edits to the current path.
+
+use std::{collections::VecDeque, sync::Mutex};
+
+use serde::Serialize;
+use tauri::{AppHandle, Manager};
+
+use crate::{
+    errors::{codes, AppError},
+    path::{self, PathData},
+};
+
+/// The number of edits that can be undone.
+const MAX_HISTORY: usize = 100;
+
+/// The paths before and after the edits to the current path.
+#[derive(Debug, Default)]
+struct History {
+    /// The paths before each edit, from oldest to newest.
+    undo: VecDeque<PathData>,
+    /// The paths undone, from oldest to newest.
+    redo: Vec<PathData>,
+}
+
+impl History {
+    /// Adds a path to undo to, forgetting the oldest path beyond [`MAX_HISTORY`].
+    fn push_undo(&mut self, path: PathData) {
+        self.undo.push_back(path);
+        if self.undo.len() > MAX_HISTORY {
+            self.undo.pop_front();
+        }
+    }
+
+    /// Records the path before an edit, discarding the edits undone.
+    fn record(&mut self, previous: PathData) {
+        self.push_undo(previous);
+        self.redo.clear();
+    }
+
+    /// Moves the last path to undo to once it is restored, keeping `current` to redo to.
+    fn undone(&mut self, current: PathData) {
+        self.undo.pop_back();
+        self.redo.push(current);
+    }
+
+    /// Moves the last path to redo to once it is restored, keeping `current` to undo to.
+    fn redone(&mut self, current: PathData) {
+        self.redo.pop();
+        self.push_undo(current);
+    }
+
+    /// Removes every path from the history.
+    fn clear(&mut self) {
+        self.undo.clear();
+        self.redo.clear();
+    }
+}
+
+/// Payload of the `path-history` event.
+#[derive(Debug, Clone, Copy, Serialize)]
+struct HistoryPayload {
+    /// Whether there is an edit to undo.
+    can_undo: bool,
+    /// Whether there is an edit to redo.
+    can_redo: bool,
+}
+
+/// History of the edits to the current path.
+#[derive(Debug, Default)]
+pub struct PathHistory {
+    history: Mutex<History>,
+}
+
+impl PathHistory {
+    /// Records the path before an edit, discarding the edits undone.
+    pub fn record(&self, app_handle: &AppHandle, previous: PathData) {
+        let mut history = self.history.lock().unwrap();
+        history.record(previous);
+        emit_history(app_handle, &history);
+    }
+
+    /// Removes every edit from the history.
+    pub fn clear(&self, app_handle: &AppHandle) {
+        let mut history = self.history.lock().unwrap();
+        history.clear();
+        emit_history(app_handle, &history);
+    }
+}
+
+/// Notifies the windows of whether undo and redo are available.
+fn emit_history(app_handle: &AppHandle, history: &History) {
+    let payload = HistoryPayload {
+        can_undo: !history.undo.is_empty(),
+        can_redo: !history.redo.is_empty(),
+    };
+    if let Err(e) = app_handle.emit_all("path-history", payload) {
+        log::warn!("Unable to emit path-history event: {}", e);
+    }
+}
+
+/// Undo the last edit to the current path, returning the restored path.
+#[tauri::command]
+pub fn undo_path(app_handle: AppHandle) -> Result<PathData, AppError> {
+    log::debug!("Undoing Path Edit");
+    let state = app_handle.state::<PathHistory>();
+    let restored = state
+        .history
+        .lock()
+        .unwrap()
+        .undo
+        .back()
+        .cloned()
+        .ok_or_else(|| AppError::new(codes::HISTORY_NOTHING_TO_UNDO, "Nothing to Undo"))?;
+
+    let current = path::replace_path(&app_handle, restored.clone())?;
+    let mut history = state.history.lock().unwrap();
+    history.undone(current);
+    emit_history(&app_handle, &history);
+    Ok(restored)
+}
+
+/// Redo the last edit undone to the current path, returning the restored path.
+#[tauri::command]
+pub fn redo_path(app_handle: AppHandle) -> Result<PathData, AppError> {
+    log::debug!("Redoing Path Edit");
+    let state = app_handle.state::<PathHistory>();
+    let restored = state
+        .history
+        .lock()
+        .unwrap()
+        .redo
+        .last()
+        .cloned()
+        .ok_or_else(|| AppError::new(codes::HISTORY_NOTHING_TO_REDO, "Nothing to Redo"))?;
+
+    let current = path::replace_path(&app_handle, restored.clone())?;
+    let mut history = state.history.lock().unwrap();
+    history.redone(current);
+    emit_history(&app_handle, &history);
+    Ok(restored)
+}
+
+#[cfg(test)]
+mod tests {
+    use geo_types::Point;
+
+    use super::*;
+
+    /// Creates a path with `n` collection points, identifying the edit it was made by.
+    fn path(n: usize) -> PathData {
+        let mut path = PathData::default();
+        for i in 0..n {
+            path.add_collection_point(Point::new(101.5 + i as f64 * 0.001, 3.1));
+        }
+        path
+    }
+
+    /// Gets the number of collection points of the paths, identifying their edits.
+    fn edits<'a>(paths: impl IntoIterator<Item = &'a PathData>) -> Vec<usize> {
+        paths
+            .into_iter()
+            .map(|path| path.collection_points().0.len())
+            .collect()
+    }
+
+    #[test]
+    fn record_keeps_last_hundred_paths() {
+        let mut history = History::default();
+
+        for i in 0..150 {
+            history.record(path(i));
+        }
+
+        assert_eq!(history.undo.len(), MAX_HISTORY);
+        assert_eq!(edits(&history.undo), (50..150).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn undo_and_redo_move_paths_between_stacks() {
+        let mut history = History::default();
+        history.record(path(0));
+        history.record(path(1));
+
+        // Undoing from path 2 restores path 1
+        history.undone(path(2));
+        assert_eq!(edits(&history.undo), vec![0]);
+        assert_eq!(edits(&history.redo), vec![2]);
+
+        // Redoing from path 1 restores path 2
+        history.redone(path(1));
+        assert_eq!(edits(&history.undo), vec![0, 1]);
+        assert!(history.redo.is_empty());
+    }
+
+    #[test]
+    fn record_after_undo_invalidates_redo() {
+        let mut history = History::default();
+        history.record(path(0));
+        history.record(path(1));
+        history.undone(path(2));
+        history.undone(path(1));
+        assert_eq!(edits(&history.redo), vec![2, 1]);
+
+        history.record(path(0));
+
+        assert!(history.redo.is_empty());
+        assert_eq!(edits(&history.undo), vec![0]);
+    }
+
+    #[test]
+    fn redo_keeps_hundred_paths_to_undo() {
+        let mut history = History::default();
+        for i in 0..MAX_HISTORY {
+            history.record(path(i));
+        }
+        history.undone(path(MAX_HISTORY));
+        history.undo.push_back(path(MAX_HISTORY + 1));
+
+        history.redone(path(MAX_HISTORY + 2));
+
+        assert_eq!(history.undo.len(), MAX_HISTORY);
+        assert_eq!(
+            history.undo.back().map(|p| p.collection_points().0.len()),
+            Some(MAX_HISTORY + 2)
+        );
+    }
+
+    #[test]
+    fn clear_removes_undo_and_redo() {
+        // Switching the current mission clears the history
+        let mut history = History::default();
+        history.record(path(0));
+        history.record(path(1));
+        history.undone(path(2));
+
+        history.clear();
+
+        assert!(history.undo.is_empty());
+        assert!(history.redo.is_empty());
+    }
+}
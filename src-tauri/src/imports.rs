@@ -0,0 +1,476 @@
+//! Imports of boat data running in the background, reporting their progress and stopping when
+//! cancelled.
+//!
+//! An import only returns the data in its `import-finished` event, so a cancelled import
+//! doesn't change the stored data.
+
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{self, Read},
+    path::Path,
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use serde::Serialize;
+use tauri::{AppHandle, Manager};
+
+use crate::{
+    data::ImportedData,
+    errors::{codes, AppError},
+    operations::Operation,
+};
+
+/// The time between `import-progress` events.
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(250);
+
+/// The size of the pieces the file is read in, so cancelling doesn't wait for the whole file.
+const READ_CHUNK_SIZE: usize = 1 << 20;
+
+/// Payload of the `import-progress` event.
+#[derive(Debug, Serialize, Clone, Copy)]
+pub struct ImportProgress {
+    /// The id of the import.
+    id: u64,
+    /// The number of bytes of the file read.
+    bytes_read: u64,
+    /// The size of the file in bytes.
+    total_bytes: u64,
+    /// The number of rows parsed.
+    rows: usize,
+}
+
+/// Payload of the `import-finished` event.
+#[derive(Debug, Serialize, Clone)]
+#[serde(tag = "status", rename_all = "snake_case")]
+enum ImportFinished {
+    /// The data was imported.
+    Completed { id: u64, result: ImportedData },
+    /// The import was cancelled.
+    Cancelled { id: u64 },
+    /// The data couldn't be imported.
+    Failed { id: u64, error: AppError },
+}
+
+/// The imports running.
+#[derive(Debug, Default)]
+pub struct ImportState {
+    /// The id of the next import.
+    next_id: Mutex<u64>,
+    /// The cancellation flags of the imports running.
+    running: Mutex<HashMap<u64, Arc<AtomicBool>>>,
+}
+
+/// An import running in the background.
+pub struct ImportTask {
+    id: u64,
+    cancelled: Arc<AtomicBool>,
+    bytes_read: u64,
+    total_bytes: u64,
+    /// The rows parsed, counted by every thread parsing the file.
+    rows: AtomicUsize,
+    last_progress: Mutex<Instant>,
+    /// Sends the `import-progress` events.
+    progress: Box<dyn Fn(ImportProgress) + Send + Sync>,
+}
+
+impl ImportTask {
+    /// Creates the task of import `id`, which stops once `cancelled` is set.
+    pub fn new<F>(id: u64, cancelled: Arc<AtomicBool>, progress: F) -> Self
+    where
+        F: Fn(ImportProgress) + Send + Sync + 'static,
+    {
+        Self {
+            id,
+            cancelled,
+            bytes_read: 0,
+            total_bytes: 0,
+            rows: AtomicUsize::new(0),
+            last_progress: Mutex::new(Instant::now()),
+            progress: Box::new(progress),
+        }
+    }
+
+    /// Reads the file being imported, reporting the bytes read.
+    ///
+    /// Returns `None` if the file doesn't exist.
+    pub fn read_to_string(&mut self, path: &Path) -> Result<Option<String>, AppError> {
+        let mut file = match self.open(path)? {
+            Some(v) => v,
+            None => return Ok(None),
+        };
+
+        let mut content = Vec::with_capacity(self.total_bytes as usize);
+        let mut buffer = vec![0; READ_CHUNK_SIZE];
+        loop {
+            let read = file.read(&mut buffer).map_err(|e| AppError::io(e, path))?;
+            if read == 0 {
+                break;
+            }
+            content.extend_from_slice(&buffer[..read]);
+            self.bytes_read += read as u64;
+            self.check()?;
+        }
+        self.emit_progress();
+        String::from_utf8(content).map(Some).map_err(|e| {
+            AppError::new(codes::IO_OTHER, e.to_string()).with("path", path.display().to_string())
+        })
+    }
+
+    /// Opens the file being imported to read it in pieces with [`ImportTask::read`], reporting
+    /// its size.
+    ///
+    /// Returns `None` if the file doesn't exist.
+    pub fn open(&mut self, path: &Path) -> Result<Option<File>, AppError> {
+        let file = match File::open(path) {
+            Ok(v) => v,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(AppError::io(e, path)),
+        };
+        self.total_bytes = file.metadata().map_err(|e| AppError::io(e, path))?.len();
+        self.bytes_read = 0;
+        Ok(Some(file))
+    }
+
+    /// Reads up to `len` more bytes of the file being imported into `buffer`, reporting the
+    /// bytes read.
+    ///
+    /// Returns the number of bytes read, 0 at the end of the file, or an error if the import is
+    /// cancelled.
+    pub fn read(
+        &mut self,
+        file: &mut impl Read,
+        buffer: &mut Vec<u8>,
+        len: usize,
+    ) -> Result<usize, AppError> {
+        let read = file.take(len as u64).read_to_end(buffer)?;
+        self.bytes_read += read as u64;
+        if read == 0 {
+            self.emit_progress();
+            return Ok(0);
+        }
+        self.check()?;
+        Ok(read)
+    }
+
+    /// Opens the file being imported for parsing while it is read, reporting the bytes read.
+    ///
+    /// Reading from the file fails once the import is cancelled.
+    pub fn reader(&mut self, path: &Path) -> Result<TaskReader<'_>, AppError> {
+        let file = File::open(path).map_err(|e| AppError::io(e, path))?;
+        self.total_bytes = file.metadata().map_err(|e| AppError::io(e, path))?.len();
+        self.bytes_read = 0;
+        Ok(TaskReader { task: self, file })
+    }
+
+    /// Records the number of rows parsed.
+    ///
+    /// Returns an error if the import is cancelled.
+    pub fn rows_parsed(&self, rows: usize) -> Result<(), AppError> {
+        self.rows.store(rows, Ordering::Relaxed);
+        self.check()
+    }
+
+    /// Counts a row parsed, for parsing the rows on several threads at once.
+    ///
+    /// Returns an error if the import is cancelled.
+    pub fn row_parsed(&self) -> Result<(), AppError> {
+        self.rows.fetch_add(1, Ordering::Relaxed);
+        self.check()
+    }
+
+    /// Checks if the import is cancelled, emitting `import-progress` if it is due.
+    ///
+    /// Only one of the threads parsing the file emits the event when it is due.
+    fn check(&self) -> Result<(), AppError> {
+        if self.cancelled.load(Ordering::Relaxed) {
+            return Err(AppError::new(codes::IMPORT_CANCELLED, "Import Cancelled"));
+        }
+        if let Ok(mut last_progress) = self.last_progress.try_lock() {
+            if last_progress.elapsed() >= PROGRESS_INTERVAL {
+                *last_progress = Instant::now();
+                self.send_progress();
+            }
+        }
+        Ok(())
+    }
+
+    /// Emits `import-progress`.
+    fn emit_progress(&self) {
+        *self.last_progress.lock().unwrap() = Instant::now();
+        self.send_progress();
+    }
+
+    /// Sends the `import-progress` event with the current progress.
+    fn send_progress(&self) {
+        (self.progress)(ImportProgress {
+            id: self.id,
+            bytes_read: self.bytes_read,
+            total_bytes: self.total_bytes,
+            rows: self.rows.load(Ordering::Relaxed),
+        });
+    }
+
+    /// Gets how the import finished from its result.
+    fn finished(&self, result: Result<ImportedData, AppError>) -> ImportFinished {
+        let id = self.id;
+        match result {
+            Ok(result) => {
+                log::info!("Finished Import {}", id);
+                ImportFinished::Completed { id, result }
+            }
+            Err(e) if self.cancelled.load(Ordering::Relaxed) => {
+                log::info!("Cancelled Import {}: {}", id, e);
+                ImportFinished::Cancelled { id }
+            }
+            Err(error) => {
+                log::warn!("Unable to Import {}: {}", id, error);
+                ImportFinished::Failed { id, error }
+            }
+        }
+    }
+}
+
+/// A file being imported, read while it is parsed.
+pub struct TaskReader<'a> {
+    task: &'a mut ImportTask,
+    file: File,
+}
+
+impl Read for TaskReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self.file.read(buf)?;
+        if read == 0 {
+            self.task.emit_progress();
+            return Ok(0);
+        }
+        self.task.bytes_read += read as u64;
+        self.task
+            .check()
+            .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+        Ok(read)
+    }
+}
+
+/// Starts an import in the background, returning its id.
+///
+/// `import` runs on a blocking task, and its result is sent in the `import-finished` event and
+/// written to the operations log as `operation`.
+pub fn spawn_import<F>(app_handle: AppHandle, operation: Operation, import: F) -> u64
+where
+    F: FnOnce(&mut ImportTask) -> Result<ImportedData, AppError> + Send + 'static,
+{
+    let state = app_handle.state::<ImportState>();
+    let id = {
+        let mut next_id = state.next_id.lock().unwrap();
+        *next_id += 1;
+        *next_id
+    };
+    let cancelled = Arc::new(AtomicBool::new(false));
+    state.running.lock().unwrap().insert(id, cancelled.clone());
+    log::info!("Starting Import {}", id);
+
+    tauri::async_runtime::spawn_blocking(move || {
+        let emitter = app_handle.clone();
+        let mut task = ImportTask::new(id, cancelled, move |progress| {
+            if let Err(e) = emitter.emit_all("import-progress", progress) {
+                log::warn!("Unable to emit import-progress event: {}", e);
+            }
+        });
+        let result = import(&mut task);
+        operation.finish(&app_handle, &result);
+        app_handle
+            .state::<ImportState>()
+            .running
+            .lock()
+            .unwrap()
+            .remove(&id);
+
+        let finished = task.finished(result);
+        if let Err(e) = app_handle.emit_all("import-finished", finished) {
+            log::warn!("Unable to emit import-finished event: {}", e);
+        }
+    });
+    id
+}
+
+/// Cancel a running import.
+///
+/// Returns `false` if the import already finished.
+#[tauri::command]
+pub fn cancel_import(state: tauri::State<ImportState>, import_id: u64) -> bool {
+    log::info!("Cancelling Import {}", import_id);
+    match state.running.lock().unwrap().get(&import_id) {
+        Some(cancelled) => {
+            cancelled.store(true, Ordering::Relaxed);
+            true
+        }
+        None => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{BufWriter, Write};
+
+    use super::*;
+    use crate::{
+        data::{read_csv, write_data, BoatData, CsvTime},
+        storage::tests::temp_dir,
+    };
+
+    /// Writes a CSV file of `rows` data points.
+    fn write_csv(path: &Path, rows: usize) {
+        let mut file = BufWriter::new(File::create(path).unwrap());
+        writeln!(file, "temperature,depth,layer,time,lat,lng").unwrap();
+        for i in 0..rows {
+            writeln!(
+                file,
+                "{},0.5,surface,{},{},101.5",
+                25.0 + (i % 100) as f64 / 10.0,
+                1_715_500_000_000 + i as u64 * 1000,
+                3.1 + i as f64 * 1e-6
+            )
+            .unwrap();
+        }
+        file.flush().unwrap();
+    }
+
+    /// Imports a CSV file like `import_data_csv`, cancelling the import once the first
+    /// `import-progress` event is sent.
+    fn import_cancelling(path: &Path) -> (ImportTask, ImportFinished) {
+        let cancelled = Arc::new(AtomicBool::new(false));
+        let cancel = cancelled.clone();
+        let mut task = ImportTask::new(1, cancelled, move |progress| {
+            if progress.bytes_read > 0 {
+                cancel.store(true, Ordering::Relaxed);
+            }
+        });
+        // Makes the first piece read send a progress event
+        *task.last_progress.get_mut().unwrap() = Instant::now()
+            .checked_sub(PROGRESS_INTERVAL)
+            .unwrap_or_else(Instant::now);
+
+        let time = CsvTime::new(None, None).unwrap();
+        let result = read_csv(&mut task, path, None, &time, false).and_then(|data| match data {
+            Some((data, rows)) => ImportedData::new(data, Some(&rows), None, None),
+            None => ImportedData::new(BoatData::default(), None, None, None),
+        });
+        let finished = task.finished(result);
+        (task, finished)
+    }
+
+    #[test]
+    fn cancelling_large_csv_import_keeps_dataset() {
+        let dir = temp_dir("import-cancel");
+        let import_path = dir.join("large.csv");
+        write_csv(&import_path, 500_000);
+        let data_path = dir.join("default.geojson");
+        write_data(&data_path, &BoatData::default()).unwrap();
+        let stored = std::fs::read(&data_path).unwrap();
+
+        let (task, finished) = import_cancelling(&import_path);
+
+        assert!(matches!(finished, ImportFinished::Cancelled { id: 1 }));
+        assert!(task.bytes_read > 0);
+        assert!(task.bytes_read < task.total_bytes);
+        assert_eq!(std::fs::read(&data_path).unwrap(), stored);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn import_without_cancelling_completes() {
+        let dir = temp_dir("import-complete");
+        let import_path = dir.join("small.csv");
+        write_csv(&import_path, 100);
+
+        let mut task = ImportTask::new(2, Arc::new(AtomicBool::new(false)), |_| {});
+        let time = CsvTime::new(None, None).unwrap();
+        let (data, rows) = read_csv(&mut task, &import_path, None, &time, false)
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(data.features().len(), 100);
+        assert_eq!(rows.first(), Some(&2));
+        assert_eq!(task.bytes_read, task.total_bytes);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn cancelling_after_reading_stops_parsing() {
+        let dir = temp_dir("import-cancel-parse");
+        let import_path = dir.join("rows.csv");
+        write_csv(&import_path, 1000);
+
+        let cancelled = Arc::new(AtomicBool::new(false));
+        let cancel = cancelled.clone();
+        let mut task = ImportTask::new(3, cancelled, move |progress| {
+            if progress.bytes_read == progress.total_bytes {
+                cancel.store(true, Ordering::Relaxed);
+            }
+        });
+        let time = CsvTime::new(None, None).unwrap();
+        let result = read_csv(&mut task, &import_path, None, &time, false).map(|_| unreachable!());
+        let finished = task.finished(result);
+
+        assert!(matches!(finished, ImportFinished::Cancelled { id: 3 }));
+        assert_eq!(task.bytes_read, task.total_bytes);
+        assert_eq!(task.rows.into_inner(), 1);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn task_reader_counts_the_bytes_read() {
+        let dir = temp_dir("import-reader");
+        let import_path = dir.join("data.csv");
+        write_csv(&import_path, 100);
+        let mut task = ImportTask::new(1, Arc::new(AtomicBool::new(false)), |_| {});
+
+        let mut content = String::new();
+        task.reader(&import_path)
+            .unwrap()
+            .read_to_string(&mut content)
+            .unwrap();
+        assert_eq!(task.bytes_read, content.len() as u64);
+        assert_eq!(task.total_bytes, content.len() as u64);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn task_reader_fails_once_cancelled() {
+        let dir = temp_dir("import-reader-cancel");
+        let import_path = dir.join("data.csv");
+        write_csv(&import_path, 100);
+        let mut task = ImportTask::new(1, Arc::new(AtomicBool::new(true)), |_| {});
+
+        let mut content = String::new();
+        let error = task
+            .reader(&import_path)
+            .unwrap()
+            .read_to_string(&mut content)
+            .unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::Other);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn open_and_read_count_the_bytes_read() {
+        let dir = temp_dir("import-open");
+        let import_path = dir.join("data.csv");
+        write_csv(&import_path, 100);
+        let mut task = ImportTask::new(1, Arc::new(AtomicBool::new(false)), |_| {});
+
+        assert!(task.open(&dir.join("missing.csv")).unwrap().is_none());
+        let mut file = task.open(&import_path).unwrap().unwrap();
+        let mut buffer = vec![];
+        assert_eq!(task.read(&mut file, &mut buffer, 10).unwrap(), 10);
+        while task.read(&mut file, &mut buffer, 1_000).unwrap() > 0 {}
+        assert_eq!(task.bytes_read, task.total_bytes);
+        assert_eq!(buffer, std::fs::read(&import_path).unwrap());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
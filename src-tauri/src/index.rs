@@ -0,0 +1,343 @@
+//! Spatial index over the boat data in application storage.
+
+use std::sync::Mutex;
+
+use rstar::{primitives::GeomWithData, RTree, AABB};
+use tauri::AppHandle;
+
+use crate::{
+    data::{BoatData, BoatDataFeature},
+    datasets::active_data_file,
+    migrations,
+    storage::sqlite,
+};
+
+/// A data point in the index along with its position in the boat data.
+type IndexedFeature = GeomWithData<[f64; 2], (usize, BoatDataFeature)>;
+
+/// The indexed boat data.
+#[derive(Debug, Default)]
+struct Index {
+    /// The version of the indexed boat data.
+    version: String,
+    /// The number of data points indexed.
+    len: usize,
+    /// The R-tree of the data points keyed by their coordinates.
+    tree: RTree<IndexedFeature>,
+}
+
+/// Spatial index of the boat data in application storage.
+#[derive(Debug, Default)]
+pub struct DataIndex {
+    index: Mutex<Index>,
+}
+
+/// Creates the entry of a data point in the index.
+fn indexed(position: usize, feature: BoatDataFeature) -> IndexedFeature {
+    let geometry = feature.geometry();
+    GeomWithData::new([geometry.x(), geometry.y()], (position, feature))
+}
+
+/// Converts the data points found in the index to boat data.
+///
+/// The data points are ordered by their position in the boat data.
+fn to_boat_data<'a>(version: &str, found: impl Iterator<Item = &'a IndexedFeature>) -> BoatData {
+    let mut found: Vec<_> = found.map(|f| &f.data).collect();
+    found.sort_by_key(|(position, _)| *position);
+    BoatData::new(
+        version.to_string(),
+        found.into_iter().map(|(_, f)| f.clone()).collect(),
+    )
+}
+
+impl DataIndex {
+    /// Replaces the indexed data points with `data`.
+    pub fn rebuild(&self, data: &BoatData) {
+        log::debug!("Rebuilding Data Index");
+        let features: Vec<_> = data
+            .features()
+            .iter()
+            .cloned()
+            .enumerate()
+            .map(|(i, f)| indexed(i, f))
+            .collect();
+
+        let mut index = self.index.lock().unwrap();
+        index.version = data.version().to_string();
+        index.len = features.len();
+        index.tree = RTree::bulk_load(features);
+    }
+
+    /// Adds the data points appended to `data` after the first `from` data points.
+    ///
+    /// The index is rebuilt if it doesn't contain exactly the first `from` data points.
+    pub fn extend(&self, data: &BoatData, from: usize) {
+        let len = self.index.lock().unwrap().len;
+        if len == from {
+            self.append(&data.features()[from..]);
+        } else {
+            self.rebuild(data);
+        }
+    }
+
+    /// Adds data points after the indexed data points.
+    pub fn append(&self, features: &[BoatDataFeature]) {
+        log::debug!("Adding {} Data Points to Index", features.len());
+        let mut index = self.index.lock().unwrap();
+        for feature in features {
+            let entry = indexed(index.len, feature.clone());
+            index.tree.insert(entry);
+            index.len += 1;
+        }
+    }
+
+    /// Gets the indexed data points inside a bounding box, including its boundary.
+    ///
+    /// A box with `min_lng` greater than `max_lng` crosses the antimeridian.
+    pub fn within_bbox(&self, min_lng: f64, min_lat: f64, max_lng: f64, max_lat: f64) -> BoatData {
+        let index = self.index.lock().unwrap();
+        let envelopes = if min_lng <= max_lng {
+            vec![AABB::from_corners([min_lng, min_lat], [max_lng, max_lat])]
+        } else {
+            vec![
+                AABB::from_corners([min_lng, min_lat], [180.0, max_lat]),
+                AABB::from_corners([-180.0, min_lat], [max_lng, max_lat]),
+            ]
+        };
+
+        let found = envelopes
+            .iter()
+            .flat_map(|envelope| index.tree.locate_in_envelope(envelope));
+        to_boat_data(version(&index), found)
+    }
+
+    /// Gets the `k` indexed data points nearest to a coordinate.
+    ///
+    /// The distance is measured in degrees, which is only suitable for picking nearby points.
+    pub fn nearest(&self, lng: f64, lat: f64, k: usize) -> Vec<BoatDataFeature> {
+        let index = self.index.lock().unwrap();
+        index
+            .tree
+            .nearest_neighbor_iter(&[lng, lat])
+            .take(k)
+            .map(|f| f.data.1.clone())
+            .collect()
+    }
+}
+
+/// Gets the version of the indexed data, defaulting to the current version when empty.
+fn version(index: &Index) -> &str {
+    if index.version.is_empty() {
+        migrations::BOAT_DATA_VERSION
+    } else {
+        &index.version
+    }
+}
+
+/// Query the indexed boat data inside a bounding box.
+///
+/// The bounding box is given as `[min_lng, min_lat, max_lng, max_lat]`.
+///
+/// The R*Tree of the database is queried instead when SQLite storage is enabled, falling back to
+/// the index in memory if the database can't be read.
+#[tauri::command]
+pub fn query_indexed_bbox(
+    app_handle: AppHandle,
+    index: tauri::State<DataIndex>,
+    bbox: [f64; 4],
+) -> BoatData {
+    log::debug!("Querying Indexed Data in {:?}", bbox);
+    let [min_lng, min_lat, max_lng, max_lat] = bbox;
+    if sqlite::enabled(&app_handle) {
+        let data = active_data_file(&app_handle).and_then(|file| {
+            sqlite::read_bbox(
+                &sqlite::database_file(&file),
+                min_lng,
+                min_lat,
+                max_lng,
+                max_lat,
+            )
+        });
+        match data {
+            Ok(v) => return v,
+            Err(e) => log::warn!("Unable to Query Database: {}", e),
+        }
+    }
+    index.within_bbox(min_lng, min_lat, max_lng, max_lat)
+}
+
+/// Find the `k` indexed data points nearest to a coordinate.
+#[tauri::command]
+pub fn nearest_reading(
+    index: tauri::State<DataIndex>,
+    lng: f64,
+    lat: f64,
+    k: usize,
+) -> Vec<BoatDataFeature> {
+    log::debug!("Finding {} Nearest Readings to [{}, {}]", k, lng, lat);
+    index.nearest(lng, lat, k)
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeZone, Utc};
+    use geo_types::Point;
+
+    use super::*;
+    use crate::data::Layer;
+
+    /// Creates boat data with `count` data points scattered around the globe.
+    ///
+    /// The points come from a linear congruential generator so the tests are repeatable,
+    /// with some points repeated to check that duplicates are all found.
+    fn scattered(count: usize) -> BoatData {
+        let mut state: u64 = 0x2545_f491_4f6c_dd1d;
+        let mut next = move || {
+            state = state
+                .wrapping_mul(6_364_136_223_846_793_005)
+                .wrapping_add(1_442_695_040_888_963_407);
+            (state >> 11) as f64 / (1u64 << 53) as f64
+        };
+        let mut features = Vec::with_capacity(count);
+        for i in 0..count {
+            let point = if i % 10 == 9 {
+                features.last().map(BoatDataFeature::geometry).unwrap()
+            } else {
+                Point::new(next() * 360.0 - 180.0, next() * 180.0 - 90.0)
+            };
+            features.push(BoatDataFeature::new(
+                28.5,
+                0.5,
+                Layer::Surface,
+                Utc.timestamp_opt(1_715_495_400 + i as i64, 0).unwrap(),
+                point,
+            ));
+        }
+        BoatData::new(migrations::BOAT_DATA_VERSION.to_string(), features)
+    }
+
+    /// Viewports to query, including one crossing the antimeridian and one at the edges.
+    const VIEWPORTS: [[f64; 4]; 5] = [
+        [-180.0, -90.0, 180.0, 90.0],
+        [100.0, 0.0, 110.0, 10.0],
+        [-30.5, -60.0, 20.25, -10.0],
+        [170.0, -20.0, -170.0, 20.0],
+        [0.0, 0.0, 0.0, 0.0],
+    ];
+
+    /// Gets the times of the data points, which identify them in the tests.
+    fn times(data: &BoatData) -> Vec<i64> {
+        data.features()
+            .iter()
+            .map(|f| f.time().timestamp())
+            .collect()
+    }
+
+    /// Checks that the index finds the same data points as scanning `data` in each viewport.
+    fn assert_matches_scan(index: &DataIndex, data: &BoatData) {
+        for [min_lng, min_lat, max_lng, max_lat] in VIEWPORTS {
+            let expected = data.within_bbox(min_lng, min_lat, max_lng, max_lat);
+            let found = index.within_bbox(min_lng, min_lat, max_lng, max_lat);
+            assert_eq!(
+                times(&found),
+                times(&expected),
+                "viewport {:?}",
+                [min_lng, min_lat, max_lng, max_lat]
+            );
+            assert_eq!(found.version(), data.version());
+        }
+    }
+
+    #[test]
+    fn within_bbox_matches_linear_scan() {
+        let data = scattered(5000);
+        let index = DataIndex::default();
+        index.rebuild(&data);
+
+        assert_matches_scan(&index, &data);
+        assert_eq!(
+            index
+                .within_bbox(-180.0, -90.0, 180.0, 90.0)
+                .features()
+                .len(),
+            5000
+        );
+    }
+
+    #[test]
+    fn within_bbox_of_empty_index_is_empty() {
+        let index = DataIndex::default();
+
+        let found = index.within_bbox(-180.0, -90.0, 180.0, 90.0);
+
+        assert!(found.features().is_empty());
+        assert_eq!(found.version(), migrations::BOAT_DATA_VERSION);
+    }
+
+    #[test]
+    fn extend_adds_appended_data_points() {
+        let data = scattered(3000);
+        let first = BoatData::new(data.version().to_string(), data.features()[..1000].to_vec());
+        let index = DataIndex::default();
+        index.rebuild(&first);
+
+        index.extend(&data, 1000);
+
+        assert_matches_scan(&index, &data);
+    }
+
+    #[test]
+    fn extend_rebuilds_out_of_date_index() {
+        let data = scattered(3000);
+        let index = DataIndex::default();
+        index.rebuild(&scattered(10));
+
+        index.extend(&data, 1000);
+
+        assert_matches_scan(&index, &data);
+    }
+
+    #[test]
+    fn rebuild_replaces_indexed_data() {
+        let index = DataIndex::default();
+        index.rebuild(&scattered(3000));
+        let data = scattered(500);
+
+        index.rebuild(&data);
+
+        assert_matches_scan(&index, &data);
+    }
+
+    #[test]
+    fn nearest_finds_closest_data_points() {
+        let data = scattered(2000);
+        let index = DataIndex::default();
+        index.rebuild(&data);
+
+        let nearest = index.nearest(101.5, 3.1, 5);
+
+        let distance = |f: &BoatDataFeature| {
+            let (lng, lat) = (f.geometry().x() - 101.5, f.geometry().y() - 3.1);
+            lng * lng + lat * lat
+        };
+        let mut expected: Vec<_> = data.features().iter().map(distance).collect();
+        expected.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        let found: Vec<_> = nearest.iter().map(distance).collect();
+        assert_eq!(found, expected[..5]);
+    }
+
+    #[test]
+    fn append_adds_data_points_after_indexed_ones() {
+        let data = scattered(3000);
+        let index = DataIndex::default();
+        index.rebuild(&BoatData::new(
+            data.version().to_string(),
+            data.features()[..1000].to_vec(),
+        ));
+
+        index.append(&data.features()[1000..2000]);
+        index.append(&data.features()[2000..]);
+
+        assert_matches_scan(&index, &data);
+    }
+}
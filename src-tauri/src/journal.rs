@@ -0,0 +1,316 @@
+//! Append-only journal of the data points received since the boat data was last written.
+//!
+//! Each line of the journal is a GeoJSON feature of a data point, so appending a data point
+//! doesn't rewrite the whole boat data.
+
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{BufRead, BufReader, ErrorKind, Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+};
+
+use tauri::{AppHandle, Manager};
+
+use crate::{
+    app_data::AppData,
+    autosave::AutosaveState,
+    data::{self, BoatData, BoatDataFeature},
+    datasets::active_data_file,
+    errors::{codes, AppError},
+    index::DataIndex,
+    storage::{sqlite, write_atomically},
+    watcher,
+};
+
+/// Gets the journal of the boat data stored in `data_file`.
+pub fn journal_file(data_file: &Path) -> PathBuf {
+    data_file.with_extension("ndjson")
+}
+
+/// Reads the data points in a journal.
+///
+/// A partially written last line, e.g. from a crash, is skipped with a warning.
+pub fn read_journal(journal: &Path) -> Result<Vec<BoatDataFeature>, AppError> {
+    let file = match File::open(journal) {
+        Ok(v) => v,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(vec![]),
+        Err(e) => return Err(AppError::io(e, journal)),
+    };
+
+    let lines = BufReader::new(file)
+        .lines()
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| AppError::io(e, journal))?;
+    let mut features = vec![];
+    for (i, line) in lines.iter().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        match geojson::de::deserialize_single_feature(line.as_bytes()) {
+            Ok(v) => features.push(v),
+            Err(e) if i == lines.len() - 1 => {
+                log::warn!("Skipping Incomplete Journal Line {}: {}", i + 1, e);
+            }
+            Err(e) => {
+                return Err(AppError::new(
+                    codes::DATA_INVALID_JOURNAL,
+                    format!("Invalid Boat Data Journal: Line {}: {e}", i + 1),
+                )
+                .with("line", i + 1))
+            }
+        }
+    }
+    Ok(features)
+}
+
+/// Reads the boat data stored in `data_file` along with its journal.
+pub fn read_with_journal(data_file: &Path) -> Result<BoatData, AppError> {
+    let mut data = data::load_data(data_file)?;
+    let journal = read_journal(&journal_file(data_file))?;
+    if !journal.is_empty() {
+        log::info!("Read {} Data Points from the Journal", journal.len());
+        data.extend(journal);
+    }
+    Ok(data)
+}
+
+/// Removes the journal of the boat data stored in `data_file`.
+///
+/// This should be called after the data points in the journal are written to `data_file`.
+pub fn clear_journal(data_file: &Path) -> Result<(), AppError> {
+    let journal = journal_file(data_file);
+    match fs::remove_file(&journal) {
+        Ok(_) => Ok(()),
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(()),
+        Err(e) => Err(AppError::io(e, &journal)),
+    }
+}
+
+/// Prepares the journal for appending when its last line is not terminated.
+///
+/// A complete last line is terminated, while an incomplete last line is removed so that only
+/// the last line of the journal can be incomplete.
+fn repair_last_line(file: &mut File) -> std::io::Result<()> {
+    let mut content = vec![];
+    file.seek(SeekFrom::Start(0))?;
+    file.read_to_end(&mut content)?;
+    if content.last().map_or(true, |b| *b == b'\n') {
+        return Ok(());
+    }
+
+    let start = content
+        .iter()
+        .rposition(|b| *b == b'\n')
+        .map_or(0, |i| i + 1);
+    let last = &content[start..];
+    if geojson::de::deserialize_single_feature::<BoatDataFeature>(last).is_ok() {
+        file.write_all(b"\n")
+    } else {
+        log::warn!("Removing Incomplete Journal Line");
+        file.set_len(start as u64)
+    }
+}
+
+/// Append data points to the journal of the boat data in application storage.
+///
+/// Returns the number of data points appended.
+#[tauri::command]
+pub fn append_features(
+    app_handle: AppHandle,
+    features: Vec<BoatDataFeature>,
+) -> Result<usize, AppError> {
+    log::debug!("Appending {} Data Points", features.len());
+    let features: Vec<_> = features
+        .into_iter()
+        .map(BoatDataFeature::with_random_id)
+        .collect();
+    let _writing = app_handle.state::<AutosaveState>().inner().lock_writes();
+    let data_file = active_data_file(&app_handle)?;
+    if sqlite::enabled(&app_handle) {
+        sqlite::append(&sqlite::database_file(&data_file), &features)?;
+        app_handle.state::<DataIndex>().append(&features);
+        app_handle.state::<AppData>().append(&features);
+        return Ok(features.len());
+    }
+    let journal = journal_file(&data_file);
+
+    let mut lines = String::new();
+    for feature in &features {
+        lines.push_str(
+            &geojson::ser::to_feature_string(feature)
+                .map_err(|e| AppError::new(codes::DATA_INVALID_JSON, e.to_string()))?,
+        );
+        lines.push('\n');
+    }
+
+    let mut file = OpenOptions::new()
+        .read(true)
+        .append(true)
+        .create(true)
+        .open(&journal)
+        .map_err(|e| AppError::io(e, &journal))?;
+    repair_last_line(&mut file).map_err(|e| AppError::io(e, &journal))?;
+    file.write_all(lines.as_bytes())
+        .map_err(|e| AppError::io(e, &journal))?;
+    file.sync_data().map_err(|e| AppError::io(e, &journal))?;
+
+    app_handle.state::<DataIndex>().append(&features);
+    app_handle.state::<AppData>().append(&features);
+    Ok(features.len())
+}
+
+/// Write the data points in the journal to the boat data in application storage.
+///
+/// Returns the number of data points written. Data points are appended to SQLite storage
+/// directly, so nothing is written when it is enabled.
+#[tauri::command]
+pub fn compact_data(app_handle: AppHandle) -> Result<usize, AppError> {
+    log::debug!("Compacting Data");
+    if sqlite::enabled(&app_handle) {
+        return Ok(0);
+    }
+    let _writing = app_handle.state::<AutosaveState>().inner().lock_writes();
+    let data_file = active_data_file(&app_handle)?;
+    let journal = read_journal(&journal_file(&data_file))?;
+    if journal.is_empty() {
+        return clear_journal(&data_file).map(|_| 0);
+    }
+
+    let mut data = data::load_data(&data_file)?;
+    let count = journal.len();
+    data.extend(journal);
+    write_atomically(&data_file, |file| write!(file, "{}", data))?;
+    watcher::note_write(&app_handle, &data_file);
+    clear_journal(&data_file)?;
+    log::info!("Compacted {} Data Points", count);
+    Ok(count)
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeZone, Utc};
+    use geo_types::Point;
+
+    use super::*;
+    use crate::{data::Layer, storage::tests::temp_dir};
+
+    /// Creates a data point measured `seconds` after the first one.
+    fn feature(seconds: i64) -> BoatDataFeature {
+        BoatDataFeature::new(
+            28.5,
+            0.5,
+            Layer::Surface,
+            Utc.timestamp_opt(1_715_500_000 + seconds, 0).unwrap(),
+            Point::new(101.5, 3.1),
+        )
+    }
+
+    /// Gets the journal lines of data points.
+    fn lines(features: &[BoatDataFeature]) -> String {
+        features
+            .iter()
+            .map(|f| geojson::ser::to_feature_string(f).unwrap() + "\n")
+            .collect()
+    }
+
+    /// Gets the times of data points.
+    fn times(features: &[BoatDataFeature]) -> Vec<i64> {
+        features.iter().map(|f| f.time().timestamp()).collect()
+    }
+
+    #[test]
+    fn read_journal_of_missing_file_is_empty() {
+        let dir = temp_dir("journal-missing");
+
+        assert!(read_journal(&dir.join("data.ndjson")).unwrap().is_empty());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn read_journal_skips_incomplete_last_line() {
+        let dir = temp_dir("journal-incomplete");
+        let journal = dir.join("data.ndjson");
+        let complete = lines(&[feature(0), feature(1)]);
+        let incomplete = lines(&[feature(2)]);
+        fs::write(&journal, format!("{complete}\n{}", &incomplete[..40])).unwrap();
+
+        let features = read_journal(&journal).unwrap();
+
+        assert_eq!(times(&features), times(&[feature(0), feature(1)]));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn read_journal_rejects_invalid_line_before_last() {
+        let dir = temp_dir("journal-invalid");
+        let journal = dir.join("data.ndjson");
+        fs::write(&journal, format!("{{\"type\":\n{}", lines(&[feature(0)]))).unwrap();
+
+        let error = read_journal(&journal).unwrap_err();
+
+        assert!(error.to_string().contains("Line 1"), "{error}");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn repair_last_line_terminates_complete_line() {
+        let dir = temp_dir("journal-terminate");
+        let journal = dir.join("data.ndjson");
+        let content = lines(&[feature(0), feature(1)]);
+        fs::write(&journal, content.trim_end()).unwrap();
+
+        let mut file = OpenOptions::new()
+            .read(true)
+            .append(true)
+            .open(&journal)
+            .unwrap();
+        repair_last_line(&mut file).unwrap();
+
+        assert_eq!(fs::read_to_string(&journal).unwrap(), content);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn repair_last_line_removes_incomplete_line() {
+        let dir = temp_dir("journal-truncate");
+        let journal = dir.join("data.ndjson");
+        let complete = lines(&[feature(0)]);
+        fs::write(&journal, format!("{complete}{{\"type\":\"Feat")).unwrap();
+
+        let mut file = OpenOptions::new()
+            .read(true)
+            .append(true)
+            .open(&journal)
+            .unwrap();
+        repair_last_line(&mut file).unwrap();
+        file.write_all(lines(&[feature(1)]).as_bytes()).unwrap();
+
+        let features = read_journal(&journal).unwrap();
+        assert_eq!(times(&features), times(&[feature(0), feature(1)]));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn read_with_journal_appends_journal_to_data() {
+        let dir = temp_dir("journal-read");
+        let file = dir.join("data.geojson");
+        let data = BoatData::new(
+            BoatData::default().version().to_string(),
+            vec![feature(0), feature(1)],
+        );
+        data::write_data(&file, &data).unwrap();
+        fs::write(journal_file(&file), lines(&[feature(2)])).unwrap();
+
+        let data = read_with_journal(&file).unwrap();
+        assert_eq!(
+            times(data.features()),
+            times(&[feature(0), feature(1), feature(2)])
+        );
+
+        clear_journal(&file).unwrap();
+        assert!(!journal_file(&file).exists());
+        assert_eq!(read_with_journal(&file).unwrap().features().len(), 2);
+        clear_journal(&file).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
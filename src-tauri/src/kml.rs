@@ -0,0 +1,219 @@
+//! KML export of boat data for viewing in Google Earth.
+
+use std::{fmt::Write, path::PathBuf};
+
+use crate::{
+    data::{BoatData, BoatDataFeature},
+    errors::{export_error, AppError},
+    export_preview::{self, ExportPreview},
+    storage::write_atomically,
+};
+
+/// Temperature buckets of the icon styles.
+///
+/// Each bucket contains the upper bound of the temperature and the KML color (aabbggrr),
+/// matching the color ramp used on the map.
+const TEMPERATURE_STYLES: &[(f64, &str)] = &[
+    (8.0, "ffac6621"),
+    (16.0, "ffcfa967"),
+    (24.0, "fff0e5d1"),
+    (32.0, "ffc7dbfd"),
+    (40.0, "ff628aef"),
+    (f64::INFINITY, "ff2b18b2"),
+];
+
+/// Escapes the XML special characters in `value`.
+fn escape(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Gets the ID of the style used for a temperature.
+fn style_id(temperature: f64) -> String {
+    let bucket = TEMPERATURE_STYLES
+        .iter()
+        .position(|(upper, _)| temperature < *upper)
+        .unwrap_or(TEMPERATURE_STYLES.len() - 1);
+    format!("temperature-{bucket}")
+}
+
+/// Writes the icon styles of the temperature buckets.
+fn write_styles(kml: &mut String) -> std::fmt::Result {
+    for (i, (_, color)) in TEMPERATURE_STYLES.iter().enumerate() {
+        write!(
+            kml,
+            "<Style id=\"temperature-{i}\"><IconStyle><color>{color}</color><Icon>\
+            <href>http://maps.google.com/mapfiles/kml/shapes/placemark_circle.png</href>\
+            </Icon></IconStyle></Style>"
+        )?;
+    }
+    Ok(())
+}
+
+/// Writes a data point as a placemark.
+fn write_placemark(kml: &mut String, feature: &BoatDataFeature) -> std::fmt::Result {
+    let time = feature.time().to_rfc3339();
+    let layer = escape(&feature.layer().to_string());
+    let properties = [
+        ("temperature", feature.temperature().to_string()),
+        ("depth", feature.depth().to_string()),
+        ("layer", layer),
+        ("time", time.clone()),
+    ];
+
+    write!(
+        kml,
+        "<Placemark><name>{}</name>",
+        feature.time().format("%Y-%m-%d %H:%M:%S UTC")
+    )?;
+    write!(kml, "<description><![CDATA[<table>")?;
+    for (name, value) in &properties {
+        write!(kml, "<tr><th>{name}</th><td>{value}</td></tr>")?;
+    }
+    write!(kml, "</table>]]></description>")?;
+    write!(
+        kml,
+        "<styleUrl>#{}</styleUrl>",
+        style_id(feature.temperature())
+    )?;
+    write!(kml, "<ExtendedData>")?;
+    for (name, value) in &properties {
+        write!(kml, "<Data name=\"{name}\"><value>{value}</value></Data>")?;
+    }
+    write!(kml, "</ExtendedData>")?;
+    write!(
+        kml,
+        "<TimeStamp><when>{time}</when></TimeStamp><Point><coordinates>{},{}</coordinates>\
+        </Point></Placemark>",
+        feature.geometry().x(),
+        feature.geometry().y()
+    )
+}
+
+/// Writes the boat data as a KML document with a folder for each layer.
+fn write_kml(kml: &mut String, data: &BoatData) -> std::fmt::Result {
+    write!(
+        kml,
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\
+        <kml xmlns=\"http://www.opengis.net/kml/2.2\"><Document><name>Boat Data</name>"
+    )?;
+    write_styles(kml)?;
+
+    for layer in data.layers() {
+        let features = data.features().iter().filter(|f| f.layer() == layer);
+
+        write!(kml, "<Folder><name>{}</name>", escape(&layer.to_string()))?;
+        for feature in features {
+            write_placemark(kml, feature)?;
+        }
+        write!(kml, "</Folder>")?;
+    }
+    write!(kml, "</Document></kml>")
+}
+
+/// Export boat data in KML format to the file system.
+///
+/// If `dry_run` is `true`, nothing is written and a preview of the export is returned instead.
+#[tauri::command]
+pub fn export_data_kml(
+    export_path: PathBuf,
+    data: BoatData,
+    dry_run: Option<bool>,
+) -> Result<Option<ExportPreview>, AppError> {
+    let mut kml = String::new();
+    write_kml(&mut kml, &data).map_err(|e| export_error(&export_path, e))?;
+    if dry_run.unwrap_or(false) {
+        return export_preview::preview(&export_path, &data, "NaN", |file| {
+            file.write_all(kml.as_bytes())
+        })
+        .map(Some);
+    }
+    log::debug!("Exporting to: {}", export_path.display());
+    write_atomically(&export_path, |file| file.write_all(kml.as_bytes()))?;
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeZone, Utc};
+    use geo_types::Point;
+
+    use super::*;
+    use crate::data::Layer;
+
+    /// Creates a data point measured at `layer` with `temperature`.
+    fn feature(layer: Layer, temperature: f64) -> BoatDataFeature {
+        BoatDataFeature::new(
+            temperature,
+            0.5,
+            layer,
+            Utc.with_ymd_and_hms(2024, 5, 12, 6, 30, 0).unwrap(),
+            Point::new(101.5, 3.1),
+        )
+    }
+
+    #[test]
+    fn temperatures_use_the_style_of_their_bucket() {
+        assert_eq!(style_id(-2.0), "temperature-0");
+        assert_eq!(style_id(7.9), "temperature-0");
+        assert_eq!(style_id(8.0), "temperature-1");
+        assert_eq!(style_id(39.9), "temperature-4");
+        assert_eq!(style_id(40.0), "temperature-5");
+        assert_eq!(style_id(f64::NAN), "temperature-5");
+    }
+
+    #[test]
+    fn special_characters_are_escaped() {
+        assert_eq!(escape("sea bed"), "sea bed");
+        assert_eq!(
+            escape("<a href=\"x\">&</a>"),
+            "&lt;a href=&quot;x&quot;&gt;&amp;&lt;/a&gt;"
+        );
+    }
+
+    #[test]
+    fn data_points_are_grouped_in_folders_by_layer() {
+        let data = BoatData::new(
+            String::from("0.1.0"),
+            vec![
+                feature(Layer::SeaBed, 12.0),
+                feature(Layer::Surface, 29.5),
+                feature(Layer::SeaBed, 11.5),
+            ],
+        );
+        let mut kml = String::new();
+
+        write_kml(&mut kml, &data).unwrap();
+
+        assert!(kml.starts_with("<?xml version=\"1.0\" encoding=\"UTF-8\"?>"));
+        assert!(kml.ends_with("</Document></kml>"));
+        assert_eq!(kml.matches("<Style id=").count(), TEMPERATURE_STYLES.len());
+        let folders: Vec<_> = kml
+            .split("<Folder><name>")
+            .skip(1)
+            .map(|folder| folder.split('<').next().unwrap())
+            .collect();
+        assert_eq!(folders, vec!["surface", "sea bed"]);
+        assert_eq!(kml.matches("<Placemark>").count(), 3);
+        assert!(kml.contains("<name>2024-05-12 06:30:00 UTC</name>"));
+        assert!(kml.contains("<styleUrl>#temperature-3</styleUrl>"));
+        assert!(kml.contains("<Data name=\"temperature\"><value>29.5</value></Data>"));
+        assert!(kml.contains(
+            "<TimeStamp><when>2024-05-12T06:30:00+00:00</when></TimeStamp>\
+             <Point><coordinates>101.5,3.1</coordinates></Point>"
+        ));
+    }
+
+    #[test]
+    fn empty_data_has_no_folders() {
+        let mut kml = String::new();
+
+        write_kml(&mut kml, &BoatData::default()).unwrap();
+
+        assert!(!kml.contains("<Folder>"));
+        assert!(!kml.contains("<Placemark>"));
+    }
+}
@@ -5,10 +5,71 @@ mod comm_proto;
 mod data;
 mod path;
 mod mbtiles;
+mod storage;
+mod backup;
+mod migrations;
+mod kml;
+mod statistics;
+mod index;
+mod geometry;
+mod missions;
+mod datasets;
+mod journal;
+mod history;
+mod communication;
+mod protocol;
+mod messages;
+mod upload;
+mod telemetry;
+mod tracking;
+mod control;
+mod heartbeat;
+mod transport;
+mod frame_log;
+mod simulation;
+mod handshake;
+mod health;
+mod progress;
+mod map_assets;
+mod tile_server;
+mod regions;
+mod heatmap;
+mod contours;
+mod profile;
+mod settings;
+mod units;
+mod shp;
+mod geopackage;
+mod parquet_file;
+mod netcdf;
+mod report;
+mod clipboard;
+mod thinning;
+mod recovery;
+mod watcher;
+mod windows;
+mod errors;
+mod imports;
+mod comparison;
+mod project;
+mod streaming;
+mod app_data;
+mod autosave;
+mod preflight;
+mod geo_measure;
+mod coordinates;
+mod metadata;
+mod coverage;
+mod auto_export;
+mod export_preview;
+mod calibration;
+mod operations;
+mod clock;
+mod conditions;
 
 use std::error::Error;
 
-use tauri::{Manager, State, WindowEvent};
+use tauri::{Manager, RunEvent, State, WindowEvent};
 use tauri_plugin_log::LogTarget;
 
 fn error_to_string<E: Error>(error: E) -> String {
@@ -17,45 +78,259 @@ fn error_to_string<E: Error>(error: E) -> String {
 
 fn main() {
     tauri::Builder::default()
-        .invoke_handler(tauri::generate_handler![
+        .invoke_handler(windows::read_only_guard(tauri::generate_handler![
             path::read_path,
             path::save_path,
             path::import_path,
             path::export_path,
+            path::export_path_gpx,
+            path::import_path_gpx,
+            shp::import_path_shapefile,
+            path::add_collection_point,
+            path::remove_collection_point,
+            path::set_collection_point_note,
+            path::move_collection_point,
+            path::insert_waypoint,
+            path::update_waypoint,
+            path::remove_waypoint,
+            path::reverse_path,
+            path::set_boundary,
+            path::clear_boundary,
+            path::compare_paths,
+            history::undo_path,
+            history::redo_path,
+            missions::list_missions,
+            missions::save_mission,
+            missions::load_mission,
+            missions::delete_mission,
+            missions::rename_mission,
+            missions::set_current_mission,
+            coordinates::parse_coordinate,
+            coordinates::format_coordinate,
+            geo_measure::measure_distance,
+            geo_measure::measure_area,
+            geo_measure::measure_bearing,
+            geometry::path_metrics,
+            geometry::simplify_path,
+            geometry::generate_collection_points,
+            geometry::snap_points_to_path,
+            geometry::split_path,
+            geometry::join_paths,
+            geometry::generate_survey_path,
+            profile::path_profile,
             data::read_data,
+            data::data_generation,
             data::save_data,
             data::import_data,
             data::export_data,
+            data::merge_data,
+            data::get_feature,
+            data::delete_features,
+            data::set_feature_note,
+            data::search_notes,
+            autosave::flush_now,
+            auto_export::run_auto_export,
+            auto_export::auto_export_failures,
+            auto_export::dismiss_auto_export_failures,
+            metadata::get_dataset_metadata,
+            metadata::set_dataset_metadata,
+            conditions::capture_conditions,
+            conditions::set_conditions,
+            data::compare_data,
+            data::filter_data,
+            data::filter_data_stored,
+            data::filter_data_layer,
+            data::sort_data,
+            data::filter_data_sensor,
+            data::query_data_bbox,
+            data::query_data_bbox_stored,
+            data::nearest_feature,
             data::import_data_csv,
+            shp::import_data_shapefile,
             data::export_data_csv,
+            data::export_stored_data_csv,
+            journal::append_features,
+            journal::compact_data,
+            datasets::list_datasets,
+            datasets::create_dataset,
+            datasets::load_dataset,
+            datasets::delete_dataset,
+            datasets::append_to_dataset,
+            datasets::set_active_dataset,
+            project::export_project,
+            project::import_project,
+            backup::list_backups,
+            backup::restore_backup,
+            index::query_indexed_bbox,
+            index::nearest_reading,
+            kml::export_data_kml,
+            geopackage::export_geopackage,
+            netcdf::export_data_netcdf,
+            report::generate_report,
+            clipboard::copy_features_to_clipboard,
+            thinning::downsample_data,
+            parquet_file::export_data_parquet,
+            parquet_file::import_data_parquet,
+            statistics::data_statistics,
+            statistics::data_statistics_stored,
+            statistics::aggregate_data,
+            recovery::check_recovery,
+            recovery::apply_recovery,
+            recovery::discard_recovery,
+            heatmap::generate_heatmap,
+            comparison::compare_datasets,
+            coverage::mission_coverage,
+            contours::generate_contours,
+            communication::list_serial_ports,
+            communication::connect_serial,
+            communication::disconnect_serial,
+            communication::connect_tcp,
+            communication::disconnect,
+            communication::set_auto_reconnect,
+            simulation::start_replay,
+            simulation::pause_replay,
+            simulation::resume_replay,
+            simulation::stop_replay,
+            frame_log::start_frame_log,
+            frame_log::stop_frame_log,
+            frame_log::read_frame_log,
+            operations::read_operations_log,
+            operations::export_diagnostics_bundle,
+            protocol::protocol_stats,
+            handshake::connection_info,
+            clock::correct_timestamps,
+            preflight::validate_path,
+            upload::upload_path,
+            control::emergency_stop,
+            control::return_home,
+            heartbeat::link_stats,
+            heartbeat::set_heartbeat_interval,
+            telemetry::nofix_readings,
+            health::boat_health,
+            health::boat_health_history,
+            health::set_health_thresholds,
+            health::export_health_csv,
+            progress::mission_progress,
+            tracking::current_position,
+            tracking::get_track,
+            tracking::set_position_event_rate,
+            tracking::export_track_geojson,
             comm_proto::find_ports,
             comm_proto::send_path,
             mbtiles::fetch_mbtiles,
             mbtiles::mbtiles_metadata,
-        ])
+            map_assets::ensure_map_assets,
+            map_assets::reset_map_assets,
+            regions::set_region_download_options,
+            regions::estimate_region_tiles,
+            regions::download_region,
+            regions::list_regions,
+            regions::delete_region,
+            settings::get_settings,
+            settings::update_settings,
+            settings::set_data_directory,
+            calibration::set_calibration,
+            calibration::recalibrate_dataset,
+            storage::storage_health,
+            storage::sqlite::migrate_to_sqlite,
+            storage::sqlite::export_sqlite_to_geojson,
+            windows::open_viewer_window,
+            errors::error_codes,
+            imports::cancel_import,
+        ]))
         .plugin(
             tauri_plugin_log::Builder::default()
                 .targets([LogTarget::LogDir, LogTarget::Stdout, LogTarget::Webview])
                 .build(),
         )
         .manage(comm_proto::ConnectedBoats::default())
+        .manage(communication::SerialConnection::default())
+        .manage(protocol::ProtocolState::default())
+        .manage(frame_log::FrameLogState::default())
+        .manage(operations::OperationsLog::default())
+        .manage(heartbeat::LinkStats::default())
+        .manage(handshake::ConnectionInfoState::default())
+        .manage(simulation::ReplayState::default())
+        .manage(telemetry::TelemetryState::default())
+        .manage(recovery::RecoveryState::default())
+        .manage(watcher::FileWatcherState::default())
+        .manage(tracking::TrackingState::default())
+        .manage(health::HealthState::default())
+        .manage(progress::ProgressState::default())
+        .manage(index::DataIndex::default())
+        .manage(app_data::AppData::default())
+        .manage(autosave::AutosaveState::default())
+        .manage(auto_export::AutoExportState::default())
+        .manage(path::CurrentPath::default())
+        .manage(history::PathHistory::default())
+        .manage(datasets::ActiveDataset::default())
+        .manage(map_assets::MapAssetsState::default())
+        .manage(tile_server::TileServer::default())
+        .manage(regions::RegionState::default())
+        .manage(settings::SettingsState::default())
+        .manage(storage::DataDirectory::default())
+        .manage(imports::ImportState::default())
+        .register_uri_scheme_protocol("tiles", tile_server::handle_request)
         .on_window_event(|event| {
+            // Closing the viewer keeps the connections used by the main window
             if let WindowEvent::Destroyed = event.event() {
+                if event.window().label() != windows::MAIN_LABEL {
+                    return;
+                }
                 // Dropping all connected ports when exiting
                 let boats: State<'_, comm_proto::ConnectedBoats> = event.window().state();
                 boats.boats.lock().unwrap().clear();
+                let serial: State<'_, communication::SerialConnection> = event.window().state();
+                serial.close(&event.window().app_handle());
             }
         })
         .setup(|app| {
+            if let Err(e) = settings::init_settings(&app.app_handle()) {
+                log::warn!("Unable to Load the Settings: {}", e);
+            }
+
+            if let Err(e) = datasets::init_datasets(&app.app_handle()) {
+                log::warn!("Unable to initialize the datasets: {}", e);
+            }
+
+            // Syncing the readings received to the recovery journal in the background
+            recovery::init_recovery(&app.app_handle());
+
+            // Loading the stored path for editing
+            if let Err(e) = path::read_path(app.app_handle()) {
+                log::warn!("Unable to read the stored path: {}", e);
+            }
+
+            // Downloading the map assets missing, e.g. on the first launch
+            let app_handle = app.app_handle();
+            let base_url = app_handle
+                .state::<settings::SettingsState>()
+                .get()
+                .tile_server_url;
+            tauri::async_runtime::spawn(async move {
+                if let Err(e) = map_assets::ensure_map_assets(app_handle, Some(base_url)).await {
+                    log::warn!("Unable to Download the Map Assets: {}", e);
+                }
+            });
+
             // Dropping all connected ports when exiting
             let app_handle = app.app_handle();
             ctrlc::set_handler(move || {
                 let boats: State<'_, comm_proto::ConnectedBoats> = app_handle.state();
                 boats.boats.lock().unwrap().clear();
+                let serial: State<'_, communication::SerialConnection> = app_handle.state();
+                serial.close(&app_handle);
+                autosave::flush_before_exit(&app_handle);
                 std::process::exit(0);
             })?;
             Ok(())
         })
-        .run(tauri::generate_context!())
-        .expect("error while running tauri application");
+        .build(tauri::generate_context!())
+        .expect("error while running tauri application")
+        .run(|app_handle, event| {
+            // Writing the edits not yet autosaved before exiting
+            if let RunEvent::ExitRequested { .. } = event {
+                autosave::flush_before_exit(app_handle);
+            }
+        });
 }
@@ -0,0 +1,398 @@
+//! Map data, style and fonts downloaded to application storage instead of being bundled.
+//!
+//! The assets are listed in a manifest with their sizes and SHA-256 checksums. The manifest is
+//! kept with the assets so they can be checked without a connection.
+
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{ErrorKind, Read, Write},
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tauri::{AppHandle, Manager};
+
+use crate::{
+    errors::{codes, AppError},
+    storage::{app_data_file, check_name, write_atomically},
+};
+
+/// The URL the assets are downloaded from when it is not specified.
+pub const DEFAULT_BASE_URL: &str = "https://babara-fanclub.github.io/map-assets";
+
+/// The name of the manifest, in the base URL and the map directory.
+const MANIFEST_NAME: &str = "manifest.json";
+
+/// The number of bytes downloaded between `asset-download-progress` events.
+const PROGRESS_BYTES: u64 = 256 * 1024;
+
+/// An asset listed in the manifest.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+struct Asset {
+    /// The path of the asset relative to the base URL and the map directory.
+    name: String,
+    /// The size of the asset in bytes.
+    size: u64,
+    /// The SHA-256 checksum of the asset in hexadecimal.
+    sha256: String,
+}
+
+impl Asset {
+    /// Gets the path of the asset in `dir`, checking it can't escape the directory.
+    fn path_in(&self, dir: &Path) -> Result<PathBuf, AppError> {
+        let mut path = dir.to_path_buf();
+        for component in self.name.split('/') {
+            check_name(component).map_err(|_| {
+                AppError::new(
+                    codes::MAP_ASSETS_INVALID_NAME,
+                    format!("Invalid Map Asset Name: {}", self.name),
+                )
+                .with("name", self.name.as_str())
+            })?;
+            path.push(component);
+        }
+        Ok(path)
+    }
+}
+
+/// The assets of the map.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+struct Manifest {
+    assets: Vec<Asset>,
+}
+
+/// Payload of the `asset-download-progress` event.
+#[derive(Debug, Serialize, Clone)]
+struct DownloadProgressPayload {
+    /// The name of the asset being downloaded.
+    asset: String,
+    /// The number of bytes downloaded, including a previous partial download.
+    downloaded: u64,
+    /// The size of the asset in bytes.
+    total: u64,
+}
+
+/// Gets the directory the map assets are stored in.
+pub fn map_dir(app_handle: &AppHandle) -> Result<PathBuf, AppError> {
+    app_data_file(app_handle, "map")
+}
+
+/// Gets the path of the partial download of `path`.
+fn part_path(path: &Path) -> PathBuf {
+    let mut part_path = path.as_os_str().to_owned();
+    part_path.push(".part");
+    PathBuf::from(part_path)
+}
+
+/// Creates the error of a download from `url` failing.
+fn download_error(url: &str, reason: impl std::fmt::Display) -> AppError {
+    AppError::reason(
+        codes::MAP_ASSETS_DOWNLOAD_FAILED,
+        &format!("Unable to Download {url}"),
+        reason,
+    )
+    .with("url", url)
+}
+
+/// Creates the error of the manifest being invalid.
+fn invalid_manifest(reason: serde_json::Error) -> AppError {
+    AppError::reason(
+        codes::MAP_ASSETS_INVALID_MANIFEST,
+        "Invalid Map Manifest",
+        reason,
+    )
+}
+
+/// Calculates the SHA-256 checksum of a file in hexadecimal.
+fn sha256_file(path: &Path) -> Result<String, AppError> {
+    let mut file = File::open(path).map_err(|e| AppError::io(e, path))?;
+    let mut hasher = Sha256::new();
+    let mut buf = [0; 64 * 1024];
+    loop {
+        match file.read(&mut buf).map_err(|e| AppError::io(e, path))? {
+            0 => break,
+            n => hasher.update(&buf[..n]),
+        }
+    }
+    Ok(hasher
+        .finalize()
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect())
+}
+
+/// Reads the manifest in the map directory, downloading it if it doesn't exist.
+fn load_manifest(dir: &Path, base_url: &str) -> Result<Manifest, AppError> {
+    let path = dir.join(MANIFEST_NAME);
+    match fs::read_to_string(&path) {
+        Ok(v) => return serde_json::from_str(&v).map_err(invalid_manifest),
+        Err(e) if e.kind() == ErrorKind::NotFound => {}
+        Err(e) => return Err(AppError::io(e, &path)),
+    }
+
+    log::info!("Downloading Map Manifest from: {}", base_url);
+    let url = format!("{base_url}/{MANIFEST_NAME}");
+    let content = ureq::get(&url)
+        .call()
+        .map_err(|e| download_error(&url, e))?
+        .into_string()
+        .map_err(|e| download_error(&url, e))?;
+    let manifest: Manifest = serde_json::from_str(&content).map_err(invalid_manifest)?;
+    fs::create_dir_all(dir).map_err(|e| AppError::io(e, dir))?;
+    write_atomically(&path, |file| file.write_all(content.as_bytes()))?;
+    Ok(manifest)
+}
+
+/// Downloads an asset to its partial download, continuing a previous partial download if the
+/// server supports it.
+fn download_part(
+    app_handle: &AppHandle,
+    asset: &Asset,
+    url: &str,
+    part: &Path,
+) -> Result<(), AppError> {
+    let mut downloaded = fs::metadata(part).map_or(0, |metadata| metadata.len());
+    if downloaded > asset.size {
+        downloaded = 0;
+    }
+    if downloaded == asset.size {
+        return Ok(());
+    }
+
+    let mut request = ureq::get(url);
+    if downloaded > 0 {
+        log::info!(
+            "Resuming Download of {} from {} Bytes",
+            asset.name,
+            downloaded
+        );
+        request = request.set("Range", &format!("bytes={downloaded}-"));
+    }
+    let response = request.call().map_err(|e| download_error(url, e))?;
+    // Starting again if the server sends the whole asset
+    let resume = downloaded > 0 && response.status() == 206;
+    if !resume {
+        downloaded = 0;
+    }
+
+    if let Some(parent) = part.parent() {
+        fs::create_dir_all(parent).map_err(|e| AppError::io(e, parent))?;
+    }
+    let mut file = OpenOptions::new()
+        .create(true)
+        .write(true)
+        .append(resume)
+        .truncate(!resume)
+        .open(part)
+        .map_err(|e| AppError::io(e, part))?;
+
+    let mut reader = response.into_reader();
+    let mut buf = [0; 64 * 1024];
+    let mut last_progress = downloaded;
+    loop {
+        let n = reader.read(&mut buf).map_err(|e| download_error(url, e))?;
+        if n == 0 {
+            break;
+        }
+        file.write_all(&buf[..n])
+            .map_err(|e| AppError::io(e, part))?;
+        downloaded += n as u64;
+
+        if downloaded - last_progress >= PROGRESS_BYTES || downloaded == asset.size {
+            last_progress = downloaded;
+            let payload = DownloadProgressPayload {
+                asset: asset.name.clone(),
+                downloaded,
+                total: asset.size,
+            };
+            let _ = app_handle.emit_all("asset-download-progress", payload);
+        }
+    }
+    file.sync_all().map_err(|e| AppError::io(e, part))
+}
+
+/// Downloads an asset, checking its checksum before moving it into place.
+///
+/// A partial download that fails the checksum is removed so the next download starts again.
+fn download_asset(
+    app_handle: &AppHandle,
+    asset: &Asset,
+    base_url: &str,
+    path: &Path,
+) -> Result<(), AppError> {
+    log::info!("Downloading Map Asset: {}", asset.name);
+    let part = part_path(path);
+    download_part(
+        app_handle,
+        asset,
+        &format!("{base_url}/{}", asset.name),
+        &part,
+    )?;
+
+    let checksum = sha256_file(&part)?;
+    if !checksum.eq_ignore_ascii_case(&asset.sha256) {
+        let _ = fs::remove_file(&part);
+        return Err(AppError::new(
+            codes::MAP_ASSETS_CHECKSUM_MISMATCH,
+            format!(
+                "Invalid Map Asset {}: Checksum {checksum} does not match {}",
+                asset.name, asset.sha256
+            ),
+        )
+        .with("name", asset.name.as_str()));
+    }
+    fs::rename(&part, path).map_err(|e| AppError::io(e, &part))
+}
+
+/// Downloads the assets missing from the map directory.
+///
+/// Returns the names of the assets downloaded.
+fn ensure_assets(app_handle: &AppHandle, base_url: &str) -> Result<Vec<String>, AppError> {
+    let dir = map_dir(app_handle)?;
+    let manifest = load_manifest(&dir, base_url)?;
+
+    let mut downloaded = vec![];
+    for asset in manifest.assets {
+        let path = asset.path_in(&dir)?;
+        let exists = fs::metadata(&path).map_or(false, |metadata| metadata.len() == asset.size);
+        if !exists {
+            download_asset(app_handle, &asset, base_url, &path)?;
+            downloaded.push(asset.name);
+        }
+    }
+    Ok(downloaded)
+}
+
+/// Prevents the map assets from being downloaded and reset at the same time.
+#[derive(Debug, Default)]
+pub struct MapAssetsState {
+    lock: Mutex<()>,
+}
+
+/// Download the map assets missing from application storage.
+///
+/// The assets are downloaded from `base_url`, or [`DEFAULT_BASE_URL`] if not given, emitting
+/// `asset-download-progress` events. Nothing is downloaded if all the assets exist. Returns the
+/// names of the assets downloaded.
+#[tauri::command]
+pub async fn ensure_map_assets(
+    app_handle: AppHandle,
+    base_url: Option<String>,
+) -> Result<Vec<String>, AppError> {
+    let base_url = base_url.unwrap_or_else(|| DEFAULT_BASE_URL.to_string());
+    let base_url = base_url.trim_end_matches('/').to_string();
+    tauri::async_runtime::spawn_blocking(move || {
+        let state = app_handle.state::<MapAssetsState>();
+        let _lock = state.lock.try_lock().map_err(|_| {
+            AppError::new(
+                codes::MAP_ASSETS_BUSY,
+                "Map Assets are Already Being Downloaded",
+            )
+        })?;
+        let downloaded = ensure_assets(&app_handle, &base_url)?;
+        log::info!("Downloaded {} Map Assets", downloaded.len());
+        Ok(downloaded)
+    })
+    .await
+    .map_err(|e| AppError::from(e.to_string()))?
+}
+
+/// Remove the map assets so they are downloaded again, e.g. when they are corrupted.
+#[tauri::command]
+pub fn reset_map_assets(
+    app_handle: AppHandle,
+    state: tauri::State<MapAssetsState>,
+) -> Result<(), AppError> {
+    let _lock = state
+        .lock
+        .try_lock()
+        .map_err(|_| AppError::new(codes::MAP_ASSETS_BUSY, "Map Assets are Being Downloaded"))?;
+    let dir = map_dir(&app_handle)?;
+    log::info!("Removing Map Assets: {}", dir.display());
+    match fs::remove_dir_all(&dir) {
+        Ok(_) => Ok(()),
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(()),
+        Err(e) => Err(AppError::io(e, &dir)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::storage::tests::temp_dir;
+
+    use super::*;
+
+    fn asset(name: &str) -> Asset {
+        Asset {
+            name: name.to_string(),
+            size: 3,
+            sha256: String::new(),
+        }
+    }
+
+    #[test]
+    fn asset_path_is_inside_the_map_directory() {
+        let dir = Path::new("map");
+
+        assert_eq!(
+            asset("fonts/Noto Sans/0-255.pbf").path_in(dir).unwrap(),
+            dir.join("fonts").join("Noto Sans").join("0-255.pbf")
+        );
+        assert!(asset("../secrets.json").path_in(dir).is_err());
+        assert!(asset("fonts/../../secrets.json").path_in(dir).is_err());
+        assert!(asset("/etc/passwd").path_in(dir).is_err());
+        assert!(asset("fonts//style.json").path_in(dir).is_err());
+    }
+
+    #[test]
+    fn partial_download_is_next_to_the_asset() {
+        assert_eq!(
+            part_path(Path::new("map/data.mbtiles")),
+            Path::new("map/data.mbtiles.part")
+        );
+    }
+
+    #[test]
+    fn sha256_of_a_file_is_hexadecimal() {
+        let dir = temp_dir("map-assets-sha256");
+        let path = dir.join("abc");
+        fs::write(&path, "abc").unwrap();
+
+        assert_eq!(
+            sha256_file(&path).unwrap(),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn existing_manifest_is_read_without_a_connection() {
+        let dir = temp_dir("map-assets-manifest");
+        let manifest = Manifest {
+            assets: vec![asset("style.json")],
+        };
+        fs::write(
+            dir.join(MANIFEST_NAME),
+            serde_json::to_string(&manifest).unwrap(),
+        )
+        .unwrap();
+
+        assert_eq!(load_manifest(&dir, "http://127.0.0.1:9").unwrap(), manifest);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn invalid_manifest_is_rejected() {
+        let dir = temp_dir("map-assets-invalid-manifest");
+        fs::write(dir.join(MANIFEST_NAME), "{\"assets\": 3}").unwrap();
+
+        let error = load_manifest(&dir, "http://127.0.0.1:9").unwrap_err();
+        assert!(
+            error.to_string().starts_with("Invalid Map Manifest"),
+            "{error}"
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
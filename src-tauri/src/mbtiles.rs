@@ -4,7 +4,20 @@ use std::{collections::HashMap, io::Read, str::FromStr};
 use flate2::read::GzDecoder;
 use sqlx::Connection;
 
-use crate::error_to_string;
+use crate::{
+    error_to_string,
+    errors::{codes, AppError},
+};
+
+/// Creates the error of the database `db` failing.
+fn database_error(db: &str, reason: sqlx::Error) -> AppError {
+    AppError::reason(
+        codes::MBTILES_DATABASE,
+        &format!("MBTiles Database Error: {db}"),
+        reason,
+    )
+    .with("db", db)
+}
 
 /// Fetches the tile data for the given MBTiles database, zoom level, column, and row.
 #[tauri::command]
@@ -13,10 +26,10 @@ pub async fn fetch_mbtiles(
     zoom: i32,
     column: i32,
     row: i32,
-) -> Result<Vec<u8>, String> {
+) -> Result<Vec<u8>, AppError> {
     let mut con = sqlx::SqliteConnection::connect(&db)
         .await
-        .map_err(error_to_string)?;
+        .map_err(|e| database_error(&db, e))?;
 
     let selection: (Vec<u8>,) = sqlx::query_as(
         "SELECT tile_data FROM tiles WHERE zoom_level = $1 AND tile_column = $2 AND tile_row = $3 LIMIT 1") 
@@ -25,14 +38,23 @@ pub async fn fetch_mbtiles(
         .bind(row)
         .fetch_one(&mut con)
         .await
-        .map_err(error_to_string)?;
+        .map_err(|e| match e {
+            sqlx::Error::RowNotFound => AppError::new(
+                codes::MBTILES_TILE_NOT_FOUND,
+                format!("No Tile at Zoom {zoom}, Column {column}, Row {row}"),
+            )
+            .with("zoom", zoom)
+            .with("column", column)
+            .with("row", row),
+            e => database_error(&db, e),
+        })?;
     let selection = selection.0;
 
     let decoder = GzDecoder::new(&*selection);
     decoder
         .bytes()
         .collect::<Result<_, _>>()
-        .map_err(error_to_string)
+        .map_err(|e| AppError::reason(codes::MBTILES_INVALID_TILE, "Invalid Tile Data", e))
 }
 
 /// Parse the bounds value of a MBTiles metadata.
@@ -93,23 +115,30 @@ fn parse_metadata(key: &str, value: String) -> Result<serde_json::Value, String>
 
 /// Retrieves the metadata for the given MBTiles database.
 #[tauri::command]
-pub async fn mbtiles_metadata(db: String) -> Result<HashMap<String, serde_json::Value>, String> {
+pub async fn mbtiles_metadata(db: String) -> Result<HashMap<String, serde_json::Value>, AppError> {
     let mut con = sqlx::SqliteConnection::connect(&db)
         .await
-        .map_err(error_to_string)?;
+        .map_err(|e| database_error(&db, e))?;
     log::error!("{}", db);
 
     let metadata: Vec<(String, String)> = sqlx::query_as("SELECT * FROM metadata")
         .fetch_all(&mut con)
         .await
-        .map_err(error_to_string)?;
+        .map_err(|e| database_error(&db, e))?;
     let mut metadata: HashMap<String, serde_json::Value> = metadata
         .into_iter()
         .map(|(k, v)| {
-            let value = parse_metadata(&k, v)?;
+            let value = parse_metadata(&k, v).map_err(|e| {
+                AppError::reason(
+                    codes::MBTILES_INVALID_METADATA,
+                    &format!("Invalid MBTiles Metadata {k}"),
+                    e,
+                )
+                .with("key", k.as_str())
+            })?;
             Ok((k, value))
         })
-        .collect::<Result<HashMap<_, _>, String>>()?;
+        .collect::<Result<HashMap<_, _>, AppError>>()?;
 
     // Flattening JSON value
     if let Some(serde_json::Value::Object(json_value)) = metadata.remove("json") {
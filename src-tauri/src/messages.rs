@@ -0,0 +1,256 @@
+//! Messages of the communication protocol that are not in the protobuf definitions yet.
+//!
+//! These are sent in a `Packet` like the other messages, using the packet types below.
+
+use crate::comm_proto::google::r#type::LatLng;
+
+/// Packet types of the messages, numbered after the packet types in the protobuf definitions.
+pub mod packet_type {
+    /// A chunk of an encoded `PlannedPath`.
+    pub const PATH_CHUNK: i32 = 16;
+    /// The boat accepted a message.
+    pub const ACK: i32 = 17;
+    /// The boat rejected a message.
+    pub const NACK: i32 = 18;
+    /// A reading of the sensors on the boat.
+    pub const READING: i32 = 19;
+    /// The position of the boat.
+    pub const POSITION: i32 = 20;
+    /// A command controlling the boat.
+    pub const CONTROL: i32 = 21;
+    /// A heartbeat sent to the boat.
+    pub const PING: i32 = 22;
+    /// The reply of the boat to a heartbeat.
+    pub const PONG: i32 = 23;
+    /// The versions and capabilities of each end, sent when connecting.
+    pub const HELLO: i32 = 24;
+    /// The battery and system health of the boat.
+    pub const HEALTH: i32 = 25;
+    /// The progress of the boat through its mission.
+    pub const PROGRESS: i32 = 26;
+    /// The times exchanged to measure the offset of the clock of the boat.
+    pub const TIME_SYNC: i32 = 27;
+}
+
+/// The path planned for the boat to follow.
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct PlannedPath {
+    /// The version of the path data.
+    #[prost(string, tag = "1")]
+    pub version: String,
+    /// The waypoints of the path in order.
+    #[prost(message, repeated, tag = "2")]
+    pub waypoints: Vec<LatLng>,
+    /// The points to collect data at.
+    #[prost(message, repeated, tag = "3")]
+    pub collection_points: Vec<LatLng>,
+}
+
+impl From<&crate::path::PathData> for PlannedPath {
+    fn from(value: &crate::path::PathData) -> Self {
+        Self {
+            version: value.version().to_string(),
+            waypoints: value.path().points().map(LatLng::from).collect(),
+            collection_points: value.collection_points().iter().map(LatLng::from).collect(),
+        }
+    }
+}
+
+/// A piece of a message too large to send in a single frame.
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct PathChunk {
+    /// The sequence number the boat replies with.
+    #[prost(uint32, tag = "1")]
+    pub sequence: u32,
+    /// The index of the chunk.
+    #[prost(uint32, tag = "2")]
+    pub index: u32,
+    /// The number of chunks in the message.
+    #[prost(uint32, tag = "3")]
+    pub total: u32,
+    /// The bytes of the message in the chunk.
+    #[prost(bytes = "vec", tag = "4")]
+    pub data: Vec<u8>,
+}
+
+/// A reading of the sensors on the boat.
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct Reading {
+    /// The temperature measured.
+    #[prost(double, tag = "1")]
+    pub temperature: f64,
+    /// The depth the temperature is measured at.
+    #[prost(double, tag = "2")]
+    pub depth: f64,
+    /// The layer the temperature is measured at.
+    #[prost(
+        enumeration = "crate::comm_proto::babara_project::data::boat_data::Layer",
+        tag = "3"
+    )]
+    pub layer: i32,
+    /// The time of the reading in milliseconds since the Unix epoch.
+    #[prost(int64, tag = "4")]
+    pub time_ms: i64,
+    /// Whether the GPS has a fix on the position.
+    #[prost(bool, tag = "5")]
+    pub fix: bool,
+    /// The position of the boat.
+    #[prost(message, optional, tag = "6")]
+    pub position: Option<LatLng>,
+    /// The id of the temperature sensor, empty if the boat doesn't send it.
+    #[prost(string, tag = "7")]
+    pub sensor_id: String,
+}
+
+/// The position of the boat, streamed once a second.
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct BoatPosition {
+    /// The position of the boat.
+    #[prost(message, optional, tag = "1")]
+    pub position: Option<LatLng>,
+    /// The heading of the boat in degrees clockwise from north.
+    #[prost(double, tag = "2")]
+    pub heading: f64,
+    /// The speed of the boat in metres per second.
+    #[prost(double, tag = "3")]
+    pub speed: f64,
+    /// The time of the position in milliseconds since the Unix epoch.
+    #[prost(int64, tag = "4")]
+    pub time_ms: i64,
+}
+
+/// The commands controlling the boat.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, prost::Enumeration)]
+#[repr(i32)]
+pub enum ControlCommand {
+    Undefined = 0,
+    /// Stop the boat immediately.
+    EmergencyStop = 1,
+    /// Return the boat to where it started.
+    ReturnHome = 2,
+}
+
+/// The battery and system health of the boat, streamed periodically.
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct Health {
+    /// The voltage of the battery in volts.
+    #[prost(double, tag = "1")]
+    pub battery_voltage: f64,
+    /// The current drawn from the battery in amperes.
+    #[prost(double, tag = "2")]
+    pub current: f64,
+    /// The strength of the radio signal received by the boat in dBm.
+    #[prost(double, tag = "3")]
+    pub signal_strength: f64,
+    /// The temperature inside the hull in degrees Celsius.
+    #[prost(double, tag = "4")]
+    pub temperature: f64,
+    /// The time of the report in milliseconds since the Unix epoch.
+    #[prost(int64, tag = "5")]
+    pub time_ms: i64,
+}
+
+/// The progress of the boat through its mission, sent while running a mission.
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct Progress {
+    /// The index of the waypoint the boat is heading to.
+    #[prost(uint32, tag = "1")]
+    pub waypoint_index: u32,
+    /// The indices of the collection points the data is collected at.
+    #[prost(uint32, repeated, tag = "2")]
+    pub completed_points: Vec<u32>,
+    /// The distance left along the path in metres.
+    #[prost(double, tag = "3")]
+    pub distance_remaining: f64,
+    /// The time of the report in milliseconds since the Unix epoch.
+    #[prost(int64, tag = "4")]
+    pub time_ms: i64,
+}
+
+/// A command controlling the boat.
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct Control {
+    /// The sequence number the boat replies with.
+    #[prost(uint32, tag = "1")]
+    pub sequence: u32,
+    /// The command to the boat.
+    #[prost(enumeration = "ControlCommand", tag = "2")]
+    pub command: i32,
+}
+
+/// A heartbeat checking the boat is reachable.
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct Ping {
+    /// The sequence number the boat replies with.
+    #[prost(uint32, tag = "1")]
+    pub sequence: u32,
+}
+
+/// The reply of the boat to a heartbeat.
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct Pong {
+    /// The sequence number of the heartbeat.
+    #[prost(uint32, tag = "1")]
+    pub sequence: u32,
+}
+
+/// Reply from the boat accepting a message.
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct Ack {
+    /// The sequence number of the message accepted.
+    #[prost(uint32, tag = "1")]
+    pub sequence: u32,
+}
+
+/// Reply from the boat rejecting a message.
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct Nack {
+    /// The sequence number of the message rejected.
+    #[prost(uint32, tag = "1")]
+    pub sequence: u32,
+    /// The reason the message is rejected.
+    #[prost(string, tag = "2")]
+    pub reason: String,
+}
+
+/// The versions and capabilities of each end, sent when connecting.
+///
+/// The boat replies to the hello of the desktop application with its own hello.
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct Hello {
+    /// The sequence number the boat replies with.
+    #[prost(uint32, tag = "1")]
+    pub sequence: u32,
+    /// The version of the communication protocol.
+    #[prost(string, tag = "2")]
+    pub protocol_version: String,
+    /// The version of the firmware or desktop application.
+    #[prost(string, tag = "3")]
+    pub software_version: String,
+    /// The revision of the hardware of the boat.
+    #[prost(string, tag = "4")]
+    pub hardware_revision: String,
+    /// The packet types supported.
+    #[prost(int32, repeated, tag = "5")]
+    pub supported_types: Vec<i32>,
+}
+
+/// The times exchanged to measure the offset of the clock of the boat, sent when connecting.
+///
+/// The boat replies with the same message, filling in the times it received the message and
+/// sent the reply by its own clock.
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct TimeSync {
+    /// The sequence number the boat replies with.
+    #[prost(uint32, tag = "1")]
+    pub sequence: u32,
+    /// The time the desktop application sent the message in milliseconds since the Unix epoch.
+    #[prost(int64, tag = "2")]
+    pub origin_ms: i64,
+    /// The time the boat received the message in milliseconds since the Unix epoch.
+    #[prost(int64, tag = "3")]
+    pub receive_ms: i64,
+    /// The time the boat sent the reply in milliseconds since the Unix epoch.
+    #[prost(int64, tag = "4")]
+    pub transmit_ms: i64,
+}
@@ -0,0 +1,296 @@
+//! Information about where and how a dataset was collected, e.g. the water level of the lake,
+//! so datasets collected in different seasons can be compared.
+//!
+//! The metadata is stored as foreign members of the boat data GeoJSON next to its version, and
+//! in the members table of the database when SQLite storage is used.
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use tauri::{AppHandle, Manager};
+
+use crate::{
+    app_data::AppData,
+    conditions::Conditions,
+    errors::{codes, AppError},
+};
+
+/// The foreign members of the boat data GeoJSON holding the metadata.
+const MEMBERS: [&str; 7] = [
+    "water_level_m",
+    "water_level_datum",
+    "site_name",
+    "operator",
+    "notes",
+    "clock_offset_ms",
+    "conditions",
+];
+
+/// Information about where and how a dataset was collected.
+#[derive(Debug, Default, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(default)]
+pub struct DatasetMetadata {
+    /// The level of the water surface in meters above `water_level_datum`, as the depths are
+    /// measured from the surface.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub water_level_m: Option<f64>,
+    /// The name of the datum the water level is measured from, e.g. `NAVD88`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub water_level_datum: Option<String>,
+    /// The name of the site the data was collected at.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub site_name: Option<String>,
+    /// The name of the operator of the boat.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub operator: Option<String>,
+    /// The notes of the operator on the dataset.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub notes: Option<String>,
+    /// How far the clock of the boat was ahead in milliseconds, which the times of the readings
+    /// are corrected by.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub clock_offset_ms: Option<i64>,
+    /// The weather when the data was collected.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub conditions: Option<Conditions>,
+}
+
+impl DatasetMetadata {
+    /// Reads the metadata from the foreign members of boat data.
+    ///
+    /// Members of the wrong type are ignored.
+    pub fn from_members(members: &Map<String, Value>) -> Self {
+        let get = |name: &str| members.get(name).and_then(Value::as_str).map(String::from);
+        Self {
+            water_level_m: members.get("water_level_m").and_then(Value::as_f64),
+            water_level_datum: get("water_level_datum"),
+            site_name: get("site_name"),
+            operator: get("operator"),
+            notes: get("notes"),
+            clock_offset_ms: members.get("clock_offset_ms").and_then(Value::as_i64),
+            conditions: members
+                .get("conditions")
+                .and_then(|v| serde_json::from_value(v.clone()).ok()),
+        }
+    }
+
+    /// Writes the metadata to the foreign members of boat data, replacing the previous
+    /// metadata and keeping the other members.
+    pub fn write_members(&self, members: &mut Map<String, Value>) {
+        for name in MEMBERS {
+            members.remove(name);
+        }
+        if let Ok(Value::Object(values)) = serde_json::to_value(self) {
+            members.extend(values);
+        }
+    }
+
+    /// Checks the metadata is valid, returning it with the blank text removed.
+    fn validate(self) -> Result<Self, AppError> {
+        if let Some(level) = self.water_level_m {
+            if !level.is_finite() {
+                return Err(AppError::new(
+                    codes::METADATA_INVALID_WATER_LEVEL,
+                    format!("Invalid Water Level: {level}"),
+                )
+                .with("water_level_m", level));
+            }
+        }
+        let text = |value: Option<String>| {
+            value
+                .map(|v| v.trim().to_string())
+                .filter(|v| !v.is_empty())
+        };
+        Ok(Self {
+            water_level_m: self.water_level_m,
+            water_level_datum: text(self.water_level_datum),
+            site_name: text(self.site_name),
+            operator: text(self.operator),
+            notes: text(self.notes),
+            clock_offset_ms: self.clock_offset_ms,
+            conditions: self.conditions,
+        })
+    }
+
+    /// Gets the names and values of the metadata that is set, to be written in exports.
+    pub fn rows(&self) -> Vec<(&'static str, String)> {
+        let mut rows = vec![];
+        if let Some(site_name) = &self.site_name {
+            rows.push(("Site", site_name.clone()));
+        }
+        if let Some(operator) = &self.operator {
+            rows.push(("Operator", operator.clone()));
+        }
+        if let Some(level) = self.water_level_m {
+            let level = match &self.water_level_datum {
+                Some(datum) => format!("{level} m above {datum}"),
+                None => format!("{level} m"),
+            };
+            rows.push(("Water Level", level));
+        }
+        if let Some(conditions) = &self.conditions {
+            rows.extend(conditions.rows());
+        }
+        if let Some(offset) = self.clock_offset_ms {
+            rows.push(("Clock Offset", format!("{offset} ms")));
+        }
+        if let Some(notes) = &self.notes {
+            rows.push(("Notes", notes.clone()));
+        }
+        rows
+    }
+
+    /// Writes the metadata as comment lines at the start of CSV, e.g. `# Site: Blue Lake`.
+    pub fn write_csv_comments(&self, writer: &mut dyn std::io::Write) -> std::io::Result<()> {
+        for (name, value) in self.rows() {
+            // Each line of the notes is its own comment
+            for line in value.lines() {
+                writeln!(writer, "# {name}: {line}")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Get the metadata of the active dataset.
+#[tauri::command]
+pub fn get_dataset_metadata(app_handle: AppHandle) -> Result<DatasetMetadata, AppError> {
+    log::debug!("Getting Dataset Metadata");
+    Ok(app_handle.state::<AppData>().get(&app_handle)?.metadata())
+}
+
+/// Set the metadata of the active dataset, returning it with the blank text removed.
+///
+/// The metadata is saved with the boat data by the autosave.
+#[tauri::command]
+pub fn set_dataset_metadata(
+    app_handle: AppHandle,
+    metadata: DatasetMetadata,
+) -> Result<DatasetMetadata, AppError> {
+    log::debug!("Setting Dataset Metadata");
+    let metadata = metadata.validate()?;
+    app_handle.state::<AppData>().update(&app_handle, |data| {
+        data.set_metadata(&metadata);
+        Ok(())
+    })?;
+    Ok(metadata)
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeZone, Utc};
+    use serde_json::json;
+
+    use super::*;
+    use crate::conditions::ConditionsSource;
+
+    /// Creates metadata with every field set.
+    fn metadata() -> DatasetMetadata {
+        DatasetMetadata {
+            water_level_m: Some(12.5),
+            water_level_datum: Some(String::from("NAVD88")),
+            site_name: Some(String::from("Blue Lake")),
+            operator: Some(String::from("Aida")),
+            notes: Some(String::from("Windy\nAlgae near the jetty")),
+            clock_offset_ms: Some(-1500),
+            conditions: Some(Conditions {
+                time: Utc.with_ymd_and_hms(2024, 6, 1, 2, 0, 0).unwrap(),
+                air_temperature: Some(24.5),
+                wind_speed: Some(3.0),
+                wind_direction: Some(180.0),
+                cloud_cover: None,
+                source: ConditionsSource::Api,
+            }),
+        }
+    }
+
+    #[test]
+    fn metadata_round_trips_through_the_members() {
+        let mut members = Map::new();
+
+        metadata().write_members(&mut members);
+
+        assert_eq!(members["water_level_m"], json!(12.5));
+        assert_eq!(DatasetMetadata::from_members(&members), metadata());
+    }
+
+    #[test]
+    fn writing_keeps_the_other_members() {
+        let mut members = Map::new();
+        members.insert(String::from("crs"), json!({ "type": "name" }));
+        metadata().write_members(&mut members);
+
+        DatasetMetadata::default().write_members(&mut members);
+
+        assert_eq!(members.keys().collect::<Vec<_>>(), ["crs"]);
+    }
+
+    #[test]
+    fn members_of_the_wrong_type_are_ignored() {
+        let members = json!({ "water_level_m": "high", "site_name": 3, "operator": "Aida" });
+        let members = members.as_object().unwrap();
+
+        assert_eq!(
+            DatasetMetadata::from_members(members),
+            DatasetMetadata {
+                operator: Some(String::from("Aida")),
+                ..DatasetMetadata::default()
+            }
+        );
+    }
+
+    #[test]
+    fn validation_removes_blank_text() {
+        let metadata = DatasetMetadata {
+            site_name: Some(String::from("  Blue Lake ")),
+            operator: Some(String::from("   ")),
+            ..DatasetMetadata::default()
+        };
+
+        assert_eq!(
+            metadata.validate(),
+            Ok(DatasetMetadata {
+                site_name: Some(String::from("Blue Lake")),
+                ..DatasetMetadata::default()
+            })
+        );
+    }
+
+    #[test]
+    fn validation_rejects_invalid_water_levels() {
+        let metadata = DatasetMetadata {
+            water_level_m: Some(f64::INFINITY),
+            ..DatasetMetadata::default()
+        };
+
+        let error = serde_json::to_value(metadata.validate().unwrap_err()).unwrap();
+
+        assert_eq!(error["code"], codes::METADATA_INVALID_WATER_LEVEL);
+    }
+
+    #[test]
+    fn csv_comments_list_the_metadata_that_is_set() {
+        let mut content = vec![];
+        metadata().write_csv_comments(&mut content).unwrap();
+
+        assert_eq!(
+            String::from_utf8(content).unwrap(),
+            "# Site: Blue Lake\n\
+             # Operator: Aida\n\
+             # Water Level: 12.5 m above NAVD88\n\
+             # Air Temperature: 24.5 C\n\
+             # Wind: 3 m/s from 180 degrees\n\
+             # Conditions Observed: 2024-06-01 02:00 UTC (Weather API)\n\
+             # Clock Offset: -1500 ms\n\
+             # Notes: Windy\n\
+             # Notes: Algae near the jetty\n"
+        );
+
+        let mut content = vec![];
+        let metadata = DatasetMetadata {
+            water_level_m: Some(-1.0),
+            ..DatasetMetadata::default()
+        };
+        metadata.write_csv_comments(&mut content).unwrap();
+        assert_eq!(String::from_utf8(content).unwrap(), "# Water Level: -1 m\n");
+    }
+}
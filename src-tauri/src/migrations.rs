@@ -0,0 +1,349 @@
+//! Version checking and migrations of stored GeoJSON to the current formats.
+
+use std::fmt::Display;
+
+use geojson::{Feature, FeatureCollection, GeoJson, JsonObject};
+use semver::{Version, VersionReq};
+use serde_json::json;
+
+use crate::errors::{codes, AppError};
+
+/// The current version of the boat data format.
+pub const BOAT_DATA_VERSION: &str = "0.2.0";
+
+/// The current version of the path data format.
+pub const PATH_DATA_VERSION: &str = "0.1.0";
+
+/// A step upgrading a feature collection to the next format version.
+pub type Migration = fn(&mut FeatureCollection);
+
+/// Versions of the boat data format that are read without migrating.
+///
+/// The sensor values added in 0.2 are optional, so 0.1 data is read as it is.
+const BOAT_DATA_CURRENT: &str = ">=0.1, <0.3";
+
+/// Migrations of older boat data formats, ordered from oldest to newest.
+const BOAT_DATA_MIGRATIONS: &[(&str, Migration)] = &[("^0.0", add_default_layer)];
+
+/// Versions of the path data format that are read without migrating.
+const PATH_DATA_CURRENT: &str = "^0.1";
+
+/// Migrations of older path data formats, ordered from oldest to newest.
+const PATH_DATA_MIGRATIONS: &[(&str, Migration)] = &[];
+
+/// Errors when reading stored data.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DataError {
+    /// The version is not a valid semantic version.
+    InvalidVersion(String),
+    /// The version can't be read by this version of the application.
+    UnsupportedVersion {
+        /// The version found in the data.
+        found: String,
+        /// The versions that can be read.
+        supported: Vec<String>,
+    },
+    /// The GeoJSON is not a feature collection.
+    InvalidGeoJson,
+}
+
+impl Display for DataError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::InvalidVersion(version) => write!(f, "Invalid Version: {version}"),
+            Self::UnsupportedVersion { found, supported } => write!(
+                f,
+                "Unsupported Version: {found}, supported versions are {}",
+                supported.join(", ")
+            ),
+            Self::InvalidGeoJson => write!(f, "Invalid GeoJSON: Expected a Feature Collection"),
+        }
+    }
+}
+
+impl std::error::Error for DataError {}
+
+impl From<DataError> for String {
+    fn from(value: DataError) -> Self {
+        value.to_string()
+    }
+}
+
+impl From<DataError> for AppError {
+    fn from(value: DataError) -> Self {
+        let message = value.to_string();
+        match value {
+            DataError::InvalidVersion(version) => {
+                AppError::new(codes::MIGRATION_INVALID_VERSION, message).with("version", version)
+            }
+            DataError::UnsupportedVersion { found, supported } => {
+                AppError::new(codes::MIGRATION_UNSUPPORTED_VERSION, message)
+                    .with("found", found)
+                    .with("supported", supported)
+            }
+            DataError::InvalidGeoJson => AppError::new(codes::MIGRATION_INVALID_GEOJSON, message),
+        }
+    }
+}
+
+/// Migrate boat data GeoJSON from version `from` to the current format.
+pub fn migrate(value: GeoJson, from: &str) -> Result<GeoJson, DataError> {
+    migrate_collection(
+        value,
+        from,
+        BOAT_DATA_CURRENT,
+        BOAT_DATA_MIGRATIONS,
+        BOAT_DATA_VERSION,
+    )
+}
+
+/// Migrate path data GeoJSON from version `from` to the current format.
+pub fn migrate_path(value: GeoJson, from: &str) -> Result<GeoJson, DataError> {
+    migrate_collection(
+        value,
+        from,
+        PATH_DATA_CURRENT,
+        PATH_DATA_MIGRATIONS,
+        PATH_DATA_VERSION,
+    )
+}
+
+/// Runs every migration from the first one matching `from`.
+fn migrate_collection(
+    value: GeoJson,
+    from: &str,
+    current: &str,
+    migrations: &[(&str, Migration)],
+    version: &str,
+) -> Result<GeoJson, DataError> {
+    let steps = plan(from, current, migrations)?;
+    let mut collection =
+        FeatureCollection::try_from(value).map_err(|_| DataError::InvalidGeoJson)?;
+    for migration in steps {
+        migration(&mut collection);
+    }
+
+    collection
+        .foreign_members
+        .get_or_insert_with(JsonObject::new)
+        .insert(String::from("version"), json!(version));
+    Ok(GeoJson::from(collection))
+}
+
+/// Gets the migrations to run on data of version `from`, empty if it is already current.
+fn plan(
+    from: &str,
+    current: &str,
+    migrations: &[(&str, Migration)],
+) -> Result<Vec<Migration>, DataError> {
+    let found = Version::parse(from).map_err(|_| DataError::InvalidVersion(from.to_string()))?;
+
+    let mut steps = vec![];
+    for (requirement, migration) in migrations {
+        if !steps.is_empty() || matches(requirement, &found) {
+            log::info!("Migrating from Version {} using {}", from, requirement);
+            steps.push(*migration);
+        }
+    }
+
+    if steps.is_empty() && !matches(current, &found) {
+        return Err(DataError::UnsupportedVersion {
+            found: from.to_string(),
+            supported: migrations
+                .iter()
+                .map(|(requirement, _)| requirement.to_string())
+                .chain(std::iter::once(current.to_string()))
+                .collect(),
+        });
+    }
+    Ok(steps)
+}
+
+/// Gets the migrations upgrading boat data from version `from`, for migrating the features one
+/// at a time with [`migrate_feature`].
+pub fn boat_data_migrations(from: &str) -> Result<Vec<Migration>, DataError> {
+    plan(from, BOAT_DATA_CURRENT, BOAT_DATA_MIGRATIONS)
+}
+
+/// Runs the migrations on a single feature.
+pub fn migrate_feature(feature: Feature, migrations: &[Migration]) -> Feature {
+    if migrations.is_empty() {
+        return feature;
+    }
+
+    let mut collection = FeatureCollection {
+        bbox: None,
+        features: vec![feature],
+        foreign_members: None,
+    };
+    for migration in migrations {
+        migration(&mut collection);
+    }
+    // The migrations only change the features, so the feature is still there
+    collection.features.pop().unwrap()
+}
+
+/// Checks if `version` matches the requirement.
+fn matches(requirement: &str, version: &Version) -> bool {
+    // The requirements are constants so they are always valid
+    VersionReq::parse(requirement).unwrap().matches(version)
+}
+
+/// Adds the layer property to 0.0.x boat data, which was only collected from the surface.
+fn add_default_layer(collection: &mut FeatureCollection) {
+    for feature in &mut collection.features {
+        feature
+            .properties
+            .get_or_insert_with(JsonObject::new)
+            .entry("layer")
+            .or_insert_with(|| json!("surface"));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::{BoatData, Layer};
+
+    /// Creates boat data GeoJSON of version `version` with a feature without a layer.
+    fn boat_data(version: &str) -> GeoJson {
+        let value = json!({
+            "type": "FeatureCollection",
+            "version": version,
+            "features": [{
+                "type": "Feature",
+                "geometry": { "type": "Point", "coordinates": [101.5, 3.1] },
+                "properties": {
+                    "temperature": 28.5,
+                    "depth": 0.5,
+                    "time": "2024-05-12T06:30:00Z"
+                }
+            }]
+        });
+        GeoJson::from_json_value(value).unwrap()
+    }
+
+    /// Gets the version and features of migrated GeoJSON.
+    fn collection(value: GeoJson) -> FeatureCollection {
+        FeatureCollection::try_from(value).unwrap()
+    }
+
+    #[test]
+    fn migrate_adds_surface_layer_to_0_0_data() {
+        let migrated = collection(migrate(boat_data("0.0.3"), "0.0.3").unwrap());
+
+        let properties = migrated.features[0].properties.as_ref().unwrap();
+        assert_eq!(properties.get("layer"), Some(&json!("surface")));
+        assert_eq!(
+            migrated.foreign_members.unwrap().get("version"),
+            Some(&json!(BOAT_DATA_VERSION))
+        );
+    }
+
+    #[test]
+    fn migrate_keeps_existing_layer() {
+        let mut value = collection(boat_data("0.0.1"));
+        value.features[0]
+            .properties
+            .as_mut()
+            .unwrap()
+            .insert(String::from("layer"), json!("sea_bed"));
+
+        let migrated = collection(migrate(GeoJson::from(value), "0.0.1").unwrap());
+
+        let properties = migrated.features[0].properties.as_ref().unwrap();
+        assert_eq!(properties.get("layer"), Some(&json!("sea_bed")));
+    }
+
+    #[test]
+    fn boat_data_from_0_0_is_read_from_the_surface() {
+        let data: BoatData = boat_data("0.0.1").to_string().parse().unwrap();
+
+        assert_eq!(data.version(), BOAT_DATA_VERSION);
+        assert_eq!(data.features().len(), 1);
+        assert_eq!(data.features()[0].layer(), Layer::Surface);
+    }
+
+    #[test]
+    fn migrate_current_version_runs_no_migration() {
+        let migrated = collection(migrate(boat_data("0.1.0"), "0.1.0").unwrap());
+
+        let properties = migrated.features[0].properties.as_ref().unwrap();
+        assert_eq!(properties.get("layer"), None);
+    }
+
+    #[test]
+    fn migrate_rejects_future_major_version() {
+        let error = migrate(boat_data("1.0.0"), "1.0.0").unwrap_err();
+
+        assert_eq!(
+            error,
+            DataError::UnsupportedVersion {
+                found: String::from("1.0.0"),
+                supported: vec![String::from("^0.0"), String::from(BOAT_DATA_CURRENT)],
+            }
+        );
+        let message = error.to_string();
+        assert!(message.contains("1.0.0"));
+        assert!(message.contains(BOAT_DATA_CURRENT));
+    }
+
+    #[test]
+    fn migrate_rejects_invalid_version() {
+        assert_eq!(
+            migrate(boat_data("latest"), "latest").unwrap_err(),
+            DataError::InvalidVersion(String::from("latest"))
+        );
+    }
+
+    #[test]
+    fn migrate_path_rejects_future_major_version() {
+        let value = GeoJson::from_json_value(json!({
+            "type": "FeatureCollection",
+            "version": "2.0.0",
+            "features": []
+        }))
+        .unwrap();
+
+        assert_eq!(
+            migrate_path(value, "2.0.0").unwrap_err(),
+            DataError::UnsupportedVersion {
+                found: String::from("2.0.0"),
+                supported: vec![String::from(PATH_DATA_CURRENT)],
+            }
+        );
+    }
+
+    #[test]
+    fn boat_data_with_future_major_version_has_code() {
+        let error = boat_data("1.0.0")
+            .to_string()
+            .parse::<BoatData>()
+            .unwrap_err();
+
+        let error = serde_json::to_value(error).unwrap();
+        assert_eq!(error["code"], json!(codes::MIGRATION_UNSUPPORTED_VERSION));
+        assert_eq!(error["params"]["found"], json!("1.0.0"));
+    }
+
+    #[test]
+    fn boat_data_migrations_are_planned_from_the_version() {
+        assert_eq!(boat_data_migrations("0.0.1").unwrap().len(), 1);
+        assert!(boat_data_migrations(BOAT_DATA_VERSION).unwrap().is_empty());
+        assert!(boat_data_migrations("9.0.0").is_err());
+        assert_eq!(
+            boat_data_migrations("latest").err(),
+            Some(DataError::InvalidVersion(String::from("latest")))
+        );
+    }
+
+    #[test]
+    fn migrate_feature_runs_the_migrations() {
+        let feature = collection(boat_data("0.0.1")).features.remove(0);
+
+        let migrated = migrate_feature(feature.clone(), &boat_data_migrations("0.0.1").unwrap());
+        let properties = migrated.properties.as_ref().unwrap();
+        assert_eq!(properties.get("layer"), Some(&json!("surface")));
+        assert_eq!(migrate_feature(feature.clone(), &[]), feature);
+    }
+}
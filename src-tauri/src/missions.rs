@@ -0,0 +1,351 @@
+//! Named paths in application storage, one for each site surveyed.
+
+use std::{
+    fs,
+    io::ErrorKind,
+    path::{Path, PathBuf},
+    str::FromStr,
+    time::SystemTime,
+};
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use tauri::{AppHandle, Manager};
+
+use crate::{
+    errors::{codes, AppError},
+    history::PathHistory,
+    path::{self, CurrentPath, PathData},
+    storage::{app_data_file, check_name},
+};
+
+/// The extension of the mission files.
+const EXTENSION: &str = ".geojson";
+
+/// Information on a mission in application storage.
+#[derive(Debug, Serialize)]
+pub struct MissionInfo {
+    /// The name of the mission.
+    name: String,
+    /// The time the mission is created, if supported by the file system.
+    created: Option<DateTime<Utc>>,
+    /// The time the mission is last saved.
+    modified: Option<DateTime<Utc>>,
+    /// The number of waypoints in the path.
+    vertices: usize,
+    /// The geodesic length of the path in meters.
+    length: f64,
+}
+
+/// Gets the directory the missions are stored in.
+pub fn missions_dir(app_handle: &AppHandle) -> Result<PathBuf, AppError> {
+    app_data_file(app_handle, "missions")
+}
+
+/// Gets the file a mission is stored in.
+pub fn mission_file(app_handle: &AppHandle, name: &str) -> Result<PathBuf, AppError> {
+    file_in(&missions_dir(app_handle)?, name)
+}
+
+/// Gets the file mission `name` is stored in under `dir`.
+fn file_in(dir: &Path, name: &str) -> Result<PathBuf, AppError> {
+    check_name(name)?;
+    Ok(dir.join(format!("{name}{EXTENSION}")))
+}
+
+/// Creates the error of the mission `name` not existing.
+fn not_found(name: &str) -> AppError {
+    AppError::new(
+        codes::MISSION_NOT_FOUND,
+        format!("Unable to find Mission: {name}"),
+    )
+    .with("name", name)
+}
+
+/// Gets the names of the missions in `dir` sorted alphabetically.
+fn mission_names(dir: &Path) -> Result<Vec<String>, AppError> {
+    let entries = match fs::read_dir(dir) {
+        Ok(v) => v,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(vec![]),
+        Err(e) => return Err(AppError::io(e, dir)),
+    };
+
+    let mut names: Vec<String> = entries
+        .filter_map(|entry| entry.ok()?.file_name().into_string().ok())
+        .filter_map(|name| Some(name.strip_suffix(EXTENSION)?.to_string()))
+        .filter(|name| check_name(name).is_ok())
+        .collect();
+    names.sort();
+    Ok(names)
+}
+
+/// Reads a mission from its file.
+fn read_mission(file: &Path, name: &str) -> Result<PathData, AppError> {
+    match fs::read_to_string(file) {
+        Ok(v) => PathData::from_str(&v),
+        Err(e) if e.kind() == ErrorKind::NotFound => Err(not_found(name)),
+        Err(e) => Err(AppError::io(e, file)),
+    }
+}
+
+/// Deletes mission `name` stored in `dir`.
+fn delete(dir: &Path, name: &str) -> Result<(), AppError> {
+    let file = file_in(dir, name)?;
+    fs::remove_file(&file).map_err(|e| match e.kind() {
+        ErrorKind::NotFound => not_found(name),
+        _ => AppError::io(e, &file),
+    })
+}
+
+/// Renames mission `old` stored in `dir` to `new`.
+///
+/// The mission is linked to its new name before the old name is removed, so a mission saved
+/// with the new name at the same time is never replaced.
+fn rename(dir: &Path, old: &str, new: &str) -> Result<(), AppError> {
+    let old_file = file_in(dir, old)?;
+    let new_file = file_in(dir, new)?;
+    fs::hard_link(&old_file, new_file).map_err(|e| match e.kind() {
+        ErrorKind::NotFound => not_found(old),
+        ErrorKind::AlreadyExists => AppError::new(
+            codes::MISSION_ALREADY_EXISTS,
+            format!("Mission Already Exists: {new}"),
+        )
+        .with("name", new),
+        _ => AppError::io(e, &old_file),
+    })?;
+    fs::remove_file(&old_file).map_err(|e| AppError::io(e, &old_file))
+}
+
+/// Gets the name of the current mission.
+fn current_mission(app_handle: &AppHandle) -> Option<String> {
+    app_handle
+        .state::<CurrentPath>()
+        .mission
+        .lock()
+        .unwrap()
+        .clone()
+}
+
+/// List the missions in application storage.
+#[tauri::command]
+pub fn list_missions(app_handle: AppHandle) -> Result<Vec<MissionInfo>, AppError> {
+    log::debug!("Listing Missions");
+    let dir = missions_dir(&app_handle)?;
+
+    let mut missions = vec![];
+    for name in mission_names(&dir)? {
+        let file = dir.join(format!("{name}{EXTENSION}"));
+        let path = match read_mission(&file, &name) {
+            Ok(v) => v,
+            Err(e) => {
+                log::warn!("Unable to Read Mission: {}, Reason: {}", name, e);
+                continue;
+            }
+        };
+        let metadata = fs::metadata(&file).map_err(|e| AppError::io(e, &file))?;
+        let time = |time: std::io::Result<SystemTime>| time.ok().map(DateTime::<Utc>::from);
+
+        missions.push(MissionInfo {
+            created: time(metadata.created()),
+            modified: time(metadata.modified()),
+            vertices: path.path().0.len(),
+            length: path.length(),
+            name,
+        });
+    }
+    Ok(missions)
+}
+
+/// Save a path as a mission, replacing the mission with the same name.
+#[tauri::command]
+pub fn save_mission(app_handle: AppHandle, name: String, path: PathData) -> Result<(), AppError> {
+    log::info!("Saving Mission: {}", name);
+    let file = mission_file(&app_handle, &name)?;
+    let dir = missions_dir(&app_handle)?;
+    fs::create_dir_all(&dir).map_err(|e| AppError::io(e, &dir))?;
+    path::export_path(file, path)
+}
+
+/// Load a mission from application storage.
+#[tauri::command]
+pub fn load_mission(app_handle: AppHandle, name: String) -> Result<PathData, AppError> {
+    log::info!("Loading Mission: {}", name);
+    read_mission(&mission_file(&app_handle, &name)?, &name)
+}
+
+/// Delete a mission from application storage.
+///
+/// The current mission can't be deleted.
+#[tauri::command]
+pub fn delete_mission(app_handle: AppHandle, name: String) -> Result<(), AppError> {
+    log::info!("Deleting Mission: {}", name);
+    check_name(&name)?;
+    if current_mission(&app_handle).as_deref() == Some(name.as_str()) {
+        return Err(AppError::new(
+            codes::MISSION_CURRENT,
+            format!("Unable to Delete the Current Mission: {name}"),
+        )
+        .with("name", name));
+    }
+    delete(&missions_dir(&app_handle)?, &name)
+}
+
+/// Rename a mission in application storage.
+#[tauri::command]
+pub fn rename_mission(app_handle: AppHandle, old: String, new: String) -> Result<(), AppError> {
+    log::info!("Renaming Mission: {} to {}", old, new);
+    rename(&missions_dir(&app_handle)?, &old, &new)?;
+
+    let state = app_handle.state::<CurrentPath>();
+    let mut mission = state.mission.lock().unwrap();
+    if mission.as_deref() == Some(old.as_str()) {
+        *mission = Some(new);
+    }
+    Ok(())
+}
+
+/// Set the mission used by `read_path` and `save_path`, returning its path.
+///
+/// The default path is used when `name` is `None`.
+#[tauri::command]
+pub fn set_current_mission(
+    app_handle: AppHandle,
+    name: Option<String>,
+) -> Result<PathData, AppError> {
+    log::info!("Setting Current Mission: {:?}", name);
+    if let Some(name) = &name {
+        if !mission_file(&app_handle, name)?.exists() {
+            return Err(not_found(name));
+        }
+    }
+
+    *app_handle.state::<CurrentPath>().mission.lock().unwrap() = name;
+    let path = path::read_path(app_handle.clone())?;
+    app_handle.state::<PathHistory>().clear(&app_handle);
+    if let Err(e) = app_handle.emit_all("path-updated", &path) {
+        log::warn!("Unable to emit path-updated event: {}", e);
+    }
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::tests::temp_dir;
+
+    /// Saves a mission with a path through `waypoints` to `dir`.
+    fn save(dir: &Path, name: &str, waypoints: usize) {
+        let mut path = PathData::default();
+        for i in 0..waypoints {
+            path.insert_waypoint(i, geo_types::Point::new(101.5 + i as f64 * 0.001, 3.1))
+                .unwrap();
+        }
+        path::export_path(file_in(dir, name).unwrap(), path).unwrap();
+    }
+
+    #[test]
+    fn file_in_rejects_names_escaping_directory() {
+        let dir = Path::new("missions");
+        for name in [
+            "..",
+            "../path",
+            "..\\path",
+            "a/b",
+            "a\\b",
+            "/etc/passwd",
+            "C:mission",
+            ".hidden",
+            "",
+            "  ",
+            "pond..2",
+            "nul\0",
+        ] {
+            assert!(file_in(dir, name).is_err(), "{name:?} accepted");
+        }
+        assert_eq!(
+            file_in(dir, "North Pond 2").unwrap(),
+            dir.join("North Pond 2.geojson")
+        );
+    }
+
+    #[test]
+    fn mission_names_are_sorted_and_skip_other_files() {
+        let dir = temp_dir("mission-names");
+        save(&dir, "South Pond", 2);
+        save(&dir, "North Pond", 2);
+        fs::write(dir.join("notes.txt"), "").unwrap();
+        fs::write(dir.join(".hidden.geojson"), "").unwrap();
+
+        assert_eq!(
+            mission_names(&dir).unwrap(),
+            vec!["North Pond", "South Pond"]
+        );
+        assert!(mission_names(&dir.join("missing")).unwrap().is_empty());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn rename_moves_mission() {
+        let dir = temp_dir("mission-rename");
+        save(&dir, "Pond", 3);
+
+        rename(&dir, "Pond", "North Pond").unwrap();
+
+        assert_eq!(mission_names(&dir).unwrap(), vec!["North Pond"]);
+        let path = read_mission(&file_in(&dir, "North Pond").unwrap(), "North Pond").unwrap();
+        assert_eq!(path.path().0.len(), 3);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn rename_keeps_existing_mission() {
+        let dir = temp_dir("mission-rename-exists");
+        save(&dir, "Pond", 3);
+        save(&dir, "North Pond", 2);
+
+        let error = rename(&dir, "Pond", "North Pond").unwrap_err();
+
+        assert_eq!(
+            error,
+            AppError::new(
+                codes::MISSION_ALREADY_EXISTS,
+                "Mission Already Exists: North Pond"
+            )
+            .with("name", "North Pond")
+        );
+        let path = read_mission(&file_in(&dir, "North Pond").unwrap(), "North Pond").unwrap();
+        assert_eq!(path.path().0.len(), 2);
+        assert_eq!(mission_names(&dir).unwrap(), vec!["North Pond", "Pond"]);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn rename_rejects_missing_and_invalid_missions() {
+        let dir = temp_dir("mission-rename-invalid");
+        save(&dir, "Pond", 2);
+
+        assert_eq!(
+            rename(&dir, "Lake", "North Pond").unwrap_err(),
+            not_found("Lake")
+        );
+        assert!(rename(&dir, "Pond", "../Pond").is_err());
+        assert!(rename(&dir, "../Pond", "Pond 2").is_err());
+        assert_eq!(mission_names(&dir).unwrap(), vec!["Pond"]);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn delete_removes_only_named_mission() {
+        let dir = temp_dir("mission-delete");
+        save(&dir, "Pond", 2);
+        save(&dir, "Lake", 2);
+
+        delete(&dir, "Pond").unwrap();
+
+        assert_eq!(mission_names(&dir).unwrap(), vec!["Lake"]);
+        assert_eq!(delete(&dir, "Pond").unwrap_err(), not_found("Pond"));
+        assert!(delete(&dir, "../missions").is_err());
+        assert!(dir.exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,589 @@
+//! NetCDF export of boat data following the CF conventions for oceanographic tools such as ODV
+//! and Panoply.
+//!
+//! The file is written in the NetCDF classic format with 64-bit offsets, as a single trajectory
+//! along the `obs` dimension.
+
+use std::path::PathBuf;
+
+use chrono::{SecondsFormat, Utc};
+
+use crate::{
+    data::{BoatData, Layer},
+    errors::{codes, AppError},
+    export_preview::{self, ExportPreview},
+    storage::write_atomically,
+};
+
+/// The magic number of the NetCDF classic format with 64-bit offsets.
+const MAGIC: &[u8; 4] = b"CDF\x02";
+
+/// The tag of a list of dimensions.
+const NC_DIMENSION: u32 = 0x0A;
+
+/// The tag of a list of variables.
+const NC_VARIABLE: u32 = 0x0B;
+
+/// The tag of a list of attributes.
+const NC_ATTRIBUTE: u32 = 0x0C;
+
+/// The NetCDF external type of 8-bit integers.
+const NC_BYTE: u32 = 1;
+
+/// The NetCDF external type of characters.
+const NC_CHAR: u32 = 2;
+
+/// The NetCDF external type of 32-bit integers.
+const NC_INT: u32 = 4;
+
+/// The NetCDF external type of 64-bit floating point numbers.
+const NC_DOUBLE: u32 = 6;
+
+/// The layers in the order of their flag values, layers reported by newer firmware are written
+/// as the fill value.
+const LAYERS: [Layer; 3] = [Layer::Surface, Layer::Middle, Layer::SeaBed];
+
+/// The value of an attribute.
+enum AttributeValue {
+    Text(String),
+    Bytes(Vec<i8>),
+}
+
+/// The values of a variable.
+enum Values {
+    Bytes(Vec<i8>),
+    Ints(Vec<i32>),
+    Doubles(Vec<f64>),
+}
+
+impl Values {
+    /// Gets the NetCDF external type of the values.
+    fn nc_type(&self) -> u32 {
+        match self {
+            Self::Bytes(_) => NC_BYTE,
+            Self::Ints(_) => NC_INT,
+            Self::Doubles(_) => NC_DOUBLE,
+        }
+    }
+
+    /// Gets the size of the encoded values in bytes, padded to a multiple of 4 bytes.
+    fn size(&self) -> usize {
+        let size = match self {
+            Self::Bytes(v) => v.len(),
+            Self::Ints(v) => v.len() * 4,
+            Self::Doubles(v) => v.len() * 8,
+        };
+        (size + 3) / 4 * 4
+    }
+
+    /// Encodes the values in big-endian, padded to a multiple of 4 bytes.
+    fn encode(&self) -> Vec<u8> {
+        let mut bytes = vec![];
+        match self {
+            Self::Bytes(v) => bytes.extend(v.iter().map(|b| *b as u8)),
+            Self::Ints(v) => v.iter().for_each(|i| bytes.extend(i.to_be_bytes())),
+            Self::Doubles(v) => v.iter().for_each(|d| bytes.extend(d.to_be_bytes())),
+        }
+        pad(&mut bytes);
+        bytes
+    }
+}
+
+/// A variable of the file.
+struct Variable {
+    name: &'static str,
+    /// The IDs of the dimensions of the variable, empty for a scalar.
+    dimensions: Vec<u32>,
+    attributes: Vec<(&'static str, AttributeValue)>,
+    values: Values,
+}
+
+/// Pads bytes with zeros to a multiple of 4 bytes.
+fn pad(bytes: &mut Vec<u8>) {
+    while bytes.len() % 4 != 0 {
+        bytes.push(0);
+    }
+}
+
+/// Writes a name, padded to a multiple of 4 bytes.
+fn write_name(header: &mut Vec<u8>, name: &str) {
+    header.extend((name.len() as u32).to_be_bytes());
+    header.extend(name.as_bytes());
+    pad(header);
+}
+
+/// Writes a list of attributes.
+fn write_attributes(header: &mut Vec<u8>, attributes: &[(&str, AttributeValue)]) {
+    if attributes.is_empty() {
+        // An absent list is written as a zero tag and a zero count
+        header.extend([0; 8]);
+        return;
+    }
+    header.extend(NC_ATTRIBUTE.to_be_bytes());
+    header.extend((attributes.len() as u32).to_be_bytes());
+    for (name, value) in attributes {
+        write_name(header, name);
+        match value {
+            AttributeValue::Text(v) => {
+                header.extend(NC_CHAR.to_be_bytes());
+                header.extend((v.len() as u32).to_be_bytes());
+                header.extend(v.as_bytes());
+            }
+            AttributeValue::Bytes(v) => {
+                header.extend(NC_BYTE.to_be_bytes());
+                header.extend((v.len() as u32).to_be_bytes());
+                header.extend(v.iter().map(|b| *b as u8));
+            }
+        }
+        pad(header);
+    }
+}
+
+/// Writes the header of a file with one dimension, using `begins` as the offsets of the data of
+/// the variables.
+fn write_header(
+    dimension: (&str, usize),
+    attributes: &[(&str, AttributeValue)],
+    variables: &[Variable],
+    begins: &[u64],
+) -> Vec<u8> {
+    let mut header = MAGIC.to_vec();
+    // There is no record dimension, so the number of records is 0
+    header.extend(0u32.to_be_bytes());
+
+    header.extend(NC_DIMENSION.to_be_bytes());
+    header.extend(1u32.to_be_bytes());
+    write_name(&mut header, dimension.0);
+    header.extend((dimension.1 as u32).to_be_bytes());
+
+    write_attributes(&mut header, attributes);
+
+    header.extend(NC_VARIABLE.to_be_bytes());
+    header.extend((variables.len() as u32).to_be_bytes());
+    for (variable, begin) in variables.iter().zip(begins) {
+        write_name(&mut header, variable.name);
+        header.extend((variable.dimensions.len() as u32).to_be_bytes());
+        for id in &variable.dimensions {
+            header.extend(id.to_be_bytes());
+        }
+        write_attributes(&mut header, &variable.attributes);
+        header.extend(variable.values.nc_type().to_be_bytes());
+        header.extend((variable.values.size() as u32).to_be_bytes());
+        header.extend(begin.to_be_bytes());
+    }
+    header
+}
+
+/// Creates a text attribute.
+fn text(name: &'static str, value: &str) -> (&'static str, AttributeValue) {
+    (name, AttributeValue::Text(value.to_string()))
+}
+
+/// Gets the variables of boat data following the CF conventions.
+fn variables(data: &BoatData) -> Vec<Variable> {
+    let features = data.features();
+    let obs = vec![0];
+    vec![
+        Variable {
+            name: "trajectory",
+            dimensions: vec![],
+            attributes: vec![
+                text("cf_role", "trajectory_id"),
+                text("long_name", "Trajectory of the boat"),
+            ],
+            values: Values::Ints(vec![0]),
+        },
+        Variable {
+            name: "time",
+            dimensions: obs.clone(),
+            attributes: vec![
+                text("standard_name", "time"),
+                text("long_name", "Time of the reading"),
+                text("units", "milliseconds since 1970-01-01 00:00:00 UTC"),
+                text("calendar", "standard"),
+                text("axis", "T"),
+            ],
+            values: Values::Doubles(
+                features
+                    .iter()
+                    .map(|f| f.time().timestamp_millis() as f64)
+                    .collect(),
+            ),
+        },
+        Variable {
+            name: "lat",
+            dimensions: obs.clone(),
+            attributes: vec![
+                text("standard_name", "latitude"),
+                text("long_name", "Latitude of the reading"),
+                text("units", "degrees_north"),
+                text("axis", "Y"),
+            ],
+            values: Values::Doubles(features.iter().map(|f| f.geometry().y()).collect()),
+        },
+        Variable {
+            name: "lon",
+            dimensions: obs.clone(),
+            attributes: vec![
+                text("standard_name", "longitude"),
+                text("long_name", "Longitude of the reading"),
+                text("units", "degrees_east"),
+                text("axis", "X"),
+            ],
+            values: Values::Doubles(features.iter().map(|f| f.geometry().x()).collect()),
+        },
+        Variable {
+            name: "depth",
+            dimensions: obs.clone(),
+            attributes: vec![
+                text("standard_name", "depth"),
+                text("long_name", "Depth of the reading"),
+                text("units", "m"),
+                text("positive", "down"),
+                text("axis", "Z"),
+            ],
+            values: Values::Doubles(features.iter().map(|f| f.depth()).collect()),
+        },
+        Variable {
+            name: "temperature",
+            dimensions: obs.clone(),
+            attributes: vec![
+                text("standard_name", "sea_water_temperature"),
+                text("long_name", "Temperature of the water"),
+                text("units", "degree_Celsius"),
+                text("coordinates", "time lat lon depth"),
+            ],
+            values: Values::Doubles(features.iter().map(|f| f.temperature()).collect()),
+        },
+        Variable {
+            name: "layer",
+            dimensions: obs,
+            attributes: vec![
+                text("long_name", "Layer of the reading"),
+                (
+                    "flag_values",
+                    AttributeValue::Bytes((0..LAYERS.len() as i8).collect()),
+                ),
+                text("flag_meanings", "surface middle sea_bed"),
+                ("_FillValue", AttributeValue::Bytes(vec![-1])),
+                text("coordinates", "time lat lon depth"),
+            ],
+            values: Values::Bytes(
+                features
+                    .iter()
+                    .map(|f| {
+                        let layer = f.layer();
+                        LAYERS
+                            .iter()
+                            .position(|l| *l == layer)
+                            .map_or(-1, |i| i as i8)
+                    })
+                    .collect(),
+            ),
+        },
+    ]
+}
+
+/// Encodes boat data as a CF trajectory NetCDF file.
+fn write_netcdf(data: &BoatData) -> Vec<u8> {
+    let dimension = ("obs", data.features().len());
+    let attributes = vec![
+        text("Conventions", "CF-1.8"),
+        text("featureType", "trajectory"),
+        text("title", "Water temperature readings of the boat"),
+        text("boat_data_version", data.version()),
+        text(
+            "date_created",
+            &Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true),
+        ),
+    ];
+    let variables = variables(data);
+    // The offsets don't change the size of the header, so it is written twice to find them
+    let placeholder = vec![0; variables.len()];
+    let mut begin = write_header(dimension, &attributes, &variables, &placeholder).len() as u64;
+    let mut begins = vec![];
+    for variable in &variables {
+        begins.push(begin);
+        begin += variable.values.size() as u64;
+    }
+
+    let mut content = write_header(dimension, &attributes, &variables, &begins);
+    for variable in &variables {
+        content.extend(variable.values.encode());
+    }
+    content
+}
+
+/// Export boat data to a NetCDF file following the CF conventions.
+///
+/// The readings are written as a trajectory along the `obs` dimension, with `time`, `lat`,
+/// `lon`, `depth`, `temperature` and the `layer` flags.
+///
+/// If `dry_run` is `true`, nothing is written and a preview of the export is returned instead.
+#[tauri::command]
+pub fn export_data_netcdf(
+    export_path: PathBuf,
+    data: BoatData,
+    dry_run: Option<bool>,
+) -> Result<Option<ExportPreview>, AppError> {
+    if data.features().is_empty() {
+        // A dimension of length 0 is the unlimited dimension in NetCDF
+        return Err(AppError::new(
+            codes::EXPORT_EMPTY,
+            "Unable to Export NetCDF: Boat data is empty",
+        ));
+    }
+
+    let content = write_netcdf(&data);
+    if dry_run.unwrap_or(false) {
+        return export_preview::preview(&export_path, &data, "NaN", |file| {
+            file.write_all(&content)
+        })
+        .map(Some);
+    }
+    log::debug!("Exporting to: {}", export_path.display());
+    write_atomically(&export_path, |file| file.write_all(&content))?;
+    log::info!("Exported {} Readings to NetCDF", data.features().len());
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use chrono::{TimeZone, Utc};
+    use geo_types::Point;
+
+    use super::*;
+    use crate::{data::BoatDataFeature, storage::tests::temp_dir};
+
+    /// A variable read back from the header.
+    #[derive(Debug)]
+    struct ParsedVariable {
+        dimensions: Vec<u32>,
+        attributes: HashMap<String, Vec<u8>>,
+        nc_type: u32,
+        size: usize,
+        begin: usize,
+    }
+
+    /// The header of a NetCDF file read back.
+    #[derive(Debug)]
+    struct ParsedHeader {
+        dimensions: Vec<(String, u32)>,
+        attributes: HashMap<String, Vec<u8>>,
+        variables: Vec<(String, ParsedVariable)>,
+    }
+
+    /// Reads the NetCDF classic format with 64-bit offsets.
+    struct Reader<'a> {
+        content: &'a [u8],
+        position: usize,
+    }
+
+    impl Reader<'_> {
+        fn bytes(&mut self, len: usize) -> &[u8] {
+            let bytes = &self.content[self.position..self.position + len];
+            self.position += (len + 3) / 4 * 4;
+            bytes
+        }
+
+        fn u32(&mut self) -> u32 {
+            u32::from_be_bytes(self.bytes(4).try_into().unwrap())
+        }
+
+        fn name(&mut self) -> String {
+            let len = self.u32() as usize;
+            String::from_utf8(self.bytes(len).to_vec()).unwrap()
+        }
+
+        fn attributes(&mut self) -> HashMap<String, Vec<u8>> {
+            let tag = self.u32();
+            let count = self.u32();
+            assert!(tag == NC_ATTRIBUTE || (tag == 0 && count == 0));
+            (0..count)
+                .map(|_| {
+                    let name = self.name();
+                    let nc_type = self.u32();
+                    assert!(nc_type == NC_CHAR || nc_type == NC_BYTE, "{name}");
+                    let len = self.u32() as usize;
+                    (name, self.bytes(len).to_vec())
+                })
+                .collect()
+        }
+
+        fn header(content: &[u8]) -> ParsedHeader {
+            let mut reader = Reader {
+                content,
+                position: 0,
+            };
+            assert_eq!(reader.bytes(4), MAGIC);
+            assert_eq!(reader.u32(), 0);
+
+            assert_eq!(reader.u32(), NC_DIMENSION);
+            let dimensions = (0..reader.u32())
+                .map(|_| (reader.name(), reader.u32()))
+                .collect();
+            let attributes = reader.attributes();
+            assert_eq!(reader.u32(), NC_VARIABLE);
+            let variables = (0..reader.u32())
+                .map(|_| {
+                    let name = reader.name();
+                    let dimensions = (0..reader.u32()).map(|_| reader.u32()).collect();
+                    let attributes = reader.attributes();
+                    let nc_type = reader.u32();
+                    let size = reader.u32() as usize;
+                    let begin = u64::from_be_bytes(reader.bytes(8).try_into().unwrap()) as usize;
+                    let variable = ParsedVariable {
+                        dimensions,
+                        attributes,
+                        nc_type,
+                        size,
+                        begin,
+                    };
+                    (name, variable)
+                })
+                .collect();
+            ParsedHeader {
+                dimensions,
+                attributes,
+                variables,
+            }
+        }
+    }
+
+    impl ParsedHeader {
+        fn variable(&self, name: &str) -> &ParsedVariable {
+            &self.variables.iter().find(|(n, _)| n == name).unwrap().1
+        }
+    }
+
+    impl ParsedVariable {
+        fn text(&self, name: &str) -> &str {
+            std::str::from_utf8(&self.attributes[name]).unwrap()
+        }
+
+        fn doubles(&self, content: &[u8]) -> Vec<f64> {
+            assert_eq!(self.nc_type, NC_DOUBLE);
+            content[self.begin..self.begin + self.size]
+                .chunks(8)
+                .map(|b| f64::from_be_bytes(b.try_into().unwrap()))
+                .collect()
+        }
+    }
+
+    /// Boat data with a reading at each layer.
+    fn data() -> BoatData {
+        let time = |seconds: i64| Utc.timestamp_opt(1_715_495_400 + seconds, 0).unwrap();
+        BoatData::new(
+            BoatData::default().version().to_string(),
+            vec![
+                BoatDataFeature::new(28.5, 0.5, Layer::Surface, time(0), Point::new(101.5, 3.1)),
+                BoatDataFeature::new(27.25, 4.0, Layer::SeaBed, time(60), Point::new(101.6, 3.2)),
+                BoatDataFeature::new(27.75, 2.0, Layer::Middle, time(30), Point::new(101.7, 3.0)),
+            ],
+        )
+    }
+
+    #[test]
+    fn header_follows_the_cf_trajectory_conventions() {
+        let content = write_netcdf(&data());
+        let header = Reader::header(&content);
+
+        assert_eq!(header.dimensions, vec![(String::from("obs"), 3)]);
+        let global = |name: &str| std::str::from_utf8(&header.attributes[name]).unwrap();
+        assert_eq!(global("Conventions"), "CF-1.8");
+        assert_eq!(global("featureType"), "trajectory");
+        assert_eq!(global("boat_data_version"), data().version());
+        assert!(chrono::DateTime::parse_from_rfc3339(global("date_created")).is_ok());
+
+        let trajectory = header.variable("trajectory");
+        assert!(trajectory.dimensions.is_empty());
+        assert_eq!(trajectory.text("cf_role"), "trajectory_id");
+        for (name, standard_name, units) in [
+            ("time", "time", "milliseconds since 1970-01-01 00:00:00 UTC"),
+            ("lat", "latitude", "degrees_north"),
+            ("lon", "longitude", "degrees_east"),
+            ("depth", "depth", "m"),
+            ("temperature", "sea_water_temperature", "degree_Celsius"),
+        ] {
+            let variable = header.variable(name);
+            assert_eq!(variable.dimensions, vec![0], "{name}");
+            assert_eq!(variable.text("standard_name"), standard_name);
+            assert_eq!(variable.text("units"), units);
+        }
+        assert_eq!(header.variable("depth").text("positive"), "down");
+    }
+
+    #[test]
+    fn layer_flags_match_their_meanings() {
+        let content = write_netcdf(&data());
+        let header = Reader::header(&content);
+        let layer = header.variable("layer");
+
+        let meanings: Vec<_> = layer.text("flag_meanings").split(' ').collect();
+        assert_eq!(layer.attributes["flag_values"], vec![0, 1, 2]);
+        assert_eq!(meanings, vec!["surface", "middle", "sea_bed"]);
+        assert_eq!(layer.nc_type, NC_BYTE);
+        assert_eq!(content[layer.begin..layer.begin + 3], [0, 2, 1]);
+    }
+
+    #[test]
+    fn values_are_written_at_their_offsets() {
+        let content = write_netcdf(&data());
+        let header = Reader::header(&content);
+
+        assert_eq!(
+            header.variable("time").doubles(&content),
+            vec![
+                1_715_495_400_000.0,
+                1_715_495_460_000.0,
+                1_715_495_430_000.0
+            ]
+        );
+        assert_eq!(
+            header.variable("lon").doubles(&content),
+            vec![101.5, 101.6, 101.7]
+        );
+        assert_eq!(
+            header.variable("lat").doubles(&content),
+            vec![3.1, 3.2, 3.0]
+        );
+        assert_eq!(
+            header.variable("depth").doubles(&content),
+            vec![0.5, 4.0, 2.0]
+        );
+        assert_eq!(
+            header.variable("temperature").doubles(&content),
+            vec![28.5, 27.25, 27.75]
+        );
+
+        // The data of the variables is contiguous up to the end of the file
+        let mut end = header.variables[0].1.begin;
+        for (name, variable) in &header.variables {
+            assert_eq!(variable.begin, end, "{name}");
+            assert_eq!(variable.size % 4, 0, "{name}");
+            end += variable.size;
+        }
+        assert_eq!(end, content.len());
+    }
+
+    #[test]
+    fn empty_data_is_not_exported() {
+        let dir = temp_dir("netcdf_empty");
+        let path = dir.join("data.nc");
+
+        assert_eq!(
+            export_data_netcdf(path.clone(), BoatData::default(), None),
+            Err(AppError::new(
+                codes::EXPORT_EMPTY,
+                "Unable to Export NetCDF: Boat data is empty"
+            ))
+        );
+        assert!(!path.exists());
+
+        export_data_netcdf(path.clone(), data(), None).unwrap();
+        assert_eq!(&std::fs::read(&path).unwrap()[..4], MAGIC);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
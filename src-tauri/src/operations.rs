@@ -0,0 +1,507 @@
+//! The log of the operations changing the data of the user, e.g. saves, imports and deletions,
+//! so there is a trail of what happened when data goes missing.
+//!
+//! Each line of `logs/operations.ndjson` in the application data directory is a JSON entry of an
+//! operation. The log is rotated when it grows past [`MAX_LOG_BYTES`], keeping
+//! [`MAX_ROTATED_LOGS`] older logs as `operations.1.ndjson`, `operations.2.ndjson` and so on.
+//!
+//! The log, the frame logs and the settings can be exported to a diagnostics bundle to attach to
+//! bug reports. Paths outside the application data directory are redacted in the bundle.
+
+use std::{
+    fmt::Display,
+    fs::{self, OpenOptions},
+    io::{Cursor, ErrorKind, Write},
+    path::{Path, PathBuf},
+    sync::Mutex,
+    time::Instant,
+};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tauri::{AppHandle, Manager};
+use zip::ZipWriter;
+
+use crate::{
+    errors::{export_error, AppError},
+    project::write_entry,
+    settings::SettingsState,
+    storage::{app_data_file, data_dir, write_atomically},
+};
+
+/// The folder of the logs in the application data directory.
+const LOGS_DIR: &str = "logs";
+
+/// The size in bytes the log is rotated at.
+const MAX_LOG_BYTES: u64 = 1024 * 1024;
+
+/// The number of rotated logs kept.
+const MAX_ROTATED_LOGS: usize = 3;
+
+/// The text replacing the folders of paths outside the application data directory.
+const REDACTED: &str = "<redacted>";
+
+/// The text replacing the application data directory in paths.
+const APP_DIR: &str = "<app>";
+
+/// An entry of an operation in the log.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct OperationEntry {
+    /// The time the operation started.
+    time: DateTime<Utc>,
+    /// The name of the command.
+    command: String,
+    /// The parameters needed to tell what the operation did, e.g. the number of data points.
+    params: Value,
+    /// Whether the operation succeeded.
+    success: bool,
+    /// The error of the operation, if it failed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+    /// How long the operation took in milliseconds.
+    duration_ms: f64,
+}
+
+/// An operation being timed to be written to the log.
+#[derive(Debug)]
+pub struct Operation {
+    command: &'static str,
+    params: Value,
+    time: DateTime<Utc>,
+    start: Instant,
+}
+
+impl Operation {
+    /// Starts timing an operation.
+    pub fn start(command: &'static str, params: Value) -> Self {
+        Self {
+            command,
+            params,
+            time: Utc::now(),
+            start: Instant::now(),
+        }
+    }
+
+    /// Writes the operation and its result to the log.
+    ///
+    /// Failing to write the log doesn't fail the operation, so it is only logged.
+    pub fn finish<T, E: Display>(self, app_handle: &AppHandle, result: &Result<T, E>) {
+        let entry = OperationEntry {
+            time: self.time,
+            command: self.command.to_string(),
+            params: self.params,
+            success: result.is_ok(),
+            error: result.as_ref().err().map(|e| e.to_string()),
+            duration_ms: self.start.elapsed().as_secs_f64() * 1000.0,
+        };
+        if let Err(e) = app_handle
+            .state::<OperationsLog>()
+            .append(app_handle, &entry)
+        {
+            log::warn!("Unable to Write the Operations Log: {}", e);
+        }
+    }
+}
+
+/// Runs an operation, writing it and its result to the log.
+pub fn record<T, E: Display>(
+    app_handle: &AppHandle,
+    command: &'static str,
+    params: Value,
+    run: impl FnOnce() -> Result<T, E>,
+) -> Result<T, E> {
+    let operation = Operation::start(command, params);
+    let result = run();
+    operation.finish(app_handle, &result);
+    result
+}
+
+/// The log of the operations.
+#[derive(Debug, Default)]
+pub struct OperationsLog {
+    /// Held while writing the log, so entries and rotations don't overlap.
+    writing: Mutex<()>,
+}
+
+impl OperationsLog {
+    /// Appends an entry to the log, rotating it if it is full.
+    fn append(&self, app_handle: &AppHandle, entry: &OperationEntry) -> Result<(), AppError> {
+        let mut line = serde_json::to_string(entry).map_err(|e| AppError::from(e.to_string()))?;
+        line.push('\n');
+
+        let _writing = self.writing.lock().unwrap();
+        append_line(&app_data_file(app_handle, LOGS_DIR)?, &line)
+    }
+}
+
+/// Gets the path of the log, or of a rotated log if `index` isn't 0.
+fn log_file(dir: &Path, index: usize) -> PathBuf {
+    match index {
+        0 => dir.join("operations.ndjson"),
+        _ => dir.join(format!("operations.{index}.ndjson")),
+    }
+}
+
+/// Moves each log to the next index, removing the oldest.
+fn rotate(dir: &Path) -> std::io::Result<()> {
+    match fs::remove_file(log_file(dir, MAX_ROTATED_LOGS)) {
+        Ok(_) => {}
+        Err(e) if e.kind() == ErrorKind::NotFound => {}
+        Err(e) => return Err(e),
+    }
+    for index in (0..MAX_ROTATED_LOGS).rev() {
+        let from = log_file(dir, index);
+        if from.exists() {
+            fs::rename(&from, log_file(dir, index + 1))?;
+        }
+    }
+    Ok(())
+}
+
+/// Appends a line to the log in `dir`, rotating the log if the line doesn't fit.
+fn append_line(dir: &Path, line: &str) -> Result<(), AppError> {
+    fs::create_dir_all(dir).map_err(|e| AppError::io(e, dir))?;
+    let file = log_file(dir, 0);
+    let size = fs::metadata(&file).map_or(0, |metadata| metadata.len());
+    if size > 0 && size + line.len() as u64 > MAX_LOG_BYTES {
+        log::info!("Rotating the Operations Log");
+        rotate(dir).map_err(|e| AppError::io(e, dir))?;
+    }
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&file)
+        .and_then(|mut file| file.write_all(line.as_bytes()))
+        .map_err(|e| AppError::io(e, &file))
+}
+
+/// Reads the lines of a log, `None` if it doesn't exist.
+fn read_lines(file: &Path) -> Result<Option<Vec<String>>, AppError> {
+    match fs::read_to_string(file) {
+        Ok(v) => Ok(Some(
+            v.lines()
+                .filter(|line| !line.trim().is_empty())
+                .map(String::from)
+                .collect(),
+        )),
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(AppError::io(e, file)),
+    }
+}
+
+/// Read up to `limit` entries of the operations log from the newest, skipping the first
+/// `offset` entries.
+///
+/// The rotated logs are read after the current log. Lines that aren't valid entries, e.g. cut
+/// off when the app crashed, are skipped.
+#[tauri::command]
+pub fn read_operations_log(
+    app_handle: AppHandle,
+    limit: usize,
+    offset: usize,
+) -> Result<Vec<OperationEntry>, AppError> {
+    log::debug!("Reading Operations Log");
+    read_entries(&app_data_file(&app_handle, LOGS_DIR)?, limit, offset)
+}
+
+/// Reads up to `limit` entries of the logs in `dir` from the newest, skipping the first `offset`
+/// entries.
+fn read_entries(dir: &Path, limit: usize, offset: usize) -> Result<Vec<OperationEntry>, AppError> {
+    let mut entries = vec![];
+    for index in 0..=MAX_ROTATED_LOGS {
+        if entries.len() >= offset + limit {
+            break;
+        }
+        let lines = match read_lines(&log_file(dir, index))? {
+            Some(v) => v,
+            None => continue,
+        };
+        for line in lines.iter().rev() {
+            match serde_json::from_str::<OperationEntry>(line) {
+                Ok(entry) => entries.push(entry),
+                Err(e) => log::warn!("Skipping Invalid Operations Log Entry: {}", e),
+            }
+            if entries.len() >= offset + limit {
+                break;
+            }
+        }
+    }
+    Ok(entries.into_iter().skip(offset).take(limit).collect())
+}
+
+/// Redacts the paths outside the application data directory in the diagnostics bundle.
+#[derive(Debug)]
+struct Redactor {
+    app_dir: Option<PathBuf>,
+    home_dir: Option<PathBuf>,
+}
+
+impl Redactor {
+    /// Redacts a text.
+    ///
+    /// A path inside the application data directory keeps its part inside the directory, and
+    /// other paths keep only their file name. Paths in other text can't be told apart, so only
+    /// the application data directory and the home directory are replaced in them.
+    fn redact_text(&self, text: &str) -> String {
+        let path = Path::new(text);
+        if path.is_absolute() {
+            if let Some(relative) = self
+                .app_dir
+                .as_ref()
+                .and_then(|dir| path.strip_prefix(dir).ok())
+            {
+                return Path::new(APP_DIR).join(relative).display().to_string();
+            }
+            return match path.file_name() {
+                Some(name) => Path::new(REDACTED).join(name).display().to_string(),
+                None => REDACTED.to_string(),
+            };
+        }
+
+        let mut text = text.to_string();
+        if let Some(dir) = &self.app_dir {
+            text = text.replace(&dir.display().to_string(), APP_DIR);
+        }
+        if let Some(dir) = &self.home_dir {
+            text = text.replace(&dir.display().to_string(), REDACTED);
+        }
+        text
+    }
+
+    /// Redacts the text in a JSON value.
+    fn redact_json(&self, value: &mut Value) {
+        match value {
+            Value::String(text) => *text = self.redact_text(text),
+            Value::Array(values) => values.iter_mut().for_each(|v| self.redact_json(v)),
+            Value::Object(values) => values.values_mut().for_each(|v| self.redact_json(v)),
+            _ => {}
+        }
+    }
+
+    /// Redacts the entries of a log with an entry in JSON on each line, dropping invalid lines.
+    fn redact_ndjson(&self, lines: &[String]) -> String {
+        let mut content = String::new();
+        for line in lines {
+            if let Ok(mut value) = serde_json::from_str::<Value>(line) {
+                self.redact_json(&mut value);
+                content.push_str(&value.to_string());
+                content.push('\n');
+            }
+        }
+        content
+    }
+}
+
+/// Export the operations logs, the frame logs in the application data directory, the settings
+/// and the version of the app to a zip archive to attach to bug reports.
+///
+/// Paths outside the application data directory are redacted, keeping only their file names.
+#[tauri::command]
+pub fn export_diagnostics_bundle(app_handle: AppHandle, path: PathBuf) -> Result<(), AppError> {
+    log::info!("Exporting Diagnostics Bundle to: {}", path.display());
+    let redactor = Redactor {
+        app_dir: data_dir(&app_handle).ok(),
+        home_dir: tauri::api::path::home_dir(),
+    };
+    let dir = app_data_file(&app_handle, LOGS_DIR)?;
+    let failed = |e: &dyn Display| export_error(&path, e);
+    let mut archive = ZipWriter::new(Cursor::new(vec![]));
+
+    let package = app_handle.package_info();
+    let version = json!({
+        "app_name": &package.name,
+        "app_version": package.version.to_string(),
+        "tauri_version": tauri::VERSION,
+        "os": std::env::consts::OS,
+        "arch": std::env::consts::ARCH,
+        "created": Utc::now(),
+    });
+    let json = serde_json::to_string_pretty(&version).map_err(|e| failed(&e))?;
+    write_entry(&mut archive, "version.json", &json).map_err(|e| failed(&e))?;
+
+    let mut settings =
+        serde_json::to_value(app_handle.state::<SettingsState>().get()).map_err(|e| failed(&e))?;
+    redactor.redact_json(&mut settings);
+    if let Some(key) = settings.pointer_mut("/weather_api/api_key") {
+        *key = Value::String(REDACTED.to_string());
+    }
+    let json = serde_json::to_string_pretty(&settings).map_err(|e| failed(&e))?;
+    write_entry(&mut archive, "settings.json", &json).map_err(|e| failed(&e))?;
+
+    {
+        // Entries aren't appended while the log is copied
+        let _writing = app_handle
+            .state::<OperationsLog>()
+            .inner()
+            .writing
+            .lock()
+            .unwrap();
+        for index in 0..=MAX_ROTATED_LOGS {
+            let file = log_file(&dir, index);
+            if let Some(lines) = read_lines(&file)? {
+                // The file name is always valid as it is made by `log_file`
+                let name = file.file_name().unwrap().to_string_lossy();
+                write_entry(
+                    &mut archive,
+                    &format!("operations/{name}"),
+                    &redactor.redact_ndjson(&lines),
+                )
+                .map_err(|e| failed(&e))?;
+            }
+        }
+    }
+
+    let frame_logs: Vec<PathBuf> = match fs::read_dir(&dir) {
+        Ok(v) => v.filter_map(Result::ok).map(|entry| entry.path()).collect(),
+        Err(e) if e.kind() == ErrorKind::NotFound => vec![],
+        Err(e) => return Err(AppError::io(e, &dir)),
+    };
+    for file in frame_logs {
+        let name = match file.file_name().and_then(|name| name.to_str()) {
+            Some(v) if v.starts_with("frames-") => v.to_string(),
+            _ => continue,
+        };
+        if let Some(lines) = read_lines(&file)? {
+            write_entry(
+                &mut archive,
+                &format!("frames/{name}"),
+                &redactor.redact_ndjson(&lines),
+            )
+            .map_err(|e| failed(&e))?;
+        }
+    }
+
+    let content = archive.finish().map_err(|e| failed(&e))?.into_inner();
+    write_atomically(&path, |file| file.write_all(&content))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::tests::temp_dir;
+
+    fn entry(command: &str) -> OperationEntry {
+        OperationEntry {
+            time: Utc::now(),
+            command: command.to_string(),
+            params: json!({ "path": "/home/user/survey.csv" }),
+            success: true,
+            error: None,
+            duration_ms: 1.5,
+        }
+    }
+
+    fn append(dir: &Path, entry: &OperationEntry) {
+        let mut line = serde_json::to_string(entry).unwrap();
+        line.push('\n');
+        append_line(dir, &line).unwrap();
+    }
+
+    fn commands(entries: &[OperationEntry]) -> Vec<&str> {
+        entries.iter().map(|e| e.command.as_str()).collect()
+    }
+
+    #[test]
+    fn entries_are_read_from_the_newest() {
+        let dir = temp_dir("operations-read");
+        for command in ["save_data", "merge_data", "delete_dataset"] {
+            append(&dir, &entry(command));
+        }
+
+        let entries = read_entries(&dir, 10, 0).unwrap();
+        assert_eq!(
+            commands(&entries),
+            ["delete_dataset", "merge_data", "save_data"]
+        );
+        assert_eq!(entries[0], entry_like(&entries[0], "delete_dataset"));
+        assert_eq!(commands(&read_entries(&dir, 1, 1).unwrap()), ["merge_data"]);
+        assert!(read_entries(&dir, 10, 3).unwrap().is_empty());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    /// Gets `entry` with `command`, to check the entry read has the fields written.
+    fn entry_like(read: &OperationEntry, command: &str) -> OperationEntry {
+        OperationEntry {
+            time: read.time,
+            ..entry(command)
+        }
+    }
+
+    #[test]
+    fn invalid_lines_are_skipped() {
+        let dir = temp_dir("operations-invalid");
+        append(&dir, &entry("save_data"));
+        append_line(&dir, "{\"time\": \"2024-05-\n").unwrap();
+
+        assert_eq!(commands(&read_entries(&dir, 10, 0).unwrap()), ["save_data"]);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn full_logs_are_rotated() {
+        let dir = temp_dir("operations-rotate");
+        let padding = "x".repeat(MAX_LOG_BYTES as usize / 2);
+        for index in 0..=MAX_ROTATED_LOGS + 1 {
+            let mut entry = entry(&format!("save_{index}"));
+            entry.params = json!({ "padding": padding });
+            append(&dir, &entry);
+        }
+
+        // Each log only fits one entry, so the oldest entry is removed
+        for index in 0..=MAX_ROTATED_LOGS {
+            assert!(log_file(&dir, index).exists());
+        }
+        assert!(!log_file(&dir, MAX_ROTATED_LOGS + 1).exists());
+        let entries = read_entries(&dir, 10, 0).unwrap();
+        assert_eq!(commands(&entries), ["save_4", "save_3", "save_2", "save_1"]);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn paths_outside_the_app_are_redacted() {
+        let redactor = Redactor {
+            app_dir: Some(PathBuf::from("/home/user/.local/share/app")),
+            home_dir: Some(PathBuf::from("/home/user")),
+        };
+
+        assert_eq!(
+            redactor.redact_text("/home/user/.local/share/app/datasets/lake.geojson"),
+            Path::new(APP_DIR)
+                .join("datasets/lake.geojson")
+                .display()
+                .to_string()
+        );
+        assert_eq!(
+            redactor.redact_text("/media/share/survey.csv"),
+            Path::new(REDACTED).join("survey.csv").display().to_string()
+        );
+        assert_eq!(
+            redactor.redact_text("Unable to read /home/user/survey.csv"),
+            "Unable to read <redacted>/survey.csv"
+        );
+        assert_eq!(redactor.redact_text("save_data"), "save_data");
+    }
+
+    #[test]
+    fn logs_are_redacted_line_by_line() {
+        let redactor = Redactor {
+            app_dir: None,
+            home_dir: None,
+        };
+        let lines = vec![
+            json!({ "params": { "paths": ["/tmp/a.csv"] }, "duration_ms": 2.0 }).to_string(),
+            String::from("{\"cut off"),
+        ];
+
+        assert_eq!(
+            redactor.redact_ndjson(&lines),
+            format!(
+                "{}\n",
+                json!({ "params": { "paths": [
+                    Path::new(REDACTED).join("a.csv").display().to_string()
+                ] }, "duration_ms": 2.0 })
+            )
+        );
+    }
+}
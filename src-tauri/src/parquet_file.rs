@@ -0,0 +1,478 @@
+//! Parquet export and import of boat data for analysis in data science tools such as pandas and
+//! polars.
+
+use std::{
+    fs::File,
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::Arc,
+};
+
+use chrono::{LocalResult, TimeZone, Utc};
+use geo_types::Point;
+use parquet::{
+    basic::{Compression, Type as PhysicalType},
+    data_type::{ByteArray, ByteArrayType, DataType, DoubleType, Int64Type},
+    errors::ParquetError,
+    file::{
+        properties::WriterProperties,
+        reader::{FileReader, SerializedFileReader},
+        writer::{SerializedFileWriter, SerializedRowGroupWriter},
+    },
+    record::{Field, Row},
+    schema::parser::parse_message_type,
+};
+
+use crate::{
+    data::{BoatData, BoatDataFeature, ImportedData, Layer},
+    errors::{codes, export_error, AppError},
+    export_preview::{self, ExportPreview},
+    migrations,
+    storage::write_atomically,
+};
+
+/// The schema of boat data in Parquet.
+const SCHEMA: &str = "
+message boat_data {
+    REQUIRED INT64 time (TIMESTAMP(MILLIS, true));
+    REQUIRED DOUBLE lat;
+    REQUIRED DOUBLE lng;
+    REQUIRED DOUBLE depth;
+    REQUIRED BYTE_ARRAY layer (STRING);
+    REQUIRED DOUBLE temperature;
+}
+";
+
+/// The columns required to import boat data with their physical types.
+const COLUMNS: [(&str, PhysicalType); 6] = [
+    ("time", PhysicalType::INT64),
+    ("lat", PhysicalType::DOUBLE),
+    ("lng", PhysicalType::DOUBLE),
+    ("depth", PhysicalType::DOUBLE),
+    ("layer", PhysicalType::BYTE_ARRAY),
+    ("temperature", PhysicalType::DOUBLE),
+];
+
+/// Writes the values of the next column of a row group.
+fn write_column<T: DataType>(
+    row_group: &mut SerializedRowGroupWriter<'_, &mut Vec<u8>>,
+    values: &[T::T],
+) -> Result<(), ParquetError> {
+    let mut column = row_group
+        .next_column()?
+        .ok_or_else(|| ParquetError::General(String::from("Missing Column in Schema")))?;
+    column.typed::<T>().write_batch(values, None, None)?;
+    column.close()
+}
+
+/// Encodes boat data as a Parquet file in a single row group.
+fn write_parquet(data: &BoatData) -> Result<Vec<u8>, ParquetError> {
+    let features = data.features();
+    let schema = Arc::new(parse_message_type(SCHEMA)?);
+    // Dictionary encoding is enabled so the few distinct layers are stored once
+    let properties = Arc::new(
+        WriterProperties::builder()
+            .set_compression(Compression::SNAPPY)
+            .set_dictionary_enabled(true)
+            .build(),
+    );
+
+    let mut buffer = vec![];
+    let mut writer = SerializedFileWriter::new(&mut buffer, schema, properties)?;
+    let mut row_group = writer.next_row_group()?;
+    let times: Vec<i64> = features
+        .iter()
+        .map(|f| f.time().timestamp_millis())
+        .collect();
+    write_column::<Int64Type>(&mut row_group, &times)?;
+    let lats: Vec<f64> = features.iter().map(|f| f.geometry().y()).collect();
+    write_column::<DoubleType>(&mut row_group, &lats)?;
+    let lngs: Vec<f64> = features.iter().map(|f| f.geometry().x()).collect();
+    write_column::<DoubleType>(&mut row_group, &lngs)?;
+    let depths: Vec<f64> = features.iter().map(|f| f.depth()).collect();
+    write_column::<DoubleType>(&mut row_group, &depths)?;
+    let layers: Vec<ByteArray> = features
+        .iter()
+        .map(|f| ByteArray::from(f.layer().to_string().as_str()))
+        .collect();
+    write_column::<ByteArrayType>(&mut row_group, &layers)?;
+    let temperatures: Vec<f64> = features.iter().map(|f| f.temperature()).collect();
+    write_column::<DoubleType>(&mut row_group, &temperatures)?;
+    row_group.close()?;
+    writer.close()?;
+    Ok(buffer)
+}
+
+/// Export boat data to a Parquet file.
+///
+/// The columns are `time` as a UTC timestamp in milliseconds, `lat`, `lng`, `depth`, `layer`
+/// as a dictionary encoded string and `temperature`, compressed with Snappy.
+///
+/// If `dry_run` is `true`, nothing is written and a preview of the export is returned instead.
+#[tauri::command]
+pub fn export_data_parquet(
+    export_path: PathBuf,
+    data: BoatData,
+    dry_run: Option<bool>,
+) -> Result<Option<ExportPreview>, AppError> {
+    let content = write_parquet(&data).map_err(|e| export_error(&export_path, e))?;
+    if dry_run.unwrap_or(false) {
+        return export_preview::preview(&export_path, &data, "NaN", |file| {
+            file.write_all(&content)
+        })
+        .map(Some);
+    }
+    log::debug!("Exporting to: {}", export_path.display());
+    write_atomically(&export_path, |file| file.write_all(&content))?;
+    log::info!("Exported {} Readings to Parquet", data.features().len());
+    Ok(None)
+}
+
+/// Checks the schema of a Parquet file has the columns of boat data.
+///
+/// The error lists all the missing columns and the columns with the wrong type.
+fn check_schema(reader: &SerializedFileReader<File>) -> Result<(), AppError> {
+    let schema = reader.metadata().file_metadata().schema_descr();
+    let mut missing = vec![];
+    let mut mistyped = vec![];
+    for (name, expected) in COLUMNS {
+        match schema.columns().iter().find(|c| c.name() == name) {
+            Some(column) if column.physical_type() == expected => {}
+            Some(column) => mistyped.push(format!(
+                "{name} (expected {expected}, found {})",
+                column.physical_type()
+            )),
+            None => missing.push(name),
+        }
+    }
+
+    let mut problems = vec![];
+    if !missing.is_empty() {
+        problems.push(format!("missing columns: {}", missing.join(", ")));
+    }
+    if !mistyped.is_empty() {
+        problems.push(format!("mistyped columns: {}", mistyped.join(", ")));
+    }
+    if problems.is_empty() {
+        Ok(())
+    } else {
+        Err(AppError::new(
+            codes::PARQUET_INVALID_SCHEMA,
+            format!("Invalid Parquet Schema: {}", problems.join("; ")),
+        )
+        .with("missing", missing)
+        .with("mistyped", mistyped))
+    }
+}
+
+/// Reads a data point from a row of a Parquet file.
+fn read_feature(row: &Row) -> Result<BoatDataFeature, String> {
+    let (mut time, mut lat, mut lng, mut depth, mut layer, mut temperature) =
+        (None, None, None, None, None, None);
+    for (name, field) in row.get_column_iter() {
+        match (name.as_str(), field) {
+            ("time", Field::TimestampMillis(v) | Field::Long(v)) => time = Some(*v),
+            ("time", Field::TimestampMicros(v)) => time = Some(v.div_euclid(1000)),
+            ("lat", Field::Double(v)) => lat = Some(*v),
+            ("lng", Field::Double(v)) => lng = Some(*v),
+            ("depth", Field::Double(v)) => depth = Some(*v),
+            ("layer", Field::Str(v)) => layer = Some(Layer::from_str(v)?),
+            ("temperature", Field::Double(v)) => temperature = Some(*v),
+            _ => {}
+        }
+    }
+
+    let missing = |name: &str| format!("Missing Value: {name}");
+    let time = time.ok_or_else(|| missing("time"))?;
+    let time = match Utc.timestamp_millis_opt(time) {
+        LocalResult::Single(v) => v,
+        _ => return Err(format!("Invalid Time: {time}")),
+    };
+    Ok(BoatDataFeature::new(
+        temperature.ok_or_else(|| missing("temperature"))?,
+        depth.ok_or_else(|| missing("depth"))?,
+        layer.ok_or_else(|| missing("layer"))?,
+        time,
+        Point::new(
+            lng.ok_or_else(|| missing("lng"))?,
+            lat.ok_or_else(|| missing("lat"))?,
+        ),
+    ))
+}
+
+/// Reads the boat data in a Parquet file.
+fn read_parquet(path: &Path) -> Result<BoatData, AppError> {
+    let invalid = |e: ParquetError| {
+        AppError::reason(codes::PARQUET_INVALID, "Invalid Parquet File", e)
+            .with("path", path.display().to_string())
+    };
+    let file = File::open(path).map_err(|e| AppError::io(e, path))?;
+    let reader = SerializedFileReader::new(file).map_err(invalid)?;
+    check_schema(&reader)?;
+
+    let mut features = vec![];
+    for (i, row) in reader.get_row_iter(None).map_err(invalid)?.enumerate() {
+        let row = row.map_err(invalid)?;
+        let feature = read_feature(&row).map_err(|e| {
+            AppError::reason(
+                codes::PARQUET_INVALID_ROW,
+                &format!("Invalid Parquet Row {}", i + 1),
+                e,
+            )
+            .with("row", i + 1)
+        })?;
+        features.push(feature);
+    }
+    log::info!("Read {} Data Points from Parquet", features.len());
+    Ok(BoatData::new(
+        String::from(migrations::BOAT_DATA_VERSION),
+        features,
+    ))
+}
+
+/// Import boat data from a Parquet file.
+///
+/// The file must have the columns written by `export_data_parquet`, in any order. Files with
+/// data points out of range are rejected unless `skip_invalid` is `true`, and repeated data
+/// points are removed unless `dedup` is `false`.
+#[tauri::command]
+pub fn import_data_parquet(
+    import_path: PathBuf,
+    dedup: Option<bool>,
+    skip_invalid: Option<bool>,
+) -> Result<ImportedData, AppError> {
+    log::debug!("Importing from: {}", import_path.display());
+    ImportedData::new(read_parquet(&import_path)?, None, dedup, skip_invalid)
+}
+
+#[cfg(test)]
+mod tests {
+    use parquet::basic::Encoding;
+
+    use super::*;
+    use crate::{protocol::tests::Random, storage::tests::temp_dir};
+
+    /// Creates boat data with `len` random data points.
+    fn random_data(random: &mut Random, len: usize) -> BoatData {
+        let layers = [Layer::Surface, Layer::Middle, Layer::SeaBed];
+        let features = (0..len)
+            .map(|_| {
+                BoatDataFeature::new(
+                    random.between(-5.0, 40.0),
+                    random.between(0.0, 50.0),
+                    layers[random.below(layers.len())].clone(),
+                    Utc.timestamp_millis_opt(1_700_000_000_000 + random.below(1 << 36) as i64)
+                        .unwrap(),
+                    Point::new(random.between(-180.0, 180.0), random.between(-90.0, 90.0)),
+                )
+            })
+            .collect();
+        BoatData::new(String::from(migrations::BOAT_DATA_VERSION), features)
+    }
+
+    /// Writes a Parquet file with a schema and a row group written by `write_columns`.
+    fn write_file(
+        path: &Path,
+        schema: &str,
+        write_columns: impl FnOnce(&mut SerializedRowGroupWriter<'_, &mut Vec<u8>>),
+    ) {
+        let schema = Arc::new(parse_message_type(schema).unwrap());
+        let properties = Arc::new(WriterProperties::builder().build());
+        let mut buffer = vec![];
+        let mut writer = SerializedFileWriter::new(&mut buffer, schema, properties).unwrap();
+        let mut row_group = writer.next_row_group().unwrap();
+        write_columns(&mut row_group);
+        row_group.close().unwrap();
+        writer.close().unwrap();
+        std::fs::write(path, buffer).unwrap();
+    }
+
+    /// Writes the columns of a single data point other than the time, starting from `lat`.
+    fn write_values(
+        row_group: &mut SerializedRowGroupWriter<'_, &mut Vec<u8>>,
+        layer: &str,
+    ) -> Result<(), ParquetError> {
+        write_column::<DoubleType>(row_group, &[3.1])?;
+        write_column::<DoubleType>(row_group, &[101.5])?;
+        write_column::<DoubleType>(row_group, &[0.5])?;
+        write_column::<ByteArrayType>(row_group, &[ByteArray::from(layer)])?;
+        write_column::<DoubleType>(row_group, &[28.5])
+    }
+
+    #[test]
+    fn boat_data_round_trips_through_parquet() {
+        let dir = temp_dir("parquet_round_trip");
+        let path = dir.join("data.parquet");
+        let data = random_data(&mut Random(0x5851_F42D_4C95_7F2D), 5000);
+
+        export_data_parquet(path.clone(), data.clone(), None).unwrap();
+
+        assert_eq!(read_parquet(&path).unwrap(), data);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn columns_are_compressed_and_layers_dictionary_encoded() {
+        let dir = temp_dir("parquet_encoding");
+        let path = dir.join("data.parquet");
+        export_data_parquet(path.clone(), random_data(&mut Random(1), 100), None).unwrap();
+        let reader = SerializedFileReader::new(File::open(&path).unwrap()).unwrap();
+
+        let row_group = reader.metadata().row_group(0);
+        assert_eq!(row_group.num_rows(), 100);
+        let names: Vec<_> = row_group
+            .columns()
+            .iter()
+            .map(|c| c.column_descr().name().to_string())
+            .collect();
+        assert_eq!(
+            names,
+            vec!["time", "lat", "lng", "depth", "layer", "temperature"]
+        );
+        assert!(row_group
+            .columns()
+            .iter()
+            .all(|c| c.compression() == Compression::SNAPPY));
+        assert!(row_group
+            .column(4)
+            .encodings()
+            .contains(&Encoding::RLE_DICTIONARY));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn schema_errors_list_every_column() {
+        let dir = temp_dir("parquet_schema");
+        let path = dir.join("data.parquet");
+        write_file(
+            &path,
+            "message readings {
+                REQUIRED DOUBLE time;
+                REQUIRED DOUBLE lat;
+                REQUIRED DOUBLE temperature;
+                REQUIRED INT32 depth;
+            }",
+            |row_group| {
+                write_column::<DoubleType>(row_group, &[]).unwrap();
+                write_column::<DoubleType>(row_group, &[]).unwrap();
+                write_column::<DoubleType>(row_group, &[]).unwrap();
+                write_column::<parquet::data_type::Int32Type>(row_group, &[]).unwrap();
+            },
+        );
+
+        assert_eq!(
+            read_parquet(&path).unwrap_err().to_string(),
+            "Invalid Parquet Schema: missing columns: lng, layer; mistyped columns: time \
+(expected INT64, found DOUBLE), depth (expected DOUBLE, found INT32)"
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn columns_are_read_in_any_order() {
+        let dir = temp_dir("parquet_column_order");
+        let path = dir.join("data.parquet");
+        write_file(
+            &path,
+            "message readings {
+                REQUIRED DOUBLE temperature;
+                REQUIRED BYTE_ARRAY layer (STRING);
+                REQUIRED DOUBLE lng;
+                REQUIRED DOUBLE lat;
+                REQUIRED DOUBLE depth;
+                REQUIRED INT64 time (TIMESTAMP(MILLIS, true));
+                REQUIRED DOUBLE salinity;
+            }",
+            |row_group| {
+                write_column::<DoubleType>(row_group, &[28.5]).unwrap();
+                write_column::<ByteArrayType>(row_group, &[ByteArray::from("sea bed")]).unwrap();
+                write_column::<DoubleType>(row_group, &[101.5]).unwrap();
+                write_column::<DoubleType>(row_group, &[3.1]).unwrap();
+                write_column::<DoubleType>(row_group, &[0.5]).unwrap();
+                write_column::<Int64Type>(row_group, &[1_715_495_400_000]).unwrap();
+                write_column::<DoubleType>(row_group, &[33.0]).unwrap();
+            },
+        );
+
+        let imported = read_parquet(&path).unwrap();
+
+        assert_eq!(
+            imported.features(),
+            &[BoatDataFeature::new(
+                28.5,
+                0.5,
+                Layer::SeaBed,
+                Utc.timestamp_millis_opt(1_715_495_400_000).unwrap(),
+                Point::new(101.5, 3.1),
+            )]
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn microsecond_timestamps_are_truncated_to_milliseconds() {
+        let dir = temp_dir("parquet_micros");
+        let path = dir.join("data.parquet");
+        write_file(&path, &SCHEMA.replace("MILLIS", "MICROS"), |row_group| {
+            write_column::<Int64Type>(row_group, &[1_715_495_400_000_999]).unwrap();
+            write_values(row_group, "surface").unwrap();
+        });
+
+        let imported = read_parquet(&path).unwrap();
+
+        assert_eq!(
+            imported.features()[0].time(),
+            Utc.timestamp_millis_opt(1_715_495_400_000).unwrap()
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn invalid_rows_are_reported_by_number() {
+        let dir = temp_dir("parquet_invalid_row");
+        let path = dir.join("data.parquet");
+        write_file(&path, SCHEMA, |row_group| {
+            write_column::<Int64Type>(row_group, &[1_715_495_400_000]).unwrap();
+            write_values(row_group, "").unwrap();
+        });
+
+        assert_eq!(
+            read_parquet(&path).unwrap_err().to_string(),
+            format!(
+                "Invalid Parquet Row 1: {}",
+                Layer::from_str("").unwrap_err()
+            )
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn null_values_are_reported_as_missing() {
+        let dir = temp_dir("parquet_null");
+        let path = dir.join("data.parquet");
+        write_file(
+            &path,
+            &SCHEMA.replace("REQUIRED INT64 time", "OPTIONAL INT64 time"),
+            |row_group| {
+                let mut column = row_group.next_column().unwrap().unwrap();
+                column
+                    .typed::<Int64Type>()
+                    .write_batch(&[], Some(&[0]), None)
+                    .unwrap();
+                column.close().unwrap();
+                write_values(row_group, "surface").unwrap();
+            },
+        );
+
+        assert_eq!(
+            read_parquet(&path).unwrap_err(),
+            AppError::reason(
+                codes::PARQUET_INVALID_ROW,
+                "Invalid Parquet Row 1",
+                "Missing Value: time"
+            )
+            .with("row", 1)
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -1,23 +1,29 @@
 //! States and function for working with robot paths.
 
 use std::{
-    fmt::Display,
-    io::{ErrorKind, Write},
-    path::PathBuf,
-    str::FromStr,
+    collections::BTreeMap, fmt::Display, io::ErrorKind, path::PathBuf, str::FromStr, sync::Mutex,
 };
 
-use geo_types::{LineString, MultiPoint};
-use geojson::{FeatureCollection, GeoJson, Geometry, Value};
+use geo::{GeodesicLength, Intersects};
+use geo_types::{Coord, LineString, MultiPoint, Point, Polygon};
+use geojson::{FeatureCollection, GeoJson, JsonObject, Value};
+use gpx::{Gpx, GpxVersion, Track, TrackSegment, Waypoint};
 use serde::{de, Deserialize, Serialize};
-use serde_json::{json, Map};
+use serde_json::json;
 use tauri::{
     api::{self, file},
-    AppHandle,
+    AppHandle, Manager,
+};
+
+use crate::{
+    errors::{codes, export_error, AppError},
+    history::PathHistory,
+    migrations, missions, operations,
+    storage::{app_data_file, write_atomically},
 };
 
 /// Information on where to collect data for the boat.
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct PathData {
     /// The version of the communication protocol used.
     version: String,
@@ -25,9 +31,41 @@ pub struct PathData {
     path: LineString<f64>,
     /// The coordinates to where the data should be collected.
     collection_points: MultiPoint<f64>,
+    /// The area the robot boat is allowed to travel in.
+    boundary: Option<Polygon<f64>>,
+    /// The notes of the operator on the collection points, by the index of the point.
+    notes: BTreeMap<usize, String>,
+    /// The members and properties of the GeoJSON not used by the app.
+    extra: PathExtra,
+}
+
+/// The members and properties of path GeoJSON not used by the app, kept so they aren't lost
+/// when the path is written again, e.g. when the path is made by another tool.
+#[derive(Debug, Default, Clone, PartialEq)]
+struct PathExtra {
+    /// The foreign members of the feature collection other than the version.
+    members: JsonObject,
+    /// The properties of the Line String feature.
+    path: JsonObject,
+    /// The properties of the Multi Point feature other than the notes.
+    collection_points: JsonObject,
+    /// The properties of the Polygon feature.
+    boundary: JsonObject,
 }
 
 impl PathData {
+    /// Creates path data of the current version.
+    pub fn new(path: LineString<f64>, collection_points: MultiPoint<f64>) -> Self {
+        Self {
+            version: String::from(migrations::PATH_DATA_VERSION),
+            path,
+            collection_points,
+            boundary: None,
+            notes: BTreeMap::new(),
+            extra: PathExtra::default(),
+        }
+    }
+
     /// Gets the version of the communication protocol used.
     pub fn version(&self) -> &str {
         &self.version
@@ -42,23 +80,399 @@ impl PathData {
     pub fn collection_points(&self) -> &MultiPoint<f64> {
         &self.collection_points
     }
+
+    /// Gets the area the robot boat is allowed to travel in.
+    pub fn boundary(&self) -> Option<&Polygon<f64>> {
+        self.boundary.as_ref()
+    }
+
+    /// Gets the notes of the operator on the collection points, by the index of the point.
+    pub fn notes(&self) -> &BTreeMap<usize, String> {
+        &self.notes
+    }
+
+    /// Sets the note of the collection point at `index`, removing it if `text` is blank.
+    pub fn set_collection_point_note(
+        &mut self,
+        index: usize,
+        text: String,
+    ) -> Result<(), PathError> {
+        self.check_collection_point(index)?;
+        if text.trim().is_empty() {
+            self.notes.remove(&index);
+        } else {
+            self.notes.insert(index, text);
+        }
+        Ok(())
+    }
+
+    /// Replaces the notes of the collection points, by the index of the point.
+    ///
+    /// Notes of collection points that don't exist are dropped.
+    pub fn set_notes(&mut self, notes: BTreeMap<usize, String>) {
+        let len = self.collection_points.0.len();
+        self.notes = notes;
+        self.notes.retain(|index, _| *index < len);
+    }
+
+    /// Sets the area the robot boat is allowed to travel in.
+    pub fn set_boundary(&mut self, boundary: Option<Polygon<f64>>) {
+        self.boundary = boundary;
+    }
+
+    /// Checks if the path and collection points are inside the boundary.
+    ///
+    /// Points on the edge of the boundary are inside it. Returns the indices of the waypoints
+    /// and collection points outside of the boundary.
+    pub fn check_boundary(&self) -> Result<(), PathError> {
+        let boundary = match &self.boundary {
+            Some(boundary) => boundary,
+            None => return Ok(()),
+        };
+        let outside = |points: &mut dyn Iterator<Item = Point<f64>>| -> Vec<usize> {
+            points
+                .enumerate()
+                .filter(|(_, point)| !boundary.intersects(point))
+                .map(|(i, _)| i)
+                .collect()
+        };
+
+        let waypoints = outside(&mut self.path.points());
+        let collection_points = outside(&mut self.collection_points.iter().copied());
+        if waypoints.is_empty() && collection_points.is_empty() {
+            return Ok(());
+        }
+        Err(PathError::OutsideBoundary {
+            waypoints,
+            collection_points,
+        })
+    }
+
+    /// Checks if two paths are equal, allowing the coordinates to differ by up to `epsilon`
+    /// degrees, e.g. after being written and read again.
+    pub fn approx_eq(&self, other: &Self, epsilon: f64) -> bool {
+        let boundaries_eq = match (&self.boundary, &other.boundary) {
+            (Some(a), Some(b)) => {
+                coords_approx_eq(&a.exterior().0, &b.exterior().0, epsilon)
+                    && a.interiors().len() == b.interiors().len()
+                    && a.interiors()
+                        .iter()
+                        .zip(b.interiors())
+                        .all(|(a, b)| coords_approx_eq(&a.0, &b.0, epsilon))
+            }
+            (None, None) => true,
+            _ => false,
+        };
+        let points = |path: &Self| -> Vec<Coord<f64>> {
+            path.collection_points.iter().map(|p| p.0).collect()
+        };
+        self.version == other.version
+            && coords_approx_eq(&self.path.0, &other.path.0, epsilon)
+            && coords_approx_eq(&points(self), &points(other), epsilon)
+            && boundaries_eq
+            && self.notes == other.notes
+            && self.extra == other.extra
+    }
+
+    /// Replaces the path the robot boat is following.
+    pub fn set_path(&mut self, path: LineString<f64>) {
+        self.path = path;
+    }
+
+    /// Replaces the coordinates to where the data should be collected.
+    ///
+    /// The notes of the collection points that no longer exist are removed.
+    pub fn set_collection_points(&mut self, collection_points: MultiPoint<f64>) {
+        let len = collection_points.0.len();
+        self.collection_points = collection_points;
+        self.notes.retain(|index, _| *index < len);
+    }
+
+    /// Adds a collection point after the existing ones.
+    pub fn add_collection_point(&mut self, point: Point<f64>) {
+        self.collection_points.0.push(point);
+    }
+
+    /// Removes the collection point at `index`, returning it.
+    ///
+    /// The notes of the collection points after it move with their points.
+    pub fn remove_collection_point(&mut self, index: usize) -> Result<Point<f64>, PathError> {
+        self.check_collection_point(index)?;
+        self.notes = std::mem::take(&mut self.notes)
+            .into_iter()
+            .filter(|(i, _)| *i != index)
+            .map(|(i, note)| if i > index { (i - 1, note) } else { (i, note) })
+            .collect();
+        Ok(self.collection_points.0.remove(index))
+    }
+
+    /// Moves the collection point at `index` to `point`.
+    pub fn move_collection_point(
+        &mut self,
+        index: usize,
+        point: Point<f64>,
+    ) -> Result<(), PathError> {
+        self.check_collection_point(index)?;
+        self.collection_points.0[index] = point;
+        Ok(())
+    }
+
+    /// Checks if there is a collection point at `index`.
+    fn check_collection_point(&self, index: usize) -> Result<(), PathError> {
+        let len = self.collection_points.0.len();
+        if index >= len {
+            return Err(PathError::CollectionPointOutOfRange { index, len });
+        }
+        Ok(())
+    }
+
+    /// Inserts a waypoint to the path at `index`, shifting the waypoints after it.
+    pub fn insert_waypoint(&mut self, index: usize, point: Point<f64>) -> Result<(), PathError> {
+        let len = self.path.0.len();
+        if index > len {
+            return Err(PathError::WaypointOutOfRange { index, len });
+        }
+        self.path.0.insert(index, point.0);
+        Ok(())
+    }
+
+    /// Moves the waypoint at `index` to `point`.
+    pub fn update_waypoint(&mut self, index: usize, point: Point<f64>) -> Result<(), PathError> {
+        self.check_waypoint(index)?;
+        self.path.0[index] = point.0;
+        Ok(())
+    }
+
+    /// Removes the waypoint at `index`.
+    ///
+    /// A single waypoint is not a valid path, so the path is cleared when removing a waypoint
+    /// from a path with two waypoints. A warning is returned when this happens.
+    pub fn remove_waypoint(&mut self, index: usize) -> Result<Option<String>, PathError> {
+        self.check_waypoint(index)?;
+        self.path.0.remove(index);
+        if self.path.0.len() == 1 {
+            log::warn!("Clearing Path with a Single Waypoint");
+            self.path.0.clear();
+            return Ok(Some(String::from(
+                "The path was cleared as a single waypoint is not a valid path.",
+            )));
+        }
+        Ok(None)
+    }
+
+    /// Reverses the direction of the path.
+    pub fn reverse_path(&mut self) {
+        self.path.0.reverse();
+    }
+
+    /// Checks if there is a waypoint at `index`.
+    fn check_waypoint(&self, index: usize) -> Result<(), PathError> {
+        let len = self.path.0.len();
+        if index >= len {
+            return Err(PathError::WaypointOutOfRange { index, len });
+        }
+        Ok(())
+    }
+
+    /// Gets the geodesic length of the path in meters.
+    pub fn length(&self) -> f64 {
+        self.path.geodesic_length()
+    }
+
+    /// Gets the problems with the path that would prevent the boat from following it.
+    pub fn warnings(&self) -> Vec<String> {
+        let mut warnings = vec![];
+        if self.path.0.len() == 1 {
+            warnings.push(String::from("The path requires at least two waypoints."));
+        }
+        if let Some(i) = self
+            .path
+            .0
+            .iter()
+            .position(|c| !c.x.is_finite() || !c.y.is_finite())
+        {
+            warnings.push(format!("Waypoint {i} has an invalid coordinate."));
+        }
+        warnings
+    }
 }
 
-impl Default for PathData {
-    fn default() -> Self {
+/// The path after editing its waypoints.
+#[derive(Debug, Serialize)]
+pub struct WaypointEdit {
+    /// The edited path.
+    path: PathData,
+    /// The geodesic length of the edited path in meters.
+    length: f64,
+    /// The problems with the edited path.
+    warnings: Vec<String>,
+}
+
+impl WaypointEdit {
+    /// Validates the edited path, including the warning from the edit if any.
+    fn new(path: PathData, warning: Option<String>) -> Self {
+        let warnings = warning.into_iter().chain(path.warnings()).collect();
         Self {
-            path: LineString(vec![]),
-            collection_points: MultiPoint(vec![]),
-            version: String::from("0.1.0"),
+            length: path.length(),
+            path,
+            warnings,
+        }
+    }
+}
+
+/// Errors when editing the path.
+///
+/// Sent to the frontend as an [`AppError`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(into = "AppError")]
+pub enum PathError {
+    /// There is no collection point at the index.
+    CollectionPointOutOfRange {
+        /// The index given.
+        index: usize,
+        /// The number of collection points.
+        len: usize,
+    },
+    /// There is no waypoint at the index.
+    WaypointOutOfRange {
+        /// The index given.
+        index: usize,
+        /// The number of waypoints.
+        len: usize,
+    },
+    /// Some of the points are outside of the boundary.
+    OutsideBoundary {
+        /// The indices of the waypoints outside of the boundary.
+        waypoints: Vec<usize>,
+        /// The indices of the collection points outside of the boundary.
+        collection_points: Vec<usize>,
+    },
+    /// The boundary is not a valid polygon.
+    InvalidBoundary {
+        /// The reason the boundary is invalid.
+        message: String,
+    },
+    /// The path can't be saved to application storage.
+    Storage {
+        /// The reason the path can't be saved.
+        message: String,
+    },
+}
+
+impl Display for PathError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::CollectionPointOutOfRange { index, len } => write!(
+                f,
+                "Invalid Collection Point: Index {index} is out of range for {len} points"
+            ),
+            Self::WaypointOutOfRange { index, len } => write!(
+                f,
+                "Invalid Waypoint: Index {index} is out of range for {len} waypoints"
+            ),
+            Self::OutsideBoundary {
+                waypoints,
+                collection_points,
+            } => write!(
+                f,
+                "Path Outside Boundary: Waypoints {waypoints:?} and Collection Points \
+                {collection_points:?} are outside of the boundary"
+            ),
+            Self::InvalidBoundary { message } => write!(f, "Invalid Boundary: {message}"),
+            Self::Storage { message } => write!(f, "Unable to Save Path: {message}"),
         }
     }
 }
 
+impl std::error::Error for PathError {}
+
+impl From<String> for PathError {
+    fn from(value: String) -> Self {
+        Self::Storage { message: value }
+    }
+}
+
+impl From<AppError> for PathError {
+    fn from(value: AppError) -> Self {
+        Self::Storage {
+            message: value.to_string(),
+        }
+    }
+}
+
+impl From<PathError> for AppError {
+    fn from(value: PathError) -> Self {
+        let message = value.to_string();
+        match value {
+            PathError::CollectionPointOutOfRange { index, len } => {
+                AppError::new(codes::PATH_COLLECTION_POINT_OUT_OF_RANGE, message)
+                    .with("index", index)
+                    .with("len", len)
+            }
+            PathError::WaypointOutOfRange { index, len } => {
+                AppError::new(codes::PATH_WAYPOINT_OUT_OF_RANGE, message)
+                    .with("index", index)
+                    .with("len", len)
+            }
+            PathError::OutsideBoundary {
+                waypoints,
+                collection_points,
+            } => AppError::new(codes::PATH_OUTSIDE_BOUNDARY, message)
+                .with("waypoints", waypoints)
+                .with("collection_points", collection_points),
+            PathError::InvalidBoundary { message: reason } => {
+                AppError::new(codes::PATH_INVALID_BOUNDARY, message).with("reason", reason)
+            }
+            PathError::Storage { message: reason } => {
+                AppError::new(codes::PATH_STORAGE, message).with("reason", reason)
+            }
+        }
+    }
+}
+
+/// The path being edited, kept in sync with application storage.
+#[derive(Debug, Default)]
+pub struct CurrentPath {
+    pub path: Mutex<PathData>,
+    /// The name of the mission the path is stored in, `None` for the default path.
+    pub mission: Mutex<Option<String>>,
+}
+
+/// Gets the file the current path is stored in.
+pub fn current_path_file(app_handle: &AppHandle) -> Result<PathBuf, AppError> {
+    let mission = app_handle
+        .state::<CurrentPath>()
+        .mission
+        .lock()
+        .unwrap()
+        .clone();
+    match mission {
+        Some(name) => missions::mission_file(app_handle, &name),
+        None => app_data_file(app_handle, "path.geojson"),
+    }
+}
+
+/// Checks if two sequences of coordinates differ by up to `epsilon` degrees.
+fn coords_approx_eq(a: &[Coord<f64>], b: &[Coord<f64>], epsilon: f64) -> bool {
+    a.len() == b.len()
+        && a.iter()
+            .zip(b)
+            .all(|(a, b)| (a.x - b.x).abs() <= epsilon && (a.y - b.y).abs() <= epsilon)
+}
+
+impl Default for PathData {
+    fn default() -> Self {
+        Self::new(LineString(vec![]), MultiPoint(vec![]))
+    }
+}
+
 impl FromStr for PathData {
-    type Err = String;
+    type Err = AppError;
 
     fn from_str(value: &str) -> Result<Self, Self::Err> {
-        let geojson: GeoJson = value.parse().map_err(|e| format!("{e}"))?;
+        let geojson: GeoJson = value
+            .parse()
+            .map_err(|e| AppError::new(codes::PATH_INVALID_GEOJSON, format!("{e}")))?;
         Self::try_from(geojson)
     }
 }
@@ -70,57 +484,159 @@ impl Display for PathData {
 }
 
 impl TryFrom<GeoJson> for PathData {
-    type Error = String;
+    type Error = AppError;
 
     fn try_from(value: GeoJson) -> Result<Self, Self::Error> {
+        let invalid = || AppError::new(codes::PATH_INVALID_GEOJSON, "Invalid Spec");
+        let missing_version = || {
+            AppError::new(
+                codes::PATH_MISSING_VERSION,
+                "Invalid Path GeoJSON: Missing Version",
+            )
+        };
+        let invalid_geometry = |geometry: &str, e: geojson::Error| {
+            AppError::new(
+                codes::PATH_INVALID_GEOMETRY,
+                format!("Invalid Path GeoJSON: Invalid {geometry}: {e}"),
+            )
+            .with("geometry", geometry)
+        };
+
         log::info!("Parsing Feature Collection");
-        let features =
-            FeatureCollection::try_from(value).map_err(|_| String::from("Invalid Spec"))?;
+        let features = FeatureCollection::try_from(value).map_err(|_| invalid())?;
         log::debug!("Feature Collection: {}", features);
 
         // Checking for version
         log::info!("Checking Version");
-        let foreign_members = features
+        let version = features
             .foreign_members
-            .ok_or(String::from("Invalid Path GeoJSON: Missing Version"))?;
-        let version = foreign_members
+            .as_ref()
+            .ok_or_else(missing_version)?
             .get("version")
-            .ok_or(String::from("Invalid Path GeoJSON: Missing Version"))?
+            .ok_or_else(missing_version)?
             .as_str()
-            .ok_or(String::from("Invalid Path GeoJSON: Invalid Version"))?;
+            .ok_or_else(|| {
+                AppError::new(
+                    codes::PATH_INVALID_VERSION,
+                    "Invalid Path GeoJSON: Invalid Version",
+                )
+            })?
+            .to_string();
         log::debug!("Version: {}", version);
+        let features = FeatureCollection::try_from(migrations::migrate_path(
+            GeoJson::from(features),
+            &version,
+        )?)
+        .map_err(|_| invalid())?;
 
-        log::info!("Extracting Features");
-        let features = features.features;
-        if features.len() != 2 {
-            return Err(String::from("Invalid Path GeoJSON: Path GeoJSON requires two features (Multi Point and Line String)."));
-        }
-        log::debug!("Features: {:?}", features);
+        let mut extra = PathExtra {
+            members: features.foreign_members.clone().unwrap_or_default(),
+            ..PathExtra::default()
+        };
+        extra.members.remove("version");
 
-        // Extracting Geometries
         log::info!("Extracting Geometries");
-        let mut geometries = features
-            .into_iter()
-            .map(|f| f.geometry)
-            .collect::<Option<Vec<Geometry>>>()
-            .ok_or(String::from("Invalid Path GeoJSON: Path GeoJSON requires two features (Multi Point and Line String)."))?;
-        log::debug!("Geometries: {:?}", geometries);
-
-        // Extracting Path and Points
-        log::info!("Extracting Path and Points");
-        let (path, points) = match (geometries.remove(0).value, geometries.remove(0).value) {
-            (p @ Value::MultiPoint(_), l @ Value::LineString(_)) => (l, p),
-            (l @ Value::LineString(_), p @ Value::MultiPoint(_)) => (l, p),
-            _ => return Err(String::from("Invalid Path GeoJSON: Path GeoJSON requires two features (Multi Point and Line String).")),
+        // The geometries with the properties of their features
+        let mut values = vec![];
+        for (i, feature) in features.features.into_iter().enumerate() {
+            match feature.geometry {
+                Some(geometry) => values.push((geometry.value, feature.properties)),
+                None => log::warn!("Ignoring Feature {} of Path GeoJSON without Geometry", i),
+            }
+        }
+        // Flattening geometry collections written by other tools
+        while let Some(i) = values
+            .iter()
+            .position(|(value, _)| matches!(value, Value::GeometryCollection(_)))
+        {
+            if let (Value::GeometryCollection(geometries), _) = values.remove(i) {
+                values.splice(i..i, geometries.into_iter().map(|g| (g.value, None)));
+            }
+        }
+        log::debug!("Geometries: {:?}", values);
+
+        let mut path = None;
+        let mut collection_points = None;
+        let mut points = vec![];
+        let mut boundary = None;
+        let mut notes = None;
+        for (value, properties) in values {
+            match value {
+                Value::LineString(_) if path.is_none() => {
+                    path = Some(
+                        LineString::try_from(value)
+                            .map_err(|e| invalid_geometry("Line String", e))?,
+                    );
+                    extra.path = properties.unwrap_or_default();
+                }
+                Value::MultiPoint(_) if collection_points.is_none() => {
+                    collection_points = Some(
+                        MultiPoint::try_from(value)
+                            .map_err(|e| invalid_geometry("Multi Point", e))?,
+                    );
+                    // The notes are properties of the Multi Point feature
+                    extra.collection_points = properties.unwrap_or_default();
+                    notes = extra.collection_points.remove("notes");
+                }
+                Value::Point(_) => {
+                    points.push(Point::try_from(value).map_err(|e| invalid_geometry("Point", e))?)
+                }
+                Value::Polygon(_) if boundary.is_none() => {
+                    boundary =
+                        Some(Polygon::try_from(value).map_err(|e| invalid_geometry("Polygon", e))?);
+                    extra.boundary = properties.unwrap_or_default();
+                }
+                value => log::warn!(
+                    "Ignoring Extra {} Geometry in Path GeoJSON",
+                    value.type_name()
+                ),
+            }
+        }
+
+        let path = path.ok_or_else(|| {
+            AppError::new(
+                codes::PATH_TOO_FEW_FEATURES,
+                "Invalid Path GeoJSON: Path GeoJSON requires a Line String feature.",
+            )
+        })?;
+        // Assembling the collection points from Point features if there is no Multi Point
+        let collection_points = match collection_points {
+            Some(v) => {
+                if !points.is_empty() {
+                    log::warn!("Ignoring {} Extra Points in Path GeoJSON", points.len());
+                }
+                v
+            }
+            // Route-only paths don't have collection points
+            None => {
+                log::info!(
+                    "Path GeoJSON has no Multi Point, using {} Points",
+                    points.len()
+                );
+                MultiPoint(points)
+            }
+        };
+        log::debug!("Path: {:?}", path);
+        log::debug!("Points: {:?}", collection_points);
+
+        let mut notes: BTreeMap<usize, String> = match notes {
+            Some(v) => serde_json::from_value(v).map_err(|e| {
+                AppError::new(
+                    codes::PATH_INVALID_GEOJSON,
+                    format!("Invalid Path GeoJSON: Invalid Notes: {e}"),
+                )
+            })?,
+            None => BTreeMap::new(),
         };
-        log::debug!("Path: {}", path);
-        log::debug!("Points: {}", points);
+        notes.retain(|index, _| *index < collection_points.0.len());
 
         Ok(Self {
-            // We can safely unwrap as we know the values will work
-            path: LineString::try_from(path).unwrap(),
-            collection_points: MultiPoint::try_from(points).unwrap(),
-            version: String::from(version),
+            path,
+            collection_points,
+            boundary,
+            notes,
+            version: String::from(migrations::PATH_DATA_VERSION),
+            extra,
         })
     }
 }
@@ -138,15 +654,41 @@ impl From<&mut PathData> for GeoJson {
 }
 
 impl From<&PathData> for GeoJson {
+    /// Converts to GeoJSON with the Multi Point and Line String features.
+    ///
+    /// The Multi Point feature is written even without collection points as the firmware
+    /// expects both features. The notes of the collection points are the `notes` property of
+    /// the Multi Point feature.
     fn from(value: &PathData) -> Self {
-        let points = geojson::Value::from(&value.collection_points);
-        let path = geojson::Value::from(&value.path);
-        let mut foreign_members = Map::new();
+        // Features without other properties are written like before
+        let feature = |geometry: geojson::Value, properties: JsonObject| {
+            let mut feature = geojson::Feature::from(geometry);
+            if !properties.is_empty() {
+                feature.properties = Some(properties);
+            }
+            feature
+        };
+
+        let mut properties = value.extra.collection_points.clone();
+        if !value.notes.is_empty() {
+            properties.insert(String::from("notes"), json!(&value.notes));
+        }
+        let points = feature(geojson::Value::from(&value.collection_points), properties);
+        let path = feature(geojson::Value::from(&value.path), value.extra.path.clone());
+        let mut foreign_members = value.extra.members.clone();
         foreign_members.insert(String::from("version"), json!(&value.version));
 
+        let mut features = vec![points, path];
+        if let Some(boundary) = &value.boundary {
+            features.push(feature(
+                geojson::Value::from(boundary),
+                value.extra.boundary.clone(),
+            ));
+        }
+
         let collection = FeatureCollection {
             bbox: None,
-            features: vec![points.into(), path.into()],
+            features,
             foreign_members: Some(foreign_members),
         };
         GeoJson::from(collection)
@@ -175,21 +717,19 @@ impl<'de> Deserialize<'de> for PathData {
 
 /// Read data from application storage.
 #[tauri::command]
-pub fn read_path(app_handle: AppHandle) -> Result<PathData, String> {
+pub fn read_path(app_handle: AppHandle) -> Result<PathData, AppError> {
     log::debug!("Reading Path");
-    let mut data_dir = app_handle
-        .path_resolver()
-        .app_data_dir()
-        .ok_or(String::from("Unable to Get App Data Directory"))?;
-    data_dir.push("path.geojson");
+    let data_dir = current_path_file(&app_handle)?;
     log::debug!("Application GeoJSON Path: {}", data_dir.display());
 
-    import_path(data_dir)
+    let path = import_path(data_dir)?;
+    *app_handle.state::<CurrentPath>().path.lock().unwrap() = path.clone();
+    Ok(path)
 }
 
 /// Import path data from the file system.
 #[tauri::command]
-pub fn import_path(import_path: PathBuf) -> Result<PathData, String> {
+pub fn import_path(import_path: PathBuf) -> Result<PathData, AppError> {
     log::debug!("Importing from: {}", import_path.display());
     Ok(match file::read_string(&import_path) {
         Ok(v) => PathData::from_str(&v)?,
@@ -201,31 +741,1007 @@ pub fn import_path(import_path: PathBuf) -> Result<PathData, String> {
                 );
                 PathData::default()
             }
-            _ => return Err(e.to_string()),
+            _ => return Err(AppError::io(e, &import_path)),
         },
-        Err(e) => return Err(e.to_string()),
+        Err(e) => {
+            return Err(AppError::new(codes::IO_OTHER, e.to_string())
+                .with("path", import_path.display().to_string()))
+        }
     })
 }
 
 /// Export path data to the file system.
 #[tauri::command]
-pub fn export_path(export_path: PathBuf, path: PathData) -> Result<(), String> {
+pub fn export_path(export_path: PathBuf, path: PathData) -> Result<(), AppError> {
+    log::debug!("Exporting to: {}", export_path.display());
+    write_atomically(&export_path, |file| write!(file, "{}", path))
+}
+
+/// Converts the path to GPX.
+///
+/// The path is written as a track and the collection points as named waypoints.
+fn to_gpx(path: &PathData) -> Gpx {
+    let segment = TrackSegment {
+        points: path.path.points().map(Waypoint::new).collect(),
+    };
+    let track = Track {
+        segments: vec![segment],
+        ..Default::default()
+    };
+
+    let waypoints = path
+        .collection_points
+        .iter()
+        .enumerate()
+        .map(|(i, point)| {
+            let mut waypoint = Waypoint::new(*point);
+            waypoint.name = Some(format!("CP-{}", i + 1));
+            waypoint
+        })
+        .collect();
+
+    Gpx {
+        version: GpxVersion::Gpx11,
+        creator: Some(String::from("Babara Project Desktop")),
+        waypoints,
+        tracks: vec![track],
+        ..Default::default()
+    }
+}
+
+/// Export path data in GPX format to the file system.
+#[tauri::command]
+pub fn export_path_gpx(export_path: PathBuf, path: PathData) -> Result<(), AppError> {
     log::debug!("Exporting to: {}", export_path.display());
-    let mut file = std::fs::File::create(export_path).map_err(|e| e.to_string())?;
-    write!(file, "{}", path).map_err(|e| e.to_string())?;
-    Ok(())
+    let mut buf = vec![];
+    gpx::write(&to_gpx(&path), &mut buf).map_err(|e| export_error(&export_path, e))?;
+    write_atomically(&export_path, |file| file.write_all(&buf))
+}
+
+/// Converts GPX to path data.
+///
+/// The tracks are joined into the path, falling back to the first route when there are no
+/// tracks, and the waypoints become the collection points.
+fn from_gpx(gpx: Gpx) -> Result<PathData, AppError> {
+    let path: Vec<Point<f64>> = if !gpx.tracks.is_empty() {
+        if gpx.tracks.len() > 1 {
+            log::warn!(
+                "GPX has {} Tracks, joining them into one path",
+                gpx.tracks.len()
+            );
+        }
+        gpx.tracks
+            .into_iter()
+            .flat_map(|track| track.segments)
+            .flat_map(|segment| segment.points)
+            .map(|waypoint| waypoint.point())
+            .collect()
+    } else if let Some(route) = gpx.routes.into_iter().next() {
+        route
+            .points
+            .into_iter()
+            .map(|waypoint| waypoint.point())
+            .collect()
+    } else {
+        return Err(AppError::new(
+            codes::PATH_TOO_FEW_FEATURES,
+            "Invalid Path GPX: GPX requires a track or a route for the path.",
+        ));
+    };
+    log::debug!("Path: {:?}", path);
+
+    Ok(PathData::new(
+        LineString::from(path),
+        gpx.waypoints
+            .into_iter()
+            .map(|waypoint| waypoint.point())
+            .collect(),
+    ))
+}
+
+/// Import path data in GPX format from the file system.
+#[tauri::command]
+pub fn import_path_gpx(import_path: PathBuf) -> Result<PathData, AppError> {
+    log::debug!("Importing from: {}", import_path.display());
+    let file = std::fs::File::open(&import_path).map_err(|e| AppError::io(e, &import_path))?;
+    let gpx = gpx::read(std::io::BufReader::new(file))
+        .map_err(|e| AppError::reason(codes::PATH_INVALID_GPX, "Invalid Path GPX", e))?;
+    from_gpx(gpx)
 }
 
 /// Save data to application storage.
+///
+/// The path is not saved if it leaves its boundary. Saves wait for the other writes of the
+/// current path to finish, so the file and the current path are always the last path saved.
 #[tauri::command]
-pub fn save_path(app_handle: AppHandle, path: PathData) -> Result<(), String> {
-    log::debug!("Saving Path");
-    let mut data_dir = app_handle
-        .path_resolver()
-        .app_data_dir()
-        .ok_or(String::from("Unable to Get App Data Directory"))?;
-    data_dir.push("path.geojson");
-    log::debug!("Application GeoJSON Path: {}", data_dir.display());
+pub fn save_path(app_handle: AppHandle, path: PathData) -> Result<(), PathError> {
+    operations::record(
+        &app_handle,
+        "save_path",
+        json!({
+            "waypoints": path.path().0.len(),
+            "collection_points": path.collection_points().len(),
+        }),
+        || {
+            log::debug!("Saving Path");
+            path.check_boundary()?;
+            let data_dir = current_path_file(&app_handle)?;
+            log::debug!("Application GeoJSON Path: {}", data_dir.display());
+
+            let state = app_handle.state::<CurrentPath>();
+            let mut current = state.path.lock().unwrap();
+            // Saving the current path again is not recorded in the history
+            if path == *current {
+                return Ok(());
+            }
+            export_path(data_dir, path.clone())?;
+            let previous = std::mem::replace(&mut *current, path);
+            drop(current);
+            app_handle
+                .state::<PathHistory>()
+                .record(&app_handle, previous);
+            Ok(())
+        },
+    )
+}
+
+/// Replaces the current path without recording it in the history, returning the path replaced.
+pub fn replace_path(app_handle: &AppHandle, path: PathData) -> Result<PathData, AppError> {
+    let state = app_handle.state::<CurrentPath>();
+    let mut current = state.path.lock().unwrap();
+    export_path(current_path_file(app_handle)?, path.clone())?;
+    let previous = std::mem::replace(&mut *current, path.clone());
+    drop(current);
+
+    if let Err(e) = app_handle.emit_all("path-updated", &path) {
+        log::warn!("Unable to emit path-updated event: {}", e);
+    }
+    Ok(previous)
+}
+
+/// Compare two paths, e.g. to check if the path has unsaved changes.
+///
+/// The coordinates can differ by up to `epsilon` degrees if it is given, otherwise the paths
+/// must be exactly equal.
+#[tauri::command]
+pub fn compare_paths(a: PathData, b: PathData, epsilon: Option<f64>) -> bool {
+    match epsilon {
+        Some(epsilon) => a.approx_eq(&b, epsilon),
+        None => a == b,
+    }
+}
+
+/// Applies an edit to the current path, saving it and notifying the windows of the change.
+///
+/// Returns the edited path along with the result of the edit.
+fn edit_path<T>(
+    app_handle: &AppHandle,
+    edit: impl FnOnce(&mut PathData) -> Result<T, PathError>,
+) -> Result<(PathData, T), PathError> {
+    let state = app_handle.state::<CurrentPath>();
+    let mut path = state.path.lock().unwrap();
+    let mut edited = path.clone();
+    let result = edit(&mut edited)?;
+    // Edits that don't change the path are not saved or recorded in the history
+    if edited == *path {
+        return Ok((edited, result));
+    }
+
+    let data_dir = current_path_file(app_handle)?;
+    export_path(data_dir, edited.clone())?;
+    let previous = std::mem::replace(&mut *path, edited.clone());
+    app_handle
+        .state::<PathHistory>()
+        .record(app_handle, previous);
+    drop(path);
+
+    if let Err(e) = app_handle.emit_all("path-updated", &edited) {
+        log::warn!("Unable to emit path-updated event: {}", e);
+    }
+    Ok((edited, result))
+}
+
+/// Add a collection point to the end of the current path.
+#[tauri::command]
+pub fn add_collection_point(
+    app_handle: AppHandle,
+    lng: f64,
+    lat: f64,
+) -> Result<PathData, PathError> {
+    log::debug!("Adding Collection Point [{}, {}]", lng, lat);
+    edit_path(&app_handle, |path| {
+        path.add_collection_point(Point::new(lng, lat));
+        Ok(())
+    })
+    .map(|(path, _)| path)
+}
+
+/// Set the note of the collection point at `index` of the current path, e.g. "sample bottle #3
+/// taken".
+///
+/// An empty note removes the note.
+#[tauri::command]
+pub fn set_collection_point_note(
+    app_handle: AppHandle,
+    index: usize,
+    text: String,
+) -> Result<PathData, PathError> {
+    log::debug!("Setting Note of Collection Point {}", index);
+    edit_path(&app_handle, |path| {
+        path.set_collection_point_note(index, text)
+    })
+    .map(|(path, _)| path)
+}
+
+/// Remove the collection point at `index` of the current path.
+#[tauri::command]
+pub fn remove_collection_point(app_handle: AppHandle, index: usize) -> Result<PathData, PathError> {
+    log::debug!("Removing Collection Point {}", index);
+    edit_path(&app_handle, |path| path.remove_collection_point(index)).map(|(path, _)| path)
+}
+
+/// Move the collection point at `index` of the current path to a new coordinate.
+#[tauri::command]
+pub fn move_collection_point(
+    app_handle: AppHandle,
+    index: usize,
+    lng: f64,
+    lat: f64,
+) -> Result<PathData, PathError> {
+    log::debug!("Moving Collection Point {} to [{}, {}]", index, lng, lat);
+    edit_path(&app_handle, |path| {
+        path.move_collection_point(index, Point::new(lng, lat))
+    })
+    .map(|(path, _)| path)
+}
 
-    export_path(data_dir, path)
+/// Insert a waypoint to the current path at `index`.
+#[tauri::command]
+pub fn insert_waypoint(
+    app_handle: AppHandle,
+    index: usize,
+    lng: f64,
+    lat: f64,
+) -> Result<WaypointEdit, PathError> {
+    log::debug!("Inserting Waypoint {} at [{}, {}]", index, lng, lat);
+    let (path, _) = edit_path(&app_handle, |path| {
+        path.insert_waypoint(index, Point::new(lng, lat))
+    })?;
+    Ok(WaypointEdit::new(path, None))
+}
+
+/// Move the waypoint at `index` of the current path to a new coordinate.
+#[tauri::command]
+pub fn update_waypoint(
+    app_handle: AppHandle,
+    index: usize,
+    lng: f64,
+    lat: f64,
+) -> Result<WaypointEdit, PathError> {
+    log::debug!("Moving Waypoint {} to [{}, {}]", index, lng, lat);
+    let (path, _) = edit_path(&app_handle, |path| {
+        path.update_waypoint(index, Point::new(lng, lat))
+    })?;
+    Ok(WaypointEdit::new(path, None))
+}
+
+/// Remove the waypoint at `index` of the current path.
+#[tauri::command]
+pub fn remove_waypoint(app_handle: AppHandle, index: usize) -> Result<WaypointEdit, PathError> {
+    log::debug!("Removing Waypoint {}", index);
+    let (path, warning) = edit_path(&app_handle, |path| path.remove_waypoint(index))?;
+    Ok(WaypointEdit::new(path, warning))
+}
+
+/// Reverse the direction of the current path.
+#[tauri::command]
+pub fn reverse_path(app_handle: AppHandle) -> Result<WaypointEdit, PathError> {
+    log::debug!("Reversing Path");
+    let (path, _) = edit_path(&app_handle, |path| {
+        path.reverse_path();
+        Ok(())
+    })?;
+    Ok(WaypointEdit::new(path, None))
+}
+
+/// Set the area the current path is allowed to travel in.
+///
+/// The polygon is given as its `[lng, lat]` vertices.
+#[tauri::command]
+pub fn set_boundary(
+    app_handle: AppHandle,
+    polygon: Vec<(f64, f64)>,
+) -> Result<PathData, PathError> {
+    log::debug!("Setting Boundary with {} Vertices", polygon.len());
+    let mut exterior: LineString<f64> = polygon.into_iter().collect();
+    exterior.close();
+    // A closed ring repeats its first vertex
+    if exterior.0.len() < 4 {
+        return Err(PathError::InvalidBoundary {
+            message: String::from("Boundary requires at least three vertices."),
+        });
+    }
+    if exterior
+        .0
+        .iter()
+        .any(|c| !c.x.is_finite() || !c.y.is_finite())
+    {
+        return Err(PathError::InvalidBoundary {
+            message: String::from("Boundary has an invalid coordinate."),
+        });
+    }
+
+    let boundary = Polygon::new(exterior, vec![]);
+    edit_path(&app_handle, |path| {
+        path.set_boundary(Some(boundary));
+        Ok(())
+    })
+    .map(|(path, _)| path)
+}
+
+/// Remove the boundary of the current path.
+#[tauri::command]
+pub fn clear_boundary(app_handle: AppHandle) -> Result<PathData, PathError> {
+    log::debug!("Clearing Boundary");
+    edit_path(&app_handle, |path| {
+        path.set_boundary(None);
+        Ok(())
+    })
+    .map(|(path, _)| path)
+}
+
+#[cfg(test)]
+mod tests {
+    use geo_types::Point;
+
+    use super::*;
+    use crate::protocol::tests::Random;
+    use crate::storage::tests::temp_dir;
+
+    /// A path with coordinates using the full precision of `f64`.
+    const PATH_GEOJSON: &str = include_str!("../tests/fixtures/path.geojson");
+
+    /// The path in [`PATH_GEOJSON`] in GPX format.
+    const PATH_GPX: &str = include_str!("../tests/fixtures/path.gpx");
+
+    /// The path in [`PATH_GEOJSON`] exported by QGIS, with the collection points as Point
+    /// features.
+    const PATH_QGIS_GEOJSON: &str = include_str!("../tests/fixtures/path_qgis.geojson");
+
+    /// The path in [`PATH_GEOJSON`] drawn in geojson.io, in another order with a boundary and
+    /// a launch site.
+    const PATH_GEOJSON_IO_GEOJSON: &str = include_str!("../tests/fixtures/path_geojson_io.geojson");
+
+    /// Gets the names and coordinates of GPX waypoints.
+    fn named_points(waypoints: &[Waypoint]) -> Vec<(Option<String>, Point<f64>)> {
+        waypoints
+            .iter()
+            .map(|waypoint| (waypoint.name.clone(), waypoint.point()))
+            .collect()
+    }
+
+    /// Gets the coordinates of the points in the GPX tracks.
+    fn track_points(gpx: &Gpx) -> Vec<Point<f64>> {
+        gpx.tracks
+            .iter()
+            .flat_map(|track| &track.segments)
+            .flat_map(|segment| &segment.points)
+            .map(Waypoint::point)
+            .collect()
+    }
+
+    #[test]
+    fn to_gpx_matches_fixture() {
+        let path = PathData::from_str(PATH_GEOJSON).unwrap();
+        let expected = gpx::read(PATH_GPX.as_bytes()).unwrap();
+
+        let gpx = to_gpx(&path);
+
+        assert_eq!(gpx.version, GpxVersion::Gpx11);
+        assert_eq!(gpx.tracks.len(), 1);
+        assert_eq!(track_points(&gpx), track_points(&expected));
+        assert_eq!(
+            named_points(&gpx.waypoints),
+            named_points(&expected.waypoints)
+        );
+    }
+
+    #[test]
+    fn export_path_gpx_keeps_full_precision() {
+        let dir = temp_dir("path-export-gpx");
+        let export_path = dir.join("path.gpx");
+        let path = PathData::from_str(PATH_GEOJSON).unwrap();
+
+        export_path_gpx(
+            export_path.clone(),
+            PathData::from_str(PATH_GEOJSON).unwrap(),
+        )
+        .unwrap();
+        let gpx = gpx::read(std::fs::File::open(&export_path).unwrap()).unwrap();
+
+        let points: Vec<_> = path.path().points().collect();
+        assert_eq!(track_points(&gpx), points);
+        let names = ["CP-1", "CP-2"].map(|name| Some(String::from(name)));
+        let collection_points = names
+            .into_iter()
+            .zip(path.collection_points().iter().copied())
+            .collect::<Vec<_>>();
+        assert_eq!(named_points(&gpx.waypoints), collection_points);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn gpx_round_trip_keeps_path() {
+        let path = PathData::from_str(PATH_GEOJSON).unwrap();
+
+        let round_trip = from_gpx(to_gpx(&path)).unwrap();
+
+        assert_eq!(round_trip.path(), path.path());
+        assert_eq!(round_trip.collection_points(), path.collection_points());
+    }
+
+    #[test]
+    fn import_path_gpx_reads_fixture() {
+        let dir = temp_dir("path-import-gpx");
+        let import_path = dir.join("path.gpx");
+        std::fs::write(&import_path, PATH_GPX).unwrap();
+        let expected = PathData::from_str(PATH_GEOJSON).unwrap();
+
+        let path = import_path_gpx(import_path).unwrap();
+
+        assert_eq!(path.path(), expected.path());
+        assert_eq!(path.collection_points(), expected.collection_points());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn from_gpx_joins_tracks_or_uses_first_route() {
+        let mut gpx = gpx::read(PATH_GPX.as_bytes()).unwrap();
+        gpx.waypoints.clear();
+        let mut second = gpx.tracks[0].clone();
+        second.segments[0].points.truncate(1);
+        gpx.tracks.push(second);
+
+        let path = from_gpx(gpx.clone()).unwrap();
+        assert_eq!(path.path().0.len(), 4);
+        assert_eq!(path.path().0[3], path.path().0[0]);
+        assert!(path.collection_points().0.is_empty());
+
+        let mut route = gpx::Route::new();
+        route.points = gpx.tracks.remove(0).segments.remove(0).points;
+        gpx.tracks.clear();
+        gpx.routes = vec![route, gpx::Route::new()];
+        assert_eq!(from_gpx(gpx).unwrap().path().0.len(), 3);
+
+        assert!(from_gpx(Gpx::default()).is_err());
+    }
+
+    #[test]
+    fn collection_points_are_added_moved_and_removed() {
+        let mut path = PathData::default();
+
+        path.add_collection_point(Point::new(101.5, 3.1));
+        path.add_collection_point(Point::new(101.6, 3.2));
+        path.move_collection_point(0, Point::new(101.4, 3.0))
+            .unwrap();
+        assert_eq!(
+            path.collection_points().0,
+            vec![Point::new(101.4, 3.0), Point::new(101.6, 3.2)]
+        );
+
+        assert_eq!(path.remove_collection_point(0), Ok(Point::new(101.4, 3.0)));
+        assert_eq!(path.collection_points().0, vec![Point::new(101.6, 3.2)]);
+    }
+
+    #[test]
+    fn collection_point_index_out_of_range_is_rejected() {
+        let mut path = PathData::default();
+        path.add_collection_point(Point::new(101.5, 3.1));
+
+        let out_of_range = PathError::CollectionPointOutOfRange { index: 1, len: 1 };
+        assert_eq!(path.remove_collection_point(1), Err(out_of_range.clone()));
+        assert_eq!(
+            path.move_collection_point(1, Point::new(0.0, 0.0)),
+            Err(out_of_range.clone())
+        );
+        assert_eq!(path.collection_points().0, vec![Point::new(101.5, 3.1)]);
+        assert_eq!(
+            serde_json::to_value(out_of_range).unwrap(),
+            json!({
+                "code": codes::PATH_COLLECTION_POINT_OUT_OF_RANGE,
+                "message": "Invalid Collection Point: Index 1 is out of range for 1 points",
+                "params": { "index": 1, "len": 1 },
+            })
+        );
+    }
+
+    /// Creates a path through `waypoints`, given as `(lng, lat)`.
+    fn path_through(waypoints: &[(f64, f64)]) -> PathData {
+        let mut path = PathData::default();
+        for (i, (lng, lat)) in waypoints.iter().enumerate() {
+            path.insert_waypoint(i, Point::new(*lng, *lat)).unwrap();
+        }
+        path
+    }
+
+    /// Gets the `(lng, lat)` of the waypoints of a path.
+    fn waypoints(path: &PathData) -> Vec<(f64, f64)> {
+        path.path().0.iter().map(|c| (c.x, c.y)).collect()
+    }
+
+    #[test]
+    fn waypoints_are_inserted_updated_and_reversed() {
+        let mut path = path_through(&[(101.5, 3.1), (101.7, 3.3)]);
+
+        path.insert_waypoint(1, Point::new(101.6, 3.2)).unwrap();
+        path.update_waypoint(2, Point::new(101.8, 3.4)).unwrap();
+        assert_eq!(
+            waypoints(&path),
+            vec![(101.5, 3.1), (101.6, 3.2), (101.8, 3.4)]
+        );
+
+        path.reverse_path();
+        assert_eq!(
+            waypoints(&path),
+            vec![(101.8, 3.4), (101.6, 3.2), (101.5, 3.1)]
+        );
+    }
+
+    #[test]
+    fn waypoint_index_out_of_range_is_rejected() {
+        let mut path = path_through(&[(101.5, 3.1), (101.6, 3.2)]);
+
+        let out_of_range = PathError::WaypointOutOfRange { index: 3, len: 2 };
+        assert_eq!(
+            path.insert_waypoint(3, Point::new(0.0, 0.0)),
+            Err(out_of_range.clone())
+        );
+        let out_of_range = PathError::WaypointOutOfRange { index: 2, len: 2 };
+        assert_eq!(
+            path.update_waypoint(2, Point::new(0.0, 0.0)),
+            Err(out_of_range.clone())
+        );
+        assert_eq!(path.remove_waypoint(2), Err(out_of_range));
+        assert_eq!(waypoints(&path), vec![(101.5, 3.1), (101.6, 3.2)]);
+    }
+
+    #[test]
+    fn removing_waypoint_from_two_point_path_clears_it() {
+        let mut path = path_through(&[(101.5, 3.1), (101.6, 3.2), (101.7, 3.3)]);
+
+        assert_eq!(path.remove_waypoint(1), Ok(None));
+        assert_eq!(waypoints(&path), vec![(101.5, 3.1), (101.7, 3.3)]);
+
+        let warning = path.remove_waypoint(0).unwrap();
+        assert!(warning.is_some());
+        assert!(path.path().0.is_empty());
+        assert!(path.warnings().is_empty());
+    }
+
+    #[test]
+    fn warnings_report_invalid_waypoints() {
+        let path = path_through(&[(101.5, 3.1), (f64::NAN, 3.2)]);
+        assert_eq!(
+            path.warnings(),
+            vec![String::from("Waypoint 1 has an invalid coordinate.")]
+        );
+
+        let path = path_through(&[(101.5, 3.1)]);
+        assert_eq!(path.warnings().len(), 1);
+    }
+
+    #[test]
+    fn waypoint_edit_reports_geodesic_length() {
+        // One degree of latitude along a meridian is about 110.6 km at the equator
+        let path = path_through(&[(0.0, 0.0), (0.0, 1.0)]);
+
+        let edit = WaypointEdit::new(path, None);
+
+        assert!((edit.length - 110_574.4).abs() < 1.0, "{}", edit.length);
+        assert!(edit.warnings.is_empty());
+    }
+
+    /// Creates the square boundary from `(101.5, 3.1)` to `(101.6, 3.2)`.
+    fn square_boundary() -> Polygon<f64> {
+        Polygon::new(
+            LineString::from(vec![
+                (101.5, 3.1),
+                (101.6, 3.1),
+                (101.6, 3.2),
+                (101.5, 3.2),
+                (101.5, 3.1),
+            ]),
+            vec![],
+        )
+    }
+
+    #[test]
+    fn check_boundary_accepts_points_on_edge() {
+        let mut path = path_through(&[(101.5, 3.1), (101.55, 3.15), (101.6, 3.15)]);
+        path.add_collection_point(Point::new(101.55, 3.2));
+        path.set_boundary(Some(square_boundary()));
+
+        assert_eq!(path.check_boundary(), Ok(()));
+    }
+
+    #[test]
+    fn check_boundary_reports_points_outside() {
+        let mut path = path_through(&[(101.55, 3.15), (101.7, 3.15), (101.55, 3.0)]);
+        path.add_collection_point(Point::new(101.55, 3.15));
+        path.add_collection_point(Point::new(101.6000001, 3.15));
+        assert_eq!(path.check_boundary(), Ok(()));
+
+        path.set_boundary(Some(square_boundary()));
+
+        assert_eq!(
+            path.check_boundary(),
+            Err(PathError::OutsideBoundary {
+                waypoints: vec![1, 2],
+                collection_points: vec![1],
+            })
+        );
+    }
+
+    #[test]
+    fn boundary_round_trips_as_third_feature() {
+        let mut path = path_through(&[(101.55, 3.15), (101.56, 3.16)]);
+        path.set_boundary(Some(square_boundary()));
+
+        let geojson = GeoJson::from(&path);
+        match &geojson {
+            GeoJson::FeatureCollection(collection) => assert_eq!(collection.features.len(), 3),
+            _ => panic!("{geojson}"),
+        }
+        let parsed = PathData::try_from(geojson).unwrap();
+
+        assert_eq!(parsed.boundary(), Some(&square_boundary()));
+        assert_eq!(parsed.path(), path.path());
+    }
+
+    #[test]
+    fn path_without_boundary_still_loads() {
+        let path = PathData::from_str(PATH_GEOJSON).unwrap();
+
+        assert_eq!(path.boundary(), None);
+        assert_eq!(path.check_boundary(), Ok(()));
+    }
+
+    #[test]
+    fn extra_line_string_is_ignored() {
+        let mut geojson: serde_json::Value = serde_json::from_str(PATH_GEOJSON).unwrap();
+        let features = geojson["features"].as_array_mut().unwrap();
+        let mut extra = features[0].clone();
+        extra["geometry"]["coordinates"] = json!([[0.0, 0.0], [1.0, 1.0]]);
+        features.push(extra);
+
+        let path = PathData::from_str(&geojson.to_string()).unwrap();
+
+        assert_eq!(path, PathData::from_str(PATH_GEOJSON).unwrap());
+    }
+
+    #[test]
+    fn qgis_export_with_point_features_is_parsed() {
+        let path = PathData::from_str(PATH_QGIS_GEOJSON).unwrap();
+        let expected = PathData::from_str(PATH_GEOJSON).unwrap();
+
+        assert_eq!(path.path(), expected.path());
+        assert_eq!(path.collection_points(), expected.collection_points());
+        assert_eq!(path.boundary(), None);
+    }
+
+    #[test]
+    fn geojson_io_export_in_another_order_is_parsed() {
+        let path = PathData::from_str(PATH_GEOJSON_IO_GEOJSON).unwrap();
+        let expected = PathData::from_str(PATH_GEOJSON).unwrap();
+
+        assert_eq!(path.path(), expected.path());
+        // The launch site Point is ignored as there is a Multi Point
+        assert_eq!(path.collection_points(), expected.collection_points());
+        assert_eq!(path.boundary().unwrap().exterior().0.len(), 5);
+    }
+
+    #[test]
+    fn geometry_collections_are_flattened() {
+        let geojson = json!({
+            "type": "FeatureCollection",
+            "version": migrations::PATH_DATA_VERSION,
+            "features": [{
+                "type": "Feature",
+                "properties": {},
+                "geometry": {
+                    "type": "GeometryCollection",
+                    "geometries": [
+                        { "type": "Point", "coordinates": [101.5, 3.1] },
+                        { "type": "LineString", "coordinates": [[101.5, 3.1], [101.6, 3.2]] },
+                    ],
+                },
+            }],
+        });
+
+        let path = PathData::from_str(&geojson.to_string()).unwrap();
+
+        assert_eq!(path.path().0.len(), 2);
+        assert_eq!(path.collection_points().0, vec![Point::new(101.5, 3.1)]);
+    }
+
+    #[test]
+    fn path_without_line_string_is_rejected() {
+        let mut geojson: serde_json::Value = serde_json::from_str(PATH_GEOJSON).unwrap();
+        geojson["features"].as_array_mut().unwrap().remove(0);
+
+        let error = PathData::from_str(&geojson.to_string()).unwrap_err();
+
+        assert!(error.to_string().contains("Line String"), "{error}");
+        let error = serde_json::to_value(error).unwrap();
+        assert_eq!(error["code"], codes::PATH_TOO_FEW_FEATURES);
+    }
+
+    #[test]
+    fn gpx_without_track_or_route_is_rejected() {
+        let error = serde_json::to_value(from_gpx(Gpx::default()).unwrap_err()).unwrap();
+
+        assert_eq!(error["code"], codes::PATH_TOO_FEW_FEATURES);
+    }
+
+    #[test]
+    fn import_missing_gpx_is_not_found() {
+        let dir = temp_dir("path-missing-gpx");
+        let import_path = dir.join("missing.gpx");
+
+        let error =
+            serde_json::to_value(import_path_gpx(import_path.clone()).unwrap_err()).unwrap();
+
+        assert_eq!(error["code"], codes::IO_NOT_FOUND);
+        assert_eq!(error["params"]["path"], import_path.display().to_string());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn invalid_geometry_is_reported_instead_of_panicking() {
+        let mut geojson: serde_json::Value = serde_json::from_str(PATH_GEOJSON).unwrap();
+        geojson["features"][1]["geometry"]["coordinates"] = json!([[101.5]]);
+
+        assert!(PathData::from_str(&geojson.to_string()).is_err());
+    }
+
+    #[test]
+    fn default_path_round_trips() {
+        let path = PathData::default();
+
+        assert_eq!(PathData::from_str(&path.to_string()).unwrap(), path);
+    }
+
+    #[test]
+    fn path_with_only_line_string_has_no_collection_points() {
+        let mut geojson: serde_json::Value = serde_json::from_str(PATH_GEOJSON).unwrap();
+        geojson["features"].as_array_mut().unwrap().remove(1);
+
+        let path = PathData::from_str(&geojson.to_string()).unwrap();
+
+        assert_eq!(
+            path.path(),
+            PathData::from_str(PATH_GEOJSON).unwrap().path()
+        );
+        assert_eq!(path.collection_points(), &MultiPoint(vec![]));
+    }
+
+    #[test]
+    fn route_only_path_keeps_empty_multi_point_feature() {
+        let path = path_through(&[(101.55, 3.15), (101.56, 3.16)]);
+
+        let geojson = GeoJson::from(&path);
+        let geometries: Vec<_> = match &geojson {
+            GeoJson::FeatureCollection(collection) => collection
+                .features
+                .iter()
+                .map(|f| f.geometry.as_ref().unwrap().value.type_name())
+                .collect(),
+            _ => panic!("{geojson}"),
+        };
+
+        assert_eq!(geometries, vec!["MultiPoint", "LineString"]);
+        assert_eq!(PathData::try_from(geojson).unwrap(), path);
+    }
+
+    /// Creates a random path, with a random boundary if `boundary` is set.
+    fn random_path(random: &mut Random, boundary: bool) -> PathData {
+        let waypoints = 2 + random.below(6);
+        let collection_points = random.below(6);
+        let mut coord = || Coord {
+            x: random.between(-180.0, 180.0),
+            y: random.between(-90.0, 90.0),
+        };
+        let path: Vec<_> = (0..waypoints).map(|_| coord()).collect();
+        let points: Vec<_> = (0..collection_points).map(|_| Point(coord())).collect();
+        let mut ring: Vec<_> = (0..3).map(|_| coord()).collect();
+        ring.push(ring[0]);
+        let mut path = PathData::new(LineString(path), MultiPoint(points));
+        if boundary {
+            path.set_boundary(Some(Polygon::new(LineString(ring), vec![])));
+        }
+        path
+    }
+
+    #[test]
+    fn path_round_trips_through_geojson() {
+        let mut random = Random(0x6A09_E667_F3BC_C908);
+        for i in 0..50 {
+            let path = random_path(&mut random, i % 2 == 0);
+
+            let parsed = PathData::from_str(&path.to_string()).unwrap();
+
+            assert!(parsed.approx_eq(&path, 1e-9), "{path}");
+        }
+    }
+
+    #[test]
+    fn path_approx_eq_allows_differences_up_to_epsilon() {
+        let path = PathData::from_str(PATH_GEOJSON).unwrap();
+        let mut close = path.clone();
+        let mut coords = close.path().0.clone();
+        coords[0].x += 1e-7;
+        close.set_path(LineString(coords));
+
+        assert_ne!(close, path);
+        assert!(close.approx_eq(&path, 1e-6));
+        assert!(!close.approx_eq(&path, 1e-8));
+    }
+
+    #[test]
+    fn path_approx_eq_compares_boundary_and_lengths() {
+        let path = PathData::from_str(PATH_GEOJSON).unwrap();
+        let mut bounded = path.clone();
+        bounded.set_boundary(Some(Polygon::new(
+            LineString::from(vec![(0.0, 0.0), (1.0, 0.0), (0.0, 1.0), (0.0, 0.0)]),
+            vec![],
+        )));
+        let mut fewer_points = path.clone();
+        let mut points = fewer_points.collection_points().0.clone();
+        points.pop();
+        fewer_points.set_collection_points(MultiPoint(points));
+
+        assert!(!bounded.approx_eq(&path, 1.0));
+        assert!(!path.approx_eq(&bounded, 1.0));
+        assert!(!fewer_points.approx_eq(&path, 1.0));
+        assert!(bounded.approx_eq(&bounded.clone(), 0.0));
+    }
+
+    #[test]
+    fn compare_paths_is_exact_without_epsilon() {
+        let path = PathData::from_str(PATH_GEOJSON).unwrap();
+        let mut close = path.clone();
+        let mut coords = close.path().0.clone();
+        coords[1].y -= 1e-12;
+        close.set_path(LineString(coords));
+
+        assert!(compare_paths(path.clone(), path.clone(), None));
+        assert!(!compare_paths(path.clone(), close.clone(), None));
+        assert!(compare_paths(path, close, Some(1e-9)));
+    }
+
+    /// Creates a path with `count` collection points.
+    fn path_with_points(count: usize) -> PathData {
+        let mut path = PathData::default();
+        for i in 0..count {
+            path.add_collection_point(Point::new(101.5 + i as f64 / 100.0, 3.1));
+        }
+        path
+    }
+
+    #[test]
+    fn collection_point_notes_follow_their_points() {
+        let mut path = path_with_points(3);
+        path.set_collection_point_note(0, String::from("first"))
+            .unwrap();
+        path.set_collection_point_note(2, String::from("sample bottle #3 taken"))
+            .unwrap();
+
+        path.remove_collection_point(1).unwrap();
+        assert_eq!(
+            path.notes(),
+            &BTreeMap::from([
+                (0, String::from("first")),
+                (1, String::from("sample bottle #3 taken")),
+            ])
+        );
+
+        path.remove_collection_point(0).unwrap();
+        assert_eq!(
+            path.notes(),
+            &BTreeMap::from([(0, String::from("sample bottle #3 taken"))])
+        );
+    }
+
+    #[test]
+    fn blank_collection_point_note_removes_it() {
+        let mut path = path_with_points(1);
+        path.set_collection_point_note(0, String::from("fouled"))
+            .unwrap();
+
+        path.set_collection_point_note(0, String::from("  "))
+            .unwrap();
+
+        assert!(path.notes().is_empty());
+        assert_eq!(
+            path.set_collection_point_note(1, String::from("missing")),
+            Err(PathError::CollectionPointOutOfRange { index: 1, len: 1 })
+        );
+    }
+
+    #[test]
+    fn notes_of_replaced_collection_points_are_removed() {
+        let mut path = path_with_points(2);
+        path.set_collection_point_note(1, String::from("second"))
+            .unwrap();
+
+        path.set_collection_points(MultiPoint::from(vec![(101.5, 3.1)]));
+
+        assert!(path.notes().is_empty());
+    }
+
+    #[test]
+    fn collection_point_notes_round_trip_through_geojson() {
+        let mut path = path_with_points(2);
+        path.set_collection_point_note(1, String::from("sensor fouled"))
+            .unwrap();
+
+        let geojson = GeoJson::from(&path);
+        let parsed = PathData::try_from(geojson.clone()).unwrap();
+
+        assert_eq!(parsed, path);
+        let collection = match geojson {
+            GeoJson::FeatureCollection(v) => v,
+            v => panic!("{v}"),
+        };
+        assert_eq!(
+            collection.features[0].properties,
+            Some(JsonObject::from_iter([(
+                String::from("notes"),
+                json!({"1": "sensor fouled"})
+            )]))
+        );
+        let plain = GeoJson::from(&path_with_points(2));
+        assert!(!plain.to_string().contains("notes"));
+    }
+
+    #[test]
+    fn unknown_members_and_properties_round_trip_through_geojson() {
+        let geojson = json!({
+            "type": "FeatureCollection",
+            "version": migrations::PATH_DATA_VERSION,
+            "generator": { "name": "survey-planner", "build": [2, 1] },
+            "features": [
+                {
+                    "type": "Feature",
+                    "properties": {
+                        "notes": { "0": "inflow" },
+                        "sampler": "bottle",
+                    },
+                    "geometry": { "type": "MultiPoint", "coordinates": [[101.5, 3.1]] },
+                },
+                {
+                    "type": "Feature",
+                    "properties": { "stroke": "#ff0000", "speed": { "knots": 2.5 } },
+                    "geometry": {
+                        "type": "LineString",
+                        "coordinates": [[101.5, 3.1], [101.6, 3.2]],
+                    },
+                },
+                {
+                    "type": "Feature",
+                    "properties": { "name": "permit area" },
+                    "geometry": {
+                        "type": "Polygon",
+                        "coordinates": [[[101.4, 3.0], [101.7, 3.0], [101.7, 3.3], [101.4, 3.0]]],
+                    },
+                },
+            ],
+        });
+
+        let path = PathData::from_str(&geojson.to_string()).unwrap();
+        assert_eq!(path.notes(), &BTreeMap::from([(0, String::from("inflow"))]));
+
+        let written: serde_json::Value = serde_json::from_str(&path.to_string()).unwrap();
+        assert_eq!(written, geojson);
+    }
 }
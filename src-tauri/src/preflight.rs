@@ -0,0 +1,442 @@
+//! Checks of the path before it is uploaded to the boat, so every problem can be shown at once
+//! instead of the upload failing on the first one.
+
+use geo_types::Coord;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    geometry::{segment_distance, segment_lengths, Projection},
+    path::{PathData, PathError},
+};
+
+/// The codes of the issues, used as translation keys by the frontend like the error codes.
+pub mod codes {
+    /// The path has less than two waypoints.
+    pub const TOO_FEW_WAYPOINTS: &str = "preflight.too_few_waypoints";
+    /// The path has more waypoints than the firmware can store.
+    pub const TOO_MANY_WAYPOINTS: &str = "preflight.too_many_waypoints";
+    /// A waypoint has a coordinate that isn't a finite number.
+    pub const INVALID_COORDINATE: &str = "preflight.invalid_coordinate";
+    /// A waypoint is the same as the one before it.
+    pub const DUPLICATE_WAYPOINT: &str = "preflight.duplicate_waypoint";
+    /// A segment is longer than the boat accepts.
+    pub const SEGMENT_TOO_LONG: &str = "preflight.segment_too_long";
+    /// A collection point is too far from the path for the boat to reach it.
+    pub const COLLECTION_POINT_OFF_PATH: &str = "preflight.collection_point_off_path";
+    /// A waypoint is outside of the boundary.
+    pub const WAYPOINT_OUTSIDE_BOUNDARY: &str = "preflight.waypoint_outside_boundary";
+    /// A collection point is outside of the boundary.
+    pub const COLLECTION_POINT_OUTSIDE_BOUNDARY: &str =
+        "preflight.collection_point_outside_boundary";
+}
+
+/// The default longest segment in meters, the farthest the boat navigates on a single radio
+/// message.
+const DEFAULT_MAX_SEGMENT_M: f64 = 500.0;
+
+/// The default number of waypoints the firmware can store.
+const DEFAULT_MAX_WAYPOINTS: usize = 256;
+
+/// The default farthest distance in meters a collection point can be from the path.
+const DEFAULT_COLLECTION_POINT_TOLERANCE_M: f64 = 5.0;
+
+/// The limits of the boat the path is checked against.
+///
+/// Missing limits use the defaults of the current firmware.
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq)]
+#[serde(default)]
+pub struct PathConstraints {
+    /// The longest segment in meters.
+    pub max_segment_m: f64,
+    /// The most waypoints in the path.
+    pub max_waypoints: usize,
+    /// The farthest distance in meters a collection point can be from the path.
+    pub collection_point_tolerance_m: f64,
+}
+
+impl Default for PathConstraints {
+    fn default() -> Self {
+        Self {
+            max_segment_m: DEFAULT_MAX_SEGMENT_M,
+            max_waypoints: DEFAULT_MAX_WAYPOINTS,
+            collection_point_tolerance_m: DEFAULT_COLLECTION_POINT_TOLERANCE_M,
+        }
+    }
+}
+
+/// How serious an issue is.
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Severity {
+    /// The boat can't follow the path, so it isn't uploaded.
+    Error,
+    /// The boat can follow the path, but it is likely a mistake.
+    Warning,
+}
+
+/// A problem with the path.
+#[derive(Debug, Serialize, Clone, PartialEq, Eq)]
+pub struct PathIssue {
+    /// The stable code of the issue, one of [`codes`].
+    code: &'static str,
+    /// How serious the issue is.
+    severity: Severity,
+    /// The issue in English.
+    message: String,
+    /// The index of the waypoint the issue is about.
+    waypoint: Option<usize>,
+    /// The index of the collection point the issue is about.
+    collection_point: Option<usize>,
+}
+
+impl PathIssue {
+    /// Creates an issue of the path as a whole.
+    fn new(code: &'static str, severity: Severity, message: String) -> Self {
+        Self {
+            code,
+            severity,
+            message,
+            waypoint: None,
+            collection_point: None,
+        }
+    }
+
+    /// Creates an issue of a waypoint.
+    fn waypoint(code: &'static str, severity: Severity, index: usize, message: String) -> Self {
+        Self {
+            waypoint: Some(index),
+            ..Self::new(code, severity, message)
+        }
+    }
+
+    /// Creates an issue of a collection point.
+    fn collection_point(
+        code: &'static str,
+        severity: Severity,
+        index: usize,
+        message: String,
+    ) -> Self {
+        Self {
+            collection_point: Some(index),
+            ..Self::new(code, severity, message)
+        }
+    }
+
+    /// Checks if the issue prevents the path from being uploaded.
+    pub fn is_error(&self) -> bool {
+        self.severity == Severity::Error
+    }
+}
+
+impl std::fmt::Display for PathIssue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+/// Checks the waypoints of the path.
+fn check_waypoints(path: &PathData, constraints: &PathConstraints) -> Vec<PathIssue> {
+    let coords = &path.path().0;
+    let mut issues = vec![];
+    if coords.len() < 2 {
+        issues.push(PathIssue::new(
+            codes::TOO_FEW_WAYPOINTS,
+            Severity::Error,
+            String::from("The path requires at least two waypoints."),
+        ));
+    }
+    if coords.len() > constraints.max_waypoints {
+        issues.push(PathIssue::new(
+            codes::TOO_MANY_WAYPOINTS,
+            Severity::Error,
+            format!(
+                "The path has {} waypoints, the boat can store at most {}.",
+                coords.len(),
+                constraints.max_waypoints
+            ),
+        ));
+    }
+
+    for (i, coord) in coords.iter().enumerate() {
+        if !coord.x.is_finite() || !coord.y.is_finite() {
+            issues.push(PathIssue::waypoint(
+                codes::INVALID_COORDINATE,
+                Severity::Error,
+                i,
+                format!("Waypoint {i} has an invalid coordinate."),
+            ));
+        } else if i > 0 && coords[i - 1] == *coord {
+            issues.push(PathIssue::waypoint(
+                codes::DUPLICATE_WAYPOINT,
+                Severity::Warning,
+                i,
+                format!("Waypoint {i} is the same as waypoint {}.", i - 1),
+            ));
+        }
+    }
+
+    for (i, length) in segment_lengths(path).into_iter().enumerate() {
+        if length > constraints.max_segment_m {
+            issues.push(PathIssue::waypoint(
+                codes::SEGMENT_TOO_LONG,
+                Severity::Error,
+                i + 1,
+                format!(
+                    "The segment to waypoint {} is {:.0} m long, the boat accepts at most {:.0} m.",
+                    i + 1,
+                    length,
+                    constraints.max_segment_m
+                ),
+            ));
+        }
+    }
+    issues
+}
+
+/// Checks the collection points are close enough to the path for the boat to reach them.
+fn check_collection_points(path: &PathData, constraints: &PathConstraints) -> Vec<PathIssue> {
+    let coords: Vec<Coord<f64>> = path
+        .path()
+        .0
+        .iter()
+        .copied()
+        .filter(|c| c.x.is_finite() && c.y.is_finite())
+        .collect();
+    if coords.is_empty() {
+        return vec![];
+    }
+    let projection = Projection::new(&coords);
+    let waypoints: Vec<[f64; 2]> = coords.iter().map(|c| projection.forward(*c)).collect();
+
+    let mut issues = vec![];
+    for (i, point) in path.collection_points().iter().enumerate() {
+        let point = projection.forward(point.0);
+        let distance = match waypoints.as_slice() {
+            [waypoint] => segment_distance(point, *waypoint, *waypoint),
+            _ => waypoints
+                .windows(2)
+                .map(|segment| segment_distance(point, segment[0], segment[1]))
+                .fold(f64::INFINITY, f64::min),
+        };
+        if distance.is_nan() || distance > constraints.collection_point_tolerance_m {
+            issues.push(PathIssue::collection_point(
+                codes::COLLECTION_POINT_OFF_PATH,
+                Severity::Error,
+                i,
+                format!(
+                    "Collection point {i} is {:.1} m from the path, at most {:.1} m is allowed.",
+                    distance, constraints.collection_point_tolerance_m
+                ),
+            ));
+        }
+    }
+    issues
+}
+
+/// Checks the waypoints and collection points are inside the boundary, if there is one.
+fn check_boundary(path: &PathData) -> Vec<PathIssue> {
+    let (waypoints, collection_points) = match path.check_boundary() {
+        Err(PathError::OutsideBoundary {
+            waypoints,
+            collection_points,
+        }) => (waypoints, collection_points),
+        _ => return vec![],
+    };
+    let waypoints = waypoints.into_iter().map(|i| {
+        PathIssue::waypoint(
+            codes::WAYPOINT_OUTSIDE_BOUNDARY,
+            Severity::Error,
+            i,
+            format!("Waypoint {i} is outside of the boundary."),
+        )
+    });
+    let collection_points = collection_points.into_iter().map(|i| {
+        PathIssue::collection_point(
+            codes::COLLECTION_POINT_OUTSIDE_BOUNDARY,
+            Severity::Error,
+            i,
+            format!("Collection point {i} is outside of the boundary."),
+        )
+    });
+    waypoints.chain(collection_points).collect()
+}
+
+/// Checks the path against the limits of the boat, returning every issue found.
+pub fn check_path(path: &PathData, constraints: &PathConstraints) -> Vec<PathIssue> {
+    let mut issues = check_waypoints(path, constraints);
+    issues.extend(check_collection_points(path, constraints));
+    issues.extend(check_boundary(path));
+    issues
+}
+
+/// Check the path before uploading it to the boat, returning every issue found.
+///
+/// Issues with the `error` severity prevent the path from being uploaded unless the upload is
+/// forced.
+#[tauri::command]
+pub fn validate_path(path: PathData, constraints: Option<PathConstraints>) -> Vec<PathIssue> {
+    log::debug!("Validating Path");
+    check_path(&path, &constraints.unwrap_or_default())
+}
+
+#[cfg(test)]
+mod tests {
+    use geo_types::{LineString, Point, Polygon};
+
+    use super::*;
+
+    /// Creates a path through `waypoints` with `collection_points`, given as `(lng, lat)`.
+    fn path(waypoints: &[(f64, f64)], collection_points: &[(f64, f64)]) -> PathData {
+        let mut path = PathData::default();
+        path.set_path(waypoints.iter().map(|(lng, lat)| (*lng, *lat)).collect());
+        for (lng, lat) in collection_points {
+            path.add_collection_point(Point::new(*lng, *lat));
+        }
+        path
+    }
+
+    /// Gets the codes and indices of the issues.
+    fn summary(issues: &[PathIssue]) -> Vec<(&str, Option<usize>, Option<usize>)> {
+        issues
+            .iter()
+            .map(|i| (i.code, i.waypoint, i.collection_point))
+            .collect()
+    }
+
+    #[test]
+    fn valid_path_has_no_issues() {
+        let path = path(
+            &[(101.5, 3.1), (101.5, 3.101), (101.501, 3.101)],
+            &[(101.5, 3.1005), (101.5005, 3.101)],
+        );
+
+        assert_eq!(check_path(&path, &PathConstraints::default()), vec![]);
+    }
+
+    #[test]
+    fn path_needs_two_waypoints() {
+        let issues = check_path(&path(&[(101.5, 3.1)], &[]), &PathConstraints::default());
+
+        assert_eq!(summary(&issues), [(codes::TOO_FEW_WAYPOINTS, None, None)]);
+        assert!(issues[0].is_error());
+    }
+
+    #[test]
+    fn waypoints_are_limited() {
+        let constraints = PathConstraints {
+            max_waypoints: 2,
+            ..PathConstraints::default()
+        };
+        let path = path(&[(101.5, 3.1), (101.5, 3.101), (101.5, 3.102)], &[]);
+
+        assert_eq!(
+            summary(&check_path(&path, &constraints)),
+            [(codes::TOO_MANY_WAYPOINTS, None, None)]
+        );
+    }
+
+    #[test]
+    fn duplicate_waypoints_are_warnings() {
+        let path = path(&[(101.5, 3.1), (101.5, 3.1), (101.5, 3.101)], &[]);
+
+        let issues = check_path(&path, &PathConstraints::default());
+
+        assert_eq!(
+            summary(&issues),
+            [(codes::DUPLICATE_WAYPOINT, Some(1), None)]
+        );
+        assert!(!issues[0].is_error());
+    }
+
+    #[test]
+    fn invalid_coordinates_are_named() {
+        let path = path(&[(101.5, 3.1), (f64::NAN, 3.1)], &[]);
+
+        let issues = check_path(&path, &PathConstraints::default());
+
+        assert_eq!(
+            summary(&issues)[0],
+            (codes::INVALID_COORDINATE, Some(1), None)
+        );
+    }
+
+    #[test]
+    fn long_segments_name_the_waypoint_they_end_at() {
+        // About 111 m and 333 m long
+        let path = path(&[(101.5, 3.1), (101.5, 3.101), (101.5, 3.104)], &[]);
+        let constraints = PathConstraints {
+            max_segment_m: 200.0,
+            ..PathConstraints::default()
+        };
+
+        let issues = check_path(&path, &constraints);
+
+        assert_eq!(summary(&issues), [(codes::SEGMENT_TOO_LONG, Some(2), None)]);
+        assert!(issues[0].message.contains("200 m"));
+    }
+
+    #[test]
+    fn collection_points_must_be_near_the_path() {
+        // The second point is about 11 m east of the path
+        let path = path(
+            &[(101.5, 3.1), (101.5, 3.101)],
+            &[(101.5, 3.1005), (101.5001, 3.1005)],
+        );
+
+        let issues = check_path(&path, &PathConstraints::default());
+        assert_eq!(
+            summary(&issues),
+            [(codes::COLLECTION_POINT_OFF_PATH, None, Some(1))]
+        );
+
+        let constraints = PathConstraints {
+            collection_point_tolerance_m: 20.0,
+            ..PathConstraints::default()
+        };
+        assert_eq!(check_path(&path, &constraints), vec![]);
+    }
+
+    #[test]
+    fn everything_must_be_inside_the_boundary() {
+        let mut path = path(
+            &[(101.5, 3.1), (101.5, 3.101), (101.6, 3.101)],
+            &[(101.5, 3.1005), (101.6, 3.101)],
+        );
+        path.set_boundary(Some(Polygon::new(
+            LineString::from(vec![
+                (101.49, 3.09),
+                (101.51, 3.09),
+                (101.51, 3.11),
+                (101.49, 3.11),
+                (101.49, 3.09),
+            ]),
+            vec![],
+        )));
+        let constraints = PathConstraints {
+            max_segment_m: f64::INFINITY,
+            ..PathConstraints::default()
+        };
+
+        assert_eq!(
+            summary(&check_path(&path, &constraints)),
+            [
+                (codes::WAYPOINT_OUTSIDE_BOUNDARY, Some(2), None),
+                (codes::COLLECTION_POINT_OUTSIDE_BOUNDARY, None, Some(1))
+            ]
+        );
+    }
+
+    #[test]
+    fn missing_constraints_use_the_defaults() {
+        let constraints: PathConstraints =
+            serde_json::from_value(serde_json::json!({ "max_waypoints": 10 })).unwrap();
+
+        assert_eq!(
+            constraints,
+            PathConstraints {
+                max_waypoints: 10,
+                ..PathConstraints::default()
+            }
+        );
+    }
+}
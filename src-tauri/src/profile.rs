@@ -0,0 +1,264 @@
+//! Cross sections of boat data along the path for plotting temperature against distance and
+//! depth.
+
+use chrono::{DateTime, Utc};
+use geo::GeodesicDistance;
+use geo_types::{Coord, Point};
+use serde::Serialize;
+
+use crate::{
+    data::{BoatData, Layer},
+    errors::{codes, AppError},
+    geometry::{segment_lengths, Projection},
+    path::PathData,
+};
+
+/// A reading projected onto the path.
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct ProfileSample {
+    /// The geodesic distance along the path to the projected reading in meters.
+    distance: f64,
+    /// The geodesic distance from the path to the reading in meters.
+    offset: f64,
+    /// The depth the temperature is measured at.
+    depth: f64,
+    /// The layer the temperature is measured at.
+    layer: Layer,
+    /// The temperature measured.
+    temperature: f64,
+    /// The time the temperature is measured at.
+    time: DateTime<Utc>,
+}
+
+/// Gets the point on the segment from `start` to `end` nearest to `point`.
+fn nearest_on_segment(point: [f64; 2], start: [f64; 2], end: [f64; 2]) -> [f64; 2] {
+    let (dx, dy) = (end[0] - start[0], end[1] - start[1]);
+    let length_squared = dx * dx + dy * dy;
+    let t = if length_squared == 0.0 {
+        0.0
+    } else {
+        (((point[0] - start[0]) * dx + (point[1] - start[1]) * dy) / length_squared).clamp(0.0, 1.0)
+    };
+    [start[0] + t * dx, start[1] + t * dy]
+}
+
+/// Extract the readings within a corridor along the path for a depth profile.
+///
+/// Each reading is projected onto the nearest segment of the path, so readings near sharp
+/// corners are matched to a single segment. Readings farther than half of `corridor_width_m`
+/// meters from the path are discarded. The samples are ordered by their distance along the
+/// path.
+#[tauri::command]
+pub fn path_profile(
+    data: BoatData,
+    path: PathData,
+    corridor_width_m: f64,
+) -> Result<Vec<ProfileSample>, AppError> {
+    log::debug!(
+        "Extracting Path Profile with Corridor {}m",
+        corridor_width_m
+    );
+    if !corridor_width_m.is_finite() || corridor_width_m < 0.0 {
+        return Err(AppError::new(
+            codes::PROFILE_INVALID_CORRIDOR,
+            format!("Invalid Corridor Width: {corridor_width_m}"),
+        )
+        .with("corridor_width_m", corridor_width_m));
+    }
+    let coords = &path.path().0;
+    if coords.len() < 2 {
+        return Err(AppError::new(
+            codes::PROFILE_TOO_FEW_WAYPOINTS,
+            "Invalid Path: Path requires at least two waypoints.",
+        ));
+    }
+
+    // The distance along the path to the start of each segment
+    let starts: Vec<f64> = segment_lengths(&path)
+        .into_iter()
+        .scan(0.0, |distance, length| {
+            let start = *distance;
+            *distance += length;
+            Some(start)
+        })
+        .collect();
+    let projection = Projection::new(coords);
+    let points: Vec<_> = coords.iter().map(|c| projection.forward(*c)).collect();
+    let half_width = corridor_width_m / 2.0;
+
+    let mut samples = vec![];
+    for feature in data.features() {
+        let reading = feature.geometry();
+        if !reading.x().is_finite() || !reading.y().is_finite() {
+            continue;
+        }
+        let projected = projection.forward(Coord::from(reading));
+
+        let nearest = points
+            .windows(2)
+            .enumerate()
+            .map(|(segment, w)| {
+                let foot =
+                    Point::from(projection.inverse(nearest_on_segment(projected, w[0], w[1])));
+                (segment, foot, reading.geodesic_distance(&foot))
+            })
+            .filter(|(_, _, offset)| !offset.is_nan())
+            // The offsets are comparable as NaN offsets are skipped
+            .min_by(|(_, _, a), (_, _, b)| a.partial_cmp(b).unwrap());
+        let (segment, foot, offset) = match nearest {
+            Some(v) => v,
+            None => continue,
+        };
+        if offset > half_width {
+            continue;
+        }
+
+        samples.push(ProfileSample {
+            distance: starts[segment] + Point::from(coords[segment]).geodesic_distance(&foot),
+            offset,
+            depth: feature.depth(),
+            layer: feature.layer(),
+            temperature: feature.temperature(),
+            time: feature.time(),
+        });
+    }
+    // The distances are comparable as the waypoints parsed from JSON are never NaN
+    samples.sort_by(|a, b| a.distance.partial_cmp(&b.distance).unwrap());
+    log::info!("Extracted {} Profile Samples", samples.len());
+    Ok(samples)
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+    use geo_types::{LineString, MultiPoint};
+
+    use crate::data::BoatDataFeature;
+
+    use super::*;
+
+    /// The length of a degree near the equator in meters, within 1% for the tests.
+    const DEGREE: f64 = 111_195.0;
+
+    fn reading(temperature: f64, seconds: i64, lng: f64, lat: f64) -> BoatDataFeature {
+        BoatDataFeature::new(
+            temperature,
+            0.5,
+            Layer::Surface,
+            Utc.timestamp_opt(1_715_495_400 + seconds, 0).unwrap(),
+            Point::new(lng, lat),
+        )
+    }
+
+    fn data(features: Vec<BoatDataFeature>) -> BoatData {
+        BoatData::new(BoatData::default().version().to_string(), features)
+    }
+
+    fn path(waypoints: Vec<(f64, f64)>) -> PathData {
+        PathData::new(LineString::from(waypoints), MultiPoint(vec![]))
+    }
+
+    #[test]
+    fn readings_are_ordered_along_the_path() {
+        // A path 1 km east along the equator
+        let path = path(vec![(0.0, 0.0), (1000.0 / DEGREE, 0.0)]);
+        let data = data(vec![
+            reading(21.0, 0, 750.0 / DEGREE, 10.0 / DEGREE),
+            reading(22.0, 1, 250.0 / DEGREE, -5.0 / DEGREE),
+            reading(23.0, 2, 500.0 / DEGREE, 0.0),
+        ]);
+
+        let samples = path_profile(data, path, 40.0).unwrap();
+        let temperatures: Vec<_> = samples.iter().map(|s| s.temperature).collect();
+        assert_eq!(temperatures, [22.0, 23.0, 21.0]);
+        for (sample, (distance, offset)) in
+            samples
+                .iter()
+                .zip([(250.0, 5.0), (500.0, 0.0), (750.0, 10.0)])
+        {
+            assert!(
+                (sample.distance - distance).abs() < 0.01 * distance,
+                "{sample:?}"
+            );
+            assert!((sample.offset - offset).abs() < 0.1, "{sample:?}");
+        }
+        assert_eq!(
+            samples[0].time,
+            Utc.timestamp_opt(1_715_495_401, 0).unwrap()
+        );
+    }
+
+    #[test]
+    fn readings_outside_the_corridor_are_discarded() {
+        let path = path(vec![(0.0, 0.0), (1000.0 / DEGREE, 0.0)]);
+        let data = data(vec![
+            reading(21.0, 0, 500.0 / DEGREE, 19.0 / DEGREE),
+            reading(22.0, 1, 500.0 / DEGREE, -21.0 / DEGREE),
+            // Beyond the end of the path
+            reading(23.0, 2, 1030.0 / DEGREE, 0.0),
+            reading(24.0, 3, f64::NAN, 0.0),
+        ]);
+
+        let samples = path_profile(data, path, 40.0).unwrap();
+        assert_eq!(samples.len(), 1);
+        assert_eq!(samples[0].temperature, 21.0);
+    }
+
+    #[test]
+    fn reading_at_a_sharp_corner_is_projected_onto_the_nearest_segment() {
+        // Out 1 km east and back along a parallel line 20 m north
+        let path = path(vec![
+            (0.0, 0.0),
+            (1000.0 / DEGREE, 0.0),
+            (1000.0 / DEGREE, 20.0 / DEGREE),
+            (0.0, 20.0 / DEGREE),
+        ]);
+        let data = data(vec![
+            reading(21.0, 0, 900.0 / DEGREE, 1.0 / DEGREE),
+            reading(22.0, 1, 900.0 / DEGREE, 19.0 / DEGREE),
+            reading(23.0, 2, 995.0 / DEGREE, 1.0 / DEGREE),
+        ]);
+
+        let samples = path_profile(data, path, 10.0).unwrap();
+        let temperatures: Vec<_> = samples.iter().map(|s| s.temperature).collect();
+        // The first reading is on the way out, the second on the way back
+        assert_eq!(temperatures, [21.0, 23.0, 22.0]);
+        for (sample, distance) in samples.iter().zip([900.0, 995.0, 1120.0]) {
+            assert!(
+                (sample.distance - distance).abs() < 0.01 * distance,
+                "{sample:?}"
+            );
+            assert!((sample.offset - 1.0).abs() < 0.1, "{sample:?}");
+        }
+    }
+
+    #[test]
+    fn invalid_profile_is_rejected() {
+        let valid = path(vec![(0.0, 0.0), (0.01, 0.0)]);
+
+        assert!(path_profile(data(vec![]), valid.clone(), -1.0).is_err());
+        assert!(path_profile(data(vec![]), valid.clone(), f64::NAN).is_err());
+        assert!(path_profile(data(vec![]), path(vec![(0.0, 0.0)]), 10.0).is_err());
+        assert_eq!(path_profile(data(vec![]), valid, 10.0).unwrap(), []);
+    }
+
+    #[test]
+    fn nearest_point_is_clamped_to_the_segment() {
+        assert_eq!(
+            nearest_on_segment([5.0, 3.0], [0.0, 0.0], [10.0, 0.0]),
+            [5.0, 0.0]
+        );
+        assert_eq!(
+            nearest_on_segment([-5.0, 3.0], [0.0, 0.0], [10.0, 0.0]),
+            [0.0, 0.0]
+        );
+        assert_eq!(
+            nearest_on_segment([15.0, 3.0], [0.0, 0.0], [10.0, 0.0]),
+            [10.0, 0.0]
+        );
+        assert_eq!(
+            nearest_on_segment([5.0, 3.0], [1.0, 1.0], [1.0, 1.0]),
+            [1.0, 1.0]
+        );
+    }
+}
@@ -0,0 +1,242 @@
+//! Progress of the boat through its mission while collecting data.
+
+use std::{collections::BTreeSet, sync::Mutex};
+
+use chrono::{DateTime, Utc};
+use prost::Message;
+use serde::Serialize;
+use tauri::{AppHandle, Manager};
+
+use crate::{
+    auto_export,
+    messages::Progress,
+    path::{CurrentPath, PathData},
+    telemetry::device_time,
+};
+
+/// The distance left along the path in meters below which the mission is complete.
+const COMPLETE_DISTANCE: f64 = 5.0;
+
+/// A collection point of the current path.
+#[derive(Debug, Serialize, Clone, Copy, PartialEq)]
+pub struct ProgressPoint {
+    /// The index of the collection point in the path.
+    index: u32,
+    /// The longitude of the collection point.
+    lng: f64,
+    /// The latitude of the collection point.
+    lat: f64,
+}
+
+/// The progress of the boat through the current path.
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct MissionProgress {
+    /// The index of the waypoint the boat is heading to.
+    waypoint_index: u32,
+    /// The distance left along the path in metres.
+    distance_remaining: f64,
+    /// The collection points the data is collected at.
+    completed: Vec<ProgressPoint>,
+    /// The collection points the data is not collected at yet.
+    pending: Vec<ProgressPoint>,
+    /// Whether the boat reported indices beyond the current path, e.g. when it is running an
+    /// older mission.
+    mismatch: bool,
+    /// The time of the report.
+    time: DateTime<Utc>,
+}
+
+impl MissionProgress {
+    /// Matches the indices in a progress report to the points of `path`.
+    fn new(report: Progress, path: &PathData) -> Result<Self, String> {
+        let points = &path.collection_points().0;
+        let completed_indices: BTreeSet<u32> = report.completed_points.into_iter().collect();
+        let mismatch = report.waypoint_index as usize >= path.path().0.len()
+            || completed_indices
+                .iter()
+                .any(|&index| index as usize >= points.len());
+
+        let mut completed = vec![];
+        let mut pending = vec![];
+        for (index, point) in (0..).zip(points) {
+            let point = ProgressPoint {
+                index,
+                lng: point.x(),
+                lat: point.y(),
+            };
+            if completed_indices.contains(&index) {
+                completed.push(point);
+            } else {
+                pending.push(point);
+            }
+        }
+
+        Ok(Self {
+            waypoint_index: report.waypoint_index,
+            distance_remaining: report.distance_remaining,
+            completed,
+            pending,
+            mismatch,
+            time: device_time(report.time_ms)?,
+        })
+    }
+
+    /// Checks if the boat collected the data at every collection point and reached the end of
+    /// the current path.
+    fn is_complete(&self) -> bool {
+        !self.mismatch && self.pending.is_empty() && self.distance_remaining <= COMPLETE_DISTANCE
+    }
+}
+
+/// The latest progress of the boat through its mission.
+#[derive(Debug, Default)]
+pub struct ProgressState {
+    progress: Mutex<Option<MissionProgress>>,
+}
+
+/// Handles a progress message received from the boat.
+///
+/// The progress is matched to the current path and emitted on `mission-progress`.
+pub fn handle_progress(app_handle: &AppHandle, data: &[u8]) {
+    let report = match Progress::decode(data) {
+        Ok(v) => v,
+        Err(e) => {
+            log::warn!("Received an Invalid Progress Report: {}", e);
+            return;
+        }
+    };
+    let progress = {
+        let current = app_handle.state::<CurrentPath>();
+        let path = current.path.lock().unwrap();
+        match MissionProgress::new(report, &path) {
+            Ok(v) => v,
+            Err(e) => {
+                log::warn!("Received an Invalid Progress Report: {}", e);
+                return;
+            }
+        }
+    };
+    if progress.mismatch {
+        log::warn!("Boat Reported Progress beyond the Current Path");
+    }
+
+    let previous = app_handle
+        .state::<ProgressState>()
+        .progress
+        .lock()
+        .unwrap()
+        .replace(progress.clone());
+    // The boat keeps reporting after the mission, which is only exported the first time
+    if progress.is_complete() && !previous.map_or(false, |p| p.is_complete()) {
+        log::info!("Mission Complete");
+        auto_export::export_in_background(app_handle);
+    }
+    if let Err(e) = app_handle.emit_all("mission-progress", progress) {
+        log::warn!("Unable to emit mission-progress event: {}", e);
+    }
+}
+
+/// Get the latest progress of the boat through its mission.
+#[tauri::command]
+pub fn mission_progress(state: tauri::State<ProgressState>) -> Option<MissionProgress> {
+    state.progress.lock().unwrap().clone()
+}
+
+#[cfg(test)]
+mod tests {
+    use geo_types::{LineString, MultiPoint};
+
+    use super::*;
+
+    fn path() -> PathData {
+        PathData::new(
+            LineString::from(vec![(101.0, 3.0), (101.1, 3.0), (101.2, 3.1)]),
+            MultiPoint::from(vec![(101.0, 3.0), (101.1, 3.0), (101.2, 3.1)]),
+        )
+    }
+
+    fn report(waypoint_index: u32, completed_points: Vec<u32>) -> Progress {
+        Progress {
+            waypoint_index,
+            completed_points,
+            distance_remaining: 120.5,
+            time_ms: 1_700_000_000_000,
+        }
+    }
+
+    #[test]
+    fn completed_points_are_matched_to_the_path() {
+        let progress = MissionProgress::new(report(1, vec![0, 2]), &path()).unwrap();
+
+        assert!(!progress.mismatch);
+        assert_eq!(progress.waypoint_index, 1);
+        assert_eq!(progress.distance_remaining, 120.5);
+        assert_eq!(
+            progress.completed,
+            [
+                ProgressPoint {
+                    index: 0,
+                    lng: 101.0,
+                    lat: 3.0,
+                },
+                ProgressPoint {
+                    index: 2,
+                    lng: 101.2,
+                    lat: 3.1,
+                },
+            ]
+        );
+        assert_eq!(
+            progress.pending,
+            [ProgressPoint {
+                index: 1,
+                lng: 101.1,
+                lat: 3.0,
+            }]
+        );
+    }
+
+    #[test]
+    fn completed_index_beyond_the_path_is_a_mismatch() {
+        let progress = MissionProgress::new(report(1, vec![0, 7]), &path()).unwrap();
+
+        assert!(progress.mismatch);
+        assert_eq!(progress.completed.len(), 1);
+        assert_eq!(progress.pending.len(), 2);
+    }
+
+    #[test]
+    fn waypoint_index_beyond_the_path_is_a_mismatch() {
+        let progress = MissionProgress::new(report(3, vec![]), &path()).unwrap();
+
+        assert!(progress.mismatch);
+        assert!(progress.completed.is_empty());
+        assert_eq!(progress.pending.len(), 3);
+    }
+
+    #[test]
+    fn invalid_time_is_rejected() {
+        let mut report = report(0, vec![]);
+        report.time_ms = i64::MAX;
+
+        assert!(MissionProgress::new(report, &path()).is_err());
+    }
+
+    #[test]
+    fn missions_are_complete_at_the_end_of_the_path() {
+        let mut report = report(2, vec![0, 1, 2]);
+        report.distance_remaining = 3.0;
+
+        let progress = MissionProgress::new(report.clone(), &path()).unwrap();
+        assert!(progress.is_complete());
+
+        report.distance_remaining = 120.5;
+        let progress = MissionProgress::new(report.clone(), &path()).unwrap();
+        assert!(!progress.is_complete());
+
+        report.distance_remaining = 3.0;
+        report.completed_points = vec![0, 2];
+        let progress = MissionProgress::new(report, &path()).unwrap();
+        assert!(!progress.is_complete());
+    }
+}
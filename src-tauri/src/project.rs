@@ -0,0 +1,751 @@
+//! Project archives bundling everything needed to continue a survey on another computer.
+//!
+//! A project archive is a zip file with the layout:
+//!
+//! ```text
+//! manifest.json              The `Manifest`, the versions and names of the contents.
+//! settings.json              The settings.
+//! datasets/<name>.geojson    The active dataset, including the data points in its journal or
+//!                            database.
+//! path.geojson               The default path, left out if it was never saved.
+//! missions/<name>.geojson    The current mission, left out if the default path is used.
+//! ```
+//!
+//! The names in the paths are the names in the manifest. The GeoJSON files are in the same
+//! format as in application storage, so they can also be opened on their own.
+
+use std::{
+    fs::{self, File},
+    io::{self, Cursor, ErrorKind, Read, Seek, Write},
+    path::{Path, PathBuf},
+};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tauri::{AppHandle, Manager};
+use zip::{
+    result::{ZipError, ZipResult},
+    write::FileOptions,
+    CompressionMethod, ZipArchive, ZipWriter,
+};
+
+use crate::{
+    autosave, backup,
+    data::{self, BoatData},
+    datasets,
+    errors::{codes, export_error, AppError},
+    journal,
+    migrations::{BOAT_DATA_VERSION, PATH_DATA_VERSION},
+    missions, operations,
+    path::{self, CurrentPath, PathData},
+    settings::{self, Settings, SettingsState},
+    storage::{app_data_file, check_name, sqlite, write_atomically},
+    watcher,
+};
+
+/// The version of the archive layout, increased when the layout changes.
+const PROJECT_FORMAT: u32 = 1;
+
+/// The name of the manifest in the archive.
+const MANIFEST_ENTRY: &str = "manifest.json";
+
+/// The name of the settings in the archive.
+const SETTINGS_ENTRY: &str = "settings.json";
+
+/// The name of the default path in the archive and in application storage.
+const PATH_ENTRY: &str = "path.geojson";
+
+/// The description of the contents of a project archive.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Manifest {
+    /// The version of the archive layout.
+    format: u32,
+    /// The version of the app that exported the archive.
+    app_version: String,
+    /// The version of the boat data format of the dataset.
+    boat_data_version: String,
+    /// The version of the path data format of the paths.
+    path_data_version: String,
+    /// The time the archive is exported.
+    created: DateTime<Utc>,
+    /// The name of the dataset.
+    dataset: String,
+    /// The name of the current mission, `None` if the default path is used.
+    mission: Option<String>,
+}
+
+impl Manifest {
+    /// Gets the name of the dataset in the archive.
+    fn dataset_entry(&self) -> String {
+        format!("datasets/{}.geojson", self.dataset)
+    }
+
+    /// Gets the name of the mission in the archive.
+    fn mission_entry(&self) -> Option<String> {
+        self.mission
+            .as_ref()
+            .map(|name| format!("missions/{name}.geojson"))
+    }
+}
+
+/// The result of importing a project archive.
+#[derive(Debug, Serialize)]
+pub struct ProjectImport {
+    /// Whether the contents are installed, `false` if there are conflicts and the import isn't
+    /// forced.
+    installed: bool,
+    /// The entries of the archive that would overwrite different data in application storage.
+    conflicts: Vec<String>,
+    /// The differences between the versions of the archive and of the app.
+    warnings: Vec<String>,
+    /// The manifest of the archive.
+    manifest: Manifest,
+}
+
+/// The contents of a project archive after validating them.
+struct ProjectContents {
+    manifest: Manifest,
+    settings: Settings,
+    data: BoatData,
+    path: Option<PathData>,
+    mission: Option<PathData>,
+}
+
+/// Creates the error of the archive not being a valid zip file.
+fn invalid_archive(reason: impl std::fmt::Display) -> AppError {
+    AppError::reason(
+        codes::PROJECT_INVALID_ARCHIVE,
+        "Invalid Project Archive",
+        reason,
+    )
+}
+
+/// Creates the error of the entry `name` of the archive being invalid.
+fn invalid_entry(name: &str, reason: serde_json::Error) -> AppError {
+    AppError::reason(
+        codes::PROJECT_INVALID_ENTRY,
+        &format!("Invalid Project Entry {name}"),
+        reason,
+    )
+    .with("entry", name)
+}
+
+/// Reads an entry of the archive, `None` if the archive doesn't have it.
+fn read_entry<R: Read + Seek>(
+    archive: &mut ZipArchive<R>,
+    name: &str,
+) -> Result<Option<String>, AppError> {
+    let mut entry = match archive.by_name(name) {
+        Ok(v) => v,
+        Err(ZipError::FileNotFound) => return Ok(None),
+        Err(e) => return Err(invalid_archive(e)),
+    };
+    let mut content = String::new();
+    entry
+        .read_to_string(&mut content)
+        .map_err(invalid_archive)?;
+    Ok(Some(content))
+}
+
+/// Reads an entry the archive must have.
+fn required_entry<R: Read + Seek>(
+    archive: &mut ZipArchive<R>,
+    name: &str,
+) -> Result<String, AppError> {
+    read_entry(archive, name)?.ok_or_else(|| {
+        AppError::new(
+            codes::PROJECT_MISSING_ENTRY,
+            format!("Missing Project Entry: {name}"),
+        )
+        .with("entry", name)
+    })
+}
+
+/// Reads and validates the contents of a project archive.
+fn read_contents<R: Read + Seek>(archive: &mut ZipArchive<R>) -> Result<ProjectContents, AppError> {
+    let manifest: Manifest = serde_json::from_str(&required_entry(archive, MANIFEST_ENTRY)?)
+        .map_err(|e| invalid_entry(MANIFEST_ENTRY, e))?;
+    if manifest.format > PROJECT_FORMAT {
+        return Err(AppError::new(
+            codes::PROJECT_UNSUPPORTED_FORMAT,
+            format!(
+                "Unsupported Project Format: {}, supported up to {}",
+                manifest.format, PROJECT_FORMAT
+            ),
+        )
+        .with("format", manifest.format)
+        .with("supported", PROJECT_FORMAT));
+    }
+    check_name(&manifest.dataset)?;
+    if let Some(mission) = &manifest.mission {
+        check_name(mission)?;
+    }
+
+    let settings = serde_json::from_str(&required_entry(archive, SETTINGS_ENTRY)?)
+        .map_err(|e| invalid_entry(SETTINGS_ENTRY, e))?;
+    let data: BoatData = required_entry(archive, &manifest.dataset_entry())?.parse()?;
+    let path = read_entry(archive, PATH_ENTRY)?
+        .map(|v| v.parse::<PathData>())
+        .transpose()?;
+    let mission = match manifest.mission_entry() {
+        Some(entry) => Some(required_entry(archive, &entry)?.parse::<PathData>()?),
+        None => None,
+    };
+    Ok(ProjectContents {
+        manifest,
+        settings,
+        data,
+        path,
+        mission,
+    })
+}
+
+/// Lists the differences between the versions of the archive and of the app.
+fn version_warnings(app_handle: &AppHandle, manifest: &Manifest) -> Vec<String> {
+    let app_version = app_handle.package_info().version.to_string();
+    let versions = [
+        ("App", &manifest.app_version, app_version.as_str()),
+        ("Boat Data", &manifest.boat_data_version, BOAT_DATA_VERSION),
+        ("Path Data", &manifest.path_data_version, PATH_DATA_VERSION),
+    ];
+    versions
+        .iter()
+        .filter(|(_, archive, current)| archive.as_str() != *current)
+        .map(|(name, archive, current)| {
+            format!("{name} Version Mismatch: the project has {archive}, this app has {current}")
+        })
+        .collect()
+}
+
+/// Checks if the file at `file` exists with content other than `content`.
+fn differs(file: &Path, content: &str) -> Result<bool, AppError> {
+    match fs::read_to_string(file) {
+        Ok(v) => Ok(v != content),
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(false),
+        Err(e) => Err(AppError::io(e, file)),
+    }
+}
+
+/// The data in application storage that importing an archive would overwrite.
+struct Installed {
+    /// The current settings.
+    settings: Settings,
+    /// The stored data of the dataset of the archive, `None` if there is no such dataset.
+    data: Option<BoatData>,
+    /// The file of the default path.
+    path_file: PathBuf,
+    /// The file of the mission of the archive, `None` if the archive has no mission.
+    mission_file: Option<PathBuf>,
+}
+
+impl Installed {
+    /// Reads the data in application storage that an archive with `manifest` would overwrite.
+    fn read(app_handle: &AppHandle, manifest: &Manifest) -> Result<Self, AppError> {
+        let data_file = datasets::dataset_file(app_handle, &manifest.dataset)?;
+        let data = if !data_file.exists() {
+            None
+        } else if sqlite::enabled(app_handle) {
+            Some(sqlite::read_stored(&data_file)?)
+        } else {
+            Some(journal::read_with_journal(&data_file)?)
+        };
+        let mission_file = match &manifest.mission {
+            Some(name) => Some(missions::mission_file(app_handle, name)?),
+            None => None,
+        };
+        Ok(Self {
+            settings: app_handle.state::<SettingsState>().get(),
+            data,
+            path_file: app_data_file(app_handle, PATH_ENTRY)?,
+            mission_file,
+        })
+    }
+
+    /// Lists the entries of the archive that would overwrite different data.
+    fn conflicts(&self, contents: &ProjectContents) -> Result<Vec<String>, AppError> {
+        let manifest = &contents.manifest;
+        let mut conflicts = vec![];
+        if self.settings != contents.settings {
+            conflicts.push(SETTINGS_ENTRY.to_string());
+        }
+        if matches!(&self.data, Some(data) if *data != contents.data) {
+            conflicts.push(manifest.dataset_entry());
+        }
+        if let Some(path) = &contents.path {
+            if differs(&self.path_file, &path.to_string())? {
+                conflicts.push(PATH_ENTRY.to_string());
+            }
+        }
+        if let (Some(file), Some(mission)) = (&self.mission_file, &contents.mission) {
+            if differs(file, &mission.to_string())? {
+                conflicts.extend(manifest.mission_entry());
+            }
+        }
+        Ok(conflicts)
+    }
+}
+
+/// Writes a path to application storage, creating its directory.
+fn install_path(app_handle: &AppHandle, file: PathBuf, path: PathData) -> Result<(), AppError> {
+    if let Some(dir) = file.parent() {
+        fs::create_dir_all(dir).map_err(|e| AppError::io(e, dir))?;
+    }
+    path::export_path(file.clone(), path)?;
+    watcher::note_write(app_handle, &file);
+    Ok(())
+}
+
+/// Installs the contents of the archive into application storage, making its dataset and
+/// mission the current ones.
+fn install(app_handle: &AppHandle, contents: ProjectContents) -> Result<(), AppError> {
+    let ProjectContents {
+        manifest,
+        mut settings,
+        data,
+        path,
+        mission,
+    } = contents;
+    // The edits of the active dataset are written before it can be replaced
+    autosave::flush(app_handle)?;
+    // The data directory is of this computer, and is only changed by moving the data
+    settings.data_directory = app_handle.state::<SettingsState>().get().data_directory;
+    let settings = settings::update(
+        app_handle,
+        serde_json::to_value(settings).map_err(|e| AppError::from(e.to_string()))?,
+    )?;
+
+    let data_file = datasets::dataset_file(app_handle, &manifest.dataset)?;
+    let datasets_dir = datasets::datasets_dir(app_handle)?;
+    fs::create_dir_all(&datasets_dir).map_err(|e| AppError::io(e, &datasets_dir))?;
+    backup::backup(
+        &data_file,
+        &backup::backup_dir(app_handle)?,
+        settings.max_backups,
+    )?;
+    data::write_data(&data_file, &data)?;
+    watcher::note_write(app_handle, &data_file);
+    journal::clear_journal(&data_file)?;
+    if settings.sqlite_storage {
+        sqlite::replace(&sqlite::database_file(&data_file), &data)?;
+    }
+
+    if let Some(path) = path {
+        install_path(app_handle, app_data_file(app_handle, PATH_ENTRY)?, path)?;
+    }
+    if let (Some(name), Some(mission)) = (&manifest.mission, mission) {
+        install_path(
+            app_handle,
+            missions::mission_file(app_handle, name)?,
+            mission,
+        )?;
+    }
+
+    datasets::set_active_dataset(app_handle.clone(), manifest.dataset)?;
+    missions::set_current_mission(app_handle.clone(), manifest.mission)?;
+    Ok(())
+}
+
+/// Adds an entry to the archive being written.
+pub fn write_entry<W: Write + Seek>(
+    archive: &mut ZipWriter<W>,
+    name: &str,
+    content: &str,
+) -> ZipResult<()> {
+    let options = FileOptions::default().compression_method(CompressionMethod::Deflated);
+    archive.start_file(name, options)?;
+    archive.write_all(content.as_bytes())?;
+    Ok(())
+}
+
+/// Writes a project archive with the default path and the mission as they are stored.
+fn write_archive(
+    manifest: &Manifest,
+    settings: &Settings,
+    data: &BoatData,
+    path: Option<&str>,
+    mission: Option<&str>,
+) -> ZipResult<Vec<u8>> {
+    let mut archive = ZipWriter::new(Cursor::new(vec![]));
+    let json = serde_json::to_string_pretty(manifest).map_err(io::Error::from)?;
+    write_entry(&mut archive, MANIFEST_ENTRY, &json)?;
+    let json = serde_json::to_string_pretty(settings).map_err(io::Error::from)?;
+    write_entry(&mut archive, SETTINGS_ENTRY, &json)?;
+    write_entry(&mut archive, &manifest.dataset_entry(), &data.to_string())?;
+    if let Some(path) = path {
+        write_entry(&mut archive, PATH_ENTRY, path)?;
+    }
+    if let (Some(entry), Some(mission)) = (manifest.mission_entry(), mission) {
+        write_entry(&mut archive, &entry, mission)?;
+    }
+    Ok(archive.finish()?.into_inner())
+}
+
+/// Export the active dataset, the default path, the current mission and the settings to a
+/// project archive.
+///
+/// The layout of the archive is described in the [module documentation](self).
+#[tauri::command]
+pub fn export_project(app_handle: AppHandle, path: PathBuf) -> Result<(), AppError> {
+    log::info!("Exporting Project to: {}", path.display());
+    autosave::flush(&app_handle)?;
+    let dataset = datasets::active_dataset(&app_handle);
+    let data_file = datasets::dataset_file(&app_handle, &dataset)?;
+    let data = if sqlite::enabled(&app_handle) {
+        sqlite::read_stored(&data_file)?
+    } else {
+        journal::read_with_journal(&data_file)?
+    };
+    let mission = app_handle
+        .state::<CurrentPath>()
+        .mission
+        .lock()
+        .unwrap()
+        .clone();
+
+    let manifest = Manifest {
+        format: PROJECT_FORMAT,
+        app_version: app_handle.package_info().version.to_string(),
+        boat_data_version: BOAT_DATA_VERSION.to_string(),
+        path_data_version: PATH_DATA_VERSION.to_string(),
+        created: Utc::now(),
+        dataset,
+        mission,
+    };
+    let settings = app_handle.state::<SettingsState>().get();
+
+    let path_file = app_data_file(&app_handle, PATH_ENTRY)?;
+    let default_path = match fs::read_to_string(&path_file) {
+        Ok(v) => Some(v),
+        Err(e) if e.kind() == ErrorKind::NotFound => {
+            log::debug!("Skipping Unsaved Path: {}", path_file.display());
+            None
+        }
+        Err(e) => return Err(AppError::io(e, &path_file)),
+    };
+    let mission = match &manifest.mission {
+        Some(name) => {
+            let mission_file = missions::mission_file(&app_handle, name)?;
+            let content =
+                fs::read_to_string(&mission_file).map_err(|e| AppError::io(e, &mission_file))?;
+            Some(content)
+        }
+        None => None,
+    };
+
+    let content = write_archive(
+        &manifest,
+        &settings,
+        &data,
+        default_path.as_deref(),
+        mission.as_deref(),
+    )
+    .map_err(|e| export_error(&path, e))?;
+    write_atomically(&path, |file| file.write_all(&content))?;
+    log::info!(
+        "Exported Project with {} Data Points",
+        data.features().len()
+    );
+    Ok(())
+}
+
+/// Import a project archive into application storage, making its dataset and mission the
+/// current ones.
+///
+/// Nothing is installed if the archive would overwrite different data and `force` isn't
+/// `true`, the entries that would be overwritten are returned in `conflicts` instead so the
+/// user can be asked before importing again with `force`. Differences between the versions of
+/// the archive and of the app are returned in `warnings`, older data is migrated when it is
+/// read.
+#[tauri::command]
+pub fn import_project(
+    app_handle: AppHandle,
+    path: PathBuf,
+    force: Option<bool>,
+) -> Result<ProjectImport, AppError> {
+    operations::record(
+        &app_handle,
+        "import_project",
+        json!({ "path": path, "force": force }),
+        || {
+            log::info!("Importing Project from: {}", path.display());
+            let file = File::open(&path).map_err(|e| AppError::io(e, &path))?;
+            let mut archive = ZipArchive::new(file).map_err(invalid_archive)?;
+            let contents = read_contents(&mut archive)?;
+
+            let warnings = version_warnings(&app_handle, &contents.manifest);
+            for warning in &warnings {
+                log::warn!("{}", warning);
+            }
+            let conflicts =
+                Installed::read(&app_handle, &contents.manifest)?.conflicts(&contents)?;
+            let manifest = contents.manifest.clone();
+            if !conflicts.is_empty() && !force.unwrap_or(false) {
+                log::info!("Not Importing Project with {} Conflicts", conflicts.len());
+                return Ok(ProjectImport {
+                    installed: false,
+                    conflicts,
+                    warnings,
+                    manifest,
+                });
+            }
+
+            install(&app_handle, contents)?;
+            log::info!("Imported Project: {}", manifest.dataset);
+            Ok(ProjectImport {
+                installed: true,
+                conflicts,
+                warnings,
+                manifest,
+            })
+        },
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+    use geo_types::{LineString, MultiPoint, Point};
+
+    use crate::{data::BoatDataFeature, storage::tests::temp_dir};
+
+    use super::*;
+
+    fn manifest(mission: Option<&str>) -> Manifest {
+        Manifest {
+            format: PROJECT_FORMAT,
+            app_version: String::from("0.1.0"),
+            boat_data_version: BOAT_DATA_VERSION.to_string(),
+            path_data_version: PATH_DATA_VERSION.to_string(),
+            created: Utc.timestamp_opt(1_715_495_400, 0).unwrap(),
+            dataset: String::from("pond"),
+            mission: mission.map(String::from),
+        }
+    }
+
+    fn data() -> BoatData {
+        let feature = BoatDataFeature::new(
+            28.5,
+            0.5,
+            data::Layer::Surface,
+            Utc.timestamp_opt(1_715_495_400, 0).unwrap(),
+            Point::new(101.5, 3.1),
+        );
+        let mut data = BoatData::new(BoatData::default().version().to_string(), vec![feature]);
+        data.assign_ids();
+        data
+    }
+
+    fn path(lng: f64) -> PathData {
+        PathData::new(
+            LineString::from(vec![(101.5, 3.1), (lng, 3.2)]),
+            MultiPoint::from(vec![Point::new(lng, 3.2)]),
+        )
+    }
+
+    fn read(archive: Vec<u8>) -> Result<ProjectContents, AppError> {
+        read_contents(&mut ZipArchive::new(Cursor::new(archive)).unwrap())
+    }
+
+    /// Writes an archive of only the `entries`, for archives the app wouldn't export.
+    fn archive(entries: &[(&str, &str)]) -> Vec<u8> {
+        let mut archive = ZipWriter::new(Cursor::new(vec![]));
+        for (name, content) in entries {
+            write_entry(&mut archive, name, content).unwrap();
+        }
+        archive.finish().unwrap().into_inner()
+    }
+
+    fn installed(dir: &Path) -> Installed {
+        Installed {
+            settings: Settings::default(),
+            data: None,
+            path_file: dir.join(PATH_ENTRY),
+            mission_file: Some(dir.join("missions").join("north.geojson")),
+        }
+    }
+
+    #[test]
+    fn archives_follow_the_layout() {
+        let archive = write_archive(
+            &manifest(Some("north")),
+            &Settings::default(),
+            &data(),
+            Some(&path(101.6).to_string()),
+            Some(&path(101.7).to_string()),
+        )
+        .unwrap();
+
+        let archive = ZipArchive::new(Cursor::new(archive)).unwrap();
+        let mut names: Vec<_> = archive.file_names().collect();
+        names.sort_unstable();
+        assert_eq!(
+            names,
+            [
+                "datasets/pond.geojson",
+                "manifest.json",
+                "missions/north.geojson",
+                "path.geojson",
+                "settings.json",
+            ]
+        );
+    }
+
+    #[test]
+    fn archives_round_trip() {
+        let archive = write_archive(
+            &manifest(Some("north")),
+            &Settings::default(),
+            &data(),
+            Some(&path(101.6).to_string()),
+            Some(&path(101.7).to_string()),
+        )
+        .unwrap();
+
+        let contents = read(archive).unwrap();
+        assert_eq!(contents.manifest.dataset, "pond");
+        assert_eq!(contents.manifest.mission.as_deref(), Some("north"));
+        assert_eq!(contents.manifest.created, manifest(None).created);
+        assert_eq!(contents.settings, Settings::default());
+        assert_eq!(contents.data, data());
+        assert_eq!(contents.path, Some(path(101.6)));
+        assert_eq!(contents.mission, Some(path(101.7)));
+    }
+
+    #[test]
+    fn unsaved_paths_are_left_out() {
+        let archive =
+            write_archive(&manifest(None), &Settings::default(), &data(), None, None).unwrap();
+
+        assert_eq!(
+            ZipArchive::new(Cursor::new(archive.clone())).unwrap().len(),
+            3
+        );
+        let contents = read(archive).unwrap();
+        assert_eq!(contents.path, None);
+        assert_eq!(contents.mission, None);
+    }
+
+    #[test]
+    fn missing_entries_are_rejected() {
+        let settings = serde_json::to_string(&Settings::default()).unwrap();
+        let json = serde_json::to_string(&manifest(Some("north"))).unwrap();
+        let dataset = data().to_string();
+
+        assert_eq!(
+            read(archive(&[(SETTINGS_ENTRY, &settings)])).err(),
+            Some(
+                AppError::new(
+                    codes::PROJECT_MISSING_ENTRY,
+                    "Missing Project Entry: manifest.json",
+                )
+                .with("entry", MANIFEST_ENTRY)
+            )
+        );
+        let error = read(archive(&[
+            (MANIFEST_ENTRY, &json),
+            (SETTINGS_ENTRY, &settings),
+            ("datasets/pond.geojson", &dataset),
+        ]))
+        .err()
+        .unwrap();
+        assert_eq!(
+            error.to_string(),
+            "Missing Project Entry: missions/north.geojson"
+        );
+    }
+
+    #[test]
+    fn invalid_manifests_are_rejected() {
+        let error = read(archive(&[(MANIFEST_ENTRY, "{")])).err().unwrap();
+        assert!(error
+            .to_string()
+            .starts_with("Invalid Project Entry manifest.json: "));
+
+        let mut newer = manifest(None);
+        newer.format = PROJECT_FORMAT + 1;
+        let json = serde_json::to_string(&newer).unwrap();
+        assert_eq!(
+            read(archive(&[(MANIFEST_ENTRY, &json)])).err(),
+            Some(
+                AppError::new(
+                    codes::PROJECT_UNSUPPORTED_FORMAT,
+                    "Unsupported Project Format: 2, supported up to 1",
+                )
+                .with("format", 2)
+                .with("supported", 1)
+            )
+        );
+
+        let mut escaping = manifest(None);
+        escaping.dataset = String::from("../pond");
+        let json = serde_json::to_string(&escaping).unwrap();
+        assert_eq!(
+            read(archive(&[(MANIFEST_ENTRY, &json)])).err(),
+            check_name("../pond").err()
+        );
+    }
+
+    #[test]
+    fn files_other_than_zips_are_rejected() {
+        let error = ZipArchive::new(Cursor::new(b"not a zip".to_vec()))
+            .map_err(invalid_archive)
+            .err()
+            .unwrap();
+        assert!(error.to_string().starts_with("Invalid Project Archive: "));
+    }
+
+    #[test]
+    fn identical_or_new_data_does_not_conflict() {
+        let dir = temp_dir("project-no-conflicts");
+        let archive = write_archive(
+            &manifest(Some("north")),
+            &Settings::default(),
+            &data(),
+            Some(&path(101.6).to_string()),
+            Some(&path(101.7).to_string()),
+        )
+        .unwrap();
+        let contents = read(archive).unwrap();
+
+        let mut installed = installed(&dir);
+        assert!(installed.conflicts(&contents).unwrap().is_empty());
+
+        installed.data = Some(data());
+        fs::write(&installed.path_file, path(101.6).to_string()).unwrap();
+        assert!(installed.conflicts(&contents).unwrap().is_empty());
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn different_data_conflicts() {
+        let dir = temp_dir("project-conflicts");
+        let archive = write_archive(
+            &manifest(Some("north")),
+            &Settings::default(),
+            &data(),
+            Some(&path(101.6).to_string()),
+            Some(&path(101.7).to_string()),
+        )
+        .unwrap();
+        let contents = read(archive).unwrap();
+
+        let mut installed = installed(&dir);
+        installed.settings.max_backups += 1;
+        installed.data = Some(BoatData::default());
+        fs::write(&installed.path_file, path(101.8).to_string()).unwrap();
+        let mission_file = installed.mission_file.clone().unwrap();
+        fs::create_dir_all(mission_file.parent().unwrap()).unwrap();
+        fs::write(&mission_file, path(101.9).to_string()).unwrap();
+        assert_eq!(
+            installed.conflicts(&contents).unwrap(),
+            [
+                SETTINGS_ENTRY,
+                "datasets/pond.geojson",
+                PATH_ENTRY,
+                "missions/north.geojson",
+            ]
+        );
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
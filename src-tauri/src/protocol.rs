@@ -0,0 +1,441 @@
+//! Framing of the protobuf messages sent between the boat and desktop application.
+//!
+//! Each frame is a protobuf message prefixed by its length as a varint. Firmware supporting
+//! [`CRC_VERSION`] can also append a CRC-16/CCITT-FALSE of the length and message, in
+//! little-endian, to detect frames corrupted by the radio link.
+
+use std::{marker::PhantomData, sync::Mutex};
+
+use prost::Message;
+use semver::{Version, VersionReq};
+use serde::Serialize;
+
+/// The version of the communication protocol used by the desktop application.
+pub const PROTOCOL_VERSION: &str = "0.1.0";
+
+/// The largest message accepted from the boat in bytes.
+///
+/// Lengths above this are treated as corrupted data.
+pub const MAX_FRAME_SIZE: usize = 4096;
+
+/// The longest encoding of a varint in bytes.
+const MAX_VARINT_LENGTH: usize = 10;
+
+/// The protocol versions of the firmware that send and expect the CRC trailer.
+pub const CRC_VERSION: &str = ">=0.2.0";
+
+/// The length of the CRC trailer in bytes.
+const CRC_LENGTH: usize = 2;
+
+/// Checks if the firmware with the protocol `version` uses the CRC trailer.
+///
+/// Invalid versions are treated as older firmware without the trailer.
+pub fn supports_crc(version: &str) -> bool {
+    Version::parse(version).map_or(false, |version| {
+        // The requirement is a constant so it is always valid
+        VersionReq::parse(CRC_VERSION).unwrap().matches(&version)
+    })
+}
+
+/// Calculates the CRC-16/CCITT-FALSE of `bytes`.
+pub fn crc16(bytes: &[u8]) -> u16 {
+    bytes.iter().fold(0xFFFF, |crc, &byte| {
+        (0..8).fold(crc ^ (u16::from(byte) << 8), |crc, _| {
+            if crc & 0x8000 != 0 {
+                (crc << 1) ^ 0x1021
+            } else {
+                crc << 1
+            }
+        })
+    })
+}
+
+/// Encodes a message as a frame, with the CRC trailer if `crc` is `true`.
+pub fn encode_frame<M: Message>(message: &M, crc: bool) -> Vec<u8> {
+    let mut frame = message.encode_length_delimited_to_vec();
+    if crc {
+        let crc = crc16(&frame);
+        frame.extend_from_slice(&crc.to_le_bytes());
+    }
+    frame
+}
+
+/// Counts of the frames decoded from the boat.
+#[derive(Debug, Default, Serialize, Clone, Copy, PartialEq, Eq)]
+pub struct ProtocolStats {
+    /// The number of frames decoded.
+    frames: u64,
+    /// The number of malformed frames skipped.
+    errors: u64,
+    /// The number of frames skipped because their CRC didn't match.
+    crc_errors: u64,
+    /// The number of bytes discarded while looking for the next frame.
+    discarded_bytes: u64,
+}
+
+/// Decodes frames from the bytes received in pieces.
+///
+/// Malformed frames, and frames failing the CRC when it is used, are skipped by discarding
+/// bytes until a valid frame is found.
+#[derive(Debug)]
+pub struct FrameDecoder<M> {
+    /// The bytes received that are not decoded yet.
+    buf: Vec<u8>,
+    /// Whether the frames have the CRC trailer.
+    crc: bool,
+    /// Counts of the frames decoded.
+    stats: ProtocolStats,
+    message: PhantomData<M>,
+}
+
+impl<M> Default for FrameDecoder<M> {
+    fn default() -> Self {
+        Self {
+            buf: vec![],
+            crc: false,
+            stats: ProtocolStats::default(),
+            message: PhantomData,
+        }
+    }
+}
+
+impl<M: Message + Default> FrameDecoder<M> {
+    /// Adds bytes received to the decoder.
+    pub fn push(&mut self, bytes: &[u8]) {
+        self.buf.extend_from_slice(bytes);
+    }
+
+    /// Gets the counts of the frames decoded.
+    pub fn stats(&self) -> ProtocolStats {
+        self.stats
+    }
+
+    /// Sets whether the frames have the CRC trailer, e.g. after the protocol version is
+    /// negotiated.
+    pub fn set_crc(&mut self, crc: bool) {
+        self.crc = crc;
+    }
+
+    /// Discards the first byte to look for a frame after it.
+    fn skip(&mut self) {
+        self.buf.remove(0);
+        self.stats.discarded_bytes += 1;
+    }
+
+    /// Decodes the next complete frame.
+    ///
+    /// Returns `None` when more bytes are needed.
+    pub fn next_frame(&mut self) -> Option<M> {
+        self.next_raw_frame().map(|(message, _)| message)
+    }
+
+    /// Decodes the next complete frame along with its bytes.
+    ///
+    /// Returns `None` when more bytes are needed.
+    pub fn next_raw_frame(&mut self) -> Option<(M, Vec<u8>)> {
+        loop {
+            let length_complete = self
+                .buf
+                .iter()
+                .take(MAX_VARINT_LENGTH)
+                .any(|b| b & 0x80 == 0);
+            if !length_complete && self.buf.len() < MAX_VARINT_LENGTH {
+                return None;
+            }
+
+            let length = match prost::decode_length_delimiter(&*self.buf) {
+                Ok(v) if v <= MAX_FRAME_SIZE => v,
+                // Not the start of a frame
+                _ => {
+                    self.skip();
+                    continue;
+                }
+            };
+            let start = prost::length_delimiter_len(length);
+            let trailer = if self.crc { CRC_LENGTH } else { 0 };
+            let end = start + length;
+            if self.buf.len() < end + trailer {
+                return None;
+            }
+
+            if self.crc {
+                let expected = u16::from_le_bytes([self.buf[end], self.buf[end + 1]]);
+                if crc16(&self.buf[..end]) != expected {
+                    log::debug!("Skipping Frame with Invalid CRC");
+                    self.stats.crc_errors += 1;
+                    self.skip();
+                    continue;
+                }
+            }
+
+            match M::decode(&self.buf[start..end]) {
+                Ok(message) => {
+                    let bytes = self.buf.drain(..end + trailer).collect();
+                    self.stats.frames += 1;
+                    return Some((message, bytes));
+                }
+                Err(e) => {
+                    log::debug!("Skipping Malformed Frame: {}", e);
+                    self.stats.errors += 1;
+                    self.skip();
+                }
+            }
+        }
+    }
+}
+
+/// Counts of the frames decoded on the current connection.
+#[derive(Debug, Default)]
+pub struct ProtocolState {
+    pub stats: Mutex<ProtocolStats>,
+}
+
+/// Gets the counts of the frames decoded on the current connection.
+#[tauri::command]
+pub fn protocol_stats(state: tauri::State<ProtocolState>) -> ProtocolStats {
+    *state.stats.lock().unwrap()
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    /// A message like the readings sent by the boat.
+    #[derive(Clone, PartialEq, Message)]
+    struct TestMessage {
+        #[prost(uint32, tag = "1")]
+        sequence: u32,
+        #[prost(string, tag = "2")]
+        text: String,
+    }
+
+    /// Creates `count` messages numbered from 1.
+    fn messages(count: u32) -> Vec<TestMessage> {
+        (1..=count)
+            .map(|sequence| TestMessage {
+                sequence,
+                text: format!("reading {sequence}"),
+            })
+            .collect()
+    }
+
+    /// Encodes the messages as consecutive frames.
+    fn encode_frames(messages: &[TestMessage], crc: bool) -> Vec<u8> {
+        messages
+            .iter()
+            .flat_map(|message| encode_frame(message, crc))
+            .collect()
+    }
+
+    /// A xorshift generator, so the tests split the frames the same way on each run.
+    pub(crate) struct Random(pub(crate) u64);
+
+    impl Random {
+        pub(crate) fn next(&mut self) -> u64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            self.0
+        }
+
+        /// Gets a number below `n`.
+        pub(crate) fn below(&mut self, n: usize) -> usize {
+            (self.next() % n as u64) as usize
+        }
+
+        /// Gets a number between `min` and `max`.
+        pub(crate) fn between(&mut self, min: f64, max: f64) -> f64 {
+            min + (self.next() >> 11) as f64 / (1u64 << 53) as f64 * (max - min)
+        }
+    }
+
+    /// Pushes `bytes` to a decoder in pieces of random lengths, decoding the frames completed
+    /// by each piece.
+    fn decode_in_pieces(
+        bytes: &[u8],
+        crc: bool,
+        random: &mut Random,
+    ) -> (Vec<TestMessage>, ProtocolStats) {
+        let mut decoder = FrameDecoder::<TestMessage>::default();
+        decoder.set_crc(crc);
+        let mut decoded = vec![];
+        let mut start = 0;
+        while start < bytes.len() {
+            let end = (start + 1 + random.below(24)).min(bytes.len());
+            decoder.push(&bytes[start..end]);
+            decoded.extend(std::iter::from_fn(|| decoder.next_frame()));
+            start = end;
+        }
+        (decoded, decoder.stats())
+    }
+
+    #[test]
+    fn frames_split_at_random_positions_are_decoded() {
+        let messages = messages(50);
+        for crc in [false, true] {
+            let bytes = encode_frames(&messages, crc);
+            let mut random = Random(0x9E37_79B9_7F4A_7C15);
+            for _ in 0..200 {
+                let (decoded, stats) = decode_in_pieces(&bytes, crc, &mut random);
+                assert_eq!(decoded, messages, "crc {crc}");
+                let expected = ProtocolStats {
+                    frames: 50,
+                    ..ProtocolStats::default()
+                };
+                assert_eq!(stats, expected, "crc {crc}");
+            }
+        }
+    }
+
+    #[test]
+    fn frames_split_at_every_position_are_decoded() {
+        let messages = messages(3);
+        for crc in [false, true] {
+            let bytes = encode_frames(&messages, crc);
+            for split in 0..=bytes.len() {
+                let mut decoder = FrameDecoder::<TestMessage>::default();
+                decoder.set_crc(crc);
+                decoder.push(&bytes[..split]);
+                let mut decoded: Vec<_> = std::iter::from_fn(|| decoder.next_frame()).collect();
+                decoder.push(&bytes[split..]);
+                decoded.extend(std::iter::from_fn(|| decoder.next_frame()));
+
+                assert_eq!(decoded, messages, "crc {crc}, split {split}");
+                assert_eq!(decoder.stats().discarded_bytes, 0);
+            }
+        }
+    }
+
+    #[test]
+    fn decoder_with_crc_resynchronizes_after_random_garbage() {
+        let messages = messages(400);
+        let mut random = Random(0x2545_F491_4F6C_DD1D);
+        let mut bytes = vec![];
+        let mut garbage = 0;
+        for (i, message) in messages.iter().enumerate() {
+            // The frames after the last garbage are longer than the largest frame, so a length
+            // read from the garbage never waits for bytes past the end
+            if i < 100 && i % 5 == 0 {
+                let length = 1 + random.below(8);
+                bytes.extend((0..length).map(|_| random.next() as u8));
+                garbage += length as u64;
+            }
+            bytes.extend(encode_frame(message, true));
+        }
+
+        let (decoded, stats) = decode_in_pieces(&bytes, true, &mut random);
+
+        assert_eq!(decoded, messages);
+        assert_eq!(stats.frames, 400);
+        assert_eq!(stats.errors, 0);
+        assert_eq!(stats.discarded_bytes, garbage);
+    }
+
+    #[test]
+    fn decoder_resynchronizes_after_garbage() {
+        // An overlong length, and a frame with a truncated field
+        let garbage: [&[u8]; 2] = [&[0xFF; 11], &[0x02, 0xFF, 0xFF]];
+        let messages = messages(400);
+        let mut bytes = vec![];
+        let mut discarded = 0;
+        for (i, message) in messages.iter().enumerate() {
+            // The frames after the last garbage are longer than the largest frame, so a length
+            // read from the garbage never waits for bytes past the end
+            if i < 100 && i % 5 == 0 {
+                let garbage = garbage[i / 5 % 2];
+                bytes.extend(garbage);
+                discarded += garbage.len() as u64;
+            }
+            bytes.extend(encode_frame(message, false));
+        }
+
+        let (decoded, stats) = decode_in_pieces(&bytes, false, &mut Random(0x2545_F491_4F6C_DD1D));
+
+        assert_eq!(decoded, messages);
+        assert_eq!(stats.frames, 400);
+        assert!(stats.errors >= 10);
+        assert_eq!(stats.discarded_bytes, discarded);
+    }
+
+    #[test]
+    fn raw_frame_has_bytes_received() {
+        let messages = messages(2);
+        for crc in [false, true] {
+            let mut decoder = FrameDecoder::<TestMessage>::default();
+            decoder.set_crc(crc);
+            decoder.push(&encode_frames(&messages, crc));
+
+            let frames: Vec<_> = std::iter::from_fn(|| decoder.next_raw_frame()).collect();
+
+            let expected: Vec<_> = messages
+                .iter()
+                .map(|message| (message.clone(), encode_frame(message, crc)))
+                .collect();
+            assert_eq!(frames, expected, "crc {crc}");
+        }
+    }
+
+    #[test]
+    fn crc_rejects_every_single_bit_flip() {
+        let messages = messages(400);
+        let corrupted = 100;
+        let frame = encode_frame(&messages[corrupted], true);
+        let expected: Vec<_> = messages
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| *i != corrupted)
+            .map(|(_, message)| message.clone())
+            .collect();
+
+        for bit in 0..frame.len() * 8 {
+            let mut flipped = frame.clone();
+            flipped[bit / 8] ^= 1 << (bit % 8);
+            let mut bytes = encode_frames(&messages[..corrupted], true);
+            bytes.extend(flipped);
+            bytes.extend(encode_frames(&messages[corrupted + 1..], true));
+
+            let mut decoder = FrameDecoder::<TestMessage>::default();
+            decoder.set_crc(true);
+            decoder.push(&bytes);
+            let decoded: Vec<_> = std::iter::from_fn(|| decoder.next_frame()).collect();
+
+            assert_eq!(decoded, expected, "bit {bit}");
+            let stats = decoder.stats();
+            assert_eq!(stats.frames, expected.len() as u64, "bit {bit}");
+            assert!(stats.crc_errors >= 1, "bit {bit}");
+        }
+    }
+
+    #[test]
+    fn bit_flip_without_crc_can_go_unnoticed() {
+        let message = TestMessage {
+            sequence: 1,
+            text: String::from("reading"),
+        };
+        let mut frame = encode_frame(&message, false);
+        // A letter of the text
+        let last = frame.len() - 1;
+        frame[last] ^= 0x01;
+
+        let mut decoder = FrameDecoder::<TestMessage>::default();
+        decoder.push(&frame);
+        let decoded = decoder.next_frame().unwrap();
+
+        assert_ne!(decoded, message);
+        assert_eq!(decoder.stats().crc_errors, 0);
+    }
+
+    #[test]
+    fn crc16_matches_ccitt_check_value() {
+        assert_eq!(crc16(b"123456789"), 0x29B1);
+        assert_eq!(crc16(&[]), 0xFFFF);
+    }
+
+    #[test]
+    fn newer_firmware_supports_crc() {
+        assert!(!supports_crc("0.1.0"));
+        assert!(supports_crc("0.2.0"));
+        assert!(supports_crc("1.0.0"));
+        assert!(!supports_crc("not a version"));
+    }
+}
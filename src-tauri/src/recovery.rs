@@ -0,0 +1,346 @@
+//! Write-ahead journal of the readings received since the boat data was last saved, so they can
+//! be restored after a crash.
+//!
+//! Readings are appended to `recovery.ndjson` as they arrive. The journal is synced to disk
+//! after a number of readings or an interval, whichever comes first, to bound the readings lost
+//! in a crash without syncing every reading.
+
+use std::{
+    fs::{File, OpenOptions},
+    io::{BufRead, BufReader, ErrorKind, Write},
+    path::{Path, PathBuf},
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use serde::Serialize;
+use tauri::{AppHandle, Manager};
+
+use crate::{
+    data::{self, BoatData, BoatDataFeature},
+    errors::AppError,
+    migrations,
+    settings::SettingsState,
+    storage::app_data_file,
+};
+
+/// The name of the recovery journal in the application data directory.
+const RECOVERY_FILE: &str = "recovery.ndjson";
+
+/// How often the recovery journal is checked for readings waiting to be synced.
+const SYNC_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+/// The recovery journal open for appending.
+#[derive(Debug)]
+struct RecoveryWriter {
+    file: File,
+    /// The number of readings written since the last sync.
+    unsynced: usize,
+    /// The time of the last sync.
+    last_sync: Instant,
+}
+
+impl RecoveryWriter {
+    /// Syncs the journal to disk.
+    fn sync(&mut self) -> std::io::Result<()> {
+        self.file.sync_data()?;
+        self.unsynced = 0;
+        self.last_sync = Instant::now();
+        Ok(())
+    }
+
+    /// Appends a line, syncing the journal once `max_unsynced` readings are waiting or
+    /// `interval` has passed since the last sync.
+    fn append(
+        &mut self,
+        line: &str,
+        max_unsynced: usize,
+        interval: Duration,
+    ) -> std::io::Result<()> {
+        writeln!(self.file, "{line}")?;
+        self.unsynced += 1;
+        if self.unsynced >= max_unsynced || self.last_sync.elapsed() >= interval {
+            self.sync()?;
+        }
+        Ok(())
+    }
+}
+
+/// The recovery journal of the readings received.
+#[derive(Debug, Default)]
+pub struct RecoveryState {
+    writer: Mutex<Option<RecoveryWriter>>,
+}
+
+/// The readings that can be recovered.
+#[derive(Debug, Serialize, Clone, PartialEq, Eq)]
+pub struct RecoveryInfo {
+    /// The number of readings in the recovery journal.
+    readings: usize,
+    /// The number of corrupted lines skipped.
+    corrupted: usize,
+}
+
+/// Gets the path of the recovery journal.
+fn recovery_file(app_handle: &AppHandle) -> Result<PathBuf, AppError> {
+    app_data_file(app_handle, RECOVERY_FILE)
+}
+
+/// Gets the number of readings and the interval after which the journal is synced.
+fn sync_policy(app_handle: &AppHandle) -> (usize, Duration) {
+    let settings = app_handle.state::<SettingsState>().get();
+    (
+        settings.recovery_sync_readings,
+        Duration::from_secs(settings.recovery_sync_seconds),
+    )
+}
+
+/// Opens the recovery journal for appending.
+fn open(path: &Path) -> std::io::Result<RecoveryWriter> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let mut file = OpenOptions::new().append(true).create(true).open(path)?;
+    // A line cut short by a crash is terminated so the next reading starts on its own line
+    if file.metadata()?.len() > 0 {
+        file.write_all(b"\n")?;
+    }
+    Ok(RecoveryWriter {
+        file,
+        unsynced: 0,
+        last_sync: Instant::now(),
+    })
+}
+
+/// Appends a reading received to the recovery journal.
+pub fn record(app_handle: &AppHandle, feature: &BoatDataFeature) -> Result<(), AppError> {
+    let line =
+        geojson::ser::to_feature_string(feature).map_err(|e| AppError::from(e.to_string()))?;
+    let (max_unsynced, interval) = sync_policy(app_handle);
+    let path = recovery_file(app_handle)?;
+
+    let state = app_handle.state::<RecoveryState>();
+    let mut writer = state.writer.lock().unwrap();
+    if writer.is_none() {
+        *writer = Some(open(&path).map_err(|e| AppError::io(e, &path))?);
+    }
+    // We can safely unwrap as the journal is opened above
+    writer
+        .as_mut()
+        .unwrap()
+        .append(&line, max_unsynced, interval)
+        .map_err(|e| AppError::io(e, &path))
+}
+
+/// Syncs the readings waiting longer than the sync interval.
+fn sync_if_due(app_handle: &AppHandle) {
+    let (_, interval) = sync_policy(app_handle);
+    let state = app_handle.state::<RecoveryState>();
+    let mut writer = state.writer.lock().unwrap();
+    if let Some(writer) = writer.as_mut() {
+        if writer.unsynced > 0 && writer.last_sync.elapsed() >= interval {
+            if let Err(e) = writer.sync() {
+                log::warn!("Unable to Sync the Recovery Journal: {}", e);
+            }
+        }
+    }
+}
+
+/// Syncs and closes the recovery journal, so it is opened again at its current path by the next
+/// reading, e.g. after the data directory is moved.
+pub fn close_recovery(app_handle: &AppHandle) -> Result<(), AppError> {
+    let path = recovery_file(app_handle)?;
+    let state = app_handle.state::<RecoveryState>();
+    let mut writer = state.writer.lock().unwrap();
+    if let Some(mut writer) = writer.take() {
+        writer.sync().map_err(|e| AppError::io(e, &path))?;
+    }
+    Ok(())
+}
+
+/// Starts syncing the recovery journal in the background, so readings received just before the
+/// boat stops sending aren't left unsynced.
+pub fn init_recovery(app_handle: &AppHandle) {
+    let app_handle = app_handle.clone();
+    std::thread::spawn(move || loop {
+        std::thread::sleep(SYNC_CHECK_INTERVAL);
+        sync_if_due(&app_handle);
+    });
+}
+
+/// Reads the readings in the recovery journal, returning them with the number of corrupted
+/// lines skipped.
+fn read_recovery(path: &Path) -> Result<(Vec<BoatDataFeature>, usize), AppError> {
+    let file = match File::open(path) {
+        Ok(v) => v,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok((vec![], 0)),
+        Err(e) => return Err(AppError::io(e, path)),
+    };
+
+    let mut features = vec![];
+    let mut corrupted = 0;
+    for (i, line) in BufReader::new(file).lines().enumerate() {
+        let line = line.map_err(|e| AppError::io(e, path))?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        match geojson::de::deserialize_single_feature(line.as_bytes()) {
+            Ok(v) => features.push(v),
+            Err(e) => {
+                log::warn!("Skipping Corrupted Recovery Line {}: {}", i + 1, e);
+                corrupted += 1;
+            }
+        }
+    }
+    Ok((features, corrupted))
+}
+
+/// Removes the recovery journal, closing it first.
+pub fn clear_recovery(app_handle: &AppHandle) -> Result<(), AppError> {
+    let state = app_handle.state::<RecoveryState>();
+    let mut writer = state.writer.lock().unwrap();
+    *writer = None;
+    let path = recovery_file(app_handle)?;
+    match std::fs::remove_file(&path) {
+        Ok(_) => Ok(()),
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(()),
+        Err(e) => Err(AppError::io(e, &path)),
+    }
+}
+
+/// Check for readings received since the boat data was last saved, e.g. before a crash.
+#[tauri::command]
+pub fn check_recovery(app_handle: AppHandle) -> Result<RecoveryInfo, AppError> {
+    log::debug!("Checking Recovery Journal");
+    let (features, corrupted) = read_recovery(&recovery_file(&app_handle)?)?;
+    Ok(RecoveryInfo {
+        readings: features.len(),
+        corrupted,
+    })
+}
+
+/// Restore the readings in the recovery journal into the active dataset.
+///
+/// Readings already in the dataset are not added again. The recovery journal is removed after
+/// the readings are stored. Returns the number of readings recovered.
+#[tauri::command]
+pub fn apply_recovery(app_handle: AppHandle) -> Result<usize, AppError> {
+    log::debug!("Applying Recovery Journal");
+    let (features, corrupted) = read_recovery(&recovery_file(&app_handle)?)?;
+    if corrupted > 0 {
+        log::warn!("Skipped {} Corrupted Recovery Lines", corrupted);
+    }
+
+    let count = features.len();
+    if count > 0 {
+        let recovered = BoatData::new(String::from(migrations::BOAT_DATA_VERSION), features);
+        data::merge_data(app_handle.clone(), recovered)?;
+    }
+    clear_recovery(&app_handle)?;
+    log::info!("Recovered {} Readings", count);
+    Ok(count)
+}
+
+/// Discard the readings in the recovery journal.
+#[tauri::command]
+pub fn discard_recovery(app_handle: AppHandle) -> Result<(), AppError> {
+    log::info!("Discarding Recovery Journal");
+    clear_recovery(&app_handle)
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeZone, Utc};
+    use geo_types::Point;
+
+    use super::*;
+    use crate::{data::Layer, storage::tests::temp_dir};
+
+    /// Creates a reading measured `seconds` after the start of the mission.
+    fn reading(seconds: i64) -> BoatDataFeature {
+        BoatDataFeature::new(
+            28.5,
+            0.5,
+            Layer::Surface,
+            Utc.timestamp_opt(1_715_495_400 + seconds, 0).unwrap(),
+            Point::new(101.5, 3.1),
+        )
+    }
+
+    /// Appends readings to the journal at `path` without syncing them.
+    fn append(path: &Path, readings: &[BoatDataFeature]) -> RecoveryWriter {
+        let mut writer = open(path).unwrap();
+        for reading in readings {
+            let line = geojson::ser::to_feature_string(reading).unwrap();
+            writer
+                .append(&line, usize::MAX, Duration::from_secs(3600))
+                .unwrap();
+        }
+        writer
+    }
+
+    #[test]
+    fn appended_readings_are_read_back() {
+        let dir = temp_dir("recovery_read_back");
+        let path = dir.join(RECOVERY_FILE);
+
+        drop(append(&path, &[reading(0), reading(1)]));
+        drop(append(&path, &[reading(2)]));
+
+        assert_eq!(
+            read_recovery(&path).unwrap(),
+            (vec![reading(0), reading(1), reading(2)], 0)
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn missing_journal_has_no_readings() {
+        let dir = temp_dir("recovery_missing");
+
+        assert_eq!(
+            read_recovery(&dir.join(RECOVERY_FILE)).unwrap(),
+            (vec![], 0)
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn line_cut_short_is_skipped_and_counted() {
+        let dir = temp_dir("recovery_corrupted");
+        let path = dir.join(RECOVERY_FILE);
+        drop(append(&path, &[reading(0)]));
+        let line = geojson::ser::to_feature_string(&reading(1)).unwrap();
+        let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+        file.write_all(&line.as_bytes()[..line.len() / 2]).unwrap();
+        drop(file);
+
+        // The next reading starts on its own line after the one cut short
+        drop(append(&path, &[reading(2)]));
+
+        assert_eq!(
+            read_recovery(&path).unwrap(),
+            (vec![reading(0), reading(2)], 1)
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn journal_is_synced_after_the_readings_or_interval() {
+        let dir = temp_dir("recovery_sync");
+        let path = dir.join(RECOVERY_FILE);
+        let mut writer = open(&path).unwrap();
+        let line = geojson::ser::to_feature_string(&reading(0)).unwrap();
+        let hour = Duration::from_secs(3600);
+
+        writer.append(&line, 3, hour).unwrap();
+        writer.append(&line, 3, hour).unwrap();
+        assert_eq!(writer.unsynced, 2);
+        writer.append(&line, 3, hour).unwrap();
+        assert_eq!(writer.unsynced, 0);
+
+        writer.append(&line, 3, Duration::ZERO).unwrap();
+        assert_eq!(writer.unsynced, 0);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
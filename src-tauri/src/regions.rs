@@ -0,0 +1,750 @@
+//! Regions of map tiles downloaded for use without a connection.
+//!
+//! The tiles of every region are stored once in the offline MBTiles archive in the map
+//! directory, which is served by the `tiles://` protocol. Each region records the tiles it
+//! needs, so a tile shared by overlapping regions is only removed with the last of them.
+
+use std::{
+    f64::consts::PI,
+    io::Read,
+    path::Path,
+    sync::{Arc, Mutex},
+};
+
+use serde::{Deserialize, Serialize};
+use sqlx::{sqlite::SqliteConnectOptions, Connection, Executor, SqliteConnection};
+use tauri::{async_runtime::block_on, AppHandle, Manager};
+
+use crate::{
+    errors::{codes, AppError},
+    map_assets::map_dir,
+    storage::check_name,
+};
+
+/// The file name of the archive the regions are stored in.
+pub const ARCHIVE_NAME: &str = "offline.mbtiles";
+
+/// The largest zoom level downloaded.
+const MAX_ZOOM: u32 = 20;
+
+/// The largest latitude of the Web Mercator projection.
+pub const MAX_LATITUDE: f64 = 85.051_128_78;
+
+/// The default number of tiles requested at the same time.
+const DEFAULT_MAX_CONCURRENT: usize = 4;
+
+/// The default number of tiles a region can have.
+const DEFAULT_MAX_TILES: u64 = 50_000;
+
+/// The tables of the archive, following the MBTiles schema with the regions added.
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS metadata (name TEXT PRIMARY KEY, value TEXT);
+CREATE TABLE IF NOT EXISTS tiles (
+    zoom_level INTEGER, tile_column INTEGER, tile_row INTEGER, tile_data BLOB,
+    PRIMARY KEY (zoom_level, tile_column, tile_row)
+);
+CREATE TABLE IF NOT EXISTS regions (
+    name TEXT PRIMARY KEY, min_lng REAL, min_lat REAL, max_lng REAL, max_lat REAL,
+    min_zoom INTEGER, max_zoom INTEGER, complete INTEGER
+);
+CREATE TABLE IF NOT EXISTS region_tiles (
+    region TEXT, zoom_level INTEGER, tile_column INTEGER, tile_row INTEGER,
+    PRIMARY KEY (region, zoom_level, tile_column, tile_row)
+);
+INSERT OR IGNORE INTO metadata VALUES ('name', 'Offline Regions'), ('format', 'pbf'), ('scheme', 'tms');
+";
+
+/// Options of the downloads of regions.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct RegionDownloadOptions {
+    /// The URL of the tile source with `{z}`, `{x}` and `{y}` placeholders.
+    tile_url: Option<String>,
+    /// The number of tiles requested at the same time.
+    max_concurrent: usize,
+    /// The number of tiles a region can have, to refuse downloading absurdly large regions.
+    max_tiles: u64,
+}
+
+impl Default for RegionDownloadOptions {
+    fn default() -> Self {
+        Self {
+            tile_url: None,
+            max_concurrent: DEFAULT_MAX_CONCURRENT,
+            max_tiles: DEFAULT_MAX_TILES,
+        }
+    }
+}
+
+/// A tile in XYZ coordinates.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Tile {
+    z: u32,
+    x: u32,
+    y: u32,
+}
+
+impl Tile {
+    /// Gets the row of the tile in the TMS scheme used by the archive.
+    fn row(&self) -> u32 {
+        (1 << self.z) - 1 - self.y
+    }
+}
+
+/// Gets the column and row of the tile at a coordinate.
+fn tile_at(lng: f64, lat: f64, z: u32) -> (u32, u32) {
+    let n = f64::from(1u32 << z);
+    let lat = lat.clamp(-MAX_LATITUDE, MAX_LATITUDE).to_radians();
+    let x = ((lng + 180.0) / 360.0 * n).floor();
+    let y = ((1.0 - (lat.tan() + 1.0 / lat.cos()).ln() / PI) / 2.0 * n).floor();
+    (x.clamp(0.0, n - 1.0) as u32, y.clamp(0.0, n - 1.0) as u32)
+}
+
+/// An area of the map downloaded at a range of zoom levels.
+#[derive(Debug, Clone, PartialEq)]
+struct Region {
+    name: String,
+    /// The bounding box as `[min_lng, min_lat, max_lng, max_lat]`.
+    bbox: [f64; 4],
+    min_zoom: u32,
+    max_zoom: u32,
+}
+
+impl Region {
+    /// Creates a region, checking its bounding box and zoom levels.
+    fn new(name: String, bbox: [f64; 4], min_zoom: u32, max_zoom: u32) -> Result<Self, AppError> {
+        check_name(&name)?;
+        let [min_lng, min_lat, max_lng, max_lat] = bbox;
+        if !(-180.0..=180.0).contains(&min_lng)
+            || !(-180.0..=180.0).contains(&max_lng)
+            || !(-90.0..=90.0).contains(&min_lat)
+            || !(-90.0..=90.0).contains(&max_lat)
+            || min_lng > max_lng
+            || min_lat > max_lat
+        {
+            return Err(AppError::new(
+                codes::REGION_INVALID_BBOX,
+                format!("Invalid Bounding Box: {bbox:?}"),
+            )
+            .with("bbox", bbox.to_vec()));
+        }
+        if min_zoom > max_zoom || max_zoom > MAX_ZOOM {
+            return Err(AppError::new(
+                codes::REGION_INVALID_ZOOM,
+                format!(
+                    "Invalid Zoom Levels: {min_zoom} to {max_zoom} must be between 0 and {MAX_ZOOM}"
+                ),
+            )
+            .with("min_zoom", min_zoom)
+            .with("max_zoom", max_zoom)
+            .with("max", MAX_ZOOM));
+        }
+        Ok(Self {
+            name,
+            bbox,
+            min_zoom,
+            max_zoom,
+        })
+    }
+
+    /// Gets the range of columns and rows of the tiles at zoom level `z`.
+    fn tile_range(&self, z: u32) -> ((u32, u32), (u32, u32)) {
+        let [min_lng, min_lat, max_lng, max_lat] = self.bbox;
+        let (min_x, min_y) = tile_at(min_lng, max_lat, z);
+        let (max_x, max_y) = tile_at(max_lng, min_lat, z);
+        ((min_x, max_x), (min_y, max_y))
+    }
+
+    /// Counts the tiles of the region.
+    fn count_tiles(&self) -> u64 {
+        (self.min_zoom..=self.max_zoom)
+            .map(|z| {
+                let ((min_x, max_x), (min_y, max_y)) = self.tile_range(z);
+                u64::from(max_x - min_x + 1) * u64::from(max_y - min_y + 1)
+            })
+            .sum()
+    }
+
+    /// Gets the tiles of the region.
+    fn tiles(&self) -> impl Iterator<Item = Tile> + '_ {
+        (self.min_zoom..=self.max_zoom).flat_map(move |z| {
+            let ((min_x, max_x), (min_y, max_y)) = self.tile_range(z);
+            (min_x..=max_x).flat_map(move |x| (min_y..=max_y).map(move |y| Tile { z, x, y }))
+        })
+    }
+}
+
+/// Information on a region downloaded.
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct RegionInfo {
+    name: String,
+    /// The bounding box as `[min_lng, min_lat, max_lng, max_lat]`.
+    bbox: [f64; 4],
+    min_zoom: u32,
+    max_zoom: u32,
+    /// Whether every tile of the region is downloaded.
+    complete: bool,
+    /// The number of tiles downloaded.
+    tiles: u64,
+    /// The size of the tiles in bytes, including the tiles shared with other regions.
+    bytes: u64,
+}
+
+/// A row of the regions listed, with the number and size of their tiles.
+type RegionRow = (String, f64, f64, f64, f64, u32, u32, bool, i64, i64);
+
+/// Payload of the `region-download-progress` event.
+#[derive(Debug, Serialize, Clone)]
+struct RegionProgressPayload {
+    /// The name of the region.
+    name: String,
+    /// The number of tiles downloaded, including the tiles downloaded before.
+    downloaded: u64,
+    /// The number of tiles that couldn't be downloaded.
+    failed: u64,
+    /// The number of tiles of the region.
+    total: u64,
+}
+
+/// Opens the archive, creating it if it doesn't exist.
+async fn open_archive(path: &Path) -> Result<SqliteConnection, sqlx::Error> {
+    let options = SqliteConnectOptions::new()
+        .filename(path)
+        .create_if_missing(true);
+    let mut connection = SqliteConnection::connect_with(&options).await?;
+    connection.execute(SCHEMA).await?;
+    Ok(connection)
+}
+
+/// Records the region, keeping the tiles it already has so its download is resumed.
+async fn save_region(
+    connection: &mut SqliteConnection,
+    region: &Region,
+) -> Result<(), sqlx::Error> {
+    let [min_lng, min_lat, max_lng, max_lat] = region.bbox;
+    sqlx::query("INSERT OR REPLACE INTO regions VALUES ($1, $2, $3, $4, $5, $6, $7, 0)")
+        .bind(&region.name)
+        .bind(min_lng)
+        .bind(min_lat)
+        .bind(max_lng)
+        .bind(max_lat)
+        .bind(region.min_zoom)
+        .bind(region.max_zoom)
+        .execute(connection)
+        .await?;
+    Ok(())
+}
+
+/// Adds a tile to a region if the tile is already stored.
+///
+/// Returns `true` if the region has the tile.
+async fn link_tile(
+    connection: &mut SqliteConnection,
+    region: &str,
+    tile: Tile,
+) -> Result<bool, sqlx::Error> {
+    let linked: Option<(i64,)> = sqlx::query_as(
+        "SELECT 1 FROM region_tiles WHERE region = $1 AND zoom_level = $2 AND tile_column = $3 AND tile_row = $4",
+    )
+    .bind(region)
+    .bind(tile.z)
+    .bind(tile.x)
+    .bind(tile.row())
+    .fetch_optional(&mut *connection)
+    .await?;
+    if linked.is_some() {
+        return Ok(true);
+    }
+
+    // Tiles downloaded for overlapping regions are shared instead of downloaded again
+    let result = sqlx::query(
+        "INSERT INTO region_tiles SELECT $1, zoom_level, tile_column, tile_row FROM tiles WHERE zoom_level = $2 AND tile_column = $3 AND tile_row = $4",
+    )
+    .bind(region)
+    .bind(tile.z)
+    .bind(tile.x)
+    .bind(tile.row())
+    .execute(&mut *connection)
+    .await?;
+    Ok(result.rows_affected() > 0)
+}
+
+/// Stores tiles downloaded for a region.
+///
+/// Tiles the source doesn't have, e.g. over water, are added to the region without data.
+async fn store_tiles(
+    connection: &mut SqliteConnection,
+    region: &str,
+    tiles: Vec<(Tile, Option<Vec<u8>>)>,
+) -> Result<(), sqlx::Error> {
+    let mut transaction = connection.begin().await?;
+    for (tile, data) in tiles {
+        if let Some(data) = data {
+            sqlx::query("INSERT OR REPLACE INTO tiles VALUES ($1, $2, $3, $4)")
+                .bind(tile.z)
+                .bind(tile.x)
+                .bind(tile.row())
+                .bind(data)
+                .execute(&mut *transaction)
+                .await?;
+        }
+        sqlx::query("INSERT OR IGNORE INTO region_tiles VALUES ($1, $2, $3, $4)")
+            .bind(region)
+            .bind(tile.z)
+            .bind(tile.x)
+            .bind(tile.row())
+            .execute(&mut *transaction)
+            .await?;
+    }
+    transaction.commit().await
+}
+
+/// Creates the error of the archive at `path` failing.
+fn archive_error(path: &Path, reason: sqlx::Error) -> AppError {
+    AppError::reason(
+        codes::REGION_ARCHIVE,
+        &format!("Offline Archive Error: {}", path.display()),
+        reason,
+    )
+    .with("path", path.display().to_string())
+}
+
+/// Creates the error of the region `name` not existing.
+fn not_found(name: &str) -> AppError {
+    AppError::new(codes::REGION_NOT_FOUND, format!("Region Not Found: {name}")).with("name", name)
+}
+
+/// Downloads a tile from the source.
+///
+/// Returns `None` if the source doesn't have the tile.
+fn fetch_tile(tile_url: &str, tile: Tile) -> Result<Option<Vec<u8>>, String> {
+    let url = tile_url
+        .replace("{z}", &tile.z.to_string())
+        .replace("{x}", &tile.x.to_string())
+        .replace("{y}", &tile.y.to_string());
+    let response = match ureq::get(&url).call() {
+        Ok(v) => v,
+        Err(ureq::Error::Status(404, _)) => return Ok(None),
+        Err(e) => return Err(e.to_string()),
+    };
+    if response.status() == 204 {
+        return Ok(None);
+    }
+    let mut data = vec![];
+    response
+        .into_reader()
+        .read_to_end(&mut data)
+        .map_err(|e| e.to_string())?;
+    Ok(Some(data))
+}
+
+/// Downloads the tiles of a region missing from the archive, `max_concurrent` at a time.
+///
+/// Returns the number of tiles that couldn't be downloaded.
+fn download(
+    app_handle: &AppHandle,
+    region: &Region,
+    options: &RegionDownloadOptions,
+    tile_url: &str,
+) -> Result<u64, AppError> {
+    let dir = map_dir(app_handle)?;
+    std::fs::create_dir_all(&dir).map_err(|e| AppError::io(e, &dir))?;
+    let path = dir.join(ARCHIVE_NAME);
+    let failed_query = |e: sqlx::Error| archive_error(&path, e);
+    let mut connection = block_on(open_archive(&path)).map_err(failed_query)?;
+    block_on(save_region(&mut connection, region)).map_err(failed_query)?;
+
+    let total = region.count_tiles();
+    let mut downloaded = 0;
+    let mut missing = vec![];
+    for tile in region.tiles() {
+        if block_on(link_tile(&mut connection, &region.name, tile)).map_err(failed_query)? {
+            downloaded += 1;
+        } else {
+            missing.push(tile);
+        }
+    }
+    log::info!(
+        "Downloading {} of {} Tiles of Region {}",
+        missing.len(),
+        total,
+        region.name
+    );
+
+    let tile_url: Arc<str> = Arc::from(tile_url);
+    let mut failed = 0;
+    for batch in missing.chunks(options.max_concurrent.max(1)) {
+        let fetches: Vec<_> = batch
+            .iter()
+            .map(|&tile| {
+                let tile_url = tile_url.clone();
+                std::thread::spawn(move || (tile, fetch_tile(&tile_url, tile)))
+            })
+            .collect();
+        let mut tiles = vec![];
+        for fetch in fetches {
+            match fetch.join() {
+                Ok((tile, Ok(data))) => tiles.push((tile, data)),
+                Ok((tile, Err(e))) => {
+                    log::debug!("Unable to Download Tile {:?}: {}", tile, e);
+                    failed += 1;
+                }
+                Err(_) => failed += 1,
+            }
+        }
+        downloaded += tiles.len() as u64;
+        block_on(store_tiles(&mut connection, &region.name, tiles)).map_err(failed_query)?;
+
+        let payload = RegionProgressPayload {
+            name: region.name.clone(),
+            downloaded,
+            failed,
+            total,
+        };
+        let _ = app_handle.emit_all("region-download-progress", payload);
+    }
+
+    if failed == 0 {
+        block_on(
+            sqlx::query("UPDATE regions SET complete = 1 WHERE name = $1")
+                .bind(&region.name)
+                .execute(&mut connection),
+        )
+        .map_err(failed_query)?;
+    }
+    Ok(failed)
+}
+
+/// The options and running downloads of the regions.
+#[derive(Debug, Default)]
+pub struct RegionState {
+    options: Mutex<RegionDownloadOptions>,
+    /// Held while the archive is being written to.
+    lock: Mutex<()>,
+}
+
+/// Set the tile source, the number of tiles requested at the same time, and the largest number
+/// of tiles of a region.
+#[tauri::command]
+pub fn set_region_download_options(
+    state: tauri::State<RegionState>,
+    options: RegionDownloadOptions,
+) -> Result<(), AppError> {
+    if options.max_concurrent == 0 {
+        return Err(AppError::new(
+            codes::REGION_INVALID_OPTIONS,
+            "Invalid Download Options: Concurrent Requests must be greater than 0",
+        ));
+    }
+    log::debug!("Setting Region Download Options: {:?}", options);
+    *state.options.lock().unwrap() = options;
+    Ok(())
+}
+
+/// Count the tiles of a region before downloading it.
+#[tauri::command]
+pub fn estimate_region_tiles(
+    bbox: [f64; 4],
+    min_zoom: u32,
+    max_zoom: u32,
+) -> Result<u64, AppError> {
+    Ok(Region::new(String::from("estimate"), bbox, min_zoom, max_zoom)?.count_tiles())
+}
+
+/// Download the tiles of a region from the tile source, emitting `region-download-progress`
+/// events.
+///
+/// Downloading a region again resumes it, skipping the tiles already downloaded. Regions with
+/// more tiles than the limit are refused. Returns the number of tiles that couldn't be
+/// downloaded.
+#[tauri::command]
+pub async fn download_region(
+    app_handle: AppHandle,
+    name: String,
+    bbox: [f64; 4],
+    min_zoom: u32,
+    max_zoom: u32,
+) -> Result<u64, AppError> {
+    let region = Region::new(name, bbox, min_zoom, max_zoom)?;
+    let options = app_handle
+        .state::<RegionState>()
+        .options
+        .lock()
+        .unwrap()
+        .clone();
+    let tile_url = options.tile_url.clone().ok_or_else(|| {
+        AppError::new(
+            codes::REGION_NO_TILE_SOURCE,
+            "Unable to Download Region: No Tile Source Set",
+        )
+    })?;
+    let total = region.count_tiles();
+    if total > options.max_tiles {
+        return Err(AppError::new(
+            codes::REGION_TOO_MANY_TILES,
+            format!(
+                "Unable to Download Region: {total} Tiles is more than the limit of {}",
+                options.max_tiles
+            ),
+        )
+        .with("tiles", total)
+        .with("max", options.max_tiles));
+    }
+
+    tauri::async_runtime::spawn_blocking(move || {
+        let state = app_handle.state::<RegionState>();
+        let _lock = state.lock.try_lock().map_err(|_| {
+            AppError::new(
+                codes::REGION_BUSY,
+                "Unable to Download Region: A Region is Being Downloaded",
+            )
+        })?;
+        let failed = download(&app_handle, &region, &options, &tile_url)?;
+        log::info!(
+            "Downloaded Region {} with {} Tiles Failed",
+            region.name,
+            failed
+        );
+        Ok(failed)
+    })
+    .await
+    .map_err(|e| AppError::from(e.to_string()))?
+}
+
+/// List the regions downloaded.
+#[tauri::command]
+pub async fn list_regions(app_handle: AppHandle) -> Result<Vec<RegionInfo>, AppError> {
+    let path = map_dir(&app_handle)?.join(ARCHIVE_NAME);
+    if !path.exists() {
+        return Ok(vec![]);
+    }
+    let mut connection = open_archive(&path)
+        .await
+        .map_err(|e| archive_error(&path, e))?;
+    let regions: Vec<RegionRow> = sqlx::query_as(
+        "SELECT r.name, r.min_lng, r.min_lat, r.max_lng, r.max_lat, r.min_zoom, r.max_zoom, r.complete,
+            COUNT(rt.region), COALESCE(SUM(LENGTH(t.tile_data)), 0)
+        FROM regions r
+        LEFT JOIN region_tiles rt ON rt.region = r.name
+        LEFT JOIN tiles t ON t.zoom_level = rt.zoom_level AND t.tile_column = rt.tile_column AND t.tile_row = rt.tile_row
+        GROUP BY r.name ORDER BY r.name",
+    )
+    .fetch_all(&mut connection)
+    .await
+    .map_err(|e| archive_error(&path, e))?;
+    Ok(regions
+        .into_iter()
+        .map(
+            |(
+                name,
+                min_lng,
+                min_lat,
+                max_lng,
+                max_lat,
+                min_zoom,
+                max_zoom,
+                complete,
+                tiles,
+                bytes,
+            )| {
+                RegionInfo {
+                    name,
+                    bbox: [min_lng, min_lat, max_lng, max_lat],
+                    min_zoom,
+                    max_zoom,
+                    complete,
+                    tiles: tiles as u64,
+                    bytes: bytes as u64,
+                }
+            },
+        )
+        .collect())
+}
+
+/// Delete a region, removing the tiles not used by the other regions.
+#[tauri::command]
+pub async fn delete_region(app_handle: AppHandle, name: String) -> Result<(), AppError> {
+    log::debug!("Deleting Region: {}", name);
+    let path = map_dir(&app_handle)?.join(ARCHIVE_NAME);
+    if !path.exists() {
+        return Err(not_found(&name));
+    }
+    let failed_query = |e: sqlx::Error| archive_error(&path, e);
+    let mut connection = open_archive(&path).await.map_err(failed_query)?;
+    let mut transaction = connection.begin().await.map_err(failed_query)?;
+    let deleted = sqlx::query("DELETE FROM regions WHERE name = $1")
+        .bind(&name)
+        .execute(&mut *transaction)
+        .await
+        .map_err(failed_query)?;
+    if deleted.rows_affected() == 0 {
+        return Err(not_found(&name));
+    }
+    sqlx::query("DELETE FROM region_tiles WHERE region = $1")
+        .bind(&name)
+        .execute(&mut *transaction)
+        .await
+        .map_err(failed_query)?;
+    sqlx::query(
+        "DELETE FROM tiles WHERE NOT EXISTS (
+            SELECT 1 FROM region_tiles rt WHERE rt.zoom_level = tiles.zoom_level
+                AND rt.tile_column = tiles.tile_column AND rt.tile_row = tiles.tile_row
+        )",
+    )
+    .execute(&mut *transaction)
+    .await
+    .map_err(failed_query)?;
+    transaction.commit().await.map_err(failed_query)?;
+
+    // Reclaiming the space of the tiles removed
+    sqlx::query("VACUUM")
+        .execute(&mut connection)
+        .await
+        .map_err(failed_query)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use crate::storage::tests::temp_dir;
+
+    use super::*;
+
+    /// A region around Kuala Lumpur.
+    fn region(name: &str, min_zoom: u32, max_zoom: u32) -> Region {
+        Region::new(
+            name.to_string(),
+            [101.6, 3.1, 101.8, 3.2],
+            min_zoom,
+            max_zoom,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn tile_at_coordinate_matches_the_xyz_scheme() {
+        assert_eq!(tile_at(0.0, 0.0, 0), (0, 0));
+        assert_eq!(tile_at(-180.0, 85.0, 1), (0, 0));
+        assert_eq!(tile_at(180.0, -85.0, 1), (1, 1));
+        assert_eq!(tile_at(101.7, 3.15, 14), (12820, 8048));
+        // Latitudes beyond the projection are clamped to the edge tiles
+        assert_eq!(tile_at(0.0, 90.0, 2).1, 0);
+        assert_eq!(tile_at(0.0, -90.0, 2).1, 3);
+    }
+
+    #[test]
+    fn tile_rows_are_stored_in_tms() {
+        assert_eq!(Tile { z: 0, x: 0, y: 0 }.row(), 0);
+        assert_eq!(Tile { z: 2, x: 1, y: 0 }.row(), 3);
+        assert_eq!(
+            Tile {
+                z: 14,
+                x: 12820,
+                y: 8048
+            }
+            .row(),
+            8335
+        );
+    }
+
+    #[test]
+    fn invalid_region_is_rejected() {
+        let bbox = [101.6, 3.1, 101.8, 3.2];
+
+        assert!(Region::new("lake".to_string(), bbox, 10, 14).is_ok());
+        assert!(Region::new("../lake".to_string(), bbox, 10, 14).is_err());
+        assert!(Region::new("lake".to_string(), [101.8, 3.1, 101.6, 3.2], 10, 14).is_err());
+        assert!(Region::new("lake".to_string(), [101.6, 3.2, 101.8, 3.1], 10, 14).is_err());
+        assert!(Region::new("lake".to_string(), [181.0, 3.1, 182.0, 3.2], 10, 14).is_err());
+        assert!(Region::new("lake".to_string(), [101.6, -91.0, 101.8, 3.2], 10, 14).is_err());
+        assert!(Region::new("lake".to_string(), bbox, 14, 10).is_err());
+        assert!(Region::new("lake".to_string(), bbox, 10, MAX_ZOOM + 1).is_err());
+    }
+
+    #[test]
+    fn tile_count_matches_the_tiles() {
+        let region = region("lake", 0, 14);
+
+        assert_eq!(region.count_tiles(), region.tiles().count() as u64);
+        assert_eq!(
+            estimate_region_tiles(region.bbox, 0, 14).unwrap(),
+            region.count_tiles()
+        );
+    }
+
+    #[test]
+    fn world_at_high_zoom_is_counted_without_overflow() {
+        let world = Region::new(
+            "world".to_string(),
+            [-180.0, -90.0, 180.0, 90.0],
+            0,
+            MAX_ZOOM,
+        )
+        .unwrap();
+
+        // The sum of 4^z for z in 0..=20
+        assert_eq!(world.count_tiles(), ((1u64 << 42) - 1) / 3);
+        assert!(world.count_tiles() > DEFAULT_MAX_TILES);
+    }
+
+    #[test]
+    fn tiles_are_shared_between_overlapping_regions() {
+        let dir = temp_dir("regions-shared");
+        block_on(async {
+            let mut connection = open_archive(&dir.join(ARCHIVE_NAME)).await.unwrap();
+            let first = region("first", 12, 12);
+            let second = region("second", 12, 12);
+            save_region(&mut connection, &first).await.unwrap();
+            save_region(&mut connection, &second).await.unwrap();
+
+            let tiles: Vec<Tile> = first.tiles().collect();
+            assert!(!link_tile(&mut connection, "first", tiles[0]).await.unwrap());
+            let downloaded = tiles
+                .iter()
+                .map(|&tile| (tile, Some(vec![1, 2, 3])))
+                .collect();
+            store_tiles(&mut connection, "first", downloaded)
+                .await
+                .unwrap();
+
+            // Resuming the first region finds its tiles
+            assert!(link_tile(&mut connection, "first", tiles[0]).await.unwrap());
+            // The second region uses the tiles of the first without downloading them
+            for &tile in &tiles {
+                assert!(link_tile(&mut connection, "second", tile).await.unwrap());
+            }
+            let (stored, linked): (i64, i64) = sqlx::query_as(
+                "SELECT (SELECT COUNT(*) FROM tiles), (SELECT COUNT(*) FROM region_tiles)",
+            )
+            .fetch_one(&mut connection)
+            .await
+            .unwrap();
+            assert_eq!(stored as usize, tiles.len());
+            assert_eq!(linked as usize, 2 * tiles.len());
+        });
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn missing_tiles_are_recorded_without_data() {
+        let dir = temp_dir("regions-missing");
+        block_on(async {
+            let mut connection = open_archive(&dir.join(ARCHIVE_NAME)).await.unwrap();
+            let tile = Tile { z: 1, x: 0, y: 0 };
+            store_tiles(&mut connection, "lake", vec![(tile, None)])
+                .await
+                .unwrap();
+
+            let (stored, linked): (i64, i64) = sqlx::query_as(
+                "SELECT (SELECT COUNT(*) FROM tiles), (SELECT COUNT(*) FROM region_tiles)",
+            )
+            .fetch_one(&mut connection)
+            .await
+            .unwrap();
+            assert_eq!((stored, linked), (0, 1));
+            assert!(link_tile(&mut connection, "lake", tile).await.unwrap());
+        });
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,891 @@
+//! PDF reports of missions with a summary of the readings, a map of the readings and the route,
+//! and charts of the temperature over time.
+
+use std::{f64::consts::PI, path::PathBuf};
+
+use chrono::{DateTime, Utc};
+use printpdf::{
+    image_crate::{self, imageops, DynamicImage, Rgba, RgbaImage},
+    path::{PaintMode, WindingOrder},
+    BuiltinFont, Color, Image, ImageTransform, IndirectFontRef, Line, Mm, PdfDocument,
+    PdfDocumentReference, PdfLayerReference, Point as PdfPoint, Polygon, Rgb,
+};
+use serde::Deserialize;
+use sqlx::{sqlite::SqliteConnectOptions, Connection, SqliteConnection};
+use tauri::{async_runtime::block_on, AppHandle};
+
+use crate::{
+    data::{BoatData, BoatDataFeature, Layer},
+    errors::{export_error, AppError},
+    geometry::segment_lengths,
+    map_assets::map_dir,
+    path::PathData,
+    regions::{ARCHIVE_NAME, MAX_LATITUDE},
+    storage::write_atomically,
+};
+
+/// The width of an A4 page in millimeters.
+const PAGE_WIDTH: f32 = 210.0;
+
+/// The height of an A4 page in millimeters.
+const PAGE_HEIGHT: f32 = 297.0;
+
+/// The margin around the content of a page in millimeters.
+const MARGIN: f32 = 20.0;
+
+/// The width of the content of a page in millimeters.
+const CONTENT_WIDTH: f32 = PAGE_WIDTH - 2.0 * MARGIN;
+
+/// The height of the map in millimeters.
+const MAP_HEIGHT: f32 = 120.0;
+
+/// The height of a temperature chart in millimeters.
+const CHART_HEIGHT: f32 = 55.0;
+
+/// The resolution the base map is composed at in pixels per millimeter.
+const MAP_PIXELS_PER_MM: f64 = 4.0;
+
+/// The size of map tiles in pixels.
+const TILE_SIZE: u32 = 256;
+
+/// The largest zoom level of the tiles used for the base map.
+const MAX_TILE_ZOOM: u32 = 18;
+
+/// The format of times in the report.
+const TIME_FORMAT: &str = "%Y-%m-%d %H:%M UTC";
+
+/// Options of a mission report.
+#[derive(Debug, Deserialize, Clone)]
+#[serde(default)]
+pub struct ReportOptions {
+    /// The title at the top of the report.
+    title: String,
+    /// Whether the summary table is included.
+    summary: bool,
+    /// Whether the map of the readings and the route is included.
+    map: bool,
+    /// Whether the temperature charts are included.
+    charts: bool,
+}
+
+impl Default for ReportOptions {
+    fn default() -> Self {
+        Self {
+            title: String::from("Mission Report"),
+            summary: true,
+            map: true,
+            charts: true,
+        }
+    }
+}
+
+/// Temperature statistics of a layer.
+struct LayerSummary {
+    count: usize,
+    min: f64,
+    mean: f64,
+    max: f64,
+}
+
+impl LayerSummary {
+    /// Computes the statistics of the finite temperatures measured at a layer.
+    fn new(data: &BoatData, layer: &Layer) -> Option<Self> {
+        let temperatures: Vec<f64> = data
+            .features()
+            .iter()
+            .filter(|f| f.layer() == *layer && f.temperature().is_finite())
+            .map(|f| f.temperature())
+            .collect();
+        if temperatures.is_empty() {
+            return None;
+        }
+        Some(Self {
+            count: temperatures.len(),
+            min: temperatures.iter().copied().fold(f64::INFINITY, f64::min),
+            mean: temperatures.iter().sum::<f64>() / temperatures.len() as f64,
+            max: temperatures
+                .iter()
+                .copied()
+                .fold(f64::NEG_INFINITY, f64::max),
+        })
+    }
+}
+
+/// Gets the color of a temperature between the lowest and highest temperature, from blue to
+/// red.
+fn temperature_color(temperature: f64, min: f64, max: f64) -> Color {
+    let t = if max > min {
+        ((temperature - min) / (max - min)).clamp(0.0, 1.0) as f32
+    } else {
+        0.5
+    };
+    Color::Rgb(Rgb::new(
+        0.13 + 0.57 * t,
+        0.4 - 0.3 * t,
+        0.67 - 0.57 * t,
+        None,
+    ))
+}
+
+/// Gets the color of the series of a layer in the charts.
+fn layer_color(layer: &Layer) -> Color {
+    match layer {
+        Layer::Surface => Color::Rgb(Rgb::new(0.2, 0.6, 0.86, None)),
+        Layer::Middle => Color::Rgb(Rgb::new(0.16, 0.5, 0.73, None)),
+        Layer::SeaBed => Color::Rgb(Rgb::new(0.1, 0.2, 0.4, None)),
+        Layer::Other(_) => gray(0.5),
+    }
+}
+
+/// Gets a gray color.
+fn gray(value: f32) -> Color {
+    Color::Rgb(Rgb::new(value, value, value, None))
+}
+
+/// Converts a coordinate to Web Mercator world coordinates between 0 and 1, with y increasing
+/// to the south.
+fn world(lng: f64, lat: f64) -> (f64, f64) {
+    let lat = lat.clamp(-MAX_LATITUDE, MAX_LATITUDE).to_radians();
+    (
+        (lng + 180.0) / 360.0,
+        (1.0 - (lat.tan() + 1.0 / lat.cos()).ln() / PI) / 2.0,
+    )
+}
+
+/// Converts Web Mercator world coordinates back to a longitude and latitude.
+fn unworld(x: f64, y: f64) -> (f64, f64) {
+    let lat = (PI * (1.0 - 2.0 * y)).sinh().atan().to_degrees();
+    (x * 360.0 - 180.0, lat)
+}
+
+/// The area of the world shown on the map.
+struct MapView {
+    /// The world coordinates of the top left corner.
+    min: (f64, f64),
+    /// The size of the area in world coordinates.
+    span: (f64, f64),
+}
+
+impl MapView {
+    /// Fits the view around world coordinates, keeping the aspect ratio of the map.
+    ///
+    /// Returns `None` if there are no coordinates.
+    fn fit(coords: &[(f64, f64)]) -> Option<Self> {
+        let first = coords.first()?;
+        let (mut min_x, mut min_y, mut max_x, mut max_y) = (first.0, first.1, first.0, first.1);
+        for (x, y) in coords {
+            min_x = min_x.min(*x);
+            min_y = min_y.min(*y);
+            max_x = max_x.max(*x);
+            max_y = max_y.max(*y);
+        }
+
+        // A single reading is shown in an area of about 100m
+        let aspect = f64::from(CONTENT_WIDTH / MAP_HEIGHT);
+        let mut span_x = ((max_x - min_x) * 1.1).max(2.5e-6);
+        let mut span_y = ((max_y - min_y) * 1.1).max(2.5e-6);
+        if span_x / span_y > aspect {
+            span_y = span_x / aspect;
+        } else {
+            span_x = span_y * aspect;
+        }
+        let center = ((min_x + max_x) / 2.0, (min_y + max_y) / 2.0);
+        Some(Self {
+            min: (center.0 - span_x / 2.0, center.1 - span_y / 2.0),
+            span: (span_x, span_y),
+        })
+    }
+
+    /// Converts world coordinates to a position on the map relative to its top left corner in
+    /// millimeters.
+    fn position(&self, (x, y): (f64, f64)) -> (f32, f32) {
+        (
+            ((x - self.min.0) / self.span.0 * f64::from(CONTENT_WIDTH)) as f32,
+            ((y - self.min.1) / self.span.1 * f64::from(MAP_HEIGHT)) as f32,
+        )
+    }
+}
+
+/// Reads a tile from the offline archive, returning `None` if it isn't stored.
+async fn read_tile(
+    connection: &mut SqliteConnection,
+    z: u32,
+    x: u32,
+    y: u32,
+) -> Result<Option<Vec<u8>>, sqlx::Error> {
+    // The archive uses the TMS scheme
+    let row = (1 << z) - 1 - y;
+    let tile: Option<(Vec<u8>,)> = sqlx::query_as(
+        "SELECT tile_data FROM tiles WHERE zoom_level = $1 AND tile_column = $2 AND tile_row = $3",
+    )
+    .bind(z)
+    .bind(x)
+    .bind(row)
+    .fetch_optional(connection)
+    .await?;
+    Ok(tile.map(|(data,)| data))
+}
+
+/// Composes the base map of a view from the raster tiles in the offline archive.
+///
+/// Returns `None` if the archive has no raster tiles of the view, e.g. when it only has vector
+/// tiles, which can't be drawn without the map style.
+async fn compose_base_map(
+    app_handle: &AppHandle,
+    view: &MapView,
+) -> Result<Option<DynamicImage>, String> {
+    let archive = map_dir(app_handle)?.join(ARCHIVE_NAME);
+    if !archive.exists() {
+        return Ok(None);
+    }
+    let options = SqliteConnectOptions::new()
+        .filename(&archive)
+        .read_only(true);
+    let mut connection = SqliteConnection::connect_with(&options)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let pixels_per_world = f64::from(CONTENT_WIDTH) * MAP_PIXELS_PER_MM / view.span.0;
+    let z = (pixels_per_world / f64::from(TILE_SIZE))
+        .log2()
+        .ceil()
+        .clamp(0.0, f64::from(MAX_TILE_ZOOM)) as u32;
+    let tiles = f64::from(1u32 << z);
+    let world_pixels = tiles * f64::from(TILE_SIZE);
+
+    // The view in the pixels of the zoom level
+    let left = view.min.0 * world_pixels;
+    let top = view.min.1 * world_pixels;
+    let width = (view.span.0 * world_pixels).ceil().max(1.0) as u32;
+    let height = (view.span.1 * world_pixels).ceil().max(1.0) as u32;
+    let mut canvas = RgbaImage::from_pixel(width, height, Rgba([230, 230, 230, 255]));
+
+    let first_x = (left / f64::from(TILE_SIZE)).floor().max(0.0) as u32;
+    let first_y = (top / f64::from(TILE_SIZE)).floor().max(0.0) as u32;
+    let last_x = (((left + f64::from(width)) / f64::from(TILE_SIZE)).floor()).min(tiles - 1.0);
+    let last_y = (((top + f64::from(height)) / f64::from(TILE_SIZE)).floor()).min(tiles - 1.0);
+    let mut drawn = 0;
+    for x in first_x..=last_x as u32 {
+        for y in first_y..=last_y as u32 {
+            let data = match read_tile(&mut connection, z, x, y).await {
+                Ok(Some(v)) => v,
+                Ok(None) => continue,
+                Err(e) => return Err(e.to_string()),
+            };
+            let tile = match image_crate::load_from_memory(&data) {
+                Ok(v) => v,
+                // Vector tiles aren't images
+                Err(_) => continue,
+            };
+            imageops::overlay(
+                &mut canvas,
+                &tile.to_rgba8(),
+                (f64::from(x * TILE_SIZE) - left).round() as i64,
+                (f64::from(y * TILE_SIZE) - top).round() as i64,
+            );
+            drawn += 1;
+        }
+    }
+    let _ = connection.close().await;
+
+    if drawn == 0 {
+        return Ok(None);
+    }
+    Ok(Some(DynamicImage::ImageRgb8(
+        DynamicImage::ImageRgba8(canvas).to_rgb8(),
+    )))
+}
+
+/// The report being written.
+struct Report {
+    document: PdfDocumentReference,
+    layer: PdfLayerReference,
+    font: IndirectFontRef,
+    bold: IndirectFontRef,
+    /// The distance from the top of the page to the next content in millimeters.
+    top: f32,
+}
+
+impl Report {
+    /// Creates a report with its first page.
+    fn new(title: &str) -> Result<Self, String> {
+        let (document, page, layer) =
+            PdfDocument::new(title, Mm(PAGE_WIDTH), Mm(PAGE_HEIGHT), "Content");
+        let font = document
+            .add_builtin_font(BuiltinFont::Helvetica)
+            .map_err(|e| e.to_string())?;
+        let bold = document
+            .add_builtin_font(BuiltinFont::HelveticaBold)
+            .map_err(|e| e.to_string())?;
+        let layer = document.get_page(page).get_layer(layer);
+        Ok(Self {
+            document,
+            layer,
+            font,
+            bold,
+            top: MARGIN,
+        })
+    }
+
+    /// Starts a new page if there isn't `height` millimeters left on the current page.
+    fn reserve(&mut self, height: f32) {
+        if self.top + height <= PAGE_HEIGHT - MARGIN {
+            return;
+        }
+        let (page, layer) = self
+            .document
+            .add_page(Mm(PAGE_WIDTH), Mm(PAGE_HEIGHT), "Content");
+        self.layer = self.document.get_page(page).get_layer(layer);
+        self.top = MARGIN;
+    }
+
+    /// Converts a position from the top left of the page to a PDF point.
+    fn point(x: f32, top: f32) -> (PdfPoint, bool) {
+        (PdfPoint::new(Mm(x), Mm(PAGE_HEIGHT - top)), false)
+    }
+
+    /// Writes text with its baseline at `top`.
+    fn text(&self, text: &str, size: f32, x: f32, top: f32, bold: bool) {
+        self.layer.set_fill_color(gray(0.0));
+        let font = if bold { &self.bold } else { &self.font };
+        self.layer
+            .use_text(text, size, Mm(x), Mm(PAGE_HEIGHT - top), font);
+    }
+
+    /// Draws a line through positions from the top left of the page.
+    fn line(&self, points: &[(f32, f32)], color: Color, thickness: f32) {
+        self.layer.set_outline_color(color);
+        self.layer.set_outline_thickness(thickness);
+        self.layer.add_line(Line {
+            points: points
+                .iter()
+                .map(|(x, top)| Self::point(*x, *top))
+                .collect(),
+            is_closed: false,
+        });
+    }
+
+    /// Draws the outline of a rectangle.
+    fn frame(&self, x: f32, top: f32, width: f32, height: f32) {
+        self.line(
+            &[
+                (x, top),
+                (x + width, top),
+                (x + width, top + height),
+                (x, top + height),
+                (x, top),
+            ],
+            gray(0.4),
+            0.5,
+        );
+    }
+
+    /// Draws a filled square centered at a position, used as the marker of a reading.
+    fn marker(&self, x: f32, top: f32, size: f32, color: Color) {
+        let half = size / 2.0;
+        self.layer.set_fill_color(color);
+        self.layer.add_polygon(Polygon {
+            rings: vec![vec![
+                Self::point(x - half, top - half),
+                Self::point(x + half, top - half),
+                Self::point(x + half, top + half),
+                Self::point(x - half, top + half),
+            ]],
+            mode: PaintMode::Fill,
+            winding_order: WindingOrder::NonZero,
+        });
+    }
+
+    /// Writes a heading and moves below it.
+    fn heading(&mut self, text: &str) {
+        self.reserve(12.0);
+        self.top += 8.0;
+        self.text(text, 14.0, MARGIN, self.top, true);
+        self.top += 4.0;
+    }
+
+    /// Writes the summary table of the readings and the mission.
+    fn summary(&mut self, data: &BoatData, mission: Option<&PathData>) {
+        self.heading("Summary");
+        let times = data.features().iter().map(|f| f.time());
+        let date_range = match (times.clone().min(), times.max()) {
+            (Some(first), Some(last)) => format!(
+                "{} to {}",
+                first.format(TIME_FORMAT),
+                last.format(TIME_FORMAT)
+            ),
+            _ => String::from("No Readings"),
+        };
+        let mut rows = vec![
+            (String::from("Date Range"), date_range),
+            (String::from("Readings"), data.features().len().to_string()),
+        ];
+        rows.extend(
+            data.metadata()
+                .rows()
+                .into_iter()
+                .map(|(name, value)| (String::from(name), value.replace('\n', " "))),
+        );
+        if let Some(mission) = mission {
+            let length: f64 = segment_lengths(mission).iter().sum();
+            rows.push((
+                String::from("Waypoints"),
+                mission.path().0.len().to_string(),
+            ));
+            rows.push((
+                String::from("Route Length"),
+                format!("{:.2} km", length / 1000.0),
+            ));
+        }
+        for (name, value) in rows {
+            self.reserve(7.0);
+            self.top += 6.0;
+            self.text(&name, 10.0, MARGIN, self.top, true);
+            self.text(&value, 10.0, MARGIN + 40.0, self.top, false);
+        }
+
+        // The temperature statistics of each layer
+        let columns = [0.0, 40.0, 70.0, 100.0, 130.0];
+        self.reserve(16.0);
+        self.top += 10.0;
+        let header = ["Layer", "Readings", "Min (C)", "Mean (C)", "Max (C)"];
+        for (x, name) in columns.iter().zip(header) {
+            self.text(name, 10.0, MARGIN + x, self.top, true);
+        }
+        self.line(
+            &[
+                (MARGIN, self.top + 1.5),
+                (MARGIN + CONTENT_WIDTH, self.top + 1.5),
+            ],
+            gray(0.4),
+            0.5,
+        );
+        for layer in data.layers() {
+            let summary = match LayerSummary::new(data, &layer) {
+                Some(v) => v,
+                None => continue,
+            };
+            self.reserve(7.0);
+            self.top += 6.0;
+            let values = [
+                layer.to_string(),
+                summary.count.to_string(),
+                format!("{:.2}", summary.min),
+                format!("{:.2}", summary.mean),
+                format!("{:.2}", summary.max),
+            ];
+            for (x, value) in columns.iter().zip(values) {
+                self.text(&value, 10.0, MARGIN + x, self.top, false);
+            }
+        }
+    }
+
+    /// Draws the map of the readings and the route, over the base map if there is one.
+    fn map(&mut self, app_handle: &AppHandle, data: &BoatData, mission: Option<&PathData>) {
+        self.heading("Map");
+        let readings: Vec<&BoatDataFeature> = data
+            .features()
+            .iter()
+            .filter(|f| f.geometry().x().is_finite() && f.geometry().y().is_finite())
+            .collect();
+        let route: Vec<(f64, f64)> = mission
+            .map(|m| m.path().0.iter().map(|c| world(c.x, c.y)).collect())
+            .unwrap_or_default();
+        let mut coords: Vec<(f64, f64)> = readings
+            .iter()
+            .map(|f| world(f.geometry().x(), f.geometry().y()))
+            .collect();
+        coords.extend(route.iter().copied());
+        let view = match MapView::fit(&coords) {
+            Some(v) => v,
+            None => {
+                self.reserve(8.0);
+                self.top += 6.0;
+                self.text("No Coordinates to Map", 10.0, MARGIN, self.top, false);
+                return;
+            }
+        };
+
+        self.reserve(MAP_HEIGHT + 10.0);
+        self.top += 2.0;
+        let (x0, top0) = (MARGIN, self.top);
+        match block_on(compose_base_map(app_handle, &view)) {
+            Ok(Some(base_map)) => {
+                // The DPI is chosen so the image fills the width of the map
+                let dpi = base_map.width() as f32 * 25.4 / CONTENT_WIDTH;
+                Image::from_dynamic_image(&base_map).add_to_layer(
+                    self.layer.clone(),
+                    ImageTransform {
+                        translate_x: Some(Mm(x0)),
+                        translate_y: Some(Mm(PAGE_HEIGHT - top0 - MAP_HEIGHT)),
+                        dpi: Some(dpi),
+                        ..Default::default()
+                    },
+                );
+            }
+            Ok(None) => log::info!("No Raster Tiles for Report Map, drawing readings only"),
+            Err(e) => log::warn!("Unable to Read Tiles for Report Map: {}", e),
+        }
+        self.frame(x0, top0, CONTENT_WIDTH, MAP_HEIGHT);
+
+        if route.len() > 1 {
+            let points: Vec<(f32, f32)> = route
+                .iter()
+                .map(|c| {
+                    let (x, y) = view.position(*c);
+                    (x0 + x, top0 + y)
+                })
+                .collect();
+            self.line(&points, gray(0.15), 0.8);
+        }
+
+        let temperatures = readings
+            .iter()
+            .map(|f| f.temperature())
+            .filter(|t| t.is_finite());
+        let min = temperatures.clone().fold(f64::INFINITY, f64::min);
+        let max = temperatures.fold(f64::NEG_INFINITY, f64::max);
+        for reading in readings {
+            let (x, y) = view.position(world(reading.geometry().x(), reading.geometry().y()));
+            self.marker(
+                x0 + x,
+                top0 + y,
+                1.2,
+                temperature_color(reading.temperature(), min, max),
+            );
+        }
+
+        // The coordinates of the corners, as there may be no base map to locate the readings
+        let (west, north) = unworld(view.min.0, view.min.1);
+        let (east, south) = unworld(view.min.0 + view.span.0, view.min.1 + view.span.1);
+        self.top += MAP_HEIGHT + 4.0;
+        self.text(
+            &format!("{north:.5}, {west:.5}"),
+            7.0,
+            x0,
+            top0 - 1.0,
+            false,
+        );
+        self.text(
+            &format!("{south:.5}, {east:.5}"),
+            7.0,
+            x0 + CONTENT_WIDTH - 30.0,
+            self.top,
+            false,
+        );
+    }
+
+    /// Draws a chart of the temperature over time of the readings at a layer.
+    fn chart(&mut self, data: &BoatData, layer: &Layer) {
+        let mut readings: Vec<(DateTime<Utc>, f64)> = data
+            .features()
+            .iter()
+            .filter(|f| f.layer() == *layer && f.temperature().is_finite())
+            .map(|f| (f.time(), f.temperature()))
+            .collect();
+        if readings.is_empty() {
+            return;
+        }
+        readings.sort_by_key(|(time, _)| *time);
+
+        self.reserve(CHART_HEIGHT + 16.0);
+        self.top += 8.0;
+        self.text(
+            &format!("Temperature at {layer}"),
+            11.0,
+            MARGIN,
+            self.top,
+            true,
+        );
+        self.top += 3.0;
+
+        // The values are drawn right of the temperature labels
+        let (x0, top0) = (MARGIN + 14.0, self.top);
+        let width = CONTENT_WIDTH - 14.0;
+        let first = readings[0].0;
+        let last = readings[readings.len() - 1].0;
+        let duration = (last - first).num_milliseconds().max(1) as f64;
+        let min = readings
+            .iter()
+            .map(|(_, t)| *t)
+            .fold(f64::INFINITY, f64::min);
+        let max = readings
+            .iter()
+            .map(|(_, t)| *t)
+            .fold(f64::NEG_INFINITY, f64::max);
+        let (min, max) = if max > min {
+            (min, max)
+        } else {
+            (min - 0.5, max + 0.5)
+        };
+
+        self.frame(x0, top0, width, CHART_HEIGHT);
+        let points: Vec<(f32, f32)> = readings
+            .iter()
+            .map(|(time, temperature)| {
+                let x = (*time - first).num_milliseconds() as f64 / duration;
+                let y = (max - temperature) / (max - min);
+                (
+                    x0 + (x * f64::from(width)) as f32,
+                    top0 + (y * f64::from(CHART_HEIGHT)) as f32,
+                )
+            })
+            .collect();
+        if points.len() > 1 {
+            self.line(&points, layer_color(layer), 0.6);
+        } else {
+            self.marker(points[0].0, points[0].1, 1.5, layer_color(layer));
+        }
+
+        self.text(&format!("{max:.1} C"), 7.0, MARGIN, top0 + 3.0, false);
+        self.text(
+            &format!("{min:.1} C"),
+            7.0,
+            MARGIN,
+            top0 + CHART_HEIGHT,
+            false,
+        );
+        self.top += CHART_HEIGHT + 4.0;
+        self.text(
+            &first.format(TIME_FORMAT).to_string(),
+            7.0,
+            x0,
+            self.top,
+            false,
+        );
+        self.text(
+            &last.format(TIME_FORMAT).to_string(),
+            7.0,
+            x0 + width - 28.0,
+            self.top,
+            false,
+        );
+    }
+}
+
+/// Generate a PDF report of a mission.
+///
+/// The report has a summary table of the readings, a map of the readings and the route, and a
+/// chart of the temperature over time for each layer, each of which can be left out with
+/// `options`. The map is drawn over the raster tiles of the offline regions when there are any.
+#[tauri::command]
+pub async fn generate_report(
+    app_handle: AppHandle,
+    path: PathBuf,
+    data: BoatData,
+    mission: Option<PathData>,
+    options: Option<ReportOptions>,
+) -> Result<(), AppError> {
+    log::debug!("Generating Report: {}", path.display());
+    let options = options.unwrap_or_default();
+
+    tauri::async_runtime::spawn_blocking(move || {
+        let mut report = Report::new(&options.title).map_err(|e| export_error(&path, e))?;
+        report.top += 6.0;
+        report.text(&options.title, 20.0, MARGIN, report.top, true);
+        report.top += 6.0;
+        report.text(
+            &format!("Generated {}", Utc::now().format(TIME_FORMAT)),
+            9.0,
+            MARGIN,
+            report.top,
+            false,
+        );
+
+        if options.summary {
+            report.summary(&data, mission.as_ref());
+        }
+        if options.map {
+            report.map(&app_handle, &data, mission.as_ref());
+        }
+        if options.charts {
+            report.heading("Temperature over Time");
+            for layer in data.layers() {
+                report.chart(&data, &layer);
+            }
+        }
+
+        let content = report
+            .document
+            .save_to_bytes()
+            .map_err(|e| export_error(&path, e))?;
+        write_atomically(&path, |file| file.write_all(&content))?;
+        log::info!("Generated Report: {}", path.display());
+        Ok(())
+    })
+    .await
+    .map_err(|e| AppError::from(e.to_string()))?
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+    use geo_types::{LineString, MultiPoint, Point};
+    use sqlx::{Connection, Executor};
+
+    use super::*;
+    use crate::{data::BoatDataFeature, storage::tests::temp_dir};
+
+    /// Boat data with readings at the surface and the sea bed, including a temperature that
+    /// isn't a number.
+    fn data() -> BoatData {
+        let time = |minutes: i64| Utc.timestamp_opt(1_715_495_400 + 60 * minutes, 0).unwrap();
+        let feature = |temperature, layer, minutes, lng| {
+            BoatDataFeature::new(temperature, 0.5, layer, time(minutes), Point::new(lng, 3.1))
+        };
+        BoatData::new(
+            BoatData::default().version().to_string(),
+            vec![
+                feature(28.0, Layer::Surface, 0, 101.50),
+                feature(30.0, Layer::Surface, 1, 101.51),
+                feature(f64::NAN, Layer::Surface, 2, 101.52),
+                feature(25.5, Layer::SeaBed, 3, 101.53),
+            ],
+        )
+    }
+
+    #[test]
+    fn layer_summary_skips_temperatures_that_are_not_numbers() {
+        let data = data();
+
+        let surface = LayerSummary::new(&data, &Layer::Surface).unwrap();
+        assert_eq!(
+            (surface.count, surface.min, surface.mean, surface.max),
+            (2, 28.0, 29.0, 30.0)
+        );
+        let sea_bed = LayerSummary::new(&data, &Layer::SeaBed).unwrap();
+        assert_eq!((sea_bed.count, sea_bed.mean), (1, 25.5));
+        assert!(LayerSummary::new(&data, &Layer::Middle).is_none());
+    }
+
+    #[test]
+    fn world_coordinates_round_trip() {
+        assert_eq!(world(0.0, 0.0), (0.5, 0.5));
+        let (x, y) = world(-180.0, MAX_LATITUDE);
+        assert!(x.abs() < 1e-12 && y.abs() < 1e-9, "{x} {y}");
+        // Latitudes beyond Web Mercator are clamped
+        assert_eq!(world(0.0, 90.0), world(0.0, MAX_LATITUDE));
+
+        for (lng, lat) in [(101.5, 3.1), (-70.25, -45.5), (179.0, 80.0)] {
+            let (x, y) = world(lng, lat);
+            let (lng2, lat2) = unworld(x, y);
+            assert!((lng - lng2).abs() < 1e-9 && (lat - lat2).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn map_view_fits_the_coordinates_in_the_map_aspect() {
+        let coords = [world(101.5, 3.1), world(101.6, 3.15), world(101.55, 3.0)];
+
+        let view = MapView::fit(&coords).unwrap();
+
+        let aspect = f64::from(CONTENT_WIDTH / MAP_HEIGHT);
+        assert!((view.span.0 / view.span.1 - aspect).abs() < 1e-9);
+        for coord in coords {
+            let (x, y) = view.position(coord);
+            assert!((0.0..=CONTENT_WIDTH).contains(&x), "{x}");
+            assert!((0.0..=MAP_HEIGHT).contains(&y), "{y}");
+        }
+        assert!(MapView::fit(&[]).is_none());
+    }
+
+    #[test]
+    fn single_reading_is_centered_on_the_map() {
+        let view = MapView::fit(&[world(101.5, 3.1)]).unwrap();
+
+        let (x, y) = view.position(world(101.5, 3.1));
+        assert!((x - CONTENT_WIDTH / 2.0).abs() < 1e-3);
+        assert!((y - MAP_HEIGHT / 2.0).abs() < 1e-3);
+        assert!(view.span.0 > 0.0 && view.span.1 > 0.0);
+    }
+
+    #[test]
+    fn temperature_colors_go_from_blue_to_red() {
+        let rgb = |color: Color| match color {
+            Color::Rgb(v) => (v.r, v.g, v.b),
+            _ => unreachable!(),
+        };
+        let (r, _, b) = rgb(temperature_color(20.0, 20.0, 30.0));
+        assert!(b > r);
+        let (r, _, b) = rgb(temperature_color(30.0, 20.0, 30.0));
+        assert!(r > b);
+        // Out of range temperatures are clamped and equal bounds use the middle color
+        assert_eq!(
+            rgb(temperature_color(40.0, 20.0, 30.0)),
+            rgb(temperature_color(30.0, 20.0, 30.0))
+        );
+        assert_eq!(
+            rgb(temperature_color(25.0, 25.0, 25.0)),
+            rgb(temperature_color(25.0, 20.0, 30.0))
+        );
+    }
+
+    #[test]
+    fn tiles_are_read_in_the_tms_scheme() {
+        let dir = temp_dir("report_tiles");
+        let path = dir.join("tiles.mbtiles");
+        tauri::async_runtime::block_on(async {
+            let options = SqliteConnectOptions::new()
+                .filename(&path)
+                .create_if_missing(true);
+            let mut connection = SqliteConnection::connect_with(&options).await.unwrap();
+            connection
+                .execute(
+                    "CREATE TABLE tiles (zoom_level INTEGER, tile_column INTEGER, tile_row INTEGER, tile_data BLOB);
+                    INSERT INTO tiles VALUES (2, 1, 0, x'01'), (2, 1, 3, x'02');",
+                )
+                .await
+                .unwrap();
+
+            assert_eq!(
+                read_tile(&mut connection, 2, 1, 3).await.unwrap(),
+                Some(vec![1])
+            );
+            assert_eq!(
+                read_tile(&mut connection, 2, 1, 0).await.unwrap(),
+                Some(vec![2])
+            );
+            assert_eq!(read_tile(&mut connection, 2, 2, 0).await.unwrap(), None);
+            connection.close().await.unwrap();
+        });
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn content_beyond_the_page_starts_a_new_page() {
+        let mut report = Report::new("Report").unwrap();
+
+        report.reserve(10.0);
+        assert_eq!(report.top, MARGIN);
+        report.top = PAGE_HEIGHT - MARGIN - 5.0;
+        report.reserve(10.0);
+        assert_eq!(report.top, MARGIN);
+    }
+
+    #[test]
+    fn report_sections_are_written_as_pdf() {
+        let mission = PathData::new(
+            LineString::from(vec![(101.5, 3.1), (101.53, 3.1)]),
+            MultiPoint::from(vec![(101.52, 3.1)]),
+        );
+        let mut report = Report::new("Mission Report").unwrap();
+
+        report.summary(&data(), Some(&mission));
+        report.summary(&BoatData::default(), None);
+        for layer in data().layers() {
+            report.chart(&data(), &layer);
+        }
+        let content = report.document.save_to_bytes().unwrap();
+
+        assert!(content.starts_with(b"%PDF-"));
+    }
+
+    #[test]
+    fn options_default_to_every_section() {
+        let options: ReportOptions =
+            serde_json::from_value(serde_json::json!({ "title": "Outing", "map": false })).unwrap();
+
+        assert_eq!(options.title, "Outing");
+        assert!(options.summary && options.charts && !options.map);
+    }
+}
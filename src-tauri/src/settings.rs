@@ -0,0 +1,643 @@
+//! User preferences stored in application storage so both the frontend and the backend can read
+//! them.
+
+use std::{
+    collections::BTreeMap,
+    fmt::Display,
+    fs,
+    io::ErrorKind,
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+use chrono::Utc;
+use chrono_tz::Tz;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tauri::{AppHandle, Manager};
+
+use crate::{
+    auto_export::AutoExportSettings,
+    autosave::{self, AutosaveState},
+    backup::DEFAULT_MAX_BACKUPS,
+    calibration::Calibration,
+    communication::SerialConnection,
+    conditions::WeatherApiSettings,
+    errors::{codes, AppError},
+    map_assets::DEFAULT_BASE_URL,
+    operations, recovery,
+    storage::{self, app_dir, data_dir, write_atomically, DataDirectory},
+    units::UnitSystem,
+    watcher,
+};
+
+/// The name of the settings file in the application data directory.
+const SETTINGS_FILE: &str = "settings.json";
+
+/// The format of the timestamp in the names of malformed settings files moved aside.
+const TIMESTAMP_FORMAT: &str = "%Y-%m-%dT%H-%M-%S";
+
+/// The preferences of the user.
+///
+/// Fields missing from the stored file, e.g. ones added in a later version, are filled with
+/// their defaults.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(default)]
+pub struct Settings {
+    /// The URL the map assets are downloaded from.
+    pub tile_server_url: String,
+    /// The baud rate serial connections are opened at.
+    pub baud_rate: u32,
+    /// The units temperatures and depths are displayed and exported in.
+    pub units: UnitSystem,
+    /// Whether the connection is opened again when it fails.
+    pub auto_reconnect: bool,
+    /// The number of backups of the boat data kept.
+    pub max_backups: usize,
+    /// Whether the boat data is stored in SQLite instead of GeoJSON.
+    pub sqlite_storage: bool,
+    /// The IANA name of the time zone times are shown in, e.g. `Asia/Kuala_Lumpur`.
+    pub timezone: String,
+    /// The number of readings after which the recovery journal is synced to disk.
+    pub recovery_sync_readings: usize,
+    /// The number of seconds after which the recovery journal is synced to disk.
+    pub recovery_sync_seconds: u64,
+    /// Whether the boat data and path files are watched for changes made by other programs.
+    pub watch_files: bool,
+    /// Whether CSV imports are parsed on every core while they are read, in pieces of 4 MiB for
+    /// each core.
+    pub parallel_csv_import: bool,
+    /// The number of seconds without edits after which the boat data is autosaved.
+    pub autosave_seconds: u64,
+    /// Where the boat data is exported after each mission, `None` to not export it.
+    pub auto_export: Option<AutoExportSettings>,
+    /// The calibrations of the temperature sensors by their ids.
+    pub calibrations: BTreeMap<String, Calibration>,
+    /// The directory the data is stored in instead of the application data directory, changed
+    /// with [`set_data_directory`].
+    pub data_directory: Option<PathBuf>,
+    /// The offset of the clock of the boat in milliseconds above which a warning is emitted.
+    pub clock_offset_warning_ms: u64,
+    /// The weather API the conditions are fetched from, `None` to only enter them by hand.
+    pub weather_api: Option<WeatherApiSettings>,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            tile_server_url: DEFAULT_BASE_URL.to_string(),
+            baud_rate: 115_200,
+            units: UnitSystem::default(),
+            auto_reconnect: false,
+            max_backups: DEFAULT_MAX_BACKUPS,
+            sqlite_storage: false,
+            timezone: String::from("UTC"),
+            recovery_sync_readings: 10,
+            recovery_sync_seconds: 5,
+            watch_files: true,
+            parallel_csv_import: true,
+            autosave_seconds: 5,
+            auto_export: None,
+            calibrations: BTreeMap::new(),
+            data_directory: None,
+            clock_offset_warning_ms: 2000,
+            weather_api: None,
+        }
+    }
+}
+
+/// Creates the error of `setting` having an invalid value.
+fn invalid_setting(setting: &str, reason: impl Display) -> AppError {
+    AppError::reason(
+        codes::SETTINGS_INVALID,
+        &format!("Invalid Setting {setting}"),
+        reason,
+    )
+    .with("setting", setting)
+}
+
+impl Settings {
+    /// Checks the values of the settings are usable.
+    fn validate(&self) -> Result<(), AppError> {
+        let url = self.tile_server_url.trim();
+        if !url.starts_with("http://") && !url.starts_with("https://") {
+            return Err(invalid_setting(
+                "tile_server_url",
+                format!("{} is not an HTTP URL", self.tile_server_url),
+            ));
+        }
+        if self.baud_rate == 0 {
+            return Err(invalid_setting(
+                "baud_rate",
+                "Baud rate must be greater than 0",
+            ));
+        }
+        if self.max_backups == 0 {
+            return Err(invalid_setting(
+                "max_backups",
+                "At least one backup must be kept",
+            ));
+        }
+        if self.recovery_sync_readings == 0 {
+            return Err(invalid_setting(
+                "recovery_sync_readings",
+                "Must sync after at least one reading",
+            ));
+        }
+        if self.autosave_seconds == 0 {
+            return Err(invalid_setting(
+                "autosave_seconds",
+                "Must wait at least one second",
+            ));
+        }
+        if let Some(auto_export) = &self.auto_export {
+            auto_export
+                .validate()
+                .map_err(|e| invalid_setting("auto_export", e))?;
+        }
+        if let Some(weather_api) = &self.weather_api {
+            weather_api
+                .validate()
+                .map_err(|e| invalid_setting("weather_api", e))?;
+        }
+        for (sensor_id, calibration) in &self.calibrations {
+            calibration
+                .validate()
+                .map_err(|e| invalid_setting("calibrations", format!("Sensor {sensor_id}: {e}")))?;
+        }
+        if let Some(dir) = &self.data_directory {
+            if !dir.is_absolute() {
+                return Err(invalid_setting(
+                    "data_directory",
+                    format!("{} is not an absolute path", dir.display()),
+                ));
+            }
+        }
+        if self.timezone.parse::<Tz>().is_err() {
+            return Err(invalid_setting(
+                "timezone",
+                format!("Unknown time zone {}", self.timezone),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Gets the time zone times are shown in.
+    pub fn timezone(&self) -> Tz {
+        // The time zone is checked when the settings are loaded or updated
+        self.timezone.parse().unwrap_or(Tz::UTC)
+    }
+
+    /// Applies a patch of the settings, returning the patched settings.
+    ///
+    /// An error is returned if the patch has a key that isn't a setting or an invalid value.
+    fn patch(&self, patch: Value) -> Result<Self, AppError> {
+        let invalid_patch = |reason: &dyn Display| {
+            AppError::reason(codes::SETTINGS_INVALID_PATCH, "Invalid Settings", reason)
+        };
+        let patch = match patch {
+            Value::Object(v) => v,
+            _ => return Err(invalid_patch(&"Patch must be an object")),
+        };
+        let mut settings = match serde_json::to_value(self).map_err(|e| invalid_patch(&e))? {
+            Value::Object(v) => v,
+            _ => unreachable!("Settings is serialized as an object"),
+        };
+        for (key, value) in patch {
+            match settings.get_mut(&key) {
+                Some(setting) => *setting = value,
+                None => {
+                    return Err(AppError::new(
+                        codes::SETTINGS_UNKNOWN,
+                        format!("Unknown Setting: {key}"),
+                    )
+                    .with("setting", key))
+                }
+            }
+        }
+
+        let settings: Self =
+            serde_json::from_value(Value::Object(settings)).map_err(|e| invalid_patch(&e))?;
+        settings.validate()?;
+        Ok(settings)
+    }
+}
+
+/// The settings loaded from application storage.
+#[derive(Debug, Default)]
+pub struct SettingsState {
+    settings: Mutex<Settings>,
+}
+
+impl SettingsState {
+    /// Gets a copy of the current settings.
+    pub fn get(&self) -> Settings {
+        self.settings.lock().unwrap().clone()
+    }
+}
+
+/// Gets the path of the settings file.
+///
+/// The settings are in the application data directory even if the data is stored in another
+/// directory.
+fn settings_file(app_handle: &AppHandle) -> Result<PathBuf, AppError> {
+    Ok(app_dir(app_handle)?.join(SETTINGS_FILE))
+}
+
+/// Writes the settings to `path`.
+fn write_settings(path: &Path, settings: &Settings) -> Result<(), AppError> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(|e| AppError::io(e, parent))?;
+    }
+    let content =
+        serde_json::to_string_pretty(settings).map_err(|e| AppError::from(e.to_string()))?;
+    write_atomically(path, |file| file.write_all(content.as_bytes()))
+}
+
+/// Reads the settings from `path`.
+///
+/// The defaults are written if the file doesn't exist. A malformed file is renamed aside and
+/// replaced with the defaults so it doesn't prevent the app from starting.
+fn read_settings(path: &Path) -> Result<Settings, AppError> {
+    let content = match fs::read_to_string(path) {
+        Ok(v) => v,
+        Err(e) if e.kind() == ErrorKind::NotFound => {
+            log::info!("Creating Default Settings: {}", path.display());
+            let settings = Settings::default();
+            write_settings(path, &settings)?;
+            return Ok(settings);
+        }
+        Err(e) => return Err(AppError::io(e, path)),
+    };
+
+    let settings = serde_json::from_str::<Settings>(&content)
+        .map_err(|e| AppError::new(codes::DATA_INVALID_JSON, e.to_string()))
+        .and_then(|settings| settings.validate().map(|_| settings));
+    match settings {
+        Ok(v) => Ok(v),
+        Err(e) => {
+            let aside = path.with_file_name(format!(
+                "settings-{}.invalid.json",
+                Utc::now().format(TIMESTAMP_FORMAT)
+            ));
+            log::warn!("Invalid Settings: {}, moving it to {}", e, aside.display());
+            fs::rename(path, &aside).map_err(|e| AppError::io(e, path))?;
+            let settings = Settings::default();
+            write_settings(path, &settings)?;
+            Ok(settings)
+        }
+    }
+}
+
+/// Applies the settings used by the backend.
+fn apply(app_handle: &AppHandle, settings: &Settings) {
+    app_handle
+        .state::<DataDirectory>()
+        .set(settings.data_directory.clone());
+    app_handle
+        .state::<SerialConnection>()
+        .set_auto_reconnect(settings.auto_reconnect);
+    watcher::set_watching(app_handle, settings.watch_files);
+}
+
+/// Loads the settings from application storage into the settings state.
+pub fn init_settings(app_handle: &AppHandle) -> Result<(), AppError> {
+    let settings = read_settings(&settings_file(app_handle)?)?;
+    apply(app_handle, &settings);
+    *app_handle.state::<SettingsState>().settings.lock().unwrap() = settings;
+    Ok(())
+}
+
+/// Get the current settings.
+#[tauri::command]
+pub fn get_settings(state: tauri::State<SettingsState>) -> Settings {
+    state.get()
+}
+
+/// Updates some of the settings, saving them and emitting them on `settings-changed`.
+pub fn update(app_handle: &AppHandle, patch: Value) -> Result<Settings, AppError> {
+    log::debug!("Updating Settings: {}", patch);
+    let state = app_handle.state::<SettingsState>();
+    let mut current = state.settings.lock().unwrap();
+    let settings = current.patch(patch)?;
+    if settings == *current {
+        return Ok(settings);
+    }
+
+    write_settings(&settings_file(app_handle)?, &settings)?;
+    apply(app_handle, &settings);
+    *current = settings.clone();
+    drop(current);
+
+    log::info!("Updated Settings");
+    if let Err(e) = app_handle.emit_all("settings-changed", settings.clone()) {
+        log::warn!("Unable to emit settings-changed event: {}", e);
+    }
+    Ok(settings)
+}
+
+/// Update some of the settings, keeping the others unchanged.
+///
+/// `patch` is an object of the settings to change. Unknown settings and invalid values are
+/// rejected without changing any setting. The updated settings are saved and emitted on
+/// `settings-changed`. The data directory is changed with [`set_data_directory`], as the data
+/// is moved with it.
+#[tauri::command]
+pub fn update_settings(app_handle: AppHandle, patch: Value) -> Result<Settings, AppError> {
+    if patch.get("data_directory").is_some() {
+        return Err(invalid_setting(
+            "data_directory",
+            "Use set_data_directory to move the data",
+        ));
+    }
+    update(&app_handle, patch)
+}
+
+/// Move the data to another directory and store it there from now on, e.g. when the
+/// application data directory is read only.
+///
+/// `path` is `None` to move the data back to the application data directory. The settings stay
+/// in the application data directory. Nothing is moved if a file of the data already exists in
+/// the new directory, and the files are only removed from the old directory once every file is
+/// copied. The data can't be moved while connected to the boat, as the readings received are
+/// written to the data directory.
+#[tauri::command]
+pub fn set_data_directory(
+    app_handle: AppHandle,
+    path: Option<PathBuf>,
+) -> Result<Settings, AppError> {
+    operations::record(
+        &app_handle,
+        "set_data_directory",
+        json!({ "path": path }),
+        || {
+            let from = data_dir(&app_handle)?;
+            let to = match &path {
+                Some(v) => v.clone(),
+                None => app_dir(&app_handle)?,
+            };
+            log::info!("Moving Data from {} to {}", from.display(), to.display());
+            if !to.is_absolute() {
+                return Err(invalid_setting(
+                    "data_directory",
+                    format!("{} is not an absolute path", to.display()),
+                ));
+            }
+            if to != from && to.starts_with(&from) {
+                return Err(invalid_setting(
+                    "data_directory",
+                    format!("{} is inside the data directory", to.display()),
+                ));
+            }
+            if app_handle.state::<SerialConnection>().link().is_some() {
+                return Err(AppError::new(
+                    codes::SETTINGS_CONNECTED,
+                    "Unable to Move the Data while Connected to the Boat",
+                ));
+            }
+
+            let _writing = app_handle.state::<AutosaveState>().inner().lock_writes();
+            autosave::flush_locked(&app_handle)?;
+            recovery::close_recovery(&app_handle)?;
+            let watching = app_handle.state::<SettingsState>().get().watch_files;
+            watcher::set_watching(&app_handle, false);
+
+            let copied = if to == from {
+                vec![]
+            } else {
+                match storage::copy_dir(&from, &to, &[SETTINGS_FILE]) {
+                    Ok(v) => v,
+                    Err(e) => {
+                        watcher::set_watching(&app_handle, watching);
+                        return Err(e);
+                    }
+                }
+            };
+            let settings = match update(&app_handle, json!({ "data_directory": path })) {
+                Ok(v) => v,
+                Err(e) => {
+                    log::warn!("Unable to Save the Data Directory, removing the copies");
+                    for file in &copied {
+                        // The paths are read from the directory so they always have a file name
+                        storage::remove(&to.join(file.file_name().unwrap()));
+                    }
+                    watcher::set_watching(&app_handle, watching);
+                    return Err(e);
+                }
+            };
+            for file in &copied {
+                storage::remove(file);
+            }
+            watcher::set_watching(&app_handle, settings.watch_files);
+            log::info!("Moved {} Files to {}", copied.len(), to.display());
+            Ok(settings)
+        },
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::storage::tests::temp_dir;
+
+    use super::*;
+
+    #[test]
+    fn patch_changes_only_the_given_settings() {
+        let settings = Settings::default()
+            .patch(json!({ "baud_rate": 9600, "units": "imperial" }))
+            .unwrap();
+
+        assert_eq!(
+            settings,
+            Settings {
+                baud_rate: 9600,
+                units: UnitSystem::Imperial,
+                ..Settings::default()
+            }
+        );
+    }
+
+    #[test]
+    fn invalid_patch_is_rejected() {
+        let settings = Settings::default();
+
+        for patch in [
+            json!({ "baud": 9600 }),
+            json!({ "units": "kelvin" }),
+            json!({ "units": 3 }),
+            json!({ "baud_rate": 0 }),
+            json!({ "baud_rate": -1 }),
+            json!({ "max_backups": 0 }),
+            json!({ "tile_server_url": "ftp://example.com" }),
+            json!([]),
+        ] {
+            assert!(settings.patch(patch.clone()).is_err(), "{patch}");
+        }
+    }
+
+    #[test]
+    fn unknown_key_is_named_in_the_error() {
+        let error = Settings::default()
+            .patch(json!({ "baud_rate": 9600, "theme": "dark" }))
+            .unwrap_err();
+        assert_eq!(
+            error,
+            AppError::new(codes::SETTINGS_UNKNOWN, "Unknown Setting: theme")
+                .with("setting", "theme")
+        );
+    }
+
+    #[test]
+    fn missing_file_is_created_with_the_defaults() {
+        let dir = temp_dir("settings-missing");
+        let path = dir.join(SETTINGS_FILE);
+
+        assert_eq!(read_settings(&path).unwrap(), Settings::default());
+        let stored: Settings = serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(stored, Settings::default());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn missing_settings_are_filled_with_the_defaults() {
+        let dir = temp_dir("settings-partial");
+        let path = dir.join(SETTINGS_FILE);
+        fs::write(&path, r#"{ "baud_rate": 57600 }"#).unwrap();
+
+        assert_eq!(
+            read_settings(&path).unwrap(),
+            Settings {
+                baud_rate: 57600,
+                ..Settings::default()
+            }
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn malformed_file_is_moved_aside() {
+        for (name, content) in [
+            ("settings-malformed", "{ \"baud_rate\": "),
+            ("settings-invalid-value", r#"{ "max_backups": 0 }"#),
+        ] {
+            let dir = temp_dir(name);
+            let path = dir.join(SETTINGS_FILE);
+            fs::write(&path, content).unwrap();
+
+            assert_eq!(read_settings(&path).unwrap(), Settings::default());
+            let aside: Vec<_> = fs::read_dir(&dir)
+                .unwrap()
+                .map(|entry| entry.unwrap().path())
+                .filter(|path| path.to_string_lossy().ends_with(".invalid.json"))
+                .collect();
+            assert_eq!(aside.len(), 1);
+            assert_eq!(fs::read_to_string(&aside[0]).unwrap(), content);
+            fs::remove_dir_all(&dir).unwrap();
+        }
+    }
+
+    #[test]
+    fn settings_round_trip_through_the_file() {
+        let dir = temp_dir("settings-round-trip");
+        let path = dir.join(SETTINGS_FILE);
+        let settings = Settings {
+            units: UnitSystem::Imperial,
+            auto_reconnect: true,
+            max_backups: 3,
+            ..Settings::default()
+        };
+
+        write_settings(&path, &settings).unwrap();
+        assert_eq!(read_settings(&path).unwrap(), settings);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn unknown_time_zone_is_rejected() {
+        let settings = Settings::default()
+            .patch(json!({ "timezone": "Asia/Kuala_Lumpur" }))
+            .unwrap();
+
+        assert_eq!(settings.timezone(), chrono_tz::Asia::Kuala_Lumpur);
+        assert_eq!(Settings::default().timezone(), Tz::UTC);
+        assert_eq!(
+            Settings::default().patch(json!({ "timezone": "Mars/Olympus_Mons" })),
+            Err(invalid_setting(
+                "timezone",
+                "Unknown time zone Mars/Olympus_Mons"
+            ))
+        );
+    }
+
+    #[test]
+    fn recovery_must_sync_after_a_reading() {
+        assert_eq!(
+            Settings::default().patch(json!({ "recovery_sync_readings": 0 })),
+            Err(invalid_setting(
+                "recovery_sync_readings",
+                "Must sync after at least one reading"
+            ))
+        );
+        let settings = Settings::default()
+            .patch(json!({ "recovery_sync_readings": 1, "recovery_sync_seconds": 0 }))
+            .unwrap();
+        assert_eq!(
+            (
+                settings.recovery_sync_readings,
+                settings.recovery_sync_seconds
+            ),
+            (1, 0)
+        );
+    }
+
+    #[test]
+    fn file_watching_can_be_turned_off() {
+        assert!(Settings::default().watch_files);
+        let settings = Settings::default()
+            .patch(json!({ "watch_files": false }))
+            .unwrap();
+        assert!(!settings.watch_files);
+        assert!(Settings::default()
+            .patch(json!({ "watch_files": "no" }))
+            .is_err());
+    }
+
+    #[test]
+    fn autosave_waits_at_least_a_second() {
+        assert_eq!(Settings::default().autosave_seconds, 5);
+        assert_eq!(
+            Settings::default().patch(json!({ "autosave_seconds": 0 })),
+            Err(invalid_setting(
+                "autosave_seconds",
+                "Must wait at least one second"
+            ))
+        );
+    }
+
+    #[test]
+    fn calibrations_must_be_invertible() {
+        let patch = json!({ "calibrations": { "T1": { "offset": 0.5, "scale": 0.0 } } });
+
+        assert_eq!(
+            Settings::default().patch(patch),
+            Err(invalid_setting("calibrations", "Sensor T1: Scale 0"))
+        );
+    }
+
+    #[test]
+    fn data_directory_must_be_absolute() {
+        let dir = std::env::temp_dir().join("babara-data");
+        let settings = Settings::default()
+            .patch(json!({ "data_directory": dir }))
+            .unwrap();
+        assert_eq!(settings.data_directory, Some(dir));
+
+        assert_eq!(
+            Settings::default().patch(json!({ "data_directory": "data" })),
+            Err(invalid_setting(
+                "data_directory",
+                "data is not an absolute path"
+            ))
+        );
+    }
+}
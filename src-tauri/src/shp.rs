@@ -0,0 +1,700 @@
+//! ESRI Shapefile import of boat data and paths.
+//!
+//! The coordinates are reprojected to WGS84 from the CRS in the `.prj` sidecar file. Geographic
+//! coordinates, Web Mercator and UTM zones are supported.
+
+use std::{
+    f64::consts::FRAC_PI_2,
+    path::{Path, PathBuf},
+};
+
+use chrono::{DateTime, NaiveDate, TimeZone, Utc};
+use geo_types::{Coord, LineString, MultiPoint, Point};
+use serde::Deserialize;
+use shapefile::{
+    dbase::{FieldValue, Record},
+    Shape, ShapeReader,
+};
+
+use crate::{
+    data::{BoatData, BoatDataFeature, ImportedData, Layer},
+    errors::{codes, AppError},
+    migrations,
+    path::PathData,
+};
+
+/// The semi-major axis of the WGS84 ellipsoid in meters.
+const WGS84_A: f64 = 6_378_137.0;
+
+/// The flattening of the WGS84 ellipsoid.
+const WGS84_F: f64 = 1.0 / 298.257_223_563;
+
+/// The scale factor at the central meridian of UTM zones.
+const UTM_K0: f64 = 0.9996;
+
+/// The false easting of UTM zones in meters.
+const UTM_FALSE_EASTING: f64 = 500_000.0;
+
+/// The false northing of UTM zones in the southern hemisphere in meters.
+const UTM_FALSE_NORTHING: f64 = 10_000_000.0;
+
+/// The coordinate reference system of a shapefile.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Crs {
+    /// Longitude and latitude in degrees.
+    Geographic,
+    /// Web Mercator in meters.
+    WebMercator,
+    /// A UTM zone in meters.
+    Utm {
+        /// The number of the zone, from 1 to 60.
+        zone: u8,
+        /// Whether the zone is in the southern hemisphere.
+        south: bool,
+    },
+}
+
+impl Crs {
+    /// Parses the CRS from the WKT in a `.prj` file.
+    fn from_wkt(wkt: &str) -> Result<Self, String> {
+        let wkt = wkt.trim();
+        let upper = wkt.to_uppercase();
+        if upper.starts_with("GEOGCS") {
+            return Ok(Self::Geographic);
+        }
+        if !upper.starts_with("PROJCS") {
+            return Err(format!("Unsupported Shapefile Projection: {wkt}"));
+        }
+        // The name of the projected CRS, e.g. "WGS_1984_UTM_Zone_33N" or "WGS 84 / UTM zone 33N"
+        let name = upper
+            .split('"')
+            .nth(1)
+            .unwrap_or_default()
+            .replace(['_', '/'], " ");
+        if upper.contains("MERCATOR_AUXILIARY_SPHERE")
+            || upper.contains("PSEUDO-MERCATOR")
+            || upper.contains("PSEUDO MERCATOR")
+            || name.contains("WEB MERCATOR")
+        {
+            return Ok(Self::WebMercator);
+        }
+        if let Some(zone) = name.split("UTM ZONE").nth(1) {
+            let zone = zone.trim();
+            let digits: String = zone.chars().take_while(char::is_ascii_digit).collect();
+            let south = match zone[digits.len()..].trim_start().chars().next() {
+                Some('N') => false,
+                Some('S') => true,
+                _ => return Err(format!("Unsupported Shapefile Projection: {wkt}")),
+            };
+            return match digits.parse() {
+                Ok(zone @ 1..=60) => Ok(Self::Utm { zone, south }),
+                _ => Err(format!("Unsupported Shapefile Projection: {wkt}")),
+            };
+        }
+        Err(format!("Unsupported Shapefile Projection: {wkt}"))
+    }
+
+    /// Reprojects a coordinate to WGS84 longitude and latitude.
+    fn to_wgs84(self, x: f64, y: f64) -> Coord<f64> {
+        match self {
+            Self::Geographic => Coord { x, y },
+            Self::WebMercator => Coord {
+                x: (x / WGS84_A).to_degrees(),
+                y: (2.0 * (y / WGS84_A).exp().atan() - FRAC_PI_2).to_degrees(),
+            },
+            Self::Utm { zone, south } => utm_to_wgs84(zone, south, x, y),
+        }
+    }
+}
+
+/// Converts UTM coordinates to WGS84 longitude and latitude.
+///
+/// Uses the inverse transverse Mercator series from Snyder's "Map Projections: A Working
+/// Manual", accurate to well under a meter within the zone.
+fn utm_to_wgs84(zone: u8, south: bool, easting: f64, northing: f64) -> Coord<f64> {
+    let e2 = WGS84_F * (2.0 - WGS84_F);
+    let ep2 = e2 / (1.0 - e2);
+    let x = easting - UTM_FALSE_EASTING;
+    let y = if south {
+        northing - UTM_FALSE_NORTHING
+    } else {
+        northing
+    };
+
+    let m = y / UTM_K0;
+    let mu = m / (WGS84_A * (1.0 - e2 / 4.0 - 3.0 * e2.powi(2) / 64.0 - 5.0 * e2.powi(3) / 256.0));
+    let e1 = (1.0 - (1.0 - e2).sqrt()) / (1.0 + (1.0 - e2).sqrt());
+    let phi1 = mu
+        + (3.0 * e1 / 2.0 - 27.0 * e1.powi(3) / 32.0) * (2.0 * mu).sin()
+        + (21.0 * e1.powi(2) / 16.0 - 55.0 * e1.powi(4) / 32.0) * (4.0 * mu).sin()
+        + (151.0 * e1.powi(3) / 96.0) * (6.0 * mu).sin()
+        + (1097.0 * e1.powi(4) / 512.0) * (8.0 * mu).sin();
+
+    let (sin, cos) = phi1.sin_cos();
+    let c1 = ep2 * cos.powi(2);
+    let t1 = phi1.tan().powi(2);
+    let n1 = WGS84_A / (1.0 - e2 * sin.powi(2)).sqrt();
+    let r1 = WGS84_A * (1.0 - e2) / (1.0 - e2 * sin.powi(2)).powf(1.5);
+    let d = x / (n1 * UTM_K0);
+
+    let lat = phi1
+        - (n1 * phi1.tan() / r1)
+            * (d.powi(2) / 2.0
+                - (5.0 + 3.0 * t1 + 10.0 * c1 - 4.0 * c1.powi(2) - 9.0 * ep2) * d.powi(4) / 24.0
+                + (61.0 + 90.0 * t1 + 298.0 * c1 + 45.0 * t1.powi(2)
+                    - 252.0 * ep2
+                    - 3.0 * c1.powi(2))
+                    * d.powi(6)
+                    / 720.0);
+    let lng = (d - (1.0 + 2.0 * t1 + c1) * d.powi(3) / 6.0
+        + (5.0 - 2.0 * c1 + 28.0 * t1 - 3.0 * c1.powi(2) + 8.0 * ep2 + 24.0 * t1.powi(2))
+            * d.powi(5)
+            / 120.0)
+        / cos;
+
+    let central_meridian = f64::from(zone) * 6.0 - 183.0;
+    Coord {
+        x: central_meridian + lng.to_degrees(),
+        y: lat.to_degrees(),
+    }
+}
+
+/// Gets the path of a sidecar file of a shapefile, checking that it exists.
+fn sidecar(path: &Path, extension: &str) -> Result<PathBuf, AppError> {
+    let sidecar = path.with_extension(extension);
+    if !sidecar.exists() {
+        return Err(AppError::new(
+            codes::SHAPEFILE_MISSING_SIDECAR,
+            format!("Missing Shapefile Sidecar: {} not found", sidecar.display()),
+        )
+        .with("path", sidecar.display().to_string()));
+    }
+    Ok(sidecar)
+}
+
+/// Creates the error of the shapefile at `path` being invalid.
+fn invalid_shapefile(path: &Path, reason: shapefile::Error) -> AppError {
+    AppError::reason(codes::SHAPEFILE_INVALID, "Invalid Shapefile", reason)
+        .with("path", path.display().to_string())
+}
+
+/// Reads the CRS of a shapefile from its `.prj` file.
+fn read_crs(path: &Path) -> Result<Crs, AppError> {
+    let prj = sidecar(path, "prj")?;
+    let wkt = std::fs::read_to_string(&prj).map_err(|e| AppError::io(e, &prj))?;
+    let crs = Crs::from_wkt(&wkt)
+        .map_err(|e| AppError::new(codes::SHAPEFILE_UNSUPPORTED_PROJECTION, e))?;
+    log::debug!("Shapefile CRS: {:?}", crs);
+    Ok(crs)
+}
+
+/// The DBF columns of the values of the data points.
+#[derive(Debug, Deserialize)]
+pub struct ShapefileColumns {
+    /// The column of the temperature.
+    temperature: String,
+    /// The column of the depth.
+    depth: String,
+    /// The column of the time, a date, epoch milliseconds or RFC 3339.
+    time: String,
+    /// The column of the layer, every data point is at the surface if not given.
+    layer: Option<String>,
+}
+
+/// Gets the value of a column of a record.
+fn field<'a>(record: &'a Record, column: &str) -> Result<&'a FieldValue, String> {
+    record
+        .get(column)
+        .ok_or_else(|| format!("Missing Column \"{column}\""))
+}
+
+/// Reads a number from a column of a record.
+fn number(record: &Record, column: &str) -> Result<f64, String> {
+    let value = match field(record, column)? {
+        FieldValue::Numeric(Some(v)) => *v,
+        FieldValue::Float(Some(v)) => f64::from(*v),
+        FieldValue::Double(v) | FieldValue::Currency(v) => *v,
+        FieldValue::Integer(v) => f64::from(*v),
+        FieldValue::Character(Some(v)) => v
+            .trim()
+            .parse()
+            .map_err(|_| format!("Invalid Number in Column \"{column}\": {v}"))?,
+        value => return Err(format!("Invalid Number in Column \"{column}\": {value:?}")),
+    };
+    Ok(value)
+}
+
+/// Reads a time from a column of a record.
+///
+/// Dates are at midnight UTC.
+fn time(record: &Record, column: &str) -> Result<DateTime<Utc>, String> {
+    let invalid =
+        |value: &dyn std::fmt::Debug| format!("Invalid Time in Column \"{column}\": {value:?}");
+    let date = |date: &shapefile::dbase::Date| {
+        NaiveDate::from_ymd_opt(date.year() as i32, date.month(), date.day())
+            .ok_or_else(|| invalid(date))
+    };
+    match field(record, column)? {
+        FieldValue::Date(Some(v)) => {
+            Ok(Utc.from_utc_datetime(&date(v)?.and_time(Default::default())))
+        }
+        FieldValue::DateTime(v) => {
+            let time = v.time();
+            let time = date(&v.date())?
+                .and_hms_opt(time.hours(), time.minutes(), time.seconds())
+                .ok_or_else(|| invalid(v))?;
+            Ok(Utc.from_utc_datetime(&time))
+        }
+        FieldValue::Character(Some(v)) => match v.trim().parse::<i64>() {
+            Ok(millis) => Utc
+                .timestamp_millis_opt(millis)
+                .single()
+                .ok_or_else(|| invalid(v)),
+            Err(_) => DateTime::parse_from_rfc3339(v.trim())
+                .map(|time| time.with_timezone(&Utc))
+                .map_err(|_| invalid(v)),
+        },
+        value => {
+            let millis = number(record, column).map_err(|_| invalid(value))?;
+            Utc.timestamp_millis_opt(millis as i64)
+                .single()
+                .ok_or_else(|| invalid(value))
+        }
+    }
+}
+
+/// Reads a layer from a column of a record.
+fn layer(record: &Record, column: &str) -> Result<Layer, String> {
+    match field(record, column)? {
+        FieldValue::Character(Some(v)) => v.trim().parse(),
+        value => Err(format!("Invalid Layer in Column \"{column}\": {value:?}")),
+    }
+}
+
+/// Reads a data point from a shape and its record.
+fn read_feature(
+    shape: Shape,
+    record: &Record,
+    columns: &ShapefileColumns,
+    crs: Crs,
+) -> Result<BoatDataFeature, String> {
+    let (x, y) = match shape {
+        Shape::Point(p) => (p.x, p.y),
+        Shape::PointM(p) => (p.x, p.y),
+        Shape::PointZ(p) => (p.x, p.y),
+        shape => {
+            return Err(format!(
+                "Invalid Shape: Expected a Point, found {:?}",
+                shape.shapetype()
+            ))
+        }
+    };
+    Ok(BoatDataFeature::new(
+        number(record, &columns.temperature)?,
+        number(record, &columns.depth)?,
+        match &columns.layer {
+            Some(column) => layer(record, column)?,
+            None => Layer::Surface,
+        },
+        time(record, &columns.time)?,
+        Point::from(crs.to_wgs84(x, y)),
+    ))
+}
+
+/// Import boat data from a shapefile of points.
+///
+/// The values of the data points are read from the DBF columns in `columns`, and the points
+/// are reprojected to WGS84 from the CRS in the `.prj` file. Files with data points out of
+/// range are rejected unless `skip_invalid` is `true`, and repeated data points are removed
+/// unless `dedup` is `false`.
+#[tauri::command]
+pub fn import_data_shapefile(
+    import_path: PathBuf,
+    columns: ShapefileColumns,
+    dedup: Option<bool>,
+    skip_invalid: Option<bool>,
+) -> Result<ImportedData, AppError> {
+    log::debug!("Importing from: {}", import_path.display());
+    sidecar(&import_path, "dbf")?;
+    let crs = read_crs(&import_path)?;
+
+    let mut reader = shapefile::Reader::from_path(&import_path)
+        .map_err(|e| invalid_shapefile(&import_path, e))?;
+    let mut features = vec![];
+    for (i, result) in reader.iter_shapes_and_records().enumerate() {
+        let (shape, record) = result.map_err(|e| invalid_shapefile(&import_path, e))?;
+        let feature = read_feature(shape, &record, &columns, crs).map_err(|e| {
+            AppError::reason(
+                codes::SHAPEFILE_INVALID_RECORD,
+                &format!("Invalid Shapefile Record {}", i + 1),
+                e,
+            )
+            .with("record", i + 1)
+        })?;
+        features.push(feature);
+    }
+    log::info!("Read {} Data Points from Shapefile", features.len());
+
+    let data = BoatData::new(String::from(migrations::BOAT_DATA_VERSION), features);
+    ImportedData::new(data, None, dedup, skip_invalid)
+}
+
+/// Gets the points of the parts of a polyline in order.
+fn polyline_points<P>(parts: &[Vec<P>], xy: impl Fn(&P) -> (f64, f64)) -> Vec<(f64, f64)> {
+    parts.iter().flatten().map(xy).collect()
+}
+
+/// Import a path from a shapefile of polylines.
+///
+/// The first polyline is the path, with its parts joined in order, and is reprojected to
+/// WGS84 from the CRS in the `.prj` file. The path has no collection points.
+#[tauri::command]
+pub fn import_path_shapefile(import_path: PathBuf) -> Result<PathData, AppError> {
+    log::debug!("Importing from: {}", import_path.display());
+    let crs = read_crs(&import_path)?;
+
+    let shapes = ShapeReader::from_path(&import_path)
+        .map_err(|e| invalid_shapefile(&import_path, e))?
+        .read()
+        .map_err(|e| invalid_shapefile(&import_path, e))?;
+    let points = shapes
+        .into_iter()
+        .find_map(|shape| match shape {
+            Shape::Polyline(l) => Some(polyline_points(l.parts(), |p| (p.x, p.y))),
+            Shape::PolylineM(l) => Some(polyline_points(l.parts(), |p| (p.x, p.y))),
+            Shape::PolylineZ(l) => Some(polyline_points(l.parts(), |p| (p.x, p.y))),
+            _ => None,
+        })
+        .ok_or_else(|| {
+            AppError::new(
+                codes::SHAPEFILE_NO_POLYLINE,
+                "Invalid Shapefile: Path shapefile requires a Polyline.",
+            )
+        })?;
+
+    let path: LineString<f64> = points
+        .into_iter()
+        .map(|(x, y)| crs.to_wgs84(x, y))
+        .collect();
+    log::info!("Read Path with {} Waypoints from Shapefile", path.0.len());
+    Ok(PathData::new(path, MultiPoint(vec![])))
+}
+
+#[cfg(test)]
+mod tests {
+    use shapefile::{
+        dbase::{FieldName, TableWriterBuilder},
+        Point as ShpPoint, Polyline, ShapeWriter, Writer,
+    };
+
+    use super::*;
+    use crate::storage::tests::temp_dir;
+
+    /// The WKT of geographic WGS84 coordinates.
+    const WGS84_WKT: &str = r#"GEOGCS["GCS_WGS_1984",DATUM["D_WGS_1984",SPHEROID["WGS_1984",6378137.0,298.257223563]],PRIMEM["Greenwich",0.0],UNIT["Degree",0.0174532925199433]]"#;
+
+    /// The WKT of UTM zone 47N as written by ArcGIS.
+    const UTM_47N_WKT: &str = r#"PROJCS["WGS_1984_UTM_Zone_47N",GEOGCS["GCS_WGS_1984",DATUM["D_WGS_1984",SPHEROID["WGS_1984",6378137.0,298.257223563]],PRIMEM["Greenwich",0.0],UNIT["Degree",0.0174532925199433]],PROJECTION["Transverse_Mercator"],PARAMETER["False_Easting",500000.0],PARAMETER["False_Northing",0.0],PARAMETER["Central_Meridian",99.0],PARAMETER["Scale_Factor",0.9996],PARAMETER["Latitude_Of_Origin",0.0],UNIT["Meter",1.0]]"#;
+
+    /// The tolerance of reprojected coordinates in degrees, about 1 cm.
+    const EPSILON: f64 = 1e-7;
+
+    /// Asserts that a coordinate is within [`EPSILON`] of a longitude and latitude.
+    fn assert_close(coord: Coord<f64>, lng: f64, lat: f64) {
+        assert!(
+            (coord.x - lng).abs() < EPSILON && (coord.y - lat).abs() < EPSILON,
+            "{coord:?} is not ({lng}, {lat})"
+        );
+    }
+
+    /// The columns of the data points written by [`write_points`].
+    fn columns(layer: bool) -> ShapefileColumns {
+        ShapefileColumns {
+            temperature: String::from("TEMP"),
+            depth: String::from("DEPTH"),
+            time: String::from("TIME"),
+            layer: layer.then(|| String::from("LAYER")),
+        }
+    }
+
+    /// Writes a shapefile of points in UTM zone 47N, with the `.prj` file unless `wkt` is
+    /// `None`.
+    ///
+    /// Each record is the easting, northing, temperature, depth, time and layer.
+    fn write_points(path: &Path, wkt: Option<&str>, records: &[(f64, f64, f64, f64, &str, &str)]) {
+        let table = TableWriterBuilder::new()
+            .add_numeric_field(FieldName::try_from("TEMP").unwrap(), 10, 3)
+            .add_numeric_field(FieldName::try_from("DEPTH").unwrap(), 10, 3)
+            .add_character_field(FieldName::try_from("TIME").unwrap(), 30)
+            .add_character_field(FieldName::try_from("LAYER").unwrap(), 10);
+        let mut writer = Writer::from_path(path, table).unwrap();
+        for (x, y, temperature, depth, time, layer) in records {
+            let mut record = Record::default();
+            record.insert(
+                String::from("TEMP"),
+                FieldValue::Numeric(Some(*temperature)),
+            );
+            record.insert(String::from("DEPTH"), FieldValue::Numeric(Some(*depth)));
+            record.insert(
+                String::from("TIME"),
+                FieldValue::Character(Some(time.to_string())),
+            );
+            record.insert(
+                String::from("LAYER"),
+                FieldValue::Character(Some(layer.to_string())),
+            );
+            writer
+                .write_shape_and_record(&ShpPoint::new(*x, *y), &record)
+                .unwrap();
+        }
+        drop(writer);
+        if let Some(wkt) = wkt {
+            std::fs::write(path.with_extension("prj"), wkt).unwrap();
+        }
+    }
+
+    /// Gets the boat data imported from a shapefile.
+    fn imported_data(imported: ImportedData) -> BoatData {
+        let mut value = serde_json::to_value(imported).unwrap();
+        serde_json::from_value(value["data"].take()).unwrap()
+    }
+
+    #[test]
+    fn wkt_names_are_parsed() {
+        assert_eq!(Crs::from_wkt(WGS84_WKT), Ok(Crs::Geographic));
+        assert_eq!(
+            Crs::from_wkt(UTM_47N_WKT),
+            Ok(Crs::Utm {
+                zone: 47,
+                south: false
+            })
+        );
+        assert_eq!(
+            Crs::from_wkt(r#"PROJCS["WGS 84 / UTM zone 56S",GEOGCS["WGS 84"]]"#),
+            Ok(Crs::Utm {
+                zone: 56,
+                south: true
+            })
+        );
+        assert_eq!(
+            Crs::from_wkt(
+                r#"PROJCS["WGS_1984_Web_Mercator_Auxiliary_Sphere",PROJECTION["Mercator_Auxiliary_Sphere"]]"#
+            ),
+            Ok(Crs::WebMercator)
+        );
+        assert_eq!(
+            Crs::from_wkt(r#"PROJCS["WGS 84 / Pseudo-Mercator",GEOGCS["WGS 84"]]"#),
+            Ok(Crs::WebMercator)
+        );
+    }
+
+    #[test]
+    fn unsupported_projections_are_rejected() {
+        for wkt in [
+            r#"PROJCS["GDM2000 / Peninsula RSO",GEOGCS["GDM2000"]]"#,
+            r#"PROJCS["WGS 84 / UTM zone 61N",GEOGCS["WGS 84"]]"#,
+            r#"PROJCS["WGS 84 / UTM zone 33",GEOGCS["WGS 84"]]"#,
+            r#"GEOCCS["WGS 84"]"#,
+        ] {
+            assert_eq!(
+                Crs::from_wkt(wkt),
+                Err(format!("Unsupported Shapefile Projection: {wkt}"))
+            );
+        }
+    }
+
+    #[test]
+    fn utm_coordinates_are_reprojected() {
+        // The references are from the Krüger series of the forward projection
+        assert_close(
+            utm_to_wgs84(33, false, 500_000.0, 4_649_776.224_775),
+            15.0,
+            42.0,
+        );
+        assert_close(
+            utm_to_wgs84(47, false, 800_102.657_853, 348_561.937_323),
+            101.7,
+            3.15,
+        );
+        assert_close(
+            utm_to_wgs84(56, true, 334_416.393_994, 6_251_925.360_375),
+            151.21,
+            -33.86,
+        );
+    }
+
+    #[test]
+    fn web_mercator_coordinates_are_reprojected() {
+        let y = WGS84_A
+            * (std::f64::consts::FRAC_PI_4 + 45f64.to_radians() / 2.0)
+                .tan()
+                .ln();
+
+        assert_close(Crs::WebMercator.to_wgs84(0.0, 0.0), 0.0, 0.0);
+        assert_close(
+            Crs::WebMercator.to_wgs84(20_037_508.342_789_244, y),
+            180.0,
+            45.0,
+        );
+        assert_close(Crs::Geographic.to_wgs84(101.7, 3.15), 101.7, 3.15);
+    }
+
+    #[test]
+    fn points_are_imported_with_the_column_mapping() {
+        let dir = temp_dir("shp_points");
+        let path = dir.join("points.shp");
+        write_points(
+            &path,
+            Some(UTM_47N_WKT),
+            &[
+                (
+                    800_102.657_853,
+                    348_561.937_323,
+                    28.5,
+                    0.5,
+                    "1715495400000",
+                    "surface",
+                ),
+                (
+                    500_000.0,
+                    110_530.158_802,
+                    27.25,
+                    4.0,
+                    "2024-05-12T06:31:00Z",
+                    "sea bed",
+                ),
+            ],
+        );
+
+        let data =
+            imported_data(import_data_shapefile(path.clone(), columns(true), None, None).unwrap());
+
+        let features = data.features();
+        assert_eq!(features.len(), 2);
+        assert_close(features[0].geometry().0, 101.7, 3.15);
+        assert_close(features[1].geometry().0, 99.0, 1.0);
+        assert_eq!(features[0].temperature(), 28.5);
+        assert_eq!(features[1].depth(), 4.0);
+        assert_eq!(features[0].layer(), Layer::Surface);
+        assert_eq!(features[1].layer(), Layer::SeaBed);
+        assert_eq!(
+            features[0].time(),
+            Utc.timestamp_millis_opt(1_715_495_400_000).unwrap()
+        );
+        assert_eq!(
+            features[1].time(),
+            Utc.with_ymd_and_hms(2024, 5, 12, 6, 31, 0).unwrap()
+        );
+
+        let data = imported_data(import_data_shapefile(path, columns(false), None, None).unwrap());
+        assert_eq!(data.features()[1].layer(), Layer::Surface);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn invalid_records_are_reported_by_number() {
+        let dir = temp_dir("shp_invalid_record");
+        let path = dir.join("points.shp");
+        write_points(
+            &path,
+            Some(UTM_47N_WKT),
+            &[
+                (500_000.0, 0.0, 28.5, 0.5, "1715495400000", "surface"),
+                (500_000.0, 0.0, 28.5, 0.5, "yesterday", "surface"),
+            ],
+        );
+        let mut missing = columns(false);
+        missing.depth = String::from("DEPTH_M");
+
+        assert_eq!(
+            import_data_shapefile(path.clone(), columns(false), None, None)
+                .unwrap_err()
+                .to_string(),
+            "Invalid Shapefile Record 2: Invalid Time in Column \"TIME\": \"yesterday\""
+        );
+        assert_eq!(
+            import_data_shapefile(path, missing, None, None)
+                .unwrap_err()
+                .to_string(),
+            "Invalid Shapefile Record 1: Missing Column \"DEPTH_M\""
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn missing_sidecar_files_are_named() {
+        let dir = temp_dir("shp_missing_sidecar");
+        let path = dir.join("points.shp");
+        write_points(&path, None, &[]);
+
+        let missing = |sidecar: PathBuf| {
+            AppError::new(
+                codes::SHAPEFILE_MISSING_SIDECAR,
+                format!("Missing Shapefile Sidecar: {} not found", sidecar.display()),
+            )
+            .with("path", sidecar.display().to_string())
+        };
+        assert_eq!(
+            import_data_shapefile(path.clone(), columns(false), None, None).unwrap_err(),
+            missing(dir.join("points.prj"))
+        );
+        std::fs::remove_file(path.with_extension("dbf")).unwrap();
+        assert_eq!(
+            import_data_shapefile(path, columns(false), None, None).unwrap_err(),
+            missing(dir.join("points.dbf"))
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn path_is_read_from_the_first_polyline() {
+        let dir = temp_dir("shp_path");
+        let path = dir.join("path.shp");
+        let mut writer = ShapeWriter::from_path(&path).unwrap();
+        writer
+            .write_shape(&Polyline::with_parts(vec![
+                vec![
+                    ShpPoint::new(500_000.0, 0.0),
+                    ShpPoint::new(500_000.0, 110_530.158_802),
+                ],
+                vec![
+                    ShpPoint::new(800_102.657_853, 348_561.937_323),
+                    ShpPoint::new(500_000.0, 0.0),
+                ],
+            ]))
+            .unwrap();
+        drop(writer);
+        std::fs::write(path.with_extension("prj"), UTM_47N_WKT).unwrap();
+
+        let imported = import_path_shapefile(path).unwrap();
+
+        let coords = &imported.path().0;
+        assert_eq!(coords.len(), 4);
+        assert_close(coords[0], 99.0, 0.0);
+        assert_close(coords[1], 99.0, 1.0);
+        assert_close(coords[2], 101.7, 3.15);
+        assert_close(coords[3], 99.0, 0.0);
+        assert!(imported.collection_points().0.is_empty());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn path_requires_a_polyline() {
+        let dir = temp_dir("shp_path_points");
+        let path = dir.join("points.shp");
+        write_points(
+            &path,
+            Some(WGS84_WKT),
+            &[(101.7, 3.15, 28.5, 0.5, "1715495400000", "surface")],
+        );
+
+        assert_eq!(
+            import_path_shapefile(path).unwrap_err(),
+            AppError::new(
+                codes::SHAPEFILE_NO_POLYLINE,
+                "Invalid Shapefile: Path shapefile requires a Polyline."
+            )
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,446 @@
+//! Replay of recorded data from the boat without the hardware.
+//!
+//! A replay reads a frame log or boat data GeoJSON, and feeds the readings through the same
+//! handling as the frames received from the boat.
+
+use std::{
+    io::{BufRead, BufReader},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Condvar, Mutex,
+    },
+    thread::JoinHandle,
+    time::Duration,
+};
+
+use serde::Serialize;
+use tauri::{AppHandle, Manager};
+
+use crate::{
+    comm_proto::babara_project::connection::Packet,
+    communication::{self, SerialConnection},
+    data::{self, BoatDataFeature},
+    errors::{codes, AppError},
+    frame_log::{Direction, FrameLogEntry},
+    protocol::FrameDecoder,
+    telemetry::{self, Telemetry},
+};
+
+/// The longest time a replay waits before checking if it is paused or stopped.
+const CHECK_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Something replayed.
+#[derive(Debug, Clone)]
+enum ReplayEvent {
+    /// The bytes of a frame received from the boat.
+    Frame(Vec<u8>),
+    /// A reading stored in boat data.
+    Reading(Box<BoatDataFeature>),
+}
+
+/// The events of a replay with their time since the start of the recording.
+type Recording = Vec<(Duration, ReplayEvent)>;
+
+/// Reads the frames received from the boat in a frame log.
+fn read_frame_log(file: &Path) -> Result<Recording, AppError> {
+    let reader = BufReader::new(std::fs::File::open(file).map_err(|e| AppError::io(e, file))?);
+    let mut recording = vec![];
+    let mut start = None;
+    for (i, line) in reader.lines().enumerate() {
+        let line = line.map_err(|e| AppError::io(e, file))?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let invalid = |e: &dyn std::fmt::Display| {
+            AppError::new(
+                codes::FRAME_LOG_INVALID,
+                format!("Invalid Frame Log: Line {}: {e}", i + 1),
+            )
+            .with("line", i + 1)
+        };
+        let entry: FrameLogEntry = serde_json::from_str(&line).map_err(|e| invalid(&e))?;
+        if entry.direction != Direction::Inbound {
+            continue;
+        }
+
+        let start = *start.get_or_insert(entry.time_ms);
+        let offset = Duration::from_secs_f64((entry.time_ms - start).max(0.0) / 1000.0);
+        let bytes = entry.bytes().map_err(|e| invalid(&e))?;
+        recording.push((offset, ReplayEvent::Frame(bytes)));
+    }
+    Ok(recording)
+}
+
+/// Reads the readings in boat data, timed by their timestamps.
+fn read_boat_data(file: &Path) -> Result<Recording, AppError> {
+    let data = data::load_data(file)?;
+    let start = match data.features().first() {
+        Some(v) => v.time(),
+        None => return Ok(vec![]),
+    };
+    Ok(data
+        .features()
+        .iter()
+        .map(|feature| {
+            let offset = (feature.time() - start).to_std().unwrap_or_default();
+            (offset, ReplayEvent::Reading(Box::new(feature.clone())))
+        })
+        .collect())
+}
+
+/// Controls of a running replay.
+#[derive(Debug, Default)]
+struct ReplayControl {
+    /// Whether the replay is paused.
+    paused: Mutex<bool>,
+    /// Notified when the replay is paused, resumed or stopped.
+    changed: Condvar,
+    /// Set to stop the replay.
+    stop: AtomicBool,
+    /// Set when the replay ends.
+    finished: AtomicBool,
+}
+
+impl ReplayControl {
+    /// Waits for `duration` of replay time, not counting the time paused.
+    ///
+    /// Returns `false` if the replay is stopped.
+    fn wait(&self, mut duration: Duration) -> bool {
+        loop {
+            if self.stop.load(Ordering::Relaxed) {
+                return false;
+            }
+            let paused = self.paused.lock().unwrap();
+            if *paused {
+                let _ = self.changed.wait_timeout(paused, CHECK_INTERVAL).unwrap();
+                continue;
+            }
+            if duration.is_zero() {
+                return true;
+            }
+
+            let step = duration.min(CHECK_INTERVAL);
+            let _ = self.changed.wait_timeout(paused, step).unwrap();
+            duration -= step;
+        }
+    }
+}
+
+/// Payload of the `replay-progress` event.
+#[derive(Debug, Serialize, Clone, Copy)]
+struct ReplayProgress {
+    /// The number of events replayed.
+    position: usize,
+    /// The number of events in the file.
+    total: usize,
+    /// The time since the start of the recording of the last event in milliseconds.
+    time_ms: u128,
+}
+
+/// Replays a recording, keeping the time between the events scaled by `speed_multiplier`.
+fn replay(
+    app_handle: AppHandle,
+    control: Arc<ReplayControl>,
+    source: String,
+    recording: Recording,
+    speed_multiplier: f64,
+) {
+    let total = recording.len();
+    let mut decoder = FrameDecoder::<Packet>::default();
+    let mut previous = Duration::ZERO;
+    for (i, (offset, event)) in recording.into_iter().enumerate() {
+        let delay = offset.saturating_sub(previous).div_f64(speed_multiplier);
+        previous = offset;
+        if !control.wait(delay) {
+            log::info!("Stopped Replay of {}", source);
+            break;
+        }
+
+        match event {
+            ReplayEvent::Frame(bytes) => {
+                decoder.push(&bytes);
+                while let Some(packet) = decoder.next_frame() {
+                    communication::dispatch_packet(&app_handle, &source, packet);
+                }
+            }
+            ReplayEvent::Reading(feature) => {
+                telemetry::record_reading(&app_handle, Telemetry::Fix(*feature));
+            }
+        }
+
+        let progress = ReplayProgress {
+            position: i + 1,
+            total,
+            time_ms: offset.as_millis(),
+        };
+        if let Err(e) = app_handle.emit_all("replay-progress", progress) {
+            log::warn!("Unable to emit replay-progress event: {}", e);
+        }
+    }
+    control.finished.store(true, Ordering::Relaxed);
+}
+
+/// A running replay.
+struct Replay {
+    control: Arc<ReplayControl>,
+    thread: JoinHandle<()>,
+}
+
+/// The replay of recorded data.
+#[derive(Default)]
+pub struct ReplayState {
+    replay: Mutex<Option<Replay>>,
+}
+
+impl ReplayState {
+    /// Checks if a replay is running.
+    pub fn is_running(&self) -> bool {
+        self.replay
+            .lock()
+            .unwrap()
+            .as_ref()
+            .map_or(false, |replay| {
+                !replay.control.finished.load(Ordering::Relaxed)
+            })
+    }
+
+    /// Gets the controls of the running replay.
+    fn control(&self) -> Result<Arc<ReplayControl>, AppError> {
+        self.replay
+            .lock()
+            .unwrap()
+            .as_ref()
+            .filter(|replay| !replay.control.finished.load(Ordering::Relaxed))
+            .map(|replay| replay.control.clone())
+            .ok_or_else(not_running)
+    }
+}
+
+/// Creates the error of no replay running.
+fn not_running() -> AppError {
+    AppError::new(codes::REPLAY_NOT_RUNNING, "No Replay Running")
+}
+
+/// Start replaying a frame log or boat data GeoJSON as if it is received from the boat.
+///
+/// Frame logs have the `ndjson` extension. The time between the readings is divided by
+/// `speed_multiplier`. Returns the number of events to replay.
+#[tauri::command]
+pub fn start_replay(
+    app_handle: AppHandle,
+    state: tauri::State<ReplayState>,
+    file: PathBuf,
+    speed_multiplier: f64,
+) -> Result<usize, AppError> {
+    log::debug!("Replaying: {}", file.display());
+    if !(speed_multiplier.is_finite() && speed_multiplier > 0.0) {
+        return Err(AppError::new(
+            codes::REPLAY_INVALID_SPEED,
+            format!("Invalid Speed Multiplier: {speed_multiplier} must be greater than 0"),
+        )
+        .with("speed_multiplier", speed_multiplier));
+    }
+    if app_handle
+        .state::<SerialConnection>()
+        .link()
+        .map_or(false, |link| link.is_open())
+    {
+        return Err(AppError::new(
+            codes::REPLAY_CONNECTED,
+            "Unable to Start Replay: Disconnect from the Boat First",
+        ));
+    }
+
+    let mut current = state.replay.lock().unwrap();
+    if current
+        .as_ref()
+        .map_or(false, |r| !r.control.finished.load(Ordering::Relaxed))
+    {
+        return Err(AppError::new(
+            codes::REPLAY_RUNNING,
+            "Unable to Start Replay: A Replay is Running",
+        ));
+    }
+
+    let recording = match file.extension().and_then(|e| e.to_str()) {
+        Some("ndjson") => read_frame_log(&file)?,
+        _ => read_boat_data(&file)?,
+    };
+    let total = recording.len();
+    let control = Arc::new(ReplayControl::default());
+    let thread = {
+        let app_handle = app_handle.clone();
+        let control = control.clone();
+        let source = file.display().to_string();
+        std::thread::spawn(move || replay(app_handle, control, source, recording, speed_multiplier))
+    };
+    *current = Some(Replay { control, thread });
+    log::info!("Replaying {} Events from {}", total, file.display());
+    Ok(total)
+}
+
+/// Pause the running replay.
+#[tauri::command]
+pub fn pause_replay(state: tauri::State<ReplayState>) -> Result<(), AppError> {
+    let control = state.control()?;
+    *control.paused.lock().unwrap() = true;
+    control.changed.notify_all();
+    Ok(())
+}
+
+/// Resume the paused replay.
+#[tauri::command]
+pub fn resume_replay(state: tauri::State<ReplayState>) -> Result<(), AppError> {
+    let control = state.control()?;
+    *control.paused.lock().unwrap() = false;
+    control.changed.notify_all();
+    Ok(())
+}
+
+/// Stop the running replay.
+#[tauri::command]
+pub fn stop_replay(state: tauri::State<ReplayState>) -> Result<(), AppError> {
+    let replay = state
+        .replay
+        .lock()
+        .unwrap()
+        .take()
+        .ok_or_else(not_running)?;
+    replay.control.stop.store(true, Ordering::Relaxed);
+    replay.control.changed.notify_all();
+    if replay.thread.join().is_err() {
+        log::warn!("Replay Thread Panicked");
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, time::Instant};
+
+    use chrono::{TimeZone, Utc};
+    use geo_types::Point;
+
+    use super::*;
+    use crate::{
+        data::{BoatData, Layer},
+        storage::tests::temp_dir,
+    };
+
+    /// Gets the times of the events in milliseconds and the frames replayed.
+    fn timeline(recording: &Recording) -> Vec<(u128, Option<Vec<u8>>)> {
+        recording
+            .iter()
+            .map(|(offset, event)| {
+                let frame = match event {
+                    ReplayEvent::Frame(bytes) => Some(bytes.clone()),
+                    ReplayEvent::Reading(_) => None,
+                };
+                (offset.as_millis(), frame)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn frame_log_replays_inbound_frames() {
+        let dir = temp_dir("replay-frame-log");
+        let file = dir.join("frames.ndjson");
+        fs::write(
+            &file,
+            concat!(
+                r#"{"time_ms":1000,"direction":"outbound","data":"ff"}"#,
+                "\n",
+                r#"{"time_ms":1500,"direction":"inbound","data":"0a01"}"#,
+                "\n\n",
+                r#"{"time_ms":2750,"direction":"inbound","data":"0b"}"#,
+                "\n",
+            ),
+        )
+        .unwrap();
+
+        let recording = read_frame_log(&file).unwrap();
+
+        assert_eq!(
+            timeline(&recording),
+            vec![(0, Some(vec![0x0a, 0x01])), (1250, Some(vec![0x0b]))]
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn invalid_frame_log_line_is_rejected() {
+        let dir = temp_dir("replay-invalid-log");
+        let file = dir.join("frames.ndjson");
+        fs::write(
+            &file,
+            "{\"time_ms\":0,\"direction\":\"inbound\",\"data\":\"0a\"}\nnot json\n",
+        )
+        .unwrap();
+
+        let error = read_frame_log(&file).unwrap_err();
+
+        assert!(
+            error.to_string().starts_with("Invalid Frame Log: Line 2: "),
+            "{error}"
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn boat_data_replays_readings_by_time() {
+        let dir = temp_dir("replay-boat-data");
+        let file = dir.join("data.geojson");
+        let features = [0, 2, 5]
+            .into_iter()
+            .map(|seconds| {
+                BoatDataFeature::new(
+                    28.5,
+                    0.5,
+                    Layer::Surface,
+                    Utc.timestamp_opt(1_715_500_000 + seconds, 0).unwrap(),
+                    Point::new(101.5, 3.1),
+                )
+            })
+            .collect();
+        let data = BoatData::new(String::from("0.1.0"), features);
+        fs::write(&file, data.to_string()).unwrap();
+
+        let recording = read_boat_data(&file).unwrap();
+
+        assert_eq!(
+            timeline(&recording),
+            vec![(0, None), (2000, None), (5000, None)]
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn wait_does_not_count_time_paused() {
+        let control = Arc::new(ReplayControl::default());
+        *control.paused.lock().unwrap() = true;
+        let resume = {
+            let control = control.clone();
+            std::thread::spawn(move || {
+                std::thread::sleep(Duration::from_millis(150));
+                *control.paused.lock().unwrap() = false;
+                control.changed.notify_all();
+            })
+        };
+
+        let start = Instant::now();
+        assert!(control.wait(Duration::from_millis(50)));
+
+        assert!(start.elapsed() >= Duration::from_millis(200));
+        resume.join().unwrap();
+    }
+
+    #[test]
+    fn wait_returns_when_stopped() {
+        let control = Arc::new(ReplayControl::default());
+        *control.paused.lock().unwrap() = true;
+        control.stop.store(true, Ordering::Relaxed);
+
+        assert!(!control.wait(Duration::from_secs(60)));
+    }
+}
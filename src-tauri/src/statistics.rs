@@ -0,0 +1,654 @@
+//! Summary statistics of boat data for the dashboard.
+
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, LocalResult, TimeZone, Utc};
+use serde::Serialize;
+use tauri::{AppHandle, Manager};
+
+use crate::{
+    app_data::AppData,
+    data::{BoatData, BoatDataFeature, Layer, Sensor},
+    errors::{codes, AppError},
+    units::{convert_feature_units, UnitSystem},
+};
+
+/// Summary statistics of boat data.
+///
+/// Data points with a non-finite temperature, depth or coordinate are not included in the
+/// statistics and are counted in `invalid_count` instead.
+#[derive(Debug, Default, Serialize, Clone, PartialEq)]
+pub struct DataStatistics {
+    /// The number of valid data points.
+    count: usize,
+    /// The number of data points skipped for having non-finite values.
+    invalid_count: usize,
+    /// The number of valid data points in each layer.
+    layers: BTreeMap<String, usize>,
+    /// The lowest temperature measured.
+    min_temperature: Option<f64>,
+    /// The highest temperature measured.
+    max_temperature: Option<f64>,
+    /// The mean of the temperatures measured.
+    mean_temperature: Option<f64>,
+    /// The median of the temperatures measured.
+    median_temperature: Option<f64>,
+    /// The shallowest depth measured at.
+    min_depth: Option<f64>,
+    /// The deepest depth measured at.
+    max_depth: Option<f64>,
+    /// The time of the first measurement.
+    first_time: Option<DateTime<Utc>>,
+    /// The time of the last measurement.
+    last_time: Option<DateTime<Utc>>,
+    /// The bounding box of the measurements as `[min_lng, min_lat, max_lng, max_lat]`.
+    bbox: Option<[f64; 4]>,
+    /// The statistics of the water quality sensors, by the name of the sensor. Only the
+    /// sensors with values are included.
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    sensors: BTreeMap<&'static str, SensorStatistics>,
+}
+
+/// Summary statistics of the values of a water quality sensor.
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct SensorStatistics {
+    /// The number of data points with a value of the sensor.
+    count: usize,
+    /// The lowest value measured.
+    min: f64,
+    /// The highest value measured.
+    max: f64,
+    /// The mean of the values measured.
+    mean: f64,
+}
+
+impl SensorStatistics {
+    /// Adds a value measured by the sensor.
+    fn add(&mut self, value: f64) {
+        self.min = self.min.min(value);
+        self.max = self.max.max(value);
+        self.count += 1;
+        self.mean += (value - self.mean) / self.count as f64;
+    }
+}
+
+impl Default for SensorStatistics {
+    fn default() -> Self {
+        Self {
+            count: 0,
+            min: f64::INFINITY,
+            max: f64::NEG_INFINITY,
+            mean: 0.0,
+        }
+    }
+}
+
+/// Checks if all the values of a data point are finite.
+fn is_valid(feature: &BoatDataFeature) -> bool {
+    feature.temperature().is_finite()
+        && feature.depth().is_finite()
+        && feature.geometry().x().is_finite()
+        && feature.geometry().y().is_finite()
+}
+
+/// Gets the smaller of two optional values.
+fn min(current: Option<f64>, value: f64) -> Option<f64> {
+    Some(current.map_or(value, |current| current.min(value)))
+}
+
+/// Gets the larger of two optional values.
+fn max(current: Option<f64>, value: f64) -> Option<f64> {
+    Some(current.map_or(value, |current| current.max(value)))
+}
+
+impl BoatData {
+    /// Computes the summary statistics of the data.
+    pub fn statistics(&self) -> DataStatistics {
+        let mut statistics = DataStatistics::default();
+        let mut temperatures = vec![];
+        let mut bbox: Option<[f64; 4]> = None;
+
+        for feature in self.features() {
+            if !is_valid(feature) {
+                statistics.invalid_count += 1;
+                continue;
+            }
+
+            statistics.count += 1;
+            *statistics
+                .layers
+                .entry(feature.layer().to_string())
+                .or_default() += 1;
+            temperatures.push(feature.temperature());
+            statistics.min_depth = min(statistics.min_depth, feature.depth());
+            statistics.max_depth = max(statistics.max_depth, feature.depth());
+            for sensor in Sensor::ALL {
+                if let Some(value) = feature.sensor(sensor).filter(|v| v.is_finite()) {
+                    statistics
+                        .sensors
+                        .entry(sensor.name())
+                        .or_default()
+                        .add(value);
+                }
+            }
+
+            let time = feature.time();
+            statistics.first_time = Some(statistics.first_time.map_or(time, |t| t.min(time)));
+            statistics.last_time = Some(statistics.last_time.map_or(time, |t| t.max(time)));
+
+            let (lng, lat) = (feature.geometry().x(), feature.geometry().y());
+            bbox = Some(match bbox {
+                Some([min_lng, min_lat, max_lng, max_lat]) => [
+                    min_lng.min(lng),
+                    min_lat.min(lat),
+                    max_lng.max(lng),
+                    max_lat.max(lat),
+                ],
+                None => [lng, lat, lng, lat],
+            });
+        }
+        statistics.bbox = bbox;
+
+        if !temperatures.is_empty() {
+            // The temperatures are finite as invalid data points are skipped
+            temperatures.sort_by(|a, b| a.partial_cmp(b).unwrap());
+            let length = temperatures.len();
+            statistics.min_temperature = Some(temperatures[0]);
+            statistics.max_temperature = Some(temperatures[length - 1]);
+            statistics.mean_temperature = Some(temperatures.iter().sum::<f64>() / length as f64);
+            statistics.median_temperature = Some(if length % 2 == 0 {
+                (temperatures[length / 2 - 1] + temperatures[length / 2]) / 2.0
+            } else {
+                temperatures[length / 2]
+            });
+        }
+        statistics
+    }
+}
+
+/// Compute the summary statistics of boat data.
+///
+/// The temperatures and depths are in `units`, metric by default.
+#[tauri::command]
+pub fn data_statistics(data: BoatData, units: Option<UnitSystem>) -> DataStatistics {
+    log::debug!("Computing Statistics");
+    statistics_in(&data, units)
+}
+
+/// Compute the summary statistics of the boat data in application storage, without sending the
+/// data from the frontend.
+///
+/// The temperatures and depths are in `units`, metric by default.
+#[tauri::command]
+pub fn data_statistics_stored(
+    app_handle: AppHandle,
+    units: Option<UnitSystem>,
+) -> Result<DataStatistics, AppError> {
+    log::debug!("Computing Statistics of Stored Data");
+    let data = app_handle.state::<AppData>().get(&app_handle)?;
+    Ok(statistics_in(&data, units))
+}
+
+/// Computes the summary statistics of boat data in `units`, metric by default.
+fn statistics_in(data: &BoatData, units: Option<UnitSystem>) -> DataStatistics {
+    match units.unwrap_or_default() {
+        UnitSystem::Metric => data.statistics(),
+        units => BoatData::new(
+            data.version().to_string(),
+            data.features()
+                .iter()
+                .map(|feature| convert_feature_units(feature, units))
+                .collect(),
+        )
+        .statistics(),
+    }
+}
+
+/// Aggregated readings measured within a time bucket.
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct Bucket {
+    /// The start of the bucket.
+    start: DateTime<Utc>,
+    /// The number of readings in the bucket.
+    count: usize,
+    /// The lowest temperature measured.
+    min_temperature: f64,
+    /// The highest temperature measured.
+    max_temperature: f64,
+    /// The mean of the temperatures measured.
+    mean_temperature: f64,
+    /// The mean of the depths measured at.
+    mean_depth: f64,
+    /// The means of the water quality sensors with values in the bucket, by the name of the
+    /// sensor.
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    sensors: BTreeMap<&'static str, f64>,
+}
+
+/// Parses the size of a bucket such as `15m`, `1h` or `1d` to milliseconds.
+///
+/// The units are `s` for seconds, `m` for minutes, `h` for hours and `d` for days.
+fn parse_bucket(bucket: &str) -> Result<i64, AppError> {
+    let bucket = bucket.trim();
+    let invalid = || {
+        AppError::new(
+            codes::STATISTICS_INVALID_BUCKET,
+            format!("Invalid Bucket: {bucket}, expected e.g. 15m, 1h or 1d"),
+        )
+        .with("bucket", bucket)
+    };
+    let unit = match bucket.chars().last() {
+        Some('s') => 1000,
+        Some('m') => 60 * 1000,
+        Some('h') => 60 * 60 * 1000,
+        Some('d') => 24 * 60 * 60 * 1000,
+        _ => return Err(invalid()),
+    };
+    let count: i64 = bucket[..bucket.len() - 1].parse().map_err(|_| invalid())?;
+    if count <= 0 {
+        return Err(invalid());
+    }
+    count.checked_mul(unit).ok_or_else(invalid)
+}
+
+/// Aggregate boat data into time buckets for charting.
+///
+/// The buckets are aligned to multiples of `bucket` since the Unix epoch in UTC, so daily
+/// buckets start at midnight UTC. Only readings at `layer` are aggregated if it is given, and
+/// readings with a non-finite temperature or depth are skipped. Buckets without readings are
+/// left out.
+#[tauri::command]
+pub fn aggregate_data(
+    data: BoatData,
+    bucket: String,
+    layer: Option<Layer>,
+) -> Result<Vec<Bucket>, AppError> {
+    log::debug!("Aggregating Data in {} Buckets", bucket);
+    let size = parse_bucket(&bucket)?;
+
+    // The sums of the temperatures and depths of each bucket, by its start in milliseconds
+    let mut sums: BTreeMap<i64, (usize, f64, f64, f64, f64)> = BTreeMap::new();
+    // The sums and counts of the values of each sensor in each bucket
+    let mut sensor_sums: BTreeMap<i64, BTreeMap<&'static str, (f64, usize)>> = BTreeMap::new();
+    for feature in data.features() {
+        if layer
+            .as_ref()
+            .map_or(false, |layer| feature.layer() != *layer)
+            || !feature.temperature().is_finite()
+            || !feature.depth().is_finite()
+        {
+            continue;
+        }
+        let start = feature.time().timestamp_millis().div_euclid(size) * size;
+        let temperature = feature.temperature();
+        let (count, min, max, temperatures, depths) =
+            sums.entry(start)
+                .or_insert((0, f64::INFINITY, f64::NEG_INFINITY, 0.0, 0.0));
+        *count += 1;
+        *min = min.min(temperature);
+        *max = max.max(temperature);
+        *temperatures += temperature;
+        *depths += feature.depth();
+        for sensor in Sensor::ALL {
+            if let Some(value) = feature.sensor(sensor).filter(|v| v.is_finite()) {
+                let (sum, count) = sensor_sums
+                    .entry(start)
+                    .or_default()
+                    .entry(sensor.name())
+                    .or_default();
+                *sum += value;
+                *count += 1;
+            }
+        }
+    }
+
+    sums.into_iter()
+        .map(|(start, (count, min, max, temperatures, depths))| {
+            let sensors = sensor_sums
+                .remove(&start)
+                .unwrap_or_default()
+                .into_iter()
+                .map(|(name, (sum, count))| (name, sum / count as f64))
+                .collect();
+            let start = match Utc.timestamp_millis_opt(start) {
+                LocalResult::Single(v) => v,
+                _ => return Err(AppError::from(format!("Invalid Bucket Start: {start}"))),
+            };
+            Ok(Bucket {
+                start,
+                count,
+                min_temperature: min,
+                max_temperature: max,
+                mean_temperature: temperatures / count as f64,
+                mean_depth: depths / count as f64,
+                sensors,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use geo_types::Point;
+
+    use super::*;
+
+    /// Creates a data point measured at `layer`, `seconds` after the start of the mission.
+    fn feature(
+        temperature: f64,
+        depth: f64,
+        layer: Layer,
+        seconds: i64,
+        lng: f64,
+    ) -> BoatDataFeature {
+        BoatDataFeature::new(
+            temperature,
+            depth,
+            layer,
+            Utc.timestamp_opt(1_715_495_400 + seconds, 0).unwrap(),
+            Point::new(lng, 3.1),
+        )
+    }
+
+    /// Creates boat data with `features`.
+    fn data(features: Vec<BoatDataFeature>) -> BoatData {
+        BoatData::new(BoatData::default().version().to_string(), features)
+    }
+
+    #[test]
+    fn statistics_of_empty_data_are_empty() {
+        assert_eq!(data(vec![]).statistics(), DataStatistics::default());
+    }
+
+    #[test]
+    fn statistics_summarise_valid_points() {
+        let statistics = data(vec![
+            feature(30.0, 2.0, Layer::Middle, 60, 101.6),
+            feature(28.0, 0.5, Layer::Surface, 0, 101.5),
+            feature(29.0, 1.0, Layer::Surface, 120, 101.4),
+            feature(31.0, 3.0, Layer::SeaBed, 30, 101.7),
+        ])
+        .statistics();
+
+        assert_eq!(statistics.count, 4);
+        assert_eq!(statistics.invalid_count, 0);
+        assert_eq!(
+            statistics.layers,
+            BTreeMap::from([
+                (Layer::Middle.to_string(), 1),
+                (Layer::SeaBed.to_string(), 1),
+                (Layer::Surface.to_string(), 2),
+            ])
+        );
+        assert_eq!(statistics.min_temperature, Some(28.0));
+        assert_eq!(statistics.max_temperature, Some(31.0));
+        assert_eq!(statistics.mean_temperature, Some(29.5));
+        assert_eq!(statistics.median_temperature, Some(29.5));
+        assert_eq!(statistics.min_depth, Some(0.5));
+        assert_eq!(statistics.max_depth, Some(3.0));
+        assert_eq!(
+            statistics.first_time,
+            Some(Utc.timestamp_opt(1_715_495_400, 0).unwrap())
+        );
+        assert_eq!(
+            statistics.last_time,
+            Some(Utc.timestamp_opt(1_715_495_520, 0).unwrap())
+        );
+        assert_eq!(statistics.bbox, Some([101.4, 3.1, 101.7, 3.1]));
+    }
+
+    #[test]
+    fn statistics_median_of_odd_count_is_middle_value() {
+        let statistics = data(vec![
+            feature(31.0, 1.0, Layer::Surface, 0, 101.5),
+            feature(28.0, 1.0, Layer::Surface, 1, 101.5),
+            feature(29.0, 1.0, Layer::Surface, 2, 101.5),
+        ])
+        .statistics();
+
+        assert_eq!(statistics.median_temperature, Some(29.0));
+    }
+
+    #[test]
+    fn statistics_skip_non_finite_values() {
+        let statistics = data(vec![
+            feature(28.0, 0.5, Layer::Surface, 0, 101.5),
+            feature(f64::NAN, 0.5, Layer::Surface, 1, 101.5),
+            feature(29.0, f64::INFINITY, Layer::Surface, 2, 101.5),
+            feature(50.0, 9.0, Layer::SeaBed, 3, f64::NEG_INFINITY),
+        ])
+        .statistics();
+
+        assert_eq!(statistics.count, 1);
+        assert_eq!(statistics.invalid_count, 3);
+        assert_eq!(statistics.max_temperature, Some(28.0));
+        assert_eq!(statistics.max_depth, Some(0.5));
+        assert_eq!(statistics.bbox, Some([101.5, 3.1, 101.5, 3.1]));
+    }
+
+    #[test]
+    fn statistics_are_in_the_requested_units() {
+        let data = data(vec![
+            feature(0.0, 0.3048, Layer::Surface, 0, 101.5),
+            feature(100.0, 3.048, Layer::Surface, 1, 101.5),
+        ]);
+
+        assert_eq!(
+            data_statistics(data.clone(), None),
+            data_statistics(data.clone(), Some(UnitSystem::Metric))
+        );
+        let statistics = data_statistics(data, Some(UnitSystem::Imperial));
+        assert_eq!(statistics.min_temperature, Some(32.0));
+        assert_eq!(statistics.max_temperature, Some(212.0));
+        assert_eq!(statistics.mean_temperature, Some(122.0));
+        assert!((statistics.min_depth.unwrap() - 1.0).abs() < 1e-9);
+        assert!((statistics.max_depth.unwrap() - 10.0).abs() < 1e-9);
+        assert_eq!(statistics.count, 2);
+    }
+
+    /// Creates a data point at the surface measured at a time in UTC.
+    fn at(temperature: f64, depth: f64, time: &str) -> BoatDataFeature {
+        BoatDataFeature::new(
+            temperature,
+            depth,
+            Layer::Surface,
+            time.parse().unwrap(),
+            Point::new(101.5, 3.1),
+        )
+    }
+
+    /// Gets the start times of buckets in RFC 3339.
+    fn starts(buckets: &[Bucket]) -> Vec<String> {
+        buckets
+            .iter()
+            .map(|b| b.start.format("%Y-%m-%dT%H:%M:%SZ").to_string())
+            .collect()
+    }
+
+    #[test]
+    fn bucket_sizes_are_parsed() {
+        assert_eq!(parse_bucket("30s"), Ok(30_000));
+        assert_eq!(parse_bucket("15m"), Ok(900_000));
+        assert_eq!(parse_bucket(" 1h "), Ok(3_600_000));
+        assert_eq!(parse_bucket("2d"), Ok(172_800_000));
+        for bucket in ["", "h", "0m", "-1h", "1.5h", "1w", "1 h", "1H", "15µ"] {
+            assert_eq!(
+                parse_bucket(bucket),
+                Err(AppError::new(
+                    codes::STATISTICS_INVALID_BUCKET,
+                    format!(
+                        "Invalid Bucket: {}, expected e.g. 15m, 1h or 1d",
+                        bucket.trim()
+                    )
+                )
+                .with("bucket", bucket.trim())),
+                "{bucket}"
+            );
+        }
+        assert!(parse_bucket(&format!("{}d", i64::MAX)).is_err());
+    }
+
+    #[test]
+    fn buckets_are_aligned_to_utc() {
+        let data = data(vec![
+            at(28.0, 1.0, "2024-05-12T06:31:00Z"),
+            at(30.0, 3.0, "2024-05-12T06:44:59Z"),
+            at(29.0, 2.0, "2024-05-12T06:45:00Z"),
+        ]);
+
+        let buckets = aggregate_data(data, String::from("15m"), None).unwrap();
+
+        assert_eq!(
+            starts(&buckets),
+            vec!["2024-05-12T06:30:00Z", "2024-05-12T06:45:00Z"]
+        );
+        assert_eq!(
+            buckets[0],
+            Bucket {
+                start: buckets[0].start,
+                count: 2,
+                min_temperature: 28.0,
+                max_temperature: 30.0,
+                mean_temperature: 29.0,
+                mean_depth: 2.0,
+                sensors: BTreeMap::new(),
+            }
+        );
+        assert_eq!(buckets[1].count, 1);
+    }
+
+    #[test]
+    fn daily_buckets_split_at_midnight_utc() {
+        let data = data(vec![
+            at(28.0, 1.0, "2024-05-12T23:59:59.999Z"),
+            at(29.0, 1.0, "2024-05-13T00:00:00Z"),
+            at(30.0, 1.0, "2024-05-13T07:30:00+08:00"),
+        ]);
+
+        let buckets = aggregate_data(data, String::from("1d"), None).unwrap();
+
+        assert_eq!(
+            starts(&buckets),
+            vec!["2024-05-12T00:00:00Z", "2024-05-13T00:00:00Z"]
+        );
+        // 07:30 in Kuala Lumpur is 23:30 UTC the day before
+        assert_eq!(buckets[0].count, 2);
+        assert_eq!(buckets[1].count, 1);
+    }
+
+    #[test]
+    fn empty_buckets_are_omitted() {
+        let data = data(vec![
+            at(28.0, 1.0, "2024-05-12T06:00:00Z"),
+            at(29.0, 1.0, "2024-05-12T09:10:00Z"),
+        ]);
+
+        let buckets = aggregate_data(data, String::from("1h"), None).unwrap();
+
+        assert_eq!(
+            starts(&buckets),
+            vec!["2024-05-12T06:00:00Z", "2024-05-12T09:00:00Z"]
+        );
+    }
+
+    #[test]
+    fn bucket_larger_than_the_data_has_every_reading() {
+        let data = data(vec![
+            at(28.0, 1.0, "2024-05-12T06:00:00Z"),
+            at(29.0, 1.0, "2024-05-14T09:10:00Z"),
+        ]);
+
+        let buckets = aggregate_data(data, String::from("3650d"), None).unwrap();
+
+        assert_eq!(buckets.len(), 1);
+        assert_eq!(buckets[0].count, 2);
+        assert!(buckets[0].start <= "2024-05-12T06:00:00Z".parse::<DateTime<Utc>>().unwrap());
+    }
+
+    #[test]
+    fn aggregation_filters_layer_and_invalid_values() {
+        let data = data(vec![
+            feature(28.0, 1.0, Layer::Surface, 0, 101.5),
+            feature(40.0, 1.0, Layer::SeaBed, 1, 101.5),
+            feature(f64::NAN, 1.0, Layer::Surface, 2, 101.5),
+            feature(30.0, f64::INFINITY, Layer::Surface, 3, 101.5),
+        ]);
+
+        let surface =
+            aggregate_data(data.clone(), String::from("1h"), Some(Layer::Surface)).unwrap();
+        let all = aggregate_data(data, String::from("1h"), None).unwrap();
+
+        assert_eq!(surface.len(), 1);
+        assert_eq!((surface[0].count, surface[0].max_temperature), (1, 28.0));
+        assert_eq!((all[0].count, all[0].max_temperature), (2, 40.0));
+    }
+
+    #[test]
+    fn readings_before_the_epoch_are_bucketed_down() {
+        let data = data(vec![at(28.0, 1.0, "1969-12-31T23:59:30Z")]);
+
+        let buckets = aggregate_data(data, String::from("1m"), None).unwrap();
+
+        assert_eq!(starts(&buckets), vec!["1969-12-31T23:59:00Z"]);
+    }
+
+    #[test]
+    fn statistics_summarise_sensors_with_values() {
+        let statistics = data(vec![
+            feature(28.0, 0.5, Layer::Surface, 0, 101.5).with_sensors(Some(7.0), None, None),
+            feature(29.0, 1.0, Layer::Surface, 60, 101.5).with_sensors(Some(8.0), None, None),
+            feature(30.0, 1.5, Layer::Surface, 120, 101.5).with_sensors(
+                Some(f64::NAN),
+                Some(6.5),
+                None,
+            ),
+            feature(31.0, 2.0, Layer::Surface, 180, 101.5),
+        ])
+        .statistics();
+
+        assert_eq!(
+            statistics.sensors,
+            BTreeMap::from([
+                (
+                    "dissolved_oxygen",
+                    SensorStatistics {
+                        count: 1,
+                        min: 6.5,
+                        max: 6.5,
+                        mean: 6.5,
+                    }
+                ),
+                (
+                    "ph",
+                    SensorStatistics {
+                        count: 2,
+                        min: 7.0,
+                        max: 8.0,
+                        mean: 7.5,
+                    }
+                ),
+            ])
+        );
+        assert!(serde_json::to_value(data(vec![]).statistics())
+            .unwrap()
+            .get("sensors")
+            .is_none());
+    }
+
+    #[test]
+    fn aggregate_data_averages_sensors_per_bucket() {
+        let data = data(vec![
+            feature(28.0, 0.5, Layer::Surface, 0, 101.5).with_sensors(None, None, Some(2.0)),
+            feature(29.0, 1.0, Layer::Surface, 30, 101.5).with_sensors(None, None, Some(4.0)),
+            feature(30.0, 1.5, Layer::Surface, 90, 101.5),
+        ]);
+
+        let buckets = aggregate_data(data, String::from("1m"), None).unwrap();
+
+        let sensors: Vec<_> = buckets.into_iter().map(|bucket| bucket.sensors).collect();
+        assert_eq!(
+            sensors,
+            vec![BTreeMap::from([("turbidity", 3.0)]), BTreeMap::new()]
+        );
+    }
+}
@@ -0,0 +1,672 @@
+//! Helper functions for working with files in application storage.
+
+use std::{
+    fs::{File, OpenOptions},
+    io::{BufWriter, ErrorKind, Write},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+};
+
+use serde::Serialize;
+use tauri::{AppHandle, Manager};
+use uuid::Uuid;
+
+use crate::errors::{codes, AppError};
+
+pub mod sqlite;
+
+/// The space kept free when checking a file fits on the disk, as the file system needs room for
+/// its own records.
+const SPACE_MARGIN: u64 = 1024 * 1024;
+
+/// The directory chosen by the user to store the data in instead of the application data
+/// directory, set from the settings.
+#[derive(Debug, Default)]
+pub struct DataDirectory {
+    dir: Mutex<Option<PathBuf>>,
+}
+
+impl DataDirectory {
+    /// Sets the directory the data is stored in, `None` for the application data directory.
+    pub fn set(&self, dir: Option<PathBuf>) {
+        *self.dir.lock().unwrap() = dir;
+    }
+}
+
+/// Gets the application data directory of the platform.
+///
+/// The settings are always stored here, as they say where the rest of the data is stored.
+pub fn app_dir(app_handle: &AppHandle) -> Result<PathBuf, AppError> {
+    app_handle.path_resolver().app_data_dir().ok_or_else(|| {
+        AppError::new(
+            codes::STORAGE_NO_APP_DIR,
+            "Unable to Get App Data Directory",
+        )
+    })
+}
+
+/// Gets the directory the data is stored in, the application data directory unless the user
+/// chose another one.
+pub fn data_dir(app_handle: &AppHandle) -> Result<PathBuf, AppError> {
+    match app_handle
+        .state::<DataDirectory>()
+        .dir
+        .lock()
+        .unwrap()
+        .clone()
+    {
+        Some(v) => Ok(v),
+        None => app_dir(app_handle),
+    }
+}
+
+/// Gets the path to a file in the data directory.
+pub fn app_data_file(app_handle: &AppHandle, name: &str) -> Result<PathBuf, AppError> {
+    let mut data_dir = data_dir(app_handle)?;
+    data_dir.push(name);
+    Ok(data_dir)
+}
+
+/// The state of the directory the data is stored in.
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct StorageHealth {
+    /// The directory the data is stored in.
+    path: PathBuf,
+    /// Whether the directory exists.
+    exists: bool,
+    /// Whether files can be created in the directory.
+    writable: bool,
+    /// Why files can't be created in the directory.
+    error: Option<AppError>,
+    /// The free space available to the app in bytes, `None` if it can't be read.
+    available_bytes: Option<u64>,
+}
+
+/// Creates and removes a file in `dir` to check files can be created in it.
+fn probe(dir: &Path) -> std::io::Result<()> {
+    let file = dir.join(format!(".probe-{}.tmp", Uuid::new_v4()));
+    OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(&file)?;
+    std::fs::remove_file(&file)
+}
+
+/// Checks if an IO error is caused by the disk being full.
+fn is_disk_full(error: &std::io::Error) -> bool {
+    // ENOSPC on Unix, ERROR_HANDLE_DISK_FULL and ERROR_DISK_FULL on Windows
+    let disk_full: &[i32] = if cfg!(windows) { &[39, 112] } else { &[28] };
+    error
+        .raw_os_error()
+        .map_or(false, |code| disk_full.contains(&code))
+}
+
+/// Checks if an IO error is caused by the file system being read only or not writable by the
+/// user.
+fn is_read_only(error: &std::io::Error) -> bool {
+    // EROFS on Unix, ERROR_WRITE_PROTECT on Windows
+    let code = if cfg!(windows) { 19 } else { 30 };
+    error.kind() == ErrorKind::PermissionDenied || error.raw_os_error() == Some(code)
+}
+
+/// Creates the error of writing to `path` failing, telling the frontend if the disk is full or
+/// read only.
+fn write_error(error: std::io::Error, path: &Path) -> AppError {
+    if is_disk_full(&error) {
+        AppError::new(
+            codes::STORAGE_DISK_FULL,
+            format!("Disk Full: Unable to Write {}", path.display()),
+        )
+        .with("path", path.display().to_string())
+    } else if is_read_only(&error) {
+        AppError::new(
+            codes::STORAGE_READ_ONLY,
+            format!("Read Only: Unable to Write {}, {}", path.display(), error),
+        )
+        .with("path", path.display().to_string())
+    } else {
+        AppError::io(error, path)
+    }
+}
+
+/// Gets the directory a file is written in.
+fn parent_dir(path: &Path) -> &Path {
+    match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    }
+}
+
+/// Checks files of about `bytes` bytes in total can be written to `dir` before writing them.
+///
+/// Returns a [`STORAGE_READ_ONLY`](codes::STORAGE_READ_ONLY) error if files can't be created
+/// in the directory, and a [`STORAGE_DISK_FULL`](codes::STORAGE_DISK_FULL) error if there isn't
+/// space for them.
+fn preflight_dir(dir: &Path, bytes: u64) -> Result<(), AppError> {
+    std::fs::create_dir_all(dir).map_err(|e| write_error(e, dir))?;
+    probe(dir).map_err(|e| write_error(e, dir))?;
+    let available = match fs2::available_space(dir) {
+        Ok(v) => v,
+        Err(e) => {
+            log::warn!("Unable to Get Free Space: {}, Reason: {}", dir.display(), e);
+            return Ok(());
+        }
+    };
+    let required = bytes + SPACE_MARGIN;
+    if available < required {
+        log::warn!(
+            "Not Enough Space in: {}, {} Bytes Needed, {} Bytes Available",
+            dir.display(),
+            required,
+            available
+        );
+        return Err(AppError::new(
+            codes::STORAGE_DISK_FULL,
+            format!(
+                "Disk Full: {} needs {required} bytes but {available} bytes are free",
+                dir.display()
+            ),
+        )
+        .with("path", dir.display().to_string())
+        .with("required", required)
+        .with("available", available));
+    }
+    Ok(())
+}
+
+/// Checks a file of about `bytes` bytes can be written to `path` before writing it, see
+/// [`preflight_dir`].
+///
+/// The old file is only replaced once the new one is written, so the space of the old file
+/// isn't counted.
+pub fn preflight_write(path: &Path, bytes: u64) -> Result<(), AppError> {
+    preflight_dir(parent_dir(path), bytes)
+}
+
+/// Check the directory the data is stored in exists, can be written to and has free space.
+///
+/// Invoked at startup so the user can choose another directory before saves fail.
+#[tauri::command]
+pub fn storage_health(app_handle: AppHandle) -> Result<StorageHealth, AppError> {
+    log::debug!("Checking Storage Health");
+    let path = data_dir(&app_handle)?;
+    let exists = path.is_dir();
+    let error = if exists {
+        probe(&path).err().map(|e| write_error(e, &path))
+    } else {
+        Some(
+            AppError::new(
+                codes::IO_NOT_FOUND,
+                format!("Unable to find Data Directory: {}", path.display()),
+            )
+            .with("path", path.display().to_string()),
+        )
+    };
+    if let Some(e) = &error {
+        log::warn!("Unable to Write to the Data Directory: {}", e);
+    }
+    let available_bytes = match fs2::available_space(&path) {
+        Ok(v) => Some(v),
+        Err(e) => {
+            log::warn!(
+                "Unable to Get Free Space: {}, Reason: {}",
+                path.display(),
+                e
+            );
+            None
+        }
+    };
+    Ok(StorageHealth {
+        writable: error.is_none(),
+        path,
+        exists,
+        error,
+        available_bytes,
+    })
+}
+
+/// The number of temporary files created, so writes to the same file at the same time don't
+/// share a temporary file.
+static TEMP_FILES: AtomicU64 = AtomicU64::new(0);
+
+/// Gets the path of a new temporary file used when writing to `path`, e.g. `data.geojson.3.tmp`.
+fn temp_path(path: &Path) -> PathBuf {
+    let mut temp_path = path.as_os_str().to_owned();
+    temp_path.push(format!(
+        ".{}.tmp",
+        TEMP_FILES.fetch_add(1, Ordering::Relaxed)
+    ));
+    PathBuf::from(temp_path)
+}
+
+/// Writes to `path` without leaving a partially written file behind.
+///
+/// The content is written to a temporary file in the same directory, synced to disk and
+/// then renamed over `path`. If writing fails, the original file is left untouched. Each write
+/// has its own temporary file, so writes at the same time can't mix their content, but the
+/// last one renamed wins; writes to the boat data are ordered by the write lock of the
+/// [`autosave`](crate::autosave::AutosaveState::lock_writes).
+///
+/// Returns a [`STORAGE_DISK_FULL`](codes::STORAGE_DISK_FULL) or
+/// [`STORAGE_READ_ONLY`](codes::STORAGE_READ_ONLY) error if the file can't be written for lack
+/// of space or permissions.
+pub fn write_atomically<F>(path: &Path, write: F) -> Result<(), AppError>
+where
+    F: FnOnce(&mut dyn Write) -> std::io::Result<()>,
+{
+    let temp_path = temp_path(path);
+    log::debug!("Writing to Temporary File: {}", temp_path.display());
+
+    let result = write_temp(&temp_path, write).and_then(|_| replace(&temp_path, path));
+    if result.is_err() {
+        log::warn!(
+            "Unable to Write: {}, removing temporary file",
+            path.display()
+        );
+        let _ = std::fs::remove_file(&temp_path);
+    }
+    result.map_err(|e| write_error(e, path))
+}
+
+/// Writes and syncs the temporary file.
+fn write_temp<F>(temp_path: &Path, write: F) -> std::io::Result<()>
+where
+    F: FnOnce(&mut dyn Write) -> std::io::Result<()>,
+{
+    let mut writer = BufWriter::new(File::create(temp_path)?);
+    write(&mut writer)?;
+    writer.flush()?;
+    writer.get_ref().sync_all()
+}
+
+/// Renames the temporary file over the target file.
+fn replace(temp_path: &Path, path: &Path) -> std::io::Result<()> {
+    match std::fs::rename(temp_path, path) {
+        Ok(_) => Ok(()),
+        // Windows can refuse to replace an existing file, e.g. when it is being scanned.
+        #[cfg(windows)]
+        Err(e)
+            if path.exists()
+                && matches!(
+                    e.kind(),
+                    std::io::ErrorKind::PermissionDenied | std::io::ErrorKind::AlreadyExists
+                ) =>
+        {
+            log::warn!(
+                "Unable to Replace: {}, retrying after removal",
+                path.display()
+            );
+            std::fs::remove_file(path)?;
+            std::fs::rename(temp_path, path)
+        }
+        Err(e) => Err(e),
+    }
+}
+
+/// Checks if `name` can be used as a file name in application storage.
+///
+/// Names with path separators or starting with `.` are rejected so the file can't escape its
+/// directory.
+pub fn check_name(name: &str) -> Result<(), AppError> {
+    if name.trim().is_empty()
+        || name.starts_with('.')
+        || name.contains(['/', '\\', ':', '\0'])
+        || name.contains("..")
+    {
+        return Err(
+            AppError::new(codes::STORAGE_INVALID_NAME, format!("Invalid Name: {name}"))
+                .with("name", name),
+        );
+    }
+    Ok(())
+}
+
+/// Gets the size of a file, or of the files in a directory.
+fn size(path: &Path) -> std::io::Result<u64> {
+    let metadata = std::fs::symlink_metadata(path)?;
+    if !metadata.is_dir() {
+        return Ok(metadata.len());
+    }
+    let mut total = 0;
+    for entry in std::fs::read_dir(path)? {
+        total += size(&entry?.path())?;
+    }
+    Ok(total)
+}
+
+/// Copies a file, or a directory with its files.
+fn copy(from: &Path, to: &Path) -> std::io::Result<()> {
+    if !std::fs::symlink_metadata(from)?.is_dir() {
+        return std::fs::copy(from, to).map(|_| ());
+    }
+    std::fs::create_dir_all(to)?;
+    for entry in std::fs::read_dir(from)? {
+        let entry = entry?;
+        copy(&entry.path(), &to.join(entry.file_name()))?;
+    }
+    Ok(())
+}
+
+/// Copies the files and directories in `from` to `to`, except the ones named in `skip`,
+/// returning the paths copied.
+///
+/// Nothing is copied if one of them already exists in `to`, so no file is overwritten.
+pub fn copy_dir(from: &Path, to: &Path, skip: &[&str]) -> Result<Vec<PathBuf>, AppError> {
+    let entries = match std::fs::read_dir(from) {
+        Ok(v) => v,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(vec![]),
+        Err(e) => return Err(AppError::io(e, from)),
+    };
+    let mut paths = vec![];
+    for entry in entries {
+        let entry = entry.map_err(|e| AppError::io(e, from))?;
+        if !skip.iter().any(|name| entry.file_name() == *name) {
+            paths.push(entry.path());
+        }
+    }
+
+    let mut bytes = 0;
+    for path in &paths {
+        // The paths are read from the directory so they always have a file name
+        let target = to.join(path.file_name().unwrap());
+        if target.exists() {
+            return Err(AppError::new(
+                codes::IO_ALREADY_EXISTS,
+                format!("Unable to Copy: {} already exists", target.display()),
+            )
+            .with("path", target.display().to_string()));
+        }
+        bytes += size(path).map_err(|e| AppError::io(e, path))?;
+    }
+    preflight_dir(to, bytes)?;
+
+    for (index, path) in paths.iter().enumerate() {
+        let target = to.join(path.file_name().unwrap());
+        log::debug!("Copying {} to {}", path.display(), target.display());
+        if let Err(e) = copy(path, &target) {
+            log::warn!("Unable to Copy: {}, removing the copies", path.display());
+            for path in &paths[..=index] {
+                remove(&to.join(path.file_name().unwrap()));
+            }
+            return Err(write_error(e, &target));
+        }
+    }
+    Ok(paths)
+}
+
+/// Removes a file or a directory with its files, logging the failure.
+pub fn remove(path: &Path) {
+    let result = match std::fs::symlink_metadata(path) {
+        Ok(metadata) if metadata.is_dir() => std::fs::remove_dir_all(path),
+        Ok(_) => std::fs::remove_file(path),
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(()),
+        Err(e) => Err(e),
+    };
+    if let Err(e) = result {
+        log::warn!("Unable to Remove: {}, Reason: {}", path.display(), e);
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    /// Creates an empty directory for a test to write in.
+    pub(crate) fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("babara-test-{name}-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    /// A writer that fails after writing `remaining` bytes, like a disk filling up.
+    struct FailingWriter<'a> {
+        inner: &'a mut dyn Write,
+        remaining: usize,
+    }
+
+    impl Write for FailingWriter<'_> {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            if self.remaining == 0 {
+                return Err(std::io::Error::new(ErrorKind::Other, "Disk Full"));
+            }
+            let len = buf.len().min(self.remaining);
+            let written = self.inner.write(&buf[..len])?;
+            self.remaining -= written;
+            Ok(written)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            self.inner.flush()
+        }
+    }
+
+    /// Gets the names of the files in a directory.
+    fn file_names(dir: &Path) -> Vec<String> {
+        let mut names: Vec<String> = std::fs::read_dir(dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn write_atomically_replaces_file() {
+        let dir = temp_dir("atomic-replace");
+        let path = dir.join("data.geojson");
+        std::fs::write(&path, "old").unwrap();
+
+        write_atomically(&path, |writer| writer.write_all(b"new")).unwrap();
+
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "new");
+        assert_eq!(file_names(&dir), vec!["data.geojson"]);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn write_atomically_keeps_original_when_writer_fails() {
+        let dir = temp_dir("atomic-fail");
+        let path = dir.join("data.geojson");
+        let original = serde_json::json!({ "type": "FeatureCollection", "features": [] });
+        std::fs::write(&path, original.to_string()).unwrap();
+
+        let content = serde_json::json!({ "type": "FeatureCollection", "features": [1, 2, 3] });
+        let result = write_atomically(&path, |writer| {
+            let mut writer = FailingWriter {
+                inner: writer,
+                remaining: 16,
+            };
+            serde_json::to_writer(&mut writer, &content)?;
+            writer.flush()
+        });
+
+        assert!(result.is_err());
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            original.to_string()
+        );
+        assert_eq!(file_names(&dir), vec!["data.geojson"]);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn write_atomically_creates_nothing_when_writer_fails() {
+        let dir = temp_dir("atomic-new");
+        let path = dir.join("path.geojson");
+
+        let result = write_atomically(&path, |writer| {
+            writer.write_all(b"{\"type\":")?;
+            Err(std::io::Error::new(ErrorKind::Other, "Disk Full"))
+        });
+
+        assert_eq!(
+            result,
+            Err(AppError::new(codes::IO_OTHER, "Disk Full")
+                .with("path", path.display().to_string()))
+        );
+        assert!(file_names(&dir).is_empty());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn names_escaping_the_directory_are_rejected() {
+        for name in ["Pond", "North Pond", "pond.2024"] {
+            assert_eq!(check_name(name), Ok(()), "{name}");
+        }
+        for name in ["", " ", ".hidden", "../pond", "a/b", "a\\b", "C:", "a..b"] {
+            assert_eq!(
+                check_name(name),
+                Err(
+                    AppError::new(codes::STORAGE_INVALID_NAME, format!("Invalid Name: {name}"))
+                        .with("name", name)
+                ),
+                "{name:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn writes_use_their_own_temporary_files() {
+        let path = Path::new("data.geojson");
+
+        assert_ne!(temp_path(path), temp_path(path));
+        assert_eq!(temp_path(path).parent(), path.parent());
+    }
+
+    #[test]
+    fn write_errors_name_full_and_read_only_disks() {
+        let path = Path::new("data.geojson");
+        let disk_full = if cfg!(windows) { 112 } else { 28 };
+
+        assert_eq!(
+            write_error(std::io::Error::from_raw_os_error(disk_full), path),
+            AppError::new(
+                codes::STORAGE_DISK_FULL,
+                "Disk Full: Unable to Write data.geojson"
+            )
+            .with("path", "data.geojson")
+        );
+        let error = write_error(ErrorKind::PermissionDenied.into(), path);
+        assert_eq!(
+            serde_json::to_value(error).unwrap()["code"],
+            codes::STORAGE_READ_ONLY
+        );
+        let error = write_error(ErrorKind::NotFound.into(), path);
+        assert_eq!(
+            serde_json::to_value(error).unwrap()["code"],
+            codes::IO_NOT_FOUND
+        );
+    }
+
+    #[test]
+    fn preflight_rejects_files_larger_than_the_free_space() {
+        let dir = temp_dir("preflight");
+        let path = dir.join("data.geojson");
+
+        assert_eq!(preflight_write(&path, 1024), Ok(()));
+        let error = preflight_write(&path, u64::MAX / 2).unwrap_err();
+        let error = serde_json::to_value(error).unwrap();
+        assert_eq!(error["code"], codes::STORAGE_DISK_FULL);
+        assert_eq!(error["params"]["path"], dir.display().to_string());
+        // The probe file is removed
+        assert!(file_names(&dir).is_empty());
+        remove(&dir);
+    }
+
+    #[test]
+    fn size_counts_the_files_in_directories() {
+        let dir = temp_dir("size");
+        std::fs::write(dir.join("a"), "12345").unwrap();
+        std::fs::create_dir(dir.join("nested")).unwrap();
+        std::fs::write(dir.join("nested").join("b"), "123").unwrap();
+
+        assert_eq!(size(&dir.join("a")).unwrap(), 5);
+        assert_eq!(size(&dir).unwrap(), 8);
+        remove(&dir);
+    }
+
+    #[test]
+    fn copy_dir_copies_all_but_the_skipped_files() {
+        let dir = temp_dir("copy-dir");
+        let (from, to) = (dir.join("from"), dir.join("to"));
+        std::fs::create_dir_all(from.join("datasets")).unwrap();
+        std::fs::write(from.join("data.geojson"), "data").unwrap();
+        std::fs::write(from.join("datasets").join("pond.geojson"), "pond").unwrap();
+        std::fs::write(from.join("settings.json"), "{}").unwrap();
+
+        let mut copied = copy_dir(&from, &to, &["settings.json"]).unwrap();
+        copied.sort();
+
+        assert_eq!(
+            copied,
+            vec![from.join("data.geojson"), from.join("datasets")]
+        );
+        assert_eq!(file_names(&to), vec!["data.geojson", "datasets"]);
+        assert_eq!(
+            std::fs::read_to_string(to.join("datasets").join("pond.geojson")).unwrap(),
+            "pond"
+        );
+        // The originals are kept until the caller removes them
+        assert_eq!(
+            file_names(&from),
+            vec!["data.geojson", "datasets", "settings.json"]
+        );
+        remove(&dir);
+    }
+
+    #[test]
+    fn copy_dir_never_overwrites_files() {
+        let dir = temp_dir("copy-dir-exists");
+        let (from, to) = (dir.join("from"), dir.join("to"));
+        std::fs::create_dir_all(&from).unwrap();
+        std::fs::create_dir_all(&to).unwrap();
+        std::fs::write(from.join("data.geojson"), "new").unwrap();
+        std::fs::write(from.join("mission.json"), "{}").unwrap();
+        std::fs::write(to.join("data.geojson"), "old").unwrap();
+
+        let target = to.join("data.geojson");
+        assert_eq!(
+            copy_dir(&from, &to, &[]),
+            Err(AppError::new(
+                codes::IO_ALREADY_EXISTS,
+                format!("Unable to Copy: {} already exists", target.display())
+            )
+            .with("path", target.display().to_string()))
+        );
+        assert_eq!(std::fs::read_to_string(&target).unwrap(), "old");
+        assert_eq!(file_names(&to), vec!["data.geojson"]);
+        remove(&dir);
+    }
+
+    #[test]
+    fn copy_dir_of_a_missing_directory_copies_nothing() {
+        let dir = temp_dir("copy-dir-missing");
+
+        assert_eq!(
+            copy_dir(&dir.join("missing"), &dir.join("to"), &[]),
+            Ok(vec![])
+        );
+        assert!(!dir.join("to").exists());
+        remove(&dir);
+    }
+
+    #[test]
+    fn remove_deletes_files_and_directories() {
+        let dir = temp_dir("remove");
+        std::fs::write(dir.join("a"), "a").unwrap();
+        std::fs::create_dir(dir.join("nested")).unwrap();
+        std::fs::write(dir.join("nested").join("b"), "b").unwrap();
+
+        remove(&dir.join("a"));
+        remove(&dir.join("nested"));
+        remove(&dir.join("missing"));
+
+        assert!(file_names(&dir).is_empty());
+        remove(&dir);
+        assert!(!dir.exists());
+    }
+}
@@ -0,0 +1,710 @@
+//! SQLite storage of boat data, an alternative to the GeoJSON file for large datasets.
+//!
+//! Each dataset is stored in a database next to its GeoJSON file. The readings are indexed by
+//! time and by an R*Tree on their coordinates, so time ranges and bounding boxes are queried
+//! without loading the whole dataset. The foreign members of the GeoJSON, e.g. the metadata of
+//! the dataset, are stored as JSON in the members table.
+
+use std::{
+    fmt::Display,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use chrono::{DateTime, TimeZone, Utc};
+use geo_types::Point;
+use serde_json::{Map, Value};
+use sqlx::{sqlite::SqliteConnectOptions, Connection, Executor, SqliteConnection};
+use tauri::{async_runtime::block_on, AppHandle, Manager};
+
+use crate::{
+    app_data::AppData,
+    autosave, backup,
+    data::{BoatData, BoatDataFeature, Layer, Sensor},
+    datasets::active_data_file,
+    errors::{codes, AppError},
+    index::DataIndex,
+    journal, migrations,
+    settings::{self, SettingsState},
+    storage::write_atomically,
+    watcher,
+};
+
+/// The extension of the database of a dataset.
+const EXTENSION: &str = "sqlite";
+
+/// The tables of the readings and their indices.
+const SCHEMA: &str = r#"
+CREATE TABLE IF NOT EXISTS readings (
+    id INTEGER PRIMARY KEY,
+    temperature REAL NOT NULL,
+    depth REAL NOT NULL,
+    layer TEXT NOT NULL,
+    time INTEGER NOT NULL,
+    lng REAL NOT NULL,
+    lat REAL NOT NULL,
+    ph REAL,
+    dissolved_oxygen REAL,
+    turbidity REAL,
+    feature_id TEXT,
+    note TEXT,
+    extra TEXT,
+    temperature_raw REAL,
+    sensor_id TEXT
+);
+CREATE INDEX IF NOT EXISTS readings_time ON readings (time);
+CREATE VIRTUAL TABLE IF NOT EXISTS readings_rtree USING rtree (id, min_lng, max_lng, min_lat, max_lat);
+CREATE TABLE IF NOT EXISTS members (
+    name TEXT PRIMARY KEY,
+    value TEXT NOT NULL
+);
+"#;
+
+/// The columns added to the readings table after it was created and their types: the water
+/// quality sensors of format 0.2, the ids and notes of the data points, the other properties
+/// of their features as JSON, and the calibration of the temperature.
+const ADDED_COLUMNS: [(&str, &str); 8] = [
+    ("ph", "REAL"),
+    ("dissolved_oxygen", "REAL"),
+    ("turbidity", "REAL"),
+    ("feature_id", "TEXT"),
+    ("note", "TEXT"),
+    ("extra", "TEXT"),
+    ("temperature_raw", "REAL"),
+    ("sensor_id", "TEXT"),
+];
+
+/// A row of the readings table.
+type ReadingRow = (
+    f64,
+    f64,
+    String,
+    i64,
+    f64,
+    f64,
+    Option<f64>,
+    Option<f64>,
+    Option<f64>,
+    Option<String>,
+    Option<String>,
+    Option<String>,
+    Option<f64>,
+    Option<String>,
+);
+
+/// The columns of the readings table in the order of `ReadingRow`.
+const COLUMNS: &str =
+    "temperature, depth, layer, time, lng, lat, ph, dissolved_oxygen, turbidity, \
+    feature_id, note, extra, temperature_raw, sensor_id";
+
+/// Checks if the boat data is stored in SQLite instead of GeoJSON.
+pub fn enabled(app_handle: &AppHandle) -> bool {
+    app_handle.state::<SettingsState>().get().sqlite_storage
+}
+
+/// Gets the path of the database of the boat data stored in `data_file`.
+pub fn database_file(data_file: &Path) -> PathBuf {
+    data_file.with_extension(EXTENSION)
+}
+
+/// Opens the database at `path`, creating it if it doesn't exist.
+async fn connect(path: &Path) -> Result<SqliteConnection, sqlx::Error> {
+    let options = SqliteConnectOptions::new()
+        .filename(path)
+        .create_if_missing(true);
+    let mut connection = SqliteConnection::connect_with(&options).await?;
+    connection.execute(SCHEMA).await?;
+
+    // Databases created before the columns were added don't have them
+    let existing: Vec<(String,)> = sqlx::query_as("SELECT name FROM pragma_table_info('readings')")
+        .fetch_all(&mut connection)
+        .await?;
+    for (column, kind) in ADDED_COLUMNS {
+        if !existing.iter().any(|(name,)| name == column) {
+            log::info!("Adding Column {} to the Readings Table", column);
+            connection
+                .execute(format!("ALTER TABLE readings ADD COLUMN {column} {kind}").as_str())
+                .await?;
+        }
+    }
+    Ok(connection)
+}
+
+/// Converts a row of the readings table to a data point.
+fn feature_from_row(row: ReadingRow) -> Result<BoatDataFeature, String> {
+    let (
+        temperature,
+        depth,
+        layer,
+        time,
+        lng,
+        lat,
+        ph,
+        dissolved_oxygen,
+        turbidity,
+        id,
+        note,
+        extra,
+        temperature_raw,
+        sensor_id,
+    ) = row;
+    let time = match Utc.timestamp_millis_opt(time) {
+        chrono::LocalResult::Single(v) => v,
+        _ => return Err(format!("Invalid Time in Database: {time}")),
+    };
+    let extra = match extra {
+        Some(v) => {
+            serde_json::from_str(&v).map_err(|e| format!("Invalid Properties in Database: {e}"))?
+        }
+        None => Map::new(),
+    };
+    Ok(BoatDataFeature::new(
+        temperature,
+        depth,
+        Layer::from_str(&layer)?,
+        time,
+        Point::new(lng, lat),
+    )
+    .with_sensors(ph, dissolved_oxygen, turbidity)
+    .with_id(id)
+    .with_note(note)
+    .with_temperature_raw(temperature_raw)
+    .with_sensor_id(sensor_id)
+    .with_extra(extra))
+}
+
+/// Inserts data points into the readings table and the R*Tree.
+async fn insert(
+    transaction: &mut SqliteConnection,
+    features: &[BoatDataFeature],
+) -> Result<(), sqlx::Error> {
+    for feature in features {
+        let point = feature.geometry();
+        let id = sqlx::query(&format!(
+            "INSERT INTO readings ({COLUMNS}) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14)"
+        ))
+        .bind(feature.temperature())
+        .bind(feature.depth())
+        .bind(feature.layer().to_string())
+        .bind(feature.time().timestamp_millis())
+        .bind(point.x())
+        .bind(point.y())
+        .bind(feature.sensor(Sensor::Ph))
+        .bind(feature.sensor(Sensor::DissolvedOxygen))
+        .bind(feature.sensor(Sensor::Turbidity))
+        .bind(feature.id())
+        .bind(feature.note())
+        .bind((!feature.extra().is_empty()).then(|| Value::Object(feature.extra().clone()).to_string()))
+        .bind(feature.temperature_raw())
+        .bind(feature.sensor_id())
+        .execute(&mut *transaction)
+        .await?
+        .last_insert_rowid();
+
+        // The R*Tree only stores finite coordinates
+        if point.x().is_finite() && point.y().is_finite() {
+            sqlx::query("INSERT INTO readings_rtree VALUES ($1, $2, $2, $3, $3)")
+                .bind(id)
+                .bind(point.x())
+                .bind(point.y())
+                .execute(&mut *transaction)
+                .await?;
+        }
+    }
+    Ok(())
+}
+
+/// Selects the data points of a query on the readings table.
+async fn select<'q>(
+    connection: &mut SqliteConnection,
+    query: sqlx::query::QueryAs<'q, sqlx::Sqlite, ReadingRow, sqlx::sqlite::SqliteArguments<'q>>,
+) -> Result<BoatData, String> {
+    let rows = query
+        .fetch_all(&mut *connection)
+        .await
+        .map_err(|e| e.to_string())?;
+    let features = rows
+        .into_iter()
+        .map(feature_from_row)
+        .collect::<Result<Vec<_>, _>>()?;
+    let mut data = BoatData::new(String::from(migrations::BOAT_DATA_VERSION), features);
+    // Readings inserted before the ids were stored don't have one
+    data.assign_ids();
+    Ok(data)
+}
+
+/// Creates the error of the database at `path` failing.
+fn database_error(path: &Path, reason: impl Display) -> AppError {
+    AppError::reason(
+        codes::STORAGE_DATABASE,
+        &format!("Database Error: {}", path.display()),
+        reason,
+    )
+    .with("path", path.display().to_string())
+}
+
+/// Reads the foreign members of the boat data.
+async fn select_members(connection: &mut SqliteConnection) -> Result<Map<String, Value>, String> {
+    let rows: Vec<(String, String)> = sqlx::query_as("SELECT name, value FROM members")
+        .fetch_all(&mut *connection)
+        .await
+        .map_err(|e| e.to_string())?;
+    rows.into_iter()
+        .map(|(name, value)| {
+            serde_json::from_str(&value)
+                .map(|value| (name, value))
+                .map_err(|e| e.to_string())
+        })
+        .collect()
+}
+
+/// Reads all the boat data in the database at `path`.
+pub fn read(path: &Path) -> Result<BoatData, AppError> {
+    block_on(async {
+        let mut connection = connect(path).await.map_err(|e| e.to_string())?;
+        let sql = format!("SELECT {COLUMNS} FROM readings ORDER BY id");
+        let mut data = select(&mut connection, sqlx::query_as(&sql)).await?;
+        data.set_members(select_members(&mut connection).await?);
+        Ok(data)
+    })
+    .map_err(|e: String| database_error(path, e))
+}
+
+/// Appends data points to the database at `path`.
+pub fn append(path: &Path, features: &[BoatDataFeature]) -> Result<(), AppError> {
+    block_on(async {
+        let mut connection = connect(path).await?;
+        let mut transaction = connection.begin().await?;
+        insert(&mut transaction, features).await?;
+        transaction.commit().await
+    })
+    .map_err(|e| database_error(path, e))
+}
+
+/// Replaces the boat data in the database at `path`.
+pub fn replace(path: &Path, data: &BoatData) -> Result<(), AppError> {
+    block_on(async {
+        let mut connection = connect(path).await?;
+        let mut transaction = connection.begin().await?;
+        transaction
+            .execute("DELETE FROM readings; DELETE FROM readings_rtree; DELETE FROM members;")
+            .await?;
+        insert(&mut transaction, data.features()).await?;
+        for (name, value) in data.members() {
+            sqlx::query("INSERT INTO members (name, value) VALUES ($1, $2)")
+                .bind(name)
+                .bind(value.to_string())
+                .execute(&mut *transaction)
+                .await?;
+        }
+        transaction.commit().await
+    })
+    .map_err(|e| database_error(path, e))
+}
+
+/// Replaces the boat data in the database at `path`, backing up the data it replaces into
+/// `backup_dir` first.
+///
+/// The backup is written as GeoJSON like the backups of the GeoJSON file, so it is listed and
+/// restored the same way. Nothing is backed up if the database doesn't exist yet.
+pub fn save(
+    path: &Path,
+    data: &BoatData,
+    backup_dir: &Path,
+    max_backups: usize,
+) -> Result<(), AppError> {
+    if path.exists() {
+        backup::backup_data(&read(path)?, backup_dir, max_backups)?;
+    }
+    replace(path, data)
+}
+
+/// Reads the boat data measured within a time range using the time index.
+pub fn read_time_range(
+    path: &Path,
+    start: Option<DateTime<Utc>>,
+    end: Option<DateTime<Utc>>,
+) -> Result<BoatData, AppError> {
+    block_on(async {
+        let mut connection = connect(path).await.map_err(|e| e.to_string())?;
+        let sql =
+            format!("SELECT {COLUMNS} FROM readings WHERE time >= $1 AND time <= $2 ORDER BY id");
+        let query = sqlx::query_as(&sql)
+            .bind(start.map_or(i64::MIN, |t| t.timestamp_millis()))
+            .bind(end.map_or(i64::MAX, |t| t.timestamp_millis()));
+        select(&mut connection, query).await
+    })
+    .map_err(|e: String| database_error(path, e))
+}
+
+/// Reads the boat data inside a bounding box using the R*Tree.
+pub fn read_bbox(
+    path: &Path,
+    min_lng: f64,
+    min_lat: f64,
+    max_lng: f64,
+    max_lat: f64,
+) -> Result<BoatData, AppError> {
+    block_on(async {
+        let mut connection = connect(path).await.map_err(|e| e.to_string())?;
+        // The R*Tree stores rounded coordinates, so the exact coordinates are checked again
+        let sql = format!(
+            "SELECT {COLUMNS} FROM readings WHERE id IN (SELECT id FROM readings_rtree WHERE min_lng <= $3 AND max_lng >= $1 AND min_lat <= $4 AND max_lat >= $2) AND lng BETWEEN $1 AND $3 AND lat BETWEEN $2 AND $4 ORDER BY id"
+        );
+        let query = sqlx::query_as(&sql)
+            .bind(min_lng)
+            .bind(min_lat)
+            .bind(max_lng)
+            .bind(max_lat);
+        select(&mut connection, query).await
+    })
+    .map_err(|e: String| database_error(path, e))
+}
+
+/// Copy the boat data of the active dataset into SQLite storage and use it from now on.
+///
+/// The data in the database is replaced with the data in the GeoJSON file and its journal. The
+/// GeoJSON file is kept, so SQLite storage can be disabled again. Returns the number of data
+/// points copied.
+#[tauri::command]
+pub fn migrate_to_sqlite(app_handle: AppHandle) -> Result<usize, AppError> {
+    let data_file = active_data_file(&app_handle)?;
+    let database = database_file(&data_file);
+    log::info!("Migrating Data to SQLite: {}", database.display());
+    autosave::flush(&app_handle)?;
+
+    let data = journal::read_with_journal(&data_file)?;
+    replace(&database, &data)?;
+    settings::update(&app_handle, serde_json::json!({ "sqlite_storage": true }))?;
+    app_handle.state::<DataIndex>().rebuild(&data);
+    app_handle.state::<AppData>().replace(data.clone());
+    log::info!("Migrated {} Data Points to SQLite", data.features().len());
+    Ok(data.features().len())
+}
+
+/// Write the boat data of the active dataset in SQLite storage back to its GeoJSON file and
+/// stop using SQLite storage.
+///
+/// The database is kept. Returns the number of data points written.
+#[tauri::command]
+pub fn export_sqlite_to_geojson(app_handle: AppHandle) -> Result<usize, AppError> {
+    let data_file = active_data_file(&app_handle)?;
+    let database = database_file(&data_file);
+    log::info!("Exporting SQLite Data: {}", database.display());
+    autosave::flush(&app_handle)?;
+    if !database.exists() {
+        return Err(AppError::new(
+            codes::IO_NOT_FOUND,
+            format!("Unable to find Database: {}", database.display()),
+        )
+        .with("path", database.display().to_string()));
+    }
+
+    let data = read(&database)?;
+    write_atomically(&data_file, |file| write!(file, "{}", data))?;
+    watcher::note_write(&app_handle, &data_file);
+    journal::clear_journal(&data_file)?;
+    settings::update(&app_handle, serde_json::json!({ "sqlite_storage": false }))?;
+    app_handle.state::<DataIndex>().rebuild(&data);
+    app_handle.state::<AppData>().replace(data.clone());
+    log::info!("Exported {} Data Points to GeoJSON", data.features().len());
+    Ok(data.features().len())
+}
+
+/// Reads the boat data stored for `data_file` from its database.
+///
+/// The GeoJSON file and its journal are read instead if the dataset hasn't been migrated.
+pub fn read_stored(data_file: &Path) -> Result<BoatData, AppError> {
+    let database = database_file(data_file);
+    if !database.exists() {
+        log::warn!(
+            "Unable to find Database: {}, reading GeoJSON instead",
+            database.display()
+        );
+        return journal::read_with_journal(data_file);
+    }
+    read(&database)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+    use crate::storage::tests::temp_dir;
+
+    /// Creates a data point measured `seconds` after the first reading at `lng`.
+    fn feature(seconds: i64, lng: f64) -> BoatDataFeature {
+        BoatDataFeature::new(
+            28.5,
+            0.5,
+            Layer::Surface,
+            Utc.timestamp_opt(1_715_495_400 + seconds, 0).unwrap(),
+            Point::new(lng, 3.1),
+        )
+    }
+
+    /// Creates boat data with a data point at each of `lngs`, a minute apart, with the ids
+    /// given to the data points when they are read.
+    fn data(lngs: &[f64]) -> BoatData {
+        let features = lngs
+            .iter()
+            .enumerate()
+            .map(|(i, lng)| feature(60 * i as i64, *lng))
+            .collect();
+        let mut data = BoatData::new(String::from(migrations::BOAT_DATA_VERSION), features);
+        data.assign_ids();
+        data
+    }
+
+    #[test]
+    fn replaced_data_is_read_back() {
+        let dir = temp_dir("sqlite_replace");
+        let path = dir.join("data.sqlite");
+        let data = data(&[101.5, 101.6, 101.7]);
+
+        replace(&path, &BoatData::default()).unwrap();
+        replace(&path, &data).unwrap();
+        assert_eq!(read(&path).unwrap(), data);
+
+        replace(&path, &BoatData::default()).unwrap();
+        assert!(read(&path).unwrap().features().is_empty());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn appended_data_points_follow_the_stored_ones() {
+        let dir = temp_dir("sqlite_append");
+        let path = dir.join("data.sqlite");
+        let all = data(&[101.5, 101.6, 101.7]);
+
+        replace(
+            &path,
+            &BoatData::new(all.version().to_string(), all.features()[..1].to_vec()),
+        )
+        .unwrap();
+        append(&path, &all.features()[1..]).unwrap();
+
+        assert_eq!(read(&path).unwrap(), all);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn time_range_includes_its_bounds() {
+        let dir = temp_dir("sqlite_time_range");
+        let path = dir.join("data.sqlite");
+        let data = data(&[101.5, 101.6, 101.7, 101.8]);
+        replace(&path, &data).unwrap();
+        let time = |i: usize| data.features()[i].time();
+
+        let range = read_time_range(&path, Some(time(1)), Some(time(2))).unwrap();
+        assert_eq!(range.features(), &data.features()[1..3]);
+        let from = read_time_range(&path, Some(time(2)), None).unwrap();
+        assert_eq!(from.features(), &data.features()[2..]);
+        assert_eq!(read_time_range(&path, None, None).unwrap(), data);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn bbox_uses_the_exact_coordinates() {
+        let dir = temp_dir("sqlite_bbox");
+        let path = dir.join("data.sqlite");
+        // The R*Tree rounds to 32-bit floats, so 101.600_000_1 may share a box with 101.6
+        let data = data(&[101.5, 101.6, 101.600_000_1, f64::INFINITY, 101.7]);
+        replace(&path, &data).unwrap();
+
+        let inside = read_bbox(&path, 101.55, 3.0, 101.6, 3.2).unwrap();
+
+        assert_eq!(inside.features(), &data.features()[1..2]);
+        assert_eq!(read(&path).unwrap().features().len(), 5);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    /// Creates a row of the readings table without the optional values.
+    fn row(layer: &str, time: i64) -> ReadingRow {
+        (
+            28.5,
+            0.5,
+            layer.to_string(),
+            time,
+            101.5,
+            3.1,
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+        )
+    }
+
+    #[test]
+    fn invalid_rows_are_rejected() {
+        assert_eq!(
+            feature_from_row(row("surface", i64::MAX)),
+            Err(format!("Invalid Time in Database: {}", i64::MAX))
+        );
+        assert!(feature_from_row(row("", 0)).is_err());
+    }
+
+    #[test]
+    fn stored_data_falls_back_to_geojson() {
+        let dir = temp_dir("sqlite_read_stored");
+        let data_file = dir.join("data.geojson");
+        let geojson = data(&[101.5]);
+        fs::write(&data_file, geojson.to_string()).unwrap();
+
+        assert_eq!(read_stored(&data_file).unwrap(), geojson);
+
+        let stored = data(&[101.6, 101.7]);
+        replace(&database_file(&data_file), &stored).unwrap();
+        assert_eq!(read_stored(&data_file).unwrap(), stored);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn saving_backs_up_the_replaced_data() {
+        let dir = temp_dir("sqlite_save");
+        let path = dir.join("data.sqlite");
+        let backups = dir.join("backups");
+        let old = data(&[101.5]);
+        let new = data(&[101.6, 101.7]);
+
+        // Nothing to back up before the database exists
+        save(&path, &old, &backups, 10).unwrap();
+        assert!(!backups.exists());
+
+        save(&path, &new, &backups, 10).unwrap();
+        assert_eq!(read(&path).unwrap(), new);
+        let names: Vec<_> = fs::read_dir(&backups)
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .collect();
+        assert_eq!(names.len(), 1);
+        let backup = BoatData::from_str(&fs::read_to_string(&names[0]).unwrap()).unwrap();
+        assert_eq!(backup, old);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn saving_without_backups_writes_none() {
+        let dir = temp_dir("sqlite_save_no_backups");
+        let path = dir.join("data.sqlite");
+        let backups = dir.join("backups");
+
+        save(&path, &data(&[101.5]), &backups, 0).unwrap();
+        save(&path, &data(&[101.6]), &backups, 0).unwrap();
+
+        assert!(!backups.exists());
+        assert_eq!(read(&path).unwrap(), data(&[101.6]));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn sensor_values_are_stored() {
+        let dir = temp_dir("sqlite_sensors");
+        let path = dir.join("data.sqlite");
+        let mut data = BoatData::new(
+            String::from(migrations::BOAT_DATA_VERSION),
+            vec![
+                feature(0, 101.5).with_sensors(Some(7.25), Some(6.5), Some(3.5)),
+                feature(60, 101.6).with_sensors(None, Some(6.0), None),
+                feature(120, 101.7),
+            ],
+        );
+        data.assign_ids();
+
+        replace(&path, &data).unwrap();
+
+        assert_eq!(read(&path).unwrap(), data);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn databases_without_sensor_columns_gain_them() {
+        let dir = temp_dir("sqlite_add_sensor_columns");
+        let path = dir.join("data.sqlite");
+        block_on(async {
+            let options = SqliteConnectOptions::new()
+                .filename(&path)
+                .create_if_missing(true);
+            let mut connection = SqliteConnection::connect_with(&options).await.unwrap();
+            connection
+                .execute(
+                    "CREATE TABLE readings (id INTEGER PRIMARY KEY, temperature REAL NOT NULL, \
+                     depth REAL NOT NULL, layer TEXT NOT NULL, time INTEGER NOT NULL, \
+                     lng REAL NOT NULL, lat REAL NOT NULL);
+                     INSERT INTO readings (temperature, depth, layer, time, lng, lat) \
+                     VALUES (28.5, 0.5, 'surface', 1715495400000, 101.5, 3.1);",
+                )
+                .await
+                .unwrap();
+        });
+
+        assert_eq!(read(&path).unwrap(), data(&[101.5]));
+        let sensors = feature(60, 101.6)
+            .with_sensors(Some(7.25), None, None)
+            .with_random_id();
+        append(&path, std::slice::from_ref(&sensors)).unwrap();
+        assert_eq!(read(&path).unwrap().features()[1], sensors);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn foreign_members_are_stored() {
+        let dir = temp_dir("sqlite_members");
+        let path = dir.join("data.sqlite");
+        let mut data = data(&[101.5]);
+        let mut members = Map::new();
+        members.insert(String::from("site_name"), Value::from("Blue Lake"));
+        members.insert(String::from("water_level_m"), Value::from(12.5));
+        data.set_members(members);
+
+        replace(&path, &data).unwrap();
+        assert_eq!(read(&path).unwrap(), data);
+
+        replace(&path, &BoatData::default()).unwrap();
+        assert!(read(&path).unwrap().members().is_empty());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn unknown_properties_are_stored() {
+        let dir = temp_dir("sqlite_extra");
+        let path = dir.join("data.sqlite");
+        let extra = serde_json::json!({ "salinity": 0.2, "station": { "name": "A1" } });
+        let mut data = BoatData::new(
+            String::from(migrations::BOAT_DATA_VERSION),
+            vec![feature(0, 101.5).with_extra(extra.as_object().unwrap().clone())],
+        );
+        data.assign_ids();
+
+        replace(&path, &data).unwrap();
+
+        assert_eq!(read(&path).unwrap(), data);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn calibrated_temperatures_are_stored() {
+        let dir = temp_dir("sqlite_calibration");
+        let path = dir.join("data.sqlite");
+        let mut data = BoatData::new(
+            String::from(migrations::BOAT_DATA_VERSION),
+            vec![
+                feature(0, 101.5)
+                    .with_temperature_raw(Some(27.25))
+                    .with_sensor_id(Some(String::from("T1"))),
+                feature(60, 101.6),
+            ],
+        );
+        data.assign_ids();
+
+        replace(&path, &data).unwrap();
+
+        assert_eq!(read(&path).unwrap(), data);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
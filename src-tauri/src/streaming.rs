@@ -0,0 +1,416 @@
+//! Streaming reads of boat data GeoJSON, so a huge file is parsed one data point at a time
+//! instead of being read into a string first.
+//!
+//! The version of the format is written after the features, so the file is read twice: first
+//! for the version, skipping over the features, then for the features, migrating each one as
+//! it is parsed. The other foreign members, e.g. the metadata of the dataset, are kept.
+
+use std::{cell::Cell, fmt, io::Read};
+
+use serde::{
+    de::{self, DeserializeSeed, IgnoredAny, MapAccess, SeqAccess, Visitor},
+    Deserialize, Deserializer,
+};
+use serde_json::{Map, Value};
+
+use crate::{
+    data::{self, BoatData, BoatDataFeature},
+    errors::{codes, AppError},
+    migrations::{self, Migration},
+};
+
+/// The members of the feature collection read for its version, the features are skipped.
+#[derive(Debug, Deserialize)]
+struct Header {
+    #[serde(rename = "type")]
+    kind: Option<String>,
+    version: Option<Value>,
+}
+
+/// The index of the first invalid feature and the reason it is invalid.
+type InvalidFeature = Cell<Option<(usize, String)>>;
+
+/// Deserializes the features array, converting each feature to a data point as it is parsed.
+#[derive(Clone, Copy)]
+struct FeaturesSeed<'a> {
+    migrations: &'a [Migration],
+    invalid: &'a InvalidFeature,
+}
+
+impl<'de> DeserializeSeed<'de> for FeaturesSeed<'_> {
+    type Value = Vec<BoatDataFeature>;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        deserializer.deserialize_seq(self)
+    }
+}
+
+impl<'de> Visitor<'de> for FeaturesSeed<'_> {
+    type Value = Vec<BoatDataFeature>;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        write!(formatter, "an array of features")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        let mut features = Vec::with_capacity(seq.size_hint().unwrap_or(0));
+        loop {
+            let index = features.len();
+            let feature = match seq.next_element::<geojson::Feature>() {
+                Ok(Some(v)) => v,
+                Ok(None) => break,
+                Err(e) => {
+                    self.invalid.set(Some((index, e.to_string())));
+                    return Err(e);
+                }
+            };
+            let feature = migrations::migrate_feature(feature, self.migrations);
+            match data::read_feature(feature) {
+                Ok(v) => features.push(v),
+                Err(e) => {
+                    self.invalid.set(Some((index, e.to_string())));
+                    return Err(de::Error::custom(e));
+                }
+            }
+        }
+        Ok(features)
+    }
+}
+
+/// The features of the collection and its foreign members.
+type Collection = (Vec<BoatDataFeature>, Map<String, Value>);
+
+/// Deserializes the feature collection, keeping its features and foreign members.
+struct CollectionSeed<'a>(FeaturesSeed<'a>);
+
+impl<'de> DeserializeSeed<'de> for CollectionSeed<'_> {
+    type Value = Collection;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        deserializer.deserialize_map(self)
+    }
+}
+
+impl<'de> Visitor<'de> for CollectionSeed<'_> {
+    type Value = Collection;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        write!(formatter, "a feature collection")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+        let mut features = None;
+        let mut members = Map::new();
+        while let Some(key) = map.next_key::<String>()? {
+            match key.as_str() {
+                "features" => features = Some(map.next_value_seed(self.0)?),
+                "type" | "bbox" => {
+                    map.next_value::<IgnoredAny>()?;
+                }
+                _ => {
+                    members.insert(key, map.next_value()?);
+                }
+            }
+        }
+        let features = features.ok_or_else(|| de::Error::missing_field("features"))?;
+        Ok((features, members))
+    }
+}
+
+/// Converts an error of reading the JSON.
+fn json_error(error: serde_json::Error) -> AppError {
+    if error.is_io() {
+        AppError::new(codes::IO_OTHER, error.to_string())
+    } else {
+        AppError::new(codes::DATA_INVALID_JSON, error.to_string())
+    }
+}
+
+/// Reads the version of boat data GeoJSON.
+pub fn read_version(reader: impl Read) -> Result<String, AppError> {
+    let header: Header = serde_json::from_reader(reader).map_err(json_error)?;
+    if header.kind.as_deref() != Some("FeatureCollection") {
+        return Err(AppError::new(
+            codes::DATA_INVALID_GEOJSON,
+            "Invalid GeoJSON",
+        ));
+    }
+    match header.version {
+        Some(Value::String(version)) => Ok(version),
+        Some(_) => Err(AppError::new(
+            codes::DATA_INVALID_VERSION,
+            "Invalid Boat Data GeoJSON: Invalid Version",
+        )),
+        None => Err(AppError::new(
+            codes::DATA_MISSING_VERSION,
+            "Invalid Boat Data GeoJSON: Missing Version",
+        )),
+    }
+}
+
+/// Reads the features of boat data GeoJSON of version `version`, migrating them to the current
+/// format.
+///
+/// Only one feature is kept besides the data points parsed. Reading stops at the first invalid
+/// feature, which is reported by its index.
+pub fn read_boat_data(reader: impl Read, version: &str) -> Result<BoatData, AppError> {
+    let migrations = migrations::boat_data_migrations(version)?;
+    let invalid = InvalidFeature::default();
+    let seed = CollectionSeed(FeaturesSeed {
+        migrations: &migrations,
+        invalid: &invalid,
+    });
+
+    let mut deserializer = serde_json::Deserializer::from_reader(reader);
+    let features = seed
+        .deserialize(&mut deserializer)
+        .and_then(|features| deserializer.end().map(|_| features));
+    let (features, members) = match (features, invalid.take()) {
+        (Ok(collection), _) => collection,
+        (Err(e), _) if e.is_io() => return Err(json_error(e)),
+        (Err(_), Some((index, reason))) => {
+            return Err(AppError::new(
+                codes::DATA_INVALID_FEATURES,
+                format!("Invalid Boat Data GeoJSON: Invalid Data Feature {index}: {reason}"),
+            )
+            .with("index", index))
+        }
+        (Err(e), None) => return Err(json_error(e)),
+    };
+    log::info!("Read {} Features", features.len());
+
+    let mut data = BoatData::new(String::from(migrations::BOAT_DATA_VERSION), features);
+    data.set_members(members);
+    data.assign_ids();
+    Ok(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        alloc::{GlobalAlloc, Layout, System},
+        fs::File,
+        io::{BufReader, BufWriter, Write},
+        path::Path,
+    };
+
+    use super::*;
+    use crate::storage::tests::temp_dir;
+
+    /// Counts the bytes allocated by each thread, so tests running at the same time don't
+    /// count each other's allocations.
+    struct CountingAllocator;
+
+    thread_local! {
+        /// The bytes allocated by the thread that aren't freed.
+        static ALLOCATED: Cell<isize> = const { Cell::new(0) };
+        /// The most bytes allocated by the thread at once since the count was reset.
+        static PEAK: Cell<isize> = const { Cell::new(0) };
+    }
+
+    /// Records `bytes` more allocated by the thread, negative when freed.
+    fn count(bytes: isize) {
+        let _ = ALLOCATED.try_with(|allocated| {
+            let total = allocated.get() + bytes;
+            allocated.set(total);
+            let _ = PEAK.try_with(|peak| peak.set(peak.get().max(total)));
+        });
+    }
+
+    unsafe impl GlobalAlloc for CountingAllocator {
+        unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+            count(layout.size() as isize);
+            System.alloc(layout)
+        }
+
+        unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+            count(-(layout.size() as isize));
+            System.dealloc(ptr, layout)
+        }
+
+        unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+            // The old and new memory are both used while it is copied
+            count(new_size as isize);
+            let new_ptr = System.realloc(ptr, layout, new_size);
+            count(-(layout.size() as isize));
+            new_ptr
+        }
+    }
+
+    #[global_allocator]
+    static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+    /// Runs `f`, returning its result and the most bytes it allocated at once.
+    fn peak_allocated<T>(f: impl FnOnce() -> T) -> (T, usize) {
+        ALLOCATED.with(|allocated| allocated.set(0));
+        PEAK.with(|peak| peak.set(0));
+        let value = f();
+        (value, PEAK.with(Cell::get).max(0) as usize)
+    }
+
+    /// Writes boat data GeoJSON of `features` data points, with the version after the features
+    /// like the app writes it.
+    fn write_geojson(path: &Path, features: usize) {
+        let mut file = BufWriter::new(File::create(path).unwrap());
+        write!(file, "{{\"type\":\"FeatureCollection\",\"features\":[").unwrap();
+        for i in 0..features {
+            if i > 0 {
+                write!(file, ",").unwrap();
+            }
+            write!(
+                file,
+                "{{\"type\":\"Feature\",\"id\":\"point-{i}\",\
+                \"geometry\":{{\"type\":\"Point\",\"coordinates\":[101.5,{}]}},\
+                \"properties\":{{\"temperature\":{},\"depth\":0.5,\"layer\":\"surface\",\
+                \"time\":\"2024-05-12T06:30:00Z\"}}}}",
+                3.1 + i as f64 * 1e-6,
+                25.0 + (i % 100) as f64 / 10.0
+            )
+            .unwrap();
+        }
+        write!(file, "],\"version\":\"0.2.0\"}}").unwrap();
+        file.flush().unwrap();
+    }
+
+    /// Reads boat data GeoJSON like `import_data`.
+    fn read(path: &Path) -> Result<BoatData, AppError> {
+        let version = read_version(BufReader::new(File::open(path).unwrap()))?;
+        read_boat_data(BufReader::new(File::open(path).unwrap()), &version)
+    }
+
+    #[test]
+    fn read_boat_data_streams_large_file() {
+        const FEATURES: usize = 100_000;
+        let dir = temp_dir("streaming-large");
+        let path = dir.join("large.geojson");
+        write_geojson(&path, FEATURES);
+
+        let (data, peak) = peak_allocated(|| read(&path).unwrap());
+
+        assert_eq!(data.features().len(), FEATURES);
+        assert_eq!(data.features()[FEATURES - 1].id(), Some("point-99999"));
+        // The features vec can be twice its length while it grows, and the ids are copied
+        // while they are checked for repeats, but no more than a feature is parsed at once
+        let ceiling =
+            2 * FEATURES * std::mem::size_of::<BoatDataFeature>() + FEATURES * 128 + (4 << 20);
+        assert!(
+            peak < ceiling,
+            "Peak of {peak} bytes is above the ceiling of {ceiling} bytes"
+        );
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn read_boat_data_reports_first_invalid_feature() {
+        let dir = temp_dir("streaming-invalid");
+        let path = dir.join("invalid.geojson");
+        write_geojson(&path, 10);
+        let content = std::fs::read_to_string(&path)
+            .unwrap()
+            .replacen("\"depth\":0.5", "\"depth\":\"deep\"", 4)
+            .replacen("\"depth\":\"deep\"", "\"depth\":0.5", 3);
+        std::fs::write(&path, content).unwrap();
+
+        let error = serde_json::to_value(read(&path).unwrap_err()).unwrap();
+
+        assert_eq!(error["code"], Value::from(codes::DATA_INVALID_FEATURES));
+        assert_eq!(error["params"]["index"], Value::from(3));
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn read_version_skips_the_features() {
+        let dir = temp_dir("streaming-version");
+        let path = dir.join("data.geojson");
+        write_geojson(&path, 3);
+
+        let version = read_version(BufReader::new(File::open(&path).unwrap())).unwrap();
+        assert_eq!(version, "0.2.0");
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn read_version_rejects_other_geojson() {
+        let missing = r#"{"type":"FeatureCollection","features":[]}"#;
+        let invalid = r#"{"type":"FeatureCollection","features":[],"version":2}"#;
+        let feature = r#"{"type":"Feature","geometry":null,"properties":{}}"#;
+
+        assert_eq!(
+            read_version(missing.as_bytes()).err(),
+            Some(AppError::new(
+                codes::DATA_MISSING_VERSION,
+                "Invalid Boat Data GeoJSON: Missing Version"
+            ))
+        );
+        assert_eq!(
+            read_version(invalid.as_bytes()).err(),
+            Some(AppError::new(
+                codes::DATA_INVALID_VERSION,
+                "Invalid Boat Data GeoJSON: Invalid Version"
+            ))
+        );
+        assert_eq!(
+            read_version(feature.as_bytes()).err(),
+            Some(AppError::new(
+                codes::DATA_INVALID_GEOJSON,
+                "Invalid GeoJSON"
+            ))
+        );
+        let error = serde_json::to_value(read_version(&b"{"[..]).unwrap_err()).unwrap();
+        assert_eq!(error["code"], Value::from(codes::DATA_INVALID_JSON));
+    }
+
+    #[test]
+    fn read_boat_data_migrates_each_feature() {
+        let content = r#"{"type":"FeatureCollection","version":"0.0.1","features":[
+            {"type":"Feature","geometry":{"type":"Point","coordinates":[101.5,3.1]},
+            "properties":{"temperature":28.5,"depth":0.5,"time":"2024-05-12T06:30:00Z"}}]}"#;
+
+        let data = read_boat_data(content.as_bytes(), "0.0.1").unwrap();
+        assert_eq!(data.version(), migrations::BOAT_DATA_VERSION);
+        assert_eq!(data.features()[0].layer(), data::Layer::Surface);
+        assert!(data.features()[0].id().is_some());
+    }
+
+    #[test]
+    fn read_boat_data_matches_parsing_the_string() {
+        let dir = temp_dir("streaming-parse");
+        let path = dir.join("data.geojson");
+        write_geojson(&path, 20);
+
+        let parsed: BoatData = std::fs::read_to_string(&path).unwrap().parse().unwrap();
+        assert_eq!(read(&path).unwrap(), parsed);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn read_boat_data_requires_features() {
+        let content = r#"{"type":"FeatureCollection","version":"0.2.0"}"#;
+
+        let error =
+            serde_json::to_value(read_boat_data(content.as_bytes(), "0.2.0").unwrap_err()).unwrap();
+        assert_eq!(error["code"], Value::from(codes::DATA_INVALID_JSON));
+    }
+
+    #[test]
+    fn read_boat_data_keeps_the_foreign_members() {
+        let dir = temp_dir("streaming-members");
+        let path = dir.join("data.geojson");
+        let content = serde_json::json!({
+            "type": "FeatureCollection",
+            "bbox": [101.5, 3.1, 101.5, 3.1],
+            "site_name": "Blue Lake",
+            "features": [],
+            "version": migrations::BOAT_DATA_VERSION,
+        });
+        std::fs::write(&path, content.to_string()).unwrap();
+
+        let data = read(&path).unwrap();
+
+        assert_eq!(
+            Value::Object(data.members().clone()),
+            serde_json::json!({ "site_name": "Blue Lake" })
+        );
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
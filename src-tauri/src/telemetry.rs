@@ -0,0 +1,364 @@
+//! Decoding of the readings streamed from the boat.
+
+use std::{
+    collections::{BTreeMap, VecDeque},
+    sync::Mutex,
+};
+
+use chrono::{DateTime, Utc};
+use geo_types::Point;
+use prost::Message;
+use serde::Serialize;
+use tauri::{AppHandle, Manager};
+
+use crate::{
+    calibration::{self, Calibration},
+    data::{BoatDataFeature, Layer},
+    handshake::ConnectionInfoState,
+    journal,
+    messages::Reading,
+    recovery,
+    settings::SettingsState,
+};
+
+/// The number of readings without a GPS fix kept.
+const MAX_NO_FIX_READINGS: usize = 1000;
+
+/// A reading taken while the GPS has no fix on the position.
+#[derive(Debug, Serialize, Clone)]
+pub struct NoFixReading {
+    /// The temperature measured.
+    temperature: f64,
+    /// The depth the temperature is measured at.
+    depth: f64,
+    /// The layer the temperature is measured at.
+    layer: Layer,
+    /// The time of the reading.
+    time: DateTime<Utc>,
+    /// The temperature measured by the sensor, `None` if it isn't calibrated.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    temperature_raw: Option<f64>,
+    /// The id of the temperature sensor, `None` if the boat didn't send it.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    sensor_id: Option<String>,
+}
+
+/// A reading decoded from the boat.
+#[derive(Debug, Clone)]
+pub enum Telemetry {
+    /// A reading at a known position.
+    Fix(BoatDataFeature),
+    /// A reading without a known position.
+    NoFix(NoFixReading),
+}
+
+impl Telemetry {
+    /// Corrects the temperature with the calibration of its sensor, if there is one.
+    fn calibrate(&mut self, calibrations: &BTreeMap<String, Calibration>) {
+        match self {
+            Self::Fix(feature) => {
+                let calibration = feature.sensor_id().and_then(|id| calibrations.get(id));
+                if let Some(calibration) = calibration {
+                    calibration::recalibrate(feature, None, calibration);
+                }
+            }
+            Self::NoFix(reading) => {
+                let calibration = reading
+                    .sensor_id
+                    .as_ref()
+                    .and_then(|id| calibrations.get(id));
+                if let Some(calibration) = calibration {
+                    reading.temperature_raw = Some(reading.temperature);
+                    reading.temperature = calibration.apply(reading.temperature);
+                }
+            }
+        }
+    }
+}
+
+/// Converts a time sent by the boat from milliseconds since the Unix epoch.
+pub fn device_time(time_ms: i64) -> Result<DateTime<Utc>, String> {
+    DateTime::<Utc>::from_timestamp_millis(time_ms)
+        .ok_or(format!("Invalid Time: {time_ms} is out of range"))
+}
+
+/// Checks that a position sent by the boat is a valid coordinate.
+pub fn check_position(lat: f64, lng: f64) -> Result<(), String> {
+    if !(-90.0..=90.0).contains(&lat) {
+        return Err(format!("Invalid Position: Latitude {lat} is out of range"));
+    }
+    if !(-180.0..=180.0).contains(&lng) {
+        return Err(format!("Invalid Position: Longitude {lng} is out of range"));
+    }
+    Ok(())
+}
+
+impl TryFrom<Reading> for Telemetry {
+    type Error = String;
+
+    fn try_from(value: Reading) -> Result<Self, String> {
+        let time = device_time(value.time_ms)?;
+        let layer = Layer::from(value.layer());
+        let sensor_id = (!value.sensor_id.is_empty()).then(|| value.sensor_id.clone());
+        match value.position {
+            Some(position) if value.fix => {
+                check_position(position.latitude, position.longitude)?;
+                Ok(Self::Fix(
+                    BoatDataFeature::new(
+                        value.temperature,
+                        value.depth,
+                        layer,
+                        time,
+                        Point::new(position.longitude, position.latitude),
+                    )
+                    .with_sensor_id(sensor_id)
+                    .with_random_id(),
+                ))
+            }
+            _ => Ok(Self::NoFix(NoFixReading {
+                temperature: value.temperature,
+                depth: value.depth,
+                layer,
+                time,
+                temperature_raw: None,
+                sensor_id,
+            })),
+        }
+    }
+}
+
+/// Decodes a reading message received from the boat, correcting its time for the clock of the
+/// boat being `clock_offset_ms` milliseconds ahead.
+pub fn decode_reading(data: &[u8], clock_offset_ms: i64) -> Result<Telemetry, String> {
+    let mut reading = Reading::decode(data).map_err(|e| e.to_string())?;
+    reading.time_ms = reading.time_ms.saturating_sub(clock_offset_ms);
+    Telemetry::try_from(reading)
+}
+
+/// The readings received without a GPS fix.
+#[derive(Debug, Default)]
+pub struct TelemetryState {
+    no_fix: Mutex<VecDeque<NoFixReading>>,
+}
+
+/// Handles a reading message received from the boat, correcting its temperature with the
+/// calibration of its sensor and its time with the clock offset measured when connecting.
+pub fn handle_reading(app_handle: &AppHandle, data: &[u8]) {
+    let clock_offset_ms = app_handle.state::<ConnectionInfoState>().clock_offset_ms();
+    match decode_reading(data, clock_offset_ms) {
+        Ok(mut telemetry) => {
+            let calibrations = app_handle.state::<SettingsState>().get().calibrations;
+            telemetry.calibrate(&calibrations);
+            record_reading(app_handle, telemetry)
+        }
+        Err(e) => log::warn!("Received an Invalid Reading: {}", e),
+    }
+}
+
+/// Records a reading from the boat.
+///
+/// Readings with a position are added to the boat data and emitted on `reading-received`, while
+/// readings without a GPS fix are kept separately and emitted on `reading-nofix`.
+pub fn record_reading(app_handle: &AppHandle, telemetry: Telemetry) {
+    match telemetry {
+        Telemetry::Fix(feature) => {
+            if let Err(e) = recovery::record(app_handle, &feature) {
+                log::warn!("Unable to Write Reading to the Recovery Journal: {}", e);
+            }
+            if let Err(e) = journal::append_features(app_handle.clone(), vec![feature.clone()]) {
+                log::warn!("Unable to Store Reading: {}", e);
+            }
+            if let Err(e) = app_handle.emit_all("reading-received", geojson::Feature::from(feature))
+            {
+                log::warn!("Unable to emit reading-received event: {}", e);
+            }
+        }
+        Telemetry::NoFix(reading) => {
+            log::debug!("Received a Reading without a GPS Fix");
+            {
+                let state = app_handle.state::<TelemetryState>();
+                let mut no_fix = state.no_fix.lock().unwrap();
+                no_fix.push_back(reading.clone());
+                if no_fix.len() > MAX_NO_FIX_READINGS {
+                    no_fix.pop_front();
+                }
+            }
+            if let Err(e) = app_handle.emit_all("reading-nofix", reading) {
+                log::warn!("Unable to emit reading-nofix event: {}", e);
+            }
+        }
+    }
+}
+
+/// Get the readings received without a GPS fix, from oldest to newest.
+#[tauri::command]
+pub fn nofix_readings(state: tauri::State<TelemetryState>) -> Vec<NoFixReading> {
+    state.no_fix.lock().unwrap().iter().cloned().collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+    use crate::{
+        comm_proto::{babara_project::connection::Packet, google::r#type::LatLng},
+        messages::packet_type,
+        protocol::FrameDecoder,
+    };
+
+    /// Frames of readings captured from the boat: one with a fix, one without and one with a
+    /// latitude out of range.
+    const READINGS: &[u8] = include_bytes!("../tests/fixtures/readings.bin");
+
+    /// Decodes the readings in the captured frames.
+    fn captured_readings() -> Vec<Result<Telemetry, String>> {
+        let mut decoder = FrameDecoder::<Packet>::default();
+        decoder.push(READINGS);
+        std::iter::from_fn(|| decoder.next_frame())
+            .map(|packet| {
+                assert_eq!(packet.r#type, packet_type::READING);
+                decode_reading(&packet.data, 0)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn reading_with_fix_is_data_point() {
+        let readings = captured_readings();
+
+        assert_eq!(readings.len(), 3);
+        let feature = match &readings[0] {
+            Ok(Telemetry::Fix(v)) => v,
+            v => panic!("Expected a reading with a fix, got {:?}", v),
+        };
+        assert_eq!(feature.temperature(), 29.5);
+        assert_eq!(feature.depth(), 1.2);
+        assert_eq!(feature.layer(), Layer::Middle);
+        assert_eq!(
+            feature.time(),
+            Utc.with_ymd_and_hms(2024, 6, 1, 2, 0, 0).unwrap()
+        );
+        assert_eq!(feature.geometry(), Point::new(101.5, 3.1));
+    }
+
+    #[test]
+    fn reading_without_fix_is_not_placed_at_origin() {
+        let readings = captured_readings();
+
+        let reading = match &readings[1] {
+            Ok(Telemetry::NoFix(v)) => v,
+            v => panic!("Expected a reading without a fix, got {:?}", v),
+        };
+        assert_eq!(reading.temperature, 28.75);
+        assert_eq!(reading.layer, Layer::Surface);
+        assert_eq!(
+            reading.time,
+            Utc.with_ymd_and_hms(2024, 6, 1, 2, 0, 1).unwrap()
+        );
+    }
+
+    #[test]
+    fn reading_out_of_range_is_rejected() {
+        let readings = captured_readings();
+
+        assert_eq!(
+            readings[2].as_ref().unwrap_err(),
+            "Invalid Position: Latitude 95 is out of range"
+        );
+    }
+
+    #[test]
+    fn device_time_out_of_range_is_rejected() {
+        assert!(device_time(i64::MAX).is_err());
+        assert_eq!(
+            device_time(0).unwrap(),
+            Utc.with_ymd_and_hms(1970, 1, 1, 0, 0, 0).unwrap()
+        );
+    }
+
+    #[test]
+    fn fix_without_position_is_not_placed() {
+        let reading = Reading {
+            temperature: 30.0,
+            fix: true,
+            position: None,
+            ..Reading::default()
+        };
+
+        assert!(matches!(
+            Telemetry::try_from(reading),
+            Ok(Telemetry::NoFix(_))
+        ));
+    }
+
+    #[test]
+    fn readings_are_calibrated_by_their_sensor() {
+        let reading = |fix, sensor_id: &str| Reading {
+            temperature: 20.0,
+            fix,
+            position: Some(LatLng {
+                latitude: 3.1,
+                longitude: 101.5,
+            }),
+            sensor_id: String::from(sensor_id),
+            ..Reading::default()
+        };
+        let calibrations = BTreeMap::from([(
+            String::from("T1"),
+            Calibration {
+                offset: 1.0,
+                scale: 1.0,
+            },
+        )]);
+
+        let mut fix = Telemetry::try_from(reading(true, "T1")).unwrap();
+        fix.calibrate(&calibrations);
+        match fix {
+            Telemetry::Fix(feature) => {
+                assert_eq!(feature.temperature(), 21.0);
+                assert_eq!(feature.temperature_raw(), Some(20.0));
+                assert_eq!(feature.sensor_id(), Some("T1"));
+            }
+            v => panic!("Expected a reading with a fix, got {:?}", v),
+        }
+
+        let mut no_fix = Telemetry::try_from(reading(false, "T1")).unwrap();
+        no_fix.calibrate(&calibrations);
+        match no_fix {
+            Telemetry::NoFix(reading) => {
+                assert_eq!(reading.temperature, 21.0);
+                assert_eq!(reading.temperature_raw, Some(20.0));
+            }
+            v => panic!("Expected a reading without a fix, got {:?}", v),
+        }
+
+        for sensor_id in ["T2", ""] {
+            let mut other = Telemetry::try_from(reading(true, sensor_id)).unwrap();
+            other.calibrate(&calibrations);
+            match other {
+                Telemetry::Fix(feature) => {
+                    assert_eq!(feature.temperature(), 20.0);
+                    assert_eq!(feature.temperature_raw(), None);
+                }
+                v => panic!("Expected a reading with a fix, got {:?}", v),
+            }
+        }
+    }
+
+    #[test]
+    fn reading_times_are_corrected_by_the_clock_offset() {
+        let mut decoder = FrameDecoder::<Packet>::default();
+        decoder.push(READINGS);
+        let packet = decoder.next_frame().unwrap();
+
+        let feature = match decode_reading(&packet.data, 90_000) {
+            Ok(Telemetry::Fix(v)) => v,
+            v => panic!("Expected a reading with a fix, got {:?}", v),
+        };
+        assert_eq!(
+            feature.time(),
+            Utc.with_ymd_and_hms(2024, 6, 1, 1, 58, 30).unwrap()
+        );
+    }
+}
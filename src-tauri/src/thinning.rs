@@ -0,0 +1,253 @@
+//! Thinning of boat data so the map only draws as many readings as can be told apart.
+
+use std::collections::HashMap;
+
+use geo_types::Coord;
+use serde::Serialize;
+
+use crate::{
+    data::{BoatData, Layer},
+    errors::{codes, AppError},
+    geometry::Projection,
+};
+
+/// Boat data thinned for display.
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct DownsampledData {
+    /// The readings kept, in time order.
+    data: BoatData,
+    /// The number of readings before thinning.
+    original: usize,
+    /// The number of readings kept.
+    kept: usize,
+}
+
+/// Gets the cell of the grid a projected point is in.
+fn cell(point: [f64; 2], size: f64) -> (i64, i64) {
+    (
+        (point[0] / size).floor() as i64,
+        (point[1] / size).floor() as i64,
+    )
+}
+
+/// Thin boat data to readings at least `min_spacing_m` meters apart.
+///
+/// The readings are visited in time order, and a reading is kept only if it is farther than
+/// `min_spacing_m` from every reading kept before it, so the same readings are kept every time.
+/// With `per_layer`, the readings of each layer are thinned independently. Readings without a
+/// finite coordinate can't be drawn and are removed.
+#[tauri::command]
+pub fn downsample_data(
+    data: BoatData,
+    min_spacing_m: f64,
+    per_layer: bool,
+) -> Result<DownsampledData, AppError> {
+    log::debug!(
+        "Downsampling {} Readings to {}m Spacing",
+        data.features().len(),
+        min_spacing_m
+    );
+    if !min_spacing_m.is_finite() || min_spacing_m < 0.0 {
+        return Err(AppError::new(
+            codes::THINNING_INVALID_SPACING,
+            format!("Invalid Spacing: {min_spacing_m}"),
+        )
+        .with("min_spacing_m", min_spacing_m));
+    }
+
+    let original = data.features().len();
+    let mut features: Vec<_> = data
+        .features()
+        .iter()
+        .filter(|f| f.geometry().x().is_finite() && f.geometry().y().is_finite())
+        .collect();
+    // A stable sort keeps readings at the same time in their stored order
+    features.sort_by_key(|f| f.time());
+
+    let coords: Vec<Coord<f64>> = features.iter().map(|f| f.geometry().into()).collect();
+    let projection = Projection::new(&coords);
+
+    // The kept readings in each cell of a grid of `min_spacing_m` cells, so only the readings
+    // in the neighbouring cells are compared
+    let mut grid: HashMap<(Option<Layer>, i64, i64), Vec<[f64; 2]>> = HashMap::new();
+    let mut kept = vec![];
+    for (feature, coord) in features.into_iter().zip(coords) {
+        let point = projection.forward(coord);
+        if min_spacing_m == 0.0 {
+            kept.push(feature.clone());
+            continue;
+        }
+
+        let layer = if per_layer {
+            Some(feature.layer())
+        } else {
+            None
+        };
+        let (x, y) = cell(point, min_spacing_m);
+        let near = (x - 1..=x + 1)
+            .flat_map(|x| (y - 1..=y + 1).map(move |y| (x, y)))
+            .filter_map(|(x, y)| grid.get(&(layer.clone(), x, y)))
+            .flatten()
+            .any(|other| (point[0] - other[0]).hypot(point[1] - other[1]) <= min_spacing_m);
+        if near {
+            continue;
+        }
+        grid.entry((layer, x, y)).or_default().push(point);
+        kept.push(feature.clone());
+    }
+
+    log::info!("Kept {} of {} Readings", kept.len(), original);
+    Ok(DownsampledData {
+        kept: kept.len(),
+        data: BoatData::new(data.version().to_string(), kept),
+        original,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeZone, Utc};
+    use geo_types::Point;
+
+    use super::*;
+    use crate::{data::BoatDataFeature, protocol::tests::Random};
+
+    /// The coordinate `east` meters east and `north` meters north of the origin.
+    fn offset(east: f64, north: f64) -> Coord<f64> {
+        Projection::new(&[Coord { x: 101.5, y: 3.1 }]).inverse([east, north])
+    }
+
+    /// Creates a reading at `layer` measured `seconds` after the first reading.
+    fn reading(seconds: i64, layer: Layer, coord: Coord<f64>) -> BoatDataFeature {
+        BoatDataFeature::new(
+            28.5,
+            0.5,
+            layer,
+            Utc.timestamp_opt(1_715_495_400 + seconds, 0).unwrap(),
+            Point::from(coord),
+        )
+    }
+
+    fn data(features: Vec<BoatDataFeature>) -> BoatData {
+        BoatData::new(BoatData::default().version().to_string(), features)
+    }
+
+    /// Gets the times of the readings kept, relative to the first reading.
+    fn kept_seconds(downsampled: &DownsampledData) -> Vec<i64> {
+        downsampled
+            .data
+            .features()
+            .iter()
+            .map(|f| f.time().timestamp() - 1_715_495_400)
+            .collect()
+    }
+
+    #[test]
+    fn readings_within_the_spacing_of_kept_ones_are_removed() {
+        let data = data(
+            [0.0, 6.0, 12.0, 19.0, 25.0]
+                .iter()
+                .enumerate()
+                .map(|(i, east)| reading(i as i64, Layer::Surface, offset(*east, 0.0)))
+                .collect(),
+        );
+
+        let downsampled = downsample_data(data, 10.0, false).unwrap();
+
+        assert_eq!(kept_seconds(&downsampled), vec![0, 2, 4]);
+        assert_eq!((downsampled.original, downsampled.kept), (5, 3));
+    }
+
+    #[test]
+    fn readings_are_visited_in_time_order() {
+        let readings = vec![
+            reading(2, Layer::Surface, offset(5.0, 0.0)),
+            reading(0, Layer::Surface, offset(0.0, 0.0)),
+            reading(1, Layer::Surface, offset(0.0, 20.0)),
+        ];
+        let mut reversed = readings.clone();
+        reversed.reverse();
+
+        let downsampled = downsample_data(data(readings), 10.0, false).unwrap();
+
+        assert_eq!(kept_seconds(&downsampled), vec![0, 1]);
+        assert_eq!(
+            downsample_data(data(reversed), 10.0, false).unwrap(),
+            downsampled
+        );
+    }
+
+    #[test]
+    fn layers_are_thinned_independently() {
+        let readings = vec![
+            reading(0, Layer::Surface, offset(0.0, 0.0)),
+            reading(1, Layer::SeaBed, offset(1.0, 0.0)),
+            reading(2, Layer::SeaBed, offset(2.0, 0.0)),
+        ];
+
+        let per_layer = downsample_data(data(readings.clone()), 10.0, true).unwrap();
+        let together = downsample_data(data(readings), 10.0, false).unwrap();
+
+        assert_eq!(kept_seconds(&per_layer), vec![0, 1]);
+        assert_eq!(kept_seconds(&together), vec![0]);
+    }
+
+    #[test]
+    fn zero_spacing_keeps_every_drawable_reading() {
+        let readings = vec![
+            reading(0, Layer::Surface, offset(0.0, 0.0)),
+            reading(1, Layer::Surface, offset(0.0, 0.0)),
+            reading(
+                2,
+                Layer::Surface,
+                Coord {
+                    x: f64::NAN,
+                    y: 3.1,
+                },
+            ),
+        ];
+
+        let downsampled = downsample_data(data(readings), 0.0, false).unwrap();
+
+        assert_eq!(kept_seconds(&downsampled), vec![0, 1]);
+        assert_eq!((downsampled.original, downsampled.kept), (3, 2));
+    }
+
+    #[test]
+    fn invalid_spacing_is_rejected() {
+        for spacing in [-1.0, f64::NAN, f64::INFINITY] {
+            assert!(downsample_data(BoatData::default(), spacing, false).is_err());
+        }
+    }
+
+    #[test]
+    fn grid_keeps_the_same_readings_as_comparing_every_pair() {
+        let mut random = Random(0xB504_F333_F9DE_6484);
+        let readings: Vec<_> = (0..500)
+            .map(|i| {
+                let coord = offset(random.between(0.0, 200.0), random.between(0.0, 200.0));
+                reading(i, Layer::Surface, coord)
+            })
+            .collect();
+        let spacing = 15.0;
+
+        let coords: Vec<Coord<f64>> = readings.iter().map(|f| f.geometry().0).collect();
+        let projection = Projection::new(&coords);
+        let mut expected: Vec<[f64; 2]> = vec![];
+        let mut expected_seconds = vec![];
+        for (i, coord) in coords.iter().enumerate() {
+            let point = projection.forward(*coord);
+            if expected
+                .iter()
+                .all(|other| (point[0] - other[0]).hypot(point[1] - other[1]) > spacing)
+            {
+                expected.push(point);
+                expected_seconds.push(i as i64);
+            }
+        }
+
+        let downsampled = downsample_data(data(readings), spacing, false).unwrap();
+
+        assert_eq!(kept_seconds(&downsampled), expected_seconds);
+    }
+}
@@ -0,0 +1,372 @@
+//! Handler of the `tiles://` protocol serving map tiles from the MBTiles archives in the map
+//! directory.
+//!
+//! Tiles are requested at `tiles://localhost/<archive>/<z>/<x>/<y>.pbf`, which the webview on
+//! Windows rewrites to `https://tiles.localhost/...`. The rows are flipped for archives storing
+//! the tiles in the TMS scheme, the default for MBTiles.
+
+use std::{
+    collections::HashMap,
+    error::Error,
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+use sqlx::{sqlite::SqliteConnectOptions, Connection, SqliteConnection};
+use tauri::{
+    http::{Request, Response, ResponseBuilder},
+    AppHandle, Manager,
+};
+
+use crate::{map_assets::map_dir, storage::check_name};
+
+/// The largest zoom level of a tile.
+const MAX_ZOOM: u32 = 30;
+
+/// The time the webview can cache a tile for in seconds.
+const CACHE_MAX_AGE: u32 = 24 * 60 * 60;
+
+/// The host names of the protocol on each platform.
+const HOSTS: [&str; 2] = ["localhost", "tiles.localhost"];
+
+/// The order the rows of the tiles are stored in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Scheme {
+    /// Rows numbered from the south, as in MBTiles.
+    Tms,
+    /// Rows numbered from the north, as requested by MapLibre.
+    Xyz,
+}
+
+impl Scheme {
+    /// Parses the `scheme` in the metadata of an archive, defaulting to TMS.
+    fn from_metadata(scheme: Option<&str>) -> Self {
+        match scheme {
+            Some(scheme) if scheme.eq_ignore_ascii_case("xyz") => Self::Xyz,
+            _ => Self::Tms,
+        }
+    }
+}
+
+/// A tile requested in XYZ coordinates.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TileRequest {
+    /// The file name of the archive in the map directory.
+    archive: String,
+    /// The zoom level.
+    z: u32,
+    /// The column.
+    x: u32,
+    /// The row, numbered from the north.
+    y: u32,
+}
+
+impl TileRequest {
+    /// Gets the row of the tile in an archive storing its rows in `scheme`.
+    pub fn row(&self, scheme: Scheme) -> u32 {
+        match scheme {
+            Scheme::Xyz => self.y,
+            Scheme::Tms => (1 << self.z) - 1 - self.y,
+        }
+    }
+}
+
+/// Parses a coordinate of a tile, removing the extension of the last coordinate.
+fn parse_coordinate(value: &str, name: &str) -> Result<u32, String> {
+    let value = value.split('.').next().unwrap_or_default();
+    value
+        .parse()
+        .map_err(|_| format!("Invalid Tile Request: Invalid {name} {value}"))
+}
+
+/// Parses the tile requested from the URL of a request.
+///
+/// Both `tiles://localhost/<archive>/...` and `https://tiles.localhost/<archive>/...` are
+/// accepted, as well as `tiles://<archive>/...` where the archive is the host.
+pub fn parse_tile_url(url: &str) -> Result<TileRequest, String> {
+    let rest = url
+        .split_once("://")
+        .map_or(url, |(_, rest)| rest)
+        .split(['?', '#'])
+        .next()
+        .unwrap_or_default();
+    let mut parts: Vec<&str> = rest.split('/').filter(|part| !part.is_empty()).collect();
+    if parts.first().map_or(false, |host| HOSTS.contains(host)) {
+        parts.remove(0);
+    }
+
+    let (archive, z, x, y) = match parts[..] {
+        [archive, z, x, y] => (archive, z, x, y),
+        _ => return Err(format!("Invalid Tile Request: {url}")),
+    };
+    check_name(archive)?;
+    let z = parse_coordinate(z, "Zoom")?;
+    let x = parse_coordinate(x, "Column")?;
+    let y = parse_coordinate(y, "Row")?;
+    if z > MAX_ZOOM || x >= 1 << z || y >= 1 << z {
+        return Err(format!("Invalid Tile Request: {z}/{x}/{y} is out of range"));
+    }
+    Ok(TileRequest {
+        archive: archive.to_string(),
+        z,
+        x,
+        y,
+    })
+}
+
+/// Gets the content type of tiles in the `format` of an archive.
+fn content_type(format: Option<&str>) -> &'static str {
+    match format {
+        Some("png") => "image/png",
+        Some("jpg") | Some("jpeg") => "image/jpeg",
+        Some("webp") => "image/webp",
+        _ => "application/x-protobuf",
+    }
+}
+
+/// An open MBTiles archive.
+struct Archive {
+    connection: SqliteConnection,
+    /// The order the rows are stored in.
+    scheme: Scheme,
+    /// The content type of the tiles.
+    content_type: &'static str,
+}
+
+impl Archive {
+    /// Opens an archive read-only, reading its metadata.
+    async fn open(path: &Path) -> Result<Self, sqlx::Error> {
+        let options = SqliteConnectOptions::new().filename(path).read_only(true);
+        let mut connection = SqliteConnection::connect_with(&options).await?;
+        let metadata: Vec<(String, String)> =
+            sqlx::query_as("SELECT name, value FROM metadata WHERE name IN ('scheme', 'format')")
+                .fetch_all(&mut connection)
+                .await?;
+        let metadata: HashMap<String, String> = metadata.into_iter().collect();
+        Ok(Self {
+            connection,
+            scheme: Scheme::from_metadata(metadata.get("scheme").map(String::as_str)),
+            content_type: content_type(metadata.get("format").map(String::as_str)),
+        })
+    }
+
+    /// Reads a tile, returning `None` if the archive doesn't have it.
+    async fn tile(&mut self, request: &TileRequest) -> Result<Option<Vec<u8>>, sqlx::Error> {
+        let tile: Option<(Vec<u8>,)> = sqlx::query_as(
+            "SELECT tile_data FROM tiles WHERE zoom_level = $1 AND tile_column = $2 AND tile_row = $3 LIMIT 1",
+        )
+        .bind(request.z)
+        .bind(request.x)
+        .bind(request.row(self.scheme))
+        .fetch_optional(&mut self.connection)
+        .await?;
+        Ok(tile.map(|tile| tile.0))
+    }
+}
+
+/// The archives opened by the `tiles://` protocol, by their paths.
+#[derive(Default)]
+pub struct TileServer {
+    archives: Mutex<HashMap<PathBuf, Archive>>,
+}
+
+/// Builds an empty response with `status`.
+fn empty_response(status: u16) -> Result<Response, Box<dyn Error>> {
+    ResponseBuilder::new()
+        .status(status)
+        .header("Access-Control-Allow-Origin", "*")
+        .body(vec![])
+}
+
+/// Handles a request of the `tiles://` protocol.
+///
+/// Missing tiles are returned with status 204 so MapLibre treats them as empty.
+pub fn handle_request(
+    app_handle: &AppHandle,
+    request: &Request,
+) -> Result<Response, Box<dyn Error>> {
+    let tile = match parse_tile_url(request.uri()) {
+        Ok(v) => v,
+        Err(e) => {
+            log::debug!("{}", e);
+            return empty_response(400);
+        }
+    };
+    let path = map_dir(app_handle)?.join(&tile.archive);
+    if !path.exists() {
+        log::debug!("Tile Archive Not Found: {}", path.display());
+        return empty_response(404);
+    }
+
+    let state = app_handle.state::<TileServer>();
+    let mut archives = state.archives.lock().unwrap();
+    let result = tauri::async_runtime::block_on(async {
+        if !archives.contains_key(&path) {
+            let archive = Archive::open(&path).await?;
+            archives.insert(path.clone(), archive);
+        }
+        // The archive is inserted above if it isn't open
+        let archive = archives.get_mut(&path).unwrap();
+        let data = archive.tile(&tile).await?;
+        Ok::<_, sqlx::Error>(data.map(|data| (data, archive.content_type)))
+    });
+
+    let (data, content_type) = match result {
+        Ok(Some(v)) => v,
+        Ok(None) => return empty_response(204),
+        Err(e) => {
+            log::warn!("Unable to Read Tile from {}: {}", path.display(), e);
+            archives.remove(&path);
+            return empty_response(500);
+        }
+    };
+    let mut response = ResponseBuilder::new()
+        .status(200)
+        .header("Access-Control-Allow-Origin", "*")
+        .header("Content-Type", content_type)
+        .header("Cache-Control", format!("public, max-age={CACHE_MAX_AGE}"));
+    // Vector tiles are usually stored compressed
+    if data.starts_with(&[0x1f, 0x8b]) {
+        response = response.header("Content-Encoding", "gzip");
+    }
+    response.body(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use crate::storage::tests::temp_dir;
+
+    use super::*;
+
+    fn request(z: u32, x: u32, y: u32) -> TileRequest {
+        TileRequest {
+            archive: "data.mbtiles".to_string(),
+            z,
+            x,
+            y,
+        }
+    }
+
+    #[test]
+    fn tile_url_is_parsed_on_every_platform() {
+        let expected = request(14, 12998, 8026);
+
+        for url in [
+            "tiles://localhost/data.mbtiles/14/12998/8026.pbf",
+            "https://tiles.localhost/data.mbtiles/14/12998/8026.pbf",
+            "tiles://data.mbtiles/14/12998/8026.pbf",
+            "tiles://localhost/data.mbtiles/14/12998/8026.pbf?v=2#tile",
+            "tiles://localhost/data.mbtiles/14/12998/8026",
+        ] {
+            assert_eq!(parse_tile_url(url).unwrap(), expected, "{url}");
+        }
+    }
+
+    #[test]
+    fn invalid_tile_url_is_rejected() {
+        for url in [
+            "tiles://localhost/data.mbtiles/14/12998.pbf",
+            "tiles://localhost/data.mbtiles/14/12998/8026/1.pbf",
+            "tiles://localhost/data.mbtiles/z/12998/8026.pbf",
+            "tiles://localhost/data.mbtiles/14/-1/8026.pbf",
+            "tiles://localhost/../14/12998/8026.pbf",
+            "tiles://localhost/.hidden/14/12998/8026.pbf",
+        ] {
+            assert!(parse_tile_url(url).is_err(), "{url}");
+        }
+    }
+
+    #[test]
+    fn tile_outside_the_zoom_level_is_rejected() {
+        assert!(parse_tile_url("tiles://localhost/data.mbtiles/1/1/1.pbf").is_ok());
+        assert!(parse_tile_url("tiles://localhost/data.mbtiles/1/2/0.pbf").is_err());
+        assert!(parse_tile_url("tiles://localhost/data.mbtiles/1/0/2.pbf").is_err());
+        assert!(parse_tile_url("tiles://localhost/data.mbtiles/31/0/0.pbf").is_err());
+    }
+
+    #[test]
+    fn rows_are_flipped_for_tms() {
+        assert_eq!(request(0, 0, 0).row(Scheme::Tms), 0);
+        assert_eq!(request(1, 0, 0).row(Scheme::Tms), 1);
+        assert_eq!(request(14, 12998, 8026).row(Scheme::Tms), 8357);
+        assert_eq!(request(14, 12998, 8026).row(Scheme::Xyz), 8026);
+        assert_eq!(request(30, 0, 0).row(Scheme::Tms), (1 << 30) - 1);
+    }
+
+    #[test]
+    fn scheme_defaults_to_tms() {
+        assert_eq!(Scheme::from_metadata(None), Scheme::Tms);
+        assert_eq!(Scheme::from_metadata(Some("tms")), Scheme::Tms);
+        assert_eq!(Scheme::from_metadata(Some("XYZ")), Scheme::Xyz);
+        assert_eq!(Scheme::from_metadata(Some("unknown")), Scheme::Tms);
+    }
+
+    #[test]
+    fn content_type_follows_the_format() {
+        assert_eq!(content_type(None), "application/x-protobuf");
+        assert_eq!(content_type(Some("pbf")), "application/x-protobuf");
+        assert_eq!(content_type(Some("png")), "image/png");
+        assert_eq!(content_type(Some("jpg")), "image/jpeg");
+        assert_eq!(content_type(Some("webp")), "image/webp");
+    }
+
+    /// Creates an archive with a single tile at row 1 of zoom level 1.
+    async fn create_archive(path: &Path, scheme: Option<&str>) {
+        let options = SqliteConnectOptions::new()
+            .filename(path)
+            .create_if_missing(true);
+        let mut connection = SqliteConnection::connect_with(&options).await.unwrap();
+        sqlx::query("CREATE TABLE metadata (name TEXT, value TEXT)")
+            .execute(&mut connection)
+            .await
+            .unwrap();
+        sqlx::query(
+            "CREATE TABLE tiles (zoom_level INTEGER, tile_column INTEGER, tile_row INTEGER, tile_data BLOB)",
+        )
+        .execute(&mut connection)
+        .await
+        .unwrap();
+        sqlx::query("INSERT INTO tiles VALUES (1, 0, 1, x'01')")
+            .execute(&mut connection)
+            .await
+            .unwrap();
+        if let Some(scheme) = scheme {
+            sqlx::query("INSERT INTO metadata VALUES ('scheme', $1), ('format', 'png')")
+                .bind(scheme)
+                .execute(&mut connection)
+                .await
+                .unwrap();
+        }
+    }
+
+    #[test]
+    fn archive_metadata_controls_the_row_flip() {
+        let dir = temp_dir("tile-server-archive");
+        tauri::async_runtime::block_on(async {
+            let tms = dir.join("tms.mbtiles");
+            create_archive(&tms, None).await;
+            let mut archive = Archive::open(&tms).await.unwrap();
+            assert_eq!(archive.scheme, Scheme::Tms);
+            assert_eq!(archive.content_type, "application/x-protobuf");
+            assert_eq!(
+                archive.tile(&request(1, 0, 0)).await.unwrap(),
+                Some(vec![1])
+            );
+            assert_eq!(archive.tile(&request(1, 0, 1)).await.unwrap(), None);
+
+            let xyz = dir.join("xyz.mbtiles");
+            create_archive(&xyz, Some("xyz")).await;
+            let mut archive = Archive::open(&xyz).await.unwrap();
+            assert_eq!(archive.scheme, Scheme::Xyz);
+            assert_eq!(archive.content_type, "image/png");
+            assert_eq!(
+                archive.tile(&request(1, 0, 1)).await.unwrap(),
+                Some(vec![1])
+            );
+            assert_eq!(archive.tile(&request(1, 0, 0)).await.unwrap(), None);
+        });
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
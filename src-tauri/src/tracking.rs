@@ -0,0 +1,311 @@
+//! Tracking of the position of the boat.
+
+use std::{
+    collections::VecDeque,
+    path::PathBuf,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use chrono::{DateTime, Utc};
+use geo_types::LineString;
+use geojson::{Feature, GeoJson, Geometry, Value};
+use prost::Message;
+use serde::Serialize;
+use serde_json::{json, Map};
+use tauri::{AppHandle, Manager};
+
+use crate::{
+    errors::{codes, AppError},
+    messages::BoatPosition,
+    storage::write_atomically,
+    telemetry::{check_position, device_time},
+};
+
+/// The number of positions kept in the track, 10 hours at a position per second.
+const MAX_TRACK_POINTS: usize = 36_000;
+
+/// The default number of `boat-position` events emitted per second.
+const DEFAULT_EVENT_RATE: f64 = 2.0;
+
+/// A position of the boat.
+#[derive(Debug, Serialize, Clone, Copy, PartialEq)]
+pub struct TrackPoint {
+    /// The longitude of the boat.
+    lng: f64,
+    /// The latitude of the boat.
+    lat: f64,
+    /// The heading of the boat in degrees clockwise from north.
+    heading: f64,
+    /// The speed of the boat in metres per second.
+    speed: f64,
+    /// The time of the position.
+    time: DateTime<Utc>,
+}
+
+impl TryFrom<BoatPosition> for TrackPoint {
+    type Error = String;
+
+    fn try_from(value: BoatPosition) -> Result<Self, String> {
+        let position = value
+            .position
+            .ok_or(String::from("Invalid Position: Missing Coordinate"))?;
+        check_position(position.latitude, position.longitude)?;
+        Ok(Self {
+            lng: position.longitude,
+            lat: position.latitude,
+            heading: value.heading,
+            speed: value.speed,
+            time: device_time(value.time_ms)?,
+        })
+    }
+}
+
+/// The positions of the boat received.
+#[derive(Debug)]
+struct Track {
+    /// The positions from oldest to newest.
+    points: VecDeque<TrackPoint>,
+    /// The time the last `boat-position` event is emitted.
+    last_event: Option<Instant>,
+    /// The shortest time between `boat-position` events.
+    event_interval: Duration,
+}
+
+impl Default for Track {
+    fn default() -> Self {
+        Self {
+            points: VecDeque::new(),
+            last_event: None,
+            event_interval: Duration::from_secs_f64(1.0 / DEFAULT_EVENT_RATE),
+        }
+    }
+}
+
+impl Track {
+    /// Adds a position received at `now`, returning whether it should be emitted.
+    fn push(&mut self, point: TrackPoint, now: Instant) -> bool {
+        self.points.push_back(point);
+        if self.points.len() > MAX_TRACK_POINTS {
+            self.points.pop_front();
+        }
+
+        let emit = self
+            .last_event
+            .map_or(true, |last| now.duration_since(last) >= self.event_interval);
+        if emit {
+            self.last_event = Some(now);
+        }
+        emit
+    }
+
+    /// Gets the positions after `since`, or every position if not given.
+    fn since(&self, since: Option<DateTime<Utc>>) -> Vec<TrackPoint> {
+        self.points
+            .iter()
+            .filter(|point| since.map_or(true, |since| point.time > since))
+            .copied()
+            .collect()
+    }
+}
+
+/// The position and track of the boat.
+#[derive(Debug, Default)]
+pub struct TrackingState {
+    track: Mutex<Track>,
+}
+
+/// Handles a position message received from the boat.
+///
+/// The position is added to the track and emitted on `boat-position`, at most at the event rate.
+pub fn handle_position(app_handle: &AppHandle, data: &[u8]) {
+    let point = match BoatPosition::decode(data)
+        .map_err(|e| e.to_string())
+        .and_then(TrackPoint::try_from)
+    {
+        Ok(v) => v,
+        Err(e) => {
+            log::warn!("Received an Invalid Position: {}", e);
+            return;
+        }
+    };
+
+    let emit = app_handle
+        .state::<TrackingState>()
+        .track
+        .lock()
+        .unwrap()
+        .push(point, Instant::now());
+
+    if emit {
+        if let Err(e) = app_handle.emit_all("boat-position", point) {
+            log::warn!("Unable to emit boat-position event: {}", e);
+        }
+    }
+}
+
+/// Get the latest position of the boat.
+#[tauri::command]
+pub fn current_position(state: tauri::State<TrackingState>) -> Option<TrackPoint> {
+    state.track.lock().unwrap().points.back().copied()
+}
+
+/// Get the positions of the boat, from oldest to newest.
+///
+/// Only the positions after `since` are returned if given.
+#[tauri::command]
+pub fn get_track(
+    state: tauri::State<TrackingState>,
+    since: Option<DateTime<Utc>>,
+) -> Vec<TrackPoint> {
+    state.track.lock().unwrap().since(since)
+}
+
+/// Set the number of `boat-position` events emitted per second at most.
+#[tauri::command]
+pub fn set_position_event_rate(
+    state: tauri::State<TrackingState>,
+    per_second: f64,
+) -> Result<(), AppError> {
+    if !(per_second.is_finite() && per_second > 0.0) {
+        return Err(AppError::new(
+            codes::TRACKING_INVALID_EVENT_RATE,
+            format!("Invalid Event Rate: {per_second} must be greater than 0"),
+        )
+        .with("per_second", per_second));
+    }
+    state.track.lock().unwrap().event_interval = Duration::from_secs_f64(1.0 / per_second);
+    Ok(())
+}
+
+/// Converts the track to a GeoJSON feature.
+///
+/// The times of the positions are in the `times` property.
+fn to_feature(points: &[TrackPoint]) -> Feature {
+    let line: LineString<f64> = points.iter().map(|p| (p.lng, p.lat)).collect();
+    let times: Vec<_> = points.iter().map(|p| p.time).collect();
+    let mut properties = Map::new();
+    properties.insert(String::from("times"), json!(times));
+
+    Feature {
+        bbox: None,
+        geometry: Some(Geometry::new(Value::from(&line))),
+        id: None,
+        properties: Some(properties),
+        foreign_members: None,
+    }
+}
+
+/// Export the track of the boat in GeoJSON format to the file system.
+#[tauri::command]
+pub fn export_track_geojson(
+    state: tauri::State<TrackingState>,
+    path: PathBuf,
+) -> Result<(), AppError> {
+    log::debug!("Exporting Track to: {}", path.display());
+    let points: Vec<_> = state.track.lock().unwrap().points.iter().copied().collect();
+    if points.len() < 2 {
+        return Err(AppError::new(
+            codes::TRACKING_TOO_FEW_POSITIONS,
+            "Unable to Export Track: Less than 2 Positions",
+        ));
+    }
+    let geojson = GeoJson::from(to_feature(&points));
+    write_atomically(&path, |file| write!(file, "{}", geojson))
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+    use crate::comm_proto::google::r#type::LatLng;
+
+    /// Creates a position `seconds` after the start of the track.
+    fn point(seconds: i64) -> TrackPoint {
+        TrackPoint {
+            lng: 101.5 + seconds as f64 * 1e-5,
+            lat: 3.1,
+            heading: 90.0,
+            speed: 1.1,
+            time: Utc.with_ymd_and_hms(2024, 6, 1, 2, 0, 0).unwrap()
+                + chrono::Duration::seconds(seconds),
+        }
+    }
+
+    #[test]
+    fn track_keeps_latest_points() {
+        let mut track = Track::default();
+        let now = Instant::now();
+
+        for i in 0..MAX_TRACK_POINTS as i64 + 10 {
+            track.push(point(i), now);
+        }
+
+        assert_eq!(track.points.len(), MAX_TRACK_POINTS);
+        assert_eq!(track.points.front(), Some(&point(10)));
+    }
+
+    #[test]
+    fn events_are_throttled_to_rate() {
+        let mut track = Track::default();
+        let start = Instant::now();
+
+        // Positions every 100 ms at the default 2 events per second
+        let emitted: Vec<_> = (0..12)
+            .map(|i| track.push(point(i), start + Duration::from_millis(i as u64 * 100)))
+            .collect();
+
+        let expected: Vec<_> = (0..12).map(|i| i % 5 == 0).collect();
+        assert_eq!(emitted, expected);
+        assert_eq!(track.points.len(), 12);
+    }
+
+    #[test]
+    fn track_since_excludes_earlier_points() {
+        let mut track = Track::default();
+        let now = Instant::now();
+        for i in 0..5 {
+            track.push(point(i), now);
+        }
+
+        assert_eq!(track.since(None).len(), 5);
+        assert_eq!(track.since(Some(point(2).time)), vec![point(3), point(4)]);
+    }
+
+    #[test]
+    fn feature_has_line_and_times() {
+        let points = [point(0), point(1)];
+
+        let feature = serde_json::to_value(to_feature(&points)).unwrap();
+
+        assert_eq!(feature["geometry"]["type"], "LineString");
+        assert_eq!(feature["geometry"]["coordinates"][1][0], 101.50001);
+        assert_eq!(
+            feature["properties"]["times"],
+            json!(["2024-06-01T02:00:00Z", "2024-06-01T02:00:01Z"])
+        );
+    }
+
+    #[test]
+    fn position_without_coordinate_is_rejected() {
+        let position = BoatPosition {
+            time_ms: 1_717_207_200_000,
+            ..BoatPosition::default()
+        };
+        assert!(TrackPoint::try_from(position).is_err());
+
+        let position = BoatPosition {
+            position: Some(LatLng {
+                latitude: 3.1,
+                longitude: 181.0,
+            }),
+            time_ms: 1_717_207_200_000,
+            ..BoatPosition::default()
+        };
+        assert_eq!(
+            TrackPoint::try_from(position).unwrap_err(),
+            "Invalid Position: Longitude 181 is out of range"
+        );
+    }
+}
@@ -0,0 +1,422 @@
+//! Transports carrying the frames between the boat and desktop application.
+//!
+//! The boat is connected through its telemetry radio over serial, or through its WiFi bridge
+//! over TCP.
+
+use std::{
+    fmt::Display,
+    io::{ErrorKind, Read, Write},
+    net::{Shutdown, SocketAddr, TcpStream, ToSocketAddrs},
+    time::Duration,
+};
+
+use serde::Serialize;
+use serialport::SerialPort;
+use socket2::{SockRef, TcpKeepalive};
+
+use crate::{
+    comm_proto::babara_project::connection::Packet,
+    protocol::{FrameDecoder, ProtocolStats},
+};
+
+/// The time a read waits for data before checking if the reader should stop.
+pub const READ_TIMEOUT: Duration = Duration::from_millis(100);
+
+/// The time to wait for a TCP connection to the boat.
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// The idle time before TCP keepalive probes are sent.
+const KEEPALIVE_TIME: Duration = Duration::from_secs(10);
+
+/// OS error codes of an unreachable host or network.
+#[cfg(windows)]
+const UNREACHABLE_ERRORS: [i32; 2] = [10065, 10051];
+#[cfg(any(target_os = "linux", target_os = "android"))]
+const UNREACHABLE_ERRORS: [i32; 2] = [113, 101];
+#[cfg(not(any(windows, target_os = "linux", target_os = "android")))]
+const UNREACHABLE_ERRORS: [i32; 2] = [65, 51];
+
+/// A connection carrying frames to and from the boat.
+pub trait Transport: Send {
+    /// Reads the next frame, along with its bytes as received.
+    ///
+    /// Returns `None` if a complete frame is not received before the read timeout.
+    fn read_frame(&mut self) -> Result<Option<(Packet, Vec<u8>)>, String>;
+
+    /// Writes an encoded frame.
+    fn write_frame(&mut self, frame: &[u8]) -> Result<(), String>;
+
+    /// Closes the connection, including its other handles.
+    fn close(&mut self);
+
+    /// Creates another handle to the connection, e.g. for writing from another thread.
+    fn try_clone(&self) -> Result<Box<dyn Transport>, String>;
+
+    /// Gets the counts of the frames decoded.
+    fn stats(&self) -> ProtocolStats;
+
+    /// Sets whether the frames read have the CRC trailer.
+    fn set_crc(&mut self, crc: bool);
+}
+
+/// Frames read from and written to a stream of bytes.
+struct FramedStream<S> {
+    stream: S,
+    decoder: FrameDecoder<Packet>,
+    /// Whether reading nothing means the other end closed the stream.
+    closed_on_eof: bool,
+}
+
+impl<S: Read + Write> FramedStream<S> {
+    /// Creates frames on a stream.
+    fn new(stream: S, closed_on_eof: bool) -> Self {
+        Self {
+            stream,
+            decoder: FrameDecoder::default(),
+            closed_on_eof,
+        }
+    }
+
+    /// Reads the next frame from the stream.
+    fn read_frame(&mut self) -> Result<Option<(Packet, Vec<u8>)>, String> {
+        if let Some(frame) = self.decoder.next_raw_frame() {
+            return Ok(Some(frame));
+        }
+
+        let mut buf = [0; 1024];
+        match self.stream.read(&mut buf) {
+            Ok(0) if self.closed_on_eof => Err(String::from("Connection Closed by the Boat")),
+            Ok(0) => Ok(None),
+            Ok(n) => {
+                self.decoder.push(&buf[..n]);
+                Ok(self.decoder.next_raw_frame())
+            }
+            Err(e) if matches!(e.kind(), ErrorKind::TimedOut | ErrorKind::WouldBlock) => Ok(None),
+            Err(e) => Err(e.to_string()),
+        }
+    }
+
+    /// Writes an encoded frame to the stream.
+    fn write_frame(&mut self, frame: &[u8]) -> Result<(), String> {
+        self.stream
+            .write_all(frame)
+            .and_then(|_| self.stream.flush())
+            .map_err(|e| e.to_string())
+    }
+}
+
+/// Transport through a serial port, e.g. the telemetry radio.
+pub struct SerialTransport {
+    framed: FramedStream<Box<dyn SerialPort>>,
+}
+
+impl SerialTransport {
+    /// Opens a serial port.
+    pub fn open(port_name: &str, baud_rate: u32) -> Result<Self, String> {
+        let port = serialport::new(port_name, baud_rate)
+            .timeout(READ_TIMEOUT)
+            .open()
+            .map_err(|e| e.to_string())?;
+        Ok(Self {
+            framed: FramedStream::new(port, false),
+        })
+    }
+}
+
+impl Transport for SerialTransport {
+    fn read_frame(&mut self) -> Result<Option<(Packet, Vec<u8>)>, String> {
+        self.framed.read_frame()
+    }
+
+    fn write_frame(&mut self, frame: &[u8]) -> Result<(), String> {
+        self.framed.write_frame(frame)
+    }
+
+    fn close(&mut self) {
+        // The port is closed when every handle is dropped
+    }
+
+    fn try_clone(&self) -> Result<Box<dyn Transport>, String> {
+        let port = self.framed.stream.try_clone().map_err(|e| e.to_string())?;
+        Ok(Box::new(Self {
+            framed: FramedStream::new(port, false),
+        }))
+    }
+
+    fn stats(&self) -> ProtocolStats {
+        self.framed.decoder.stats()
+    }
+
+    fn set_crc(&mut self, crc: bool) {
+        self.framed.decoder.set_crc(crc);
+    }
+}
+
+/// Errors when connecting to the boat over TCP.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum TcpError {
+    /// The address can't be resolved.
+    InvalidAddress {
+        /// The address given.
+        address: String,
+        /// The reason the address can't be resolved.
+        message: String,
+    },
+    /// The host is reachable but nothing is listening on the port.
+    Refused {
+        /// The address connected to.
+        address: String,
+    },
+    /// The host or its network can't be reached.
+    Unreachable {
+        /// The address connected to.
+        address: String,
+    },
+    /// The host didn't respond in time.
+    Timeout {
+        /// The address connected to.
+        address: String,
+    },
+    /// The connection failed for another reason.
+    Io {
+        /// The address connected to.
+        address: String,
+        /// The reason the connection failed.
+        message: String,
+    },
+}
+
+impl TcpError {
+    /// Classifies the error connecting to `address`.
+    fn from_io(address: &str, error: std::io::Error) -> Self {
+        let address = address.to_string();
+        match error.kind() {
+            ErrorKind::ConnectionRefused => Self::Refused { address },
+            ErrorKind::TimedOut | ErrorKind::WouldBlock => Self::Timeout { address },
+            _ if error
+                .raw_os_error()
+                .map_or(false, |code| UNREACHABLE_ERRORS.contains(&code)) =>
+            {
+                Self::Unreachable { address }
+            }
+            _ => Self::Io {
+                address,
+                message: error.to_string(),
+            },
+        }
+    }
+}
+
+impl Display for TcpError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::InvalidAddress { address, message } => {
+                write!(f, "Invalid Address {address}: {message}")
+            }
+            Self::Refused { address } => write!(f, "Connection Refused by {address}"),
+            Self::Unreachable { address } => write!(f, "Host Unreachable: {address}"),
+            Self::Timeout { address } => write!(f, "Connection to {address} Timed Out"),
+            Self::Io { address, message } => {
+                write!(f, "Unable to Connect to {address}: {message}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for TcpError {}
+
+/// Transport through TCP, e.g. the WiFi bridge.
+pub struct TcpTransport {
+    framed: FramedStream<TcpStream>,
+}
+
+impl TcpTransport {
+    /// Connects to the boat at `host` and `port`, trying each address the host resolves to.
+    pub fn connect(host: &str, port: u16) -> Result<Self, TcpError> {
+        let address = format!("{host}:{port}");
+        let addrs: Vec<SocketAddr> = (host, port)
+            .to_socket_addrs()
+            .map_err(|e| TcpError::InvalidAddress {
+                address: address.clone(),
+                message: e.to_string(),
+            })?
+            .collect();
+
+        let mut error = TcpError::InvalidAddress {
+            address: address.clone(),
+            message: String::from("No Address Found"),
+        };
+        for addr in addrs {
+            log::debug!("Connecting to {}", addr);
+            match TcpStream::connect_timeout(&addr, CONNECT_TIMEOUT) {
+                Ok(stream) => return Self::configure(stream, &address),
+                Err(e) => error = TcpError::from_io(&address, e),
+            }
+        }
+        Err(error)
+    }
+
+    /// Sets the timeouts and keepalive of a connected stream.
+    fn configure(stream: TcpStream, address: &str) -> Result<Self, TcpError> {
+        let io_error = |e| TcpError::from_io(address, e);
+        stream
+            .set_read_timeout(Some(READ_TIMEOUT))
+            .map_err(io_error)?;
+        stream.set_nodelay(true).map_err(io_error)?;
+        SockRef::from(&stream)
+            .set_tcp_keepalive(&TcpKeepalive::new().with_time(KEEPALIVE_TIME))
+            .map_err(io_error)?;
+        Ok(Self {
+            framed: FramedStream::new(stream, true),
+        })
+    }
+}
+
+impl Transport for TcpTransport {
+    fn read_frame(&mut self) -> Result<Option<(Packet, Vec<u8>)>, String> {
+        self.framed.read_frame()
+    }
+
+    fn write_frame(&mut self, frame: &[u8]) -> Result<(), String> {
+        self.framed.write_frame(frame)
+    }
+
+    fn close(&mut self) {
+        if let Err(e) = self.framed.stream.shutdown(Shutdown::Both) {
+            log::debug!("Unable to Shut Down TCP Connection: {}", e);
+        }
+    }
+
+    fn try_clone(&self) -> Result<Box<dyn Transport>, String> {
+        let stream = self.framed.stream.try_clone().map_err(|e| e.to_string())?;
+        Ok(Box::new(Self {
+            framed: FramedStream::new(stream, true),
+        }))
+    }
+
+    fn stats(&self) -> ProtocolStats {
+        self.framed.decoder.stats()
+    }
+
+    fn set_crc(&mut self, crc: bool) {
+        self.framed.decoder.set_crc(crc);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::TcpListener;
+
+    use super::*;
+    use crate::protocol::encode_frame;
+
+    fn packet(r#type: i32) -> Packet {
+        Packet {
+            version: String::from("0.1.0"),
+            r#type,
+            data: vec![1, 2, 3],
+        }
+    }
+
+    #[test]
+    fn tcp_transport_carries_frames_both_ways() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+
+        let mut transport = TcpTransport::connect("127.0.0.1", port).unwrap();
+        let (mut boat, _) = listener.accept().unwrap();
+
+        transport
+            .write_frame(&encode_frame(&packet(17), false))
+            .unwrap();
+        let mut boat_framed = FramedStream::new(boat.try_clone().unwrap(), true);
+        boat.set_read_timeout(Some(READ_TIMEOUT)).unwrap();
+        let received = loop {
+            if let Some((packet, _)) = boat_framed.read_frame().unwrap() {
+                break packet;
+            }
+        };
+        assert_eq!(received, packet(17));
+
+        boat.write_all(&encode_frame(&packet(19), false)).unwrap();
+        let received = loop {
+            if let Some(frame) = transport.read_frame().unwrap() {
+                break frame;
+            }
+        };
+        assert_eq!(received, (packet(19), encode_frame(&packet(19), false)));
+        assert_eq!(transport.stats(), {
+            let mut decoder = FrameDecoder::<Packet>::default();
+            decoder.push(&encode_frame(&packet(19), false));
+            decoder.next_frame();
+            decoder.stats()
+        });
+    }
+
+    #[test]
+    fn tcp_transport_fails_when_boat_disconnects() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let mut transport = TcpTransport::connect("127.0.0.1", port).unwrap();
+
+        drop(listener.accept().unwrap());
+
+        let result = loop {
+            match transport.read_frame() {
+                Ok(None) => continue,
+                v => break v,
+            }
+        };
+        assert_eq!(result, Err(String::from("Connection Closed by the Boat")));
+    }
+
+    #[test]
+    fn closed_port_refuses_connection() {
+        let port = {
+            let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+            listener.local_addr().unwrap().port()
+        };
+
+        let result = TcpTransport::connect("127.0.0.1", port);
+
+        assert_eq!(
+            result.err(),
+            Some(TcpError::Refused {
+                address: format!("127.0.0.1:{port}")
+            })
+        );
+    }
+
+    #[test]
+    fn invalid_host_is_rejected() {
+        let result = TcpTransport::connect("boat.invalid:", 5760);
+
+        assert!(matches!(result, Err(TcpError::InvalidAddress { .. })));
+    }
+
+    #[test]
+    fn errors_are_classified() {
+        let refused = std::io::Error::from(ErrorKind::ConnectionRefused);
+        let unreachable = std::io::Error::from_raw_os_error(UNREACHABLE_ERRORS[0]);
+        let timeout = std::io::Error::from(ErrorKind::TimedOut);
+        let address = String::from("192.168.4.1:5760");
+
+        assert_eq!(
+            TcpError::from_io(&address, refused),
+            TcpError::Refused {
+                address: address.clone()
+            }
+        );
+        assert_eq!(
+            TcpError::from_io(&address, unreachable),
+            TcpError::Unreachable {
+                address: address.clone()
+            }
+        );
+        assert_eq!(
+            TcpError::from_io(&address, timeout),
+            TcpError::Timeout { address }
+        );
+    }
+}
@@ -0,0 +1,256 @@
+//! Conversion of boat data from the stored units, degrees Celsius and meters, to the units
+//! shown to the user.
+
+use serde::{Deserialize, Serialize};
+
+use crate::data::{BoatDataFeature, Sensor};
+
+/// The number of meters in a foot.
+const METERS_PER_FOOT: f64 = 0.3048;
+
+/// The units temperatures and depths are displayed and exported in.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum UnitSystem {
+    /// Degrees Celsius and meters, the units the data is stored in.
+    Metric,
+    /// Degrees Fahrenheit and feet.
+    Imperial,
+}
+
+impl Default for UnitSystem {
+    fn default() -> Self {
+        Self::Metric
+    }
+}
+
+impl UnitSystem {
+    /// Converts a temperature in degrees Celsius to this unit system.
+    pub fn temperature(self, celsius: f64) -> f64 {
+        match self {
+            Self::Metric => celsius,
+            Self::Imperial => celsius_to_fahrenheit(celsius),
+        }
+    }
+
+    /// Converts a depth in meters to this unit system.
+    pub fn depth(self, meters: f64) -> f64 {
+        match self {
+            Self::Metric => meters,
+            Self::Imperial => meters_to_feet(meters),
+        }
+    }
+
+    /// Converts a temperature in this unit system to degrees Celsius.
+    pub fn to_celsius(self, temperature: f64) -> f64 {
+        match self {
+            Self::Metric => temperature,
+            Self::Imperial => fahrenheit_to_celsius(temperature),
+        }
+    }
+
+    /// Converts a depth in this unit system to meters.
+    pub fn to_meters(self, depth: f64) -> f64 {
+        match self {
+            Self::Metric => depth,
+            Self::Imperial => feet_to_meters(depth),
+        }
+    }
+
+    /// Gets the name of the temperature column in exports.
+    pub fn temperature_column(self) -> &'static str {
+        match self {
+            Self::Metric => "temperature",
+            Self::Imperial => "temperature_f",
+        }
+    }
+
+    /// Gets the name of the column of the temperatures measured before calibration in exports.
+    pub fn temperature_raw_column(self) -> &'static str {
+        match self {
+            Self::Metric => "temperature_raw",
+            Self::Imperial => "temperature_raw_f",
+        }
+    }
+
+    /// Gets the name of the depth column in exports.
+    pub fn depth_column(self) -> &'static str {
+        match self {
+            Self::Metric => "depth",
+            Self::Imperial => "depth_ft",
+        }
+    }
+}
+
+/// Converts degrees Celsius to degrees Fahrenheit.
+pub fn celsius_to_fahrenheit(celsius: f64) -> f64 {
+    celsius * 9.0 / 5.0 + 32.0
+}
+
+/// Converts degrees Fahrenheit to degrees Celsius.
+pub fn fahrenheit_to_celsius(fahrenheit: f64) -> f64 {
+    (fahrenheit - 32.0) * 5.0 / 9.0
+}
+
+/// Converts meters to feet.
+pub fn meters_to_feet(meters: f64) -> f64 {
+    meters / METERS_PER_FOOT
+}
+
+/// Converts feet to meters.
+pub fn feet_to_meters(feet: f64) -> f64 {
+    feet * METERS_PER_FOOT
+}
+
+/// Converts the temperature and depth of a data point from the stored units to `units`.
+pub fn convert_feature_units(feature: &BoatDataFeature, units: UnitSystem) -> BoatDataFeature {
+    map_feature_units(feature, |t| units.temperature(t), |d| units.depth(d))
+}
+
+/// Converts the temperature and depth of a data point from `units` to the stored units, e.g.
+/// when importing an export in imperial units.
+pub fn convert_feature_to_stored_units(
+    feature: &BoatDataFeature,
+    units: UnitSystem,
+) -> BoatDataFeature {
+    map_feature_units(feature, |t| units.to_celsius(t), |d| units.to_meters(d))
+}
+
+/// Converts the temperatures and depth of a data point.
+fn map_feature_units(
+    feature: &BoatDataFeature,
+    temperature: impl Fn(f64) -> f64,
+    depth: impl Fn(f64) -> f64,
+) -> BoatDataFeature {
+    BoatDataFeature::new(
+        temperature(feature.temperature()),
+        depth(feature.depth()),
+        feature.layer(),
+        feature.time(),
+        feature.geometry(),
+    )
+    .with_sensors(
+        feature.sensor(Sensor::Ph),
+        feature.sensor(Sensor::DissolvedOxygen),
+        feature.sensor(Sensor::Turbidity),
+    )
+    .with_id(feature.id().map(String::from))
+    .with_note(feature.note().map(String::from))
+    .with_temperature_raw(feature.temperature_raw().map(temperature))
+    .with_sensor_id(feature.sensor_id().map(String::from))
+    .with_extra(feature.extra().clone())
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeZone, Utc};
+    use geo_types::Point;
+
+    use super::*;
+    use crate::data::Layer;
+
+    /// The tolerance of values converted there and back.
+    const TOLERANCE: f64 = 1e-9;
+
+    /// Checks `actual` is within the tolerance of `expected`, relative to large values.
+    fn assert_close(actual: f64, expected: f64) {
+        let tolerance = TOLERANCE * expected.abs().max(1.0);
+        assert!(
+            (actual - expected).abs() <= tolerance,
+            "{actual} is not close to {expected}"
+        );
+    }
+
+    #[test]
+    fn celsius_to_fahrenheit_at_boundaries() {
+        assert_close(celsius_to_fahrenheit(-273.15), -459.67);
+        assert_close(celsius_to_fahrenheit(-40.0), -40.0);
+        assert_close(celsius_to_fahrenheit(0.0), 32.0);
+        assert_close(celsius_to_fahrenheit(37.0), 98.6);
+        assert_close(celsius_to_fahrenheit(100.0), 212.0);
+    }
+
+    #[test]
+    fn fahrenheit_to_celsius_at_boundaries() {
+        assert_close(fahrenheit_to_celsius(-459.67), -273.15);
+        assert_close(fahrenheit_to_celsius(-40.0), -40.0);
+        assert_close(fahrenheit_to_celsius(32.0), 0.0);
+        assert_close(fahrenheit_to_celsius(212.0), 100.0);
+    }
+
+    #[test]
+    fn meters_to_feet_at_boundaries() {
+        assert_eq!(meters_to_feet(0.0), 0.0);
+        assert_close(meters_to_feet(METERS_PER_FOOT), 1.0);
+        assert_close(meters_to_feet(1.0), 3.28083989501);
+        assert_close(meters_to_feet(10_994.0), 36_069.553805774);
+        assert_close(meters_to_feet(-1.0), -3.28083989501);
+    }
+
+    #[test]
+    fn feet_to_meters_at_boundaries() {
+        assert_eq!(feet_to_meters(0.0), 0.0);
+        assert_close(feet_to_meters(1.0), METERS_PER_FOOT);
+        assert_close(feet_to_meters(3.28083989501), 1.0);
+    }
+
+    #[test]
+    fn conversions_round_trip() {
+        let values = [
+            -273.15,
+            -40.0,
+            -1e-12,
+            0.0,
+            f64::EPSILON,
+            0.1,
+            28.5,
+            100.0,
+            11_034.0,
+            1e12,
+        ];
+        for value in values {
+            assert_close(fahrenheit_to_celsius(celsius_to_fahrenheit(value)), value);
+            assert_close(celsius_to_fahrenheit(fahrenheit_to_celsius(value)), value);
+            assert_close(feet_to_meters(meters_to_feet(value)), value);
+            assert_close(meters_to_feet(feet_to_meters(value)), value);
+        }
+    }
+
+    #[test]
+    fn conversions_keep_special_values() {
+        assert!(celsius_to_fahrenheit(f64::NAN).is_nan());
+        assert_eq!(celsius_to_fahrenheit(f64::INFINITY), f64::INFINITY);
+        assert_eq!(fahrenheit_to_celsius(f64::NEG_INFINITY), f64::NEG_INFINITY);
+        assert_eq!(feet_to_meters(f64::INFINITY), f64::INFINITY);
+    }
+
+    #[test]
+    fn metric_is_unchanged() {
+        let units = UnitSystem::Metric;
+        assert_eq!(units.temperature(28.5), 28.5);
+        assert_eq!(units.depth(0.5), 0.5);
+        assert_eq!(units.to_celsius(28.5), 28.5);
+        assert_eq!(units.to_meters(0.5), 0.5);
+    }
+
+    #[test]
+    fn feature_units_round_trip() {
+        let feature = BoatDataFeature::new(
+            28.5,
+            12.25,
+            Layer::Middle,
+            Utc.timestamp_millis_opt(1715495400000).unwrap(),
+            Point::new(101.5, 3.1),
+        );
+
+        let imperial = convert_feature_units(&feature, UnitSystem::Imperial);
+        assert_close(imperial.temperature(), 83.3);
+        assert_close(imperial.depth(), 40.1902887139);
+        let stored = convert_feature_to_stored_units(&imperial, UnitSystem::Imperial);
+
+        assert_close(stored.temperature(), feature.temperature());
+        assert_close(stored.depth(), feature.depth());
+        assert_eq!(stored.time(), feature.time());
+        assert_eq!(stored.geometry(), feature.geometry());
+    }
+}
@@ -0,0 +1,352 @@
+//! Uploading the planned path to the boat.
+
+use std::{fmt::Display, time::Duration};
+
+use prost::Message;
+use serde::Serialize;
+use serde_json::json;
+use tauri::{AppHandle, Manager};
+
+use crate::{
+    communication::{Link, Priority, RequestError, SerialConnection},
+    errors::{codes, AppError},
+    messages::{packet_type, PathChunk, PlannedPath},
+    operations::Operation,
+    path::PathData,
+    preflight::{check_path, PathConstraints, PathIssue},
+};
+
+/// The largest piece of the path sent in a chunk in bytes.
+///
+/// This leaves room for the chunk and packet around it in a single radio frame.
+const CHUNK_SIZE: usize = 192;
+
+/// The default time to wait for the boat to reply to a chunk.
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// The default number of times a chunk is sent again when the boat doesn't reply.
+const DEFAULT_RETRIES: u32 = 3;
+
+/// Errors when uploading the path to the boat.
+///
+/// Sent to the frontend as an [`AppError`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(into = "AppError")]
+pub enum UploadError {
+    /// There is no connection to the boat.
+    NotConnected,
+    /// The path failed the checks before uploading and the upload wasn't forced.
+    Preflight {
+        /// The issues found, including warnings.
+        issues: Vec<PathIssue>,
+    },
+    /// The boat rejected a chunk of the path.
+    Rejected {
+        /// The index of the chunk rejected.
+        chunk: usize,
+        /// The reason given by the boat.
+        reason: String,
+    },
+    /// The boat didn't reply to a chunk of the path.
+    Timeout {
+        /// The index of the chunk.
+        chunk: usize,
+        /// The number of times the chunk was sent.
+        attempts: u32,
+    },
+    /// The upload stopped unexpectedly.
+    Serial {
+        /// The reason the upload stopped.
+        message: String,
+    },
+}
+
+impl Display for UploadError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::NotConnected => write!(f, "Unable to Upload Path: Not Connected to the Boat"),
+            Self::Preflight { issues } => {
+                let errors: Vec<String> = issues
+                    .iter()
+                    .filter(|i| i.is_error())
+                    .map(ToString::to_string)
+                    .collect();
+                write!(f, "Unable to Upload Path: {}", errors.join(" "))
+            }
+            Self::Rejected { chunk, reason } => {
+                write!(f, "Path Rejected by the Boat: Chunk {chunk}: {reason}")
+            }
+            Self::Timeout { chunk, attempts } => write!(
+                f,
+                "No Response from the Boat: Chunk {chunk} was sent {attempts} times"
+            ),
+            Self::Serial { message } => write!(f, "Unable to Upload Path: {message}"),
+        }
+    }
+}
+
+impl std::error::Error for UploadError {}
+
+impl From<UploadError> for AppError {
+    fn from(value: UploadError) -> Self {
+        let message = value.to_string();
+        match value {
+            UploadError::NotConnected => AppError::new(codes::UPLOAD_NOT_CONNECTED, message),
+            UploadError::Preflight { issues } => AppError::new(codes::UPLOAD_PREFLIGHT, message)
+                .with("issues", serde_json::to_value(issues).unwrap_or_default()),
+            UploadError::Rejected { chunk, reason } => {
+                AppError::new(codes::UPLOAD_REJECTED, message)
+                    .with("chunk", chunk)
+                    .with("reason", reason)
+            }
+            UploadError::Timeout { chunk, attempts } => {
+                AppError::new(codes::UPLOAD_TIMEOUT, message)
+                    .with("chunk", chunk)
+                    .with("attempts", attempts)
+            }
+            UploadError::Serial { message: reason } => {
+                AppError::new(codes::UPLOAD_SERIAL, message).with("reason", reason)
+            }
+        }
+    }
+}
+
+/// Payload of the `upload-progress` event.
+#[derive(Debug, Serialize, Clone, Copy)]
+struct UploadProgress {
+    /// The number of chunks accepted by the boat.
+    sent: usize,
+    /// The number of chunks in the upload.
+    total: usize,
+}
+
+/// Notifies the windows of the chunks accepted by the boat.
+fn emit_progress(app_handle: &AppHandle, sent: usize, total: usize) {
+    if let Err(e) = app_handle.emit_all("upload-progress", UploadProgress { sent, total }) {
+        log::warn!("Unable to emit upload-progress event: {}", e);
+    }
+}
+
+/// Encodes the path in chunks small enough to send in a single frame.
+///
+/// The sequence numbers are set when the chunks are sent.
+fn path_chunks(path: &PathData) -> Vec<PathChunk> {
+    let encoded = PlannedPath::from(path).encode_to_vec();
+    let total = encoded.chunks(CHUNK_SIZE).len();
+    encoded
+        .chunks(CHUNK_SIZE)
+        .enumerate()
+        .map(|(index, data)| PathChunk {
+            sequence: 0,
+            index: index as u32,
+            total: total as u32,
+            data: data.to_vec(),
+        })
+        .collect()
+}
+
+/// Sends a chunk until the boat replies or the attempts run out.
+fn send_chunk(
+    link: &Link,
+    index: usize,
+    mut chunk: PathChunk,
+    timeout: Duration,
+    retries: u32,
+) -> Result<(), UploadError> {
+    chunk.sequence = link.next_sequence();
+    link.request(
+        packet_type::PATH_CHUNK,
+        &chunk,
+        chunk.sequence,
+        Priority::Normal,
+        timeout,
+        retries,
+    )
+    .map_err(|e| match e {
+        RequestError::NotConnected => UploadError::NotConnected,
+        RequestError::Rejected(reason) => UploadError::Rejected {
+            chunk: index,
+            reason,
+        },
+        RequestError::Timeout { attempts } => UploadError::Timeout {
+            chunk: index,
+            attempts,
+        },
+    })
+}
+
+/// Upload the path to the boat through the serial connection.
+///
+/// The path is sent in chunks, each of which is sent again up to `retries` times if the boat
+/// doesn't reply within `timeout_ms`. Emits `upload-progress` events as chunks are accepted.
+///
+/// The path is first checked against `constraints` like
+/// [`validate_path`](crate::preflight::validate_path), and isn't uploaded if there are errors
+/// unless `force` is set.
+#[tauri::command]
+pub async fn upload_path(
+    app_handle: AppHandle,
+    path: PathData,
+    timeout_ms: Option<u64>,
+    retries: Option<u32>,
+    constraints: Option<PathConstraints>,
+    force: Option<bool>,
+) -> Result<(), UploadError> {
+    let operation = Operation::start(
+        "upload_path",
+        json!({
+            "waypoints": path.path().0.len(),
+            "collection_points": path.collection_points().len(),
+            "force": force,
+        }),
+    );
+    let result = upload(
+        app_handle.clone(),
+        path,
+        timeout_ms,
+        retries,
+        constraints,
+        force,
+    )
+    .await;
+    operation.finish(&app_handle, &result);
+    result
+}
+
+/// Uploads a path to the boat, see [`upload_path`].
+async fn upload(
+    app_handle: AppHandle,
+    path: PathData,
+    timeout_ms: Option<u64>,
+    retries: Option<u32>,
+    constraints: Option<PathConstraints>,
+    force: Option<bool>,
+) -> Result<(), UploadError> {
+    log::debug!("Uploading Path");
+    let issues = check_path(&path, &constraints.unwrap_or_default());
+    if issues.iter().any(PathIssue::is_error) {
+        if !force.unwrap_or(false) {
+            return Err(UploadError::Preflight { issues });
+        }
+        log::warn!("Forcing Upload of Path with {} Issues", issues.len());
+    }
+    let link = app_handle
+        .state::<SerialConnection>()
+        .link()
+        .ok_or(UploadError::NotConnected)?;
+    let timeout = timeout_ms.map_or(DEFAULT_TIMEOUT, Duration::from_millis);
+    let retries = retries.unwrap_or(DEFAULT_RETRIES);
+
+    let chunks = path_chunks(&path);
+    let total = chunks.len();
+
+    tauri::async_runtime::spawn_blocking(move || {
+        log::info!("Uploading Path to {} in {} Chunks", link.port_name(), total);
+        emit_progress(&app_handle, 0, total);
+        for (index, chunk) in chunks.into_iter().enumerate() {
+            send_chunk(&link, index, chunk, timeout, retries)?;
+            emit_progress(&app_handle, index + 1, total);
+        }
+        log::info!("Successfully Uploaded Path to Boat");
+        Ok(())
+    })
+    .await
+    .map_err(|e| UploadError::Serial {
+        message: e.to_string(),
+    })?
+}
+
+#[cfg(test)]
+mod tests {
+    use geo_types::Point;
+
+    use super::*;
+
+    /// Creates a path with `count` collection points.
+    fn path(count: usize) -> PathData {
+        let mut path = PathData::default();
+        for i in 0..count {
+            path.add_collection_point(Point::new(101.5 + i as f64 * 1e-4, 3.1));
+        }
+        path
+    }
+
+    #[test]
+    fn chunks_reassemble_to_path() {
+        let path = path(40);
+
+        let chunks = path_chunks(&path);
+
+        assert!(chunks.len() > 1);
+        let mut encoded = vec![];
+        for (index, chunk) in chunks.iter().enumerate() {
+            assert_eq!(chunk.index, index as u32);
+            assert_eq!(chunk.total, chunks.len() as u32);
+            assert!(!chunk.data.is_empty() && chunk.data.len() <= CHUNK_SIZE);
+            encoded.extend(&chunk.data);
+        }
+        assert_eq!(
+            PlannedPath::decode(&*encoded).unwrap(),
+            PlannedPath::from(&path)
+        );
+    }
+
+    #[test]
+    fn small_path_is_sent_in_one_chunk() {
+        let chunks = path_chunks(&path(1));
+
+        assert_eq!(chunks.len(), 1);
+        assert_eq!(chunks[0].total, 1);
+    }
+
+    #[test]
+    fn errors_have_distinct_codes() {
+        let errors = [
+            UploadError::NotConnected,
+            UploadError::Rejected {
+                chunk: 2,
+                reason: String::from("Path too long"),
+            },
+            UploadError::Timeout {
+                chunk: 0,
+                attempts: 4,
+            },
+        ];
+
+        let codes: Vec<_> = errors
+            .iter()
+            .map(|e| serde_json::to_value(e).unwrap()["code"].clone())
+            .collect();
+
+        assert_eq!(
+            codes,
+            [
+                codes::UPLOAD_NOT_CONNECTED,
+                codes::UPLOAD_REJECTED,
+                codes::UPLOAD_TIMEOUT
+            ]
+        );
+        assert_eq!(
+            serde_json::to_value(&errors[1]).unwrap()["params"],
+            serde_json::json!({"chunk": 2, "reason": "Path too long"})
+        );
+    }
+
+    #[test]
+    fn preflight_error_names_only_the_errors() {
+        let mut path = path(0);
+        path.set_path(vec![(101.5, 3.1), (101.5, 3.1), (f64::NAN, 3.1)].into());
+        let issues = check_path(&path, &PathConstraints::default());
+        assert_eq!(issues.len(), 2);
+
+        let error = UploadError::Preflight { issues };
+
+        assert_eq!(
+            error.to_string(),
+            "Unable to Upload Path: Waypoint 2 has an invalid coordinate."
+        );
+        let error = serde_json::to_value(&error).unwrap();
+        assert_eq!(error["code"], codes::UPLOAD_PREFLIGHT);
+        assert_eq!(error["params"]["issues"].as_array().unwrap().len(), 2);
+    }
+}
@@ -0,0 +1,295 @@
+//! Watching of the boat data and path files for changes made by other programs, e.g. editing
+//! the path in QGIS while the app is open.
+//!
+//! Changes written by the app itself don't emit events. The path is compared with the current
+//! path, and the boat data is compared with the checksum of the content the app last wrote.
+
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::{mpsc, Mutex},
+    time::Duration,
+};
+
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use tauri::{AppHandle, Manager};
+
+use crate::{
+    app_data::AppData,
+    data::BoatData,
+    datasets::active_data_file,
+    path::{current_path_file, CurrentPath, PathData},
+    storage::data_dir,
+};
+
+/// The time to wait for more changes to a file before reading it, as editors often write a
+/// file in several steps.
+const DEBOUNCE: Duration = Duration::from_millis(200);
+
+/// Event payload of a watched file that can't be parsed after being changed.
+#[derive(Debug, Serialize, Clone)]
+struct WatchedFileErrorPayload {
+    /// The path of the file.
+    path: PathBuf,
+    /// The reason the file can't be parsed.
+    error: String,
+}
+
+/// The watcher of the files in application storage.
+#[derive(Default)]
+pub struct FileWatcherState {
+    watcher: Mutex<Option<RecommendedWatcher>>,
+    /// The SHA-256 checksums of the boat data files last written by the app.
+    written: Mutex<HashMap<PathBuf, Vec<u8>>>,
+}
+
+/// Gets the SHA-256 checksum of a file, `None` if it can't be read.
+fn checksum(path: &Path) -> Option<Vec<u8>> {
+    let content = std::fs::read(path).ok()?;
+    Some(Sha256::digest(content).to_vec())
+}
+
+/// Records that the app wrote a boat data file, so the change doesn't emit an event.
+pub fn note_write(app_handle: &AppHandle, path: &Path) {
+    let state = app_handle.state::<FileWatcherState>();
+    if state.watcher.lock().unwrap().is_none() {
+        return;
+    }
+    if let Some(checksum) = checksum(path) {
+        state
+            .written
+            .lock()
+            .unwrap()
+            .insert(path.to_path_buf(), checksum);
+    }
+}
+
+/// Emits an event, logging if it can't be emitted.
+fn emit<S: Serialize + Clone>(app_handle: &AppHandle, event: &str, payload: S) {
+    if let Err(e) = app_handle.emit_all(event, payload) {
+        log::warn!("Unable to emit {} event: {}", event, e);
+    }
+}
+
+/// Emits `watched-file-error` for a file that can't be parsed.
+fn emit_error(app_handle: &AppHandle, path: &Path, error: String) {
+    log::warn!(
+        "Unable to Parse Changed File: {}, {}",
+        path.display(),
+        error
+    );
+    emit(
+        app_handle,
+        "watched-file-error",
+        WatchedFileErrorPayload {
+            path: path.to_path_buf(),
+            error,
+        },
+    );
+}
+
+/// Handles a change to the current path file.
+fn path_changed(app_handle: &AppHandle, file: &Path) {
+    let content = match std::fs::read_to_string(file) {
+        Ok(v) => v,
+        Err(e) => return emit_error(app_handle, file, e.to_string()),
+    };
+    let path = match content.parse::<PathData>() {
+        Ok(v) => v,
+        Err(e) => return emit_error(app_handle, file, e.to_string()),
+    };
+
+    {
+        let state = app_handle.state::<CurrentPath>();
+        let mut current = state.path.lock().unwrap();
+        if *current == path {
+            return;
+        }
+        // Later edits in the app continue from the path changed outside of it
+        *current = path.clone();
+    }
+    log::info!("Path File Changed: {}", file.display());
+    emit(app_handle, "path-file-changed", path);
+}
+
+/// Records the checksum of a changed file, returning whether its content differs from the
+/// content last written or changed.
+fn record_change(written: &mut HashMap<PathBuf, Vec<u8>>, file: &Path) -> bool {
+    let checksum = checksum(file);
+    if checksum.is_some() && written.get(file) == checksum.as_ref() {
+        return false;
+    }
+    // Reporting each change once
+    match checksum {
+        Some(v) => written.insert(file.to_path_buf(), v),
+        None => written.remove(file),
+    };
+    true
+}
+
+/// Handles a change to the active boat data file.
+fn data_changed(app_handle: &AppHandle, file: &Path) {
+    let state = app_handle.state::<FileWatcherState>();
+    if !record_change(&mut state.written.lock().unwrap(), file) {
+        return;
+    }
+
+    let data = std::fs::read_to_string(file)
+        .map_err(|e| e.to_string())
+        .and_then(|content| content.parse::<BoatData>().map_err(|e| e.to_string()));
+    match data {
+        Ok(data) => {
+            log::info!("Data File Changed: {}", file.display());
+            // The cached data is read again with the data points in the journal
+            app_handle.state::<AppData>().invalidate();
+            emit(app_handle, "data-file-changed", data);
+        }
+        Err(e) => emit_error(app_handle, file, e),
+    }
+}
+
+/// Handles the files changed.
+fn files_changed(app_handle: &AppHandle, files: Vec<PathBuf>) {
+    let path_file = current_path_file(app_handle).ok();
+    let data_file = active_data_file(app_handle).ok();
+    for file in files {
+        if Some(&file) == path_file.as_ref() {
+            path_changed(app_handle, &file);
+        } else if Some(&file) == data_file.as_ref() {
+            data_changed(app_handle, &file);
+        }
+    }
+}
+
+/// Receives the files changed, handling them once they stop changing.
+fn handle_changes(app_handle: AppHandle, receiver: mpsc::Receiver<PathBuf>) {
+    while let Ok(file) = receiver.recv() {
+        let mut files = vec![file];
+        while let Ok(file) = receiver.recv_timeout(DEBOUNCE) {
+            if !files.contains(&file) {
+                files.push(file);
+            }
+        }
+        files_changed(&app_handle, files);
+    }
+}
+
+/// Starts watching the data directory.
+fn start(app_handle: &AppHandle) -> Result<RecommendedWatcher, String> {
+    let dir = data_dir(app_handle)?;
+    std::fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+
+    let (sender, receiver) = mpsc::channel();
+    let mut watcher =
+        notify::recommended_watcher(move |event: notify::Result<Event>| match event {
+            Ok(event) if matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_)) => {
+                for path in event.paths {
+                    let _ = sender.send(path);
+                }
+            }
+            Ok(_) => {}
+            Err(e) => log::warn!("File Watcher Error: {}", e),
+        })
+        .map_err(|e| e.to_string())?;
+    watcher
+        .watch(&dir, RecursiveMode::Recursive)
+        .map_err(|e| e.to_string())?;
+
+    let app_handle = app_handle.clone();
+    std::thread::spawn(move || handle_changes(app_handle, receiver));
+    log::info!("Watching Files in {}", dir.display());
+    Ok(watcher)
+}
+
+/// Starts or stops watching the files in application storage.
+///
+/// The watcher thread stops when the watcher is dropped.
+pub fn set_watching(app_handle: &AppHandle, enabled: bool) {
+    let state = app_handle.state::<FileWatcherState>();
+    let mut watcher = state.watcher.lock().unwrap();
+    if enabled == watcher.is_some() {
+        return;
+    }
+    if !enabled {
+        log::info!("Stopping File Watcher");
+        *watcher = None;
+        state.written.lock().unwrap().clear();
+        return;
+    }
+    match start(app_handle) {
+        Ok(v) => *watcher = Some(v),
+        Err(e) => log::warn!("Unable to Watch Files: {}", e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::tests::temp_dir;
+
+    #[test]
+    fn checksum_is_sha256_of_content() {
+        let dir = temp_dir("watcher_checksum");
+        let file = dir.join("data.geojson");
+        std::fs::write(&file, "abc").unwrap();
+
+        assert_eq!(
+            checksum(&file).unwrap(),
+            [
+                0xba, 0x78, 0x16, 0xbf, 0x8f, 0x01, 0xcf, 0xea, 0x41, 0x41, 0x40, 0xde, 0x5d, 0xae,
+                0x22, 0x23, 0xb0, 0x03, 0x61, 0xa3, 0x96, 0x17, 0x7a, 0x9c, 0xb4, 0x10, 0xff, 0x61,
+                0xf2, 0x00, 0x15, 0xad
+            ]
+        );
+        assert_eq!(checksum(&dir.join("missing.geojson")), None);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn own_writes_are_not_changes() {
+        let dir = temp_dir("watcher_own_writes");
+        let file = dir.join("data.geojson");
+        std::fs::write(&file, "written by the app").unwrap();
+        let mut written = HashMap::from([(file.clone(), checksum(&file).unwrap())]);
+
+        // Editors often save without changing the content
+        assert!(!record_change(&mut written, &file));
+        std::fs::write(&file, "written by the app").unwrap();
+        assert!(!record_change(&mut written, &file));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn external_change_is_reported_once() {
+        let dir = temp_dir("watcher_external_change");
+        let file = dir.join("data.geojson");
+        std::fs::write(&file, "written by the app").unwrap();
+        let mut written = HashMap::from([(file.clone(), checksum(&file).unwrap())]);
+
+        std::fs::write(&file, "edited in QGIS").unwrap();
+        assert!(record_change(&mut written, &file));
+        assert!(!record_change(&mut written, &file));
+        std::fs::write(&file, "edited again").unwrap();
+        assert!(record_change(&mut written, &file));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn deleted_file_is_a_change() {
+        let dir = temp_dir("watcher_deleted");
+        let file = dir.join("data.geojson");
+        std::fs::write(&file, "written by the app").unwrap();
+        let mut written = HashMap::from([(file.clone(), checksum(&file).unwrap())]);
+
+        std::fs::remove_file(&file).unwrap();
+        assert!(record_change(&mut written, &file));
+        assert!(written.is_empty());
+
+        // Recreating it with the old content is a change again
+        std::fs::write(&file, "written by the app").unwrap();
+        assert!(record_change(&mut written, &file));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,186 @@
+//! Windows of the app besides the main window, such as the read-only viewer shown on a
+//! projector.
+//!
+//! All windows share the managed state and receive the events emitted to all windows, so the
+//! viewer shows the same data as the main window. Commands that change the state or write files
+//! are rejected when invoked from the viewer.
+
+use std::path::PathBuf;
+
+use tauri::{AppHandle, Invoke, Manager, WindowBuilder, WindowUrl};
+
+use crate::errors::{codes, AppError};
+
+/// The label of the main window.
+pub const MAIN_LABEL: &str = "main";
+
+/// The label of the viewer window.
+const VIEWER_LABEL: &str = "viewer";
+
+/// The page of the viewer in the frontend.
+const VIEWER_URL: &str = "index.html#viewer";
+
+/// The commands the viewer can invoke, which only read the state.
+const VIEWER_COMMANDS: &[&str] = &[
+    "read_path",
+    "compare_paths",
+    "validate_path",
+    "list_missions",
+    "parse_coordinate",
+    "format_coordinate",
+    "measure_distance",
+    "measure_area",
+    "measure_bearing",
+    "path_metrics",
+    "path_profile",
+    "read_data",
+    "data_generation",
+    "compare_data",
+    "filter_data",
+    "filter_data_stored",
+    "filter_data_layer",
+    "filter_data_sensor",
+    "sort_data",
+    "query_data_bbox",
+    "query_data_bbox_stored",
+    "nearest_feature",
+    "get_feature",
+    "search_notes",
+    "get_dataset_metadata",
+    "auto_export_failures",
+    "read_operations_log",
+    "list_datasets",
+    "load_dataset",
+    "query_indexed_bbox",
+    "nearest_reading",
+    "downsample_data",
+    "data_statistics",
+    "data_statistics_stored",
+    "aggregate_data",
+    "generate_heatmap",
+    "compare_datasets",
+    "mission_coverage",
+    "generate_contours",
+    "protocol_stats",
+    "connection_info",
+    "correct_timestamps",
+    "link_stats",
+    "nofix_readings",
+    "boat_health",
+    "boat_health_history",
+    "mission_progress",
+    "current_position",
+    "get_track",
+    "fetch_mbtiles",
+    "mbtiles_metadata",
+    "list_regions",
+    "get_settings",
+    "error_codes",
+];
+
+/// Checks whether the window with the label can invoke the command.
+fn is_allowed(label: &str, command: &str) -> bool {
+    label != VIEWER_LABEL || VIEWER_COMMANDS.contains(&command)
+}
+
+/// Creates the error rejecting a command invoked from the viewer.
+fn read_only_error(command: &str) -> AppError {
+    AppError::new(
+        codes::WINDOW_READ_ONLY,
+        format!("Read-Only Window: {command} can't be used from the viewer"),
+    )
+    .with("command", command)
+}
+
+/// Wraps the command handler to reject the commands the viewer can't invoke with a
+/// [`WINDOW_READ_ONLY`](codes::WINDOW_READ_ONLY) error.
+pub fn read_only_guard<F>(handler: F) -> impl Fn(Invoke) + Send + Sync + 'static
+where
+    F: Fn(Invoke) + Send + Sync + 'static,
+{
+    move |invoke| {
+        let command = invoke.message.command();
+        if !is_allowed(invoke.message.window_ref().label(), command) {
+            log::warn!("Rejected {} from the Read-Only Viewer", command);
+            invoke.resolver.reject(read_only_error(command));
+            return;
+        }
+        handler(invoke)
+    }
+}
+
+/// Open the read-only viewer window, or focus it if it is already open.
+///
+/// The viewer can be moved to another screen, e.g. a projector, while the main window is used
+/// to control the boat.
+#[tauri::command]
+pub async fn open_viewer_window(app_handle: AppHandle) -> Result<(), AppError> {
+    let failed =
+        |e: tauri::Error| AppError::reason(codes::WINDOW_FAILED, "Unable to Open Viewer Window", e);
+    if let Some(window) = app_handle.get_window(VIEWER_LABEL) {
+        log::debug!("Focusing Viewer Window");
+        return window.set_focus().map_err(failed);
+    }
+
+    log::info!("Opening Viewer Window");
+    WindowBuilder::new(
+        &app_handle,
+        VIEWER_LABEL,
+        WindowUrl::App(PathBuf::from(VIEWER_URL)),
+    )
+    .title("Babara Project Viewer")
+    .inner_size(1280.0, 720.0)
+    .build()
+    .map_err(failed)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn main_window_can_invoke_every_command() {
+        for command in ["save_data", "open_viewer_window", "read_data", "unknown"] {
+            assert!(is_allowed(MAIN_LABEL, command), "{command}");
+        }
+    }
+
+    #[test]
+    fn viewer_can_only_read() {
+        for command in VIEWER_COMMANDS {
+            assert!(is_allowed(VIEWER_LABEL, command), "{command}");
+        }
+        for command in [
+            "save_data",
+            "save_path",
+            "merge_data",
+            "update_settings",
+            "open_viewer_window",
+            "unknown",
+        ] {
+            assert!(!is_allowed(VIEWER_LABEL, command), "{command}");
+        }
+    }
+
+    #[test]
+    fn viewer_commands_are_registered() {
+        // A renamed command would otherwise be rejected from the viewer
+        let main = include_str!("main.rs");
+        for command in VIEWER_COMMANDS {
+            assert!(main.contains(&format!("::{command},")), "{command}");
+        }
+    }
+
+    #[test]
+    fn read_only_error_names_the_command() {
+        let error = serde_json::to_value(read_only_error("save_data")).unwrap();
+
+        assert_eq!(error["code"], "window.read_only");
+        assert_eq!(error["params"]["command"], "save_data");
+        assert_eq!(
+            error["message"],
+            "Read-Only Window: save_data can't be used from the viewer"
+        );
+    }
+}
@@ -211,11 +211,13 @@ async function import_data(file_path) {
 
     try {
         logging.info("Reading Boat Data File");
-        /** @type{import("./data").BoatData} */
-        const new_path = await invoke("import_data_csv", {
+        /** @type{{data: import("./data").BoatData, duplicates: number}} */
+        const imported = await invoke("import_data_csv", {
             importPath: file_path,
         });
+        const new_path = imported.data;
         logging.debug("New Data: " + JSON.stringify(new_path));
+        logging.info(`Removed ${imported.duplicates} Repeated Data Points`);
 
         logging.info("Updating Boat Data");
         boat_vars.update_data(new_path);